@@ -21,7 +21,6 @@ OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWA
 
 extern crate clap;
 extern crate cpu_time;
-extern crate flate2;
 extern crate platsat;
 
 #[cfg(not(feature = "logging"))]
@@ -44,10 +43,13 @@ extern crate env_logger;
 #[macro_use]
 extern crate log;
 
-use clap::{App, Arg};
-use flate2::bufread::GzDecoder;
+use clap::{App, Arg, ArgGroup};
+use platsat::check::{self, CheckMode, LratStep};
+use platsat::dimacs::decompress;
+use platsat::drat::ProofOutput;
 use platsat::{
-    drat, lbool, Callbacks, ClauseKind, Lit, ProgressStatus, Solver, SolverInterface, SolverOpts,
+    drat, lbool, BranchingHeuristic, Callbacks, ClauseKind, Lit, MaxSatSolver, Optimizer, Print,
+    ProgressStatus, RestartStrategy, Solver, SolverInterface, SolverOpts,
 };
 use std::fs::File;
 use std::io::{self, BufRead, BufReader, BufWriter, Write};
@@ -55,6 +57,7 @@ use std::mem;
 use std::process::exit;
 use std::time::Instant;
 
+mod opb;
 mod system;
 
 fn main() {
@@ -185,6 +188,34 @@ fn main2() -> io::Result<i32> {
         .arg(Arg::with_name("input-file"))
         .arg(Arg::with_name("result-output-file"))
         .arg(Arg::with_name("proof").long("proof").help("produce proof in (D)RAT on stdout"))
+        .arg(Arg::with_name("drat-file").long("drat-file")
+             .help("stream a DRAT proof into the given file")
+             .takes_value(true))
+        .arg(Arg::with_name("lrat-file").long("lrat-file")
+             .help("stream a LRAT proof into the given file")
+             .takes_value(true))
+        .group(ArgGroup::with_name("proof-file").args(&["drat-file", "lrat-file"]))
+        .arg(Arg::with_name("binary-drat").long("binary-drat")
+             .requires("proof-file")
+             .help("use the binary format for --drat-file or --lrat-file"))
+        .arg(Arg::with_name("check-drat").long("check-drat")
+             .help("check the DRAT proof in the given file against the input, instead of solving it")
+             .takes_value(true))
+        .arg(Arg::with_name("check-lrat").long("check-lrat")
+             .help("check the LRAT proof in the given file against the input, instead of solving it")
+             .conflicts_with("check-drat")
+             .takes_value(true))
+        .arg(Arg::with_name("forward").long("forward")
+             .requires("check-drat")
+             .help("check all the lemmas of the DRAT proof, not only the ones the empty clause depends on"))
+        .arg(Arg::with_name("core-file").long("core-file")
+             .requires("check-lrat")
+             .help("write the original clauses used by the checked LRAT proof into the given file")
+             .takes_value(true))
+        .arg(Arg::with_name("trimmed-proof").long("trimmed-proof")
+             .requires("check-lrat")
+             .help("write the checked LRAT proof, reduced to the steps the empty clause depends on and renumbered for --core-file, into the given file")
+             .takes_value(true))
         .arg(
             Arg::with_name("verbosity")
                 .long("verb")
@@ -197,7 +228,12 @@ fn main2() -> io::Result<i32> {
                 .default_value("-1.0")
                 .takes_value(true),
         )
+        .arg(Arg::with_name("mem-lim").long("mem-lim")
+             .help("Limit on the memory used by the solver, in megabytes")
+             .takes_value(true))
         .arg(Arg::with_name("is-strict").long("strict"))
+        .arg(Arg::with_name("oll").long("oll")
+             .help("solve WCNF files with the core-guided OLL algorithm instead of linear search"))
         .arg(Arg::with_name("var-decay").long("var-decay")
              .help("The variable activity decay factor")
              .default_value("0.95")
@@ -210,9 +246,13 @@ fn main2() -> io::Result<i32> {
              .help("The frequency with which the decision heuristic tries to choose a random variable")
              .default_value("0.0")
              .takes_value(true))
+        .arg(Arg::with_name("random-pol-freq").long("rnd-pol-freq")
+             .help("The frequency with which the decisions use a random polarity")
+             .default_value("0.0")
+             .takes_value(true))
         .arg(Arg::with_name("random-seed").long("rnd-seed")
-             .help("The frequency with which the decision heuristic tries to choose a random variable")
-             .default_value("91648253.0")
+             .help("The seed of the random choices")
+             .default_value("91648253")
              .takes_value(true))
         .arg(Arg::with_name("ccmin-mode").long("ccmin-mode")
              .help("Controls conflict clause minimization (0=none, 1=basic, 2=deep)")
@@ -227,11 +267,21 @@ fn main2() -> io::Result<i32> {
              .help("Randomize the initial activity"))
         .arg(Arg::with_name("no-rnd-init").long("no-rnd-init")
              .help("Do not randomize the initial activity [default]"))
+        .arg(Arg::with_name("branching").long("branching")
+             .help("The heuristic choosing the decision variables")
+             .possible_values(&["vsids", "vmtf", "chb", "lrb"])
+             .default_value("vsids")
+             .takes_value(true))
+        .arg(Arg::with_name("restart").long("restart")
+             .help("The restart strategy")
+             .possible_values(&["luby", "geometric", "glucose"])
+             .default_value("luby")
+             .takes_value(true))
         .arg(Arg::with_name("luby-restart").long("luby")
              .conflicts_with("no-luby-restart")
-             .help("Use the Luby restart sequence [default]"))
+             .help("Use the Luby restart sequence, the same as --restart luby"))
         .arg(Arg::with_name("no-luby-restart").long("no-luby")
-             .help("Do not use the Luby restart sequence"))
+             .help("Use geometric restarts, the same as --restart geometric"))
         .arg(Arg::with_name("restart-first").long("rfirst")
              .help("The base restart interval")
              .default_value("100")
@@ -248,8 +298,83 @@ fn main2() -> io::Result<i32> {
              .help("Minimum learnt clause limit")
              .default_value("0")
              .takes_value(true))
+        .arg(Arg::with_name("elim").long("elim")
+             .conflicts_with("no-elim")
+             .help("Perform variable elimination"))
+        .arg(Arg::with_name("no-elim").long("no-elim")
+             .help("Do not perform variable elimination [default]"))
+        .arg(Arg::with_name("subst").long("subst")
+             .help("Substitute the literals that are equivalent according to the binary clauses during search"))
+        .arg(Arg::with_name("bce").long("bce")
+             .help("Also perform blocked clause elimination"))
+        .arg(Arg::with_name("bva").long("bva")
+             .help("Also perform bounded variable addition"))
+        .arg(Arg::with_name("elim-clause-lim").long("cl-lim")
+             .help("Do not eliminate a variable if this produces a resolvent longer than this (-1 means no limit)")
+             .default_value("20")
+             .takes_value(true))
+        .arg(Arg::with_name("elim-grow").long("grow")
+             .help("Allow a variable elimination step to grow by a number of clauses")
+             .default_value("0")
+             .takes_value(true))
+        .arg(Arg::with_name("subsumption-lim").long("sub-lim")
+             .help("Do not use a clause for subsumption if all its variables occur in more clauses than this (-1 means no limit)")
+             .default_value("1000")
+             .takes_value(true))
+        .arg(Arg::with_name("vivify").long("vivify")
+             .help("Vivify clauses during search"))
+        .arg(Arg::with_name("vivify-effort").long("vivify-effort")
+             .help("Fraction of the propagation ticks spent on vivification")
+             .default_value("0.1")
+             .takes_value(true))
+        .arg(Arg::with_name("probe").long("probe")
+             .help("Probe failed literals during search"))
+        .arg(Arg::with_name("probe-effort").long("probe-effort")
+             .help("Fraction of the propagation ticks spent on probing")
+             .default_value("0.05")
+             .takes_value(true))
+        .arg(Arg::with_name("chrono").long("chrono")
+             .help("Backtrack chronologically when the assertion level is more than this many levels below the conflict (-1 means never)")
+             .default_value("-1")
+             .takes_value(true))
+        .arg(Arg::with_name("lbd-reduce").long("lbd-reduce")
+             .help("Reduce the learnt clauses by tiers of LBD rather than by activity"))
+        .arg(Arg::with_name("tier2-lbd").long("tier2-lbd")
+             .help("Keep the learnt clauses with at most this LBD while they are used")
+             .default_value("6")
+             .takes_value(true))
+        .arg(Arg::with_name("otfs").long("otfs")
+             .help("Strengthen the clauses subsumed by resolvents during conflict analysis"))
+        .arg(Arg::with_name("binary-minimize-lbd").long("bin-minimize-lbd")
+             .help("Shrink the learnt clauses with at most this LBD with binary clauses (0 means never)")
+             .default_value("0")
+             .takes_value(true))
+        .arg(Arg::with_name("target-phase").long("target-phase")
+             .help("Decide on the value of the variables in the largest conflict-free trail rather than on their last one"))
+        .arg(Arg::with_name("rephase").long("rephase")
+             .help("Periodically reset the saved phases of the variables"))
+        .arg(Arg::with_name("rephase-int").long("rephase-int")
+             .help("Number of conflicts before the first rephasing")
+             .default_value("1000")
+             .takes_value(true))
+        .arg(Arg::with_name("local-search").long("local-search")
+             .help("Run a local search after each rephasing, to find better phases"))
+        .arg(Arg::with_name("local-search-effort").long("local-search-effort")
+             .help("Ticks spent in local search, relative to those spent searching")
+             .default_value("0.1")
+             .takes_value(true))
+        .arg(Arg::with_name("mode-switching").long("mode-switching")
+             .help("Alternate between focused and stable modes (overrides restart and target phases)"))
+        .arg(Arg::with_name("mode-init").long("mode-init")
+             .help("Number of conflicts of the first focused mode")
+             .default_value("1000")
+             .takes_value(true))
         .get_matches();
 
+    if let Some(exitcode) = check_proof(&matches)? {
+        return Ok(exitcode);
+    }
+
     let mut solver_opts = SolverOpts::default();
     solver_opts.var_decay = matches
         .value_of("var-decay")
@@ -263,10 +388,13 @@ fn main2() -> io::Result<i32> {
         .value_of("random-var-freq")
         .and_then(|s| s.parse().ok())
         .unwrap_or(solver_opts.random_var_freq);
-    solver_opts.random_seed = matches
-        .value_of("random-seed")
+    solver_opts.random_pol_freq = matches
+        .value_of("random-pol-freq")
         .and_then(|s| s.parse().ok())
-        .unwrap_or(solver_opts.random_seed);
+        .unwrap_or(solver_opts.random_pol_freq);
+    if let Some(s) = matches.value_of("random-seed") {
+        solver_opts.seed = parse_seed(s)?;
+    }
     solver_opts.ccmin_mode = matches
         .value_of("ccmin-mode")
         .and_then(|s| s.parse().ok())
@@ -276,7 +404,19 @@ fn main2() -> io::Result<i32> {
         .and_then(|s| s.parse().ok())
         .unwrap_or(solver_opts.phase_saving);
     solver_opts.rnd_init_act = matches.is_present("rnd-init-act");
-    solver_opts.luby_restart = !matches.is_present("no-luby-restart");
+    solver_opts.branching = match matches.value_of("branching") {
+        Some("vmtf") => BranchingHeuristic::Vmtf,
+        Some("chb") => BranchingHeuristic::Chb,
+        Some("lrb") => BranchingHeuristic::Lrb,
+        _ => BranchingHeuristic::Vsids,
+    };
+    solver_opts.restart_strategy = match matches.value_of("restart") {
+        _ if matches.is_present("luby-restart") => RestartStrategy::Luby,
+        _ if matches.is_present("no-luby-restart") => RestartStrategy::Geometric,
+        Some("geometric") => RestartStrategy::Geometric,
+        Some("glucose") => RestartStrategy::Glucose,
+        _ => RestartStrategy::Luby,
+    };
     solver_opts.restart_first = matches
         .value_of("restart-first")
         .and_then(|s| s.parse().ok())
@@ -293,10 +433,72 @@ fn main2() -> io::Result<i32> {
         .value_of("min-learnts-lim")
         .and_then(|s| s.parse().ok())
         .unwrap_or(solver_opts.min_learnts_lim);
+    solver_opts.elim_clause_lim = matches
+        .value_of("elim-clause-lim")
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(solver_opts.elim_clause_lim);
+    solver_opts.elim_grow = matches
+        .value_of("elim-grow")
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(solver_opts.elim_grow);
+    solver_opts.blocked_clause_elim = matches.is_present("bce");
+    solver_opts.bva = matches.is_present("bva");
+    solver_opts.subsumption_lim = matches
+        .value_of("subsumption-lim")
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(solver_opts.subsumption_lim);
+    solver_opts.vivify = matches.is_present("vivify");
+    solver_opts.vivify_effort = matches
+        .value_of("vivify-effort")
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(solver_opts.vivify_effort);
+    solver_opts.probe = matches.is_present("probe");
+    solver_opts.probe_effort = matches
+        .value_of("probe-effort")
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(solver_opts.probe_effort);
+    solver_opts.chrono = matches
+        .value_of("chrono")
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(solver_opts.chrono);
+    solver_opts.lbd_reduce = matches.is_present("lbd-reduce");
+    solver_opts.tier2_lbd = matches
+        .value_of("tier2-lbd")
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(solver_opts.tier2_lbd);
+    solver_opts.otfs = matches.is_present("otfs");
+    solver_opts.binary_minimize_lbd = matches
+        .value_of("binary-minimize-lbd")
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(solver_opts.binary_minimize_lbd);
+    solver_opts.target_phase = matches.is_present("target-phase");
+    solver_opts.rephase = matches.is_present("rephase");
+    solver_opts.rephase_int = matches
+        .value_of("rephase-int")
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(solver_opts.rephase_int);
+    solver_opts.local_search = matches.is_present("local-search");
+    solver_opts.local_search_effort = matches
+        .value_of("local-search-effort")
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(solver_opts.local_search_effort);
+    solver_opts.mode_switching = matches.is_present("mode-switching");
+    solver_opts.mode_init = matches
+        .value_of("mode-init")
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(solver_opts.mode_init);
     let produce_proof = matches.is_present("proof");
+    let drat_file = matches.value_of("drat-file");
+    let lrat_file = matches.value_of("lrat-file");
+    let binary_drat = matches.is_present("binary-drat");
+    // the proof built by the callbacks does not record the eliminations
+    let elim = matches.is_present("elim") && !produce_proof;
+    if matches.is_present("subst") && !produce_proof {
+        solver_opts.substitute_equivalences = true;
+    }
 
-    if !solver_opts.check() {
-        eprintln!("Invalid option value");
+    if let Err(e) = solver_opts.validate() {
+        eprintln!("{}", e);
         exit(1);
     }
 
@@ -307,18 +509,21 @@ fn main2() -> io::Result<i32> {
         .unwrap()
         .parse::<i32>()
         .unwrap_or(0);
-    if verbosity < 0 || verbosity > 2 {
+    if !(0..=2).contains(&verbosity) {
         eprintln!(
             "ERROR! value <{}> is too small for option \"verb\".",
             verbosity
         );
         exit(1);
     }
-    let is_strict = matches.value_of("is-strict").is_some();
+    let is_strict = matches.is_present("is-strict");
     let cpu_lim = matches
         .value_of("cpu-lim")
         .and_then(|s| s.parse().ok())
         .filter(|x| *x > 0.);
+    let mem_lim = matches
+        .value_of("mem-lim")
+        .and_then(|s| s.parse::<usize>().ok());
 
     // allocate callbacks
     let mut cb = CB::new();
@@ -334,22 +539,50 @@ fn main2() -> io::Result<i32> {
     }
 
     let mut solver = Solver::new(solver_opts, cb);
+    if let Some(mem_lim) = mem_lim {
+        solver.set_memory_limit(mem_lim.saturating_mul(1024 * 1024));
+    }
+    if let Some(drat_file) = drat_file {
+        let out = BufWriter::new(File::create(drat_file)?);
+        solver.set_drat_output(out, binary_drat);
+    } else if let Some(lrat_file) = lrat_file {
+        let out = BufWriter::new(File::create(lrat_file)?);
+        solver.set_lrat_output(out, binary_drat);
+    }
 
     let initial_time = Instant::now();
 
     let mut incremental = false;
+    let mut is_opb = false;
+    let mut objective = None;
     if let Some(input_file) = input_file {
-        incremental = input_file.ends_with(".icnf");
+        let name = input_file.trim_end_matches(".gz").trim_end_matches(".xz");
+        incremental = name.ends_with(".icnf");
+        is_opb = name.ends_with(".opb");
         if incremental {
             solver.cb_mut().verbosity = 0;
         }
         debug!("solve file {} (incremental: {})", input_file, incremental);
         let file = BufReader::new(File::open(input_file)?);
-        read_input_autogz(file, &mut solver, is_strict, incremental)?;
+        if name.ends_with(".wcnf") {
+            let oll = matches.is_present("oll");
+            let input = decompress(file)?;
+            return solve_wcnf(solver, input, result_output_file, produce_proof, oll);
+        } else if name.ends_with(".qdimacs") || name.ends_with(".qcnf") {
+            return solve_qdimacs(decompress(file)?, result_output_file);
+        } else if name.ends_with(".gcnf") {
+            return solve_gcnf(solver, decompress(file)?, result_output_file);
+        } else if is_opb {
+            objective = read_opb(decompress(file)?, &mut solver)?;
+        } else if name.ends_with(".aag") || name.ends_with(".aig") {
+            read_aiger(decompress(file)?, &mut solver)?;
+        } else {
+            read_input(decompress(file)?, &mut solver, is_strict, incremental)?;
+        }
     } else {
         println!("c Reading from standard input... Use '--help' for help.");
         let stdin = io::stdin();
-        read_input_autogz(stdin.lock(), &mut solver, is_strict, false)?;
+        read_input(decompress(stdin.lock())?, &mut solver, is_strict, false)?;
     }
 
     let mut resfile = if let Some(result_output_file) = result_output_file {
@@ -382,7 +615,8 @@ fn main2() -> io::Result<i32> {
         );
     }
 
-    if !solver.simplify() {
+    if !solver.simplify() || (elim && !solver.eliminate()) {
+        solver.flush_proof()?;
         if let Some(resfile) = resfile.as_mut() {
             writeln!(resfile, "s UNSAT")?;
             if let Some(p) = &solver.cb().proof {
@@ -395,7 +629,7 @@ fn main2() -> io::Result<i32> {
             println!(
                 "c ==============================================================================="
             );
-            println!("c Solved by unit propagation");
+            println!("c Solved by simplification");
             if let Some(p) = &solver.cb().proof {
                 println!("{}", p);
             }
@@ -406,6 +640,20 @@ fn main2() -> io::Result<i32> {
     }
 
     let ret = solver.solve_limited(&[]);
+    // model of an OPB problem, the best one found if it has an objective
+    let mut opb_model = None;
+    let mut optimum = false;
+    if is_opb && ret == lbool::TRUE {
+        opb_model = Some(match &objective {
+            Some(objective) => {
+                let (model, is_optimal) = minimize(&mut solver, objective);
+                optimum = is_optimal;
+                model
+            }
+            None => solver.get_model().to_vec(),
+        });
+    }
+    solver.flush_proof()?;
     if solver.cb().verbosity > 0 {
         solver.print_stats();
         println!("c CPU time              : {:.3}s", resource.cpu_time());
@@ -413,11 +661,15 @@ fn main2() -> io::Result<i32> {
     if incremental {
         return Ok(0);
     } else if ret == lbool::TRUE {
-        println!("s SATISFIABLE");
+        if optimum {
+            println!("s OPTIMUM FOUND");
+        } else {
+            println!("s SATISFIABLE");
+        }
 
         // print model
         if produce_proof && resfile.is_none() {
-            println!("{}", solver.dimacs_model());
+            print_model(&mut io::stdout(), &solver, &opb_model)?;
         }
     } else if ret == lbool::FALSE {
         println!("s UNSATISFIABLE");
@@ -426,12 +678,15 @@ fn main2() -> io::Result<i32> {
             println!("{}", &solver.cb_mut().take_proof().unwrap());
         }
     } else {
+        if solver.memory_limit_reached() {
+            println!("c Memory limit reached");
+        }
         println!("s INDETERMINATE");
     }
     if let Some(resfile) = resfile.as_mut() {
         if ret == lbool::TRUE {
             writeln!(resfile, "s SAT")?;
-            writeln!(resfile, "{}", solver.dimacs_model())?;
+            print_model(resfile, &solver, &opb_model)?;
         } else if ret == lbool::FALSE {
             writeln!(resfile, "s UNSAT")?;
             if produce_proof {
@@ -460,25 +715,120 @@ fn main2() -> io::Result<i32> {
     Ok(exitcode)
 }
 
-fn read_input_autogz<R: BufRead>(
-    mut input: R,
-    solver: &mut MSolver,
-    is_strict: bool,
-    incremental: bool,
-) -> io::Result<()> {
-    let is_gz = input.fill_buf()?.starts_with(b"\x1F\x8B");
-    if is_gz {
-        read_input(
-            BufReader::new(GzDecoder::new(input)),
-            solver,
-            is_strict,
-            incremental,
+/// Parse the value of `--rnd-seed`: an integer, or an integral float like the former
+/// default `91648253.0`.
+fn parse_seed(s: &str) -> io::Result<u64> {
+    let float = || {
+        let x: f64 = s.parse().ok()?;
+        (x.fract() == 0.0 && 0.0 <= x && x < u64::MAX as f64).then_some(x as u64)
+    };
+    s.parse().ok().or_else(float).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("invalid value for --rnd-seed: {:?}", s),
         )
+    })
+}
+
+/// Check the proof of `--check-drat` or `--check-lrat` against the input; returns the
+/// exit code, or `None` if there is no proof to check.
+fn check_proof(matches: &clap::ArgMatches) -> io::Result<Option<i32>> {
+    let (proof_file, lrat) = match (
+        matches.value_of("check-drat"),
+        matches.value_of("check-lrat"),
+    ) {
+        (Some(file), _) => (file, false),
+        (None, Some(file)) => (file, true),
+        (None, None) => return Ok(None),
+    };
+    let cnf = match matches.value_of("input-file") {
+        Some(input_file) => {
+            let file = BufReader::new(File::open(input_file)?);
+            check::parse_cnf(&mut decompress(file)?)?
+        }
+        None => check::parse_cnf(&mut decompress(io::stdin().lock())?)?,
+    };
+    let mut proof = decompress(BufReader::new(File::open(proof_file)?))?;
+    let res = if lrat {
+        let proof = check::parse_lrat(&mut proof)?;
+        let res = check::check_lrat(&cnf, &proof);
+        if res.is_ok() {
+            write_trimmed(matches, &cnf, &proof)?;
+        }
+        res
     } else {
-        read_input(input, solver, is_strict, incremental)
+        let mode = if matches.is_present("forward") {
+            CheckMode::Forward
+        } else {
+            CheckMode::Backward
+        };
+        let proof = check::parse_drat(&mut proof)?;
+        check::check_drat(&cnf, &proof, mode).map(|n| println!("c {} lemmas checked", n))
+    };
+    match res {
+        Ok(()) => {
+            println!("s VERIFIED");
+            Ok(Some(0))
+        }
+        Err(e) => {
+            println!("c {}", e);
+            println!("s NOT VERIFIED");
+            Ok(Some(1))
+        }
     }
 }
 
+/// Write the clausal core of `--core-file` and the trimmed proof of `--trimmed-proof`,
+/// from the checked LRAT proof `proof`.
+fn write_trimmed(
+    matches: &clap::ArgMatches,
+    cnf: &[Vec<Lit>],
+    proof: &[LratStep],
+) -> io::Result<()> {
+    let core_file = matches.value_of("core-file");
+    let trimmed_file = matches.value_of("trimmed-proof");
+    if core_file.is_none() && trimmed_file.is_none() {
+        return Ok(());
+    }
+    let trimmed = check::trim_lrat(cnf.len(), proof)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    println!(
+        "c core of {} clauses out of {}",
+        trimmed.core.len(),
+        cnf.len()
+    );
+    if let Some(core_file) = core_file {
+        let mut out = BufWriter::new(File::create(core_file)?);
+        let core = trimmed.core.iter().map(|&i| &cnf[i]);
+        let num_vars = core.clone().flatten().map(|lit| lit.var().idx() + 1).max();
+        writeln!(
+            out,
+            "p cnf {} {}",
+            num_vars.unwrap_or(0),
+            trimmed.core.len()
+        )?;
+        for c in core {
+            writeln!(out, "{}", c.pp_dimacs())?;
+        }
+        out.flush()?;
+    }
+    if let Some(trimmed_file) = trimmed_file {
+        let mut w = drat::LratWriter::new(BufWriter::new(File::create(trimmed_file)?));
+        for step in &trimmed.proof {
+            match step {
+                LratStep::Add { id, clause, hints } => w.add_clause_with_hints(*id, clause, hints),
+                LratStep::Delete(ids) => {
+                    for &id in ids {
+                        w.delete_clause_with_id(id, &[]);
+                    }
+                }
+            }
+        }
+        w.flush()?;
+    }
+    Ok(())
+}
+
 fn read_input<R: BufRead>(
     mut input: R,
     solver: &mut MSolver,
@@ -493,6 +843,219 @@ fn read_input<R: BufRead>(
             "c |                                                                             |"
         );
     }
-    platsat::dimacs::parse(&mut input, solver, is_strict, incremental)?;
+    if incremental {
+        platsat::dimacs::parse_icnf(&mut input, solver, |solver, lits| {
+            solver.simplify();
+            match solver.solve_limited(lits) {
+                x if x == lbool::TRUE => println!("SAT"),
+                x if x == lbool::FALSE => println!("UNSAT"),
+                _ => println!("UNKNOWN"),
+            }
+        })?;
+    } else {
+        platsat::dimacs::parse(&mut input, solver, is_strict, false)?;
+    }
+    Ok(())
+}
+
+/// Read an AIGER circuit, which is satisfiable if one of its outputs can be `1`.
+fn read_aiger<R: BufRead>(mut input: R, solver: &mut MSolver) -> io::Result<()> {
+    if solver.cb().verbosity > 0 {
+        println!(
+            "c ============================[ Problem Statistics ]============================="
+        );
+        println!(
+            "c |                                                                             |"
+        );
+    }
+    let circuit = platsat::aiger::parse(&mut input, solver)?;
+    solver.add_clause_reuse(&mut circuit.outputs.clone());
     Ok(())
 }
+
+fn read_opb<R: BufRead>(mut input: R, solver: &mut MSolver) -> io::Result<Option<opb::Objective>> {
+    if solver.cb().verbosity > 0 {
+        println!(
+            "c ============================[ Problem Statistics ]============================="
+        );
+        println!(
+            "c |                                                                             |"
+        );
+    }
+    opb::parse(&mut input, solver)
+}
+
+/// Minimize `objective` by linear search, starting from the current model: each
+/// model found is printed and excluded by requiring the objective to be smaller.
+///
+/// Returns the best model, and whether it was proven optimal.
+fn minimize(solver: &mut MSolver, objective: &[(i64, Lit)]) -> (Vec<lbool>, bool) {
+    let bound: Vec<(i64, Lit)> = objective.iter().map(|&(coef, lit)| (-coef, lit)).collect();
+    loop {
+        let model = solver.get_model().to_vec();
+        let value = opb::eval(objective, &model);
+        println!("o {}", value);
+        // `sum(coef * lit) <= value - 1`
+        if !solver.add_pb_constraint(&bound, 1 - value) {
+            return (model, true);
+        }
+        let ret = solver.solve_limited(&[]);
+        if ret != lbool::TRUE {
+            return (model, ret == lbool::FALSE);
+        }
+    }
+}
+
+fn print_model<W: Write>(
+    out: &mut W,
+    solver: &MSolver,
+    opb_model: &Option<Vec<lbool>>,
+) -> io::Result<()> {
+    match opb_model {
+        Some(model) => writeln!(out, "{}", opb::PrintModel(model)),
+        None => writeln!(out, "{}", solver.dimacs_model()),
+    }
+}
+
+/// Solve the MaxSAT problem `input`, in the WCNF format, printing the cost
+/// of each improving model.
+fn solve_wcnf<R: BufRead>(
+    solver: MSolver,
+    mut input: R,
+    result_output_file: Option<&str>,
+    produce_proof: bool,
+    oll: bool,
+) -> io::Result<i32> {
+    let mut solver = MaxSatSolver::new(solver);
+    platsat::dimacs::parse_wcnf(&mut input, &mut solver)?;
+    // the other variables are used for the relaxation of soft clauses
+    let num_vars = solver.solver().num_vars() as usize;
+    let (ret, model) = if oll {
+        let softs: Vec<(Vec<Lit>, u64)> = solver
+            .soft_clauses()
+            .map(|(c, weight)| (c.to_vec(), weight))
+            .collect();
+        let mut opt = Optimizer::new(solver.into_solver());
+        let ret = opt.maximize(&softs);
+        if let Some(cost) = opt.cost() {
+            println!("o {}", cost);
+        }
+        if opt.solver().cb().verbosity > 0 {
+            opt.solver().print_stats();
+        }
+        (ret, opt.model().to_vec())
+    } else {
+        let ret = solver.solve_with(|_, cost| println!("o {}", cost));
+        if solver.solver().cb().verbosity > 0 {
+            solver.solver().print_stats();
+        }
+        (ret, solver.model().to_vec())
+    };
+    let (status, exitcode) = if ret == lbool::TRUE {
+        ("OPTIMUM FOUND", 30)
+    } else if ret == lbool::FALSE {
+        ("UNSATISFIABLE", 20)
+    } else if !model.is_empty() {
+        ("SATISFIABLE", 10)
+    } else {
+        ("UNKNOWN", 0)
+    };
+    println!("s {}", status);
+    if let Some(result_output_file) = result_output_file {
+        let mut resfile = BufWriter::new(File::create(result_output_file)?);
+        writeln!(resfile, "s {}", status)?;
+        if !model.is_empty() {
+            write_dimacs_model(&mut resfile, &model[..num_vars])?;
+        }
+        resfile.flush()?;
+    } else if produce_proof && !model.is_empty() {
+        write_dimacs_model(&mut io::stdout(), &model[..num_vars])?;
+    }
+    Ok(exitcode)
+}
+
+/// Find a minimal unsatisfiable subset of the groups of `input`, in the GCNF format,
+/// and print their numbers.
+fn solve_gcnf<R: BufRead>(
+    mut solver: MSolver,
+    mut input: R,
+    result_output_file: Option<&str>,
+) -> io::Result<i32> {
+    let groups = platsat::dimacs::parse_gcnf(&mut input, &mut solver)?;
+    let ret = solver.solve_limited(&[]);
+    let mus = if ret == lbool::FALSE {
+        solver.extract_group_mus()
+    } else {
+        None
+    };
+    if solver.cb().verbosity > 0 {
+        solver.print_stats();
+    }
+    let (status, exitcode) = if ret == lbool::TRUE {
+        ("SATISFIABLE", 10)
+    } else if ret == lbool::FALSE {
+        ("UNSATISFIABLE", 20)
+    } else {
+        ("UNKNOWN", 0)
+    };
+    let mut out: Box<dyn Write> = match result_output_file {
+        Some(file) => Box::new(BufWriter::new(File::create(file)?)),
+        None => Box::new(io::stdout()),
+    };
+    writeln!(out, "s {}", status)?;
+    if let Some(mus) = mus {
+        write!(out, "v")?;
+        for (i, g) in groups.iter().enumerate() {
+            if mus.contains(g) {
+                write!(out, " {}", i + 1)?;
+            }
+        }
+        writeln!(out, " 0")?;
+    }
+    out.flush()?;
+    Ok(exitcode)
+}
+
+/// Decide the QBF `input`, in the QDIMACS format, and print the result in the QDIMACS
+/// output format, with the values of the outermost block that show it.
+fn solve_qdimacs<R: BufRead>(mut input: R, result_output_file: Option<&str>) -> io::Result<i32> {
+    let qbf = platsat::dimacs::parse_qdimacs(&mut input)?;
+    let mut witness = vec![];
+    let ret = qbf.solve(&mut witness);
+    let (status, exitcode) = if ret == lbool::TRUE {
+        ("1", 10)
+    } else if ret == lbool::FALSE {
+        ("0", 20)
+    } else {
+        ("-1", 0)
+    };
+    let mut out: Box<dyn Write> = match result_output_file {
+        Some(file) => Box::new(BufWriter::new(File::create(file)?)),
+        None => Box::new(io::stdout()),
+    };
+    writeln!(
+        out,
+        "s cnf {} {} {}",
+        status,
+        qbf.num_vars,
+        qbf.clauses.len()
+    )?;
+    for lit in witness {
+        let sign = if lit.sign() { "" } else { "-" };
+        writeln!(out, "V {}{} 0", sign, lit.var().idx() + 1)?;
+    }
+    out.flush()?;
+    Ok(exitcode)
+}
+
+fn write_dimacs_model<W: Write>(out: &mut W, model: &[lbool]) -> io::Result<()> {
+    write!(out, "v")?;
+    for (i, &val) in model.iter().enumerate() {
+        if val == lbool::TRUE {
+            write!(out, " {}", i + 1)?
+        } else if val == lbool::FALSE {
+            write!(out, " -{}", i + 1)?
+        }
+    }
+    writeln!(out, " 0")
+}
@@ -0,0 +1,253 @@
+//! Parser for pseudo-Boolean problems in the OPB format
+//!
+//! A problem is made of an optional objective `min: +2 x1 -3 ~x2 ;` followed by linear
+//! constraints such as `+1 x1 +2 x3 >= 2 ;`, where the relation is one of `>=`, `<=` and `=`.
+//! Lines starting with `*` are comments, except that the number of variables is read
+//! from the `* #variable= n #constraint= m` header. Non-linear terms are not supported.
+
+use platsat::{lbool, Callbacks, Lit, Solver, SolverInterface};
+use std::fmt;
+use std::io::{self, BufRead};
+use std::iter::Peekable;
+use std::str::SplitWhitespace;
+
+/// Linear objective `sum(coef * lit)`, to be minimized.
+pub(crate) type Objective = Vec<(i64, Lit)>;
+
+fn error<T>(msg: impl fmt::Display) -> io::Result<T> {
+    let msg = format!("PARSE ERROR! {}", msg);
+    Err(io::Error::new(io::ErrorKind::InvalidData, msg))
+}
+
+/// Parse an OPB problem from `input` and add its constraints to `solver`.
+///
+/// Returns the objective, if any.
+pub(crate) fn parse<R: BufRead, Cb: Callbacks>(
+    input: &mut R,
+    solver: &mut Solver<Cb>,
+) -> io::Result<Option<Objective>> {
+    let mut num_vars = None;
+    let mut objective = None;
+    let mut line = String::new();
+    let mut stmt = String::new();
+    loop {
+        line.clear();
+        if input.read_line(&mut line)? == 0 {
+            break;
+        }
+        if line.starts_with('*') {
+            if num_vars.is_none() {
+                num_vars = parse_header(&line)?;
+                for i in 0..num_vars.unwrap_or(0) {
+                    solver.var_of_int(i);
+                }
+            }
+            continue;
+        }
+        // statements end with `;` and may span several lines
+        let mut rest = line.as_str();
+        while let Some(end) = rest.find(';') {
+            stmt.push_str(&rest[..end]);
+            parse_statement(&stmt, solver, num_vars, &mut objective)?;
+            stmt.clear();
+            rest = &rest[end + 1..];
+        }
+        stmt.push_str(rest);
+        stmt.push(' ');
+    }
+    if !stmt.trim().is_empty() {
+        return error("missing `;` at the end of the input");
+    }
+    Ok(objective)
+}
+
+/// Number of variables announced by the header, if `line` is one.
+fn parse_header(line: &str) -> io::Result<Option<u32>> {
+    let mut tokens = line.split_whitespace();
+    if tokens.by_ref().find(|&t| t == "#variable=").is_none() {
+        return Ok(None);
+    }
+    match tokens.next().map(str::parse) {
+        Some(Ok(n)) => Ok(Some(n)),
+        _ => error("invalid header"),
+    }
+}
+
+fn parse_statement<Cb: Callbacks>(
+    stmt: &str,
+    solver: &mut Solver<Cb>,
+    num_vars: Option<u32>,
+    objective: &mut Option<Objective>,
+) -> io::Result<()> {
+    let mut tokens = stmt.split_whitespace().peekable();
+    match tokens.peek() {
+        None => return Ok(()),
+        Some(&"min:") => {
+            tokens.next();
+            if objective.is_some() {
+                return error("more than one objective");
+            }
+            *objective = Some(parse_terms(&mut tokens, solver, num_vars)?);
+            if let Some(t) = tokens.next() {
+                return error(format_args!("unexpected `{}` in the objective", t));
+            }
+            return Ok(());
+        }
+        Some(_) => (),
+    }
+    let mut terms = parse_terms(&mut tokens, solver, num_vars)?;
+    let rel = match tokens.next() {
+        Some(rel @ (">=" | "<=" | "=")) => rel,
+        Some(t) => return error(format_args!("expected a relation, got `{}`", t)),
+        None => return error("missing relation"),
+    };
+    let Some(bound) = tokens.next() else {
+        return error("missing right-hand side");
+    };
+    let bound = parse_int(bound)?;
+    if let Some(t) = tokens.next() {
+        return error(format_args!("unexpected `{}` after the right-hand side", t));
+    }
+    if rel != "<=" {
+        solver.add_pb_constraint(&terms, bound);
+    }
+    if rel != ">=" {
+        // `sum(coef * lit) <= bound` is `sum(-coef * lit) >= -bound`
+        for t in &mut terms {
+            t.0 = neg(t.0)?;
+        }
+        solver.add_pb_constraint(&terms, neg(bound)?);
+    }
+    Ok(())
+}
+
+/// Parse the terms `coef lit` until the end of the statement or a relation.
+fn parse_terms<Cb: Callbacks>(
+    tokens: &mut Peekable<SplitWhitespace>,
+    solver: &mut Solver<Cb>,
+    num_vars: Option<u32>,
+) -> io::Result<Objective> {
+    let mut terms = vec![];
+    while let Some(&t) = tokens.peek() {
+        if t.starts_with(['>', '<', '=']) {
+            break;
+        }
+        tokens.next();
+        let coef = parse_int(t)?;
+        let Some(lit) = tokens.next() else {
+            return error("missing literal after coefficient");
+        };
+        let lit = parse_lit(lit, solver, num_vars)?;
+        if let Some(&t) = tokens.peek() {
+            if t.starts_with(['x', '~']) {
+                return error("non-linear terms are not supported");
+            }
+        }
+        terms.push((coef, lit));
+    }
+    Ok(terms)
+}
+
+fn parse_lit<Cb: Callbacks>(
+    t: &str,
+    solver: &mut Solver<Cb>,
+    num_vars: Option<u32>,
+) -> io::Result<Lit> {
+    let (sign, var) = match t.strip_prefix('~') {
+        Some(var) => (false, var),
+        None => (true, t),
+    };
+    let var = match var.strip_prefix('x').map(str::parse::<u32>) {
+        Some(Ok(var)) if var > 0 => var,
+        _ => return error(format_args!("invalid literal `{}`", t)),
+    };
+    if let Some(num_vars) = num_vars.filter(|&n| var > n) {
+        let msg = format!("variable {} exceeds declared count {}", var, num_vars);
+        return error(msg);
+    }
+    Ok(Lit::new(solver.var_of_int(var - 1), sign))
+}
+
+fn parse_int(t: &str) -> io::Result<i64> {
+    match t.parse() {
+        Ok(n) => Ok(n),
+        Err(_) => error(format_args!("invalid integer `{}`", t)),
+    }
+}
+
+fn neg(n: i64) -> io::Result<i64> {
+    n.checked_neg()
+        .map_or_else(|| error("integer overflow"), Ok)
+}
+
+/// Value of `objective` in `model`.
+pub(crate) fn eval(objective: &[(i64, Lit)], model: &[lbool]) -> i64 {
+    let value_lit = |lit: Lit| model[lit.var().idx() as usize] ^ !lit.sign();
+    objective
+        .iter()
+        .filter(|t| value_lit(t.1) == lbool::TRUE)
+        .map(|t| t.0)
+        .sum()
+}
+
+/// Print a model in the format of the pseudo-Boolean competitions, `v x1 -x2 ...`.
+pub(crate) struct PrintModel<'a>(pub(crate) &'a [lbool]);
+
+impl fmt::Display for PrintModel<'_> {
+    fn fmt(&self, out: &mut fmt::Formatter) -> fmt::Result {
+        write!(out, "v")?;
+        for (i, &val) in self.0.iter().enumerate() {
+            if val == lbool::TRUE {
+                write!(out, " x{}", i + 1)?
+            } else if val == lbool::FALSE {
+                write!(out, " -x{}", i + 1)?
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use platsat::BasicSolver;
+
+    #[test]
+    fn test_parse() {
+        let input = "* #variable= 4 #constraint= 3\n\
+                     min: +1 x1 +2 x2\n  +3 ~x3 ;\n\
+                     +1 x1 +1 x2 +1 x3 >= 2 ;\n\
+                     * a comment\n\
+                     -2 x2 +1 ~x4 <= -1 ;\n\
+                     +1 x3 +1 x4 = 1 ;\n";
+        let mut solver = BasicSolver::default();
+        let obj = parse(&mut input.as_bytes(), &mut solver).unwrap().unwrap();
+        assert_eq!(solver.num_vars(), 4);
+        assert_eq!(obj.len(), 3);
+        assert_eq!(solver.solve_limited(&[]), lbool::TRUE);
+        // x2 is true, and x3 is true iff x4 is false
+        let model = solver.get_model().to_vec();
+        assert_eq!(model[1], lbool::TRUE);
+        assert_ne!(model[2], model[3]);
+        assert!(eval(&obj, &model) >= 2);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let mut solver = BasicSolver::default();
+        for input in [
+            "+1 x1 +1 x2 >= 1",
+            "+1 x1 x2 >= 1 ;",
+            "+1 x1 +1 y2 >= 1 ;",
+            "+1 x1 +1 x0 >= 1 ;",
+            "+1 x1 > 1 ;",
+            "* #variable= 1 #constraint= 1\n+1 x2 >= 1 ;",
+        ] {
+            assert!(
+                parse(&mut input.as_bytes(), &mut solver).is_err(),
+                "{}",
+                input
+            );
+        }
+    }
+}
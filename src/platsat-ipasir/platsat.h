@@ -0,0 +1,217 @@
+#ifndef PLATSAT_H
+#define PLATSAT_H
+
+/* Generated by cbindgen from src/platsat-ipasir, do not edit: run `make header` */
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+// The option was set.
+#define PLATSAT_OPTION_OK 0
+
+// There is no option with this name.
+#define PLATSAT_OPTION_UNKNOWN 1
+
+// The value could not be parsed for this option.
+#define PLATSAT_OPTION_PARSE_ERROR 2
+
+// The value is outside of the valid range of the option.
+#define PLATSAT_OPTION_INVALID 3
+
+// The handle is not the one of a clause group, or the group was retracted.
+#define PLATSAT_INVALID_GROUP -1
+
+typedef void (**LearntCB)(void*, const int*);
+
+// Callbacks of an external propagator, following IPASIR-UP.
+//
+// Each callback is given `data`. The literals are non-zero integers, as in
+// `ipasir_add`; the lists of literals given to the propagator are arrays with their
+// length, the ones it returns literal by literal end with `0`.
+typedef struct IpasirPropagator {
+  void *data;
+  void (*notify_assignment)(void*, const int*, size_t);
+  void (*notify_new_decision_level)(void*);
+  void (*notify_backtrack)(void*, size_t);
+  bool (*cb_check_found_model)(void*, const int*, size_t);
+  int (*cb_decide)(void*);
+  int (*cb_propagate)(void*);
+  int (*cb_add_reason_clause_lit)(void*, int);
+  bool (*cb_has_external_clause)(void*, bool*);
+  int (*cb_add_external_clause_lit)(void*);
+} IpasirPropagator;
+
+// Callback of `platsat_enumerate_models`, given its `data` and a model as the array of
+// its true literals. The enumeration stops if it returns a non-zero value.
+typedef int (*PlatsatModelCallback)(void*, const int*, size_t);
+
+// Statistics of a solver, see `platsat_stats`.
+typedef struct PlatsatStats {
+  // Calls to the solver.
+  uint64_t solves;
+  uint64_t restarts;
+  uint64_t conflicts;
+  uint64_t decisions;
+  uint64_t propagations;
+  // Literals of the learnt clauses.
+  uint64_t learnt_literals;
+  // Current number of original clauses.
+  uint64_t clauses;
+  // Current number of learnt clauses.
+  uint64_t learnts;
+  // Learnt clauses removed by the reductions of the clause database.
+  uint64_t deleted_clauses;
+  // Garbage collections of the clause allocator.
+  uint64_t gcs;
+  // Bytes used by the clause allocator.
+  uint64_t clause_memory;
+} PlatsatStats;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+const char *ipasir_signature(void);
+
+const void *ipasir_init(void);
+
+void ipasir_release(void *ptr);
+
+void ipasir_add(void *ptr, int lit);
+
+void ipasir_assume(void *ptr, int lit);
+
+int ipasir_solve(void *ptr);
+
+int ipasir_val(void *ptr, int lit);
+
+int ipasir_failed(void *ptr, int lit);
+
+void ipasir_set_terminate(void *ptr, void *state, int (*terminate)(void*));
+
+void ipasir_set_learn(void *ptr, void *state, int max_len, LearntCB learn);
+
+// Connect the external propagator `prop`, which replaces the previous one. It observes
+// no variable at first.
+//
+// # Safety
+//
+// `prop` must point to valid callbacks, which are copied.
+void ipasir_connect_external_propagator(void *ptr, const struct IpasirPropagator *prop);
+
+void ipasir_disconnect_external_propagator(void *ptr);
+
+// Notify the propagator of the assignments of `var`, which is kept by the
+// simplifications of the solver.
+void ipasir_add_observed_var(void *ptr, int var);
+
+void ipasir_remove_observed_var(void *ptr, int var);
+
+void ipasir_reset_observed_vars(void *ptr);
+
+// Is the observed literal `lit`, which is assigned, a decision?
+int ipasir_is_decision(void *ptr, int lit);
+
+// Version of this library, as a null-terminated string.
+const char *platsat_version(void);
+
+// Set the option `name` of the solver (see `SolverOpts::set` in the Rust API) to
+// `value`; returns `PLATSAT_OPTION_OK` or the reason why it was not set.
+//
+// # Safety
+//
+// `name` and `value` must be null-terminated strings.
+int platsat_set_option(void *ptr, const char *name, const char *value);
+
+// Stop the following calls to `ipasir_solve` after `n` more conflicts; there is no
+// limit if `n` is negative. They return `0` once the budget is exhausted.
+void platsat_set_conflict_budget(void *ptr, int64_t n);
+
+// Stop the following calls to `ipasir_solve` after `n` more propagations; there is
+// no limit if `n` is negative.
+void platsat_set_propagation_budget(void *ptr, int64_t n);
+
+// Add the clause `lits[..n]`; returns `0` if the solver is now known to be
+// unsatisfiable, `1` otherwise.
+//
+// # Safety
+//
+// `lits` must point to `n` literals.
+int platsat_add_clause(void *ptr, const int *lits, size_t n);
+
+// Assume the literals `lits[..n]` during the next call to `ipasir_solve`, like
+// `ipasir_assume`.
+//
+// # Safety
+//
+// `lits` must point to `n` literals.
+void platsat_assume_all(void *ptr, const int *lits, size_t n);
+
+// Model found by the last call to `ipasir_solve`, as the array of its true literals,
+// written to `*model`; returns its length.
+//
+// # Safety
+//
+// `model` must be null or valid for writes.
+size_t platsat_model(void *ptr, const int **model);
+
+// Assumptions of the last call to `ipasir_solve` that are enough for the clauses to
+// be unsatisfiable, written to `*core`; returns their number.
+//
+// The clause groups involved are given by `platsat_group_in_core`.
+//
+// # Safety
+//
+// `core` must be null or valid for writes.
+size_t platsat_unsat_core(void *ptr, const int **core);
+
+// Call `cb` on each model of the clauses, projected on the variables `vars[..n]`:
+// each assignment of them that can be extended into a model is given once, as the
+// array of its true literals. All the variables are used if `n` is `0`.
+//
+// Returns the number of models, or `-1` if the enumeration was stopped by `cb` or by
+// the budgets.
+//
+// # Safety
+//
+// `vars` must point to `n` variables.
+int64_t platsat_enumerate_models(void *ptr,
+                                 const int *vars,
+                                 size_t n,
+                                 void *data,
+                                 PlatsatModelCallback cb);
+
+// Create a new, active, group of clauses; returns its handle, which is positive.
+int platsat_new_group(void *ptr);
+
+// Add the clause `lits[..n]` to `group`, it is only used while the group is active.
+// Returns `0` if the solver is now known to be unsatisfiable, `1` otherwise, and
+// `PLATSAT_INVALID_GROUP` if `group` is not a valid handle.
+//
+// # Safety
+//
+// `lits` must point to `n` literals.
+int platsat_add_clause_in_group(void *ptr, int group, const int *lits, size_t n);
+
+// Retract the clauses of `group` for good, its handle is no longer valid. Returns `0`
+// if the solver is now known to be unsatisfiable, `1` otherwise, and
+// `PLATSAT_INVALID_GROUP` if `group` is not a valid handle.
+int platsat_retract_group(void *ptr, int group);
+
+// Were clauses of `group` used to prove that the last call to `ipasir_solve` had no
+// model? Returns `PLATSAT_INVALID_GROUP` if `group` is not a valid handle.
+int platsat_group_in_core(void *ptr, int group);
+
+// Write the statistics of the solver to `*stats`.
+//
+// # Safety
+//
+// `stats` must be valid for writes.
+void platsat_stats(void *ptr, struct PlatsatStats *stats);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* PLATSAT_H */
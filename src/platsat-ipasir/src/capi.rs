@@ -0,0 +1,481 @@
+//! # Platsat C API
+//!
+//! Functions beyond IPASIR, on the solvers created by `ipasir_init`: options, budgets,
+//! arrays of clauses and assumptions, unsat cores, model enumeration, clause groups and
+//! statistics. They are declared in `platsat.h`, generated by `cbindgen` (see `makefile`).
+//!
+//! Literals are non-zero integers, as in `ipasir_add`. The arrays returned by the
+//! solver belong to it, and are valid until the next call on the same solver.
+use platsat::{lbool, Lit, SetOptionError, SolverInterface, VMap, Var};
+use std::ffi::CStr;
+use std::mem;
+use std::os::raw::{c_char, c_int, c_void};
+use std::slice;
+use {get_solver, IpasirSolver};
+
+/// The option was set.
+pub const PLATSAT_OPTION_OK: c_int = 0;
+/// There is no option with this name.
+pub const PLATSAT_OPTION_UNKNOWN: c_int = 1;
+/// The value could not be parsed for this option.
+pub const PLATSAT_OPTION_PARSE_ERROR: c_int = 2;
+/// The value is outside of the valid range of the option.
+pub const PLATSAT_OPTION_INVALID: c_int = 3;
+
+/// The handle is not the one of a clause group, or the group was retracted.
+pub const PLATSAT_INVALID_GROUP: c_int = -1;
+
+static VERSION: &str = concat!(env!("CARGO_PKG_VERSION"), "\0");
+
+/// Statistics of a solver, see `platsat_stats`.
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct PlatsatStats {
+    /// Calls to the solver.
+    pub solves: u64,
+    pub restarts: u64,
+    pub conflicts: u64,
+    pub decisions: u64,
+    pub propagations: u64,
+    /// Literals of the learnt clauses.
+    pub learnt_literals: u64,
+    /// Current number of original clauses.
+    pub clauses: u64,
+    /// Current number of learnt clauses.
+    pub learnts: u64,
+    /// Learnt clauses removed by the reductions of the clause database.
+    pub deleted_clauses: u64,
+    /// Garbage collections of the clause allocator.
+    pub gcs: u64,
+    /// Bytes used by the clause allocator.
+    pub clause_memory: u64,
+}
+
+/// Callback of `platsat_enumerate_models`, given its `data` and a model as the array of
+/// its true literals. The enumeration stops if it returns a non-zero value.
+pub type PlatsatModelCallback = extern "C" fn(*mut c_void, *const c_int, usize) -> c_int;
+
+impl IpasirSolver {
+    /// Integer of `lit`, if it is a literal of the user (and not of a clause group).
+    fn lit_to_int(&self, lit: Lit) -> Option<c_int> {
+        lit_to_int(&self.solver.cb().lit_to_int, lit)
+    }
+
+    /// Return `lits`, as integers, through `out`.
+    fn output(&mut self, lits: &[Lit], out: *mut *const c_int) -> usize {
+        let mut ints = mem::take(&mut self.out);
+        ints.clear();
+        ints.extend(lits.iter().filter_map(|&lit| self.lit_to_int(lit)));
+        self.out = ints;
+        if !out.is_null() {
+            unsafe { *out = self.out.as_ptr() };
+        }
+        self.out.len()
+    }
+
+    /// The clause group of the handle `group`, if it was not retracted.
+    fn group(&self, group: c_int) -> Option<platsat::ClauseGroup> {
+        if group <= 0 {
+            return None;
+        }
+        self.groups.get(group as usize - 1).copied().flatten()
+    }
+}
+
+/// Integer of `lit` in `map`, which is `0` for the variables of the clause groups.
+fn lit_to_int(map: &VMap<c_int>, lit: Lit) -> Option<c_int> {
+    match if map.has(lit.var()) {
+        map[lit.var()]
+    } else {
+        0
+    } {
+        0 => None,
+        i if lit.sign() => Some(i),
+        i => Some(-i),
+    }
+}
+
+/// The literals of `lits[..n]`.
+unsafe fn lits<'a>(lits: *const c_int, n: usize) -> &'a [c_int] {
+    if n == 0 {
+        &[]
+    } else {
+        slice::from_raw_parts(lits, n)
+    }
+}
+
+/// Version of this library, as a null-terminated string.
+#[no_mangle]
+pub extern "C" fn platsat_version() -> *const c_char {
+    VERSION.as_ptr() as *const c_char
+}
+
+/// Set the option `name` of the solver (see `SolverOpts::set` in the Rust API) to
+/// `value`; returns `PLATSAT_OPTION_OK` or the reason why it was not set.
+///
+/// # Safety
+///
+/// `name` and `value` must be null-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn platsat_set_option(
+    ptr: *mut c_void,
+    name: *const c_char,
+    value: *const c_char,
+) -> c_int {
+    let mut s = get_solver(ptr);
+
+    let (name, value) = (CStr::from_ptr(name), CStr::from_ptr(value));
+    let res = match (name.to_str(), value.to_str()) {
+        (Ok(name), Ok(value)) => match s.solver.set_option(name, value) {
+            Ok(()) => PLATSAT_OPTION_OK,
+            Err(SetOptionError::Unknown(_)) => PLATSAT_OPTION_UNKNOWN,
+            Err(SetOptionError::Parse(_)) => PLATSAT_OPTION_PARSE_ERROR,
+            Err(SetOptionError::Invalid(_)) => PLATSAT_OPTION_INVALID,
+        },
+        (Err(_), _) => PLATSAT_OPTION_UNKNOWN,
+        (_, Err(_)) => PLATSAT_OPTION_PARSE_ERROR,
+    };
+
+    mem::forget(s);
+    res
+}
+
+/// Stop the following calls to `ipasir_solve` after `n` more conflicts; there is no
+/// limit if `n` is negative. They return `0` once the budget is exhausted.
+#[no_mangle]
+pub extern "C" fn platsat_set_conflict_budget(ptr: *mut c_void, n: i64) {
+    let mut s = get_solver(ptr);
+    if n < 0 {
+        s.solver.set_conflict_budget(u64::MAX);
+    } else {
+        s.solver.set_conflict_budget(n as u64);
+    }
+    mem::forget(s)
+}
+
+/// Stop the following calls to `ipasir_solve` after `n` more propagations; there is
+/// no limit if `n` is negative.
+#[no_mangle]
+pub extern "C" fn platsat_set_propagation_budget(ptr: *mut c_void, n: i64) {
+    let mut s = get_solver(ptr);
+    if n < 0 {
+        s.solver.set_propagation_budget(u64::MAX);
+    } else {
+        s.solver.set_propagation_budget(n as u64);
+    }
+    mem::forget(s)
+}
+
+/// Add the clause `lits[..n]`; returns `0` if the solver is now known to be
+/// unsatisfiable, `1` otherwise.
+///
+/// # Safety
+///
+/// `lits` must point to `n` literals.
+#[no_mangle]
+pub unsafe extern "C" fn platsat_add_clause(
+    ptr: *mut c_void,
+    lits: *const c_int,
+    n: usize,
+) -> c_int {
+    let mut s = get_solver(ptr);
+
+    let mut c: Vec<Lit> = self::lits(lits, n).iter().map(|&l| s.get_lit(l)).collect();
+    let res = s.solver.add_clause_reuse(&mut c);
+
+    mem::forget(s);
+    res as c_int
+}
+
+/// Assume the literals `lits[..n]` during the next call to `ipasir_solve`, like
+/// `ipasir_assume`.
+///
+/// # Safety
+///
+/// `lits` must point to `n` literals.
+#[no_mangle]
+pub unsafe extern "C" fn platsat_assume_all(ptr: *mut c_void, lits: *const c_int, n: usize) {
+    let mut s = get_solver(ptr);
+
+    for &lit in self::lits(lits, n) {
+        let lit = s.get_lit(lit);
+        s.assumptions.push(lit);
+    }
+
+    mem::forget(s)
+}
+
+/// Model found by the last call to `ipasir_solve`, as the array of its true literals,
+/// written to `*model`; returns its length.
+///
+/// # Safety
+///
+/// `model` must be null or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn platsat_model(ptr: *mut c_void, model: *mut *const c_int) -> usize {
+    let mut s = get_solver(ptr);
+
+    let lits: Vec<Lit> = (1..s.vars.len())
+        .filter_map(|i| {
+            let v = s.vars[i];
+            match s.solver.get_model().get(v.idx() as usize) {
+                Some(&x) if x != lbool::UNDEF => Some(Lit::new(v, x == lbool::TRUE)),
+                _ => None,
+            }
+        })
+        .collect();
+    let n = s.output(&lits, model);
+
+    mem::forget(s);
+    n
+}
+
+/// Assumptions of the last call to `ipasir_solve` that are enough for the clauses to
+/// be unsatisfiable, written to `*core`; returns their number.
+///
+/// The clause groups involved are given by `platsat_group_in_core`.
+///
+/// # Safety
+///
+/// `core` must be null or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn platsat_unsat_core(ptr: *mut c_void, core: *mut *const c_int) -> usize {
+    let mut s = get_solver(ptr);
+
+    let lits = s.solver.unsat_core().to_vec();
+    let n = s.output(&lits, core);
+
+    mem::forget(s);
+    n
+}
+
+/// Call `cb` on each model of the clauses, projected on the variables `vars[..n]`:
+/// each assignment of them that can be extended into a model is given once, as the
+/// array of its true literals. All the variables are used if `n` is `0`.
+///
+/// Returns the number of models, or `-1` if the enumeration was stopped by `cb` or by
+/// the budgets.
+///
+/// # Safety
+///
+/// `vars` must point to `n` variables.
+#[no_mangle]
+pub unsafe extern "C" fn platsat_enumerate_models(
+    ptr: *mut c_void,
+    vars: *const c_int,
+    n: usize,
+    data: *mut c_void,
+    cb: PlatsatModelCallback,
+) -> i64 {
+    let mut s = get_solver(ptr);
+
+    let vars: Vec<Var> = if n == 0 {
+        s.vars.iter().skip(1).cloned().collect()
+    } else {
+        let ints = self::lits(vars, n);
+        ints.iter().map(|&v| s.get_lit(v).var()).collect()
+    };
+    let map = s.solver.cb().lit_to_int.clone();
+    let mut count = 0;
+    let mut ints = vec![];
+    let exhausted = {
+        let mut models = s.solver.iter_projected_models(&vars);
+        let mut stopped = false;
+        for model in models.by_ref() {
+            count += 1;
+            ints.clear();
+            ints.extend(model.iter().filter_map(|&lit| lit_to_int(&map, lit)));
+            if cb(data, ints.as_ptr(), ints.len()) != 0 {
+                stopped = true;
+                break;
+            }
+        }
+        !stopped && models.is_exhausted()
+    };
+
+    mem::forget(s);
+    if exhausted {
+        count
+    } else {
+        -1
+    }
+}
+
+/// Create a new, active, group of clauses; returns its handle, which is positive.
+#[no_mangle]
+pub extern "C" fn platsat_new_group(ptr: *mut c_void) -> c_int {
+    let mut s = get_solver(ptr);
+
+    let group = s.solver.new_clause_group();
+    s.groups.push(Some(group));
+    let res = s.groups.len() as c_int;
+
+    mem::forget(s);
+    res
+}
+
+/// Add the clause `lits[..n]` to `group`, it is only used while the group is active.
+/// Returns `0` if the solver is now known to be unsatisfiable, `1` otherwise, and
+/// `PLATSAT_INVALID_GROUP` if `group` is not a valid handle.
+///
+/// # Safety
+///
+/// `lits` must point to `n` literals.
+#[no_mangle]
+pub unsafe extern "C" fn platsat_add_clause_in_group(
+    ptr: *mut c_void,
+    group: c_int,
+    lits: *const c_int,
+    n: usize,
+) -> c_int {
+    let mut s = get_solver(ptr);
+
+    let res = match s.group(group) {
+        Some(group) => {
+            let c: Vec<Lit> = self::lits(lits, n).iter().map(|&l| s.get_lit(l)).collect();
+            s.solver.add_clause_in_group(group, &c) as c_int
+        }
+        None => PLATSAT_INVALID_GROUP,
+    };
+
+    mem::forget(s);
+    res
+}
+
+/// Retract the clauses of `group` for good, its handle is no longer valid. Returns `0`
+/// if the solver is now known to be unsatisfiable, `1` otherwise, and
+/// `PLATSAT_INVALID_GROUP` if `group` is not a valid handle.
+#[no_mangle]
+pub extern "C" fn platsat_retract_group(ptr: *mut c_void, group: c_int) -> c_int {
+    let mut s = get_solver(ptr);
+
+    let res = match s.group(group) {
+        Some(g) => {
+            s.groups[group as usize - 1] = None;
+            s.solver.retract_clause_group(g) as c_int
+        }
+        None => PLATSAT_INVALID_GROUP,
+    };
+
+    mem::forget(s);
+    res
+}
+
+/// Were clauses of `group` used to prove that the last call to `ipasir_solve` had no
+/// model? Returns `PLATSAT_INVALID_GROUP` if `group` is not a valid handle.
+#[no_mangle]
+pub extern "C" fn platsat_group_in_core(ptr: *mut c_void, group: c_int) -> c_int {
+    let s = get_solver(ptr);
+
+    let res = match s.group(group) {
+        Some(g) => s.solver.unsat_core_contains_lit(g.activation_lit()) as c_int,
+        None => PLATSAT_INVALID_GROUP,
+    };
+
+    mem::forget(s);
+    res
+}
+
+/// Write the statistics of the solver to `*stats`.
+///
+/// # Safety
+///
+/// `stats` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn platsat_stats(ptr: *mut c_void, stats: *mut PlatsatStats) {
+    let s = get_solver(ptr);
+
+    let st = s.solver.stats();
+    *stats = PlatsatStats {
+        solves: st.solves,
+        restarts: st.restarts,
+        conflicts: st.conflicts,
+        decisions: st.decisions,
+        propagations: st.propagations,
+        learnt_literals: st.learnt_literals,
+        clauses: st.clauses,
+        learnts: st.learnts,
+        deleted_clauses: st.deleted_clauses,
+        gcs: st.gcs,
+        clause_memory: st.clause_memory as u64,
+    };
+
+    mem::forget(s)
+}
+
+#[test]
+fn test_capi() {
+    use std::ptr;
+    extern "C" fn count(data: *mut c_void, model: *const c_int, n: usize) -> c_int {
+        assert_eq!(n, 2);
+        assert!(!model.is_null());
+        unsafe { *(data as *mut i64) += 1 };
+        0
+    }
+    let s = ::ipasir_init() as *mut c_void;
+    unsafe {
+        let opt = |name: &[u8], value: &[u8]| {
+            let (name, value) = (
+                name.as_ptr() as *const c_char,
+                value.as_ptr() as *const c_char,
+            );
+            platsat_set_option(s, name, value)
+        };
+        assert_eq!(opt(b"ccmin_mode\0", b"1\0"), PLATSAT_OPTION_OK);
+        assert_eq!(opt(b"no_such_option\0", b"1\0"), PLATSAT_OPTION_UNKNOWN);
+        assert_eq!(opt(b"var_decay\0", b"2\0"), PLATSAT_OPTION_INVALID);
+        assert_eq!(platsat_add_clause(s, [1, 2, 3].as_ptr(), 3), 1);
+        let g = platsat_new_group(s);
+        assert_eq!(platsat_add_clause_in_group(s, g, [-1].as_ptr(), 1), 1);
+        assert_eq!(platsat_add_clause_in_group(s, g, [-2].as_ptr(), 1), 1);
+        platsat_assume_all(s, [-3].as_ptr(), 1);
+        assert_eq!(::ipasir_solve(s), 20);
+        let mut out = ptr::null();
+        assert_eq!(platsat_unsat_core(s, &mut out), 1);
+        assert_eq!(*out, -3);
+        assert_eq!(platsat_group_in_core(s, g), 1);
+        assert_eq!(::ipasir_solve(s), 10);
+        assert_eq!(platsat_model(s, &mut out), 3);
+        assert_eq!(slice::from_raw_parts(out, 3), [-1, -2, 3]);
+        assert_eq!(platsat_retract_group(s, g), 1);
+        for g in [g, 0, -1, g + 1] {
+            assert_eq!(platsat_retract_group(s, g), PLATSAT_INVALID_GROUP);
+            assert_eq!(platsat_group_in_core(s, g), PLATSAT_INVALID_GROUP);
+            let res = platsat_add_clause_in_group(s, g, [1].as_ptr(), 1);
+            assert_eq!(res, PLATSAT_INVALID_GROUP);
+        }
+        let mut n = 0i64;
+        let data = &mut n as *mut i64 as *mut c_void;
+        assert_eq!(
+            platsat_enumerate_models(s, [1, 2].as_ptr(), 2, data, count),
+            4
+        );
+        assert_eq!(n, 4);
+        let mut stats = PlatsatStats::default();
+        platsat_stats(s, &mut stats);
+        assert!(stats.solves >= 6);
+    }
+    ::ipasir_release(s);
+
+    // the terminate callback interrupts the search, 5 pigeons in 4 holes
+    extern "C" fn terminate(_: *mut c_void) -> c_int {
+        1
+    }
+    let s = ::ipasir_init() as *mut c_void;
+    let p = |i: c_int, h: c_int| 1 + 4 * i + h;
+    for i in 0..5 {
+        let c: Vec<c_int> = (0..4).map(|h| p(i, h)).collect();
+        assert_eq!(unsafe { platsat_add_clause(s, c.as_ptr(), 4) }, 1);
+        for j in 0..i {
+            for h in 0..4 {
+                assert_eq!(
+                    unsafe { platsat_add_clause(s, [-p(i, h), -p(j, h)].as_ptr(), 2) },
+                    1
+                );
+            }
+        }
+    }
+    ::ipasir_set_terminate(s, ptr::null_mut(), terminate);
+    assert_eq!(::ipasir_solve(s), 0);
+    ::ipasir_release(s);
+}
@@ -2,14 +2,23 @@
 ///
 /// API for platsat following the [IPASIR](https://github.com/biotomas/ipasir) convention.
 /// See `ipasir` directory at the root of the project
+///
+/// The user propagators of IPASIR-UP are supported too, see
+/// `ipasir_connect_external_propagator`.
+///
+/// The `platsat_*` functions of `capi` give access to more of the solver, on the same
+/// solvers. All of them are declared in the header `platsat.h`.
 extern crate platsat;
 
-use platsat::{self as sat, lbool, Lit, SolverInterface, Var};
+use platsat::{self as sat, lbool, ExternalPropagator, Lit, SolverInterface, UserPropagator, Var};
 use std::boxed::Box;
 use std::mem;
 use std::os::raw::{c_char, c_int, c_void};
 
-static NAME: &'static str = "platsat-0.2\0";
+mod capi;
+pub use capi::*;
+
+static NAME: &str = "platsat-0.2\0";
 
 /// The wrapper around a solver. It contains partial clauses, assumptions, etc.
 struct IpasirSolver {
@@ -17,18 +26,22 @@ struct IpasirSolver {
     vars: Vec<Var>, // int->var
     cur: Vec<Lit>,  // current clause
     assumptions: Vec<Lit>,
+    propagator: Option<UserPropagator<CPropagator>>,
+    groups: Vec<Option<sat::ClauseGroup>>, // handle-1 -> group until retracted, see `capi`
+    out: Vec<c_int>,                       // arrays returned by `capi`
 }
 
+#[allow(clippy::upper_case_acronyms)]
 type SAT = sat::Solver<CB>;
 
 type LearntCB = *const extern "C" fn(*mut c_void, *const c_int) -> c_void;
 
 /// Callbacks
 struct CB {
-    basic: sat::BasicCallbacks,
     lits: Vec<c_int>,             // temporary
     lit_to_int: sat::VMap<c_int>, // reverse mapping lit->int
     learn_cb: Option<(c_int, *mut c_void, LearntCB)>,
+    terminate_cb: Option<(*mut c_void, extern "C" fn(*mut c_void) -> c_int)>,
 }
 
 impl IpasirSolver {
@@ -38,11 +51,27 @@ impl IpasirSolver {
             solver: SAT::new(sat::SolverOpts::default(), CB::new()),
             cur: Vec::new(),
             assumptions: Vec::new(),
+            propagator: None,
+            groups: Vec::new(),
+            out: Vec::new(),
         }
     }
 
-    fn decompose(&mut self) -> (&mut SAT, &mut Vec<Lit>, &mut Vec<Lit>) {
-        (&mut self.solver, &mut self.cur, &mut self.assumptions)
+    #[allow(clippy::type_complexity)]
+    fn decompose(
+        &mut self,
+    ) -> (
+        &mut SAT,
+        &mut Vec<Lit>,
+        &mut Vec<Lit>,
+        &mut Option<UserPropagator<CPropagator>>,
+    ) {
+        (
+            &mut self.solver,
+            &mut self.cur,
+            &mut self.assumptions,
+            &mut self.propagator,
+        )
     }
 
     /// Allocate variables until we get the one corresponding to `x`
@@ -50,9 +79,12 @@ impl IpasirSolver {
         while x >= self.vars.len() {
             let i = self.vars.len();
             let v = self.solver.new_var_default();
-            self.vars[i] = v;
+            self.vars.push(v);
+            if let Some(ref mut prop) = self.propagator {
+                prop.prop.vars.push(v);
+            }
             // reverse mapping
-            self.solver.cb_mut().lit_to_int.insert(v, x as c_int, 0);
+            self.solver.cb_mut().lit_to_int.insert(v, i as c_int, 0);
         }
         self.vars[x]
     }
@@ -60,23 +92,34 @@ impl IpasirSolver {
     #[inline]
     fn get_lit(&mut self, lit: c_int) -> Lit {
         assert!(lit != 0);
-        let v = self.get_var(lit.abs() as usize);
+        let v = self.get_var(lit.unsigned_abs() as usize);
         Lit::new(v, lit > 0)
     }
+
+    fn propagator(&mut self) -> &mut UserPropagator<CPropagator> {
+        self.propagator
+            .as_mut()
+            .expect("no external propagator is connected")
+    }
 }
 
 impl CB {
     fn new() -> Self {
         CB {
-            basic: sat::BasicCallbacks::new(),
             lits: vec![],
             lit_to_int: sat::VMap::new(),
             learn_cb: None,
+            terminate_cb: None,
         }
     }
 }
 
 impl platsat::Callbacks for CB {
+    fn stop(&self) -> bool {
+        self.terminate_cb
+            .is_some_and(|(state, terminate)| terminate(state) != 0)
+    }
+
     fn on_new_clause(&mut self, lits: &[Lit], kind: sat::ClauseKind) {
         if self.learn_cb.is_none() {
             return;
@@ -107,15 +150,15 @@ impl platsat::Callbacks for CB {
 
 #[no_mangle]
 pub extern "C" fn ipasir_signature() -> *const c_char {
-    let ptr: *const c_char = NAME.as_bytes().as_ptr() as *const u8 as *const i8;
+    let ptr: *const c_char = NAME.as_bytes().as_ptr() as *const c_char;
     ptr
 }
 
 #[no_mangle]
 pub extern "C" fn ipasir_init() -> *const c_void {
     let s = Box::new(IpasirSolver::new());
-    let ptr = Box::into_raw(s) as *const IpasirSolver as *const c_void;
-    ptr
+
+    Box::into_raw(s) as *const IpasirSolver as *const c_void
 }
 
 #[inline]
@@ -134,7 +177,7 @@ pub extern "C" fn ipasir_add(ptr: *mut c_void, lit: c_int) {
     let mut s = get_solver(ptr);
     if lit == 0 {
         // push current clause into vector `clauses`, reset it
-        let (solver, cur, _) = s.decompose();
+        let (solver, cur, _, _) = s.decompose();
         solver.add_clause_reuse(cur);
         cur.clear();
     } else {
@@ -170,10 +213,13 @@ pub extern "C" fn ipasir_solve(ptr: *mut c_void) -> c_int {
     let mut s = get_solver(ptr);
 
     let res = {
-        let (solver, _, assumptions) = s.decompose();
+        let (solver, _, assumptions, propagator) = s.decompose();
 
         // solve under assumptions
-        let res = solver.solve_limited(&assumptions);
+        let res = match *propagator {
+            Some(ref mut prop) => solver.solve_limited_th(prop, assumptions),
+            None => solver.solve_limited(assumptions),
+        };
 
         // reset assumptions
         assumptions.clear();
@@ -188,7 +234,7 @@ pub extern "C" fn ipasir_solve(ptr: *mut c_void) -> c_int {
 pub extern "C" fn ipasir_val(ptr: *mut c_void, lit: c_int) -> c_int {
     let mut s = get_solver(ptr);
 
-    let var = s.get_var(lit.abs() as usize);
+    let var = s.get_var(lit.unsigned_abs() as usize);
     let val = {
         let v = s.solver.get_model()[var.idx() as usize];
         if lit > 0 {
@@ -213,7 +259,7 @@ pub extern "C" fn ipasir_failed(ptr: *mut c_void, lit: c_int) -> c_int {
 
     // check unsat-core
     let lit = s.get_lit(lit);
-    let res = s.solver.unsat_core_contains_var(lit.var());
+    let res = s.solver.unsat_core_contains_lit(lit);
 
     mem::forget(s);
     res as c_int
@@ -228,11 +274,7 @@ pub extern "C" fn ipasir_set_terminate(
     let mut s = get_solver(ptr);
 
     // set handler using the given C function
-    let f = move || {
-        let should_stop = terminate(state) != 0;
-        should_stop
-    };
-    s.solver.cb_mut().basic.set_stop(f);
+    s.solver.cb_mut().terminate_cb = Some((state, terminate));
 
     mem::forget(s)
 }
@@ -253,3 +295,183 @@ pub extern "C" fn ipasir_set_learn(
 
     mem::forget(s)
 }
+
+/// Callbacks of an external propagator, following IPASIR-UP.
+///
+/// Each callback is given `data`. The literals are non-zero integers, as in
+/// `ipasir_add`; the lists of literals given to the propagator are arrays with their
+/// length, the ones it returns literal by literal end with `0`.
+#[repr(C)]
+pub struct IpasirPropagator {
+    pub data: *mut c_void,
+    pub notify_assignment: extern "C" fn(*mut c_void, *const c_int, usize),
+    pub notify_new_decision_level: extern "C" fn(*mut c_void),
+    pub notify_backtrack: extern "C" fn(*mut c_void, usize),
+    pub cb_check_found_model: extern "C" fn(*mut c_void, *const c_int, usize) -> bool,
+    pub cb_decide: extern "C" fn(*mut c_void) -> c_int,
+    pub cb_propagate: extern "C" fn(*mut c_void) -> c_int,
+    pub cb_add_reason_clause_lit: extern "C" fn(*mut c_void, c_int) -> c_int,
+    pub cb_has_external_clause: extern "C" fn(*mut c_void, *mut bool) -> bool,
+    pub cb_add_external_clause_lit: extern "C" fn(*mut c_void) -> c_int,
+}
+
+/// The propagator of the user, behind its callbacks.
+struct CPropagator {
+    cbs: IpasirPropagator,
+    vars: Vec<Var>,   // int->var
+    lits: Vec<c_int>, // temporary
+}
+
+impl CPropagator {
+    fn lit_to_int(&self, lit: Lit) -> c_int {
+        // `get_var` allocates the variables in order, so `vars[x]` has index `x`
+        debug_assert_eq!(self.vars[lit.var().idx() as usize], lit.var());
+        let i = lit.var().idx() as c_int;
+        if lit.sign() {
+            i
+        } else {
+            -i
+        }
+    }
+
+    fn int_to_lit(&self, lit: c_int) -> Option<Lit> {
+        if lit == 0 {
+            None
+        } else {
+            let v = self.vars[lit.unsigned_abs() as usize];
+            Some(Lit::new(v, lit > 0))
+        }
+    }
+
+    fn set_lits(&mut self, lits: &[Lit]) {
+        self.lits.clear();
+        for &lit in lits {
+            let i = self.lit_to_int(lit);
+            self.lits.push(i);
+        }
+    }
+}
+
+impl ExternalPropagator for CPropagator {
+    fn notify_assignment(&mut self, lits: &[Lit]) {
+        self.set_lits(lits);
+        (self.cbs.notify_assignment)(self.cbs.data, self.lits.as_ptr(), self.lits.len())
+    }
+    fn notify_new_decision_level(&mut self) {
+        (self.cbs.notify_new_decision_level)(self.cbs.data)
+    }
+    fn notify_backtrack(&mut self, new_level: usize) {
+        (self.cbs.notify_backtrack)(self.cbs.data, new_level)
+    }
+    fn check_found_model(&mut self, model: &[Lit]) -> bool {
+        self.set_lits(model);
+        (self.cbs.cb_check_found_model)(self.cbs.data, self.lits.as_ptr(), self.lits.len())
+    }
+    fn decide(&mut self) -> Option<Lit> {
+        self.int_to_lit((self.cbs.cb_decide)(self.cbs.data))
+    }
+    fn propagate(&mut self) -> Option<Lit> {
+        self.int_to_lit((self.cbs.cb_propagate)(self.cbs.data))
+    }
+    fn add_reason_clause(&mut self, propagated: Lit, clause: &mut Vec<Lit>) {
+        let p = self.lit_to_int(propagated);
+        while let Some(lit) = self.int_to_lit((self.cbs.cb_add_reason_clause_lit)(self.cbs.data, p))
+        {
+            clause.push(lit);
+        }
+    }
+    fn add_external_clause(&mut self, clause: &mut Vec<Lit>) -> bool {
+        // the clauses are kept even if they are forgettable
+        let mut forgettable = false;
+        if !(self.cbs.cb_has_external_clause)(self.cbs.data, &mut forgettable) {
+            return false;
+        }
+        while let Some(lit) = self.int_to_lit((self.cbs.cb_add_external_clause_lit)(self.cbs.data))
+        {
+            clause.push(lit);
+        }
+        true
+    }
+}
+
+/// Connect the external propagator `prop`, which replaces the previous one. It observes
+/// no variable at first.
+///
+/// # Safety
+///
+/// `prop` must point to valid callbacks, which are copied.
+#[no_mangle]
+pub unsafe extern "C" fn ipasir_connect_external_propagator(
+    ptr: *mut c_void,
+    prop: *const IpasirPropagator,
+) {
+    let mut s = get_solver(ptr);
+
+    let cbs = std::ptr::read(prop);
+    let vars = s.vars.clone();
+    s.propagator = Some(UserPropagator::new(CPropagator {
+        cbs,
+        vars,
+        lits: vec![],
+    }));
+
+    mem::forget(s)
+}
+
+#[no_mangle]
+pub extern "C" fn ipasir_disconnect_external_propagator(ptr: *mut c_void) {
+    let mut s = get_solver(ptr);
+    s.propagator = None;
+    mem::forget(s)
+}
+
+/// Notify the propagator of the assignments of `var`, which is kept by the
+/// simplifications of the solver.
+#[no_mangle]
+pub extern "C" fn ipasir_add_observed_var(ptr: *mut c_void, var: c_int) {
+    let mut s = get_solver(ptr);
+
+    let v = s.get_var(var.unsigned_abs() as usize);
+    s.solver.set_frozen(v, true);
+    s.propagator().add_observed_var(v);
+
+    mem::forget(s)
+}
+
+#[no_mangle]
+pub extern "C" fn ipasir_remove_observed_var(ptr: *mut c_void, var: c_int) {
+    let mut s = get_solver(ptr);
+
+    let v = s.get_var(var.unsigned_abs() as usize);
+    s.solver.set_frozen(v, false);
+    s.propagator().remove_observed_var(v);
+
+    mem::forget(s)
+}
+
+#[no_mangle]
+pub extern "C" fn ipasir_reset_observed_vars(ptr: *mut c_void) {
+    let mut s = get_solver(ptr);
+
+    let vars: Vec<Var> = s.vars.clone();
+    for v in vars {
+        if s.propagator().is_observed(v) {
+            s.solver.set_frozen(v, false);
+        }
+    }
+    s.propagator().reset_observed_vars();
+
+    mem::forget(s)
+}
+
+/// Is the observed literal `lit`, which is assigned, a decision?
+#[no_mangle]
+pub extern "C" fn ipasir_is_decision(ptr: *mut c_void, lit: c_int) -> c_int {
+    let mut s = get_solver(ptr);
+
+    let lit = s.get_lit(lit);
+    let res = s.propagator().is_decision(lit);
+
+    mem::forget(s);
+    res as c_int
+}
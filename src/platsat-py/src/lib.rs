@@ -0,0 +1,282 @@
+//! # Python
+//!
+//! Bindings of platsat for Python, with the incremental interface of the solvers of
+//! [PySAT](https://pysathq.github.io/). Literals are non-zero integers, as in DIMACS:
+//! `v` or `-v` for the variable `v >= 1`, whose variables are created when they first
+//! occur.
+//!
+//! Built with `maturin develop` (or `maturin build`) in `src/platsat-py`, it is used as:
+//!
+//! ```python
+//! from platsat import Solver
+//!
+//! with Solver(bootstrap_with=[[1, 2], [-1, 2]]) as s:
+//!     s.add_clause([-2, 3])
+//!     assert not s.solve(assumptions=[-3])
+//!     print(s.get_core())  # [-3]
+//!     assert s.solve()
+//!     print(s.get_model())  # [-1, 2, 3] or [1, 2, 3]
+//! ```
+//!
+//! A search of [`solve_limited`](Solver::solve_limited) with `expect_interrupt=True`
+//! releases the GIL, so another thread (such as a `threading.Timer`) can stop it with
+//! [`interrupt`](Solver::interrupt).
+use platsat::{lbool, BasicSolver, Lit, SolverInterface, Var};
+use pyo3::exceptions::{PyRuntimeError, PyValueError};
+use pyo3::prelude::*;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// The solver and the mapping of the integers to its variables.
+struct Inner {
+    solver: BasicSolver,
+    /// The variable of each integer, minus 1.
+    vars: Vec<Var>,
+    /// Result of the last search, `None` if it was interrupted or there was none.
+    status: Option<bool>,
+}
+
+impl Inner {
+    fn lit(&mut self, lit: i64) -> PyResult<Lit> {
+        let v = lit.unsigned_abs();
+        if lit == 0 || v > i32::MAX as u64 {
+            return Err(PyValueError::new_err(format!("invalid literal {}", lit)));
+        }
+        while (self.vars.len() as u64) < v {
+            self.vars.push(self.solver.new_var_default());
+        }
+        Ok(Lit::new(self.vars[v as usize - 1], lit > 0))
+    }
+
+    fn lits(&mut self, lits: &[i64]) -> PyResult<Vec<Lit>> {
+        lits.iter().map(|&lit| self.lit(lit)).collect()
+    }
+
+    /// Integer of `lit`: the variables are only created by [`lit`](Self::lit), so `v` is
+    /// `vars[v.idx()]`.
+    fn int(&self, lit: Lit) -> i64 {
+        let v = lit.var().idx() as i64 + 1;
+        if lit.sign() {
+            v
+        } else {
+            -v
+        }
+    }
+
+    fn solve(&mut self, assumptions: &[Lit]) -> Option<bool> {
+        let res = self.solver.solve_limited(assumptions);
+        self.status = if res == lbool::UNDEF {
+            None
+        } else {
+            Some(res == lbool::TRUE)
+        };
+        self.status
+    }
+}
+
+/// An incremental SAT solver.
+#[pyclass(frozen)]
+pub struct Solver {
+    inner: Mutex<Inner>,
+    /// Set by [`interrupt`](Self::interrupt).
+    stop: Arc<AtomicBool>,
+}
+
+impl Solver {
+    fn lock(&self) -> PyResult<std::sync::MutexGuard<'_, Inner>> {
+        // never wait for the lock with the GIL held, the search may need it to finish
+        self.inner
+            .try_lock()
+            .map_err(|_| PyRuntimeError::new_err("the solver is already solving"))
+    }
+}
+
+#[pymethods]
+impl Solver {
+    /// A solver with the clauses of `bootstrap_with`.
+    #[new]
+    #[pyo3(signature = (bootstrap_with = None))]
+    fn new(bootstrap_with: Option<Vec<Vec<i64>>>) -> PyResult<Self> {
+        let solver = Solver {
+            inner: Mutex::new(Inner {
+                solver: BasicSolver::default(),
+                vars: vec![],
+                status: None,
+            }),
+            stop: Arc::new(AtomicBool::new(false)),
+        };
+        solver.append_formula(bootstrap_with.unwrap_or_default())?;
+        Ok(solver)
+    }
+
+    fn __enter__(slf: Py<Self>) -> Py<Self> {
+        slf
+    }
+
+    #[pyo3(signature = (*_args))]
+    fn __exit__(&self, _args: &Bound<'_, pyo3::types::PyTuple>) {}
+
+    /// Add the clause `clause`, returns `False` if the solver is now known to be
+    /// unsatisfiable.
+    fn add_clause(&self, clause: Vec<i64>) -> PyResult<bool> {
+        let mut inner = self.lock()?;
+        let mut c = inner.lits(&clause)?;
+        Ok(inner.solver.add_clause_reuse(&mut c))
+    }
+
+    /// Add the clauses of `formula`, returns `False` if the solver is now known to be
+    /// unsatisfiable.
+    fn append_formula(&self, formula: Vec<Vec<i64>>) -> PyResult<bool> {
+        let mut ok = true;
+        for clause in formula {
+            ok &= self.add_clause(clause)?;
+        }
+        Ok(ok)
+    }
+
+    /// Search for a model of the clauses under `assumptions`, without budget.
+    #[pyo3(signature = (assumptions = vec![]))]
+    fn solve(&self, py: Python<'_>, assumptions: Vec<i64>) -> PyResult<bool> {
+        let mut inner = self.lock()?;
+        let assumptions = inner.lits(&assumptions)?;
+        inner.solver.budget_off();
+        inner.solver.clear_stop_flag();
+        let inner = &mut *inner;
+        Ok(py.detach(|| inner.solve(&assumptions)) == Some(true))
+    }
+
+    /// Like `solve`, within the budgets given by `conf_budget` and `prop_budget`;
+    /// returns `None` if the search stopped before the end. If `expect_interrupt` is
+    /// set, `interrupt` stops it too.
+    #[pyo3(signature = (assumptions = vec![], expect_interrupt = false))]
+    fn solve_limited(
+        &self,
+        py: Python<'_>,
+        assumptions: Vec<i64>,
+        expect_interrupt: bool,
+    ) -> PyResult<Option<bool>> {
+        let mut inner = self.lock()?;
+        let assumptions = inner.lits(&assumptions)?;
+        if expect_interrupt {
+            inner.solver.set_stop_flag(self.stop.clone());
+        } else {
+            inner.solver.clear_stop_flag();
+        }
+        let inner = &mut *inner;
+        Ok(py.detach(|| inner.solve(&assumptions)))
+    }
+
+    /// Stop the following searches of `solve_limited` after `budget` conflicts.
+    fn conf_budget(&self, budget: u64) -> PyResult<()> {
+        self.lock()?.solver.set_conflict_budget(budget);
+        Ok(())
+    }
+
+    /// Stop the following searches of `solve_limited` after `budget` propagations.
+    fn prop_budget(&self, budget: u64) -> PyResult<()> {
+        self.lock()?.solver.set_propagation_budget(budget);
+        Ok(())
+    }
+
+    /// Stop the search of `solve_limited(expect_interrupt=True)`, from another thread.
+    /// The following searches are stopped too, until `clear_interrupt` is called.
+    fn interrupt(&self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+
+    /// Allow the searches interrupted by `interrupt` again.
+    fn clear_interrupt(&self) {
+        self.stop.store(false, Ordering::Relaxed);
+    }
+
+    /// Result of the last search, `None` if it was interrupted.
+    fn get_status(&self) -> PyResult<Option<bool>> {
+        Ok(self.lock()?.status)
+    }
+
+    /// Model found by the last search, as the literals true in it, or `None` if it
+    /// found none.
+    fn get_model(&self) -> PyResult<Option<Vec<i64>>> {
+        let inner = self.lock()?;
+        if inner.status != Some(true) {
+            return Ok(None);
+        }
+        let model = inner.solver.get_model();
+        let lits = inner.vars.iter().filter_map(|&v| {
+            let value = model.get(v.idx() as usize).copied()?;
+            (value != lbool::UNDEF).then(|| inner.int(Lit::new(v, value == lbool::TRUE)))
+        });
+        Ok(Some(lits.collect()))
+    }
+
+    /// Assumptions of the last search that are enough for the clauses to have no model,
+    /// or `None` if it found a model.
+    fn get_core(&self) -> PyResult<Option<Vec<i64>>> {
+        let inner = self.lock()?;
+        if inner.status != Some(false) {
+            return Ok(None);
+        }
+        let core = inner.solver.unsat_core().iter();
+        Ok(Some(core.map(|&lit| inner.int(lit)).collect()))
+    }
+
+    /// Number of variables, the largest one occurring in the clauses and assumptions.
+    fn nof_vars(&self) -> PyResult<usize> {
+        Ok(self.lock()?.vars.len())
+    }
+
+    /// Number of clauses of the solver.
+    fn nof_clauses(&self) -> PyResult<u64> {
+        Ok(self.lock()?.solver.num_clauses())
+    }
+}
+
+/// The Python module `platsat`.
+#[pymodule(name = "platsat")]
+fn platsat_py(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<Solver>()
+}
+
+#[test]
+fn test_py_solver() {
+    Python::initialize();
+    Python::attach(|py| {
+        let s = Solver::new(Some(vec![vec![1, 2], vec![-1, 2]])).unwrap();
+        assert!(s.add_clause(vec![-2, 3]).unwrap());
+        assert!(!s.solve(py, vec![-3]).unwrap());
+        assert_eq!(s.get_core().unwrap(), Some(vec![-3]));
+        assert_eq!(s.get_model().unwrap(), None);
+        assert!(s.solve(py, vec![]).unwrap());
+        let model = s.get_model().unwrap().unwrap();
+        assert!(model.ends_with(&[2, 3]));
+        assert_eq!((s.nof_vars().unwrap(), s.nof_clauses().unwrap()), (3, 3));
+        assert!(s.add_clause(vec![0]).is_err());
+        // pigeonhole, interrupted by another thread
+        let (p, h) = (11, 10);
+        let var = |i: i64, j: i64| i * h + j + 1;
+        let s = Solver::new(Some(
+            (0..p)
+                .map(|i| (0..h).map(|j| var(i, j)).collect())
+                .collect(),
+        ));
+        let s = s.unwrap();
+        for j in 0..h {
+            for i in 0..p {
+                for k in i + 1..p {
+                    s.add_clause(vec![-var(i, j), -var(k, j)]).unwrap();
+                }
+            }
+        }
+        std::thread::scope(|scope| {
+            scope.spawn(|| {
+                std::thread::sleep(std::time::Duration::from_millis(50));
+                s.interrupt();
+            });
+            assert_eq!(s.solve_limited(py, vec![], true).unwrap(), None);
+        });
+        assert_eq!(s.get_status().unwrap(), None);
+        s.clear_interrupt();
+        s.conf_budget(10).unwrap();
+        assert_eq!(s.solve_limited(py, vec![], true).unwrap(), None);
+    });
+}
@@ -0,0 +1,485 @@
+//! Equality with uninterpreted functions, by congruence closure.
+//!
+//! Terms are hash-consed applications of function symbols, and each equality between
+//! two terms is a literal of the SAT solver. The true equalities are merged in an
+//! E-graph, which also merges the applications whose arguments become equal; a conflict
+//! is raised when a false equality relates two terms of the same class, and the
+//! equalities between terms of the same class are propagated.
+//!
+//! Conflicts and propagations are explained by the proof forest of Nieuwenhuis and
+//! Oliveras ("Fast congruence closure and extensions"): each merge adds an edge between
+//! the two terms it was asked for, labelled with its reason, so that the equalities
+//! that made two terms equal are found on the path between them.
+//!
+//! Every change of the E-graph is recorded on an undo stack, and undone when the SAT
+//! solver backtracks.
+
+use {
+    platsat::{core::ExplainTheoryArg, lbool, theory::TheoryArg, Lit, SolverInterface, Theory},
+    std::collections::HashMap,
+};
+
+/// A function symbol.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Fun(u32);
+
+/// A term, the application of a function symbol to terms.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Term(u32);
+
+#[derive(Clone, Copy, Debug)]
+enum Reason {
+    /// The true equality literal.
+    Lit(Lit),
+    /// The arguments of the two applications are equal.
+    Congruence(Term, Term),
+}
+
+/// A change of the E-graph.
+enum Undo {
+    /// `small` was merged into `big`, which had `big_uses` uses.
+    Merge {
+        small: Term,
+        big: Term,
+        big_uses: usize,
+    },
+    /// The proof forest edge of a term was changed from this one.
+    Proof(Term, Option<(Term, Reason)>),
+    /// A signature was added to the table.
+    Signature((Fun, Vec<Term>)),
+}
+
+/// The theory of equality with uninterpreted functions.
+///
+/// The terms and equalities must be created between the calls to the solver, at level
+/// 0, and the same theory must be given to each call.
+#[derive(Default)]
+pub struct Euf {
+    funs: Vec<String>,
+    apps: Vec<(Fun, Vec<Term>)>,
+    terms: HashMap<(Fun, Vec<Term>), Term>,
+    /// Equalities `(a, b, lit)` with `a < b`, and the equality of each variable.
+    atoms: Vec<(Term, Term, Lit)>,
+    atom_ids: HashMap<(Term, Term), Lit>,
+    atom_of_var: HashMap<u32, usize>,
+    /// Union-find, without path compression so that merges can be undone.
+    parent: Vec<Term>,
+    size: Vec<u32>,
+    /// Circular list of the terms of each class.
+    next: Vec<Term>,
+    /// Applications that have an argument in the class, for its representative.
+    uses: Vec<Vec<Term>>,
+    /// Application of each signature, the function and the representatives of the
+    /// arguments.
+    signatures: HashMap<(Fun, Vec<Term>), Term>,
+    proof: Vec<Option<(Term, Reason)>>,
+    /// The false equalities `(a, b, lit)`, where `lit` is the true negation.
+    diseqs: Vec<(Term, Term, Lit)>,
+    pending: Vec<(Term, Term, Reason)>,
+    assigned: Vec<Lit>,
+    undo: Vec<Undo>,
+    /// Lengths of `undo` and `diseqs` at the start of each level.
+    levels: Vec<(usize, usize)>,
+    /// Explanation clause of each propagated equality.
+    explanations: HashMap<Lit, Vec<Lit>>,
+    tmp: Vec<Lit>,
+}
+
+impl Euf {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A new function symbol.
+    pub fn new_fun(&mut self, name: &str) -> Fun {
+        self.funs.push(name.to_string());
+        Fun(self.funs.len() as u32 - 1)
+    }
+
+    /// The term `f(args)`.
+    pub fn app(&mut self, f: Fun, args: &[Term]) -> Term {
+        debug_assert!(self.levels.is_empty());
+        let key = (f, args.to_vec());
+        if let Some(&t) = self.terms.get(&key) {
+            return t;
+        }
+        let t = Term(self.apps.len() as u32);
+        self.apps.push(key.clone());
+        self.terms.insert(key, t);
+        self.parent.push(t);
+        self.size.push(1);
+        self.next.push(t);
+        self.uses.push(vec![]);
+        self.proof.push(None);
+        for &a in args {
+            let r = self.find(a);
+            self.uses[r.0 as usize].push(t);
+        }
+        self.add_signature(t);
+        self.process_pending();
+        t
+    }
+
+    /// The literal of the equality `a = b`, with a new variable of `solver` the first
+    /// time.
+    pub fn eq<S: SolverInterface>(&mut self, solver: &mut S, a: Term, b: Term) -> Lit {
+        let (a, b) = if a <= b { (a, b) } else { (b, a) };
+        if let Some(&lit) = self.atom_ids.get(&(a, b)) {
+            return lit;
+        }
+        let lit = Lit::new(solver.new_var_default(), true);
+        self.atom_ids.insert((a, b), lit);
+        self.atom_of_var.insert(lit.var().idx(), self.atoms.len());
+        self.atoms.push((a, b, lit));
+        lit
+    }
+
+    /// Are `a` and `b` in the same class under the current assignment?
+    pub fn are_equal(&self, a: Term, b: Term) -> bool {
+        self.find(a) == self.find(b)
+    }
+
+    fn find(&self, mut t: Term) -> Term {
+        while self.parent[t.0 as usize] != t {
+            t = self.parent[t.0 as usize];
+        }
+        t
+    }
+
+    fn signature(&self, t: Term) -> (Fun, Vec<Term>) {
+        let (f, args) = &self.apps[t.0 as usize];
+        (*f, args.iter().map(|&a| self.find(a)).collect())
+    }
+
+    /// Add the signature of `t` to the table, or merge `t` with the application that
+    /// has it.
+    fn add_signature(&mut self, t: Term) {
+        let sig = self.signature(t);
+        match self.signatures.get(&sig) {
+            Some(&u) => {
+                if self.find(u) != self.find(t) {
+                    self.pending.push((t, u, Reason::Congruence(t, u)));
+                }
+            }
+            None => {
+                self.signatures.insert(sig.clone(), t);
+                self.undo.push(Undo::Signature(sig));
+            }
+        }
+    }
+
+    fn set_proof(&mut self, t: Term, edge: Option<(Term, Reason)>) {
+        let old = std::mem::replace(&mut self.proof[t.0 as usize], edge);
+        self.undo.push(Undo::Proof(t, old));
+    }
+
+    fn process_pending(&mut self) {
+        while let Some((a, b, reason)) = self.pending.pop() {
+            let (ra, rb) = (self.find(a), self.find(b));
+            if ra == rb {
+                continue;
+            }
+            // make `a` the root of its proof tree, then add the edge `a -> b`
+            let mut prev = None;
+            let mut cur = a;
+            loop {
+                let old = self.proof[cur.0 as usize];
+                self.set_proof(cur, prev);
+                match old {
+                    Some((next, r)) => {
+                        prev = Some((cur, r));
+                        cur = next;
+                    }
+                    None => break,
+                }
+            }
+            self.set_proof(a, Some((b, reason)));
+            // merge the smaller class into the larger one
+            let (small, big) = if self.size[ra.0 as usize] < self.size[rb.0 as usize] {
+                (ra, rb)
+            } else {
+                (rb, ra)
+            };
+            let (s, b) = (small.0 as usize, big.0 as usize);
+            self.undo.push(Undo::Merge {
+                small,
+                big,
+                big_uses: self.uses[b].len(),
+            });
+            self.parent[s] = big;
+            self.size[b] += self.size[s];
+            self.next.swap(s, b);
+            let uses = std::mem::take(&mut self.uses[s]);
+            for &t in &uses {
+                self.add_signature(t);
+            }
+            self.uses[s] = uses;
+            let (uses_s, uses_b) = if s < b {
+                let (x, y) = self.uses.split_at_mut(b);
+                (&x[s], &mut y[0])
+            } else {
+                let (x, y) = self.uses.split_at_mut(s);
+                (&y[0], &mut x[b])
+            };
+            uses_b.extend_from_slice(uses_s);
+        }
+    }
+
+    /// Add the true literals whose equalities make `a` and `b` equal to `out`.
+    fn explain(&self, a: Term, b: Term, out: &mut Vec<Lit>) {
+        let mut todo = vec![(a, b)];
+        while let Some((a, b)) = todo.pop() {
+            if a == b {
+                continue;
+            }
+            // the nearest common ancestor in the proof forest
+            let mut ancestors = vec![a];
+            let mut t = a;
+            while let Some((next, _)) = self.proof[t.0 as usize] {
+                ancestors.push(next);
+                t = next;
+            }
+            let mut common = b;
+            while !ancestors.contains(&common) {
+                common = self.proof[common.0 as usize].unwrap().0;
+            }
+            for mut t in [a, b] {
+                while t != common {
+                    let (next, reason) = self.proof[t.0 as usize].unwrap();
+                    match reason {
+                        Reason::Lit(lit) => out.push(lit),
+                        Reason::Congruence(x, y) => {
+                            let args = self.apps[x.0 as usize].1.iter();
+                            todo.extend(
+                                args.zip(&self.apps[y.0 as usize].1).map(|(&x, &y)| (x, y)),
+                            );
+                        }
+                    }
+                    t = next;
+                }
+            }
+        }
+        out.sort_unstable();
+        out.dedup();
+    }
+
+    /// Add the newly assigned equalities to the E-graph, then raise a conflict or
+    /// propagate the equalities it implies.
+    fn check(&mut self, acts: &mut TheoryArg) {
+        for lit in std::mem::take(&mut self.assigned) {
+            let Some(&i) = self.atom_of_var.get(&lit.var().idx()) else {
+                continue;
+            };
+            let (a, b, atom) = self.atoms[i];
+            if lit == atom {
+                self.pending.push((a, b, Reason::Lit(lit)));
+            } else {
+                self.diseqs.push((a, b, lit));
+            }
+        }
+        self.process_pending();
+        for i in 0..self.diseqs.len() {
+            let (a, b, lit) = self.diseqs[i];
+            if self.are_equal(a, b) {
+                let mut c = vec![];
+                self.explain(a, b, &mut c);
+                for x in &mut c {
+                    *x = !*x;
+                }
+                c.push(!lit);
+                acts.raise_conflict(&c, false);
+                return;
+            }
+        }
+        for i in 0..self.atoms.len() {
+            let (a, b, lit) = self.atoms[i];
+            if acts.value_lit(lit) == lbool::UNDEF && self.are_equal(a, b) {
+                let mut c = vec![];
+                self.explain(a, b, &mut c);
+                for x in &mut c {
+                    *x = !*x;
+                }
+                c.insert(0, lit);
+                self.explanations.insert(lit, c);
+                if !acts.propagate(lit) {
+                    return;
+                }
+            }
+        }
+    }
+}
+
+impl Theory for Euf {
+    fn final_check(&mut self, acts: &mut TheoryArg) {
+        self.check(acts)
+    }
+
+    fn partial_check(&mut self, acts: &mut TheoryArg) {
+        self.check(acts)
+    }
+
+    fn create_level(&mut self) {
+        self.levels.push((self.undo.len(), self.diseqs.len()));
+    }
+
+    fn pop_levels(&mut self, n: usize) {
+        if n == 0 {
+            return;
+        }
+        let (undo_len, diseqs_len) = self.levels[self.levels.len() - n];
+        self.levels.truncate(self.levels.len() - n);
+        self.diseqs.truncate(diseqs_len);
+        self.pending.clear();
+        self.assigned.clear();
+        while self.undo.len() > undo_len {
+            match self.undo.pop().unwrap() {
+                Undo::Merge {
+                    small,
+                    big,
+                    big_uses,
+                } => {
+                    let (s, b) = (small.0 as usize, big.0 as usize);
+                    self.parent[s] = small;
+                    self.size[b] -= self.size[s];
+                    self.next.swap(s, b);
+                    self.uses[b].truncate(big_uses);
+                }
+                Undo::Proof(t, edge) => self.proof[t.0 as usize] = edge,
+                Undo::Signature(sig) => {
+                    self.signatures.remove(&sig);
+                }
+            }
+        }
+    }
+
+    fn n_levels(&self) -> usize {
+        self.levels.len()
+    }
+
+    fn on_assign(&mut self, lit: Lit, _is_decision: bool) {
+        self.assigned.push(lit);
+    }
+
+    fn explain_propagation_clause(&mut self, p: Lit, _: &mut ExplainTheoryArg) -> &[Lit] {
+        self.tmp.clear();
+        self.tmp.extend_from_slice(&self.explanations[&p]);
+        &self.tmp
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use platsat::BasicSolver;
+
+    #[test]
+    fn test_congruence() {
+        // `a = b`, `b = c` and `f(a) != f(c)` is unsatisfiable
+        let mut solver = BasicSolver::default();
+        let mut euf = Euf::new();
+        let f = euf.new_fun("f");
+        let [a, b, c] = ["a", "b", "c"].map(|name| {
+            let x = euf.new_fun(name);
+            euf.app(x, &[])
+        });
+        let (fa, fc) = (euf.app(f, &[a]), euf.app(f, &[c]));
+        let ab = euf.eq(&mut solver, a, b);
+        let bc = euf.eq(&mut solver, b, c);
+        let fafc = euf.eq(&mut solver, fa, fc);
+        assert_eq!(solver.solve_limited_th(&mut euf, &[ab, bc]), lbool::TRUE);
+        assert_eq!(
+            solver.solve_limited_th(&mut euf, &[ab, bc, !fafc]),
+            lbool::FALSE
+        );
+        assert_eq!(solver.solve_limited_th(&mut euf, &[ab, !fafc]), lbool::TRUE);
+        // `f(f(f(a))) = a` and `f(f(f(f(f(a))))) = a` imply `f(a) = a`
+        let mut t = vec![a];
+        for _ in 0..5 {
+            let x = euf.app(f, &[*t.last().unwrap()]);
+            t.push(x);
+        }
+        let f3 = euf.eq(&mut solver, t[3], a);
+        let f5 = euf.eq(&mut solver, t[5], a);
+        let f1 = euf.eq(&mut solver, t[1], a);
+        assert_eq!(solver.solve_limited_th(&mut euf, &[f3, f5]), lbool::TRUE);
+        assert_eq!(
+            solver.solve_limited_th(&mut euf, &[f3, f5, !f1]),
+            lbool::FALSE
+        );
+        assert!(solver.unsat_core().len() == 3);
+        assert_eq!(solver.solve_limited_th(&mut euf, &[f3, !f1]), lbool::TRUE);
+    }
+
+    #[test]
+    fn test_random() {
+        // compare with the Ackermann reduction: an equality variable for each pair of
+        // terms, with the transitivity and congruence clauses
+        let mut seed = 11u64;
+        let mut rand = |n: u64| {
+            seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1);
+            (seed >> 33) % n
+        };
+        for _ in 0..200 {
+            let mut solver = BasicSolver::default();
+            let mut euf = Euf::new();
+            let funs: Vec<(Fun, usize)> = [("a", 0), ("b", 0), ("c", 0), ("f", 1), ("g", 2)]
+                .iter()
+                .map(|&(name, arity)| (euf.new_fun(name), arity))
+                .collect();
+            let mut terms = vec![];
+            while terms.len() < 8 {
+                let (f, arity) = funs[rand(if terms.is_empty() { 3 } else { 5 }) as usize];
+                let args: Vec<Term> = (0..arity)
+                    .map(|_| terms[rand(terms.len() as u64) as usize])
+                    .collect();
+                let t = euf.app(f, &args);
+                if !terms.contains(&t) {
+                    terms.push(t);
+                }
+            }
+            let n = terms.len();
+            let mut reference = BasicSolver::default();
+            let vars: Vec<Vec<Lit>> = (0..n)
+                .map(|_| {
+                    (0..n)
+                        .map(|_| Lit::new(reference.new_var_default(), true))
+                        .collect()
+                })
+                .collect();
+            let e = |i: usize, j: usize| vars[i.min(j)][i.max(j)];
+            let idx = |t: Term| terms.iter().position(|&u| u == t).unwrap();
+            for i in 0..n {
+                reference.add_clause_reuse(&mut vec![e(i, i)]);
+                for j in 0..n {
+                    for k in 0..n {
+                        reference.add_clause_reuse(&mut vec![!e(i, j), !e(j, k), e(i, k)]);
+                    }
+                    let ((f, a), (g, b)) = (euf.apps[i].clone(), euf.apps[j].clone());
+                    if i < j && f == g {
+                        let mut c: Vec<Lit> = a
+                            .iter()
+                            .zip(&b)
+                            .map(|(&x, &y)| !e(idx(x), idx(y)))
+                            .collect();
+                        c.push(e(i, j));
+                        reference.add_clause_reuse(&mut c);
+                    }
+                }
+            }
+            for _ in 0..rand(12) {
+                let mut c = vec![];
+                let mut c_ref = vec![];
+                for _ in 0..1 + rand(3) {
+                    let (i, j) = (rand(n as u64) as usize, rand(n as u64) as usize);
+                    let sign = rand(3) != 0;
+                    c.push(euf.eq(&mut solver, terms[i], terms[j]) ^ !sign);
+                    c_ref.push(e(i, j) ^ !sign);
+                }
+                solver.add_clause_reuse(&mut c);
+                reference.add_clause_reuse(&mut c_ref);
+            }
+            let expected = reference.solve_limited(&[]);
+            assert_eq!(solver.solve_limited_th(&mut euf, &[]), expected);
+        }
+    }
+}
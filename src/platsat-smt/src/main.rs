@@ -0,0 +1,35 @@
+//! An SMT solver for the QF_UF subset of SMT-LIB 2.
+//!
+//! It is an example of a theory for the SAT solver: the equalities between
+//! terms are checked by congruence closure, in [`euf`], which raises conflicts and
+//! propagates the equalities that are implied by the others.
+//!
+//! Usage: `platsat-smt [FILE]...`, reading the standard input without files.
+
+mod euf;
+mod smtlib;
+
+use std::io::{self, Read};
+
+fn main() {
+    let files: Vec<String> = std::env::args().skip(1).collect();
+    let inputs = if files.is_empty() {
+        let mut s = String::new();
+        io::stdin()
+            .read_to_string(&mut s)
+            .expect("cannot read stdin");
+        vec![s]
+    } else {
+        let read = |f: &String| {
+            std::fs::read_to_string(f).unwrap_or_else(|e| panic!("cannot read {}: {}", f, e))
+        };
+        files.iter().map(read).collect()
+    };
+    for input in inputs {
+        let mut ctx = smtlib::Context::new(io::stdout());
+        if let Err(e) = ctx.run(&input) {
+            println!("(error \"{}\")", e.to_string().replace('"', "\"\""));
+            std::process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,562 @@
+//! A front end for the QF_UF subset of SMT-LIB 2.
+//!
+//! The Boolean structure of the assertions is encoded into clauses with
+//! [`Encoder`], and the equalities between terms become the atoms of the [`Euf`]
+//! theory. Predicates and Boolean terms in argument position are terms that are
+//! equal to one of two distinguished terms, `@true` and `@false`.
+//!
+//! The supported commands are `declare-sort` (of arity 0), `declare-fun`,
+//! `declare-const`, `define-fun` (without parameters), `assert`, `check-sat`, `echo`
+//! and `exit`; `set-logic`, `set-info` and `set-option` are ignored, and the other
+//! commands print `unsupported`.
+
+use {
+    crate::euf::{Euf, Fun, Term},
+    platsat::{formula::Encoder, lbool, BasicSolver, Formula, Lit, SolverInterface},
+    std::{
+        collections::{HashMap, HashSet},
+        fmt,
+        io::Write,
+    },
+};
+
+/// An error in a script.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Error(pub String);
+
+impl fmt::Display for Error {
+    fn fmt(&self, out: &mut fmt::Formatter) -> fmt::Result {
+        write!(out, "{}", self.0)
+    }
+}
+
+impl std::error::Error for Error {}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+macro_rules! bail {
+    ($( $x:expr ),*) => {
+        return Err(Error(format!($( $x ),*)))
+    };
+}
+
+/// An S-expression.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Sexp {
+    Atom(String),
+    /// A string literal, without its quotes.
+    Str(String),
+    List(Vec<Sexp>),
+}
+
+/// Parse all the S-expressions of `input`.
+pub fn parse(input: &str) -> Result<Vec<Sexp>> {
+    let mut stack: Vec<Vec<Sexp>> = vec![vec![]];
+    let mut chars = input.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            ';' => {
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        break;
+                    }
+                }
+            }
+            '(' => stack.push(vec![]),
+            ')' => {
+                if stack.len() == 1 {
+                    bail!("unexpected `)`");
+                }
+                let list = stack.pop().unwrap();
+                stack.last_mut().unwrap().push(Sexp::List(list));
+            }
+            '"' => {
+                let mut s = String::new();
+                loop {
+                    match chars.next() {
+                        // `""` is an escaped quote
+                        Some('"') if chars.peek() == Some(&'"') => {
+                            chars.next();
+                            s.push('"');
+                        }
+                        Some('"') => break,
+                        Some(c) => s.push(c),
+                        None => bail!("unterminated string"),
+                    }
+                }
+                stack.last_mut().unwrap().push(Sexp::Str(s));
+            }
+            '|' => {
+                let mut s = String::new();
+                loop {
+                    match chars.next() {
+                        Some('|') => break,
+                        Some(c) => s.push(c),
+                        None => bail!("unterminated quoted symbol"),
+                    }
+                }
+                stack.last_mut().unwrap().push(Sexp::Atom(s));
+            }
+            c if c.is_whitespace() => {}
+            c => {
+                let mut s = c.to_string();
+                while let Some(&c) = chars.peek() {
+                    if c.is_whitespace() || "();\"|".contains(c) {
+                        break;
+                    }
+                    s.push(c);
+                    chars.next();
+                }
+                stack.last_mut().unwrap().push(Sexp::Atom(s));
+            }
+        }
+    }
+    if stack.len() > 1 {
+        bail!("missing `)`");
+    }
+    Ok(stack.pop().unwrap())
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Sort {
+    Bool,
+    Uninterpreted(String),
+}
+
+impl fmt::Display for Sort {
+    fn fmt(&self, out: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Sort::Bool => write!(out, "Bool"),
+            Sort::Uninterpreted(s) => write!(out, "{}", s),
+        }
+    }
+}
+
+/// The meaning of an expression.
+#[derive(Debug, Clone)]
+enum Value {
+    Formula(Formula),
+    Term(Term, Sort),
+}
+
+/// The state of a script: the declarations, and the solver with the assertions.
+pub struct Context<W> {
+    solver: BasicSolver,
+    euf: Euf,
+    out: W,
+    sorts: HashSet<String>,
+    funs: HashMap<String, (Fun, Vec<Sort>, Sort)>,
+    defs: HashMap<String, Value>,
+    /// The `let` bindings in scope, the last ones first.
+    env: Vec<(String, Value)>,
+    top: Term,
+    bot: Term,
+    /// The Boolean terms that are known to be `@true` or `@false`.
+    bool_terms: HashSet<Term>,
+    fresh: u32,
+}
+
+impl<W: Write> Context<W> {
+    /// A new context, printing the responses to `out`.
+    pub fn new(out: W) -> Self {
+        let mut solver = BasicSolver::default();
+        let mut euf = Euf::new();
+        let (t, f) = (euf.new_fun("@true"), euf.new_fun("@false"));
+        let (top, bot) = (euf.app(t, &[]), euf.app(f, &[]));
+        let lit = euf.eq(&mut solver, top, bot);
+        solver.add_clause_reuse(&mut vec![!lit]);
+        Context {
+            solver,
+            euf,
+            out,
+            sorts: HashSet::new(),
+            funs: HashMap::new(),
+            defs: HashMap::new(),
+            env: vec![],
+            top,
+            bot,
+            bool_terms: HashSet::new(),
+            fresh: 0,
+        }
+    }
+
+    /// Run the commands of `input`, until the end or `exit`.
+    pub fn run(&mut self, input: &str) -> Result<()> {
+        for cmd in parse(input)? {
+            if !self.command(&cmd)? {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// Run `cmd`, and return `false` if it is `exit`.
+    fn command(&mut self, cmd: &Sexp) -> Result<bool> {
+        let (name, args) = match cmd {
+            Sexp::List(l) => match l.split_first() {
+                Some((Sexp::Atom(name), args)) => (name.as_str(), args),
+                _ => bail!("invalid command"),
+            },
+            _ => bail!("invalid command"),
+        };
+        match (name, args) {
+            ("set-logic" | "set-info" | "set-option", _) => {}
+            ("exit", []) => return Ok(false),
+            ("echo", [Sexp::Str(s)]) => self.print(&format!("\"{}\"", s.replace('"', "\"\"")))?,
+            ("declare-sort", [Sexp::Atom(s), Sexp::Atom(n)]) => {
+                if n != "0" {
+                    bail!("sort `{}` has arity {}, only 0 is supported", s, n);
+                }
+                if !self.sorts.insert(s.clone()) {
+                    bail!("sort `{}` is already declared", s);
+                }
+            }
+            ("declare-const", [Sexp::Atom(f), sort]) => self.declare(f, &[], sort)?,
+            ("declare-fun", [Sexp::Atom(f), Sexp::List(args), sort]) => {
+                self.declare(f, args, sort)?
+            }
+            ("define-fun", [Sexp::Atom(f), Sexp::List(params), sort, body]) => {
+                if !params.is_empty() {
+                    bail!("`{}` has parameters, which are not supported", f);
+                }
+                let sort = self.sort(sort)?;
+                let v = self.value(body)?;
+                if self.sort_of(&v) != sort {
+                    bail!(
+                        "`{}` is declared of sort {}, but defined of sort {}",
+                        f,
+                        sort,
+                        self.sort_of(&v)
+                    );
+                }
+                self.check_fresh(f)?;
+                self.defs.insert(f.clone(), v);
+            }
+            ("assert", [e]) => {
+                let f = self.formula(e)?;
+                Encoder::plaisted_greenbaum().assert(&mut self.solver, &f);
+            }
+            ("check-sat", []) => {
+                let res = self.solver.solve_limited_th(&mut self.euf, &[]);
+                let res = if res == lbool::TRUE {
+                    "sat"
+                } else if res == lbool::FALSE {
+                    "unsat"
+                } else {
+                    "unknown"
+                };
+                self.print(res)?
+            }
+            _ => self.print("unsupported")?,
+        }
+        Ok(true)
+    }
+
+    fn print(&mut self, s: &str) -> Result<()> {
+        writeln!(self.out, "{}", s).map_err(|e| Error(e.to_string()))
+    }
+
+    fn check_fresh(&self, f: &str) -> Result<()> {
+        if self.funs.contains_key(f) || self.defs.contains_key(f) || f == "true" || f == "false" {
+            bail!("`{}` is already declared", f);
+        }
+        Ok(())
+    }
+
+    fn declare(&mut self, f: &str, args: &[Sexp], sort: &Sexp) -> Result<()> {
+        self.check_fresh(f)?;
+        let args = args.iter().map(|s| self.sort(s)).collect::<Result<_>>()?;
+        let sort = self.sort(sort)?;
+        let fun = self.euf.new_fun(f);
+        self.funs.insert(f.to_string(), (fun, args, sort));
+        Ok(())
+    }
+
+    fn sort(&self, e: &Sexp) -> Result<Sort> {
+        match e {
+            Sexp::Atom(s) if s == "Bool" => Ok(Sort::Bool),
+            Sexp::Atom(s) if self.sorts.contains(s) => Ok(Sort::Uninterpreted(s.clone())),
+            _ => bail!("unknown sort `{:?}`", e),
+        }
+    }
+
+    fn sort_of(&self, v: &Value) -> Sort {
+        match v {
+            Value::Formula(_) => Sort::Bool,
+            Value::Term(_, sort) => sort.clone(),
+        }
+    }
+
+    /// The formula of the Boolean expression `e`.
+    fn formula(&mut self, e: &Sexp) -> Result<Formula> {
+        let v = self.value(e)?;
+        self.formula_of(v)
+    }
+
+    fn formula_of(&mut self, v: Value) -> Result<Formula> {
+        match v {
+            Value::Formula(f) => Ok(f),
+            Value::Term(t, Sort::Bool) => Ok(self.euf.eq(&mut self.solver, t, self.top).into()),
+            Value::Term(_, sort) => bail!("expected a Bool, got a term of sort {}", sort),
+        }
+    }
+
+    /// The term of `v`, with a new constant for a formula.
+    fn term_of(&mut self, v: Value) -> Term {
+        match v {
+            Value::Term(t, _) => t,
+            Value::Formula(f) => {
+                self.fresh += 1;
+                let fun = self.euf.new_fun(&format!("@b{}", self.fresh));
+                let t = self.euf.app(fun, &[]);
+                let lit = self.bool_term(t);
+                Encoder::tseitin().assert(&mut self.solver, &Formula::iff(lit, f));
+                t
+            }
+        }
+    }
+
+    /// Constrain the Boolean term `t` to be `@true` or `@false`, and return `t = @true`.
+    fn bool_term(&mut self, t: Term) -> Lit {
+        let lit = self.euf.eq(&mut self.solver, t, self.top);
+        if self.bool_terms.insert(t) {
+            let lit_bot = self.euf.eq(&mut self.solver, t, self.bot);
+            self.solver.add_clause_reuse(&mut vec![lit, lit_bot]);
+        }
+        lit
+    }
+
+    /// `a = b`, between two values of the same sort.
+    fn equal(&mut self, a: &Value, b: &Value) -> Result<Formula> {
+        let (sa, sb) = (self.sort_of(a), self.sort_of(b));
+        if sa != sb {
+            bail!("cannot compare a {} with a {}", sa, sb);
+        }
+        Ok(match (a, b) {
+            (Value::Term(a, _), Value::Term(b, _)) if sa != Sort::Bool => {
+                self.euf.eq(&mut self.solver, *a, *b).into()
+            }
+            _ => {
+                let a = self.formula_of(a.clone())?;
+                let b = self.formula_of(b.clone())?;
+                Formula::iff(a, b)
+            }
+        })
+    }
+
+    fn lookup(&self, s: &str) -> Option<Value> {
+        let local = self.env.iter().rev().find(|(name, _)| name == s);
+        local.map(|(_, v)| v).or_else(|| self.defs.get(s)).cloned()
+    }
+
+    fn value(&mut self, e: &Sexp) -> Result<Value> {
+        let (head, args) = match e {
+            Sexp::Str(_) => bail!("unexpected string literal"),
+            Sexp::Atom(s) => match s.as_str() {
+                "true" => return Ok(Value::Formula(true.into())),
+                "false" => return Ok(Value::Formula(false.into())),
+                _ => match self.lookup(s) {
+                    Some(v) => return Ok(v),
+                    None => (s.as_str(), &[][..]),
+                },
+            },
+            Sexp::List(l) => match l.split_first() {
+                Some((Sexp::Atom(head), args)) => (head.as_str(), args),
+                _ => bail!("invalid expression `{:?}`", e),
+            },
+        };
+        match (head, args) {
+            ("let", [Sexp::List(bindings), body]) => {
+                let mut values = vec![];
+                for b in bindings {
+                    match b {
+                        Sexp::List(b) => match &b[..] {
+                            [Sexp::Atom(name), e] => {
+                                // name the formula, to share it between its occurrences
+                                let v = match self.value(e)? {
+                                    Value::Formula(f @ Formula::Lit(_)) => Value::Formula(f),
+                                    Value::Formula(f) => Value::Formula(
+                                        Encoder::tseitin().encode(&mut self.solver, &f).into(),
+                                    ),
+                                    v => v,
+                                };
+                                values.push((name.clone(), v));
+                            }
+                            _ => bail!("invalid binding `{:?}`", b),
+                        },
+                        _ => bail!("invalid binding `{:?}`", b),
+                    }
+                }
+                let len = self.env.len();
+                self.env.extend(values);
+                let v = self.value(body);
+                self.env.truncate(len);
+                return v;
+            }
+            ("!", [e, ..]) => return self.value(e),
+            _ => {}
+        }
+        let args = args
+            .iter()
+            .map(|e| self.value(e))
+            .collect::<Result<Vec<_>>>()?;
+        let formulas = |ctx: &mut Self, args: Vec<Value>| {
+            args.into_iter()
+                .map(|v| ctx.formula_of(v))
+                .collect::<Result<Vec<_>>>()
+        };
+        let f = match (head, &args[..]) {
+            ("not", [_]) => !formulas(self, args)?.pop().unwrap(),
+            ("and", _) => Formula::And(formulas(self, args)?),
+            ("or", _) => Formula::Or(formulas(self, args)?),
+            ("xor", [_, _, ..]) => {
+                let mut fs = formulas(self, args)?.into_iter();
+                let first = fs.next().unwrap();
+                fs.fold(first, Formula::xor)
+            }
+            ("=>", [_, _, ..]) => {
+                // right associative
+                let mut fs = formulas(self, args)?;
+                let last = fs.pop().unwrap();
+                fs.into_iter()
+                    .rev()
+                    .fold(last, |b, a| Formula::implies(a, b))
+            }
+            ("=", [_, _, ..]) => {
+                let eqs = args.windows(2).map(|w| self.equal(&w[0], &w[1]));
+                Formula::And(eqs.collect::<Result<_>>()?)
+            }
+            ("distinct", [_, _, ..]) => {
+                let mut fs = vec![];
+                for (i, a) in args.iter().enumerate() {
+                    for b in &args[i + 1..] {
+                        fs.push(!self.equal(a, b)?);
+                    }
+                }
+                Formula::And(fs)
+            }
+            ("ite", [c, a, b]) => {
+                let c = self.formula_of(c.clone())?;
+                let sort = self.sort_of(a);
+                if sort == Sort::Bool {
+                    let a = self.formula_of(a.clone())?;
+                    let b = self.formula_of(b.clone())?;
+                    Formula::ite(c, a, b)
+                } else {
+                    // a new constant equal to the chosen branch
+                    self.fresh += 1;
+                    let fun = self.euf.new_fun(&format!("@ite{}", self.fresh));
+                    let t = Value::Term(self.euf.app(fun, &[]), sort);
+                    let def = Formula::ite(c, self.equal(&t, a)?, self.equal(&t, b)?);
+                    Encoder::plaisted_greenbaum().assert(&mut self.solver, &def);
+                    return Ok(t);
+                }
+            }
+            (f, _) => {
+                let (fun, sorts, sort) = match self.funs.get(f) {
+                    Some(d) => d.clone(),
+                    None => bail!("unknown symbol `{}` with {} arguments", f, args.len()),
+                };
+                if args.len() != sorts.len() {
+                    bail!(
+                        "`{}` expects {} arguments, got {}",
+                        f,
+                        sorts.len(),
+                        args.len()
+                    );
+                }
+                let mut terms = vec![];
+                for (v, s) in args.into_iter().zip(&sorts) {
+                    if self.sort_of(&v) != *s {
+                        bail!(
+                            "`{}` expects an argument of sort {}, got {}",
+                            f,
+                            s,
+                            self.sort_of(&v)
+                        );
+                    }
+                    terms.push(self.term_of(v));
+                }
+                let t = self.euf.app(fun, &terms);
+                if sort == Sort::Bool {
+                    self.bool_term(t);
+                }
+                return Ok(Value::Term(t, sort));
+            }
+        };
+        Ok(Value::Formula(f))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn run(script: &str) -> String {
+        let mut ctx = Context::new(vec![]);
+        ctx.run(script).unwrap();
+        String::from_utf8(ctx.out).unwrap()
+    }
+
+    #[test]
+    fn test_parse() {
+        let e = parse("(a |b c| (\"d\"\"e\" ; comment\n f))").unwrap();
+        let atom = |s: &str| Sexp::Atom(s.to_string());
+        let expected = Sexp::List(vec![
+            atom("a"),
+            atom("b c"),
+            Sexp::List(vec![Sexp::Str("d\"e".to_string()), atom("f")]),
+        ]);
+        assert_eq!(e, vec![expected]);
+        assert!(parse("(a").is_err());
+        assert!(parse("a)").is_err());
+    }
+
+    #[test]
+    fn test_script() {
+        let script = "
+            (set-logic QF_UF)
+            (declare-sort U 0)
+            (declare-fun f (U) U)
+            (declare-const a U)
+            (assert (= (f (f (f a))) a))
+            (assert (= (f (f (f (f (f a))))) a))
+            (check-sat)
+            (assert (distinct (f a) a))
+            (check-sat)
+        ";
+        assert_eq!(run(script), "sat\nunsat\n");
+        let script = "
+            (declare-sort U 0)
+            (declare-fun p (U Bool) Bool)
+            (declare-const b U)
+            (declare-const c U)
+            (declare-const x Bool)
+            (define-fun d () U (ite x b c))
+            (assert (let ((q (p d (= b c)))) (and q (not (p b false)) (not (p c false)))))
+            (check-sat)
+            (echo \"b = c\")
+            (assert (not (= b c)))
+            (check-sat)
+            (get-model)
+            (exit)
+            (check-sat)
+        ";
+        assert_eq!(run(script), "sat\n\"b = c\"\nunsat\nunsupported\n");
+        let script = "
+            (declare-sort U 0)
+            (declare-fun g (Bool) U)
+            (declare-const x Bool)
+            (declare-const y Bool)
+            (declare-const z Bool)
+            (assert (distinct (g x) (g y) (g z)))
+            (check-sat)
+        ";
+        assert_eq!(run(script), "unsat\n");
+        let mut ctx = Context::new(vec![]);
+        assert!(ctx.run("(declare-const a U)").is_err());
+        assert!(ctx
+            .run("(declare-const a Bool) (assert (not (a a)))")
+            .is_err());
+    }
+}
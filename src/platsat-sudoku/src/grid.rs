@@ -33,10 +33,7 @@ impl Cell {
     /// Is the cell empty?
     #[inline(always)]
     pub fn empty(&self) -> bool {
-        match self {
-            Cell::Empty => true,
-            _ => false,
-        }
+        matches!(self, Cell::Empty)
     }
 
     #[inline(always)]
@@ -101,7 +98,7 @@ impl Grid {
         &self,
     ) -> impl Iterator<Item = ((Position, &Cell), (Position, &Cell))> {
         IterSq {
-            grid: &self,
+            grid: self,
             line: 0,
             col: 0,
             i: 0,
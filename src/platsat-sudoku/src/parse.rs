@@ -15,7 +15,7 @@ pub fn parse<R: Read>(r: R) -> Result<Vec<Grid>> {
     for line in r.lines() {
         let line = line?;
         let line = line.trim().as_bytes();
-        if line == &[] {
+        if line.is_empty() {
             continue; // blank line
         } else if line.len() < 81 {
             let msg = format!("cannot parse {:?}, too short", line);
@@ -28,8 +28,8 @@ pub fn parse<R: Read>(r: R) -> Result<Vec<Grid>> {
             let cell = match *c {
                 b'0' | b'.' => Cell::Empty,
                 b'1'..=b'9' => {
-                    let n = *c as u8 - b'0' as u8;
-                    assert!(n >= 1 && n <= 9);
+                    let n = *c - b'0';
+                    assert!((1..=9).contains(&n));
                     Cell::Full(n)
                 }
                 _ => {
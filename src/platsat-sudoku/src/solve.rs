@@ -18,6 +18,7 @@ pub struct Solver {
     s0: Solver0,
 }
 
+#[allow(clippy::upper_case_acronyms)]
 type SAT = sat::Solver<sat::StatsCallbacks>;
 
 /// Given position = given number
@@ -50,12 +51,14 @@ impl Solver {
     /// New solver.
     pub fn new(grid: Grid) -> Self {
         let mut s0 = Solver0::new(grid.clone());
-        let mut opts = sat::SolverOpts::default();
-        opts.luby_restart = false;
-        opts.restart_first = 1000;
-        opts.restart_inc = 15.;
-        //opts.ccmin_mode = 1; // NOTE: this can trigger various bugs
-        opts.min_learnts_lim = 1_200; // min number of learnt clauses
+        let opts = sat::SolverOpts {
+            restart_strategy: sat::RestartStrategy::Geometric,
+            restart_first: 1000,
+            restart_inc: 15.,
+            //ccmin_mode: 1, // NOTE: this can trigger various bugs
+            min_learnts_lim: 1_200, // min number of learnt clauses
+            ..Default::default()
+        };
         let mut sat = SAT::new_with(opts, Default::default());
         s0.create_lits(&mut sat); // be sure to create literals to decide
         Solver {
@@ -314,7 +317,7 @@ impl Solver0 {
             self.solution = Some((*self.grid).clone());
         } else {
             assert!(self.status.confl.len() >= 2);
-            arg.raise_conflict(&mut self.status.confl, true);
+            arg.raise_conflict(&self.status.confl, true);
         }
     }
 }
@@ -323,7 +326,7 @@ impl sat::Theory for Solver0 {
     fn final_check(&mut self, arg: &mut theory::TheoryArg) {
         debug!("final-check");
         assert!(self.ok());
-        let trail = &arg.model()[..];
+        let trail = arg.model();
         self.update_grid(trail);
         self.check_full();
 
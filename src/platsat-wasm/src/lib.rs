@@ -0,0 +1,229 @@
+//! # WASM
+//!
+//! Bindings of platsat for JavaScript, through `wasm-bindgen`, so the solver can run in
+//! browsers. Literals are non-zero integers, as in DIMACS: `v` or `-v` for the variable
+//! `v >= 1`, whose variables are created when they first occur.
+//!
+//! Built with `wasm-pack build src/platsat-wasm --target web`, it is used as:
+//!
+//! ```js
+//! import init, { Solver } from "./pkg/platsat_wasm.js";
+//! await init();
+//! const solver = new Solver();
+//! solver.add_clause(Int32Array.of(1, 2));
+//! solver.add_clause(Int32Array.of(-1, 2));
+//! solver.assume(-2);
+//! console.log(solver.solve()); // 20, unsatisfiable under `-2`
+//! console.log(solver.failed(-2)); // true
+//! // the search runs by slices of 1000 conflicts, the page stays responsive in between
+//! let res;
+//! while ((res = solver.solve_step(1000)) === 0) {
+//!   await new Promise((resolve) => setTimeout(resolve));
+//! }
+//! console.log(res, solver.value(2)); // 10 2
+//! ```
+use platsat::{lbool, BasicSolver, Lit, SolverInterface, Var};
+use wasm_bindgen::prelude::*;
+
+/// Result of a search that found a model, as in IPASIR.
+const SAT: i32 = 10;
+/// Result of a search that proved that there is no model.
+const UNSAT: i32 = 20;
+/// Result of a search that was interrupted.
+const UNKNOWN: i32 = 0;
+
+/// A SAT solver.
+#[wasm_bindgen]
+pub struct Solver {
+    solver: BasicSolver,
+    /// The variable of each integer, minus 1.
+    vars: Vec<Var>,
+    /// Literals assumed by the next search.
+    assumptions: Vec<Lit>,
+    /// Is a search started by [`Solver::solve_step`] not finished?
+    pending: bool,
+}
+
+impl Default for Solver {
+    fn default() -> Self {
+        Solver::new()
+    }
+}
+
+impl Solver {
+    fn lit(&mut self, lit: i32) -> Lit {
+        assert!(lit != 0 && lit != i32::MIN, "invalid literal {}", lit);
+        let v = lit.unsigned_abs() as usize;
+        while self.vars.len() < v {
+            self.vars.push(self.solver.new_var_default());
+        }
+        Lit::new(self.vars[v - 1], lit > 0)
+    }
+
+    fn result(res: lbool) -> i32 {
+        if res == lbool::TRUE {
+            SAT
+        } else if res == lbool::FALSE {
+            UNSAT
+        } else {
+            UNKNOWN
+        }
+    }
+}
+
+#[wasm_bindgen]
+impl Solver {
+    /// A solver without clauses.
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Solver {
+        Solver {
+            solver: BasicSolver::default(),
+            vars: vec![],
+            assumptions: vec![],
+            pending: false,
+        }
+    }
+
+    /// Number of variables, the largest one occurring in the clauses and assumptions.
+    pub fn num_vars(&self) -> u32 {
+        self.vars.len() as u32
+    }
+
+    /// Add the clause `lits`, returns `false` if the solver is now known to be
+    /// unsatisfiable. It cancels a search started by [`solve_step`](Self::solve_step).
+    pub fn add_clause(&mut self, lits: &[i32]) -> bool {
+        if self.pending {
+            self.interrupt();
+        }
+        let mut c: Vec<Lit> = lits.iter().map(|&lit| self.lit(lit)).collect();
+        self.solver.add_clause_reuse(&mut c)
+    }
+
+    /// Assume `lit` during the next search only. It cancels a search started by
+    /// [`solve_step`](Self::solve_step).
+    pub fn assume(&mut self, lit: i32) {
+        if self.pending {
+            self.interrupt();
+        }
+        let lit = self.lit(lit);
+        self.assumptions.push(lit);
+    }
+
+    /// Search for a model of the clauses satisfying the assumptions, which are then
+    /// cleared; returns `10` if there is one, `20` otherwise.
+    pub fn solve(&mut self) -> i32 {
+        self.pending = false;
+        self.solver.budget_off();
+        let res = self.solver.solve_limited(&self.assumptions);
+        self.assumptions.clear();
+        Self::result(res)
+    }
+
+    /// Like [`solve`](Self::solve), but return `0` after `conflicts` conflicts if the
+    /// search is not over yet. Calling it again resumes the search, with the same
+    /// assumptions and the clauses learnt so far, so it can be run from an event loop
+    /// without blocking it.
+    pub fn solve_step(&mut self, conflicts: u32) -> i32 {
+        self.pending = true;
+        self.solver.budget_off();
+        self.solver.set_conflict_budget(conflicts as u64);
+        let res = self.solver.solve_limited(&self.assumptions);
+        self.solver.budget_off();
+        if res != lbool::UNDEF {
+            self.pending = false;
+            self.assumptions.clear();
+        }
+        Self::result(res)
+    }
+
+    /// Give up the search started by [`solve_step`](Self::solve_step), and clear its
+    /// assumptions.
+    pub fn interrupt(&mut self) {
+        self.pending = false;
+        self.assumptions.clear();
+    }
+
+    /// Value of the variable of `lit` in the model found by the last search: `v` if the
+    /// variable `v` is true, `-v` if it is false, `0` if it does not matter.
+    pub fn value(&self, lit: i32) -> i32 {
+        let v = lit.unsigned_abs() as usize;
+        let Some(&var) = self.vars.get(v.wrapping_sub(1)) else {
+            return 0;
+        };
+        match self.solver.get_model().get(var.idx() as usize) {
+            Some(&x) if x == lbool::TRUE => v as i32,
+            Some(&x) if x == lbool::FALSE => -(v as i32),
+            _ => 0,
+        }
+    }
+
+    /// Was the assumption `lit` used to prove that the last search had no model?
+    pub fn failed(&self, lit: i32) -> bool {
+        let v = lit.unsigned_abs() as usize;
+        match self.vars.get(v.wrapping_sub(1)) {
+            Some(&var) => self.solver.unsat_core_contains_lit(Lit::new(var, lit > 0)),
+            None => false,
+        }
+    }
+}
+
+#[test]
+fn test_wasm_solver() {
+    // pigeonhole: 7 pigeons in 6 holes, solved by small slices
+    let (p, h) = (7, 6);
+    let var = |i: i32, j: i32| i * h + j + 1;
+    let mut solver = Solver::new();
+    for i in 0..p {
+        let c: Vec<i32> = (0..h).map(|j| var(i, j)).collect();
+        assert!(solver.add_clause(&c));
+    }
+    for j in 0..h {
+        for i in 0..p {
+            for k in i + 1..p {
+                assert!(solver.add_clause(&[-var(i, j), -var(k, j)]));
+            }
+        }
+    }
+    let mut steps = 0;
+    let res = loop {
+        steps += 1;
+        match solver.solve_step(10) {
+            UNKNOWN => continue,
+            res => break res,
+        }
+    };
+    assert_eq!(res, UNSAT);
+    assert!(steps > 1);
+    // without the last pigeon, under assumptions
+    let mut solver = Solver::new();
+    let relax = p * h + 1;
+    for i in 0..p {
+        let mut c: Vec<i32> = (0..h).map(|j| var(i, j)).collect();
+        if i == p - 1 {
+            c.push(relax);
+        }
+        assert!(solver.add_clause(&c));
+    }
+    for j in 0..h {
+        for i in 0..p {
+            for k in i + 1..p {
+                assert!(solver.add_clause(&[-var(i, j), -var(k, j)]));
+            }
+        }
+    }
+    solver.assume(-relax);
+    assert_eq!(solver.solve(), UNSAT);
+    assert!(solver.failed(-relax));
+    assert_eq!(solver.solve(), SAT);
+    assert_eq!(solver.value(relax), relax);
+    assert_eq!(solver.value(-relax), relax);
+    for i in 0..p - 1 {
+        assert!((0..h).any(|j| solver.value(var(i, j)) > 0));
+    }
+    assert_eq!(solver.value(1000), 0);
+    // an interrupted search keeps no assumption
+    solver.assume(-relax);
+    solver.interrupt();
+    assert_eq!(solver.solve(), SAT);
+    assert_eq!(solver.num_vars(), relax as u32);
+}
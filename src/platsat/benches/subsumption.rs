@@ -0,0 +1,69 @@
+//! Time of the subsumption checks of `Solver::eliminate` on random formulas with long
+//! clauses, to compare the scalar and vectorized searches:
+//!
+//! ```sh
+//! cargo bench -p platsat --bench subsumption
+//! cargo bench -p platsat --bench subsumption --features simd
+//! ```
+use platsat::{BasicSolver, Lit, SolverInterface, SolverOpts, Var};
+use std::time::{Duration, Instant};
+
+fn rand(seed: &mut u64, n: u64) -> u64 {
+    *seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1);
+    (*seed >> 33) % n
+}
+
+fn formula(mut seed: u64) -> (BasicSolver, Duration) {
+    let opts = SolverOpts {
+        subsumption_lim: -1,
+        ..SolverOpts::default()
+    };
+    let mut solver = BasicSolver::new(opts, Default::default());
+    let vars: Vec<Var> = (0..2000).map(|_| solver.new_var_default()).collect();
+    // only subsumption and strengthening, no variable is eliminated
+    for &v in &vars {
+        solver.set_frozen(v, true);
+    }
+    let lit = |seed: &mut u64| {
+        let v = vars[rand(seed, vars.len() as u64) as usize];
+        Lit::new(v, rand(seed, 2) == 0)
+    };
+    let mut clauses = vec![];
+    for _ in 0..20_000 {
+        let len = 20 + rand(&mut seed, 60) as usize;
+        let c: Vec<Lit> = (0..len).map(|_| lit(&mut seed)).collect();
+        clauses.push(c);
+    }
+    // short clauses made of the prefixes of the long ones, which subsume them
+    for i in 0..2000 {
+        let mut c = clauses[i * 10][..4].to_vec();
+        c.push(lit(&mut seed));
+        clauses.push(c);
+    }
+    for mut c in clauses {
+        solver.add_clause_reuse(&mut c);
+    }
+    let start = Instant::now();
+    solver.eliminate();
+    (solver, start.elapsed())
+}
+
+fn main() {
+    let simd = if cfg!(feature = "simd") {
+        "simd"
+    } else {
+        "scalar"
+    };
+    let mut total = Duration::ZERO;
+    for seed in 0..5 {
+        let (solver, time) = formula(seed);
+        println!(
+            "seed {}: {} clauses left, eliminate: {:?}",
+            seed,
+            solver.num_clauses(),
+            time
+        );
+        total += time;
+    }
+    println!("subsumption ({}): {:?} in total", simd, total);
+}
@@ -0,0 +1,246 @@
+//! And-inverter graphs
+//!
+//! An [`Aig`] represents Boolean functions of the solver's variables by conjunctions
+//! and negations. Its nodes are hashed, so that a conjunction of the same two edges is
+//! only built once, and the trivial ones are simplified away.
+use no_std_compat::prelude::v1::*;
+use {
+    crate::{
+        clause::{Lit, Var},
+        interface::SolverInterface,
+    },
+    std::{collections::BTreeMap, ops},
+};
+
+/// An edge to a node of an [`Aig`], possibly negated.
+///
+/// It is encoded as in the AIGER format: `2 * node + negated`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct AigLit(u32);
+
+impl AigLit {
+    /// The constant `false`, node `0`.
+    pub const FALSE: AigLit = AigLit(0);
+    /// The constant `true`.
+    pub const TRUE: AigLit = AigLit(1);
+
+    /// Edge to the node `node`, negated if `negated`.
+    #[inline]
+    pub fn new(node: usize, negated: bool) -> Self {
+        AigLit((node as u32) << 1 | negated as u32)
+    }
+
+    /// Index of the node.
+    #[inline]
+    pub fn node(self) -> usize {
+        (self.0 >> 1) as usize
+    }
+
+    /// Is the edge negated?
+    #[inline]
+    pub fn is_negated(self) -> bool {
+        self.0 & 1 != 0
+    }
+
+    /// Is the edge one of the constants?
+    #[inline]
+    pub fn is_const(self) -> bool {
+        self.0 < 2
+    }
+}
+
+impl ops::Not for AigLit {
+    type Output = AigLit;
+    #[inline]
+    fn not(self) -> AigLit {
+        AigLit(self.0 ^ 1)
+    }
+}
+
+impl ops::BitXor<bool> for AigLit {
+    type Output = AigLit;
+    #[inline]
+    fn bitxor(self, rhs: bool) -> AigLit {
+        AigLit(self.0 ^ rhs as u32)
+    }
+}
+
+/// A node of an [`Aig`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AigNode {
+    /// The constant `false`, which is node `0`.
+    False,
+    /// A variable of the solver.
+    Input(Var),
+    /// The conjunction of two edges to previous nodes.
+    And(AigLit, AigLit),
+}
+
+/// An and-inverter graph, whose nodes are in topological order.
+#[derive(Clone, Debug)]
+pub struct Aig {
+    nodes: Vec<AigNode>,
+    outputs: Vec<AigLit>,
+    /// Node of each variable.
+    inputs: BTreeMap<Var, AigLit>,
+    /// Node of each conjunction, by ordered edges.
+    ands: BTreeMap<(AigLit, AigLit), AigLit>,
+}
+
+impl Default for Aig {
+    fn default() -> Self {
+        Aig::new()
+    }
+}
+
+impl Aig {
+    /// A graph with the constant node only.
+    pub fn new() -> Self {
+        Aig {
+            nodes: vec![AigNode::False],
+            outputs: vec![],
+            inputs: BTreeMap::new(),
+            ands: BTreeMap::new(),
+        }
+    }
+
+    /// Number of nodes, including the constant one.
+    pub fn num_nodes(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// The node `a` points to.
+    pub fn node(&self, a: AigLit) -> AigNode {
+        self.nodes[a.node()]
+    }
+
+    /// The nodes, in topological order.
+    pub fn nodes(&self) -> &[AigNode] {
+        &self.nodes
+    }
+
+    /// Edges marked as outputs, see [`add_output`](Self::add_output).
+    pub fn outputs(&self) -> &[AigLit] {
+        &self.outputs
+    }
+
+    /// Mark `a` as an output.
+    pub fn add_output(&mut self, a: AigLit) {
+        self.outputs.push(a)
+    }
+
+    /// Edge to the node of the variable `v`.
+    pub fn input(&mut self, v: Var) -> AigLit {
+        let nodes = &mut self.nodes;
+        *self.inputs.entry(v).or_insert_with(|| {
+            nodes.push(AigNode::Input(v));
+            AigLit::new(nodes.len() - 1, false)
+        })
+    }
+
+    /// Edge to the node of the variable of `lit`, negated if `lit` is negative.
+    pub fn lit(&mut self, lit: Lit) -> AigLit {
+        let a = self.input(lit.var());
+        if lit.sign() {
+            a
+        } else {
+            !a
+        }
+    }
+
+    /// Conjunction of `a` and `b`.
+    pub fn and(&mut self, a: AigLit, b: AigLit) -> AigLit {
+        let (a, b) = if a <= b { (a, b) } else { (b, a) };
+        if a == AigLit::FALSE || a == !b {
+            return AigLit::FALSE;
+        } else if a == AigLit::TRUE || a == b {
+            return b;
+        }
+        let nodes = &mut self.nodes;
+        *self.ands.entry((a, b)).or_insert_with(|| {
+            nodes.push(AigNode::And(a, b));
+            AigLit::new(nodes.len() - 1, false)
+        })
+    }
+
+    /// Disjunction of `a` and `b`.
+    pub fn or(&mut self, a: AigLit, b: AigLit) -> AigLit {
+        !self.and(!a, !b)
+    }
+
+    /// Value of `a` when each variable `v` has the value `value(v)`.
+    pub fn eval(&self, a: AigLit, mut value: impl FnMut(Var) -> bool) -> bool {
+        let mut values = Vec::with_capacity(a.node() + 1);
+        let edge = |values: &[bool], e: AigLit| values[e.node()] ^ e.is_negated();
+        for node in &self.nodes[..=a.node()] {
+            let x = match *node {
+                AigNode::False => false,
+                AigNode::Input(v) => value(v),
+                AigNode::And(l, r) => edge(&values, l) && edge(&values, r),
+            };
+            values.push(x);
+        }
+        edge(&values, a)
+    }
+
+    /// Tseitin-encode the edges `edges` into `solver`, returning the literal of each.
+    ///
+    /// Each conjunction they depend on gets a fresh variable, defined by three clauses;
+    /// the inputs are the variables of the solver. Nodes are not shared between calls.
+    pub fn encode<S: SolverInterface>(&self, solver: &mut S, edges: &[AigLit]) -> Vec<Lit> {
+        let mut needed = vec![false; self.nodes.len()];
+        for &e in edges {
+            needed[e.node()] = true;
+        }
+        for i in (0..self.nodes.len()).rev() {
+            if let (true, AigNode::And(l, r)) = (needed[i], self.nodes[i]) {
+                needed[l.node()] = true;
+                needed[r.node()] = true;
+            }
+        }
+        let mut lits = vec![Lit::UNDEF; self.nodes.len()];
+        let edge = |lits: &[Lit], e: AigLit| lits[e.node()] ^ e.is_negated();
+        for (i, node) in self.nodes.iter().enumerate().filter(|(i, _)| needed[*i]) {
+            lits[i] = match *node {
+                AigNode::False => {
+                    let x = Lit::new(solver.new_var_default(), true);
+                    solver.add_clause_reuse(&mut vec![!x]);
+                    x
+                }
+                AigNode::Input(v) => Lit::new(v, true),
+                AigNode::And(l, r) => {
+                    let x = Lit::new(solver.new_var_default(), true);
+                    let (l, r) = (edge(&lits, l), edge(&lits, r));
+                    solver.add_clause_reuse(&mut vec![!x, l]);
+                    solver.add_clause_reuse(&mut vec![!x, r]);
+                    solver.add_clause_reuse(&mut vec![x, !l, !r]);
+                    x
+                }
+            };
+        }
+        edges.iter().map(|&e| edge(&lits, e)).collect()
+    }
+}
+
+#[test]
+fn test_aig() {
+    let [x, y] = [0, 1].map(|i| Lit::new(Var::from_idx(i), true));
+    let mut aig = Aig::new();
+    let (a, b) = (aig.lit(x), aig.lit(!y));
+    assert_eq!(aig.lit(x), a);
+    assert_eq!(aig.and(a, !a), AigLit::FALSE);
+    assert_eq!(aig.and(a, AigLit::TRUE), a);
+    assert_eq!(aig.or(a, AigLit::TRUE), AigLit::TRUE);
+    let xor = {
+        let l = aig.and(a, !b);
+        let r = aig.and(!a, b);
+        aig.or(l, r)
+    };
+    assert_eq!(aig.and(b, a), aig.and(a, b));
+    assert_eq!(aig.num_nodes(), 7);
+    for (vx, vy) in [(false, false), (false, true), (true, false), (true, true)] {
+        let value = |v: Var| if v == x.var() { vx } else { vy };
+        assert_eq!(aig.eval(xor, value), vx == vy);
+        assert!(!aig.eval(AigLit::FALSE, value));
+    }
+}
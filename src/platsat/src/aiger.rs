@@ -0,0 +1,448 @@
+//! Reader for circuits in the [AIGER format](https://fmv.jku.at/aiger/)
+//!
+//! Both the ASCII (`aag`) and the binary (`aig`) variants are read into an [`Aig`], whose
+//! outputs are then Tseitin-encoded into a solver. The header has the form
+//! `aag M I L O A [B C J F]`: `M` is the largest variable, followed by the number of
+//! inputs, latches, outputs, and-gates, and (since AIGER 1.9) bad state properties,
+//! invariant constraints, justice and fairness properties. The latter two are not
+//! supported; the symbol table and comments at the end of the file are ignored.
+use no_std_compat::prelude::v1::*;
+use {
+    crate::{
+        aig::{Aig, AigLit},
+        clause::{Lit, Var},
+        interface::SolverInterface,
+    },
+    std::{
+        error, fmt,
+        io::{self, BufRead},
+    },
+};
+
+/// Error returned when parsing an AIGER file.
+#[derive(Debug)]
+pub enum ParseError {
+    /// Error while reading the input
+    Io(io::Error),
+    /// The header is not of the form `aag M I L O A [B C J F]` (or `aig ...`)
+    InvalidHeader,
+    /// Unexpected character in the input
+    UnexpectedChar(char),
+    /// The input ended before the last and-gate
+    UnexpectedEof,
+    /// A literal is larger than `2M + 1`, or is defined twice or as a constant
+    InvalidLiteral(u64),
+    /// A literal is used but never defined
+    UndefinedLiteral(u64),
+    /// An and-gate depends on itself
+    Cycle(u64),
+    /// The circuit has justice or fairness properties
+    Unsupported,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseError::Io(e) => write!(f, "{}", e),
+            ParseError::InvalidHeader => write!(f, "PARSE ERROR! Invalid AIGER header"),
+            ParseError::UnexpectedChar(ch) => {
+                write!(f, "PARSE ERROR! Unexpected char: {:?}", ch)
+            }
+            ParseError::UnexpectedEof => write!(f, "PARSE ERROR! Unexpected EOF"),
+            ParseError::InvalidLiteral(l) => write!(f, "PARSE ERROR! Invalid literal {}", l),
+            ParseError::UndefinedLiteral(l) => {
+                write!(f, "PARSE ERROR! Literal {} is not defined", l)
+            }
+            ParseError::Cycle(l) => write!(f, "PARSE ERROR! Literal {} depends on itself", l),
+            ParseError::Unsupported => {
+                write!(
+                    f,
+                    "PARSE ERROR! Justice and fairness properties are not supported"
+                )
+            }
+        }
+    }
+}
+
+impl error::Error for ParseError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            ParseError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for ParseError {
+    fn from(e: io::Error) -> Self {
+        ParseError::Io(e)
+    }
+}
+
+impl From<ParseError> for io::Error {
+    fn from(e: ParseError) -> Self {
+        match e {
+            ParseError::Io(e) => e,
+            e => io::Error::new(io::ErrorKind::InvalidInput, e),
+        }
+    }
+}
+
+/// A circuit read by [`parse`].
+#[derive(Clone, Debug)]
+pub struct Circuit {
+    /// The graph, whose outputs are those of the file followed by its bad state
+    /// properties.
+    pub aig: Aig,
+    /// Variable of each input, in the order of the file.
+    pub inputs: Vec<Var>,
+    /// Variable of each latch, standing for its current state, with the edge of its next
+    /// state (which is not encoded).
+    pub latches: Vec<(Var, AigLit)>,
+    /// Literal of each output of `aig` in the solver.
+    pub outputs: Vec<Lit>,
+}
+
+/// `parse(input, solver)` reads the AIGER file `input` and encodes it into `solver`.
+///
+/// Each input and latch gets a fresh variable, in this order, so that they are the
+/// first variables of an empty solver. The invariant constraints are asserted, and
+/// the outputs are left free: to check whether the circuit can output `1`, add the
+/// clause [`Circuit::outputs`].
+pub fn parse<S: SolverInterface, R: BufRead>(
+    input: &mut R,
+    solver: &mut S,
+) -> Result<Circuit, ParseError> {
+    let mut buf = vec![];
+    input.read_to_end(&mut buf)?;
+    let mut r = Reader { buf, pos: 0 };
+
+    let binary = match r.buf.get(..3) {
+        Some(b"aag") => false,
+        Some(b"aig") => true,
+        _ => return Err(ParseError::InvalidHeader),
+    };
+    r.pos = 3;
+    let mut header = vec![];
+    while r.skip_spaces() {
+        header.push(r.uint().map_err(|_| ParseError::InvalidHeader)?);
+    }
+    r.end_line()?;
+    if header.len() < 5 || header.len() > 9 {
+        return Err(ParseError::InvalidHeader);
+    }
+    header.resize(9, 0);
+    let [m, i, l, o, a, b, c, j, f] = <[u64; 9]>::try_from(header).unwrap();
+    if m > u32::MAX as u64 / 2 || i + l + a > m || (binary && i + l + a != m) {
+        return Err(ParseError::InvalidHeader);
+    } else if j != 0 || f != 0 {
+        return Err(ParseError::Unsupported);
+    }
+
+    let mut circuit = Circuit {
+        aig: Aig::new(),
+        inputs: vec![],
+        latches: vec![],
+        outputs: vec![],
+    };
+    let mut g = Graph {
+        max_lit: 2 * m + 1,
+        defs: vec![Def::Undefined; m as usize + 1],
+    };
+    g.defs[0] = Def::Edge(AigLit::FALSE);
+    for k in 1..=i {
+        let lit = if binary { 2 * k } else { r.line(1)?[0] };
+        let v = solver.new_var_default();
+        g.define(lit, Def::Edge(circuit.aig.input(v)))?;
+        circuit.inputs.push(v);
+    }
+    let mut next = vec![];
+    for k in 1..=l {
+        let lit = if binary { 2 * (i + k) } else { r.uint()? };
+        r.skip_spaces();
+        next.push(r.uint()?);
+        // the initial value is not relevant here
+        if r.skip_spaces() {
+            r.uint()?;
+        }
+        r.end_line()?;
+        let v = solver.new_var_default();
+        g.define(lit, Def::Edge(circuit.aig.input(v)))?;
+        circuit.latches.push((v, AigLit::FALSE));
+    }
+    let outputs = r.lines(o + b)?;
+    let constraints = r.lines(c)?;
+    for k in 1..=a {
+        let (lhs, rhs0, rhs1) = if binary {
+            let lhs = 2 * (i + l + k);
+            let (d0, d1) = (r.delta()?, r.delta()?);
+            let rhs = lhs
+                .checked_sub(d0)
+                .and_then(|rhs0| Some((rhs0, rhs0.checked_sub(d1)?)));
+            match rhs {
+                Some((rhs0, rhs1)) if d0 > 0 => (lhs, rhs0, rhs1),
+                _ => return Err(ParseError::InvalidLiteral(lhs)),
+            }
+        } else {
+            let x = r.line(3)?;
+            (x[0], x[1], x[2])
+        };
+        g.check(rhs0)?;
+        g.check(rhs1)?;
+        g.define(lhs, Def::And(rhs0, rhs1))?;
+    }
+
+    let aig = &mut circuit.aig;
+    for (latch, lit) in circuit.latches.iter_mut().zip(next) {
+        latch.1 = g.edge(aig, lit)?;
+    }
+    for lit in outputs {
+        let e = g.edge(aig, lit)?;
+        aig.add_output(e);
+    }
+    let constraints = constraints
+        .into_iter()
+        .map(|lit| g.edge(aig, lit))
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut lits = aig.encode(solver, &[aig.outputs(), &constraints].concat());
+    for x in lits.drain(aig.outputs().len()..) {
+        solver.add_clause_reuse(&mut vec![x]);
+    }
+    circuit.outputs = lits;
+    Ok(circuit)
+}
+
+/// Definition of an AIGER variable.
+#[derive(Clone, Copy)]
+enum Def {
+    Undefined,
+    /// And-gate whose edges are not translated yet.
+    And(u64, u64),
+    /// And-gate being translated.
+    Visiting,
+    Edge(AigLit),
+}
+
+/// The variables of the file.
+struct Graph {
+    max_lit: u64,
+    defs: Vec<Def>,
+}
+
+impl Graph {
+    fn check(&self, lit: u64) -> Result<(), ParseError> {
+        if lit > self.max_lit {
+            return Err(ParseError::InvalidLiteral(lit));
+        }
+        Ok(())
+    }
+
+    fn define(&mut self, lit: u64, def: Def) -> Result<(), ParseError> {
+        self.check(lit)?;
+        let d = &mut self.defs[(lit >> 1) as usize];
+        if lit & 1 != 0 || lit < 2 || !matches!(d, Def::Undefined) {
+            return Err(ParseError::InvalidLiteral(lit));
+        }
+        *d = def;
+        Ok(())
+    }
+
+    /// Edge of `lit`, if its variable is translated.
+    fn get(&self, lit: u64) -> Option<AigLit> {
+        match self.defs[(lit >> 1) as usize] {
+            Def::Edge(e) => Some(e ^ (lit & 1 != 0)),
+            _ => None,
+        }
+    }
+
+    /// Edge of `lit` in `aig`, translating the gates it depends on.
+    ///
+    /// The gates of ASCII files may be in any order, so this uses an explicit stack
+    /// rather than recursion.
+    fn edge(&mut self, aig: &mut Aig, lit: u64) -> Result<AigLit, ParseError> {
+        self.check(lit)?;
+        let mut stack = vec![];
+        let mut v = (lit >> 1) as usize;
+        loop {
+            match self.defs[v] {
+                Def::Undefined => return Err(ParseError::UndefinedLiteral(2 * v as u64)),
+                Def::Visiting => return Err(ParseError::Cycle(2 * v as u64)),
+                Def::And(rhs0, rhs1) => {
+                    self.defs[v] = Def::Visiting;
+                    stack.push((v, rhs0, rhs1));
+                }
+                Def::Edge(_) => {}
+            }
+            // translate the gates whose children are, until one of them is not
+            loop {
+                let Some(&(w, rhs0, rhs1)) = stack.last() else {
+                    return Ok(self.get(lit).unwrap());
+                };
+                match (self.get(rhs0), self.get(rhs1)) {
+                    (Some(x), Some(y)) => {
+                        self.defs[w] = Def::Edge(aig.and(x, y));
+                        stack.pop();
+                    }
+                    (None, _) => {
+                        v = (rhs0 >> 1) as usize;
+                        break;
+                    }
+                    (_, None) => {
+                        v = (rhs1 >> 1) as usize;
+                        break;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// A cursor in the content of the file.
+struct Reader {
+    buf: Vec<u8>,
+    pos: usize,
+}
+
+impl Reader {
+    fn peek(&self) -> Option<u8> {
+        self.buf.get(self.pos).copied()
+    }
+
+    /// Skip spaces, returning whether there was any.
+    fn skip_spaces(&mut self) -> bool {
+        let start = self.pos;
+        while let Some(b' ' | b'\t') = self.peek() {
+            self.pos += 1;
+        }
+        self.pos > start
+    }
+
+    fn uint(&mut self) -> Result<u64, ParseError> {
+        let start = self.pos;
+        let mut x: u64 = 0;
+        while let Some(d @ b'0'..=b'9') = self.peek() {
+            x = x
+                .checked_mul(10)
+                .and_then(|x| x.checked_add((d - b'0') as u64))
+                .ok_or(ParseError::InvalidLiteral(u64::MAX))?;
+            self.pos += 1;
+        }
+        match self.peek() {
+            _ if self.pos > start => Ok(x),
+            Some(ch) => Err(ParseError::UnexpectedChar(ch as char)),
+            None => Err(ParseError::UnexpectedEof),
+        }
+    }
+
+    fn end_line(&mut self) -> Result<(), ParseError> {
+        if self.peek() == Some(b'\r') {
+            self.pos += 1;
+        }
+        match self.peek() {
+            Some(b'\n') => {
+                self.pos += 1;
+                Ok(())
+            }
+            Some(ch) => Err(ParseError::UnexpectedChar(ch as char)),
+            None => Ok(()),
+        }
+    }
+
+    /// A line of `n` integers.
+    fn line(&mut self, n: usize) -> Result<Vec<u64>, ParseError> {
+        let mut x = Vec::with_capacity(n);
+        for k in 0..n {
+            if k > 0 && !self.skip_spaces() {
+                return Err(match self.peek() {
+                    Some(ch) => ParseError::UnexpectedChar(ch as char),
+                    None => ParseError::UnexpectedEof,
+                });
+            }
+            x.push(self.uint()?);
+        }
+        self.end_line()?;
+        Ok(x)
+    }
+
+    /// `n` lines of one integer each.
+    fn lines(&mut self, n: u64) -> Result<Vec<u64>, ParseError> {
+        (0..n).map(|_| Ok(self.line(1)?[0])).collect()
+    }
+
+    /// An integer in the 7-bit encoding of the binary format.
+    fn delta(&mut self) -> Result<u64, ParseError> {
+        let mut x = 0;
+        for shift in (0..64).step_by(7) {
+            let byte = self.peek().ok_or(ParseError::UnexpectedEof)?;
+            self.pos += 1;
+            x |= ((byte & 0x7f) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(x);
+            }
+        }
+        Err(ParseError::InvalidLiteral(u64::MAX))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{lbool, BasicSolver};
+
+    fn parse_str(s: &[u8]) -> Result<(BasicSolver, Circuit), ParseError> {
+        let mut solver = BasicSolver::default();
+        let circuit = parse(&mut &s[..], &mut solver)?;
+        Ok((solver, circuit))
+    }
+
+    /// Value of the only output for the values `m` of the inputs.
+    fn output(solver: &mut BasicSolver, circuit: &Circuit, m: u32) -> bool {
+        let assumps: Vec<Lit> = (circuit.inputs.iter().enumerate())
+            .map(|(k, &v)| Lit::new(v, m >> k & 1 != 0))
+            .collect();
+        assert_eq!(solver.solve_limited(&assumps), lbool::TRUE);
+        solver.value_lit(circuit.outputs[0]) == lbool::TRUE
+    }
+
+    #[test]
+    fn test_aiger() {
+        // the examples of the format description: an and-gate, in both variants,
+        // and a xor whose gates are not ordered
+        let and = [
+            &b"aag 3 2 0 1 1\n2\n4\n6\n6 2 4\n"[..],
+            b"aig 3 2 0 1 1\n6\n\x02\x02",
+        ];
+        for s in and {
+            let (mut solver, circuit) = parse_str(s).unwrap();
+            assert_eq!(circuit.inputs.len(), 2);
+            for m in 0..4 {
+                assert_eq!(output(&mut solver, &circuit, m), m == 3);
+            }
+        }
+        let xor = b"aag 7 2 0 1 3\n2\n4\n6\n6 13 15\n12 2 4\n14 3 5\nc\nxor\n";
+        let (mut solver, circuit) = parse_str(xor).unwrap();
+        for m in 0..4 {
+            assert_eq!(output(&mut solver, &circuit, m), m == 1 || m == 2);
+        }
+        // a latch, a constant output, and the constraint that the latch is set
+        let (mut solver, mut circuit) = parse_str(b"aag 1 0 1 2 0 0 1\n2 3\n3\n1\n2\n").unwrap();
+        assert_eq!(
+            circuit.latches,
+            vec![(Var::from_idx(0), !circuit.aig.input(Var::from_idx(0)))]
+        );
+        assert_eq!(solver.solve_limited(&[circuit.outputs[0]]), lbool::FALSE);
+        assert_eq!(solver.solve_limited(&[circuit.outputs[1]]), lbool::TRUE);
+
+        for (s, err) in [
+            (&b"aag 1 1 0 1\n"[..], "Invalid AIGER header"),
+            (b"aag 3 1 0 1 1\n2\n6\n6 2 7\n", "depends on itself"),
+            (b"aag 2 1 0 1 0\n2\n4\n", "not defined"),
+            (b"aag 2 1 0 0 1\n2\n2 2 2\n", "Invalid literal 2"),
+            (b"aig 3 2 0 1 1\n6\n\x02", "Unexpected EOF"),
+            (b"aag 1 1 0 0 0 0 0 1\n2\n", "not supported"),
+        ] {
+            let e = parse_str(s).map(|_| ()).unwrap_err();
+            assert!(e.to_string().contains(err), "{}", e);
+        }
+    }
+}
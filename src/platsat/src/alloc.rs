@@ -17,37 +17,57 @@ NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FO
 DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT
 OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
 **************************************************************************************************/
+// the derives on `Ref` repeat the `T: Copy` bound
+#![allow(clippy::multiple_bound_locations)]
 use bytemuck::{Pod, Zeroable};
 use no_std_compat::prelude::v1::*;
 use std::cmp;
 use std::fmt;
 use std::marker::PhantomData;
+use std::mem;
 use std::ops;
 
-#[derive(Debug)]
+/// Index of an item in a region: `u32`, or `u64` with the `cref64` feature, for regions
+/// of more than 2^32 items at the cost of larger references.
+#[cfg(not(feature = "cref64"))]
+pub type RefIndex = u32;
+/// Index of an item in a region: `u32`, or `u64` with the `cref64` feature, for regions
+/// of more than 2^32 items at the cost of larger references.
+#[cfg(feature = "cref64")]
+pub type RefIndex = u64;
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct RegionAllocator<T: Copy> {
     vec: Vec<T>,
     wasted: usize,
 }
 
 impl<T: Copy + Default> RegionAllocator<T> {
-    pub fn new(start_cap: u32) -> Self {
+    pub fn new(start_cap: usize) -> Self {
         Self {
-            vec: Vec::with_capacity(start_cap as usize),
+            vec: Vec::with_capacity(start_cap),
             wasted: 0,
         }
     }
-    pub fn len(&self) -> u32 {
-        self.vec.len() as u32
+    pub fn len(&self) -> usize {
+        self.vec.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.vec.is_empty()
     }
-    pub fn wasted(&self) -> u32 {
-        self.wasted as u32
+    pub fn wasted(&self) -> usize {
+        self.wasted
+    }
+    /// Bytes allocated for the region
+    pub fn memory(&self) -> usize {
+        self.vec.capacity() * mem::size_of::<T>()
     }
     pub fn alloc(&mut self, size: u32) -> Ref<T> {
         debug_assert!(size > 0);
-        let r = Ref(self.vec.len() as u32, PhantomData);
-        if r >= Ref::SPECIAL {
-            panic!("allocator: max capacity reached");
+        let r = Ref(self.vec.len() as RefIndex, PhantomData);
+        if self.vec.len() + size as usize > Ref::<T>::SPECIAL.0 as usize {
+            panic!("allocator: max capacity reached, see the `cref64` feature");
         }
         self.vec.extend((0..size).map(|_| T::default()));
         r
@@ -80,7 +100,8 @@ impl<T: Copy> ops::IndexMut<Ref<T>> for RegionAllocator<T> {
 /// A reference to a `T` value living in the allocator.
 ///
 /// The reference is invariant in `T`.
-pub struct Ref<T: Copy>(u32, PhantomData<fn(T) -> T>);
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Ref<T: Copy>(RefIndex, PhantomData<fn(T) -> T>);
 
 impl<T: Copy> fmt::Debug for Ref<T> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
@@ -102,7 +123,7 @@ impl<T: Copy> PartialEq for Ref<T> {
 impl<T: Copy> Eq for Ref<T> {}
 impl<T: Copy> PartialOrd for Ref<T> {
     fn partial_cmp(&self, rhs: &Self) -> Option<cmp::Ordering> {
-        PartialOrd::partial_cmp(&self.0, &rhs.0)
+        Some(self.cmp(rhs))
     }
 }
 impl<T: Copy> Ord for Ref<T> {
@@ -125,6 +146,13 @@ impl<T: Copy> Ref<T> {
 impl<T: Copy> ops::Add<u32> for Ref<T> {
     type Output = Ref<T>;
     fn add(self, rhs: u32) -> Self::Output {
-        Ref(self.0 + rhs, PhantomData)
+        Ref(self.0 + rhs as RefIndex, PhantomData)
+    }
+}
+
+impl<T: Copy> ops::Sub<u32> for Ref<T> {
+    type Output = Ref<T>;
+    fn sub(self, rhs: u32) -> Self::Output {
+        Ref(self.0 - rhs as RefIndex, PhantomData)
     }
 }
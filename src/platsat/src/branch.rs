@@ -0,0 +1,593 @@
+//! Branching heuristics, which choose the variable of the next decision
+use crate::{
+    clause::{VMap, Var},
+    core::{BranchingHeuristic, SolverOpts},
+    heap::{CachedKeyComparator, Heap, IdxHeap},
+};
+use no_std_compat::prelude::v1::*;
+
+/// A heuristic that chooses the variable of the next decision.
+///
+/// The solver tells the heuristic about the life of the variables (assignments,
+/// conflicts...), and asks it for the best candidate when it needs to decide.
+pub(crate) trait Branch {
+    /// Register the new variable `v`, with an initial score.
+    fn new_var(&mut self, v: Var, score: f32);
+
+    /// `v` may be a candidate for [`pick`](Branch::pick) again, because it was unassigned
+    /// or became a decision variable.
+    fn insert(&mut self, v: Var);
+
+    /// Insert `vars` when no variable is inserted yet, for instance after a reset.
+    fn insert_all(&mut self, vars: impl Iterator<Item = Var>) {
+        for v in vars {
+            self.insert(v);
+        }
+    }
+
+    /// `v` was just assigned.
+    fn on_assign(&mut self, _v: Var) {}
+
+    /// `v` is being unassigned by backtracking, it will be inserted right after.
+    fn on_unassign(&mut self, _v: Var) {}
+
+    /// `v` occurs in the analysis of the current conflict.
+    fn bump(&mut self, v: Var);
+
+    /// The current conflict was analysed, and the solver backtracked.
+    fn on_conflict(&mut self, opts: &SolverOpts);
+
+    /// Increase the score of `v` by `delta` times a bump, outside of the conflicts.
+    fn boost(&mut self, v: Var, delta: f64);
+
+    /// Return the best inserted variable for which `candidate` holds, or `Var::UNDEF`.
+    ///
+    /// The variables that are skipped need to be inserted again to be picked later.
+    fn pick(&mut self, candidate: impl FnMut(Var) -> bool) -> Var;
+
+    /// Score of `v`, higher for the variables that are picked first.
+    fn score(&self, v: Var) -> f64;
+}
+
+/// The heuristic selected by [`SolverOpts::branching`].
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub(crate) enum Branching {
+    Vsids(Vsids),
+    Vmtf(Vmtf),
+    Erwa(Erwa),
+}
+
+impl Branching {
+    pub(crate) fn new(heuristic: BranchingHeuristic) -> Self {
+        match heuristic {
+            BranchingHeuristic::Vsids => Branching::Vsids(Vsids::new()),
+            BranchingHeuristic::Vmtf => Branching::Vmtf(Vmtf::new()),
+            BranchingHeuristic::Chb => Branching::Erwa(Erwa::new(false)),
+            BranchingHeuristic::Lrb => Branching::Erwa(Erwa::new(true)),
+        }
+    }
+}
+
+macro_rules! dispatch {
+    ($self:ident, $b:ident => $e:expr) => {
+        match $self {
+            Branching::Vsids($b) => $e,
+            Branching::Vmtf($b) => $e,
+            Branching::Erwa($b) => $e,
+        }
+    };
+}
+
+impl Branch for Branching {
+    fn new_var(&mut self, v: Var, score: f32) {
+        dispatch!(self, b => b.new_var(v, score))
+    }
+    #[inline]
+    fn insert(&mut self, v: Var) {
+        dispatch!(self, b => b.insert(v))
+    }
+    fn insert_all(&mut self, vars: impl Iterator<Item = Var>) {
+        dispatch!(self, b => b.insert_all(vars))
+    }
+    #[inline]
+    fn on_assign(&mut self, v: Var) {
+        dispatch!(self, b => b.on_assign(v))
+    }
+    #[inline]
+    fn on_unassign(&mut self, v: Var) {
+        dispatch!(self, b => b.on_unassign(v))
+    }
+    #[inline]
+    fn bump(&mut self, v: Var) {
+        dispatch!(self, b => b.bump(v))
+    }
+    fn on_conflict(&mut self, opts: &SolverOpts) {
+        dispatch!(self, b => b.on_conflict(opts))
+    }
+    fn boost(&mut self, v: Var, delta: f64) {
+        dispatch!(self, b => b.boost(v, delta))
+    }
+    fn pick(&mut self, candidate: impl FnMut(Var) -> bool) -> Var {
+        dispatch!(self, b => b.pick(candidate))
+    }
+    fn score(&self, v: Var) -> f64 {
+        dispatch!(self, b => b.score(v))
+    }
+}
+
+/// Variable state independent decaying sum: the activity of the variables of each conflict
+/// is bumped by an increment that grows exponentially (MiniSat's heuristic).
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub(crate) struct Vsids {
+    /// A heuristic measurement of the activity of a variable.
+    activity: VMap<f32>,
+    /// A priority queue of variables ordered with respect to the variable activity.
+    heap: IdxHeap<Var, VarOrderKey>,
+    /// Amount to bump next variable with.
+    var_inc: f32,
+}
+
+impl Vsids {
+    fn new() -> Self {
+        Vsids {
+            activity: VMap::new(),
+            heap: IdxHeap::new(),
+            var_inc: 1.0,
+        }
+    }
+
+    fn heap(&mut self) -> Heap<'_, Var, VarOrder<'_>> {
+        self.heap.promote(VarOrder {
+            activity: &self.activity,
+        })
+    }
+
+    fn rescale(&mut self) {
+        let scale = -f32::MIN_EXP as u32;
+        for (_, x) in self.activity.iter_mut() {
+            *x = scale_down_float(*x, scale)
+        }
+        for x in self.heap.heap_mut().iter_mut() {
+            x.map_activity(|activity| scale_down_float(activity, scale))
+        }
+        self.var_inc = scale_down_float(self.var_inc, scale);
+    }
+}
+
+impl Branch for Vsids {
+    fn new_var(&mut self, v: Var, score: f32) {
+        self.activity.insert_default(v, score);
+    }
+
+    fn insert(&mut self, v: Var) {
+        let mut heap = self.heap();
+        if !heap.in_heap(v) {
+            heap.insert(v);
+        }
+    }
+
+    fn insert_all(&mut self, vars: impl Iterator<Item = Var>) {
+        debug_assert!(self.heap.is_empty());
+        self.heap().rebuild(vars);
+    }
+
+    /// Increase a variable with the current 'bump' value.
+    fn bump(&mut self, v: Var) {
+        self.activity[v] += self.var_inc;
+
+        // Update heap with respect to new activity:
+        let mut heap = self.heap();
+        if heap.in_heap(v) {
+            heap.decrease(v);
+        }
+    }
+
+    fn on_conflict(&mut self, opts: &SolverOpts) {
+        self.var_inc *= 1.0 / opts.var_decay;
+        if self.var_inc > THRESHOLD {
+            self.rescale();
+        }
+    }
+
+    fn boost(&mut self, v: Var, delta: f64) {
+        let activity = self.activity[v] as f64 + delta * self.var_inc as f64;
+        self.activity[v] = activity.clamp(0.0, THRESHOLD as f64) as f32;
+        let mut heap = self.heap();
+        if heap.in_heap(v) {
+            heap.update(v);
+        }
+        if self.activity[v] >= THRESHOLD {
+            self.rescale();
+        }
+    }
+
+    fn pick(&mut self, mut candidate: impl FnMut(Var) -> bool) -> Var {
+        let mut heap = self.heap();
+        while !heap.is_empty() {
+            let v = heap.remove_min();
+            if candidate(v) {
+                return v;
+            }
+        }
+        Var::UNDEF
+    }
+
+    fn score(&self, v: Var) -> f64 {
+        self.activity[v] as f64
+    }
+}
+
+/// Variable move-to-front: the variables of each conflict are moved to the front of a
+/// queue, and the first unassigned variable of the queue is picked (CaDiCaL's heuristic).
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub(crate) struct Vmtf {
+    /// Towards the back of the queue.
+    prev: VMap<Var>,
+    /// Towards the front of the queue.
+    next: VMap<Var>,
+    back: Var,
+    front: Var,
+    /// When each variable was last moved to the front, increasing towards the front.
+    stamp: VMap<u64>,
+    stamps: u64,
+    /// The variables in front of this one are not candidates.
+    search: Var,
+    /// Variables of the current conflict.
+    bumped: Vec<Var>,
+}
+
+impl Vmtf {
+    fn new() -> Self {
+        Vmtf {
+            prev: VMap::new(),
+            next: VMap::new(),
+            back: Var::UNDEF,
+            front: Var::UNDEF,
+            stamp: VMap::new(),
+            stamps: 0,
+            search: Var::UNDEF,
+            bumped: vec![],
+        }
+    }
+
+    fn unlink(&mut self, v: Var) {
+        let (p, n) = (self.prev[v], self.next[v]);
+        if p == Var::UNDEF {
+            self.back = n;
+        } else {
+            self.next[p] = n;
+        }
+        if n == Var::UNDEF {
+            self.front = p;
+        } else {
+            self.prev[n] = p;
+        }
+    }
+
+    fn push_front(&mut self, v: Var) {
+        self.prev[v] = self.front;
+        self.next[v] = Var::UNDEF;
+        if self.front == Var::UNDEF {
+            self.back = v;
+        } else {
+            self.next[self.front] = v;
+        }
+        self.front = v;
+        self.stamps += 1;
+        self.stamp[v] = self.stamps;
+    }
+}
+
+impl Branch for Vmtf {
+    fn new_var(&mut self, v: Var, _score: f32) {
+        self.prev.insert(v, Var::UNDEF, Var::UNDEF);
+        self.next.insert(v, Var::UNDEF, Var::UNDEF);
+        self.stamp.insert_default(v, 0);
+        self.push_front(v);
+    }
+
+    fn insert(&mut self, v: Var) {
+        if self.search == Var::UNDEF || self.stamp[v] > self.stamp[self.search] {
+            self.search = v;
+        }
+    }
+
+    fn bump(&mut self, v: Var) {
+        self.bumped.push(v);
+    }
+
+    fn on_conflict(&mut self, _: &SolverOpts) {
+        // keep the relative order of the bumped variables
+        let stamp = &self.stamp;
+        self.bumped.sort_unstable_by_key(|&v| stamp[v]);
+        for i in 0..self.bumped.len() {
+            let v = self.bumped[i];
+            self.unlink(v);
+            self.push_front(v);
+        }
+        self.bumped.clear();
+        // the bumped variables may have been unassigned
+        self.search = self.front;
+    }
+
+    fn boost(&mut self, v: Var, delta: f64) {
+        // the order of the queue cannot be scaled, so `v` goes to the front
+        if delta > 0.0 {
+            self.unlink(v);
+            self.push_front(v);
+            self.search = self.front;
+        }
+    }
+
+    fn pick(&mut self, mut candidate: impl FnMut(Var) -> bool) -> Var {
+        let mut v = self.search;
+        while v != Var::UNDEF && !candidate(v) {
+            v = self.prev[v];
+        }
+        self.search = v;
+        v
+    }
+
+    fn score(&self, v: Var) -> f64 {
+        self.stamp[v] as f64
+    }
+}
+
+/// Learning rate at the start of the search, for [`Erwa`].
+const ERWA_ALPHA_START: f32 = 0.4;
+/// Minimum learning rate, reached after `(0.4 - 0.06) / 1e-6` conflicts.
+const ERWA_ALPHA_MIN: f32 = 0.06;
+const ERWA_ALPHA_DECAY: f32 = 1e-6;
+
+/// Exponential recency weighted average of a reward (Liang et al.), which is either:
+///
+/// - LRB (learning rate branching): when a variable is unassigned, the fraction of the
+///   conflicts since its assignment that it took part in;
+/// - CHB (conflict history-based branching): when a variable is assigned, a reward
+///   that decreases with the number of conflicts since it last took part in one.
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub(crate) struct Erwa {
+    lrb: bool,
+    /// Average reward of each variable.
+    q: VMap<f32>,
+    heap: IdxHeap<Var, VarOrderKey>,
+    alpha: f32,
+    conflicts: u64,
+    /// LRB: number of conflicts when the variable was assigned.
+    /// CHB: last conflict the variable took part in.
+    stamp: VMap<u64>,
+    /// LRB: number of conflicts the variable took part in since it was assigned.
+    participated: VMap<u32>,
+    /// CHB: variables assigned since the last rewards.
+    assigned: Vec<Var>,
+    num_vars: usize,
+}
+
+impl Erwa {
+    fn new(lrb: bool) -> Self {
+        Erwa {
+            lrb,
+            q: VMap::new(),
+            heap: IdxHeap::new(),
+            alpha: ERWA_ALPHA_START,
+            conflicts: 0,
+            stamp: VMap::new(),
+            participated: VMap::new(),
+            assigned: vec![],
+            num_vars: 0,
+        }
+    }
+
+    fn heap(&mut self) -> Heap<'_, Var, VarOrder<'_>> {
+        self.heap.promote(VarOrder { activity: &self.q })
+    }
+
+    fn reward(&mut self, v: Var, r: f32) {
+        self.q[v] = (1.0 - self.alpha) * self.q[v] + self.alpha * r;
+        let mut heap = self.heap();
+        if heap.in_heap(v) {
+            heap.update(v);
+        }
+    }
+
+    /// CHB: reward the variables assigned since the last time, `multiplier` is higher
+    /// if their propagation led to a conflict.
+    fn reward_assigned(&mut self, multiplier: f32) {
+        let mut assigned = core::mem::take(&mut self.assigned);
+        for &v in &assigned {
+            let age = self.conflicts - self.stamp[v] + 1;
+            self.reward(v, multiplier / age as f32);
+        }
+        assigned.clear();
+        self.assigned = assigned;
+    }
+}
+
+impl Branch for Erwa {
+    fn new_var(&mut self, v: Var, score: f32) {
+        self.q.insert_default(v, score);
+        self.stamp.insert_default(v, 0);
+        self.participated.insert_default(v, 0);
+        self.num_vars = self.num_vars.max(v.idx() as usize + 1);
+    }
+
+    fn insert(&mut self, v: Var) {
+        let mut heap = self.heap();
+        if !heap.in_heap(v) {
+            heap.insert(v);
+        }
+    }
+
+    fn insert_all(&mut self, vars: impl Iterator<Item = Var>) {
+        debug_assert!(self.heap.is_empty());
+        self.heap().rebuild(vars);
+    }
+
+    #[inline]
+    fn on_assign(&mut self, v: Var) {
+        if self.lrb {
+            self.stamp[v] = self.conflicts;
+            self.participated[v] = 0;
+        } else {
+            self.assigned.push(v);
+            if self.assigned.len() > self.num_vars {
+                // many assignments without decisions nor conflicts (e.g. by probing)
+                self.reward_assigned(0.9);
+            }
+        }
+    }
+
+    fn on_unassign(&mut self, v: Var) {
+        if self.lrb {
+            let interval = self.conflicts - self.stamp[v];
+            if interval > 0 {
+                let r = self.participated[v] as f32 / interval as f32;
+                self.reward(v, r);
+            }
+        }
+    }
+
+    fn bump(&mut self, v: Var) {
+        if self.lrb {
+            self.participated[v] += 1;
+        } else {
+            self.stamp[v] = self.conflicts;
+        }
+    }
+
+    fn on_conflict(&mut self, _: &SolverOpts) {
+        if !self.lrb {
+            self.reward_assigned(1.0);
+        }
+        self.conflicts += 1;
+        self.alpha = (self.alpha - ERWA_ALPHA_DECAY).max(ERWA_ALPHA_MIN);
+    }
+
+    fn boost(&mut self, v: Var, delta: f64) {
+        // as much as a reward of `delta`
+        let q = self.q[v] as f64 + delta * self.alpha as f64;
+        self.q[v] = q.clamp(0.0, THRESHOLD as f64) as f32;
+        let mut heap = self.heap();
+        if heap.in_heap(v) {
+            heap.update(v);
+        }
+    }
+
+    fn pick(&mut self, mut candidate: impl FnMut(Var) -> bool) -> Var {
+        if !self.lrb {
+            self.reward_assigned(0.9);
+        }
+        let mut heap = self.heap();
+        while !heap.is_empty() {
+            let v = heap.remove_min();
+            if candidate(v) {
+                return v;
+            }
+        }
+        Var::UNDEF
+    }
+
+    fn score(&self, v: Var) -> f64 {
+        self.q[v] as f64
+    }
+}
+
+/// Orders the variables by decreasing activity.
+struct VarOrder<'a> {
+    activity: &'a VMap<f32>,
+}
+
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub(crate) struct VarOrderKey(u64);
+
+impl VarOrderKey {
+    #[inline]
+    fn new(var: Var, activity: f32) -> Self {
+        VarOrderKey((!(activity.to_bits() as u64) << u32::BITS) | (var.idx() as u64))
+    }
+
+    fn var(self) -> Var {
+        Var::unsafe_from_idx(self.0 as u32)
+    }
+
+    fn activity(self) -> f32 {
+        f32::from_bits(!((self.0 >> u32::BITS) as u32))
+    }
+
+    fn map_activity(&mut self, f: impl FnOnce(f32) -> f32) {
+        *self = VarOrderKey::new(self.var(), f(self.activity()))
+    }
+}
+
+impl<'a> CachedKeyComparator<Var> for VarOrder<'a> {
+    type Key = VarOrderKey;
+
+    fn cache_key(&self, t: Var) -> Self::Key {
+        VarOrderKey::new(t, self.activity[t])
+    }
+
+    fn max_key(&self) -> Self::Key {
+        VarOrderKey::new(Var::UNDEF, 0.0)
+    }
+
+    fn un_cache_key(&self, k: Self::Key) -> Var {
+        k.var()
+    }
+}
+
+/// Large f32 that is still small enough that it can't cause another f32 to overflow to infinity
+pub(crate) const THRESHOLD: f32 = 1.0141204e31;
+#[test]
+fn test_threshold() {
+    let f = f32::MAX * 2.0f32.powi(-1 - (f32::MANTISSA_DIGITS as i32));
+    assert_eq!(THRESHOLD, f);
+    // adding THRESHOLD to a float can never make it overflow to infinity
+    assert_eq!(f32::MAX + THRESHOLD, f32::MAX)
+}
+
+/// multiply a positive float `f` by 0.5f32.powi(`pow2`)
+/// truncates to positive 0 instead of using sub-normal numbers
+#[inline]
+fn scale_down_float(f: f32, pow2: u32) -> f32 {
+    f32::from_bits(
+        f.to_bits()
+            .saturating_sub(pow2 << (f32::MANTISSA_DIGITS - 1)),
+    )
+}
+
+#[test]
+fn test_scale_down_float() {
+    assert_eq!(scale_down_float(42.0, 10), 42.0 * 0.5f32.powi(10));
+    let actual = scale_down_float(42.0, 140);
+    let expect = 42.0 * 0.5_f32.powi(140);
+    assert!(0.0 <= actual && actual <= expect && expect < f32::MIN_POSITIVE)
+}
+
+#[test]
+fn test_vmtf() {
+    let vars: Vec<Var> = (0..4).map(Var::from_idx).collect();
+    let mut vmtf = Vmtf::new();
+    for &v in &vars {
+        vmtf.new_var(v, 0.0);
+        vmtf.insert(v);
+    }
+    // the last variables are picked first
+    assert_eq!(vmtf.pick(|_| true), vars[3]);
+    assert_eq!(vmtf.pick(|v| v != vars[3]), vars[2]);
+    // bumped variables move to the front, in the same order
+    vmtf.bump(vars[1]);
+    vmtf.bump(vars[0]);
+    vmtf.on_conflict(&SolverOpts::default());
+    assert_eq!(vmtf.pick(|_| true), vars[1]);
+    assert_eq!(vmtf.pick(|v| v != vars[1]), vars[0]);
+    // unassigned variables can be picked again
+    assert_eq!(vmtf.pick(|v| v == vars[3]), vars[3]);
+    assert_eq!(vmtf.pick(|_| false), Var::UNDEF);
+    vmtf.insert(vars[2]);
+    assert_eq!(vmtf.pick(|_| true), vars[2]);
+}
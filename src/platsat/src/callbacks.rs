@@ -23,6 +23,10 @@ pub trait Callbacks: Sized {
     /// Called after a clause GC
     fn on_gc(&mut self, _old_size: usize, _new_size: usize) {}
 
+    /// Called after the learnt clauses are reduced, with the number of learnt clauses
+    /// kept and deleted
+    fn on_reduce_db(&mut self, _kept: usize, _deleted: usize) {}
+
     /// Called whenever a new clause is learnt.
     ///
     /// ## Params
@@ -49,6 +53,26 @@ pub trait Callbacks: Sized {
     }
 }
 
+/// Listener of the clauses learnt, deleted and strengthened by the solver, for instance
+/// to share clauses or collect data about the search.
+///
+/// Unlike [`Callbacks`], it is installed on an existing solver, see
+/// [`Solver::set_clause_listener`](crate::Solver::set_clause_listener).
+pub trait ClauseListener {
+    /// Called when clause `c` is learnt, during the search or the inprocessing.
+    ///
+    /// `lbd` is its literal block distance for a clause learnt from a conflict, its
+    /// length otherwise.
+    fn on_learnt(&mut self, _c: &[Lit], _lbd: u32) {}
+
+    /// Called when the learnt clause `c` is deleted.
+    fn on_deleted(&mut self, _c: &[Lit]) {}
+
+    /// Called when clause `old`, original or learnt, is replaced by `new`, made of some
+    /// of its literals.
+    fn on_strengthened(&mut self, _old: &[Lit], _new: &[Lit]) {}
+}
+
 /// Progress indicator from the SAT solver.
 ///
 /// This is given to `Callbacks` regularly so it can log it somehow.
@@ -66,9 +90,11 @@ pub struct ProgressStatus {
 
 /// Basic set of callbacks
 ///
-/// This doesn't do anything except storing a function to `stop`
+/// This doesn't do anything except storing a function to `stop`, which is shared by
+/// the clones. It is `Send`, so that solvers can be moved to other threads.
+#[derive(Clone)]
 pub struct Basic {
-    stop: Option<Box<dyn Fn() -> bool>>, // to stop
+    stop: Option<Arc<dyn Fn() -> bool + Send + Sync>>, // to stop
 }
 
 impl Callbacks for Basic {
@@ -89,9 +115,9 @@ impl Basic {
     /// Set the `stop` function
     pub fn set_stop<F>(&mut self, f: F)
     where
-        F: 'static + Fn() -> bool,
+        F: 'static + Fn() -> bool + Send + Sync,
     {
-        self.stop = Some(Box::new(f));
+        self.stop = Some(Arc::new(f));
     }
 }
 
@@ -102,6 +128,7 @@ impl Default for Basic {
 }
 
 /// Basic set of callbacks, maintaining some statistics and a "stop" predicate.
+#[derive(Clone)]
 pub struct Stats {
     basic: Basic,
     pub n_restarts: usize,
@@ -170,7 +197,72 @@ impl Default for Stats {
     }
 }
 
+/// Callbacks reporting the progress of the solver through the `log` crate
+///
+/// The periodic progress reports are logged at the `info` level, the restarts, clause GCs
+/// and reductions at the `debug` level.
+#[cfg(feature = "logging")]
+#[derive(Clone, Default)]
+pub struct Logging {
+    basic: Basic,
+}
+
+#[cfg(feature = "logging")]
+impl Callbacks for Logging {
+    fn stop(&self) -> bool {
+        self.basic.stop()
+    }
+    fn on_restart(&mut self) {
+        debug!("restart");
+    }
+    fn on_gc(&mut self, old: usize, new: usize) {
+        debug!("garbage collection: {} bytes => {} bytes", old / 8, new / 8);
+    }
+    fn on_reduce_db(&mut self, kept: usize, deleted: usize) {
+        debug!(
+            "reduce_db: kept {} learnt clauses, deleted {}",
+            kept, deleted
+        );
+    }
+    fn on_progress<F>(&mut self, f: F)
+    where
+        F: FnOnce() -> ProgressStatus,
+    {
+        if log_enabled!(log::Level::Info) {
+            let p = f();
+            info!(
+                "conflicts: {}, vars: {}, clauses: {} ({} lits), learnts: {}/{} ({:.0} lits/cl), \
+                 progress: {:.3} %",
+                p.conflicts,
+                p.dec_vars,
+                p.n_clauses,
+                p.n_clause_lits,
+                p.n_learnt,
+                p.max_learnt,
+                p.n_learnt_lits,
+                p.progress_estimate
+            );
+        }
+    }
+}
+
+#[cfg(feature = "logging")]
+impl Logging {
+    /// Allocate a new set of callbacks.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cast the logging CB into a basic CB.
+    pub fn basic_mut(&mut self) -> &mut Basic {
+        &mut self.basic
+    }
+}
+
 /// [`Callbacks`] that allow the solver to be asynchronously interrupted
+///
+/// The clones are interrupted along with the original.
+#[derive(Clone)]
 pub struct AsyncInterrupt(Arc<AtomicBool>);
 
 /// Handle used to interrupt a solver using [`AsyncInterrupt`]
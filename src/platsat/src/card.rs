@@ -0,0 +1,263 @@
+//! Cardinality constraints, propagated with counters
+use crate::{
+    clause::{lbool, LMap, Lit, Var},
+    core::TheoryArg,
+    native::Reasons,
+};
+use no_std_compat::prelude::v1::*;
+
+/// At most `k` of `lits` are true.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+struct Card {
+    lits: Vec<Lit>,
+    k: u32,
+    /// Number of true literals in the processed part of the trail.
+    count: u32,
+}
+
+/// Set of at-most-k constraints.
+///
+/// Each constraint counts its true literals as the trail is processed, and
+/// propagates its unassigned literals to `false` once the count reaches `k`.
+#[derive(Debug, Default, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub(crate) struct CardEngine {
+    cards: Vec<Card>,
+    /// Constraints in which each literal occurs.
+    occs: LMap<Vec<u32>>,
+    /// Length of the prefix of the trail that was counted.
+    processed: usize,
+    tmp: Vec<Lit>,
+}
+
+impl CardEngine {
+    pub(crate) fn contains_var(&self, v: Var) -> bool {
+        let occurs = |lit| self.occs.get(lit).is_some_and(|o| !o.is_empty());
+        occurs(Lit::new(v, true)) || occurs(Lit::new(v, false))
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.cards.is_empty()
+    }
+
+    /// Add the constraint "at most `k` of `lits` are true", where `lits` has no duplicate variables.
+    pub(crate) fn add(&mut self, lits: &[Lit], k: u32) {
+        let i = self.cards.len() as u32;
+        for &lit in lits {
+            self.occs.get_or_insert_with(lit, Vec::new).push(i);
+        }
+        self.cards.push(Card {
+            lits: lits.to_vec(),
+            k,
+            count: 0,
+        });
+        // count again from the start of the trail, which only contains level 0
+        for card in &mut self.cards {
+            card.count = 0;
+        }
+        self.processed = 0;
+    }
+
+    /// Count the newly true literals, and propagate or raise a conflict
+    /// in the constraints that reach their bound.
+    pub(crate) fn check(&mut self, acts: &mut TheoryArg, reasons: &mut Reasons) {
+        if self.cards.is_empty() {
+            return;
+        }
+        // propagations are appended to the trail, and processed in turn
+        while self.processed < acts.model().len() {
+            let lit = acts.model()[self.processed];
+            self.processed += 1;
+            let Some(occs) = self.occs.get(lit) else {
+                continue;
+            };
+            // count `lit` everywhere first, `backtrack` relies on it
+            for &i in occs {
+                self.cards[i as usize].count += 1;
+            }
+            for &i in occs {
+                let card = &self.cards[i as usize];
+                if card.count < card.k {
+                    continue;
+                }
+                // the true literals (all before the propagations on the trail) are the reason
+                self.tmp.clear();
+                self.tmp.extend(
+                    card.lits
+                        .iter()
+                        .filter(|&&q| acts.value_lit(q) == lbool::TRUE)
+                        .map(|&q| !q),
+                );
+                if card.count > card.k {
+                    acts.raise_conflict(&self.tmp, false);
+                    return;
+                }
+                for &q in &card.lits {
+                    if acts.value_lit(q) == lbool::UNDEF && !reasons.propagate(acts, !q, &self.tmp)
+                    {
+                        return;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Stop counting the literals of `trail[trail_len..]`.
+    pub(crate) fn backtrack(&mut self, trail: &[Lit], trail_len: usize) {
+        if self.processed <= trail_len {
+            return;
+        }
+        for &lit in &trail[trail_len..self.processed] {
+            for &i in self.occs.get(lit).into_iter().flatten() {
+                self.cards[i as usize].count -= 1;
+            }
+        }
+        self.processed = trail_len;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{test_util::Rng, *};
+    use no_std_compat::prelude::v1::*;
+
+    fn eval(lits: &[Lit], a: u32) -> impl Iterator<Item = bool> + '_ {
+        lits.iter()
+            .map(move |l| ((a >> l.var().idx()) & 1 == 1) == l.sign())
+    }
+
+    #[test]
+    fn test_at_most_k() {
+        let mut solver = BasicSolver::default();
+        let x: Vec<Lit> = (0..6)
+            .map(|_| Lit::new(solver.new_var_default(), true))
+            .collect();
+        assert!(solver.add_at_most_k(&x, 2));
+        assert_eq!(solver.solve_limited(&[x[0], x[1]]), lbool::TRUE);
+        assert!(x[2..].iter().all(|&l| solver.value_lit(l) == lbool::FALSE));
+        assert_eq!(solver.solve_limited(&[x[0], x[3], x[5]]), lbool::FALSE);
+        let mut core = solver.unsat_core().to_vec();
+        core.sort();
+        assert_eq!(core, [x[0], x[3], x[5]]);
+        // at least 5 of them
+        assert!(solver.add_at_most_k(&x.iter().map(|&l| !l).collect::<Vec<_>>(), 1));
+        assert_eq!(solver.solve_limited(&[]), lbool::FALSE);
+    }
+
+    #[test]
+    fn test_at_most_k_trivial() {
+        let mut solver = BasicSolver::default();
+        let x: Vec<Lit> = (0..4)
+            .map(|_| Lit::new(solver.new_var_default(), true))
+            .collect();
+        // all of them, and one less: a clause
+        assert!(solver.add_at_most_k(&x, 4));
+        assert!(solver.add_at_most_k(&x, 3));
+        assert_eq!(solver.solve_limited(&x[..3]), lbool::TRUE);
+        assert_eq!(solver.value_lit(x[3]), lbool::FALSE);
+        // none of them: units
+        assert!(solver.add_at_most_k(&x[2..], 0));
+        assert_eq!(solver.solve_limited(&[]), lbool::TRUE);
+        assert!(x[2..].iter().all(|&l| solver.value_lit(l) == lbool::FALSE));
+        assert!(!solver.add_at_most_k(&[!x[2], x[0]], 0));
+    }
+
+    #[test]
+    fn test_at_most_k_duplicates() {
+        let mut solver = BasicSolver::default();
+        let x: Vec<Lit> = (0..4)
+            .map(|_| Lit::new(solver.new_var_default(), true))
+            .collect();
+        // `x0` counts twice
+        assert!(solver.add_at_most_k(&[x[0], x[1], x[0], x[2]], 2));
+        assert_eq!(solver.solve_limited(&[x[0], x[1]]), lbool::FALSE);
+        assert_eq!(solver.solve_limited(&[x[1], x[2]]), lbool::TRUE);
+        assert_eq!(solver.solve_limited(&[x[0]]), lbool::TRUE);
+        // `x3, !x3` counts once
+        assert!(solver.add_at_most_k(&[x[3], x[1], !x[3], x[2]], 1));
+        assert_eq!(solver.solve_limited(&[]), lbool::TRUE);
+        assert!(x[1..3].iter().all(|&l| solver.value_lit(l) == lbool::FALSE));
+        assert!(!solver.add_at_most_k(&[x[3], !x[3], !x[3], x[3]], 1));
+
+        let mut solver = BasicSolver::default();
+        let x: Vec<Lit> = (0..9)
+            .map(|_| Lit::new(solver.new_var_default(), true))
+            .collect();
+        let mut lits = x.clone();
+        lits.push(!x[0]);
+        assert!(solver.add_at_most_one(&lits));
+        assert_eq!(solver.solve_limited(&[]), lbool::TRUE);
+        assert!(x[1..].iter().all(|&l| solver.value_lit(l) == lbool::FALSE));
+        assert!(solver.add_at_most_one(&[x[0], x[0], !x[1]]));
+        assert_eq!(solver.solve_limited(&[x[0]]), lbool::FALSE);
+        assert_eq!(solver.solve_limited(&[!x[0]]), lbool::TRUE);
+    }
+
+    #[test]
+    fn test_at_most_k_random() {
+        const N: u32 = 9;
+        let mut rng = Rng::new(17);
+        for _ in 0..100 {
+            let mut solver = BasicSolver::default();
+            let vars: Vec<Var> = (0..N).map(|_| solver.new_var_default()).collect();
+            let mut cards = vec![];
+            let mut xors = vec![];
+            let mut clauses = vec![];
+            for _ in 0..rng.next(4) + 1 {
+                let mut vs = vars.clone();
+                let lits: Vec<Lit> = (0..rng.next(N as u64 - 2) + 2)
+                    .map(|_| {
+                        let v = vs.remove(rng.next(vs.len() as u64) as usize);
+                        Lit::new(v, rng.next(2) == 0)
+                    })
+                    .collect();
+                let k = rng.next(lits.len() as u64) as usize;
+                solver.add_at_most_k(&lits, k as u32);
+                cards.push((lits, k));
+            }
+            for _ in 0..rng.next(3) {
+                let lits: Vec<Lit> = (0..rng.next(3) + 3)
+                    .map(|_| Lit::new(vars[rng.next(N as u64) as usize], true))
+                    .collect();
+                let parity = rng.next(2) == 0;
+                solver.add_xor_clause(&lits, parity);
+                xors.push((lits, parity));
+            }
+            for _ in 0..rng.next(6) {
+                let mut c: Vec<Lit> = (0..3)
+                    .map(|_| Lit::new(vars[rng.next(N as u64) as usize], rng.next(2) == 0))
+                    .collect();
+                clauses.push(c.clone());
+                solver.add_clause_reuse(&mut c);
+            }
+            let assumps: Vec<Lit> = (0..rng.next(4))
+                .map(|_| Lit::new(vars[rng.next(N as u64) as usize], rng.next(2) == 0))
+                .collect();
+            let is_model = |a: u32| {
+                cards
+                    .iter()
+                    .all(|(lits, k)| eval(lits, a).filter(|&b| b).count() <= *k)
+                    && xors
+                        .iter()
+                        .all(|(lits, parity)| eval(lits, a).fold(false, |x, b| x ^ b) == *parity)
+                    && clauses.iter().all(|c| eval(c, a).any(|b| b))
+            };
+            let sat = (0..1u32 << N).any(|a| is_model(a) && eval(&assumps, a).all(|b| b));
+            let res = solver.solve_limited(&assumps);
+            if sat {
+                assert_eq!(res, lbool::TRUE);
+                let a = vars
+                    .iter()
+                    .filter(|&&v| solver.value_var(v) == lbool::TRUE)
+                    .fold(0, |a, v| a | 1 << v.idx());
+                assert!(is_model(a));
+            } else {
+                assert_eq!(res, lbool::FALSE);
+                let core = solver.unsat_core().to_vec();
+                assert!(core.iter().all(|l| assumps.contains(l)));
+                assert!(!(0..1u32 << N).any(|a| is_model(a) && eval(&core, a).all(|b| b)));
+            }
+        }
+    }
+}
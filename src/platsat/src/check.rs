@@ -0,0 +1,1162 @@
+//! Checking of DRAT and LRAT proofs
+//!
+//! The proofs written by [`DratWriter`](crate::drat::DratWriter) and
+//! [`LratWriter`](crate::drat::LratWriter) (or by any other tool) can be validated
+//! against the original clauses, without an external checker:
+//!
+//! - [`check_drat`] checks each lemma of a DRAT proof by unit propagation (RUP), and
+//!   falls back on RAT on its first literal. It works either forward, checking every
+//!   lemma, or backward from the empty clause, checking only the lemmas it depends on.
+//! - [`LratChecker`] follows the hints of a LRAT proof, which needs no search at all.
+//!
+//! The checker has its own watched literals propagation rather than the one of
+//! [`Solver`](crate::Solver), so that a bug of the solver cannot hide in the check of
+//! its own proofs. As in `drat-trim`, the deletion of a clause that is the reason of a
+//! literal implied at level 0 is ignored, since solvers do not undo these literals.
+use no_std_compat::prelude::v1::*;
+use {
+    crate::{
+        clause::{lbool, LMap, Lit, VMap, Var},
+        intmap::IntMapBool,
+    },
+    std::{
+        collections::{BTreeMap, BTreeSet},
+        fmt, mem,
+    },
+};
+
+#[cfg(feature = "std")]
+pub use self::parse::{parse_cnf, parse_drat, parse_lrat};
+
+/// A step of a DRAT proof.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DratStep {
+    /// Addition of a lemma, RUP or RAT on its first literal.
+    Add(Vec<Lit>),
+    /// Deletion of a clause.
+    Delete(Vec<Lit>),
+}
+
+/// A step of a LRAT proof.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LratStep {
+    /// Addition of the clause `id`, which follows by unit propagation over the clauses
+    /// `hints`, in this order.
+    Add {
+        id: u64,
+        clause: Vec<Lit>,
+        hints: Vec<u64>,
+    },
+    /// Deletion of the clauses with these ids.
+    Delete(Vec<u64>),
+}
+
+/// How [`check_drat`] goes through the proof.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheckMode {
+    /// Check every lemma, in the order of the proof.
+    Forward,
+    /// Replay the proof up to the empty clause, then check only the lemmas used to
+    /// derive it, from the last one to the first one.
+    Backward,
+}
+
+/// Reason why a proof is rejected.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProofError {
+    /// The lemma of the step `step` (counted from `0`) is neither RUP nor RAT on its
+    /// first literal.
+    NotImplied { step: usize },
+    /// The proof does not derive the empty clause.
+    NotRefuted,
+    /// The clause `id` does not exist, or was deleted.
+    UnknownClause { id: u64 },
+    /// The id of the added clause `id` is already used.
+    DuplicateId { id: u64 },
+    /// The hint `hint` of the step adding the clause `id` is neither unit nor falsified.
+    NotUnit { id: u64, hint: u64 },
+    /// The hints of the step adding the clause `id` do not lead to a conflict.
+    NoConflict { id: u64 },
+}
+
+impl fmt::Display for ProofError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ProofError::NotImplied { step } => {
+                write!(f, "the lemma of step {} is neither RUP nor RAT", step)
+            }
+            ProofError::NotRefuted => write!(f, "the proof does not derive the empty clause"),
+            ProofError::UnknownClause { id } => write!(f, "unknown clause {}", id),
+            ProofError::DuplicateId { id } => write!(f, "clause id {} is already used", id),
+            ProofError::NotUnit { id, hint } => {
+                write!(f, "hint {} of clause {} is not unit", hint, id)
+            }
+            ProofError::NoConflict { id } => {
+                write!(f, "the hints of clause {} do not lead to a conflict", id)
+            }
+        }
+    }
+}
+
+impl core::error::Error for ProofError {}
+
+/// Id of no clause: the reason of the negated literals of the checked clause.
+const NONE: u32 = u32::MAX;
+
+/// Sorted literals of `c` without duplicates, or `None` if it is a tautology.
+fn normalize(c: &[Lit]) -> Option<Vec<Lit>> {
+    let mut lits = c.to_vec();
+    lits.sort_unstable();
+    lits.dedup();
+    // `x` and `!x` are adjacent once sorted
+    if lits.windows(2).any(|w| w[0] == !w[1]) {
+        None
+    } else {
+        Some(lits)
+    }
+}
+
+struct DbClause {
+    lits: Vec<Lit>,
+    active: bool,
+    /// Is it used to derive the empty clause? (backward checking)
+    core: bool,
+}
+
+/// Clauses with their watches, and a partial assignment.
+#[derive(Default)]
+struct Db {
+    clauses: Vec<DbClause>,
+    /// Clauses watching each literal, visited when it becomes false. Inactive clauses
+    /// and clauses that no longer watch the literal are removed lazily.
+    watches: LMap<Vec<u32>>,
+    /// Clauses with a single literal, which are never watched.
+    units: Vec<u32>,
+    /// Ids of the clauses, by literals, for the deletions.
+    index: BTreeMap<Vec<Lit>, Vec<u32>>,
+    vals: VMap<lbool>,
+    reasons: VMap<u32>,
+    trail: Vec<Lit>,
+    seen: IntMapBool<Var>,
+}
+
+/// What a step of a DRAT proof did to the clauses.
+enum Done {
+    Added(u32),
+    /// A tautology, which is not stored.
+    AddedNothing,
+    Deleted(u32),
+    Ignored,
+}
+
+impl Db {
+    #[inline]
+    fn value(&self, lit: Lit) -> lbool {
+        self.vals[lit.var()] ^ !lit.sign()
+    }
+
+    fn assign(&mut self, lit: Lit, reason: u32) {
+        self.vals[lit.var()] = lbool::new(lit.sign());
+        self.reasons[lit.var()] = reason;
+        self.trail.push(lit);
+    }
+
+    fn backtrack(&mut self, len: usize) {
+        for lit in self.trail.drain(len..) {
+            self.vals[lit.var()] = lbool::UNDEF;
+        }
+    }
+
+    /// Make room for the variables of `lits`.
+    fn reserve(&mut self, lits: &[Lit]) {
+        for &lit in lits {
+            self.vals.reserve(lit.var(), lbool::UNDEF);
+            self.reasons.reserve(lit.var(), NONE);
+            self.seen.reserve(lit.var());
+            self.watches.reserve_default(Lit::new(lit.var(), true));
+            self.watches.reserve_default(Lit::new(lit.var(), false));
+        }
+    }
+
+    /// Store the clause `lits`, which must be normalized, without activating it.
+    fn insert(&mut self, lits: Vec<Lit>) -> u32 {
+        self.reserve(&lits);
+        let id = self.clauses.len() as u32;
+        self.index.entry(lits.clone()).or_default().push(id);
+        self.clauses.push(DbClause {
+            lits,
+            active: false,
+            core: false,
+        });
+        id
+    }
+
+    fn activate(&mut self, id: u32) {
+        let c = &mut self.clauses[id as usize];
+        c.active = true;
+        if c.lits.len() == 1 {
+            self.units.push(id);
+        } else {
+            self.watches[c.lits[0]].push(id);
+            self.watches[c.lits[1]].push(id);
+        }
+    }
+
+    /// Active clause made of the literals `lits` (normalized), if any.
+    fn find(&self, lits: &[Lit]) -> Option<u32> {
+        let ids = self.index.get(lits)?;
+        ids.iter()
+            .rev()
+            .copied()
+            .find(|&id| self.clauses[id as usize].active)
+    }
+
+    /// Is the clause `id` the reason of a literal of the trail?
+    fn is_reason(&self, id: u32) -> bool {
+        let first = self.clauses[id as usize].lits[0];
+        self.value(first) == lbool::TRUE && self.reasons[first.var()] == id
+    }
+
+    /// Activate the new clause `id` at level 0, propagating it if it is unit; returns
+    /// `false` if the clauses are now inconsistent.
+    fn attach_top(&mut self, id: u32) -> bool {
+        // the literals that are not false are watched, true ones first
+        let mut lits = mem::take(&mut self.clauses[id as usize].lits);
+        lits.sort_by_key(|&lit| {
+            let value = self.value(lit);
+            if value == lbool::TRUE {
+                0
+            } else if value == lbool::UNDEF {
+                1
+            } else {
+                2
+            }
+        });
+        self.clauses[id as usize].lits = lits;
+        self.activate(id);
+        let lits = &self.clauses[id as usize].lits;
+        let first = lits[0];
+        if lits.len() > 1 && self.value(lits[1]) != lbool::FALSE {
+            return true;
+        }
+        let value = self.value(first);
+        if value == lbool::UNDEF {
+            let start = self.trail.len();
+            self.assign(first, id);
+            self.propagate(start).is_none()
+        } else {
+            value == lbool::TRUE
+        }
+    }
+
+    /// Propagate the literals of the trail from `qhead`, returns the conflicting
+    /// clause if any.
+    fn propagate(&mut self, mut qhead: usize) -> Option<u32> {
+        while qhead < self.trail.len() {
+            let false_lit = !self.trail[qhead];
+            qhead += 1;
+            let mut ws = mem::take(&mut self.watches[false_lit]);
+            let (mut i, mut j) = (0, 0);
+            let mut conflict = None;
+            while i < ws.len() {
+                let id = ws[i];
+                i += 1;
+                let c = &mut self.clauses[id as usize];
+                if !c.active || (c.lits[0] != false_lit && c.lits[1] != false_lit) {
+                    continue;
+                }
+                if c.lits[0] == false_lit {
+                    c.lits.swap(0, 1);
+                }
+                let vals = &self.vals;
+                let value = |lit: Lit| vals[lit.var()] ^ !lit.sign();
+                if value(c.lits[0]) == lbool::TRUE {
+                    ws[j] = id;
+                    j += 1;
+                    continue;
+                }
+                if let Some(k) = (2..c.lits.len()).find(|&k| value(c.lits[k]) != lbool::FALSE) {
+                    c.lits.swap(1, k);
+                    self.watches[c.lits[1]].push(id);
+                    continue;
+                }
+                ws[j] = id;
+                j += 1;
+                let first = c.lits[0];
+                if value(first) == lbool::FALSE {
+                    conflict = Some(id);
+                    while i < ws.len() {
+                        ws[j] = ws[i];
+                        j += 1;
+                        i += 1;
+                    }
+                } else {
+                    self.assign(first, id);
+                }
+            }
+            ws.truncate(j);
+            self.watches[false_lit] = ws;
+            if conflict.is_some() {
+                return conflict;
+            }
+        }
+        None
+    }
+
+    /// Assign the negation of `lits` and propagate, with the active unit clauses too if
+    /// `scratch` (the trail is then empty, otherwise it holds the level 0). Returns the
+    /// conflicting clause if any, `NONE` if `lits` is satisfied by the trail.
+    ///
+    /// The assignment is left for [`mark_core`](Self::mark_core), the caller backtracks.
+    fn rup(&mut self, lits: &[Lit], scratch: bool) -> Option<u32> {
+        // the lemma may have variables that no clause has
+        self.reserve(lits);
+        let start = self.trail.len();
+        for &lit in lits {
+            let value = self.value(lit);
+            if value == lbool::TRUE {
+                return Some(NONE);
+            } else if value == lbool::UNDEF {
+                self.assign(!lit, NONE);
+            }
+        }
+        if scratch {
+            for i in 0..self.units.len() {
+                let id = self.units[i];
+                let c = &self.clauses[id as usize];
+                if !c.active {
+                    continue;
+                }
+                let lit = c.lits[0];
+                let value = self.value(lit);
+                if value == lbool::FALSE {
+                    return Some(id);
+                } else if value == lbool::UNDEF {
+                    self.assign(lit, id);
+                }
+            }
+            self.units.retain(|&id| self.clauses[id as usize].active);
+        }
+        self.propagate(start)
+    }
+
+    /// Mark as core the clause `conflict` and the reasons of its literals, recursively.
+    fn mark_core(&mut self, conflict: u32) {
+        let mut stack = vec![conflict];
+        while let Some(id) = stack.pop() {
+            if id == NONE {
+                continue;
+            }
+            let c = &mut self.clauses[id as usize];
+            c.core = true;
+            for &lit in &c.lits {
+                if !self.seen[lit.var()] {
+                    self.seen.insert(lit.var());
+                    stack.push(self.reasons[lit.var()]);
+                }
+            }
+        }
+        for &lit in &self.trail {
+            self.seen.set(lit.var(), false);
+        }
+    }
+
+    /// Is the normalized clause `lits` RUP, or RAT on `pivot`? In `scratch` mode, the
+    /// clauses it depends on are marked as core.
+    fn implied(&mut self, lits: &[Lit], pivot: Option<Lit>, scratch: bool) -> bool {
+        let start = self.trail.len();
+        let conflict = self.rup(lits, scratch);
+        if let (Some(conflict), true) = (conflict, scratch) {
+            self.mark_core(conflict);
+        }
+        self.backtrack(start);
+        if conflict.is_some() {
+            return true;
+        }
+        let Some(pivot) = pivot else {
+            return false;
+        };
+        // every resolvent on `pivot` with an active clause must be RUP
+        let candidates: Vec<u32> = (0..self.clauses.len() as u32)
+            .filter(|&id| {
+                let c = &self.clauses[id as usize];
+                c.active && c.lits.contains(&!pivot)
+            })
+            .collect();
+        let mut resolvent = vec![];
+        for id in candidates {
+            resolvent.clear();
+            resolvent.extend_from_slice(lits);
+            let d = &self.clauses[id as usize].lits;
+            resolvent.extend(d.iter().copied().filter(|&lit| lit != !pivot));
+            let conflict = self.rup(&resolvent, scratch);
+            if let (Some(conflict), true) = (conflict, scratch) {
+                self.mark_core(conflict);
+                self.clauses[id as usize].core = true;
+            }
+            self.backtrack(start);
+            if conflict.is_none() {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// Check that the DRAT proof `proof` refutes the clauses `cnf`.
+///
+/// Returns the number of lemmas that were checked: all the lemmas up to the empty
+/// clause in [`CheckMode::Forward`], only the ones it depends on in
+/// [`CheckMode::Backward`]. The proof ends with the first lemma that leads to a
+/// conflict by unit propagation, the following steps are ignored.
+pub fn check_drat<C: AsRef<[Lit]>>(
+    cnf: &[C],
+    proof: &[DratStep],
+    mode: CheckMode,
+) -> Result<usize, ProofError> {
+    let forward = mode == CheckMode::Forward;
+    let mut db = Db::default();
+    for c in cnf {
+        let Some(lits) = normalize(c.as_ref()) else {
+            continue;
+        };
+        if lits.is_empty() {
+            return Ok(0);
+        }
+        let id = db.insert(lits);
+        if !db.attach_top(id) {
+            return Ok(0);
+        }
+    }
+    // replay the proof at level 0, up to the empty clause
+    let mut checked = 0;
+    let mut done = vec![];
+    let mut end = None;
+    for (step, s) in proof.iter().enumerate() {
+        match s {
+            DratStep::Add(c) => {
+                let lits = normalize(c);
+                if forward {
+                    // a tautology is trivially implied
+                    if let Some(lits) = &lits {
+                        if !db.implied(lits, c.first().copied(), false) {
+                            return Err(ProofError::NotImplied { step });
+                        }
+                    }
+                    checked += 1;
+                }
+                match lits {
+                    None => done.push(Done::AddedNothing),
+                    Some(lits) if lits.is_empty() => {
+                        end = Some(step);
+                        break;
+                    }
+                    Some(lits) => {
+                        let id = db.insert(lits);
+                        done.push(Done::Added(id));
+                        if !db.attach_top(id) {
+                            end = Some(step);
+                            break;
+                        }
+                    }
+                }
+            }
+            DratStep::Delete(c) => {
+                let id = normalize(c).and_then(|lits| db.find(&lits));
+                match id {
+                    Some(id) if !db.is_reason(id) => {
+                        db.clauses[id as usize].active = false;
+                        done.push(Done::Deleted(id));
+                    }
+                    _ => done.push(Done::Ignored),
+                }
+            }
+        }
+    }
+    let end = end.ok_or(ProofError::NotRefuted)?;
+    if forward {
+        return Ok(checked);
+    }
+    // the conflict of the empty clause, without the level 0
+    db.backtrack(0);
+    let Some(conflict) = db.rup(&[], true) else {
+        return Err(ProofError::NotImplied { step: end });
+    };
+    db.mark_core(conflict);
+    db.backtrack(0);
+    if done.len() == end {
+        // the last step is the empty clause, not stored
+        checked += 1;
+    }
+    for (step, d) in done.iter().enumerate().rev() {
+        match *d {
+            Done::Added(id) => {
+                db.clauses[id as usize].active = false;
+                if db.clauses[id as usize].core {
+                    let lits = mem::take(&mut db.clauses[id as usize].lits);
+                    let pivot = match &proof[step] {
+                        DratStep::Add(c) => c.first().copied(),
+                        DratStep::Delete(_) => unreachable!(),
+                    };
+                    let ok = db.implied(&lits, pivot, true);
+                    db.clauses[id as usize].lits = lits;
+                    if !ok {
+                        return Err(ProofError::NotImplied { step });
+                    }
+                    checked += 1;
+                }
+            }
+            Done::Deleted(id) => db.activate(id),
+            Done::AddedNothing | Done::Ignored => (),
+        }
+    }
+    Ok(checked)
+}
+
+/// Checker of LRAT proofs, step by step.
+///
+/// The original clauses are numbered from `1`, in the order they are given to
+/// [`add_original`](Self::add_original), as [`LratWriter`](crate::drat::LratWriter)
+/// numbers them.
+#[derive(Default)]
+pub struct LratChecker {
+    clauses: BTreeMap<u64, Vec<Lit>>,
+    num_original: u64,
+    vals: VMap<lbool>,
+    assigned: Vec<Var>,
+    refuted: bool,
+}
+
+impl LratChecker {
+    /// A checker without clauses.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add the next clause of the original problem.
+    pub fn add_original(&mut self, c: &[Lit]) {
+        self.num_original += 1;
+        self.refuted |= c.is_empty();
+        self.clauses.insert(self.num_original, c.to_vec());
+    }
+
+    /// Has the empty clause been derived (or given)?
+    pub fn is_refuted(&self) -> bool {
+        self.refuted
+    }
+
+    #[inline]
+    fn value(&self, lit: Lit) -> lbool {
+        self.vals
+            .get(lit.var())
+            .map_or(lbool::UNDEF, |&val| val ^ !lit.sign())
+    }
+
+    fn assign(&mut self, lit: Lit) {
+        self.vals
+            .insert(lit.var(), lbool::new(lit.sign()), lbool::UNDEF);
+        self.assigned.push(lit.var());
+    }
+
+    /// Does the negation of `c` lead to a conflict by propagating `hints`?
+    fn propagate(&mut self, id: u64, c: &[Lit], hints: &[u64]) -> Result<(), ProofError> {
+        for &lit in c {
+            let value = self.value(lit);
+            if value == lbool::TRUE {
+                // `c` is a tautology
+                return Ok(());
+            } else if value == lbool::UNDEF {
+                self.assign(!lit);
+            }
+        }
+        for &hint in hints {
+            let d = self
+                .clauses
+                .get(&hint)
+                .ok_or(ProofError::UnknownClause { id: hint })?;
+            let mut unit = None;
+            for &lit in d {
+                let value = self.value(lit);
+                if value == lbool::TRUE || (value == lbool::UNDEF && unit.is_some_and(|u| u != lit))
+                {
+                    return Err(ProofError::NotUnit { id, hint });
+                } else if value == lbool::UNDEF {
+                    unit = Some(lit);
+                }
+            }
+            match unit {
+                Some(lit) => self.assign(lit),
+                None => return Ok(()),
+            }
+        }
+        Err(ProofError::NoConflict { id })
+    }
+
+    /// Add the clause `id`, which must follow by unit propagation over `hints`.
+    pub fn add_clause(&mut self, id: u64, c: &[Lit], hints: &[u64]) -> Result<(), ProofError> {
+        if self.clauses.contains_key(&id) {
+            return Err(ProofError::DuplicateId { id });
+        }
+        let res = self.propagate(id, c, hints);
+        for v in self.assigned.drain(..) {
+            self.vals[v] = lbool::UNDEF;
+        }
+        res?;
+        self.refuted |= c.is_empty();
+        self.clauses.insert(id, c.to_vec());
+        Ok(())
+    }
+
+    /// Delete the clause `id`.
+    pub fn delete_clause(&mut self, id: u64) -> Result<(), ProofError> {
+        match self.clauses.remove(&id) {
+            Some(_) => Ok(()),
+            None => Err(ProofError::UnknownClause { id }),
+        }
+    }
+
+    /// Check the step `step`.
+    pub fn step(&mut self, step: &LratStep) -> Result<(), ProofError> {
+        match step {
+            LratStep::Add { id, clause, hints } => self.add_clause(*id, clause, hints),
+            LratStep::Delete(ids) => ids.iter().try_for_each(|&id| self.delete_clause(id)),
+        }
+    }
+}
+
+/// Check that the LRAT proof `proof` refutes the clauses `cnf`.
+pub fn check_lrat<C: AsRef<[Lit]>>(cnf: &[C], proof: &[LratStep]) -> Result<(), ProofError> {
+    let mut checker = LratChecker::new();
+    for c in cnf {
+        checker.add_original(c.as_ref());
+    }
+    for step in proof {
+        if checker.is_refuted() {
+            break;
+        }
+        checker.step(step)?;
+    }
+    if checker.is_refuted() {
+        Ok(())
+    } else {
+        Err(ProofError::NotRefuted)
+    }
+}
+
+/// A LRAT proof reduced to the steps the empty clause depends on, see [`trim_lrat`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrimmedProof {
+    /// Indices of the original clauses used by the proof, in increasing order: a
+    /// clausal unsat core.
+    pub core: Vec<usize>,
+    /// Refutation of the clauses of `core`, which are numbered from `1` in this order,
+    /// followed by the lemmas. Every clause is deleted after its last use.
+    pub proof: Vec<LratStep>,
+}
+
+/// Trim the LRAT proof `proof` of `num_original` clauses, by a backward pass from the
+/// empty clause over the hints, and find the original clauses it uses.
+///
+/// The proof is not checked, see [`check_lrat`]; the steps after the first empty
+/// clause are ignored.
+pub fn trim_lrat(num_original: usize, proof: &[LratStep]) -> Result<TrimmedProof, ProofError> {
+    let is_empty = |s: &LratStep| matches!(s, LratStep::Add { clause, .. } if clause.is_empty());
+    let end = proof
+        .iter()
+        .position(is_empty)
+        .ok_or(ProofError::NotRefuted)?;
+    // clauses used by the steps kept so far, which are not kept yet themselves
+    let mut needed = BTreeSet::new();
+    let mut kept = vec![];
+    for (i, step) in proof[..=end].iter().enumerate().rev() {
+        if let LratStep::Add { id, hints, .. } = step {
+            if i == end || needed.remove(id) {
+                kept.push(i);
+                needed.extend(hints.iter().copied());
+            }
+        }
+    }
+    kept.reverse();
+    // what remains is made of original clauses
+    let mut new_ids = BTreeMap::new();
+    let mut core = vec![];
+    for id in needed {
+        if id == 0 || id > num_original as u64 {
+            return Err(ProofError::UnknownClause { id });
+        }
+        core.push(id as usize - 1);
+        new_ids.insert(id, core.len() as u64);
+    }
+    let mut steps = vec![];
+    // position in `steps` of the last use of each clause, by new id
+    let mut last_use = BTreeMap::new();
+    for &i in &kept {
+        let LratStep::Add { id, clause, hints } = &proof[i] else {
+            unreachable!()
+        };
+        let hints: Vec<u64> = hints.iter().map(|h| new_ids[h]).collect();
+        for &h in &hints {
+            last_use.insert(h, steps.len());
+        }
+        let new_id = (core.len() + steps.len() + 1) as u64;
+        new_ids.insert(*id, new_id);
+        steps.push((new_id, clause.clone(), hints));
+    }
+    let mut deletions = vec![vec![]; steps.len()];
+    for (id, pos) in last_use {
+        deletions[pos].push(id);
+    }
+    let mut trimmed = vec![];
+    let last = steps.len() - 1;
+    for (pos, ((id, clause, hints), ids)) in steps.into_iter().zip(deletions).enumerate() {
+        trimmed.push(LratStep::Add { id, clause, hints });
+        if !ids.is_empty() && pos < last {
+            trimmed.push(LratStep::Delete(ids));
+        }
+    }
+    Ok(TrimmedProof {
+        core,
+        proof: trimmed,
+    })
+}
+
+#[cfg(feature = "std")]
+mod parse {
+    use {
+        super::*,
+        crate::dimacs::{next_byte, parse_int, skip_line, skip_whitespace, ParseError},
+        std::io::BufRead,
+    };
+
+    /// Literal of the DIMACS integer `i`, which is not `0`.
+    fn int_lit(i: i32) -> Lit {
+        Lit::new(Var::from_idx(i.unsigned_abs() - 1), i > 0)
+    }
+
+    /// Read integers up to the next `0`.
+    fn read_ints<R: BufRead>(input: &mut R, out: &mut Vec<i32>) -> Result<(), ParseError> {
+        out.clear();
+        loop {
+            match parse_int(input)? {
+                0 => return Ok(()),
+                i => out.push(i),
+            }
+        }
+    }
+
+    fn read_lits<R: BufRead>(input: &mut R, buf: &mut Vec<i32>) -> Result<Vec<Lit>, ParseError> {
+        read_ints(input, buf)?;
+        Ok(buf.iter().map(|&i| int_lit(i)).collect())
+    }
+
+    fn read_ids<R: BufRead>(input: &mut R, buf: &mut Vec<i32>) -> Result<Vec<u64>, ParseError> {
+        read_ints(input, buf)?;
+        // negative hints (RAT steps) are not supported
+        match buf.iter().find(|&&i| i < 0) {
+            Some(_) => Err(ParseError::UnexpectedChar('-')),
+            None => Ok(buf.iter().map(|&i| i as u64).collect()),
+        }
+    }
+
+    fn read_varint<R: BufRead>(input: &mut R) -> Result<u64, ParseError> {
+        let mut u = 0u64;
+        let mut shift = 0;
+        loop {
+            let b = next_byte(input)?.ok_or(ParseError::UnexpectedEof)?;
+            input.consume(1);
+            if shift > 63 {
+                return Err(ParseError::IntOverflow);
+            }
+            u |= ((b & 127) as u64) << shift;
+            if b < 128 {
+                return Ok(u);
+            }
+            shift += 7;
+        }
+    }
+
+    /// Read varints up to the next `0`.
+    fn read_varints<R: BufRead>(input: &mut R) -> Result<Vec<u64>, ParseError> {
+        let mut out = vec![];
+        loop {
+            match read_varint(input)? {
+                0 => return Ok(out),
+                u => out.push(u),
+            }
+        }
+    }
+
+    fn varint_lit(u: u64) -> Result<Lit, ParseError> {
+        if u < 2 || u / 2 > i32::MAX as u64 {
+            return Err(ParseError::IntOverflow);
+        }
+        Ok(Lit::new(Var::from_idx(u as u32 / 2 - 1), u & 1 == 0))
+    }
+
+    fn varint_id(u: u64) -> Result<u64, ParseError> {
+        // ids are encoded as signed integers, negative ones are RAT hints
+        match u % 2 {
+            0 => Ok(u / 2),
+            _ => Err(ParseError::UnexpectedChar('-')),
+        }
+    }
+
+    /// Does `input` start with a binary proof? Binary proofs start with `a` or contain
+    /// bytes that are not printable, text proofs are made of integers and `d`.
+    fn is_binary<R: BufRead>(input: &mut R) -> Result<bool, ParseError> {
+        let head = input.fill_buf()?;
+        Ok(head.first() == Some(&b'a')
+            || head
+                .iter()
+                .take(32)
+                .any(|&b| !(b.is_ascii_graphic() || b.is_ascii_whitespace())))
+    }
+
+    /// Read the clauses of the DIMACS file `input`, whose header is optional.
+    pub fn parse_cnf<R: BufRead>(input: &mut R) -> Result<Vec<Vec<Lit>>, ParseError> {
+        let mut clauses = vec![];
+        let mut buf = vec![];
+        loop {
+            skip_whitespace(input)?;
+            match next_byte(input)? {
+                None => return Ok(clauses),
+                Some(b'c') | Some(b'p') => skip_line(input)?,
+                Some(_) => clauses.push(read_lits(input, &mut buf)?),
+            }
+        }
+    }
+
+    /// Read the DRAT proof `input`, in the text or binary format (which is detected).
+    pub fn parse_drat<R: BufRead>(input: &mut R) -> Result<Vec<DratStep>, ParseError> {
+        let mut steps = vec![];
+        if is_binary(input)? {
+            while let Some(b) = next_byte(input)? {
+                input.consume(1);
+                let lits = read_varints(input)?.into_iter().map(varint_lit);
+                let lits = lits.collect::<Result<_, _>>()?;
+                steps.push(match b {
+                    b'a' => DratStep::Add(lits),
+                    b'd' => DratStep::Delete(lits),
+                    _ => return Err(ParseError::UnexpectedChar(b as char)),
+                });
+            }
+            return Ok(steps);
+        }
+        let mut buf = vec![];
+        loop {
+            skip_whitespace(input)?;
+            match next_byte(input)? {
+                None => return Ok(steps),
+                Some(b'c') => skip_line(input)?,
+                Some(b'd') => {
+                    input.consume(1);
+                    steps.push(DratStep::Delete(read_lits(input, &mut buf)?));
+                }
+                Some(_) => steps.push(DratStep::Add(read_lits(input, &mut buf)?)),
+            }
+        }
+    }
+
+    /// Read the LRAT proof `input`, in the text or binary format (which is detected).
+    pub fn parse_lrat<R: BufRead>(input: &mut R) -> Result<Vec<LratStep>, ParseError> {
+        let mut steps = vec![];
+        if is_binary(input)? {
+            while let Some(b) = next_byte(input)? {
+                input.consume(1);
+                steps.push(match b {
+                    b'a' => {
+                        let id = varint_id(read_varint(input)?)?;
+                        let lits = read_varints(input)?.into_iter().map(varint_lit);
+                        let clause = lits.collect::<Result<_, _>>()?;
+                        let hints = read_varints(input)?.into_iter().map(varint_id);
+                        let hints = hints.collect::<Result<_, _>>()?;
+                        LratStep::Add { id, clause, hints }
+                    }
+                    b'd' => {
+                        let ids = read_varints(input)?.into_iter().map(varint_id);
+                        LratStep::Delete(ids.collect::<Result<_, _>>()?)
+                    }
+                    _ => return Err(ParseError::UnexpectedChar(b as char)),
+                });
+            }
+            return Ok(steps);
+        }
+        let mut buf = vec![];
+        loop {
+            skip_whitespace(input)?;
+            match next_byte(input)? {
+                None => return Ok(steps),
+                Some(b'c') => skip_line(input)?,
+                Some(_) => {
+                    let id = parse_int(input)?;
+                    if id <= 0 {
+                        return Err(ParseError::UnexpectedChar('-'));
+                    }
+                    skip_whitespace(input)?;
+                    if next_byte(input)? == Some(b'd') {
+                        input.consume(1);
+                        steps.push(LratStep::Delete(read_ids(input, &mut buf)?));
+                    } else {
+                        let clause = read_lits(input, &mut buf)?;
+                        let hints = read_ids(input, &mut buf)?;
+                        let id = id as u64;
+                        steps.push(LratStep::Add { id, clause, hints });
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod test {
+    use super::*;
+    use crate::{drat::DratWriter, drat::LratWriter, lbool, BasicSolver, SolverInterface};
+    use std::sync::{Arc, Mutex};
+
+    /// Shared buffer, so the test can read the proof after the solver took ownership.
+    #[derive(Clone, Default)]
+    struct Shared(Arc<Mutex<Vec<u8>>>);
+
+    impl std::io::Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// `p` pigeons in `p - 1` holes.
+    fn pigeons(p: i32) -> Vec<Vec<Lit>> {
+        let h = p - 1;
+        let x = |i: i32, j: i32| int_lit(1 + h * i + j);
+        let mut cnf = vec![];
+        for i in 0..p {
+            cnf.push((0..h).map(|j| x(i, j)).collect());
+        }
+        for j in 0..h {
+            for i in 0..p {
+                for k in 0..i {
+                    cnf.push(vec![!x(i, j), !x(k, j)]);
+                }
+            }
+        }
+        cnf
+    }
+
+    fn int_lit(i: i32) -> Lit {
+        Lit::new(Var::from_idx(i.unsigned_abs() - 1), i > 0)
+    }
+
+    /// Proof of the refutation of `cnf` by the solver, in the given format.
+    fn refutation(cnf: &[Vec<Lit>], lrat: bool, binary: bool) -> Vec<u8> {
+        let mut sat = BasicSolver::default();
+        let buf = Shared::default();
+        if lrat {
+            sat.set_lrat_output(buf.clone(), binary);
+        } else {
+            sat.set_drat_output(buf.clone(), binary);
+        }
+        for c in cnf {
+            for lit in c {
+                sat.var_of_int(lit.var().idx());
+            }
+            sat.add_clause_reuse(&mut c.clone());
+        }
+        assert_eq!(sat.solve_limited(&[]), lbool::FALSE);
+        sat.flush_proof().unwrap();
+        let proof = buf.0.lock().unwrap().clone();
+        proof
+    }
+
+    #[test]
+    fn test_check_solver_drat() {
+        let cnf = pigeons(6);
+        for binary in [false, true] {
+            let proof = parse_drat(&mut &refutation(&cnf, false, binary)[..]).unwrap();
+            let num_lemmas = proof
+                .iter()
+                .filter(|s| matches!(s, DratStep::Add(_)))
+                .count();
+            let forward = check_drat(&cnf, &proof, CheckMode::Forward).unwrap();
+            let backward = check_drat(&cnf, &proof, CheckMode::Backward).unwrap();
+            assert!(forward <= num_lemmas);
+            assert!(0 < backward && backward <= forward);
+            // without the pigeon clauses, the lemmas are no longer implied
+            let weaker = &cnf[1..];
+            assert!(check_drat(weaker, &proof, CheckMode::Forward).is_err());
+            assert!(check_drat(weaker, &proof, CheckMode::Backward).is_err());
+            // without the end of the proof
+            let truncated = &proof[..proof.len() / 2];
+            let res = check_drat(&cnf, truncated, CheckMode::Forward);
+            assert_eq!(res, Err(ProofError::NotRefuted));
+        }
+    }
+
+    #[test]
+    fn test_check_drat_rat() {
+        // all the clauses over x1, x2, x4, then `x3 <-> x1 & x2`, which is only RAT
+        let x = |i: i32| int_lit(i);
+        let mut cnf = vec![];
+        for signs in 0..8 {
+            let lit = |i: i32, bit: i32| x(if signs & bit == 0 { i } else { -i });
+            cnf.push(vec![lit(1, 1), lit(2, 2), lit(4, 4)]);
+        }
+        cnf.push(vec![x(-5), x(6)]);
+        let proof = vec![
+            DratStep::Add(vec![x(-3), x(1)]),
+            DratStep::Add(vec![x(-3), x(2)]),
+            DratStep::Add(vec![x(3), x(-1), x(-2)]),
+            DratStep::Add(vec![x(1), x(2)]),
+            DratStep::Add(vec![x(1), x(-2)]),
+            DratStep::Add(vec![x(1)]),
+            DratStep::Add(vec![x(2)]),
+            DratStep::Add(vec![]),
+        ];
+        assert_eq!(check_drat(&cnf, &proof, CheckMode::Forward), Ok(7));
+        assert_eq!(check_drat(&cnf, &proof, CheckMode::Backward), Ok(4));
+        // `x5` is neither RUP nor RAT, but the refutation does not use it
+        let mut bad = proof.clone();
+        bad[1] = DratStep::Add(vec![x(5)]);
+        let res = check_drat(&cnf, &bad, CheckMode::Forward);
+        assert_eq!(res, Err(ProofError::NotImplied { step: 1 }));
+        assert_eq!(check_drat(&cnf, &bad, CheckMode::Backward), Ok(4));
+        // the deletion removes a clause needed by `x1 | x2`
+        let mut bad = proof.clone();
+        bad.insert(3, DratStep::Delete(vec![x(4), x(2), x(1)]));
+        for mode in [CheckMode::Forward, CheckMode::Backward] {
+            let res = check_drat(&cnf, &bad, mode);
+            assert_eq!(res, Err(ProofError::NotImplied { step: 4 }));
+        }
+    }
+
+    #[test]
+    fn test_check_drat_top_level() {
+        let x = |i: i32| int_lit(i);
+        // `x3` is only implied by the second clause once `x2` is false
+        let cnf = vec![
+            vec![x(1)],
+            vec![x(-1), x(2), x(3)],
+            vec![x(-2)],
+            vec![x(-3), x(4)],
+            vec![x(-3), x(-4)],
+        ];
+        // `x5` occurs in no clause
+        let proof = vec![DratStep::Add(vec![x(5), x(1)]), DratStep::Add(vec![])];
+        for mode in [CheckMode::Forward, CheckMode::Backward] {
+            assert!(check_drat(&cnf, &proof, mode).is_ok());
+        }
+    }
+
+    #[test]
+    fn test_check_solver_lrat() {
+        let cnf = pigeons(5);
+        for binary in [false, true] {
+            let proof = parse_lrat(&mut &refutation(&cnf, true, binary)[..]).unwrap();
+            check_lrat(&cnf, &proof).unwrap();
+            assert!(check_lrat(&cnf[1..], &proof).is_err());
+        }
+        // repeated literals, false at level 0
+        let x = |i: i32| int_lit(i);
+        let cnf = vec![
+            vec![x(-2)],
+            vec![x(1), x(2), x(2), x(1), x(2)],
+            vec![x(-1), x(3), x(3)],
+            vec![x(-1), x(-3)],
+        ];
+        let proof = parse_lrat(&mut &refutation(&cnf, true, false)[..]).unwrap();
+        check_lrat(&cnf, &proof).unwrap();
+        // round trip of the writers and parsers
+        let [a, b] = [int_lit(1), int_lit(2)];
+        let mut w = LratWriter::new(vec![]);
+        use crate::drat::ProofOutput;
+        w.add_clause_with_hints(5, &[a, !b], &[1, 3]);
+        w.delete_clause_with_id(3, &[!a]);
+        let expected = vec![
+            LratStep::Add {
+                id: 5,
+                clause: vec![a, !b],
+                hints: vec![1, 3],
+            },
+            LratStep::Delete(vec![3]),
+        ];
+        assert_eq!(parse_lrat(&mut &w.into_inner()[..]).unwrap(), expected);
+        let mut w = DratWriter::new_binary(vec![]);
+        w.add_clause(&[a, !b]);
+        w.delete_clause(&[a]);
+        let expected = vec![DratStep::Add(vec![a, !b]), DratStep::Delete(vec![a])];
+        assert_eq!(parse_drat(&mut &w.into_inner()[..]).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_trim_lrat() {
+        // pigeons, and satisfiable clauses that the refutation does not need
+        let mut cnf = pigeons(5);
+        let num_pigeon_clauses = cnf.len();
+        for i in 30..40 {
+            cnf.insert(3, vec![int_lit(i), int_lit(i + 1)]);
+            cnf.push(vec![int_lit(-i), int_lit(-i - 1)]);
+        }
+        let proof = parse_lrat(&mut &refutation(&cnf, true, false)[..]).unwrap();
+        let trimmed = trim_lrat(cnf.len(), &proof).unwrap();
+        assert!(trimmed.core.len() <= num_pigeon_clauses);
+        assert!(trimmed
+            .core
+            .iter()
+            .all(|&i| cnf[i].iter().all(|l| l.var().idx() < 20)));
+        let num_added = |p: &[LratStep]| {
+            p.iter()
+                .filter(|s| matches!(s, LratStep::Add { .. }))
+                .count()
+        };
+        assert!(num_added(&trimmed.proof) <= num_added(&proof));
+        let core: Vec<&[Lit]> = trimmed.core.iter().map(|&i| &cnf[i][..]).collect();
+        check_lrat(&core, &trimmed.proof).unwrap();
+        // the core is unsatisfiable, and the trimmed proof needs all of it
+        assert!(check_lrat(&core[1..], &trimmed.proof).is_err());
+        let mut sat = BasicSolver::default();
+        for c in &core {
+            for lit in c.iter() {
+                sat.var_of_int(lit.var().idx());
+            }
+            sat.add_clause_reuse(&mut c.to_vec());
+        }
+        assert_eq!(sat.solve_limited(&[]), lbool::FALSE);
+        assert_eq!(
+            trim_lrat(cnf.len(), &proof[..1]),
+            Err(ProofError::NotRefuted)
+        );
+    }
+
+    #[test]
+    fn test_check_lrat_errors() {
+        let [a, b] = [int_lit(1), int_lit(2)];
+        let cnf = vec![vec![a, b], vec![a, !b], vec![!a, b], vec![!a, !b]];
+        let mut checker = LratChecker::new();
+        for c in &cnf {
+            checker.add_original(c);
+        }
+        assert_eq!(
+            checker.add_clause(5, &[a], &[1]),
+            Err(ProofError::NoConflict { id: 5 })
+        );
+        assert_eq!(
+            checker.add_clause(5, &[], &[1, 2]),
+            Err(ProofError::NotUnit { id: 5, hint: 1 })
+        );
+        assert_eq!(
+            checker.add_clause(1, &[a], &[1, 2]),
+            Err(ProofError::DuplicateId { id: 1 })
+        );
+        checker.add_clause(5, &[a], &[1, 2]).unwrap();
+        checker.delete_clause(1).unwrap();
+        assert_eq!(
+            checker.add_clause(6, &[b], &[1]),
+            Err(ProofError::UnknownClause { id: 1 })
+        );
+        assert!(!checker.is_refuted());
+        checker.add_clause(6, &[], &[5, 3, 4]).unwrap();
+        assert!(checker.is_refuted());
+    }
+}
@@ -18,18 +18,21 @@ NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FO
 DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT
 OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
 **************************************************************************************************/
-use bytemuck::{must_cast, must_cast_mut, must_cast_ref, must_cast_slice, Pod, Zeroable};
+use bytemuck::{
+    must_cast, must_cast_mut, must_cast_ref, must_cast_slice, must_cast_slice_mut, Pod, Zeroable,
+};
 use no_std_compat::prelude::v1::*;
 use {
     crate::{
         alloc::{self, RegionAllocator},
         intmap::{AsIndex, IntMap, IntMapBool, IntSet},
     },
-    std::{fmt, iter::DoubleEndedIterator, ops},
+    std::{fmt, iter::DoubleEndedIterator, mem, ops},
 };
 
 #[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 #[repr(transparent)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Var(u32);
 
 impl fmt::Debug for Var {
@@ -75,6 +78,7 @@ pub type VMap<V> = IntMap<Var, V>;
 
 #[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Pod, Zeroable)]
 #[repr(transparent)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Lit(u32);
 
 impl Lit {
@@ -167,8 +171,9 @@ pub type LMap<V> = IntMap<Lit, V>;
 pub type LSet = IntSet<Lit>;
 
 #[allow(non_camel_case_types)]
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Default)]
 /// A ternary boolean (true, false, undefined) used for partial assignments.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct lbool(u8);
 
 impl fmt::Debug for lbool {
@@ -185,11 +190,6 @@ impl fmt::Debug for lbool {
         }
     }
 }
-impl Default for lbool {
-    fn default() -> Self {
-        lbool(0)
-    }
-}
 
 impl lbool {
     pub const TRUE: lbool = lbool(0);
@@ -309,6 +309,7 @@ pub(crate) struct ClauseRef<'a> {
     header: ClauseHeader,
     data: &'a [ClauseData],
     extra: Option<ClauseData>,
+    lbd: Option<ClauseData>,
 }
 #[derive(Debug)]
 /// A mutable reference to some clause, with a temporary lifetime
@@ -316,6 +317,7 @@ pub(crate) struct ClauseMut<'a> {
     header: &'a mut ClauseHeader,
     data: &'a mut [ClauseData],
     extra: Option<&'a mut ClauseData>,
+    lbd: Option<&'a mut ClauseData>,
 }
 
 impl<'a, 'b> PartialEq<ClauseRef<'b>> for ClauseRef<'a> {
@@ -351,9 +353,22 @@ impl<'a> ClauseRef<'a> {
         debug_assert!(self.has_extra());
         self.extra.expect("no extra field").f32()
     }
+    /// Literal block distance of a learnt clause, the number of distinct levels of
+    /// its literals when it was last used in conflict analysis.
+    #[inline(always)]
+    pub fn lbd(&self) -> u32 {
+        debug_assert!(self.learnt());
+        self.lbd.expect("no lbd field").0 & !LBD_USED
+    }
+    /// Was this learnt clause used in conflict analysis since the flag was last reset?
+    #[inline(always)]
+    pub fn used(&self) -> bool {
+        debug_assert!(self.learnt());
+        self.lbd.expect("no lbd field").0 & LBD_USED != 0
+    }
     #[inline(always)]
     pub fn lits(&self) -> &'a [Lit] {
-        must_cast_slice(&self.data)
+        must_cast_slice(self.data)
     }
     #[inline(always)]
     pub fn iter(&self) -> impl DoubleEndedIterator<Item = &'a Lit> {
@@ -391,28 +406,28 @@ impl<T: ClauseIterable> display::Print for T {
 impl<'a> ClauseIterable for ClauseRef<'a> {
     type Item = Lit;
     fn items(&self) -> &[Self::Item] {
-        must_cast_slice(&self.data)
+        must_cast_slice(self.data)
     }
 }
 
 impl<'a> ClauseIterable for ClauseMut<'a> {
     type Item = Lit;
     fn items(&self) -> &[Self::Item] {
-        must_cast_slice(&self.data)
+        must_cast_slice(self.data)
     }
 }
 
-impl<'a> ClauseIterable for &'a [Lit] {
+impl ClauseIterable for &[Lit] {
     type Item = Lit;
     fn items(&self) -> &[Self::Item] {
-        &self
+        self
     }
 }
 
 impl ClauseIterable for Vec<Lit> {
     type Item = Lit;
     fn items(self: &Vec<Lit>) -> &[Self::Item] {
-        &self
+        self
     }
 }
 
@@ -429,10 +444,6 @@ impl<'a> ClauseMut<'a> {
         self.header.has_extra()
     }
     #[inline(always)]
-    pub fn reloced(&self) -> bool {
-        self.header.reloced()
-    }
-    #[inline(always)]
     pub fn size(&self) -> u32 {
         self.data.len() as u32
     }
@@ -442,10 +453,6 @@ impl<'a> ClauseMut<'a> {
         self.header.set_mark(mark);
     }
     #[inline(always)]
-    pub fn set_reloced(&mut self, reloced: bool) {
-        self.header.set_reloced(reloced);
-    }
-    #[inline(always)]
     pub fn activity(&self) -> f32 {
         debug_assert!(self.has_extra());
         self.extra.as_ref().expect("no extra field").f32()
@@ -455,39 +462,40 @@ impl<'a> ClauseMut<'a> {
         debug_assert!(self.has_extra());
         *self.extra.as_mut().expect("no extra field").f32_mut() = activity;
     }
-    pub fn relocation(&self) -> CRef {
-        debug_assert!(self.reloced());
-        self.data[0].cref()
+    #[inline(always)]
+    pub fn set_lbd(&mut self, lbd: u32) {
+        debug_assert!(lbd < LBD_USED);
+        let w = self.lbd.as_mut().expect("no lbd field");
+        w.0 = (w.0 & LBD_USED) | lbd;
     }
-    pub fn relocate(mut self, c: CRef) {
-        debug_assert!(!self.reloced());
-        self.set_reloced(true);
-        *self.data[0].cref_mut() = c;
+    #[inline(always)]
+    pub fn set_used(&mut self, used: bool) {
+        let w = self.lbd.as_mut().expect("no lbd field");
+        w.0 = (w.0 & !LBD_USED) | if used { LBD_USED } else { 0 };
     }
     pub fn shrink(self, new_size: u32) {
         debug_assert!(2 <= new_size);
         debug_assert!(new_size <= self.size());
         if new_size < self.size() {
             self.header.set_size(new_size);
-            if let Some(extra) = self.extra {
-                self.data[new_size as usize] = *extra;
+            // move the extra words right after the remaining literals
+            let words = [self.extra.as_deref().copied(), self.lbd.as_deref().copied()];
+            let mut slots = self.data[new_size as usize..]
+                .iter_mut()
+                .chain(self.extra)
+                .chain(self.lbd);
+            for w in words.into_iter().flatten() {
+                *slots.next().unwrap() = w;
             }
         }
     }
-    pub fn as_clause_ref(&mut self) -> ClauseRef {
-        ClauseRef {
-            header: *self.header,
-            data: self.data,
-            extra: self.extra.as_mut().map(|extra| **extra),
-        }
-    }
 }
 
 impl<'a> ops::Index<u32> for ClauseRef<'a> {
     type Output = Lit;
     #[inline(always)]
     fn index(&self, index: u32) -> &Self::Output {
-        &self.data[index as usize].lit()
+        self.data[index as usize].lit()
     }
 }
 impl<'a> ops::Index<u32> for ClauseMut<'a> {
@@ -504,17 +512,23 @@ impl<'a> ops::IndexMut<u32> for ClauseMut<'a> {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 /// Main clause allocator. It stores a set of clauses efficiently.
+///
+/// If `clause_ids` is set, each clause is preceded by a 64 bits identifier
+/// (used for proofs) that is preserved by relocation.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ClauseAllocator {
     ra: RegionAllocator<ClauseData>,
     extra_clause_field: bool,
+    clause_ids: bool,
 }
 
 #[repr(C)]
-#[derive(Clone, Copy, Pod, Zeroable)]
+#[derive(Clone, Copy, Default, Pod, Zeroable)]
 /// Items used in the clause allocator. It should be compact enough that
 /// we do no waste space.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub(crate) struct ClauseData(u32);
 
 impl ClauseData {
@@ -538,16 +552,6 @@ impl ClauseData {
         must_cast_mut(self)
     }
 
-    #[inline(always)]
-    fn cref(self) -> CRef {
-        must_cast(self)
-    }
-
-    #[inline(always)]
-    fn cref_mut(&mut self) -> &mut CRef {
-        must_cast_mut(self)
-    }
-
     fn header(self) -> ClauseHeader {
         must_cast(self)
     }
@@ -557,25 +561,26 @@ impl ClauseData {
     }
 }
 
-impl Into<Lit> for ClauseData {
-    fn into(self: ClauseData) -> Lit {
-        *self.lit()
+impl From<ClauseData> for Lit {
+    fn from(val: ClauseData) -> Self {
+        *val.lit()
     }
 }
 
-impl Default for ClauseData {
-    fn default() -> Self {
-        ClauseData(0)
-    }
-}
 impl fmt::Debug for ClauseData {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "ClauseData({})", self.0)
     }
 }
 
+/// Flag of the LBD word of a learnt clause, set when the clause is used.
+const LBD_USED: u32 = 1 << 31;
+
 /// Metadata of a clause
 ///
+/// Learnt clauses have two extra words after their literals: the activity, then the
+/// LBD and `used` flag.
+///
 /// Layout:
 /// unsigned mark      : 2;
 /// unsigned learnt    : 1;
@@ -630,6 +635,12 @@ impl ClauseHeader {
     pub fn size(&self) -> u32 {
         self.0 & ((1 << 27) - 1)
     }
+    /// Number of words after the literals.
+    #[inline(always)]
+    fn extra_len(&self) -> u32 {
+        debug_assert!(!self.learnt() || self.has_extra());
+        self.has_extra() as u32 + self.learnt() as u32
+    }
     pub fn set_mark(&mut self, mark: u32) {
         debug_assert!(mark < 4);
         self.0 = (self.0 & !(3 << 30)) | (mark << 30);
@@ -649,27 +660,72 @@ impl ClauseHeader {
     }
 }
 
+impl Default for ClauseAllocator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl ClauseAllocator {
     pub const UNIT_SIZE: u32 = 32;
-    pub fn with_start_cap(start_cap: u32) -> Self {
+    pub fn with_start_cap(start_cap: usize) -> Self {
         Self {
             ra: RegionAllocator::new(start_cap),
             extra_clause_field: false,
+            clause_ids: false,
         }
     }
     pub fn new() -> Self {
         Self::with_start_cap(1024 * 1024)
     }
     #[inline(always)]
-    pub fn len(&self) -> u32 {
+    pub fn len(&self) -> usize {
         self.ra.len()
     }
-    pub fn wasted(&self) -> u32 {
+    #[inline(always)]
+    pub fn is_empty(&self) -> bool {
+        self.ra.is_empty()
+    }
+    pub fn wasted(&self) -> usize {
         self.ra.wasted()
     }
+    /// Bytes allocated for the clauses
+    pub fn memory(&self) -> usize {
+        self.ra.memory()
+    }
+    #[inline(always)]
+    pub(crate) fn has_clause_ids(&self) -> bool {
+        self.clause_ids
+    }
+    /// Store an identifier along with each clause. Must be called before any allocation.
+    pub(crate) fn set_clause_ids(&mut self, clause_ids: bool) {
+        debug_assert!(self.is_empty());
+        self.clause_ids = clause_ids;
+    }
+    #[inline(always)]
+    fn id_words(&self) -> u32 {
+        2 * self.clause_ids as u32
+    }
+    /// Allocate `size` words, preceded by the clause id if needed
+    fn alloc_words(&mut self, size: u32) -> CRef {
+        let n = self.id_words();
+        self.ra.alloc(n + clause_words(size)) + n
+    }
+    /// Identifier of the clause `cr`, if `clause_ids` is set.
+    pub(crate) fn id(&self, cr: CRef) -> u64 {
+        debug_assert!(self.clause_ids);
+        let lo = self.ra[cr - 2].0 as u64;
+        let hi = self.ra[cr - 1].0 as u64;
+        lo | (hi << 32)
+    }
+    pub(crate) fn set_id(&mut self, cr: CRef, id: u64) {
+        debug_assert!(self.clause_ids);
+        self.ra[cr - 2].0 = id as u32;
+        self.ra[cr - 1].0 = (id >> 32) as u32;
+    }
     pub(crate) fn alloc_with_learnt(&mut self, clause: &[Lit], learnt: bool) -> CRef {
         let use_extra = learnt | self.extra_clause_field;
-        let cid = self.ra.alloc(1 + clause.len() as u32 + use_extra as u32);
+        let cid = self.alloc_words(1 + clause.len() as u32 + use_extra as u32 + learnt as u32);
         *self.ra[cid].header_mut() =
             ClauseHeader::new(0, learnt, use_extra, false, clause.len() as u32);
         let clause_ptr = cid + 1;
@@ -679,6 +735,8 @@ impl ClauseAllocator {
         if use_extra {
             if learnt {
                 *self.ra[clause_ptr + clause.len() as u32].f32_mut() = 0.0;
+                // the size is an upper bound of the LBD, until it is computed
+                self.ra[clause_ptr + clause.len() as u32 + 1].0 = clause.len() as u32;
             } else {
                 // NOTE: not used right now, but can be used to accelerate `lit_redundant`
                 let mut abstraction: u32 = 0;
@@ -694,7 +752,7 @@ impl ClauseAllocator {
 
     pub(crate) fn alloc_copy(&mut self, from: ClauseRef) -> CRef {
         let use_extra = from.learnt() | self.extra_clause_field;
-        let cid = self.ra.alloc(1 + from.size() + use_extra as u32);
+        let cid = self.alloc_words(1 + from.size() + use_extra as u32 + from.learnt() as u32);
         *self.ra[cid].header_mut() = from.header;
         // NOTE: the copied clause may lose the extra field.
         self.ra[cid].header_mut().set_has_extra(use_extra);
@@ -704,13 +762,16 @@ impl ClauseAllocator {
         if use_extra {
             self.ra[cid + 1 + from.size()] = from.extra.unwrap();
         }
+        if let Some(lbd) = from.lbd {
+            self.ra[cid + 2 + from.size()] = lbd;
+        }
         cid
     }
 
     pub(crate) fn free(&mut self, cr: CRef) {
         let size = {
             let c = self.get_ref(cr);
-            1 + c.size() + c.has_extra() as u32
+            self.id_words() + clause_words(1 + c.size() + c.header.extra_len())
         };
         self.ra.free(size);
     }
@@ -723,19 +784,34 @@ impl ClauseAllocator {
     ///
     /// post condition: `*cr` now contains the index of the copy in `to`
     pub(crate) fn reloc(&mut self, cr: &mut CRef, to: &mut ClauseAllocator) {
-        let mut c = self.get_mut(*cr);
-
-        if c.reloced() {
-            *cr = c.relocation();
+        if self.ra[*cr].header().reloced() {
+            *cr = self.relocation(*cr);
             return;
         }
 
-        *cr = to.alloc_copy(c.as_clause_ref());
-        c.relocate(*cr);
+        let new_cr = to.alloc_copy(self.get_ref(*cr));
+        self.relocate(*cr, new_cr);
+        if to.clause_ids {
+            let id = self.id(*cr);
+            to.set_id(new_cr, id);
+        }
+        *cr = new_cr;
+    }
+
+    /// New reference of the relocated clause `cr`, stored in the words after its header.
+    fn relocation(&self, cr: CRef) -> CRef {
+        let words = self.ra.subslice(cr + 1, CREF_WORDS);
+        bytemuck::pod_read_unaligned(must_cast_slice(words))
+    }
+
+    fn relocate(&mut self, cr: CRef, to: CRef) {
+        self.ra[cr].header_mut().set_reloced(true);
+        let words = self.ra.subslice_mut(cr + 1, CREF_WORDS);
+        must_cast_slice_mut(words).copy_from_slice(bytemuck::bytes_of(&to));
     }
 
     /// Get a reference on the clause `cr` points to
-    pub(crate) fn get_ref(&self, cr: CRef) -> ClauseRef {
+    pub(crate) fn get_ref(&self, cr: CRef) -> ClauseRef<'_> {
         let header = self.ra[cr].header();
         let has_extra = header.has_extra();
         let size = header.size();
@@ -746,33 +822,55 @@ impl ClauseAllocator {
         } else {
             None
         };
+        let lbd = if header.learnt() {
+            Some(self.ra[cr + 2 + size])
+        } else {
+            None
+        };
         ClauseRef {
             header,
             data,
             extra,
+            lbd,
         }
     }
 
     /// Get a mutable reference on the clause `cr` points to
-    pub(crate) fn get_mut(&mut self, cr: CRef) -> ClauseMut {
+    pub(crate) fn get_mut(&mut self, cr: CRef) -> ClauseMut<'_> {
         let header = self.ra[cr].header();
-        let has_extra = header.has_extra();
         let size = header.size();
-        let len = 1 + size + has_extra as u32;
+        let len = 1 + size + header.extra_len();
 
         let subslice = self.ra.subslice_mut(cr, len);
         let (subslice0, subslice) = subslice.split_at_mut(1);
         let (subslice1, subslice2) = subslice.split_at_mut(size as usize);
+        let (extra, lbd) = match subslice2 {
+            [] => (None, None),
+            [extra] => (Some(extra), None),
+            [extra, lbd] => (Some(extra), Some(lbd)),
+            _ => unreachable!(),
+        };
         ClauseMut {
             header: subslice0[0].header_mut(),
             data: subslice1,
-            extra: subslice2.first_mut(),
+            extra,
+            lbd,
         }
     }
 }
 
 pub(crate) type CRef = alloc::Ref<ClauseData>;
 
+/// Number of words of a `CRef` (2 with the `cref64` feature).
+const CREF_WORDS: u32 = (mem::size_of::<CRef>() / mem::size_of::<ClauseData>()) as u32;
+
+/// Words allocated for a clause of `size` words including its header: enough to store
+/// its relocation after the header.
+#[inline(always)]
+fn clause_words(size: u32) -> u32 {
+    size.max(1 + CREF_WORDS)
+}
+
 /// Predicate that decides whether a value `V` is deleted or not
 pub trait DeletePred<V> {
     fn deleted(&self, v: &V) -> bool;
@@ -783,12 +881,19 @@ pub type OccVec<V> = Vec<V>;
 #[derive(Debug, Clone)]
 /// List of occurrences of objects of type `K` (e.g. literals) in values
 /// of type `V` (e.g. clauses)
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct OccListsData<K: AsIndex, V> {
     occs: IntMap<K, OccVec<V>>,
     dirty: IntMapBool<K>,
     dirties: Vec<K>, // to know what keys to examine in `clean_all_pred`
 }
 
+impl<K: AsIndex, V> Default for OccListsData<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl<K: AsIndex, V> OccListsData<K, V> {
     pub fn new() -> Self {
         Self {
@@ -806,7 +911,7 @@ impl<K: AsIndex, V> OccListsData<K, V> {
     }
 
     /// Obtain a fully usable occurrence list using the given predicate
-    pub fn promote<P: DeletePred<V>>(&mut self, pred: P) -> OccLists<K, V, P> {
+    pub fn promote<P: DeletePred<V>>(&mut self, pred: P) -> OccLists<'_, K, V, P> {
         OccLists { data: self, pred }
     }
 
@@ -846,6 +951,17 @@ impl<K: AsIndex, V> OccListsData<K, V> {
         }
     }
 
+    /// Bytes allocated for the lists
+    pub fn memory(&self) -> usize {
+        let occs = self
+            .occs
+            .iter()
+            .map(|(_, o)| o.capacity() * mem::size_of::<V>());
+        self.occs.memory_usage()
+            + occs.sum::<usize>()
+            + self.dirties.capacity() * mem::size_of::<K>()
+    }
+
     /// Reset internal data
     pub fn clear(&mut self) {
         self.occs.clear();
@@ -897,13 +1013,13 @@ impl<'a, K: AsIndex + 'a, V: 'a, P: DeletePred<V>> OccLists<'a, K, V, P> {
 impl<'a, K: AsIndex + 'a, V: 'a, P: DeletePred<V>> ops::Deref for OccLists<'a, K, V, P> {
     type Target = OccListsData<K, V>;
     fn deref(&self) -> &Self::Target {
-        &self.data
+        self.data
     }
 }
 
 impl<'a, K: AsIndex + 'a, V: 'a, P: DeletePred<V>> ops::DerefMut for OccLists<'a, K, V, P> {
     fn deref_mut(&mut self) -> &mut Self::Target {
-        &mut self.data
+        self.data
     }
 }
 
@@ -923,8 +1039,8 @@ pub mod display {
         /// let v: Vec<Lit> = vec![];
         /// format!("as dimacs: {}", v.pp_dimacs());
         /// ```
-        fn pp_dimacs(&self) -> PrintWrapper<Self> {
-            PrintWrapper(&self)
+        fn pp_dimacs(&self) -> PrintWrapper<'_, Self> {
+            PrintWrapper(self)
         }
     }
 
@@ -1018,4 +1134,86 @@ mod test {
     fn test_cref_undef_special() {
         assert_eq!(CRef::UNDEF, CRef::SPECIAL + 1);
     }
+
+    /// relocation keeps the clauses, even the unit ones that barely fit a `CRef`
+    #[test]
+    fn test_reloc() {
+        for ids in [false, true] {
+            let mut ca = ClauseAllocator::with_start_cap(16);
+            ca.set_clause_ids(ids);
+            let lits: Vec<Lit> = (0..4)
+                .map(|i| Lit::new(Var::from_idx(i), i % 2 == 0))
+                .collect();
+            let clauses = [&lits[..1], &lits[1..2], &lits[..], &lits[2..]];
+            let mut crefs: Vec<CRef> = clauses
+                .iter()
+                .enumerate()
+                .map(|(i, c)| {
+                    let cr = ca.alloc_with_learnt(c, i == 2);
+                    if ids {
+                        ca.set_id(cr, (i as u64) << 40 | 7);
+                    }
+                    cr
+                })
+                .collect();
+            ca.free(crefs[1]);
+            let mut to = ClauseAllocator::with_start_cap(16);
+            to.set_clause_ids(ids);
+            for cr in crefs.iter_mut() {
+                ca.reloc(cr, &mut to);
+            }
+            // already relocated
+            let mut cr = crefs[0];
+            ca.reloc(&mut cr, &mut to);
+            assert_eq!(cr, crefs[0]);
+            for (i, (&cr, c)) in crefs.iter().zip(clauses).enumerate() {
+                assert_eq!(to.get_ref(cr).lits(), c);
+                assert_eq!(to.get_ref(cr).learnt(), i == 2);
+                if ids {
+                    assert_eq!(to.id(cr), (i as u64) << 40 | 7);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_shrink_learnt() {
+        let lits: Vec<Lit> = (0..4).map(|i| Lit::new(Var::from_idx(i), true)).collect();
+        let mut ca = ClauseAllocator::new();
+        let cr = ca.alloc_with_learnt(&lits, true);
+        let mut c = ca.get_mut(cr);
+        c.set_activity(2.5);
+        c.set_lbd(3);
+        c.set_used(true);
+        c.shrink(3);
+        let c = ca.get_ref(cr);
+        assert_eq!(c.lits(), &lits[..3]);
+        assert_eq!(c.activity(), 2.5);
+        assert_eq!(c.lbd(), 3);
+        assert!(c.used());
+    }
+
+    #[test]
+    fn test_clause_id_survives_reloc() {
+        let lits: Vec<Lit> = (0..3).map(|i| Lit::new(Var::from_idx(i), true)).collect();
+        let mut ca = ClauseAllocator::new();
+        ca.set_clause_ids(true);
+        let c1 = ca.alloc_with_learnt(&lits, false);
+        let mut c2 = ca.alloc_with_learnt(&lits[1..], true);
+        ca.set_id(c1, 1);
+        ca.set_id(c2, (1 << 40) + 2);
+        ca.get_mut(c2).set_activity(1.5);
+        ca.get_mut(c2).set_lbd(1);
+        ca.free(c1);
+
+        let mut to = ClauseAllocator::new();
+        to.set_clause_ids(true);
+        ca.reloc(&mut c2, &mut to);
+        assert_eq!(to.id(c2), (1 << 40) + 2);
+        assert_eq!(to.get_ref(c2).lits(), &lits[1..]);
+        assert_eq!(to.get_ref(c2).activity(), 1.5);
+        assert_eq!(to.get_ref(c2).lbd(), 1);
+        assert!(!to.get_ref(c2).used());
+        assert_eq!(to.len(), 2 + 1 + 2 + 2);
+    }
 }
@@ -20,20 +20,33 @@ OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWA
 **************************************************************************************************/
 use no_std_compat::prelude::v1::*;
 use {
-    crate::callbacks::{Callbacks, ProgressStatus},
+    crate::aig::Aig,
+    crate::branch::{Branch, Branching, THRESHOLD},
+    crate::callbacks::{Callbacks, ClauseListener, ProgressStatus},
     crate::clause::{
         self, lbool, CRef, ClauseAllocator, ClauseRef, DeletePred, LSet, Lit, OccLists,
         OccListsData, VMap, Var,
     },
-    crate::heap::{CachedKeyComparator, Heap, HeapData},
+    crate::drat::ProofOutput,
+    crate::encodings::{self, AmoEncoding},
+    crate::equiv::binary_sccs,
     crate::interface::SolverInterface,
-    crate::theory::Theory,
-    std::{cmp, fmt, mem},
+    crate::interpolate,
+    crate::intmap::IntMapClearable,
+    crate::native::NativeConstraints,
+    crate::reconstruct::Reconstruction,
+    crate::simd,
+    crate::simp::{SimpLimits, Simplifier},
+    crate::theory::{self, Theory},
+    crate::walk::walk,
+    std::sync::atomic::{AtomicBool, Ordering},
+    std::sync::Arc,
+    std::{cmp, fmt, mem, time::Duration},
 };
 
 #[cfg(feature = "logging")]
 use crate::clause::display::Print;
-use crate::core::utils::LubyIter;
+use crate::core::utils::{Ema, LubyIter, Rand};
 
 /// The main solver structure.
 ///
@@ -43,47 +56,432 @@ use crate::core::utils::LubyIter;
 ///
 /// A `Solver` object contains the whole state of the SAT solver, including
 /// a clause allocator, literals, clauses, and statistics.
+///
+/// With the `serde` feature, this state can be serialized, to resume the search in another
+/// process. The callbacks, the proof output, the deadline and the stop flag are left out:
+/// they are reset to their defaults by deserialization.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(bound(serialize = "", deserialize = "Cb: Default"))
+)]
 pub struct Solver<Cb: Callbacks> {
     // Extra results: (read-only member variable)
     /// If problem is satisfiable, this vector contains the model (if any).
     model: Vec<lbool>,
     /// If problem is unsatisfiable (possibly under assumptions),
-    /// this vector represent the final conflict clause expressed in the assumptions.
+    /// this vector contains the subset of the assumptions that led to the conflict.
     conflict: LSet,
 
-    cb: Cb, // the callbacks
+    // the callbacks, which are not serialized
+    #[cfg_attr(feature = "serde", serde(skip))]
+    cb: Cb,
 
     /// List of problem clauses.
     clauses: Vec<CRef>,
     /// List of learnt clauses.
     learnts: Vec<CRef>,
+    /// Activation literals of the clause groups that were not retracted.
+    groups: Vec<Lit>,
+    /// Index in `groups` of the variable of each activation literal, or `u32::MAX`.
+    group_index: VMap<u32>,
+    /// Clauses of these groups as they were added, for [`Solver::interpolant`]: the
+    /// activation literal of each one, then its literals, then `Lit::UNDEF`.
+    group_clauses: Vec<Lit>,
+    /// Assumptions whose decision levels were kept on the trail by the last solve, see
+    /// [`SolverOpts::reuse_trail`].
+    kept_assumptions: Vec<Lit>,
+    /// Clauses removed by [`Solver::eliminate`], to extend the models.
+    elim: Reconstruction,
+    /// Selector variables of the random XOR constraints of
+    /// [`Solver::approx_count_models`], reused from one round to the next. They are
+    /// never substituted by equivalent literals, as their constraints are removed.
+    xor_selectors: Vec<Var>,
+
+    /// See [`Solver::set_terminate_callback`].
+    #[cfg_attr(feature = "serde", serde(skip))]
+    terminate: Option<Box<dyn FnMut() -> bool + Send>>,
+    /// See [`Solver::set_learn_callback`].
+    #[cfg_attr(feature = "serde", serde(skip))]
+    learn: Option<LearnCallback>,
+    learn_max_len: usize,
+    /// See [`Solver::set_clause_listener`].
+    #[cfg_attr(feature = "serde", serde(skip))]
+    listener: Option<Box<dyn ClauseListener + Send>>,
 
     v: SolverV,
     tmp_c_th: Vec<Lit>,     // used for theory conflict
     tmp_c_add_cl: Vec<Lit>, // used for adding clauses during search
 }
 
+type LearnCallback = Box<dyn FnMut(&[Lit]) + Send>;
+
+/// A set of clauses that can be retracted together, see [`Solver::new_clause_group`].
+///
+/// Clauses of a group are guarded by the negation of its activation literal,
+/// which the solver assumes while the group is active.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ClauseGroup(Lit);
+
+impl ClauseGroup {
+    /// The literal assumed by the solver while the group is active.
+    ///
+    /// It occurs in the unsat core if clauses of the group were used to derive
+    /// the conflict.
+    #[inline(always)]
+    pub fn activation_lit(self) -> Lit {
+        self.0
+    }
+}
+
+/// Handle on a clause that can be removed, see [`Solver::add_removable_clause`].
+///
+/// The clause is the only member of its own [`ClauseGroup`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ClauseHandle(ClauseGroup);
+
+impl ClauseHandle {
+    /// The group containing the clause.
+    #[inline(always)]
+    pub fn group(self) -> ClauseGroup {
+        self.0
+    }
+}
+
+/// State of a solver saved by [`Solver::save_state`], which can be brought back
+/// with [`Solver::restore`].
+///
+/// It holds the clauses (including the learnt ones), the level 0 assignments and the
+/// state of the heuristics of each variable, but not the statistics nor the options.
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Snapshot {
+    ok: bool,
+    num_vars: u32,
+    /// Literals of the original clauses, each one followed by `Lit::UNDEF`.
+    clauses: Vec<Lit>,
+    /// Literals of the learnt clauses, each one followed by `Lit::UNDEF`, and their LBD.
+    learnts: Vec<Lit>,
+    lbds: Vec<u32>,
+    /// Literals assigned at level 0, in trail order.
+    units: Vec<Lit>,
+    /// Score of each variable in the branching heuristic, and its preferred polarity.
+    scores: Vec<f64>,
+    polarity: Vec<bool>,
+    user_pol: Vec<lbool>,
+    assump_priority: Vec<i32>,
+    decision: Vec<bool>,
+    frozen: Vec<bool>,
+    eliminated: Vec<bool>,
+    blocking: Vec<bool>,
+    internal: Vec<bool>,
+    groups: Vec<Lit>,
+    group_clauses: Vec<Lit>,
+    elim: Reconstruction,
+    natives: NativeConstraints,
+}
+
+/// Iterator over the models of a solver, see [`Solver::iter_models`].
+///
+/// Each model is excluded by a blocking clause before the next one is searched for.
+/// The blocking clauses belong to a clause group that is retracted when the
+/// iterator is dropped, so the solver is left with its original clauses.
+pub struct Models<'a, Cb: Callbacks> {
+    solver: &'a mut Solver<Cb>,
+    /// Variables on which the models are projected.
+    vars: Vec<Var>,
+    assumps: Vec<Lit>,
+    group: ClauseGroup,
+    /// Result of the last call to the solver.
+    status: lbool,
+    blocking: Vec<Lit>,
+}
+
+impl<'a, Cb: Callbacks> Models<'a, Cb> {
+    fn new(solver: &'a mut Solver<Cb>, vars: Vec<Var>, assumps: Vec<Lit>) -> Self {
+        let group = solver.new_clause_group();
+        Models {
+            solver,
+            vars,
+            assumps,
+            group,
+            status: lbool::TRUE,
+            blocking: vec![],
+        }
+    }
+
+    /// Were all the models enumerated? This is `false` if the enumeration was
+    /// interrupted by the callbacks (the iterator then also returns `None`).
+    pub fn is_exhausted(&self) -> bool {
+        self.status == lbool::FALSE
+    }
+}
+
+impl<'a, Cb: Callbacks> Iterator for Models<'a, Cb> {
+    /// The true literals of the model, on the projection variables that are assigned.
+    type Item = Vec<Lit>;
+
+    fn next(&mut self) -> Option<Vec<Lit>> {
+        if self.status != lbool::TRUE {
+            return None;
+        }
+        self.status = self.solver.solve_limited(&self.assumps);
+        if self.status != lbool::TRUE {
+            return None;
+        }
+        let model: Vec<Lit> = self
+            .vars
+            .iter()
+            .filter_map(|&v| {
+                let val = self.solver.value_var(v);
+                (val != lbool::UNDEF).then(|| Lit::new(v, val == lbool::TRUE))
+            })
+            .collect();
+        self.blocking.clear();
+        self.blocking.extend(model.iter().map(|&lit| !lit));
+        self.solver.add_clause_in_group(self.group, &self.blocking);
+        Some(model)
+    }
+}
+
+impl<'a, Cb: Callbacks> Drop for Models<'a, Cb> {
+    fn drop(&mut self) {
+        self.solver.retract_clause_group(self.group);
+    }
+}
+
+/// Statistics of a solver, see [`Solver::stats`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Stats {
+    /// Calls to the solver.
+    pub solves: u64,
+    pub restarts: u64,
+    pub conflicts: u64,
+    pub decisions: u64,
+    /// Decisions on a random variable, see [`SolverOpts::random_var_freq`].
+    pub random_decisions: u64,
+    pub propagations: u64,
+    /// Deterministic measure of the work done by unit propagation, see
+    /// [`Solver::set_tick_limit`].
+    pub ticks: u64,
+    /// Literals of the learnt clauses.
+    pub learnt_literals: u64,
+    /// Literals removed from the learnt clauses by minimization.
+    pub minimized_literals: u64,
+    /// Clauses strengthened by on-the-fly subsumption.
+    pub otfs_strengthened: u64,
+    /// Current number of original clauses.
+    pub clauses: u64,
+    /// Current number of learnt clauses.
+    pub learnts: u64,
+    /// Learnt clauses removed by the reductions of the clause database.
+    pub deleted_clauses: u64,
+    /// Garbage collections of the clause allocator.
+    pub gcs: u64,
+    /// Bytes of removed clauses reclaimed by the garbage collections.
+    pub gc_reclaimed: u64,
+    /// Switches between the focused and stable modes, see [`SolverOpts::mode_switching`].
+    pub mode_switches: u64,
+    /// Solves that started from levels of assumptions kept by the previous one, see
+    /// [`SolverOpts::reuse_trail`].
+    pub trail_reuses: u64,
+    /// Bytes used by the clause allocator, including the removed clauses until the
+    /// next garbage collection.
+    pub clause_memory: usize,
+    /// Calls to the theory given to the solves.
+    pub theory: TheoryStats,
+}
+
+/// Statistics of the calls to the theory, see [`Stats::theory`].
+///
+/// The times are only measured with [`SolverOpts::theory_timing`], and stay zero
+/// without the `std` feature.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TheoryStats {
+    pub partial_checks: u64,
+    pub final_checks: u64,
+    /// Conflicts of the checks, raised as a clause or by propagating a false literal.
+    pub conflicts: u64,
+    /// Literals propagated by the checks.
+    pub propagations: u64,
+    /// Propagations of the theory explained by `explain_propagation_clause(_final)`.
+    pub explanations: u64,
+    /// Explanations found in the cache instead, see [`SolverOpts::cache_explanations`].
+    pub cached_explanations: u64,
+    /// Decisions of `Theory::decide` made by the solver.
+    pub decisions: u64,
+    pub partial_check_time: Duration,
+    pub final_check_time: Duration,
+    pub explain_time: Duration,
+    /// Time in `Theory::on_assign`.
+    pub assign_time: Duration,
+    /// Time in `Theory::create_level`, `pop_levels` and `on_backtrack`.
+    pub level_time: Duration,
+    pub decide_time: Duration,
+}
+
+/// Start of a call to the theory, to add its duration to [`TheoryStats`].
+#[derive(Clone, Copy)]
+struct Timer(#[cfg(feature = "std")] Option<std::time::Instant>);
+
+impl Timer {
+    /// Start timing if `enabled`, see [`SolverOpts::theory_timing`].
+    #[inline]
+    fn start(enabled: bool) -> Self {
+        #[cfg(feature = "std")]
+        return Timer(enabled.then(std::time::Instant::now));
+        #[cfg(not(feature = "std"))]
+        {
+            let _ = enabled;
+            Timer()
+        }
+    }
+
+    /// Add the time since the start to `time`.
+    #[inline]
+    fn stop(self, time: &mut Duration) {
+        #[cfg(feature = "std")]
+        if let Some(start) = self.0 {
+            *time += start.elapsed();
+        }
+        #[cfg(not(feature = "std"))]
+        let _ = time;
+    }
+}
+
+#[cfg(feature = "json")]
+impl Stats {
+    /// The statistics as a JSON object, with the names of the fields as keys.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("statistics are serializable")
+    }
+}
+
+/// The assignment obtained by unit propagation in [`Solver::propagate_under`].
+///
+/// The solver keeps it until the view is dropped, and then backtracks to level 0.
+pub struct TrailView<'a, Cb: Callbacks> {
+    solver: &'a mut Solver<Cb>,
+    /// Start of the literals implied by the assumptions on the trail.
+    start: usize,
+}
+
+impl<'a, Cb: Callbacks> TrailView<'a, Cb> {
+    /// The true literals, in the order of their propagation: the ones true at level 0,
+    /// the assumptions, and the literals they imply.
+    pub fn trail(&self) -> &[Lit] {
+        &self.solver.v.vars.trail
+    }
+
+    /// The literals implied by the assumptions, but not true at level 0.
+    pub fn implied(&self) -> &[Lit] {
+        &self.solver.v.vars.trail[self.start..]
+    }
+
+    /// Value of `lit` in the assignment.
+    pub fn value_lit(&self, lit: Lit) -> lbool {
+        self.solver.v.value_lit(lit)
+    }
+
+    /// Reason of the true literal `lit`: a clause (or the explanation of a native
+    /// constraint) whose first literal is `lit` and whose other ones are false.
+    ///
+    /// Returns `None` for the assumptions and the literals without a reason at level 0.
+    pub fn reason(&self, lit: Lit) -> Option<&[Lit]> {
+        debug_assert_eq!(self.value_lit(lit), lbool::TRUE);
+        self.solver.v.reason_lits(lit.var())
+    }
+
+    /// Start in [`trail`](Self::trail) of each decision level above 0.
+    pub fn trail_limits(&self) -> impl ExactSizeIterator<Item = usize> + '_ {
+        self.solver.trail_limits()
+    }
+
+    /// See [`Solver::level_of`].
+    pub fn level_of(&self, v: Var) -> Option<u32> {
+        self.solver.level_of(v)
+    }
+
+    /// See [`Solver::reason_of`].
+    pub fn reason_of(&self, v: Var) -> Option<&[Lit]> {
+        self.solver.reason_of(v)
+    }
+
+    /// See [`Solver::dump_implication_graph`].
+    #[cfg(feature = "std")]
+    pub fn dump_implication_graph<W: std::io::Write>(
+        &self,
+        w: &mut W,
+        format: crate::graph::GraphFormat,
+        conflict: Option<&[Lit]>,
+    ) -> std::io::Result<()> {
+        self.solver.dump_implication_graph(w, format, conflict)
+    }
+}
+
+impl<'a, Cb: Callbacks> Drop for TrailView<'a, Cb> {
+    fn drop(&mut self) {
+        self.solver.v.cancel_until(0);
+    }
+}
+
+/// Why [`Solver::propagate_under`] failed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PropagationConflict {
+    /// The solver is in an `UNSAT` state.
+    Unsat,
+    /// The assumption is false at level 0, or its negation is an earlier assumption.
+    Assumption(Lit),
+    /// A clause (or the explanation of a native constraint) made false by propagation.
+    Clause(Vec<Lit>),
+}
+
 /// The current assignments.
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 struct VarState {
-    /// A heuristic measurement of the activity of a variable.
-    activity: VMap<f32>,
-    /// A priority queue of variables ordered with respect to the variable activity.
-    order_heap_data: HeapData<Var, VarOrderKey>,
+    /// The decision heuristic, see [`SolverOpts::branching`].
+    branch: Branching,
     /// Current assignment for each variable.
     ass: VMap<lbool>,
     /// Stores reason and level for each variable.
     vardata: VMap<VarData>,
-    /// Amount to bump next variable with.
-    var_inc: f32,
 
     /// Assignment stack; stores all assigments made in the order they were made.
     trail: Vec<Lit>,
     /// Separator indices for different decision levels in `trail`.
+    ///
+    /// With chronological backtracking, the literals of a level are not all in its slice:
+    /// they may be in the slice of a higher level, but never of a lower one.
     trail_lim: Vec<i32>,
 }
 
+/// Flag interrupting the search when it is set, see [`Solver::set_stop_flag`].
+#[derive(Clone)]
+enum StopFlag {
+    Shared(Arc<AtomicBool>),
+    Static(&'static AtomicBool),
+}
+
+impl StopFlag {
+    fn get(&self) -> &AtomicBool {
+        match self {
+            StopFlag::Shared(flag) => flag,
+            StopFlag::Static(flag) => flag,
+        }
+    }
+}
+
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 struct SolverV {
     opts: SolverOpts,
+    /// Source of the random choices, see [`SolverOpts::seed`].
+    rand: Rand,
     vars: VarState,
 
     learntsize_adjust_start_confl: i32,
@@ -99,7 +497,11 @@ struct SolverV {
     starts: u64,
     decisions: u64,
     rnd_decisions: u64,
+    /// Clauses strengthened by on-the-fly subsumption.
+    otfs_strengthened: u64,
     propagations: u64,
+    /// Deterministic measure of the work done by unit propagation.
+    ticks: u64,
     conflicts: u64,
     dec_vars: u64,
     // v.num_clauses: u64,
@@ -108,6 +510,12 @@ struct SolverV {
     // v.learnts_literals: u64,
     max_literals: u64,
     tot_literals: u64,
+    /// Learnt clauses removed by the reductions of the clause database.
+    deleted_clauses: u64,
+    /// Garbage collections of the clause allocator.
+    gcs: u64,
+    /// Bytes reclaimed by the garbage collections.
+    gc_reclaimed: u64,
 
     num_clauses: u64,
     num_learnts: u64,
@@ -122,13 +530,38 @@ struct SolverV {
     // v.order_heap_data: HeapData<Var>,
     /// The preferred polarity of each variable.
     polarity: VMap<bool>,
+    /// Value of each variable in the largest conflict-free trail since the last
+    /// rephasing, or `UNDEF`, see [`SolverOpts::target_phase`].
+    target: VMap<lbool>,
+    /// Value of each variable in the largest conflict-free trail, for rephasing.
+    best: VMap<lbool>,
     /// The users preferred polarity of each variable.
     user_pol: VMap<lbool>,
+    /// Priority of each variable as an assumption, see [`Solver::set_assumption_priority`].
+    assump_priority: VMap<i32>,
+    /// Number of unsat cores each variable occurred in, see [`AssumptionOrder::Cores`].
+    core_hits: VMap<u32>,
     /// Declares if a variable is eligible for selection in the decision heuristic.
     decision: VMap<bool>,
+    /// Variables that must not be eliminated, see [`Solver::set_frozen`].
+    frozen: VMap<bool>,
+    /// Variables removed by [`Solver::eliminate`].
+    eliminated: VMap<bool>,
+    /// Variables on which clauses removed by [`Solver::eliminate`] are blocked.
+    blocking: VMap<bool>,
+    /// Variables created by the solver for its own use, see [`Solver::iter_models`].
+    internal: VMap<bool>,
     // /// Stores reason and level for each variable.
     /// `watches[lit]` is a list of constraints watching 'lit' (will go there if literal becomes true).
     watches_data: OccListsData<Lit, Watcher>,
+    /// Binary clauses watching `lit`, the blocker of each watcher being the literal it implies
+    /// when `lit` becomes true. They are propagated first, and only visited when they
+    /// propagate or are in conflict.
+    ///
+    /// The clauses themselves are still allocated in `ca`, like the longer ones: they are
+    /// the reasons of the literals they propagate (with the implied literal moved first),
+    /// and keep their proof ids, flags and deletion handling.
+    bin_watches_data: OccListsData<Lit, Watcher>,
     /// If `false`, the constraints are already unsatisfiable. No part of the solver state may be used!
     ok: bool,
     /// Amount to bump next clause with.
@@ -150,6 +583,8 @@ struct SolverV {
     ca: ClauseAllocator,
 
     free_vars: Vec<Var>,
+    /// Variables released by [`Solver::release_var`], removed by [`Solver::compact`].
+    released: Vec<Var>,
 
     // /// Assignment stack; stores all assigments made in the order they were made.
     // v.trail: Vec<Lit>,
@@ -159,23 +594,323 @@ struct SolverV {
     assumptions: Vec<Lit>,
 
     // Temporaries (to reduce allocation overhead). Each variable is prefixed by the method in which it is
-    // used, except `seen` wich is used in several places.
-    seen: VMap<Seen>,
+    // used, except `seen` wich is used in several places (and cleared at once by `analyze`).
+    seen: IntMapClearable<Var, Seen>,
     minimize_stack: Vec<Lit>,
     analyze_toclear: Vec<Lit>,
+    lbd_levels: LevelStamps,
 
     // Resource contraints:
     conflict_budget: i64,
     propagation_budget: i64,
+    tick_budget: i64,
+    /// The search is interrupted after this instant.
+    #[cfg(feature = "std")]
+    #[cfg_attr(feature = "serde", serde(skip))]
+    deadline: Option<std::time::Instant>,
+    /// If present and set, the search is interrupted.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    stop_flag: Option<StopFlag>,
+    /// Bound on [`Solver::memory_usage`], see [`Solver::set_memory_limit`].
+    memory_limit: usize,
+    /// Number of conflicts after which the memory usage is checked again.
+    next_memory_check: u64,
+    /// Was the last search interrupted by the memory limit?
+    memory_out: bool,
+
+    /// Number of conflicts after which the clauses are vivified again.
+    next_vivify: u64,
+    /// Value of `ticks` at the end of the last vivification.
+    vivify_ticks: u64,
+    /// Where the last vivification stopped, in the original and learnt clauses.
+    vivify_next: [usize; 2],
+    /// Number of conflicts after which the literals are probed, and the equivalent ones
+    /// substituted, again.
+    next_probe: u64,
+    /// Value of `ticks` at the end of the last probing.
+    probe_ticks: u64,
+    /// Index of the variable where the last probing stopped.
+    probe_next: u32,
+    /// Number of conflicts after which the learnt clauses are reduced by LBD again.
+    next_reduce: u64,
+    /// Number of reductions of the learnt clauses by LBD so far.
+    num_reduces: u64,
+    /// Clauses to strengthen after the conflict analysis, with the literal to remove.
+    otfs: Vec<(CRef, Lit)>,
+    /// Moving averages of the LBD of the recent learnt clauses, of all of them, and of the
+    /// size of the trail at conflicts, for Glucose restarts.
+    lbd_ema_fast: Ema,
+    lbd_ema_slow: Ema,
+    trail_ema: Ema,
+    /// Glucose restarts are blocked until this number of conflicts.
+    restart_blocked: u64,
+    /// Is the search in stable mode rather than focused mode? See
+    /// [`SolverOpts::mode_switching`].
+    stable: bool,
+    mode_switches: u64,
+    trail_reuses: u64,
+    /// Ticks of the first focused mode, the unit of the length of the following ones.
+    mode_ticks: u64,
+    /// Ticks after which the mode is switched at the next restart.
+    mode_limit: u64,
+    /// Size of the trails saved in `target` and `best`.
+    target_len: u32,
+    best_len: u32,
+    /// Number of conflicts after which the phases are reset again.
+    next_rephase: u64,
+    /// Number of times the phases were reset so far.
+    num_rephases: u64,
+    /// Value of `ticks` at the end of the last local search.
+    local_search_ticks: u64,
 
     th_st: ExplainTheoryArg,
+    th_stats: TheoryStats,
+    /// Length of the prefix of the trail notified to the theory by `Theory::on_assign`.
+    th_notified: usize,
+    /// Did the theory ask for a restart with `TheoryArg::request_restart`?
+    th_restart: bool,
+    /// Theory propagations not assigned yet, see [`SolverOpts::theory_prop_batch`].
+    th_queue: Vec<Lit>,
+    /// Explanations of the theory propagations on the trail, see
+    /// [`SolverOpts::cache_explanations`].
+    th_cache: ExplainCache,
+    /// XOR, cardinality and pseudo-Boolean constraints, see [`Solver::add_xor_clause`],
+    /// [`Solver::add_at_most_k`] and [`Solver::add_pb_constraint`].
+    natives: NativeConstraints,
+
+    #[cfg_attr(feature = "serde", serde(skip))]
+    proof: ProofState,
 }
 
-/// Enables adding lemmas during explanations
+/// Proof logging.
 #[derive(Default)]
+struct ProofState {
+    /// If present, receives a proof of the clauses learnt and deleted.
+    out: Option<Box<dyn ProofOutput + Send>>,
+    /// Are clause ids and antecedents tracked (LRAT)?
+    lrat: bool,
+    /// Id of the last clause added, whether original or derived.
+    last_id: u64,
+    /// Ids of the unit clauses justifying level 0 literals, `0` if not derived yet.
+    unit_ids: VMap<u64>,
+    /// Antecedents of the clause being derived, in propagation order.
+    hints: Vec<u64>,
+    /// Antecedents of the unit being derived.
+    unit_hints: Vec<u64>,
+    /// Stack for the traversal of the implication graph (var, expanded?).
+    stack: Vec<(Var, bool)>,
+    unit_stack: Vec<Var>,
+    /// Original clause (id and literals) found false at level 0, whose refutation
+    /// is logged once the input is complete.
+    pending_unsat: Option<(u64, Vec<Lit>)>,
+}
+
+/// The proof output cannot be shared, so the clone of a solver logs no proof.
+impl Clone for ProofState {
+    fn clone(&self) -> Self {
+        ProofState::default()
+    }
+}
+
+impl ProofState {
+    #[inline]
+    fn new_id(&mut self) -> u64 {
+        self.last_id += 1;
+        self.last_id
+    }
+
+    #[inline]
+    fn unit_id(&self, x: Var) -> u64 {
+        self.unit_ids.get(x).copied().unwrap_or(0)
+    }
+
+    /// Log clause `c` with id `id`, justified by `self.hints`
+    fn add(&mut self, id: u64, c: &[Lit]) {
+        if let Some(out) = &mut self.out {
+            if self.lrat {
+                out.add_clause_with_hints(id, c, &self.hints);
+            } else {
+                out.add_clause(c);
+            }
+        }
+        self.hints.clear();
+    }
+
+    fn delete(&mut self, id: u64, c: &[Lit]) {
+        if let Some(out) = &mut self.out {
+            if self.lrat {
+                out.delete_clause_with_id(id, c);
+            } else {
+                out.delete_clause(c);
+            }
+        }
+    }
+}
+
+/// Explanation of `p`, which was propagated with the reason `CRef::SPECIAL`
+/// either by the native constraints or by the theory.
+///
+/// The explanations of the theory are cached in `cache`, if given: it must only be
+/// given for the propagations that are true on the trail, since the cache forgets
+/// them when they are undone.
+fn explain_special<'a, Th: Theory>(
+    natives: &'a NativeConstraints,
+    th: &'a mut Th,
+    st: &'a mut ExplainTheoryArg,
+    cache: Option<&'a mut ExplainCache>,
+    stats: (&mut TheoryStats, bool),
+    p: Lit,
+    is_final: bool,
+) -> &'a [Lit] {
+    if let Some(lits) = natives.explain(p) {
+        return lits;
+    }
+    let (stats, timing) = stats;
+    if let Some(cache) = cache {
+        if cache.contains(p) {
+            stats.cached_explanations += 1;
+            return cache.get(p);
+        }
+        let lits = explain_theory(th, st, (stats, timing), p, is_final);
+        return cache.insert(p, lits);
+    }
+    explain_theory(th, st, (stats, timing), p, is_final)
+}
+
+fn explain_theory<'a, Th: Theory>(
+    th: &'a mut Th,
+    st: &'a mut ExplainTheoryArg,
+    stats: (&mut TheoryStats, bool),
+    p: Lit,
+    is_final: bool,
+) -> &'a [Lit] {
+    let _span = span!(TRACE, "theory.explain", final = is_final);
+    let (stats, timing) = stats;
+    stats.explanations += 1;
+    let timer = Timer::start(timing);
+    let lits = if is_final {
+        th.explain_propagation_clause_final(p, &mut *st)
+    } else {
+        th.explain_propagation_clause(p, &mut *st)
+    };
+    timer.stop(&mut stats.explain_time);
+    st.expand(lits)
+}
+
+/// Merge the literals of a cardinality constraint that share a variable: the literals
+/// left, each with its number of occurrences, and the number of pairs `x, !x`, which
+/// always count as one true literal.
+fn merge_card_lits(lits: &[Lit]) -> (Vec<(Lit, u32)>, u32) {
+    let mut lits = lits.to_vec();
+    lits.sort_unstable();
+    let mut merged = vec![];
+    let mut pairs = 0;
+    let mut i = 0;
+    while i < lits.len() {
+        let var = lits[i].var();
+        let j = i + lits[i..].iter().take_while(|l| l.var() == var).count();
+        let pos = lits[i..j].iter().filter(|l| l.sign()).count() as u32;
+        let neg = (j - i) as u32 - pos;
+        pairs += pos.min(neg);
+        if pos != neg {
+            merged.push((Lit::new(var, pos > neg), pos.abs_diff(neg)));
+        }
+        i = j;
+    }
+    (merged, pairs)
+}
+
+/// The explanations of the theory propagations on the trail, so that the theory is
+/// asked once for each, however many conflicts are analyzed before they are undone.
+#[derive(Default, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+struct ExplainCache {
+    lits: Vec<Lit>,
+    /// For each variable, its literal on the trail (or `Lit::UNDEF`) and its
+    /// explanation `lits[start..end]`.
+    entries: Vec<(Lit, u32, u32)>,
+    /// Number of literals of `lits` in the explanations that were removed.
+    dead: usize,
+}
+
+impl ExplainCache {
+    fn contains(&self, p: Lit) -> bool {
+        let i = p.var().idx() as usize;
+        i < self.entries.len() && self.entries[i].0 == p
+    }
+
+    fn get(&self, p: Lit) -> &[Lit] {
+        let (_, start, end) = self.entries[p.var().idx() as usize];
+        &self.lits[start as usize..end as usize]
+    }
+
+    fn insert(&mut self, p: Lit, lits: &[Lit]) -> &[Lit] {
+        let i = p.var().idx() as usize;
+        if i >= self.entries.len() {
+            self.entries.resize(i + 1, (Lit::UNDEF, 0, 0));
+        }
+        self.remove(p.var());
+        let start = self.lits.len();
+        self.lits.extend_from_slice(lits);
+        self.entries[i] = (p, start as u32, self.lits.len() as u32);
+        &self.lits[start..]
+    }
+
+    /// Forget the explanation of `v`, which is unassigned.
+    ///
+    /// The explanations are not added in trail order, so `lits` is compacted once
+    /// most of it is dead, rather than truncated.
+    fn remove(&mut self, v: Var) {
+        let Some(e) = self.entries.get_mut(v.idx() as usize) else {
+            return;
+        };
+        if e.0 == Lit::UNDEF {
+            return;
+        }
+        e.0 = Lit::UNDEF;
+        self.dead += (e.2 - e.1) as usize;
+        if self.dead > self.lits.len() / 2 + self.entries.len() {
+            self.compact();
+        }
+    }
+
+    fn compact(&mut self) {
+        let mut lits = Vec::with_capacity(self.lits.len() - self.dead);
+        for e in &mut self.entries {
+            if e.0 != Lit::UNDEF {
+                let start = lits.len();
+                lits.extend_from_slice(&self.lits[e.1 as usize..e.2 as usize]);
+                *e = (e.0, start as u32, lits.len() as u32);
+            }
+        }
+        self.lits = lits;
+        self.dead = 0;
+    }
+
+    fn clear(&mut self) {
+        self.lits.clear();
+        self.entries.clear();
+        self.dead = 0;
+    }
+}
+
+/// Enables adding lemmas during explanations, and introducing fresh literals for
+/// them.
+#[derive(Default, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ExplainTheoryArg {
     lemma_lits: Vec<Lit>,
     lemma_offsets: Vec<usize>, // contiguous slices in `lemma_lits`
+    /// Index of the next variable created by the solver.
+    next_var: u32,
+    /// Number of fresh variables, from `next_var` on, not created yet.
+    fresh: u32,
+    /// Definitions `(d, end)` of fresh literals as the conjunction of
+    /// `def_lits[start..end]`, where `start` is the end of the previous one.
+    defs: Vec<(Lit, usize)>,
+    def_lits: Vec<Lit>,
+    /// Explanation with the defined literals expanded.
+    expanded: Vec<Lit>,
 }
 
 impl ExplainTheoryArg {
@@ -184,6 +919,11 @@ impl ExplainTheoryArg {
         ExplainTheoryArg {
             lemma_lits: vec![],
             lemma_offsets: vec![],
+            next_var: 0,
+            fresh: 0,
+            defs: vec![],
+            def_lits: vec![],
+            expanded: vec![],
         }
     }
 
@@ -204,6 +944,76 @@ impl ExplainTheoryArg {
         self.lemma_offsets.push(idx);
     }
 
+    /// Allocate a fresh literal, that can be used in the lemmas.
+    ///
+    /// Its variable is created by the solver, frozen, once the explanation is done
+    /// (or before any other variable); it is unassigned until then, so it cannot be
+    /// part of an explanation clause, unless it is defined by
+    /// [`define_and`](Self::define_and).
+    pub fn new_lit(&mut self) -> Lit {
+        let v = Var::from_idx(self.next_var + self.fresh);
+        self.fresh += 1;
+        Lit::new(v, true)
+    }
+
+    /// Allocate a fresh literal `d` defined as the conjunction of `lits`, which
+    /// adds the lemmas `¬d ∨ a` for each `a` in `lits` and `d ∨ ¬lits`.
+    ///
+    /// `¬d` may be part of the explanation clause returned by
+    /// [`Theory::explain_propagation_clause`], as long as all of `lits` are true: the
+    /// solver resolves it away with the definition. `lits` may contain defined literals
+    /// too.
+    ///
+    /// The definition is only expanded in the explanations given before the solver
+    /// creates the variable of `d`, that is during the same conflict analysis (or
+    /// theory check). From then on, `d` is an ordinary variable constrained by the
+    /// lemmas above (extended resolution): a later explanation may contain `¬d` only
+    /// if `d` is true on the trail before the propagated literal, like any other
+    /// literal.
+    pub fn define_and(&mut self, lits: &[Lit]) -> Lit {
+        let d = self.new_lit();
+        for &a in lits {
+            self.add_theory_lemma(&[!d, a]);
+        }
+        self.lemma_lits.push(d);
+        self.lemma_lits.extend(lits.iter().map(|&a| !a));
+        self.lemma_offsets.push(self.lemma_lits.len());
+        self.def_lits.extend_from_slice(lits);
+        self.defs.push((d, self.def_lits.len()));
+        d
+    }
+
+    /// The body of `d`, if it is defined.
+    fn definition(&self, d: Lit) -> Option<&[Lit]> {
+        let i = self.defs.iter().position(|&(x, _)| x == d)?;
+        let start = if i == 0 { 0 } else { self.defs[i - 1].1 };
+        Some(&self.def_lits[start..self.defs[i].1])
+    }
+
+    /// `lits`, with the negations of the defined literals (after the first one)
+    /// replaced by the negations of their bodies.
+    fn expand<'a>(&'a mut self, lits: &'a [Lit]) -> &'a [Lit] {
+        if self.defs.is_empty() || !lits[1..].iter().any(|&q| self.definition(!q).is_some()) {
+            return lits;
+        }
+        let mut expanded = mem::take(&mut self.expanded);
+        expanded.clear();
+        expanded.push(lits[0]);
+        let mut stack: Vec<Lit> = lits[1..].to_vec();
+        while let Some(q) = stack.pop() {
+            match self.definition(!q) {
+                Some(body) => stack.extend(body.iter().map(|&a| !a)),
+                None => {
+                    if !expanded[1..].contains(&q) {
+                        expanded.push(q)
+                    }
+                }
+            }
+        }
+        self.expanded = expanded;
+        &self.expanded
+    }
+
     /// Iterate over the clauses contained in this theory state
     fn iter_lemmas(&self) -> impl Iterator<Item = &[Lit]> {
         let mut last = 0;
@@ -269,22 +1079,34 @@ impl<Cb: Callbacks> SolverInterface for Solver<Cb> {
     // in the API, we can only add clauses at level 0
     fn add_clause_reuse(&mut self, clause: &mut Vec<Lit>) -> bool {
         debug!("add toplevel clause {:?}", clause);
+        self.release_trail();
         debug_assert_eq!(
             self.v.decision_level(),
             0,
             "add clause at non-zero decision level"
         );
+        self.restore_vars(clause.iter().map(|l| l.var()));
         clause.sort_unstable();
-        self.add_clause_(clause)
+        let id = self.v.proof.new_id();
+        self.add_clause_(clause, id)
     }
 
     fn reset(&mut self) {
-        let new_v = SolverV::new(&self.v.opts);
+        let mut new_v = SolverV::new(&self.v.opts);
+        if let Some(out) = self.v.proof.out.take() {
+            new_v.set_proof_output(out);
+        }
         self.v = new_v;
         self.model.clear();
         self.conflict.clear();
         self.clauses.clear();
         self.learnts.clear();
+        self.groups.clear();
+        self.group_index.clear();
+        self.group_clauses.clear();
+        self.kept_assumptions.clear();
+        self.elim.clear();
+        self.xor_selectors.clear();
         self.tmp_c_th.clear();
         self.tmp_c_add_cl.clear();
     }
@@ -295,14 +1117,48 @@ impl<Cb: Callbacks> SolverInterface for Solver<Cb> {
         assumps: &[Lit],
     ) -> lbool {
         let old_len = self.v.assumptions.len();
+        self.v.assumptions.extend_from_slice(&self.groups);
         self.v.assumptions.extend_from_slice(assumps);
+        if !self.elim.is_empty() {
+            let assumps = self.v.assumptions.clone();
+            self.restore_vars(assumps.iter().map(|l| l.var()));
+        }
+        self.order_assumptions(self.v.assumptions.len() - assumps.len());
+        self.reuse_trail(th);
         let res = self.solve_internal(th);
+        if res == lbool::FALSE {
+            for &lit in self.conflict.iter() {
+                self.v.core_hits[lit.var()] += 1;
+            }
+        }
+        if self.v.opts.reuse_trail {
+            // the levels of the assumptions that `pop_model` keeps
+            let n = (self.v.decision_level() as usize).min(self.v.assumptions.len());
+            let kept = &self.v.assumptions[..n];
+            self.kept_assumptions.clear();
+            self.kept_assumptions.extend_from_slice(kept);
+        }
         self.v.assumptions.truncate(old_len);
         res
     }
 
     fn pop_model<Th: Theory>(&mut self, th: &mut Th) {
-        self.cancel_until(th, 0)
+        let keep = self.kept_assumptions.len() as u32;
+        let level = self.v.decision_level();
+        if keep == 0 || level == 0 {
+            return self.cancel_until(th, 0);
+        }
+        // the theory forgets the kept levels, they are created again by the next solve
+        let first_removed = self.v.vars.trail_lim[0] as usize;
+        if th.n_levels() > 0 {
+            th.pop_levels(th.n_levels());
+            th.on_backtrack(0, first_removed);
+        }
+        self.v.th_queue.clear();
+        if level > keep {
+            self.v.cancel_until(keep);
+        }
+        self.v.th_notified = self.v.th_notified.min(first_removed);
     }
 
     fn raw_value_lit(&self, l: Lit) -> lbool {
@@ -358,18 +1214,26 @@ impl<Cb: Callbacks> SolverInterface for Solver<Cb> {
 
     #[cfg(feature = "std")]
     fn print_stats(&self) {
-        println!("c restarts              : {}", self.v.starts);
-        println!("c conflicts             : {:<12}", self.v.conflicts);
+        let stats = self.stats();
+        println!("c restarts              : {}", stats.restarts);
+        println!("c conflicts             : {:<12}", stats.conflicts);
         println!(
             "c decisions             : {:<12}   ({:4.2} % random)",
-            self.v.decisions,
-            self.v.rnd_decisions as f32 * 100.0 / self.v.decisions as f32
+            stats.decisions,
+            stats.random_decisions as f32 * 100.0 / stats.decisions as f32
         );
-        println!("c propagations          : {:<12}", self.v.propagations);
+        println!("c propagations          : {:<12}", stats.propagations);
+        println!("c otfs strengthened     : {:<12}", stats.otfs_strengthened);
         println!(
             "c conflict literals     : {:<12}   ({:4.2} % deleted)",
-            self.v.tot_literals,
-            (self.v.max_literals - self.v.tot_literals) as f64 * 100.0 / self.v.max_literals as f64
+            stats.learnt_literals,
+            stats.minimized_literals as f64 * 100.0
+                / (stats.learnt_literals + stats.minimized_literals) as f64
+        );
+        println!("c deleted clauses       : {:<12}", stats.deleted_clauses);
+        println!(
+            "c garbage collections   : {:<12}   ({} bytes reclaimed)",
+            stats.gcs, stats.gc_reclaimed
         );
     }
 
@@ -412,6 +1276,36 @@ impl<Cb: Callbacks + Default> Default for Solver<Cb> {
     }
 }
 
+/// Fork the solver, with its clauses, assignments and heuristics, even in the middle of
+/// a search (see [`solve_limited_preserving_trail_th`](SolverInterface::solve_limited_preserving_trail_th)).
+///
+/// The terminate and learn callbacks, and the proof output, are not cloned. When the trail
+/// is preserved, the theory must be cloned along.
+impl<Cb: Callbacks + Clone> Clone for Solver<Cb> {
+    fn clone(&self) -> Self {
+        Solver {
+            model: self.model.clone(),
+            conflict: self.conflict.clone(),
+            cb: self.cb.clone(),
+            clauses: self.clauses.clone(),
+            learnts: self.learnts.clone(),
+            groups: self.groups.clone(),
+            group_index: self.group_index.clone(),
+            group_clauses: self.group_clauses.clone(),
+            kept_assumptions: self.kept_assumptions.clone(),
+            elim: self.elim.clone(),
+            xor_selectors: self.xor_selectors.clone(),
+            terminate: None,
+            learn: None,
+            learn_max_len: 0,
+            listener: None,
+            v: self.v.clone(),
+            tmp_c_th: vec![],
+            tmp_c_add_cl: vec![],
+        }
+    }
+}
+
 impl<Cb: Callbacks> Solver<Cb> {
     /// Create a new solver with the given options and the callbacks `cb`.
     pub fn new(opts: SolverOpts, cb: Cb) -> Self {
@@ -431,7 +1325,7 @@ struct ConflictAtLevel0;
 impl<Cb: Callbacks> Solver<Cb> {
     /// Create a new solver with the given options and callbacks.
     pub fn new_with(opts: SolverOpts, cb: Cb) -> Self {
-        assert!(opts.check());
+        opts.validate().expect("invalid options");
         Self {
             // Parameters (user settable):
             model: vec![],
@@ -439,9 +1333,19 @@ impl<Cb: Callbacks> Solver<Cb> {
             cb,
             clauses: vec![],
             learnts: vec![],
+            groups: vec![],
+            group_index: VMap::new(),
+            group_clauses: vec![],
+            kept_assumptions: vec![],
+            elim: Reconstruction::default(),
+            xor_selectors: vec![],
             v: SolverV::new(&opts),
             tmp_c_th: vec![],
             tmp_c_add_cl: vec![],
+            terminate: None,
+            learn: None,
+            learn_max_len: 0,
+            listener: None,
         }
     }
 
@@ -451,13 +1355,230 @@ impl<Cb: Callbacks> Solver<Cb> {
     }
 
     /// Tries to set the options being used to `new_opts`
-    /// If `new_opts` aren't valid, returns `Err(())` and leaves the options unchanged
-    pub fn set_options(&mut self, new_opts: SolverOpts) -> Result<(), ()> {
-        if new_opts.check() {
-            self.v.opts = new_opts;
-            Ok(())
+    /// If `new_opts` aren't valid, returns the invalid option and leaves the options
+    /// unchanged.
+    ///
+    /// All the options can be changed between two calls to the solver: the new random
+    /// seed restarts the random choices, and a new branching heuristic starts from fresh
+    /// scores.
+    pub fn set_options(&mut self, new_opts: SolverOpts) -> Result<(), InvalidOption> {
+        new_opts.validate()?;
+        let old = mem::replace(&mut self.v.opts, new_opts);
+        if self.v.opts.rand_seed() != old.rand_seed() {
+            self.v.rand = Rand::new(self.v.opts.rand_seed());
+        }
+        if self.v.opts.branching != old.branching {
+            self.v.reset_branching();
+        }
+        Ok(())
+    }
+
+    /// Set the option called `name` to `value`, see [`SolverOpts::set`] and
+    /// [`set_options`](Self::set_options).
+    ///
+    /// ```
+    /// use platsat::{BasicSolver, RestartStrategy};
+    /// let mut solver = BasicSolver::default();
+    /// solver.set_option("restart_strategy", "glucose").unwrap();
+    /// solver.set_option("tier2_lbd", "8").unwrap();
+    /// assert_eq!(solver.options().restart_strategy, RestartStrategy::Glucose);
+    /// assert!(solver.set_option("tier2_lbd", "1").is_err());
+    /// ```
+    pub fn set_option(&mut self, name: &str, value: &str) -> Result<(), SetOptionError> {
+        let mut opts = self.options();
+        opts.set(name, value)?;
+        self.set_options(opts).map_err(SetOptionError::Invalid)
+    }
+
+    /// Allow at most `n` more conflicts, counted from now over all the following calls
+    /// to the solver.
+    ///
+    /// Once the budget is exhausted, [`solve_limited`](SolverInterface::solve_limited)
+    /// returns `UNDEF`. Learnt clauses and heuristics are kept, so solving can be resumed
+    /// after raising the budget.
+    pub fn set_conflict_budget(&mut self, n: u64) {
+        let budget = self.v.conflicts.saturating_add(n);
+        self.v.conflict_budget = budget.min(i64::MAX as u64) as i64;
+    }
+
+    /// Allow at most `n` more propagations, counted from now over all the following calls
+    /// to the solver.
+    ///
+    /// See [`set_conflict_budget`](Self::set_conflict_budget).
+    pub fn set_propagation_budget(&mut self, n: u64) {
+        let budget = self.v.propagations.saturating_add(n);
+        self.v.propagation_budget = budget.min(i64::MAX as u64) as i64;
+    }
+
+    /// Allow at most `n` more ticks, counted from now over all the following calls
+    /// to the solver.
+    ///
+    /// Ticks measure the work done by unit propagation (the number of watched clauses
+    /// visited), so unlike a [time limit](Self::set_time_limit) this gives reproducible
+    /// results. See [`set_conflict_budget`](Self::set_conflict_budget).
+    pub fn set_tick_limit(&mut self, n: u64) {
+        let budget = self.v.ticks.saturating_add(n);
+        self.v.tick_budget = budget.min(i64::MAX as u64) as i64;
+    }
+
+    /// Stop searching once `limit` has elapsed from now, over all the following calls
+    /// to the solver.
+    ///
+    /// [`solve_limited`](SolverInterface::solve_limited) then returns `UNDEF`, and the
+    /// search can be resumed after setting a new limit.
+    #[cfg(feature = "std")]
+    pub fn set_time_limit(&mut self, limit: std::time::Duration) {
+        self.v.deadline = std::time::Instant::now().checked_add(limit);
+    }
+
+    /// Stop searching once the solver uses more than `bytes` bytes of memory, as measured
+    /// by [`memory_usage`](Self::memory_usage).
+    ///
+    /// The memory is checked regularly during the search. When the limit is exceeded, the
+    /// learnt clauses are reduced aggressively, and only if this is not enough,
+    /// [`solve_limited`](SolverInterface::solve_limited) returns `UNDEF` and
+    /// [`memory_limit_reached`](Self::memory_limit_reached) returns `true`. Passing
+    /// `usize::MAX` removes the limit.
+    pub fn set_memory_limit(&mut self, bytes: usize) {
+        self.v.memory_limit = bytes;
+    }
+
+    /// Was the last call to [`solve_limited`](SolverInterface::solve_limited) interrupted
+    /// by the [memory limit](Self::set_memory_limit)?
+    pub fn memory_limit_reached(&self) -> bool {
+        self.v.memory_out
+    }
+
+    /// Estimate of the memory used by the solver, in bytes: the clauses, the watch lists
+    /// and the main per-variable tables. The latter only shrink with [`compact`](Self::compact).
+    pub fn memory_usage(&self) -> usize {
+        let crefs = self.clauses.capacity() + self.learnts.capacity();
+        self.v.memory_usage() + crefs * mem::size_of::<CRef>()
+    }
+
+    /// Interrupt the search whenever `flag` is set, for instance by another thread.
+    ///
+    /// The flag is checked regularly during the search, and
+    /// [`solve_limited`](SolverInterface::solve_limited) returns `UNDEF` once it is set.
+    /// The solver remains usable, and solving can be resumed after clearing the flag,
+    /// which the solver never does by itself.
+    pub fn set_stop_flag(&mut self, flag: Arc<AtomicBool>) {
+        self.v.stop_flag = Some(StopFlag::Shared(flag));
+    }
+
+    /// Like [`set_stop_flag`](Self::set_stop_flag), with a flag that needs no allocation,
+    /// such as a `static` set by a signal handler or an interrupt.
+    pub fn set_static_stop_flag(&mut self, flag: &'static AtomicBool) {
+        self.v.stop_flag = Some(StopFlag::Static(flag));
+    }
+
+    /// Stop checking the flag given to [`set_stop_flag`](Self::set_stop_flag) or
+    /// [`set_static_stop_flag`](Self::set_static_stop_flag).
+    pub fn clear_stop_flag(&mut self) {
+        self.v.stop_flag = None;
+    }
+
+    /// Call `f` regularly during the search, which is interrupted as soon as it returns `true`.
+    ///
+    /// [`solve_limited`](SolverInterface::solve_limited) then returns `UNDEF`.
+    /// This replaces the previous callback, if any.
+    pub fn set_terminate_callback(&mut self, f: impl FnMut() -> bool + Send + 'static) {
+        self.terminate = Some(Box::new(f));
+    }
+
+    /// Call `f` on each clause learnt during the search that has at most `max_len` literals,
+    /// for instance to share it with other solvers.
+    ///
+    /// This replaces the previous callback, if any.
+    pub fn set_learn_callback(&mut self, max_len: usize, f: impl FnMut(&[Lit]) + Send + 'static) {
+        self.learn = Some(Box::new(f));
+        self.learn_max_len = max_len;
+    }
+
+    /// Notify `listener` of the clauses learnt, deleted and strengthened from now on.
+    ///
+    /// This replaces the previous listener, if any. The listener is not cloned along with
+    /// the solver.
+    pub fn set_clause_listener(&mut self, listener: impl ClauseListener + Send + 'static) {
+        self.listener = Some(Box::new(listener));
+    }
+
+    /// Remove the listener set with [`set_clause_listener`](Self::set_clause_listener),
+    /// and return it.
+    pub fn take_clause_listener(&mut self) -> Option<Box<dyn ClauseListener + Send>> {
+        self.listener.take()
+    }
+
+    /// Remove the callbacks set with [`set_terminate_callback`](Self::set_terminate_callback)
+    /// and [`set_learn_callback`](Self::set_learn_callback).
+    pub fn clear_callbacks(&mut self) {
+        self.terminate = None;
+        self.learn = None;
+    }
+
+    /// Remove the conflict, propagation and tick budgets, and the time limit.
+    pub fn budget_off(&mut self) {
+        self.v.conflict_budget = -1;
+        self.v.propagation_budget = -1;
+        self.v.tick_budget = -1;
+        #[cfg(feature = "std")]
+        {
+            self.v.deadline = None;
+        }
+    }
+
+    /// Emit a DRAT proof of every clause the solver learns, simplifies or deletes
+    /// from now on into `out`.
+    ///
+    /// The original clauses are not part of the proof; clauses added after this
+    /// is called are considered part of the problem, not of the proof.
+    /// The proof is only meaningful for pure SAT problems: theory lemmas are
+    /// logged as if they were RUP.
+    ///
+    /// If `out` [wants hints](ProofOutput::wants_hints) (LRAT), clauses are identified
+    /// by the order in which they were added, so `out` must be set before adding
+    /// any clause.
+    pub fn set_proof_output(&mut self, out: Box<dyn ProofOutput + Send>) {
+        self.v.set_proof_output(out)
+    }
+
+    /// Stop emitting a proof, and return the current proof output if any.
+    pub fn take_proof_output(&mut self) -> Option<Box<dyn ProofOutput + Send>> {
+        self.v.proof.out.take()
+    }
+
+    /// Emit a DRAT proof into `out` (binary DRAT if `binary` is true).
+    ///
+    /// See [`set_proof_output`](Self::set_proof_output).
+    #[cfg(feature = "std")]
+    pub fn set_drat_output<W: std::io::Write + Send + 'static>(&mut self, out: W, binary: bool) {
+        let w = if binary {
+            crate::drat::DratWriter::new_binary(out)
+        } else {
+            crate::drat::DratWriter::new(out)
+        };
+        self.set_proof_output(Box::new(w))
+    }
+
+    /// Emit a LRAT proof into `out` (binary LRAT if `binary` is true).
+    ///
+    /// Must be called before adding clauses, see [`set_proof_output`](Self::set_proof_output).
+    #[cfg(feature = "std")]
+    pub fn set_lrat_output<W: std::io::Write + Send + 'static>(&mut self, out: W, binary: bool) {
+        let w = if binary {
+            crate::drat::LratWriter::new_binary(out)
         } else {
-            Err(())
+            crate::drat::LratWriter::new(out)
+        };
+        self.set_proof_output(Box::new(w))
+    }
+
+    /// Flush the proof output, returning the first error that occurred while writing it.
+    #[cfg(feature = "std")]
+    pub fn flush_proof(&mut self) -> std::io::Result<()> {
+        match &mut self.v.proof.out {
+            Some(p) => p.flush(),
+            None => Ok(()),
         }
     }
 
@@ -465,7 +1586,9 @@ impl<Cb: Callbacks> Solver<Cb> {
     fn new_decision_level<Th: Theory>(&mut self, th: &mut Th) {
         trace!("new decision level {}", 1 + self.v.decision_level());
         self.v.vars.new_decision_level();
+        let timer = Timer::start(self.v.opts.theory_timing);
         th.create_level();
+        timer.stop(&mut self.v.th_stats.level_time);
         debug_assert_eq!(
             self.v.decision_level() as usize,
             th.n_levels(),
@@ -474,9 +1597,14 @@ impl<Cb: Callbacks> Solver<Cb> {
     }
 
     fn simplify_internal<Th>(&mut self, _: &mut Th) -> bool {
+        self.release_trail();
         debug_assert_eq!(self.v.decision_level(), 0);
 
-        if !self.v.ok || self.v.propagate().is_some() {
+        if !self.v.ok {
+            self.v.flush_pending_unsat();
+            return false;
+        } else if let Some(confl) = self.v.propagate() {
+            self.v.proof_unsat(Some(confl));
             self.v.ok = false;
             return false;
         }
@@ -505,7 +1633,7 @@ impl<Cb: Callbacks> Solver<Cb> {
     /// # Output:
     ///
     /// - `lbool::TRUE` if a partial assigment that is consistent with respect to the clauseset is found. If
-    ///    all variables are decision variables, this means that the clause set is satisfiable.
+    ///   all variables are decision variables, this means that the clause set is satisfiable.
     /// - `lbool::FALSE` if the clause set is unsatisfiable.
     /// - 'lbool::UNDEF` if the bound on number of conflicts is reached.
     fn search<Th: Theory>(
@@ -516,6 +1644,8 @@ impl<Cb: Callbacks> Solver<Cb> {
     ) -> lbool {
         debug_assert!(self.v.ok);
         let mut conflict_c = 0;
+        // restart to use the phases of a successful local search
+        let mut restart = false;
         self.v.starts += 1;
 
         'main: loop {
@@ -526,17 +1656,32 @@ impl<Cb: Callbacks> Solver<Cb> {
                 // conflict analysis
                 self.v.conflicts += 1;
                 conflict_c += 1;
-                if self.v.decision_level() == 0 {
+                // (with chronological backtracking, the conflict can be at level 0 too)
+                if self.v.decision_level() == 0
+                    || (self.v.opts.chrono >= 0 && self.v.clause_level(confl) == 0)
+                {
+                    self.v.proof_unsat(Some(confl));
+                    self.v.ok = false;
                     return lbool::FALSE;
                 }
 
+                if self.v.opts.target_phase || self.v.opts.rephase || self.v.opts.mode_switching {
+                    self.v.save_phases();
+                }
                 let learnt = self
                     .v
                     .analyze(Conflict::BCP(confl), &self.learnts, tmp_learnt, th);
+                self.v.new_fresh_vars();
                 self.add_learnt_and_backtrack(th, learnt, clause::Kind::Learnt);
 
-                self.v.vars.var_decay_activity(self.v.opts.var_decay);
+                self.v.vars.branch.on_conflict(&self.v.opts);
                 self.v.cla_decay_activity();
+                if self.v.opts.rephase && self.v.conflicts >= self.v.next_rephase {
+                    self.v.rephase();
+                    if self.v.opts.local_search {
+                        restart = self.local_search();
+                    }
+                }
 
                 self.v.learntsize_adjust_cnt -= 1;
                 if self.v.learntsize_adjust_cnt == 0 {
@@ -565,7 +1710,11 @@ impl<Cb: Callbacks> Solver<Cb> {
                 }
             } else {
                 // no boolean conflict
-                if (nof_conflicts >= 0 && conflict_c >= nof_conflicts) || !self.within_budget() {
+                if (nof_conflicts >= 0 && conflict_c >= nof_conflicts)
+                    || restart
+                    || self.v.glucose_restart(conflict_c)
+                    || !self.within_budget()
+                {
                     // Reached bound on number of conflicts:
                     self.v.progress_estimate = self.v.progress_estimate();
                     self.cancel_until(th, 0);
@@ -577,8 +1726,14 @@ impl<Cb: Callbacks> Solver<Cb> {
                     return lbool::FALSE;
                 }
 
-                if self.learnts.len() as f64 - self.v.num_assigns() as f64 >= self.v.max_learnts {
-                    // Reduce the set of learnt clauses:
+                // Reduce the set of learnt clauses:
+                if self.v.opts.lbd_reduce {
+                    if self.v.conflicts >= self.v.next_reduce {
+                        self.reduce_db_tiers();
+                    }
+                } else if self.learnts.len() as f64 - self.v.num_assigns() as f64
+                    >= self.v.max_learnts
+                {
                     self.reduce_db();
                 }
 
@@ -601,6 +1756,10 @@ impl<Cb: Callbacks> Solver<Cb> {
                         continue 'main;
                     }
                 }
+                if mem::take(&mut self.v.th_restart) {
+                    self.cancel_until(th, 0);
+                    return lbool::UNDEF;
+                }
 
                 // select the next decision (using assumptions, or variable heap)
                 let mut next = Lit::UNDEF;
@@ -611,11 +1770,19 @@ impl<Cb: Callbacks> Solver<Cb> {
                         // Dummy decision level, since `p` is true already:
                         self.new_decision_level(th);
                     } else if self.v.value_lit(p) == lbool::FALSE {
-                        // conflict at level 0 because of `p`, unsat
-                        let mut conflict = mem::replace(&mut self.conflict, LSet::new());
-                        self.v.analyze_final(th, !p, &mut conflict);
-                        self.cb.on_new_clause(&conflict, clause::Kind::Learnt);
-                        self.conflict = conflict;
+                        // `p` is false because of the previous assumptions, unsat
+                        let mut core = mem::replace(&mut self.conflict, LSet::new());
+                        self.v.analyze_final(th, p, &mut core);
+                        self.v.new_fresh_vars();
+                        // the learnt clause is the negation of the core
+                        let mut c = mem::take(&mut self.tmp_c_add_cl);
+                        c.clear();
+                        c.extend(core.as_slice().iter().map(|&a| !a));
+                        self.on_learnt_clause(&c, c.len() as u32);
+                        let id = self.v.proof.new_id();
+                        self.v.proof.add(id, &c);
+                        self.tmp_c_add_cl = c;
+                        self.conflict = core;
                         return lbool::FALSE;
                     } else {
                         next = p;
@@ -624,8 +1791,11 @@ impl<Cb: Callbacks> Solver<Cb> {
                 }
 
                 if next == Lit::UNDEF {
-                    // new variable decision:
-                    next = self.v.pick_branch_lit();
+                    // new variable decision, by the theory or the branching heuristic:
+                    next = self.theory_decision(th);
+                    if next == Lit::UNDEF {
+                        next = self.v.pick_branch_lit();
+                    }
 
                     if next == Lit::UNDEF {
                         // no decision? time for a theory final-check
@@ -673,28 +1843,102 @@ impl<Cb: Callbacks> Solver<Cb> {
         learnt: LearntClause,
         k: clause::Kind,
     ) {
-        self.cb.on_new_clause(learnt.clause, k);
-        self.cancel_until(th, learnt.backtrack_lvl as u32);
+        if k == clause::Kind::Learnt {
+            self.on_learnt_clause(learnt.clause, learnt.lbd);
+        } else {
+            self.on_new_clause(learnt.clause, k);
+        }
+        let id = self.v.proof.new_id();
+        self.v.proof.add(id, learnt.clause);
+        if self.v.opts.mode_switching || self.v.opts.restarts() == RestartStrategy::Glucose {
+            self.v.update_restart_emas(learnt.lbd);
+        }
+        // chronological backtracking: only undo the conflict level when the assertion
+        // level is far below it, the literals of the levels in between are kept
+        let chrono = self.v.opts.chrono;
+        let level = if learnt.clause.len() > 1
+            && chrono >= 0
+            && learnt.conflict_lvl - learnt.backtrack_lvl > chrono
+        {
+            learnt.conflict_lvl - 1
+        } else {
+            learnt.backtrack_lvl
+        };
+        self.cancel_until(th, level as u32);
+        self.strengthen_otfs();
 
         // propagate the only lit of `learnt_clause` that isn't false
         if learnt.clause.len() == 1 {
             // directly propagate the unit clause at level 0
             self.v.vars.unchecked_enqueue(learnt.clause[0], CRef::UNDEF);
+            self.v.set_unit_id(learnt.clause[0].var(), id);
         } else if learnt.clause.is_empty() {
             self.v.ok = false;
         } else {
             // propagate the lit, justified by `cr`
             let cr = self.v.ca.alloc_with_learnt(learnt.clause, true);
+            self.v.set_clause_id(cr, id);
+            self.v.ca.get_mut(cr).set_lbd(learnt.lbd);
             self.learnts.push(cr);
             self.v.attach_clause(cr);
             self.v.cla_bump_activity(&self.learnts, cr);
-            self.v.vars.unchecked_enqueue(learnt.clause[0], cr);
+            let level = learnt.backtrack_lvl;
+            self.v
+                .vars
+                .unchecked_enqueue_at(learnt.clause[0], cr, level);
         }
 
         self.flush_th_lemmas(th);
     }
 
+    /// Strengthen the clauses subsumed by a resolvent of the last conflict analysis, by
+    /// removing the literal they were resolved on (on-the-fly subsumption).
+    ///
+    /// This is done after backtracking, when they are not reasons anymore.
+    fn strengthen_otfs(&mut self) {
+        let mut otfs = mem::take(&mut self.v.otfs);
+        let (mut old, mut new) = (vec![], vec![]);
+        for (cr, p) in otfs.drain(..) {
+            debug_assert!(!self.v.locked(self.v.ca.get_ref(cr)));
+            old.clear();
+            old.extend_from_slice(self.v.ca.get_ref(cr).lits());
+            // the literals of the conflict level are not false anymore, watch them
+            let vars = &self.v.vars;
+            new.clear();
+            new.extend(old.iter().copied().filter(|&lit| {
+                lit != p && (vars.value_lit(lit) != lbool::FALSE || vars.level(lit.var()) > 0)
+            }));
+            new.sort_by_key(|&lit| vars.value_lit(lit) == lbool::FALSE);
+            debug_assert!(new.len() >= 2 && vars.value_lit(new[1]) != lbool::FALSE);
+            trace!("otfs: strengthen {:?} into {:?}", old, new);
+
+            self.v.detach_clause(cr, true);
+            let c = self.v.ca.get_ref(cr);
+            let lbd = c.learnt().then(|| c.lbd().min(new.len() as u32));
+            let mut c = self.v.ca.get_mut(cr);
+            for (i, &lit) in new.iter().enumerate() {
+                c[i as u32] = lit;
+            }
+            if let Some(lbd) = lbd {
+                c.set_lbd(lbd);
+            }
+            c.shrink(new.len() as u32);
+            self.v.ca.free_amount(old.len() as u32 - new.len() as u32);
+            self.v.attach_clause(cr);
+
+            self.on_strengthen_clause(&old, &new);
+            let old_id = self.v.clause_id(cr);
+            let id = self.v.proof.new_id();
+            self.v.proof.add(id, &new);
+            self.v.proof.delete(old_id, &old);
+            self.v.set_clause_id(cr, id);
+            self.v.otfs_strengthened += 1;
+        }
+        self.v.otfs = otfs;
+    }
+
     fn flush_th_lemmas<Th: Theory>(&mut self, th: &mut Th) {
+        self.v.new_fresh_vars();
         let mut th_st = mem::take(&mut self.v.th_st);
         let mut c = mem::take(&mut self.tmp_c_add_cl);
         for lemma in th_st.iter_lemmas() {
@@ -730,10 +1974,43 @@ impl<Cb: Callbacks> Solver<Cb> {
                 conflict: TheoryConflict::Nil,
             }
         };
+        // queued theory propagations first, then the native constraints: the theory
+        // only sees their fixpoint
+        if !th_arg.v.th_queue.is_empty() {
+            th_arg.assign_queue();
+        }
+        if th_arg.is_ok() && !th_arg.has_propagated {
+            let mut natives = mem::take(&mut th_arg.v.natives);
+            natives.check(&mut th_arg);
+            th_arg.v.natives = natives;
+        }
         // call theory
-        match k {
-            TheoryCall::Partial => th.partial_check(&mut th_arg),
-            TheoryCall::Final => th.final_check(&mut th_arg),
+        if th_arg.is_ok() && !th_arg.has_propagated {
+            th_arg.v.notify_assignments(th);
+            let trail_len = th_arg.v.vars.trail.len();
+            let timer = Timer::start(th_arg.v.opts.theory_timing);
+            let time = match k {
+                TheoryCall::Partial => {
+                    let _span = span!(TRACE, "theory.partial_check");
+                    th.partial_check(&mut th_arg);
+                    th_arg.v.th_stats.partial_checks += 1;
+                    &mut th_arg.v.th_stats.partial_check_time
+                }
+                TheoryCall::Final => {
+                    let _span = span!(DEBUG, "theory.final_check");
+                    th.final_check(&mut th_arg);
+                    th_arg.v.th_stats.final_checks += 1;
+                    &mut th_arg.v.th_stats.final_check_time
+                }
+            };
+            timer.stop(time);
+            let stats = &mut th_arg.v.th_stats;
+            match th_arg.conflict {
+                TheoryConflict::Nil => {
+                    stats.propagations += (th_arg.v.vars.trail.len() - trail_len) as u64
+                }
+                _ => stats.conflicts += 1,
+            }
         }
         let r = if let TheoryConflict::Clause { costly } = th_arg.conflict {
             if th_arg.lits.is_empty() {
@@ -762,6 +2039,9 @@ impl<Cb: Callbacks> Solver<Cb> {
             }
 
             self.flush_th_lemmas(th);
+            if !self.v.ok {
+                return Err(ConflictAtLevel0);
+            }
 
             return if has_propagated {
                 Ok(lbool::UNDEF)
@@ -773,20 +2053,96 @@ impl<Cb: Callbacks> Solver<Cb> {
             return Err(ConflictAtLevel0);
         }
         let learnt = self.v.analyze(r, &self.learnts, tmp_learnt, th);
+        self.v.new_fresh_vars();
         self.add_learnt_and_backtrack(th, learnt, clause::Kind::Theory);
         Ok(lbool::FALSE)
     }
 
+    /// The decision chosen by `th`, or `Lit::UNDEF` if it has none (or one that cannot
+    /// be decided).
+    fn theory_decision<Th: Theory>(&mut self, th: &mut Th) -> Lit {
+        let th_arg = TheoryArg {
+            v: &mut self.v,
+            lits: &mut self.tmp_c_th,
+            has_propagated: false,
+            conflict: TheoryConflict::Nil,
+        };
+        let timer = Timer::start(th_arg.v.opts.theory_timing);
+        let decision = th.decide(&th_arg);
+        timer.stop(&mut self.v.th_stats.decide_time);
+        match decision {
+            Some(lit)
+                if lit.var().idx() < self.v.num_vars()
+                    && !self.v.eliminated[lit.var()]
+                    && self.v.value_lit(lit) == lbool::UNDEF =>
+            {
+                self.v.th_stats.decisions += 1;
+                lit
+            }
+            _ => Lit::UNDEF,
+        }
+    }
+
+    /// Sort the assumptions from `start` by decreasing priority, then as given by
+    /// [`SolverOpts::assumption_order`].
+    fn order_assumptions(&mut self, start: usize) {
+        let mut assumps = mem::take(&mut self.v.assumptions);
+        let v = &self.v;
+        let priority = |lit: &Lit| cmp::Reverse(v.assump_priority[lit.var()]);
+        let to_sort = &mut assumps[start..];
+        match v.opts.assumption_order {
+            AssumptionOrder::Given => to_sort.sort_by_key(priority),
+            AssumptionOrder::Activity => to_sort.sort_by(|a, b| {
+                let score = |lit: &Lit| v.vars.branch.score(lit.var());
+                priority(a)
+                    .cmp(&priority(b))
+                    .then(score(b).total_cmp(&score(a)))
+            }),
+            AssumptionOrder::Cores => {
+                to_sort.sort_by_key(|lit| (priority(lit), cmp::Reverse(v.core_hits[lit.var()])))
+            }
+        }
+        self.v.assumptions = assumps;
+    }
+
+    /// Backtrack the levels kept by the last solve to the longest prefix of assumptions
+    /// it shares with the current ones, and create them in `th`.
+    fn reuse_trail<Th: Theory>(&mut self, th: &mut Th) {
+        let kept = mem::take(&mut self.kept_assumptions);
+        let shared = (kept.iter().zip(&self.v.assumptions))
+            .take_while(|(a, b)| a == b)
+            .count() as u32;
+        if self.v.decision_level() > shared {
+            self.v.cancel_until(shared);
+        }
+        for _ in 0..self.v.decision_level() {
+            th.create_level();
+        }
+        self.v.trail_reuses += (shared > 0) as u64;
+    }
+
+    /// Backtrack to level 0 if the last solve kept the levels of its assumptions.
+    fn release_trail(&mut self) {
+        if !mem::take(&mut self.kept_assumptions).is_empty() && self.v.decision_level() > 0 {
+            self.v.cancel_until(0);
+        }
+    }
+
     /// Main solve method (assumptions given in `self.assumptions`).
     fn solve_internal<Th: Theory>(&mut self, th: &mut Th) -> lbool {
-        assert!(self.v.decision_level() == 0);
+        assert!(self.v.decision_level() as usize <= self.v.assumptions.len());
         self.model.clear();
         self.conflict.clear();
         if !self.v.ok {
+            self.v.flush_pending_unsat();
             return lbool::FALSE;
         }
 
         self.v.solves += 1;
+        self.v.th_restart = false;
+        self.v.memory_out = false;
+        self.v.next_memory_check = self.v.conflicts;
+        let _span = span!(INFO, "solve", solves = self.v.solves);
         let mut tmp_learnt: Vec<Lit> = vec![];
 
         self.v.max_learnts = self.num_clauses() as f64 * self.v.opts.learntsize_factor;
@@ -805,7 +2161,31 @@ impl<Cb: Callbacks> Solver<Cb> {
         let mut rest_base: f64 = 1.0;
         let mut luby_state = LubyIter::new();
         loop {
-            let nof_clauses = (rest_base * self.v.opts.restart_first as f64) as i32;
+            if (self.v.opts.probe || self.v.opts.substitute_equivalences)
+                && self.v.conflicts >= self.v.next_probe
+            {
+                self.v.next_probe = self.v.conflicts + PROBE_INTERVAL;
+                self.cancel_until(th, 0);
+                if (self.v.opts.probe && !self.probe())
+                    || (self.v.opts.substitute_equivalences && !self.substitute_equivalences())
+                {
+                    status = lbool::FALSE;
+                    break;
+                }
+            }
+            if self.v.opts.vivify && self.v.conflicts >= self.v.next_vivify {
+                self.v.next_vivify = self.v.conflicts + VIVIFY_INTERVAL;
+                self.cancel_until(th, 0);
+                if !self.vivify() {
+                    status = lbool::FALSE;
+                    break;
+                }
+            }
+            let nof_clauses = match self.v.restart_strategy() {
+                RestartStrategy::Glucose => -1,
+                _ if self.v.stable => (rest_base * STABLE_RESTART_FIRST as f64) as i32,
+                _ => (rest_base * self.v.opts.restart_first as f64) as i32,
+            };
             status = self.search(th, nof_clauses, &mut tmp_learnt);
             if !self.within_budget() {
                 break;
@@ -815,12 +2195,20 @@ impl<Cb: Callbacks> Solver<Cb> {
                 break;
             } else {
                 info!("search.restart");
+                event!(DEBUG, conflicts = self.v.conflicts, "restart");
                 self.cb.on_restart();
-                if self.v.opts.luby_restart {
-                    luby_state.step(&mut rest_base, self.v.opts.restart_inc);
-                } else {
-                    rest_base *= self.v.opts.restart_inc;
-                };
+                if self.v.switch_mode() {
+                    rest_base = 1.0;
+                    luby_state = LubyIter::new();
+                    continue;
+                }
+                match self.v.restart_strategy() {
+                    RestartStrategy::Luby => {
+                        luby_state.step(&mut rest_base, self.v.opts.restart_inc)
+                    }
+                    RestartStrategy::Geometric => rest_base *= self.v.opts.restart_inc,
+                    RestartStrategy::Glucose => {}
+                }
             }
         }
 
@@ -833,15 +2221,27 @@ impl<Cb: Callbacks> Solver<Cb> {
             for i in 0..num_vars {
                 self.model[i as usize] = self.v.value(Var::from_idx(i));
             }
-        } else if status == lbool::FALSE && self.conflict.len() == 0 {
+            self.elim.extend(&mut self.model);
+        } else if status == lbool::FALSE && self.conflict.is_empty() {
             // NOTE: we may return `false` without an empty conflict in case we had assumptions. In
             // this case `self.conflict` contains the unsat-core but adding new clauses might
             // succeed in the absence of these assumptions.
+            if self.v.ok {
+                self.v.proof_unsat(None);
+            }
             self.v.ok = false;
         }
 
         debug!("res: {:?}", status);
         trace!("proved at lvl 0: {:?}", self.v.vars.proved_at_lvl_0());
+        event!(
+            INFO,
+            result = ?status,
+            conflicts = self.v.conflicts,
+            decisions = self.v.decisions,
+            propagations = self.v.propagations,
+            "solve.done"
+        );
         status
     }
 
@@ -851,6 +2251,7 @@ impl<Cb: Callbacks> Solver<Cb> {
         let extra_lim = self.v.cla_inc / self.learnts.len() as f64; // Remove any clause below this activity
 
         debug!("reduce_db.start");
+        let _span = span!(DEBUG, "reduce_db", learnts = self.learnts.len());
 
         {
             let ca = &self.v.ca;
@@ -877,7 +2278,12 @@ impl<Cb: Callbacks> Solver<Cb> {
             };
             if cond {
                 self.v.remove_clause(cr);
-                self.cb.on_delete_clause(self.v.ca.get_ref(cr).lits());
+                let lits = self.v.ca.get_ref(cr).lits();
+                self.cb.on_delete_clause(lits);
+                if let Some(listener) = &mut self.listener {
+                    listener.on_deleted(lits);
+                }
+                self.v.proof_delete(cr);
             } else {
                 self.learnts[j] = cr;
                 j += 1;
@@ -885,29 +2291,126 @@ impl<Cb: Callbacks> Solver<Cb> {
         }
 
         // self.learnts.resize_default(j);
-        let _deleted = self.learnts.len() - j;
+        let deleted = self.learnts.len() - j;
+        self.v.deleted_clauses += deleted as u64;
         self.learnts.resize(j, CRef::UNDEF);
+        self.cb.on_reduce_db(j, deleted);
+
+        debug!("reduce_db.done (deleted {})", deleted);
+        event!(DEBUG, deleted, "reduce_db.done");
+
+        self.check_garbage();
+    }
+
+    /// Reduce the learnt clauses by tiers of LBD, as in Glucose: the clauses of the core tier
+    /// are kept, those of the second tier are kept if they were used since the last reduction,
+    /// and the least active half of the others is removed. Binary and locked clauses are never
+    /// removed.
+    fn reduce_db_tiers(&mut self) {
+        let _span = span!(DEBUG, "reduce_db_tiers", learnts = self.learnts.len());
+        let opts = &self.v.opts;
+        self.v.num_reduces += 1;
+        self.v.next_reduce = self.v.conflicts
+            + opts.reduce_first as u64
+            + self.v.num_reduces * opts.reduce_inc as u64;
+        let (tier1_lbd, tier2_lbd) = (opts.tier1_lbd, opts.tier2_lbd);
+        let kept = |c: ClauseRef| {
+            c.size() <= 2 || c.lbd() <= tier1_lbd || (c.lbd() <= tier2_lbd && c.used())
+        };
+
+        debug!("reduce_db_tiers.start");
+
+        // the local clauses first, by increasing activity
+        let ca = &self.v.ca;
+        self.learnts.sort_unstable_by(|&x, &y| {
+            let x = ca.get_ref(x);
+            let y = ca.get_ref(y);
+            Ord::cmp(&kept(x), &kept(y))
+                .then(PartialOrd::partial_cmp(&x.activity(), &y.activity()).expect("NaN activity"))
+        });
+        let n_local = self
+            .learnts
+            .iter()
+            .take_while(|&&cr| !kept(ca.get_ref(cr)))
+            .count();
+
+        let mut j = 0;
+        for i in 0..self.learnts.len() {
+            let cr = self.learnts[i];
+            if i < n_local / 2 && !self.v.locked(self.v.ca.get_ref(cr)) {
+                self.v.remove_clause(cr);
+                let lits = self.v.ca.get_ref(cr).lits();
+                self.cb.on_delete_clause(lits);
+                if let Some(listener) = &mut self.listener {
+                    listener.on_deleted(lits);
+                }
+                self.v.proof_delete(cr);
+            } else {
+                self.v.ca.get_mut(cr).set_used(false);
+                self.learnts[j] = cr;
+                j += 1;
+            }
+        }
+
+        let deleted = self.learnts.len() - j;
+        self.v.deleted_clauses += deleted as u64;
+        self.learnts.truncate(j);
+        self.cb.on_reduce_db(j, deleted);
 
-        debug!("reduce_db.done (deleted {})", _deleted);
+        debug!("reduce_db_tiers.done (deleted {})", deleted);
+        event!(DEBUG, deleted, "reduce_db.done");
 
         self.check_garbage();
     }
 
+    /// Remove the clauses of the given set that contain `lit`, which is true at level 0.
+    fn remove_clauses_with(&mut self, which: ClauseSetSelect, lit: Lit) {
+        debug_assert_eq!(self.v.value_lit(lit), lbool::TRUE);
+        let learnt = matches!(which, ClauseSetSelect::Learnt);
+        let cs: &mut Vec<CRef> = match which {
+            ClauseSetSelect::Learnt => &mut self.learnts,
+            ClauseSetSelect::Original => &mut self.clauses,
+        };
+        let self_v = &mut self.v;
+        let listener = &mut self.listener;
+        cs.retain(|&cr| {
+            let contains = simd::find_lit(self_v.ca.get_ref(cr).lits(), lit).is_some();
+            if contains {
+                self_v.remove_clause(cr);
+                self_v.proof_delete(cr);
+                if let Some(listener) = listener.as_mut().filter(|_| learnt) {
+                    listener.on_deleted(self_v.ca.get_ref(cr).lits());
+                }
+            }
+            !contains
+        });
+    }
+
     /// Shrink the given set to contain only non-satisfied clauses.
     fn remove_satisfied(&mut self, which: ClauseSetSelect) {
         assert_eq!(self.v.decision_level(), 0);
+        let learnt = matches!(which, ClauseSetSelect::Learnt);
         let cs: &mut Vec<CRef> = match which {
             ClauseSetSelect::Learnt => &mut self.learnts,
             ClauseSetSelect::Original => &mut self.clauses,
         };
         let self_v = &mut self.v;
+        let listener = &mut self.listener;
+        let mut old_lits = vec![];
         cs.retain(|&cr| {
             let satisfied = self_v.satisfied(self_v.ca.get_ref(cr));
             if satisfied {
                 self_v.remove_clause(cr);
                 debug!("remove satisfied clause {:?}", self_v.ca.get_ref(cr).lits());
-            // we should not need to tell the proof checker to remove the clause
+                self_v.proof_delete(cr);
+                if let Some(listener) = listener.as_mut().filter(|_| learnt) {
+                    listener.on_deleted(self_v.ca.get_ref(cr).lits());
+                }
             } else {
+                if self_v.proof.out.is_some() || listener.is_some() {
+                    old_lits.clear();
+                    old_lits.extend_from_slice(self_v.ca.get_ref(cr).lits());
+                }
                 let amount_shaved = {
                     let mut c = self_v.ca.get_mut(cr);
                     // Trim clause (but keep the 2 first lits as they are watching):
@@ -931,11 +2434,458 @@ impl<Cb: Callbacks> Solver<Cb> {
                 };
                 // It was not in MiniSAT, but it is needed for correct wasted calculation.
                 self_v.ca.free_amount(amount_shaved);
+                if let Some(listener) = listener.as_mut().filter(|_| amount_shaved > 0) {
+                    listener.on_strengthened(&old_lits, self_v.ca.get_ref(cr).lits());
+                }
+                if amount_shaved > 0 && self_v.proof.out.is_some() {
+                    // the shorter clause is RUP using the level 0 units
+                    let old_id = self_v.clause_id(cr);
+                    if self_v.proof.lrat {
+                        for &lit in &old_lits {
+                            if self_v.value_lit(lit) == lbool::FALSE {
+                                let h = self_v.unit_id(lit.var());
+                                self_v.proof.hints.push(h);
+                            }
+                        }
+                        self_v.proof.hints.push(old_id);
+                    }
+                    let id = self_v.proof.new_id();
+                    self_v.proof.add(id, self_v.ca.get_ref(cr).lits());
+                    self_v.proof.delete(old_id, &old_lits);
+                    self_v.set_clause_id(cr, id);
+                }
             }
             !satisfied
         });
     }
 
+    /// Vivify the long clauses: assign the negations of their literals one by one, and
+    /// shorten them when unit propagation shows that the other literals are not needed.
+    ///
+    /// Learnt and original clauses are visited in turns from where the last call stopped,
+    /// for a number of ticks proportional to those of the search since then.
+    /// Returns `false` if the solver is in an `UNSAT` state.
+    fn vivify(&mut self) -> bool {
+        debug_assert_eq!(self.v.decision_level(), 0);
+        if let Some(confl) = self.v.propagate() {
+            self.v.proof_unsat(Some(confl));
+            self.v.ok = false;
+            return false;
+        }
+        let effort = (self.v.ticks - self.v.vivify_ticks) as f64 * self.v.opts.vivify_effort;
+        let mut out = vec![];
+        // the assignments made to vivify the clauses must not change the saved phases
+        let phase_saving = mem::replace(&mut self.v.opts.phase_saving, 0);
+        for learnt in [true, false] {
+            let budget = self.v.ticks + (effort / 2.0) as u64;
+            let mut cs = mem::take(if learnt {
+                &mut self.learnts
+            } else {
+                &mut self.clauses
+            });
+            let mut i = self.v.vivify_next[learnt as usize];
+            for _ in 0..cs.len() {
+                if self.v.ticks >= budget {
+                    break;
+                }
+                i = if i + 1 < cs.len() { i + 1 } else { 0 };
+                let cr = cs[i];
+                if self.v.ca.get_ref(cr).size() <= 2 || !self.vivify_clause(cr, &mut out) {
+                    continue;
+                }
+                debug!("vivify {:?} into {:?}", self.v.ca.get_ref(cr).lits(), out);
+                let old = self.v.ca.get_ref(cr).lits().to_vec();
+                self.on_strengthen_clause(&old, &out);
+                let id = self.v.proof.new_id();
+                self.v.proof.add(id, &out);
+                self.v.proof_delete(cr);
+                self.v.remove_clause(cr);
+                if out.len() == 1 {
+                    self.v.vars.unchecked_enqueue(out[0], CRef::UNDEF);
+                    self.v.set_unit_id(out[0].var(), id);
+                    cs[i] = CRef::UNDEF;
+                } else {
+                    let new_cr = self.v.ca.alloc_with_learnt(&out, learnt);
+                    self.v.set_clause_id(new_cr, id);
+                    if learnt {
+                        let old = self.v.ca.get_ref(cr);
+                        let (act, lbd) = (old.activity(), old.lbd().min(out.len() as u32));
+                        let mut c = self.v.ca.get_mut(new_cr);
+                        c.set_activity(act);
+                        c.set_lbd(lbd);
+                    }
+                    self.v.attach_clause(new_cr);
+                    cs[i] = new_cr;
+                }
+            }
+            self.v.vivify_next[learnt as usize] = i;
+            cs.retain(|&cr| cr != CRef::UNDEF);
+            *(if learnt {
+                &mut self.learnts
+            } else {
+                &mut self.clauses
+            }) = cs;
+        }
+        self.v.opts.phase_saving = phase_saving;
+        self.v.vivify_ticks = self.v.ticks;
+        true
+    }
+
+    /// Try to shorten the clause `cr`, which is not satisfied at level 0.
+    ///
+    /// Returns `true` if a subset of its literals, put in `out`, is implied by unit
+    /// propagation; its LRAT antecedents are then in `self.v.proof.hints`.
+    fn vivify_clause(&mut self, cr: CRef, out: &mut Vec<Lit>) -> bool {
+        let c = self.v.ca.get_ref(cr);
+        if self.v.satisfied(c) {
+            return false;
+        }
+        let mut lits = mem::take(&mut self.tmp_c_add_cl);
+        lits.clear();
+        lits.extend_from_slice(c.lits());
+        out.clear();
+        self.v.vars.new_decision_level();
+        let mut confl = None;
+        for &l in &lits {
+            let value = self.v.value_lit(l);
+            if value == lbool::TRUE {
+                // implied by the negations of the previous literals
+                out.push(l);
+                confl = Some(self.v.reason(l.var()));
+                break;
+            } else if value == lbool::UNDEF {
+                out.push(l);
+                self.v.vars.unchecked_enqueue(!l, CRef::UNDEF);
+                confl = self.v.propagate();
+                if confl.is_some() {
+                    break;
+                }
+            }
+        }
+        let shorter = out.len() < lits.len();
+        if let Some(confl) = confl.filter(|_| shorter && self.v.proof.lrat) {
+            self.v.lrat_chain(confl, out);
+        }
+        self.v.cancel_until(0);
+        self.tmp_c_add_cl = lits;
+        shorter && confl.is_some()
+    }
+
+    /// Probe the literals that imply others by binary clauses: assign each of them at
+    /// level 1 and propagate.
+    ///
+    /// A literal whose propagation fails is learnt false. Otherwise, the literals it
+    /// implies through longer clauses are learnt as binary clauses (hyper-binary
+    /// resolution), to strengthen the binary implication graph.
+    /// Variables are visited from where the last call stopped, for a number of ticks
+    /// proportional to those of the search since then.
+    /// Returns `false` if the solver is in an `UNSAT` state.
+    fn probe(&mut self) -> bool {
+        debug_assert_eq!(self.v.decision_level(), 0);
+        if let Some(confl) = self.v.propagate() {
+            self.v.proof_unsat(Some(confl));
+            self.v.ok = false;
+            return false;
+        }
+        let effort = (self.v.ticks - self.v.probe_ticks) as f64 * self.v.opts.probe_effort;
+        let budget = self.v.ticks + effort as u64;
+        let n = self.num_vars();
+        let phase_saving = mem::replace(&mut self.v.opts.phase_saving, 0);
+        let mut ok = true;
+        let mut i = self.v.probe_next;
+        for _ in 0..n {
+            if self.v.ticks >= budget {
+                break;
+            }
+            i = if i + 1 < n { i + 1 } else { 0 };
+            let v = Var::from_idx(i);
+            if !self.v.decision[v] {
+                continue;
+            }
+            for lit in [Lit::new(v, true), Lit::new(v, false)] {
+                if self.v.value_lit(lit) == lbool::UNDEF && self.v.has_binary_implication(lit) {
+                    ok = self.probe_lit(lit);
+                    if !ok {
+                        break;
+                    }
+                }
+            }
+            if !ok {
+                break;
+            }
+        }
+        self.v.probe_next = i;
+        self.v.opts.phase_saving = phase_saving;
+        self.v.probe_ticks = self.v.ticks;
+        ok
+    }
+
+    /// Run a local search on the original clauses from the saved phases, and save the best
+    /// assignment it finds as the phases, see [`SolverOpts::local_search`].
+    ///
+    /// The local search runs for a number of ticks proportional to those of the search
+    /// since the last one. Returns `true` if the assignment satisfies all the clauses.
+    fn local_search(&mut self) -> bool {
+        let effort =
+            (self.v.ticks - self.v.local_search_ticks) as f64 * self.v.opts.local_search_effort;
+        self.v.local_search_ticks = self.v.ticks;
+        let vars = &self.v.vars;
+        let fixed = |v: Var| vars.value(v) != lbool::UNDEF && vars.level(v) == 0;
+        // the clauses not satisfied at level 0, without their false literals
+        let mut lits = vec![];
+        'clauses: for &cr in &self.clauses {
+            let c = self.v.ca.get_ref(cr);
+            if c.mark() == 1 {
+                continue;
+            }
+            let start = lits.len();
+            for &lit in c.lits() {
+                if !fixed(lit.var()) {
+                    lits.push(lit);
+                } else if vars.value_lit(lit) == lbool::TRUE {
+                    lits.truncate(start);
+                    continue 'clauses;
+                }
+            }
+            if lits.len() > start {
+                lits.push(Lit::UNDEF);
+            }
+        }
+        let n = self.v.next_var.idx();
+        let mut phases: Vec<bool> = (0..n).map(|i| self.v.polarity[Var::from_idx(i)]).collect();
+        let seed = self.v.rand.next_u64();
+        let (unsat, _ticks) = walk(&lits, &mut phases, effort as u64, seed);
+        debug!(
+            "local search: {} falsified clauses in {} ticks",
+            unsat, _ticks
+        );
+        for (i, phase) in phases.into_iter().enumerate() {
+            self.v.polarity[Var::from_idx(i as u32)] = phase;
+        }
+        unsat == 0
+    }
+
+    /// Assign `lit` at level 1 and propagate, see [`Solver::probe`].
+    ///
+    /// Returns `false` if the solver is in an `UNSAT` state.
+    fn probe_lit(&mut self, lit: Lit) -> bool {
+        self.v.vars.new_decision_level();
+        self.v.vars.unchecked_enqueue(lit, CRef::UNDEF);
+        if let Some(confl) = self.v.propagate() {
+            debug!("failed literal {:?}", lit);
+            if self.v.proof.lrat {
+                self.v.lrat_chain(confl, &[!lit]);
+            }
+            self.v.cancel_until(0);
+            self.on_learnt_clause(&[!lit], 1);
+            let id = self.v.proof.new_id();
+            self.v.proof.add(id, &[!lit]);
+            self.v.vars.unchecked_enqueue(!lit, CRef::UNDEF);
+            self.v.set_unit_id(lit.var(), id);
+            if let Some(confl) = self.v.propagate() {
+                self.v.proof_unsat(Some(confl));
+                self.v.ok = false;
+                return false;
+            }
+            return true;
+        }
+        // hyper-binary resolution: `lit` implies `q` through the long clause `reason`
+        let mut hbr = vec![];
+        let start = self.v.vars.trail_lim[0] as usize + 1;
+        for j in start..self.v.vars.trail.len() {
+            let q = self.v.vars.trail[j];
+            let reason = self.v.reason(q.var());
+            if self.v.ca.get_ref(reason).size() <= 2 {
+                continue;
+            }
+            if self.v.proof.lrat {
+                self.v.lrat_chain(reason, &[!lit, q]);
+            }
+            let id = self.v.proof.new_id();
+            self.v.proof.add(id, &[!lit, q]);
+            hbr.push((q, id));
+        }
+        self.v.cancel_until(0);
+        for (q, id) in hbr {
+            let c = [!lit, q];
+            debug!("hyper-binary resolvent {:?}", c);
+            self.on_learnt_clause(&c, 2);
+            let cr = self.v.ca.alloc_with_learnt(&c, true);
+            self.v.set_clause_id(cr, id);
+            self.v.attach_clause(cr);
+            self.learnts.push(cr);
+        }
+        true
+    }
+
+    /// Replace the literals that are equivalent, according to the strongly connected
+    /// components of the binary implication graph, by a representative of their component.
+    ///
+    /// The substituted variables are removed as if they were eliminated by
+    /// [`Solver::eliminate`], with the clauses defining them as equal to their
+    /// representative, and are restored in the same way.
+    /// Returns `false` if the solver is in an `UNSAT` state.
+    fn substitute_equivalences(&mut self) -> bool {
+        debug_assert_eq!(self.v.decision_level(), 0);
+        if let Some(confl) = self.v.propagate() {
+            self.v.proof_unsat(Some(confl));
+            self.v.ok = false;
+            return false;
+        } else if self.v.proof.lrat {
+            return true;
+        }
+        let n = self.num_vars() as usize;
+        let mut binaries = vec![];
+        let unassigned = (0..n as u32)
+            .map(Var::from_idx)
+            .filter(|&v| self.v.value(v) == lbool::UNDEF);
+        for p in unassigned.flat_map(|v| [Lit::new(v, false), Lit::new(v, true)]) {
+            // each clause is watched by both its literals
+            for w in &self.v.bin_watches_data[p] {
+                let c = [!p, w.blocker];
+                if c[0] < c[1]
+                    && self.v.value_lit(c[1]) == lbool::UNDEF
+                    && self.v.ca.get_ref(w.cref).mark() != 1
+                {
+                    binaries.push(c);
+                }
+            }
+        }
+        let mut assumed = vec![false; n];
+        for &a in &self.v.assumptions {
+            assumed[a.var().idx() as usize] = true;
+        }
+        let self_v = &self.v;
+        let substitutable = |v: Var| {
+            !self_v.frozen[v]
+                && !self_v.blocking[v]
+                && self_v.decision[v]
+                && !self_v.natives.contains_var(v)
+                && !assumed[v.idx() as usize]
+        };
+        // representative of the positive literal of each substituted variable
+        let mut repr = vec![Lit::UNDEF; n];
+        let mut substituted = vec![];
+        for mut scc in binary_sccs(n as u32, &binaries) {
+            if scc.iter().any(|&l| self_v.blocking[l.var()]) {
+                continue; // the models are not extended in the right order
+            }
+            if scc.iter().any(|&l| self.xor_selectors.contains(&l.var())) {
+                continue;
+            }
+            scc.sort_unstable();
+            if let Some(w) = scc.windows(2).find(|w| w[0] == !w[1]) {
+                // both polarities of a variable are equivalent
+                let l = w[0];
+                self.v.proof.add(0, &[l]);
+                self.v.vars.unchecked_enqueue(l, CRef::UNDEF);
+                let confl = self.v.propagate();
+                self.v.proof_unsat(confl);
+                self.v.ok = false;
+                return false;
+            }
+            let r = *scc
+                .iter()
+                .min_by_key(|l| (substitutable(l.var()), l.var()))
+                .unwrap();
+            if !r.sign() {
+                continue; // the component of the negations is substituted instead
+            }
+            for &l in &scc {
+                if l != r && substitutable(l.var()) {
+                    repr[l.var().idx() as usize] = r ^ !l.sign();
+                    substituted.push(l.var());
+                }
+            }
+        }
+        if substituted.is_empty() {
+            return true;
+        }
+        debug!("substitute {} equivalent variables", substituted.len());
+        let map = |l: Lit| match repr[l.var().idx() as usize] {
+            Lit::UNDEF => l,
+            r => r ^ !l.sign(),
+        };
+        let mut lits = vec![];
+        let mut units = vec![];
+        let mut removed = vec![];
+        for learnt in [false, true] {
+            let mut cs = mem::take(if learnt {
+                &mut self.learnts
+            } else {
+                &mut self.clauses
+            });
+            for cr in &mut cs {
+                let c = self.v.ca.get_ref(*cr);
+                if c.iter().all(|&l| map(l) == l) {
+                    continue;
+                }
+                removed.push(*cr);
+                lits.clear();
+                lits.extend(c.iter().map(|&l| map(l)));
+                lits.sort_unstable();
+                lits.dedup();
+                let tautology = lits.windows(2).any(|w| w[0] == !w[1]);
+                if tautology || lits.iter().any(|&l| self.v.value_lit(l) == lbool::TRUE) {
+                    *cr = CRef::UNDEF;
+                    continue;
+                }
+                lits.retain(|&l| self.v.value_lit(l) == lbool::UNDEF);
+                self.v.proof.add(0, &lits);
+                if lits.len() == 1 {
+                    units.push(lits[0]);
+                    *cr = CRef::UNDEF;
+                } else {
+                    let new_cr = self.v.ca.alloc_with_learnt(&lits, learnt);
+                    if learnt {
+                        let old = self.v.ca.get_ref(*cr);
+                        let (act, lbd) = (old.activity(), old.lbd().min(lits.len() as u32));
+                        let mut c = self.v.ca.get_mut(new_cr);
+                        c.set_activity(act);
+                        c.set_lbd(lbd);
+                    }
+                    self.v.attach_clause(new_cr);
+                    *cr = new_cr;
+                }
+            }
+            cs.retain(|&cr| cr != CRef::UNDEF);
+            *(if learnt {
+                &mut self.learnts
+            } else {
+                &mut self.clauses
+            }) = cs;
+        }
+        // the old clauses are deleted last, as the new ones are derived from all of them
+        for cr in removed {
+            self.v.proof_delete(cr);
+            self.v.remove_clause(cr);
+        }
+        for v in substituted {
+            let x = Lit::new(v, true);
+            let r = repr[v.idx() as usize];
+            self.elim.push(x, &[x, !r]);
+            self.elim.push(!x, &[!x, r]);
+            self.v.eliminated[v] = true;
+            self.v.set_decision_var(v, false);
+        }
+        for l in units {
+            if self.v.value_lit(l) == lbool::FALSE {
+                self.v.proof_unsat(None);
+                self.v.ok = false;
+                return false;
+            } else if self.v.value_lit(l) == lbool::UNDEF {
+                self.v.vars.unchecked_enqueue(l, CRef::UNDEF);
+            }
+        }
+        if let Some(confl) = self.v.propagate() {
+            self.v.proof_unsat(Some(confl));
+            self.v.ok = false;
+            return false;
+        }
+        self.check_garbage();
+        true
+    }
+
     /// Revert to the state at given level (keeping all assignment at `level` but not beyond).
     fn cancel_until<Th: Theory>(&mut self, th: &mut Th, level: u32) {
         let dl = self.v.decision_level();
@@ -946,8 +2896,12 @@ impl<Cb: Callbacks> Solver<Cb> {
                 level,
                 n_th_levels
             );
+            let first_removed = self.v.vars.trail_lim[level as usize] as usize;
             self.v.cancel_until(level);
+            let timer = Timer::start(self.v.opts.theory_timing);
             th.pop_levels(n_th_levels); // backtrack theory state
+            th.on_backtrack(level, first_removed);
+            timer.stop(&mut self.v.th_stats.level_time);
         }
     }
 
@@ -957,15 +2911,18 @@ impl<Cb: Callbacks> Solver<Cb> {
         // Initialize the next region to a size corresponding to the estimated utilization degree. This
         // is not precise but should avoid some unnecessary reallocations for the new region:
         let mut to = ClauseAllocator::with_start_cap(self.v.ca.len() - self.v.ca.wasted());
+        to.set_clause_ids(self.v.ca.has_clause_ids());
 
         self.v
             .reloc_all(&mut self.learnts, &mut self.clauses, &mut to);
 
-        self.cb.on_gc(
-            (self.v.ca.len() * ClauseAllocator::UNIT_SIZE) as usize,
-            (to.len() * ClauseAllocator::UNIT_SIZE) as usize,
-        );
+        let old_size = self.v.ca.len() * ClauseAllocator::UNIT_SIZE as usize;
+        let new_size = to.len() * ClauseAllocator::UNIT_SIZE as usize;
+        event!(DEBUG, old_size, new_size, "garbage_collect");
+        self.cb.on_gc(old_size, new_size);
         self.v.ca = to;
+        self.v.gcs += 1;
+        self.v.gc_reclaimed += ((old_size - new_size) / 8) as u64;
     }
 
     /// Check whether the space wasted by dead clauses in the clause allocator exceeds
@@ -976,6 +2933,32 @@ impl<Cb: Callbacks> Solver<Cb> {
         }
     }
 
+    /// Statistics of the solver since its creation.
+    pub fn stats(&self) -> Stats {
+        let v = &self.v;
+        Stats {
+            solves: v.solves,
+            restarts: v.starts,
+            conflicts: v.conflicts,
+            decisions: v.decisions,
+            random_decisions: v.rnd_decisions,
+            propagations: v.propagations,
+            ticks: v.ticks,
+            learnt_literals: v.tot_literals,
+            minimized_literals: v.max_literals - v.tot_literals,
+            otfs_strengthened: v.otfs_strengthened,
+            clauses: v.num_clauses,
+            learnts: v.num_learnts,
+            deleted_clauses: v.deleted_clauses,
+            gcs: v.gcs,
+            gc_reclaimed: v.gc_reclaimed,
+            mode_switches: v.mode_switches,
+            trail_reuses: v.trail_reuses,
+            clause_memory: v.ca.len() * ClauseAllocator::UNIT_SIZE as usize / 8,
+            theory: v.th_stats,
+        }
+    }
+
     /// Temporary access to the callbacks
     pub fn cb_mut(&mut self) -> &mut Cb {
         &mut self.cb
@@ -986,1158 +2969,6024 @@ impl<Cb: Callbacks> Solver<Cb> {
         &self.cb
     }
 
-    pub fn dimacs_model(&self) -> SolverPrintDimacs<Cb> {
+    pub fn dimacs_model(&self) -> SolverPrintDimacs<'_, Cb> {
         SolverPrintDimacs {
             s: self,
             model: true,
         }
     }
 
-    fn within_budget(&self) -> bool {
-        (self.v.conflict_budget < 0 || self.v.conflicts < self.v.conflict_budget as u64)
-            && (self.v.propagation_budget < 0
-                || self.v.propagations < self.v.propagation_budget as u64)
-            && !self.cb.stop()
-    }
-
-    /// Add clause.
+    /// Add the XOR constraint `lits[0] ^ lits[1] ^ ... = parity`, that is an odd number
+    /// of the literals must be true if `parity` is `true`, and an even number otherwise.
+    /// Returns `false` if the solver is in an `UNSAT` state.
     ///
-    /// Precondition: `clause` is sorted for some ordering on `Lit`
-    fn add_clause_(&mut self, clause: &mut Vec<Lit>) -> bool {
+    /// Constraints over one or two variables are added as clauses. The others are
+    /// propagated together by Gauss-Jordan elimination (before the theory is called),
+    /// which is not justified in the proofs.
+    pub fn add_xor_clause(&mut self, lits: &[Lit], parity: bool) -> bool {
+        self.release_trail();
+        debug_assert_eq!(self.v.decision_level(), 0);
         if !self.v.ok {
             return false;
         }
-
-        let mut last_lit = Lit::UNDEF;
-        let mut j = 0;
-        // remove duplicates, true literals, etc.
-        for i in 0..clause.len() {
-            let lit_i = clause[i];
-            let value = self.v.value_lit(lit_i);
-            let lvl = self.v.level_lit(lit_i);
-            if (value == lbool::TRUE && lvl == 0) || lit_i == !last_lit {
-                return true; // tauto or satisfied already at level 0
-            } else if !(value == lbool::FALSE && lvl == 0) && lit_i != last_lit {
-                // not a duplicate
-                last_lit = lit_i;
-                clause[j] = lit_i;
-                j += 1;
+        let (vars, rhs) = self.normalize_xor(lits, parity);
+        if vars.len() <= 2 {
+            // one clause per assignment of `vars` with the wrong parity
+            let mut c = vec![];
+            for a in 0u32..(1 << vars.len()) {
+                if (a.count_ones() % 2 == 1) != rhs {
+                    c.clear();
+                    c.extend(
+                        vars.iter()
+                            .enumerate()
+                            .map(|(k, &v)| Lit::new(v, a & (1 << k) == 0)),
+                    );
+                    if !self.add_clause_reuse(&mut c) {
+                        return false;
+                    }
+                }
             }
+            true
+        } else if self.v.natives.xor.add(&vars, rhs) {
+            true
+        } else {
+            self.add_clause_reuse(&mut vec![])
         }
+    }
 
-        clause.resize(j, Lit::UNDEF);
-        if clause.is_empty() {
-            self.v.ok = false;
+    /// Normalize the XOR constraint over `lits` into `vars[0] ^ vars[1] ^ ... = rhs`
+    /// over unassigned variables, restoring the eliminated ones.
+    fn normalize_xor(&mut self, lits: &[Lit], parity: bool) -> (Vec<Var>, bool) {
+        self.restore_vars(lits.iter().map(|l| l.var()));
+        let mut rhs = parity;
+        let mut vars = vec![];
+        for &lit in lits {
+            rhs ^= !lit.sign();
+            let val = self.v.value(lit.var());
+            if val == lbool::UNDEF {
+                vars.push(lit.var());
+            } else {
+                rhs ^= val == lbool::TRUE;
+            }
+        }
+        vars.sort_unstable();
+        // `x ^ x = 0`
+        let mut n = 0;
+        let mut i = 0;
+        while i < vars.len() {
+            let j = i + vars[i..].iter().take_while(|&&v| v == vars[i]).count();
+            if (j - i) % 2 == 1 {
+                vars[n] = vars[i];
+                n += 1;
+            }
+            i = j;
+        }
+        vars.truncate(n);
+        (vars, rhs)
+    }
+
+    /// Add the constraint "at most `k` of `lits` are true". Returns `false` if the solver
+    /// is in an `UNSAT` state.
+    ///
+    /// A variable can occur several times in `lits`: `x, !x` always counts as one true
+    /// literal, and a literal that occurs `n` times counts `n` times, which makes it a
+    /// pseudo-Boolean constraint (see [`add_pb_constraint`](Self::add_pb_constraint)).
+    /// Constraints that are equivalent to clauses are added as such, the others are
+    /// propagated natively by counting their true literals. As with
+    /// [`add_xor_clause`](Self::add_xor_clause), the propagations are not justified in
+    /// the proofs.
+    pub fn add_at_most_k(&mut self, lits: &[Lit], k: u32) -> bool {
+        self.release_trail();
+        debug_assert_eq!(self.v.decision_level(), 0);
+        if !self.v.ok {
             return false;
-        } else if clause.len() == 1 {
-            self.v.vars.unchecked_enqueue(clause[0], CRef::UNDEF);
+        }
+        let (merged, pairs) = merge_card_lits(lits);
+        if merged.len() < lits.len() {
+            let Some(k) = k.checked_sub(pairs) else {
+                return self.add_clause_reuse(&mut vec![]);
+            };
+            if merged.iter().any(|&(_, n)| n > 1) {
+                // `sum(n * lit) <= k`
+                let terms: Vec<(i64, Lit)> =
+                    merged.iter().map(|&(lit, n)| (-(n as i64), lit)).collect();
+                return self.add_pb_constraint(&terms, -(k as i64));
+            }
+            let lits: Vec<Lit> = merged.iter().map(|t| t.0).collect();
+            return self.add_at_most_k(&lits, k);
+        }
+        self.restore_vars(lits.iter().map(|l| l.var()));
+        let mut k = k as usize;
+        let mut c: Vec<Lit> = vec![];
+        for &lit in lits {
+            let val = self.v.value_lit(lit);
+            if val == lbool::UNDEF {
+                c.push(lit);
+            } else if val == lbool::TRUE {
+                if k == 0 {
+                    return self.add_clause_reuse(&mut vec![]);
+                }
+                k -= 1;
+            }
+        }
+        if k >= c.len() {
+            true
+        } else if k == 0 {
+            c.iter().all(|&lit| self.add_clause_reuse(&mut vec![!lit]))
+        } else if k + 1 == c.len() {
+            // not all of them
+            c.iter_mut().for_each(|lit| *lit = !*lit);
+            self.add_clause_reuse(&mut c)
         } else {
-            let cr = self.v.ca.alloc_with_learnt(clause, false);
-            self.clauses.push(cr);
-            self.v.attach_clause(cr);
+            self.v.natives.card.add(&c, k as u32);
+            true
         }
+    }
 
-        true
+    /// Add the constraint "at most one of `lits` is true". Returns `false` if the solver
+    /// is in an `UNSAT` state.
+    ///
+    /// Small constraints are added as binary clauses. Larger ones are propagated natively,
+    /// as with [`add_at_most_k`](Self::add_at_most_k), unless a proof is being produced:
+    /// they are then encoded with the commander or (above 64 literals) the product
+    /// encoding, see [`encodings::at_most_one`].
+    ///
+    /// A variable can occur several times in `lits`, as in [`add_at_most_k`](Self::add_at_most_k).
+    pub fn add_at_most_one(&mut self, lits: &[Lit]) -> bool {
+        let (merged, pairs) = merge_card_lits(lits);
+        if merged.len() < lits.len() {
+            // after a pair `x, !x`, or with a literal that occurs twice, the literals are false
+            let mut ok = pairs < 2 || self.add_clause_reuse(&mut vec![]);
+            let mut rest = vec![];
+            for (lit, n) in merged {
+                if pairs > 0 || n > 1 {
+                    ok &= self.add_clause_reuse(&mut vec![!lit]);
+                } else {
+                    rest.push(lit);
+                }
+            }
+            return ok && self.add_at_most_one(&rest);
+        }
+        let encoding = if lits.len() <= 6 {
+            AmoEncoding::Pairwise
+        } else if self.v.proof.out.is_none() {
+            return self.add_at_most_k(lits, 1);
+        } else if lits.len() <= 64 {
+            AmoEncoding::Commander
+        } else {
+            AmoEncoding::Product
+        };
+        encodings::at_most_one(self, lits, encoding)
     }
 
-    /// Add clause during search
-    fn add_clause_during_search<Th: Theory>(&mut self, th: &mut Th, clause: &mut Vec<Lit>) -> bool {
-        debug!("add internal clause {:?}", clause);
+    /// Add the pseudo-Boolean constraint `sum(coef * lit for (coef, lit) in terms) >= bound`,
+    /// where a literal counts as `1` if it is true and `0` otherwise. Returns `false`
+    /// if the solver is in an `UNSAT` state.
+    ///
+    /// Coefficients can be negative and literals can share variables. The constraint is
+    /// normalized (and simplified with the level 0 assignment), without overflow for
+    /// any `i64` coefficients and bound; it is added as a cardinality constraint if all
+    /// its coefficients are equal, and otherwise propagated natively by watching
+    /// a subset of its literals whose coefficients add up to enough.
+    ///
+    /// Conflict analysis remains clausal, without cutting planes: propagations are
+    /// explained by clauses obtained by weakening the constraint.
+    /// As with [`add_xor_clause`](Self::add_xor_clause), they are not justified in the proofs.
+    pub fn add_pb_constraint(&mut self, terms: &[(i64, Lit)], bound: i64) -> bool {
+        self.release_trail();
+        debug_assert_eq!(self.v.decision_level(), 0);
         if !self.v.ok {
             return false;
         }
-        if clause.len() == 1 {
-            self.cancel_until(th, 0); // only at level 0
+        self.restore_vars(terms.iter().map(|t| t.1.var()));
+        // normalize `coef * lit` into `coef * var`
+        let mut bound = bound as i128;
+        let mut coefs: Vec<(Var, i128)> = terms
+            .iter()
+            .map(|&(coef, lit)| {
+                let coef = coef as i128;
+                if lit.sign() {
+                    (lit.var(), coef)
+                } else {
+                    bound -= coef; // `coef * !var = coef - coef * var`
+                    (lit.var(), -coef)
+                }
+            })
+            .collect();
+        coefs.sort_unstable_by_key(|t| t.0);
+        let mut c: Vec<(u128, Lit)> = vec![];
+        for (i, &(var, _)) in coefs.iter().enumerate() {
+            if i > 0 && coefs[i - 1].0 == var {
+                continue;
+            }
+            let coef: i128 = coefs[i..]
+                .iter()
+                .take_while(|t| t.0 == var)
+                .map(|t| t.1)
+                .sum();
+            let val = self.v.value(var);
+            if val != lbool::UNDEF {
+                bound -= coef * (val == lbool::TRUE) as i128;
+            } else if coef > 0 {
+                c.push((coef as u128, Lit::new(var, true)));
+            } else if coef < 0 {
+                bound -= coef;
+                c.push((-coef as u128, Lit::new(var, false)));
+            }
+        }
+        if bound <= 0 {
+            return true;
+        }
+        // a coefficient larger than the bound can be reduced to it; the sums of
+        // `i64` coefficients fit in `i128`
+        let bound = bound as u128;
+        for t in &mut c {
+            t.0 = t.0.min(bound);
+        }
+        let sum: u128 = c.iter().map(|t| t.0).sum();
+        if sum < bound {
+            self.add_clause_reuse(&mut vec![])
+        } else if c.iter().all(|t| t.0 == c[0].0) {
+            // at least `ceil(bound / coef)` of the literals are true
+            let n = c.len() as u128;
+            let k = n - bound.div_ceil(c[0].0);
+            let lits: Vec<Lit> = c.iter().map(|t| !t.1).collect();
+            self.add_at_most_k(&lits, k as u32)
+        } else {
+            self.v.natives.pb.add(&c, bound);
+            true
         }
-
-        self.v.sort_clause_lits(clause);
-        self.add_clause_(clause)
     }
-}
-
-/// Theory-triggered conflict.
-enum TheoryConflict {
-    Nil,
-    Clause { costly: bool },
-    Prop(Lit),
-}
-
-/// The temporary theory argument, passed to the theory.
-///
-/// This is where the theory can perform actions such as adding clauses.
-/// When the theory is called (on a partial model that is satisfiable on
-/// the boolean level), it can use this object to trigger conflicts,
-/// add lemmas, and propagate literals.
-pub struct TheoryArg<'a> {
-    v: &'a mut SolverV,
-    lits: &'a mut Vec<Lit>,
-    has_propagated: bool,
-    conflict: TheoryConflict,
-}
-
-/// Temporary representation of a learnt clause, produced in `analyze`.
-struct LearntClause<'a> {
-    clause: &'a [Lit],  // the clause
-    backtrack_lvl: i32, // where to backtrack?
-}
-
-#[derive(Clone, Copy, Debug)]
-enum Conflict<'a> {
-    BCP(CRef), // boolean propagation conflict
-    ThLemma { lits: &'a [Lit], add: bool },
-    ThProp(Lit), // literal was propagated, but is false
-}
 
-#[derive(Clone, Copy, Debug)]
-enum ResolveWith<'a> {
-    Init(Conflict<'a>), // initial conflict
-    Resolve(Lit, CRef), // propagation of lit because of clause
-}
-
-impl SolverV {
-    #[inline(always)]
-    pub fn num_assigns(&self) -> u32 {
-        self.vars.num_assigns()
+    /// Create a new, active, group of clauses.
+    ///
+    /// Clauses are added to it with [`add_clause_in_group`](Self::add_clause_in_group)
+    /// and are only taken into account while the group is active, that is until
+    /// [`retract_clause_group`](Self::retract_clause_group) is called on it.
+    pub fn new_clause_group(&mut self) -> ClauseGroup {
+        let act = Lit::new(self.new_internal_var(), true);
+        self.group_index
+            .insert(act.var(), self.groups.len() as u32, u32::MAX);
+        self.groups.push(act);
+        ClauseGroup(act)
+    }
+
+    /// Index of the active group of activation literal `act` in `groups`.
+    fn group_index(&self, act: Lit) -> Option<usize> {
+        let i = *self.group_index.get(act.var())? as usize;
+        (self.groups.get(i) == Some(&act)).then_some(i)
+    }
+
+    /// Set the activation literals of the active groups, and index them.
+    fn set_groups(&mut self, groups: Vec<Lit>) {
+        self.group_index.clear();
+        for (i, &act) in groups.iter().enumerate() {
+            self.group_index.insert(act.var(), i as u32, u32::MAX);
+        }
+        self.groups = groups;
     }
 
-    #[inline(always)]
-    fn num_vars(&self) -> u32 {
-        self.next_var.idx()
+    /// Add a clause to the given group. Returns `false` if the solver is in
+    /// an `UNSAT` state.
+    ///
+    /// Adding a clause to a retracted group has no effect.
+    pub fn add_clause_in_group(&mut self, group: ClauseGroup, lits: &[Lit]) -> bool {
+        if self.group_index(group.0).is_some() {
+            self.group_clauses.push(group.0);
+            self.group_clauses.extend_from_slice(lits);
+            self.group_clauses.push(Lit::UNDEF);
+        }
+        let mut c = mem::take(&mut self.tmp_c_add_cl);
+        c.clear();
+        c.extend_from_slice(lits);
+        c.push(!group.0);
+        let res = self.add_clause_reuse(&mut c);
+        self.tmp_c_add_cl = c;
+        res
     }
-    fn num_clauses(&self) -> u64 {
-        self.num_clauses
+
+    /// Is `group` still active?
+    pub fn is_clause_group_active(&self, group: ClauseGroup) -> bool {
+        self.group_index(group.0).is_some()
     }
-    fn num_conflicts(&self) -> u64 {
-        self.conflicts
+
+    /// Retract all the clauses of `group`, they are removed from the clause database
+    /// at the next simplification. The variable of its activation literal is released,
+    /// see [`release_var`](Self::release_var).
+    ///
+    /// Returns `false` if the solver is in an `UNSAT` state.
+    pub fn retract_clause_group(&mut self, group: ClauseGroup) -> bool {
+        self.release_trail();
+        if let Some(i) = self.group_index(group.0) {
+            self.groups.swap_remove(i);
+            self.group_index[group.0.var()] = u32::MAX;
+            if let Some(&moved) = self.groups.get(i) {
+                self.group_index[moved.var()] = i as u32;
+            }
+            self.retain_group_clauses(|c| c[0] != group.0);
+            // the group's clauses are now satisfied at level 0
+            self.release_var(!group.0);
+        }
+        self.is_ok()
     }
-    fn num_props(&self) -> u64 {
-        self.propagations
+
+    /// Keep the clauses of [`group_clauses`](Self::group_clauses) (given as the
+    /// activation literal followed by the literals) that satisfy `keep`.
+    fn retain_group_clauses(&mut self, mut keep: impl FnMut(&[Lit]) -> bool) {
+        let mut kept = Vec::with_capacity(self.group_clauses.len());
+        for c in self.group_clauses.split(|&lit| lit == Lit::UNDEF) {
+            if !c.is_empty() && keep(c) {
+                kept.extend_from_slice(c);
+                kept.push(Lit::UNDEF);
+            }
+        }
+        self.group_clauses = kept;
     }
-    fn num_learnts(&self) -> u64 {
-        self.num_learnts
+
+    /// Clauses added to `group`, as they were given to
+    /// [`add_clause_in_group`](Self::add_clause_in_group). A retracted group has none.
+    pub fn group_clauses(&self, group: ClauseGroup) -> Vec<Vec<Lit>> {
+        self.group_clauses
+            .split(|&lit| lit == Lit::UNDEF)
+            .filter(|c| c.first() == Some(&group.0))
+            .map(|c| c[1..].to_vec())
+            .collect()
     }
 
-    #[inline(always)]
-    pub fn level(&self, x: Var) -> i32 {
-        self.vars.level(x)
+    /// A Craig interpolant of the clauses of the groups `a` and `b`, if they have no
+    /// model together: a formula over the variables that occur in both groups, which
+    /// the clauses of `a` imply, and which contradicts the clauses of `b`.
+    ///
+    /// The clauses in no group, or in other groups, are not taken into account. The
+    /// interpolant is computed with McMillan's system from the resolution proof of a
+    /// separate solver, given by its LRAT hints, and is the only output of the graph.
+    /// Returns `None` if the clauses of `a` and `b` have a model.
+    pub fn interpolant(&self, a: ClauseGroup, b: ClauseGroup) -> Option<Aig> {
+        interpolate::interpolant(&self.group_clauses(a), &self.group_clauses(b))
     }
 
-    #[inline(always)]
-    pub fn level_lit(&self, x: Lit) -> i32 {
-        self.level(x.var())
+    /// Add a clause that can later be removed with [`remove_clause`](Self::remove_clause).
+    ///
+    /// The clause gets its own clause group, so that the clauses learnt from it and
+    /// the literals it implies are forgotten along with it. Each removable clause
+    /// costs a variable and an assumption while it is present.
+    /// Returns `None` if the solver is in an `UNSAT` state.
+    pub fn add_removable_clause(&mut self, lits: &[Lit]) -> Option<ClauseHandle> {
+        let group = self.new_clause_group();
+        if self.add_clause_in_group(group, lits) {
+            Some(ClauseHandle(group))
+        } else {
+            None
+        }
     }
 
-    #[inline(always)]
-    pub fn value(&self, x: Var) -> lbool {
-        self.vars.value(x)
+    /// Is the clause of `handle` still present?
+    pub fn has_clause(&self, handle: ClauseHandle) -> bool {
+        self.is_clause_group_active(handle.0)
     }
 
-    #[inline(always)]
-    pub fn value_lit(&self, x: Lit) -> lbool {
-        self.vars.value_lit(x)
+    /// Remove the clause of `handle`, along with the learnt clauses that depend on it.
+    ///
+    /// The literals it implied are not kept at level 0, as they were only propagated
+    /// under the clause's activation literal. This scans the clause database.
+    /// Returns `false` if the solver is in an `UNSAT` state.
+    pub fn remove_clause(&mut self, handle: ClauseHandle) -> bool {
+        self.release_trail();
+        if !self.retract_clause_group(handle.0) {
+            return false;
+        }
+        // the clauses containing the guard are now satisfied at level 0,
+        // so they can be deleted at once
+        let guard = !handle.0.activation_lit();
+        self.remove_clauses_with(ClauseSetSelect::Original, guard);
+        self.remove_clauses_with(ClauseSetSelect::Learnt, guard);
+        self.check_garbage();
+        true
     }
 
-    fn order_heap(&mut self) -> Heap<Var, VarOrder> {
-        self.vars.order_heap()
+    /// Iterate over all the models of the clauses, each given as the list of its true
+    /// literals.
+    ///
+    /// The enumeration is done by calling the solver again after adding a clause that
+    /// blocks the last model, which is removed once the iterator is dropped.
+    /// Variables that are not decision variables may be left unassigned, in which
+    /// case they do not appear in the models. Neither do the variables that the solver
+    /// created for its own use: the activation literals of the clause groups, and the
+    /// variables added by [`eliminate`](Self::eliminate) or by model counting.
+    pub fn iter_models(&mut self) -> Models<'_, Cb> {
+        let vars = (0..self.num_vars())
+            .map(Var::from_idx)
+            .filter(|&v| !self.v.internal[v])
+            .collect();
+        Models::new(self, vars, vec![])
+    }
+
+    /// Create a variable for the solver's own use. It is not a decision variable,
+    /// and it is left out of the models of [`iter_models`](Self::iter_models).
+    fn new_internal_var(&mut self) -> Var {
+        let v = self.v.new_var(lbool::UNDEF, false);
+        self.v.internal[v] = true;
+        v
     }
 
-    fn set_decision_var(&mut self, v: Var, b: bool) {
-        if b && !self.decision[v] {
-            self.dec_vars += 1;
-        } else if !b && self.decision[v] {
-            self.dec_vars -= 1;
-        }
-        self.decision[v] = b;
-        self.insert_var_order(v);
+    /// Iterate over the models of the clauses projected on `vars`: each assignment of
+    /// `vars` that can be extended into a model is returned once.
+    pub fn iter_projected_models(&mut self, vars: &[Var]) -> Models<'_, Cb> {
+        Models::new(self, vars.to_vec(), vec![])
     }
 
-    fn insert_var_order(&mut self, x: Var) {
-        if !self.order_heap().in_heap(x) && self.decision[x] {
-            self.order_heap().insert(x);
-        }
+    /// Number of assignments of `projection` that can be extended into a model,
+    /// or `None` if the search was interrupted by the callbacks.
+    ///
+    /// The models are enumerated with [`iter_projected_models`](Self::iter_projected_models),
+    /// so this is only practical for small counts, see
+    /// [`approx_count_models`](Self::approx_count_models) otherwise. The formula is not
+    /// decomposed into independent components, and no counts are cached.
+    pub fn count_models(&mut self, projection: &[Var]) -> Option<u64> {
+        self.count_models_upto(projection, vec![], u64::MAX)
     }
 
-    fn cla_decay_activity(&mut self) {
-        self.cla_inc *= 1.0 / self.opts.clause_decay;
+    /// Count the models projected on `projection` under `assumps`, stopping at `limit`.
+    fn count_models_upto(
+        &mut self,
+        projection: &[Var],
+        assumps: Vec<Lit>,
+        limit: u64,
+    ) -> Option<u64> {
+        let mut models = Models::new(self, projection.to_vec(), assumps);
+        let n = models.by_ref().take(limit as usize).count() as u64;
+        (n == limit || models.is_exhausted()).then_some(n)
     }
 
-    fn cla_bump_activity(&mut self, learnts: &[CRef], cr: CRef) {
-        let new_activity = {
-            let mut c = self.ca.get_mut(cr);
-            let r = c.activity() + self.cla_inc as f32;
-            c.set_activity(r);
-            r
-        };
-        if new_activity > 1e20 {
-            // Rescale:
-            for &learnt in learnts.iter() {
-                let mut c = self.ca.get_mut(learnt);
-                let r = c.activity() * 1e-20;
-                c.set_activity(r);
+    /// Estimate the number of assignments of `projection` that can be extended into a model,
+    /// or return `None` if the search was interrupted by the callbacks.
+    ///
+    /// This uses the ApproxMC algorithm: random XOR constraints over `projection` split its
+    /// assignments into cells, and the models of one cell are counted. The estimate is within
+    /// a factor `1 + epsilon` of the exact count with probability at least `1 - delta`.
+    /// The random XOR constraints are native ones, which are only enabled by assumptions
+    /// and are removed before returning; their selector variables are reused by the next
+    /// calls. The random seed is [`SolverOpts::seed`].
+    pub fn approx_count_models(
+        &mut self,
+        projection: &[Var],
+        epsilon: f64,
+        delta: f64,
+    ) -> Option<f64> {
+        assert!(epsilon > 0.0 && 0.0 < delta && delta < 1.0);
+        let inv_eps = 1.0 + 1.0 / epsilon;
+        let thresh = (1.0 + 9.84 * (1.0 + epsilon / (1.0 + epsilon)) * inv_eps * inv_eps) as u64;
+        let n = self.count_models_upto(projection, vec![], thresh)?;
+        if n < thresh {
+            return Some(n as f64);
+        }
+        // `17 * log2(3 / delta)` rounds, rounded up
+        let mut rounds = 0;
+        let mut x = 1.0;
+        while x < 3.0 / delta {
+            x *= 2.0;
+            rounds += 17;
+        }
+        let mut estimates = vec![];
+        for _ in 0..rounds {
+            // add random XOR constraints until the cell is small enough
+            let mut assumps = vec![];
+            let mut estimate = None;
+            let mut interrupted = false;
+            let mut scale = 1.0;
+            for i in 0..projection.len() {
+                assumps.push(self.add_random_xor(projection, i));
+                scale *= 2.0;
+                match self.count_models_upto(projection, assumps.clone(), thresh) {
+                    Some(n) if n < thresh => {
+                        estimate = Some(n as f64 * scale);
+                        break;
+                    }
+                    Some(_) => (),
+                    None => {
+                        interrupted = true;
+                        break;
+                    }
+                }
             }
-            self.cla_inc *= 1e-20;
+            self.release_xor_selectors(&assumps);
+            if interrupted {
+                return None;
+            }
+            // the cells can remain too large (with a small probability)
+            estimates.extend(estimate);
         }
+        if estimates.is_empty() {
+            return Some(0.0);
+        }
+        estimates.sort_unstable_by(|a, b| a.partial_cmp(b).unwrap());
+        Some(estimates[estimates.len() / 2])
     }
 
-    /// Pick a literal to make a decision with
-    fn pick_branch_lit(&mut self) -> Lit {
-        let mut next = Var::UNDEF;
-
-        // Random decision:
-        if utils::drand(&mut self.opts.random_seed) < self.opts.random_var_freq
-            && !self.order_heap().is_empty()
-        {
-            let idx_tmp = utils::irand(
-                &mut self.opts.random_seed,
-                self.vars.order_heap_data.len() as i32,
-            ) as usize;
-            next = self.vars.order_heap_data[idx_tmp].var();
-            if self.value(next) == lbool::UNDEF && self.decision[next] {
-                self.rnd_decisions += 1;
+    /// Add a XOR constraint over a random subset of `vars`, with a random parity, that is
+    /// enabled by the returned assumption on the `i`-th selector variable.
+    fn add_random_xor(&mut self, vars: &[Var], i: usize) -> Lit {
+        let rand = &mut self.v.rand;
+        let mut lits: Vec<Lit> = vars
+            .iter()
+            .filter(|_| rand.f64() < 0.5)
+            .map(|&v| Lit::new(v, true))
+            .collect();
+        let parity = rand.f64() < 0.5;
+        // the constraint is free as long as `p` is unassigned
+        let v = match self.xor_selectors.get(i) {
+            Some(&v) => v,
+            None => {
+                let v = self.new_internal_var();
+                self.xor_selectors.push(v);
+                v
             }
+        };
+        let p = Lit::new(v, true);
+        lits.push(p);
+        // even a short constraint is kept native, so that it can be removed
+        self.release_trail();
+        let (vars, rhs) = self.normalize_xor(&lits, parity);
+        let added = self.v.natives.xor.add(&vars, rhs);
+        debug_assert!(added, "the selector only occurs in this constraint");
+        !p
+    }
+
+    /// Remove the XOR constraints enabled by `assumps`, so that their selector
+    /// variables can be reused.
+    ///
+    /// The learnt clauses that mention a selector are consequences of its constraint,
+    /// so they are removed. A selector fixed at level 0 is not reused.
+    fn release_xor_selectors(&mut self, assumps: &[Lit]) {
+        self.release_trail();
+        self.cancel_until(&mut theory::EmptyTheory::new(), 0);
+        for a in assumps {
+            self.v.natives.xor.forget(a.var());
         }
+        let self_v = &mut self.v;
+        self.xor_selectors
+            .retain(|&v| self_v.value(v) == lbool::UNDEF);
+        let selectors = &self.xor_selectors;
+        let listener = &mut self.listener;
+        self.learnts.retain(|&cr| {
+            let lits = self_v.ca.get_ref(cr).lits();
+            let contains = lits.iter().any(|lit| selectors.contains(&lit.var()));
+            if contains {
+                self_v.remove_clause(cr);
+                self_v.proof_delete(cr);
+                if let Some(listener) = listener.as_mut() {
+                    listener.on_deleted(self_v.ca.get_ref(cr).lits());
+                }
+            }
+            !contains
+        });
+        self.check_garbage();
+    }
 
-        // Activity based decision:
-        while next == Var::UNDEF || self.value(next) != lbool::UNDEF || !self.decision[next] {
-            let mut order_heap = self.order_heap();
-            if order_heap.is_empty() {
-                next = Var::UNDEF;
-                break;
+    /// Shrink the unsat core of the last call to `solve_limited` to a minimal one,
+    /// see [`minimize_unsat_core_th`](Self::minimize_unsat_core_th).
+    pub fn minimize_unsat_core(&mut self) -> &[Lit] {
+        self.minimize_unsat_core_th(&mut theory::EmptyTheory::new())
+    }
+
+    /// Shrink the unsat core of the last call to `solve_limited_th` so that removing any
+    /// literal from it makes the remaining assumptions satisfiable, and return it.
+    ///
+    /// Each literal of the core is dropped in turn and the solver is called again on
+    /// the rest; the literal is kept unless this call returns `Unsat`, in which case
+    /// its (possibly smaller) core replaces the current one. Calls that exceed the
+    /// current budget keep the literal, so the result is only minimal if all of them
+    /// terminate.
+    ///
+    /// Activation literals of the active clause groups are always assumed, so they are
+    /// only removed from the core if a smaller core does not contain them.
+    ///
+    /// The model is not preserved, and the minimized core is also what
+    /// [`unsat_core`](SolverInterface::unsat_core) returns afterwards.
+    ///
+    /// Precondition: last result was `Unsat`
+    pub fn minimize_unsat_core_th<Th: Theory>(&mut self, th: &mut Th) -> &[Lit] {
+        let mut core = self.conflict.as_slice().to_vec();
+        let mut i = 0;
+        while i < core.len() {
+            if self.group_index(core[i]).is_some() {
+                i += 1;
+                continue;
+            }
+            let lit = core.remove(i);
+            if self.solve_limited_th(th, &core) == lbool::FALSE {
+                // keep the order of `core`, the new core is a subset of it
+                let new_core = &self.conflict;
+                core.retain(|&a| new_core.has(a));
             } else {
-                next = order_heap.remove_min();
+                core.insert(i, lit);
+                i += 1;
             }
         }
-
-        // Choose polarity based on different polarity modes (global or per-variable):
-        if next == Var::UNDEF {
-            Lit::UNDEF
-        } else if self.user_pol[next] != lbool::UNDEF {
-            Lit::new(next, self.user_pol[next] == lbool::TRUE)
-        } else if self.opts.rnd_pol {
-            Lit::new(next, utils::drand(&mut self.opts.random_seed) < 0.5)
-        } else {
-            Lit::new(next, self.polarity[next])
+        self.model.clear();
+        self.conflict.clear();
+        for &a in &core {
+            self.conflict.insert(a);
         }
+        self.conflict.as_slice()
     }
 
-    fn watches(&mut self) -> OccLists<Lit, Watcher, WatcherDeleted> {
-        self.watches_data.promote(WatcherDeleted { ca: &self.ca })
+    /// Find a minimal unsatisfiable subset of the clauses guarded by `selectors`,
+    /// see [`extract_mus_th`](Self::extract_mus_th).
+    pub fn extract_mus(&mut self, selectors: &[Lit]) -> Option<Vec<Lit>> {
+        self.extract_mus_th(&mut theory::EmptyTheory::new(), selectors)
     }
 
-    fn new_var(&mut self, upol: lbool, dvar: bool) -> Var {
-        let v = self.free_vars.pop().unwrap_or_else(|| {
-            let v = self.next_var;
-            self.next_var = Var::from_idx(self.next_var.idx() + 1);
-            v
-        });
-        self.watches().init(Lit::new(v, false));
-        self.watches().init(Lit::new(v, true));
-        self.vars.ass.insert_default(v, lbool::UNDEF);
-        self.vars
-            .vardata
-            .insert_default(v, VarData::new(CRef::UNDEF, 0));
-        if self.opts.rnd_init_act {
-            self.vars.activity.insert_default(
-                v,
-                (utils::drand(&mut self.opts.random_seed) * 0.00001) as f32,
-            );
+    /// Find a minimal unsatisfiable subset (MUS) of the clauses guarded by `selectors`.
+    ///
+    /// A selector `s` guards the clauses that contain `!s`, which are only enabled when `s`
+    /// is assumed (it can be the activation literal of a clause group). Returns `None` if
+    /// the clauses are satisfiable when all the selectors are assumed (or the search is
+    /// interrupted), and otherwise a subset of `selectors` whose clauses, with the unguarded
+    /// ones, are unsatisfiable, and that is satisfiable if any of its selectors is removed.
+    ///
+    /// The subset is computed by [`minimize_unsat_core_th`](Self::minimize_unsat_core_th),
+    /// so it is only minimal if none of the calls to the solver exceeds the budget.
+    pub fn extract_mus_th<Th: Theory>(
+        &mut self,
+        th: &mut Th,
+        selectors: &[Lit],
+    ) -> Option<Vec<Lit>> {
+        // the groups among the selectors must not be assumed implicitly
+        let groups = self.groups.clone();
+        let mut implicit = groups.clone();
+        implicit.retain(|a| !selectors.contains(a));
+        self.set_groups(implicit);
+        let mus = if self.solve_limited_th(th, selectors) == lbool::FALSE {
+            Some(self.minimize_unsat_core_th(th).to_vec())
         } else {
-            self.vars.activity.insert_default(v, 0.0);
-        }
-        self.seen.insert_default(v, Seen::UNDEF);
-        self.polarity.insert_default(v, false);
-        self.user_pol.insert_default(v, upol);
-        self.decision.reserve_default(v);
-        let len = self.vars.trail.len();
-        if v.idx() as usize > len {
-            self.vars.trail.reserve(v.idx() as usize + 1 - len);
-        }
-        self.set_decision_var(v, dvar);
-        v
+            None
+        };
+        self.set_groups(groups);
+        mus
     }
 
-    /// Analyze conflict and produce a reason clause.
-    ///
-    /// # Pre-conditions:
+    /// Find a minimal set of active clause groups whose clauses, with the clauses that are
+    /// in no group, are unsatisfiable, see [`extract_mus_th`](Self::extract_mus_th).
+    pub fn extract_group_mus(&mut self) -> Option<Vec<ClauseGroup>> {
+        let selectors = self.groups.clone();
+        let mus = self.extract_mus(&selectors)?;
+        Some(mus.into_iter().map(ClauseGroup).collect())
+    }
+
+    /// Shrink the last model into a prime implicant of the clauses: a subset of its
+    /// literals that contains a literal of every clause, and from which no literal can
+    /// be removed. The literals of `relevant_vars` that belong to it are returned.
     ///
-    /// - current decision level must be greater than root level.
-    /// - `orig` is false in the current trail
+    /// The literals of the other variables are all kept, and can be used to satisfy
+    /// the clauses. So are the literals that are fixed at level 0 (the formula implies
+    /// them) and the literals of the variables that occur in native XOR, cardinality or
+    /// pseudo-Boolean constraints. Constraints that are only known to a theory are
+    /// not taken into account.
     ///
-    /// # Post-conditions:
+    /// Precondition: last result was `Sat`
+    pub fn minimize_model(&self, relevant_vars: &[Var]) -> Vec<Lit> {
+        let is_true = |lit: Lit| self.value_lit(lit) == lbool::TRUE;
+        let mut droppable: VMap<bool> = VMap::new();
+        droppable.reserve(Var::from_idx(self.num_vars()), false);
+        for &v in relevant_vars {
+            droppable[v] = self.value_var(v) != lbool::UNDEF
+                && self.value_lvl_0(Lit::new(v, true)) == lbool::UNDEF
+                && !self.v.natives.contains_var(v);
+        }
+        // the clauses that are not satisfied by a literal that is kept anyway
+        let clauses: Vec<&[Lit]> = self
+            .clauses
+            .iter()
+            .map(|&cr| self.v.ca.get_ref(cr).lits())
+            .chain(self.elim.iter().map(|(_, c)| c))
+            .filter(|c| !c.iter().any(|&l| is_true(l) && !droppable[l.var()]))
+            .collect();
+        // select the only true literal of a clause first, then any one of them
+        let mut selected: VMap<bool> = VMap::new();
+        selected.reserve(Var::from_idx(self.num_vars()), false);
+        for c in &clauses {
+            let mut true_lits = c.iter().filter(|&&l| is_true(l));
+            if let (Some(l), None) = (true_lits.next(), true_lits.next()) {
+                selected[l.var()] = true;
+            }
+        }
+        for c in &clauses {
+            if !c.iter().any(|&l| is_true(l) && selected[l.var()]) {
+                if let Some(l) = c.iter().find(|&&l| is_true(l)) {
+                    selected[l.var()] = true;
+                }
+            }
+        }
+        // then remove the selected literals that are not needed
+        let mut count = vec![0u32; clauses.len()];
+        let mut occs: VMap<Vec<u32>> = VMap::new();
+        for (i, c) in clauses.iter().enumerate() {
+            for &l in c.iter().filter(|&&l| is_true(l) && selected[l.var()]) {
+                count[i] += 1;
+                occs.get_or_insert_with(l.var(), Vec::new).push(i as u32);
+            }
+        }
+        for &v in relevant_vars {
+            if selected[v] && occs[v].iter().all(|&i| count[i as usize] >= 2) {
+                selected[v] = false;
+                for &i in &occs[v] {
+                    count[i as usize] -= 1;
+                }
+            }
+        }
+        relevant_vars
+            .iter()
+            .filter(|&&v| self.value_var(v) != lbool::UNDEF && (!droppable[v] || selected[v]))
+            .map(|&v| Lit::new(v, self.value_var(v) == lbool::TRUE))
+            .collect()
+    }
+
+    /// Simplify the problem by subsumption, self-subsuming resolution and bounded variable
+    /// elimination, as in MiniSat's `SimpSolver`. Returns `false` if the solver is in
+    /// an `UNSAT` state.
     ///
-    /// - `btlevel` is returned.
-    /// - `out_learnt[0]` is the asserting literal at level `btlevel`.
-    /// - if `out_learnt.size() > 1` then `out_learnt[1]` has the greatest decision level of the
-    ///   rest of literals. There may be others from the same level though.
-    fn analyze<'a, Th: Theory>(
-        &mut self,
-        orig: Conflict<'_>,
-        learnts: &[CRef],
-        out_learnt: &'a mut Vec<Lit>,
-        th: &mut Th,
-    ) -> LearntClause<'a> {
-        out_learnt.clear();
+    /// A variable is eliminated by replacing the clauses in which it occurs by their
+    /// resolvents on it, unless this adds more than [`elim_grow`](SolverOpts::elim_grow)
+    /// clauses or a resolvent longer than [`elim_clause_lim`](SolverOpts::elim_clause_lim).
+    /// If some of these clauses define the variable as an AND, ITE or XOR gate of other
+    /// variables, only their resolvents with the other clauses are needed, which makes the
+    /// elimination of gate outputs much cheaper.
+    /// The removed clauses are kept to extend the models to the eliminated variables.
+    ///
+    /// If [`blocked_clause_elim`](SolverOpts::blocked_clause_elim) is set, the clauses
+    /// that are blocked on a literal `l` are removed too: their resolvents on `l` with the
+    /// clauses containing `!l` are tautologies. `l` is made true in the models that do
+    /// not satisfy them.
+    ///
+    /// If [`bva`](SolverOpts::bva) is set, bounded variable addition then introduces new
+    /// variables to factor out the literals that occur together with the same clauses:
+    /// the clauses `l_i | C_j`, for all `i` and `j`, are replaced by `!x | l_i` and `x | C_j`.
+    ///
+    /// Variables that are [frozen](Self::set_frozen), that are not decision variables or
+    /// that occur in native XOR, cardinality or pseudo-Boolean constraints are not
+    /// eliminated, and no clause is blocked on them. An eliminated variable is restored,
+    /// with the clauses it was removed from, when a new clause, constraint or assumption
+    /// contains it; so are the clauses blocked on a variable. The solver does not know
+    /// which variables a theory uses, so these must be frozen.
+    ///
+    /// Nothing is eliminated while an LRAT proof is produced.
+    pub fn eliminate(&mut self) -> bool {
+        self.release_trail();
+        if !self.simplify() {
+            return false;
+        } else if self.v.proof.lrat {
+            return true;
+        }
+        let limits = SimpLimits {
+            clause_lim: self.v.opts.elim_clause_lim,
+            grow: self.v.opts.elim_grow,
+            subsumption_lim: self.v.opts.subsumption_lim,
+        };
+        let mut simp = Simplifier::new(self.num_vars(), limits, self.v.proof.out.is_some());
+        for v in (0..self.num_vars()).map(Var::from_idx) {
+            if !self.v.frozen[v]
+                && !self.v.eliminated[v]
+                && self.v.decision[v]
+                && self.v.value(v) == lbool::UNDEF
+                && !self.v.natives.contains_var(v)
+            {
+                simp.set_eliminable(v);
+            }
+        }
+        // move the clauses to the simplifier, without their false literals
+        let mut lits = vec![];
+        for cr in mem::take(&mut self.clauses) {
+            let c = self.v.ca.get_ref(cr);
+            let satisfied = self.v.satisfied(c);
+            if !satisfied {
+                lits.clear();
+                lits.extend(c.iter().filter(|&&l| self.v.value_lit(l) == lbool::UNDEF));
+                if lits.len() != c.size() as usize {
+                    self.v.proof.add(0, &lits);
+                }
+                simp.add_clause(&lits);
+            }
+            self.v.remove_clause(cr);
+            if satisfied || lits.len() != self.v.ca.get_ref(cr).size() as usize {
+                self.v.proof_delete(cr);
+            }
+        }
+        simp.run();
+        if self.v.opts.blocked_clause_elim && simp.ok {
+            simp.eliminate_blocked();
+            simp.run();
+        }
+        if self.v.opts.bva && simp.ok {
+            simp.bva();
+            simp.run();
+        }
+        for (add, c) in simp.proof.take().unwrap_or_default() {
+            if add {
+                self.v.proof.add(0, &c);
+            } else {
+                self.v.proof.delete(0, &c);
+            }
+        }
+        if !simp.ok {
+            self.v.ok = false;
+            return false;
+        }
+        // variables added by bounded variable addition
+        while self.num_vars() < simp.num_vars() {
+            let v = self.new_var_default();
+            self.v.internal[v] = true;
+        }
+        for &l in &simp.units {
+            self.v.vars.unchecked_enqueue(l, CRef::UNDEF);
+        }
+        for c in simp.clauses() {
+            let cr = self.v.ca.alloc_with_learnt(c, false);
+            self.clauses.push(cr);
+            self.v.attach_clause(cr);
+        }
+        for &v in &simp.eliminated {
+            self.v.eliminated[v] = true;
+            self.v.set_decision_var(v, false);
+        }
+        for (x, _) in simp.elim.iter() {
+            if !self.v.eliminated[x.var()] {
+                self.v.blocking[x.var()] = true;
+            }
+        }
+        self.elim.append(&mut simp.elim);
+        // the learnt clauses are implied by the remaining ones, unless they contain
+        // eliminated variables
+        let self_v = &mut self.v;
+        let listener = &mut self.listener;
+        self.learnts.retain(|&cr| {
+            let c = self_v.ca.get_ref(cr);
+            let keep = !c.iter().any(|&l| self_v.eliminated[l.var()]);
+            if !keep {
+                self_v.remove_clause(cr);
+                self_v.proof_delete(cr);
+                if let Some(listener) = listener {
+                    listener.on_deleted(self_v.ca.get_ref(cr).lits());
+                }
+            }
+            keep
+        });
+        if let Some(confl) = self.v.propagate() {
+            self.v.proof_unsat(Some(confl));
+            self.v.ok = false;
+            return false;
+        }
+        self.check_garbage();
+        true
+    }
 
-        debug!("analyze.start {:?}", orig);
+    /// Forbid (if `b`) or allow the elimination of `v` by [`eliminate`](Self::eliminate),
+    /// and its substitution by an equivalent literal
+    /// (see [`substitute_equivalences`](SolverOpts::substitute_equivalences)).
+    ///
+    /// Freezing a variable that was already eliminated restores it.
+    pub fn set_frozen(&mut self, v: Var, b: bool) {
+        if b {
+            self.restore_vars([v]);
+        }
+        self.v.frozen[v] = b;
+    }
 
-        // at what level did the conflict happen?
-        let conflict_level = match orig {
-            Conflict::BCP(_) | Conflict::ThProp(_) => {
-                self.decision_level() as i32 // current level
+    /// Was `v` removed by [`eliminate`](Self::eliminate), or substituted by an equivalent
+    /// literal?
+    pub fn is_eliminated(&self, v: Var) -> bool {
+        self.v.eliminated[v]
+    }
+
+    /// Set the saved phase of `v` to `b`, so that the next decision on `v` assigns it
+    /// to `b`, to warm start the search from a known assignment.
+    ///
+    /// The phase is saved again when `v` is unassigned, and it is ignored if `v` was
+    /// given a polarity when it was created (see [`new_var`](SolverInterface::new_var)).
+    pub fn set_polarity(&mut self, v: Var, b: bool) {
+        self.v.polarity[v] = b;
+        self.v.target[v] = lbool::UNDEF;
+    }
+
+    /// Seed the saved phases from the assignment `lits`, for instance the model of a
+    /// similar formula, so that the search starts from it: if it satisfies the clauses,
+    /// the search finds it without any conflict.
+    ///
+    /// The phases are also the best ones found so far, which the solver goes back to
+    /// when it resets its phases (see [`rephase_int`](SolverOpts::rephase_int)).
+    pub fn set_initial_phases(&mut self, lits: &[Lit]) {
+        let v = &mut self.v;
+        for &lit in lits {
+            let phase = lbool::new(lit.sign());
+            v.polarity[lit.var()] = lit.sign();
+            v.target[lit.var()] = phase;
+            v.best[lit.var()] = phase;
+        }
+        // the next conflict-free trails replace the seeds
+        v.target_len = 0;
+        v.best_len = 0;
+    }
+
+    /// Polarity of the next decision on `v`, unless it is a random one.
+    pub fn polarity(&self, v: Var) -> bool {
+        let v_ = &self.v;
+        if v_.user_pol[v] != lbool::UNDEF {
+            v_.user_pol[v] == lbool::TRUE
+        } else if v_.target_phase() && v_.target[v] != lbool::UNDEF {
+            v_.target[v] == lbool::TRUE
+        } else {
+            v_.polarity[v]
+        }
+    }
+
+    /// Increase the activity of `v` by `delta` times the bump of the variables of a
+    /// conflict, for the branching heuristic to decide `v` sooner (or later if `delta` is
+    /// negative). With [`BranchingHeuristic::Vmtf`], `v` is just moved to the front of
+    /// the queue if `delta` is positive.
+    pub fn bump_activity(&mut self, v: Var, delta: f64) {
+        self.v.vars.branch.boost(v, delta)
+    }
+
+    /// Set the priority of `v` as an assumption (0 by default): the assumptions are
+    /// decided by decreasing priority, and those of equal priority in the order of
+    /// [`SolverOpts::assumption_order`].
+    ///
+    /// The assumptions decided first are the most likely to be in the unsat cores.
+    pub fn set_assumption_priority(&mut self, v: Var, priority: i32) {
+        self.v.assump_priority[v] = priority;
+    }
+
+    /// Priority of `v` as an assumption, see
+    /// [`set_assumption_priority`](Self::set_assumption_priority).
+    pub fn assumption_priority(&self, v: Var) -> i32 {
+        self.v.assump_priority[v]
+    }
+
+    /// Activity of `v`: the branching heuristic decides the variables of higher
+    /// activity first.
+    pub fn activity(&self, v: Var) -> f64 {
+        self.v.vars.branch.score(v)
+    }
+
+    /// Can `v` be decided? (see [`set_decision_var`](SolverInterface::set_decision_var))
+    pub fn is_decision_var(&self, v: Var) -> bool {
+        self.v.decision[v]
+    }
+
+    /// The true literals, in the order they were assigned.
+    ///
+    /// Between the calls to the solver, these are the literals true at level 0, followed
+    /// by the levels of assumptions kept with [`SolverOpts::reuse_trail`]. See
+    /// [`propagate_under`](Self::propagate_under) to inspect the consequences of
+    /// assumptions.
+    pub fn trail(&self) -> &[Lit] {
+        &self.v.vars.trail
+    }
+
+    /// Start in [`trail`](Self::trail) of each decision level above 0.
+    pub fn trail_limits(&self) -> impl ExactSizeIterator<Item = usize> + '_ {
+        self.v.vars.trail_lim.iter().map(|&i| i as usize)
+    }
+
+    /// Decision level at which `v` was assigned, or `None` if it is unassigned.
+    pub fn level_of(&self, v: Var) -> Option<u32> {
+        (self.v.value(v) != lbool::UNDEF).then(|| self.v.level(v) as u32)
+    }
+
+    /// Reason of the assignment of `v`: a clause (or the explanation of a native
+    /// constraint) whose first literal is the true literal of `v` and whose other ones
+    /// are false.
+    ///
+    /// Returns `None` for unassigned variables, decisions, assumptions, theory
+    /// propagations and the literals without a reason at level 0.
+    pub fn reason_of(&self, v: Var) -> Option<&[Lit]> {
+        self.v.reason_lits(v)
+    }
+
+    /// Write the implication graph of the current assignment into `w`, see
+    /// [`graph`](crate::graph).
+    ///
+    /// `conflict`, a clause false in the assignment, is added as a conflict node.
+    #[cfg(feature = "std")]
+    pub fn dump_implication_graph<W: std::io::Write>(
+        &self,
+        w: &mut W,
+        format: crate::graph::GraphFormat,
+        conflict: Option<&[Lit]>,
+    ) -> std::io::Result<()> {
+        let graph = crate::graph::ImplicationGraph {
+            trail: self.trail(),
+            value: |lit| self.v.value_lit(lit),
+            level: |v| self.v.level(v) as u32,
+            reason: |v| self.v.reason_lits(v),
+            conflict,
+        };
+        graph.write(w, format)
+    }
+
+    /// Fix `lit` to true, and release its variable: it must not be used anymore,
+    /// and it is removed by the next call to [`compact`](Self::compact).
+    ///
+    /// Nothing is done if `lit` is false at level 0. The variables of XOR, cardinality
+    /// or pseudo-Boolean constraints are only fixed.
+    pub fn release_var(&mut self, lit: Lit) {
+        self.release_trail();
+        if self.v.value_lit(lit) == lbool::UNDEF {
+            self.add_clause_reuse(&mut vec![lit]);
+        }
+        if self.v.value_lit(lit) == lbool::TRUE && !self.v.natives.contains_var(lit.var()) {
+            self.v.released.push(lit.var());
+        }
+    }
+
+    /// Remove the variables released by [`release_var`](Self::release_var), and renumber
+    /// the other ones so that they are contiguous, to reclaim the memory used by each
+    /// variable. The clauses satisfied at level 0 are removed, and the false literals
+    /// of the others.
+    ///
+    /// Returns the map from the old variables to the new ones, where the removed variables
+    /// are mapped to `Var::UNDEF`. A theory must rename its variables with it.
+    /// The model and the unsat core of the last call to the solver are kept, renamed.
+    ///
+    /// Returns `None`, and renames nothing, if the solver is in an `UNSAT` state, if a
+    /// proof is logged (its variables must keep their numbers), or if there are XOR,
+    /// cardinality or pseudo-Boolean constraints.
+    pub fn compact(&mut self) -> Option<VMap<Var>> {
+        self.release_trail();
+        self.cancel_until(&mut theory::EmptyTheory::new(), 0);
+        if !self.v.ok || self.v.proof.out.is_some() || !self.v.natives.is_empty() {
+            return None;
+        }
+        if let Some(confl) = self.v.propagate() {
+            self.v.proof_unsat(Some(confl));
+            self.v.ok = false;
+            return None;
+        }
+        // no clause mentions a released variable anymore
+        self.remove_satisfied(ClauseSetSelect::Learnt);
+        self.remove_satisfied(ClauseSetSelect::Original);
+        self.garbage_collect();
+
+        let mut map: VMap<Var> = VMap::new();
+        for v in (0..self.v.num_vars()).map(Var::from_idx) {
+            map.insert(v, v, Var::UNDEF);
+        }
+        for &v in self.v.released.iter().chain(&self.v.free_vars) {
+            map[v] = Var::UNDEF;
+        }
+        let mut n = 0;
+        for (_, x) in map.iter_mut() {
+            if *x != Var::UNDEF {
+                *x = Var::from_idx(n);
+                n += 1;
             }
-            Conflict::ThLemma { lits, .. } => {
-                // check it's a proper conflict clause
-                debug_assert!(lits.iter().all(|&p| self.value_lit(p) == lbool::FALSE));
-                debug_assert!(lits.len() >= 1, "theory lemma should have at least 1 lit");
+        }
+        let rename = |lit: Lit| Lit::new(map[lit.var()], lit.sign());
 
-                let lvl = lits
-                    .iter()
-                    .map(|&lit| self.level_lit(lit))
-                    .max()
-                    .unwrap_or(0);
+        for &cr in self.clauses.iter().chain(&self.learnts) {
+            let mut c = self.v.ca.get_mut(cr);
+            for i in 0..c.size() {
+                debug_assert_ne!(map[c[i].var()], Var::UNDEF);
+                c[i] = rename(c[i]);
+            }
+        }
+        let self_v = &self.v;
+        self.elim.remap(&map, |lit| self_v.value_lit(lit));
+        let groups = (self.groups.iter())
+            .filter(|&&act| map[act.var()] != Var::UNDEF)
+            .map(|&act| rename(act))
+            .collect();
+        self.set_groups(groups);
+        self.retain_group_clauses(|c| c.iter().all(|lit| map[lit.var()] != Var::UNDEF));
+        for lit in &mut self.group_clauses {
+            if *lit != Lit::UNDEF {
+                *lit = rename(*lit);
+            }
+        }
+        let mut model = vec![lbool::UNDEF; n as usize];
+        for (i, &val) in self.model.iter().enumerate() {
+            let v = map[Var::from_idx(i as u32)];
+            if v != Var::UNDEF {
+                model[v.idx() as usize] = val;
+            }
+        }
+        self.model = model;
+        let mut conflict = LSet::new();
+        for &lit in self.conflict.iter() {
+            if map[lit.var()] != Var::UNDEF {
+                conflict.insert(rename(lit));
+            }
+        }
+        self.conflict = conflict;
+        let crefs = self.clauses.iter().chain(&self.learnts).copied();
+        self.v.remap_vars(&map, n, crefs);
+        self.v.vivify_next = [0; 2];
+        self.v.probe_next = 0;
+        Some(map)
+    }
+
+    /// Write the current irredundant clauses into `out` in DIMACS, after level 0
+    /// simplification: the level 0 assignments are written as unit clauses, the clauses
+    /// they satisfy are skipped and their false literals are removed. The `p cnf` header
+    /// declares the exact number of variables and clauses.
+    ///
+    /// The clauses of eliminated variables and the XOR, cardinality or pseudo-Boolean
+    /// constraints are not written, nor are the learnt clauses. The output of an `UNSAT`
+    /// solver is the empty clause.
+    ///
+    /// If `renumber` is true, the variables that occur in the output are renumbered
+    /// contiguously. Returns the map from the variables of the solver to the ones of the
+    /// output, where the variables that do not occur are mapped to `Var::UNDEF` (the
+    /// identity if `renumber` is false).
+    #[cfg(feature = "std")]
+    pub fn write_dimacs<W: std::io::Write>(
+        &mut self,
+        out: &mut W,
+        renumber: bool,
+    ) -> std::io::Result<VMap<Var>> {
+        use crate::clause::display::Print;
+        self.cancel_until(&mut theory::EmptyTheory::new(), 0);
+        if self.v.ok {
+            if let Some(confl) = self.v.propagate() {
+                self.v.proof_unsat(Some(confl));
+                self.v.ok = false;
+            }
+        }
+        // the literals of each clause, followed by `Lit::UNDEF`
+        let mut lits: Vec<Lit> = vec![];
+        if self.v.ok {
+            for &lit in &self.v.vars.trail {
+                lits.push(lit);
+                lits.push(Lit::UNDEF);
+            }
+            for &cr in &self.clauses {
+                let c = self.v.ca.get_ref(cr);
+                if self.v.satisfied(c) {
+                    continue;
+                }
+                let not_false = c.iter().filter(|&&l| self.v.value_lit(l) != lbool::FALSE);
+                lits.extend(not_false);
+                lits.push(Lit::UNDEF);
+            }
+        } else {
+            lits.push(Lit::UNDEF);
+        }
 
-                if lits.len() == 1 {
-                    // unit clause: learn the clause itself at level 0
-                    trace!("analyze: learn unit clause {:?} itself", lits);
-                    out_learnt.extend_from_slice(lits);
-                    return LearntClause {
-                        clause: &*out_learnt,
-                        backtrack_lvl: 0,
-                    };
-                } else if lvl == 0 {
-                    // all at level 0: empty clause
-                    trace!("analyze: conflict level 0, learn empty clause");
-                    return LearntClause {
-                        clause: &[],
-                        backtrack_lvl: 0,
-                    };
+        let mut map: VMap<Var> = VMap::new();
+        let mut n = self.v.num_vars();
+        for v in (0..n).map(Var::from_idx) {
+            map.insert(v, if renumber { Var::UNDEF } else { v }, Var::UNDEF);
+        }
+        if renumber {
+            n = 0;
+            for &lit in &lits {
+                if lit != Lit::UNDEF && map[lit.var()] == Var::UNDEF {
+                    map[lit.var()] = Var::from_idx(n);
+                    n += 1;
                 }
+            }
+        }
+        let num_clauses = lits.iter().filter(|&&l| l == Lit::UNDEF).count();
+        writeln!(out, "p cnf {} {}", n, num_clauses)?;
+        let mut clause = vec![];
+        for &lit in &lits {
+            if lit == Lit::UNDEF {
+                writeln!(out, "{}", clause.pp_dimacs())?;
+                clause.clear();
+            } else {
+                clause.push(Lit::new(map[lit.var()], lit.sign()));
+            }
+        }
+        Ok(map)
+    }
 
-                lvl
+    /// Save the current clauses, level 0 assignments and heuristics, so that the solver
+    /// can go back to this state with [`restore`](Self::restore).
+    pub fn save_state(&self) -> Snapshot {
+        let v = &self.v;
+        let vars = (0..v.num_vars()).map(Var::from_idx);
+        let mut snapshot = Snapshot {
+            ok: v.ok,
+            num_vars: v.num_vars(),
+            clauses: vec![],
+            learnts: vec![],
+            lbds: vec![],
+            units: v
+                .vars
+                .trail
+                .iter()
+                .copied()
+                .filter(|&lit| v.level(lit.var()) == 0)
+                .collect(),
+            scores: vars.clone().map(|x| v.vars.branch.score(x)).collect(),
+            polarity: vars.clone().map(|x| v.polarity[x]).collect(),
+            user_pol: vars.clone().map(|x| v.user_pol[x]).collect(),
+            assump_priority: vars.clone().map(|x| v.assump_priority[x]).collect(),
+            decision: vars.clone().map(|x| v.decision[x]).collect(),
+            frozen: vars.clone().map(|x| v.frozen[x]).collect(),
+            eliminated: vars.clone().map(|x| v.eliminated[x]).collect(),
+            blocking: vars.clone().map(|x| v.blocking[x]).collect(),
+            internal: vars.map(|x| v.internal[x]).collect(),
+            groups: self.groups.clone(),
+            group_clauses: self.group_clauses.clone(),
+            elim: self.elim.clone(),
+            natives: v.natives.clone(),
+        };
+        for &cr in &self.clauses {
+            snapshot.clauses.extend_from_slice(v.ca.get_ref(cr).lits());
+            snapshot.clauses.push(Lit::UNDEF);
+        }
+        for &cr in &self.learnts {
+            let c = v.ca.get_ref(cr);
+            snapshot.learnts.extend_from_slice(c.lits());
+            snapshot.learnts.push(Lit::UNDEF);
+            snapshot.lbds.push(c.lbd());
+        }
+        // the native constraints start over from an empty trail
+        snapshot.natives.backtrack(&v.vars.trail, 0);
+        snapshot
+    }
+
+    /// Go back to the state saved in `snapshot` by [`save_state`](Self::save_state).
+    ///
+    /// The variables created since then are kept, but they occur in no clause anymore.
+    /// A theory must be brought back to its own state separately.
+    ///
+    /// # Panics
+    ///
+    /// If a proof is logged, as it cannot take back the deleted clauses.
+    pub fn restore(&mut self, snapshot: Snapshot) {
+        self.release_trail();
+        assert!(
+            self.v.proof.out.is_none(),
+            "cannot restore a snapshot while logging a proof"
+        );
+        self.cancel_until(&mut theory::EmptyTheory::new(), 0);
+        for &cr in self.clauses.iter().chain(&self.learnts) {
+            self.v.remove_clause(cr);
+        }
+        self.clauses.clear();
+        self.learnts.clear();
+        self.garbage_collect();
+        self.v.clear_level0();
+        while self.v.num_vars() < snapshot.num_vars {
+            self.v.new_var(lbool::UNDEF, true);
+        }
+        let mut scores = vec![];
+        for (i, x) in (0..snapshot.num_vars).map(Var::from_idx).enumerate() {
+            scores.push((snapshot.scores[i], x));
+            self.v.polarity[x] = snapshot.polarity[i];
+            self.v.user_pol[x] = snapshot.user_pol[i];
+            self.v.assump_priority[x] = snapshot.assump_priority[i];
+            self.v.frozen[x] = snapshot.frozen[i];
+            self.v.eliminated[x] = snapshot.eliminated[i];
+            self.v.blocking[x] = snapshot.blocking[i];
+            self.v.internal[x] = snapshot.internal[i];
+            self.v.set_decision_var(x, snapshot.decision[i]);
+        }
+        for x in (snapshot.num_vars..self.v.num_vars()).map(Var::from_idx) {
+            scores.push((0.0, x));
+            if self.v.eliminated[x] {
+                self.v.eliminated[x] = false;
+                self.v.set_decision_var(x, true);
+            }
+            self.v.blocking[x] = false;
+        }
+        self.v.rebuild_branching(scores);
+        self.set_groups(snapshot.groups);
+        self.group_clauses = snapshot.group_clauses;
+        self.elim = snapshot.elim;
+        self.v.natives = snapshot.natives;
+        self.model.clear();
+        self.conflict.clear();
+
+        for &lit in &snapshot.units {
+            self.v.vars.unchecked_enqueue(lit, CRef::UNDEF);
+        }
+        for c in snapshot.clauses.split(|&lit| lit == Lit::UNDEF) {
+            if !c.is_empty() {
+                let cr = self.v.ca.alloc_with_learnt(c, false);
+                self.clauses.push(cr);
+                self.v.attach_clause(cr);
+            }
+        }
+        let learnts = snapshot.learnts.split(|&lit| lit == Lit::UNDEF);
+        for (c, &lbd) in learnts.zip(&snapshot.lbds) {
+            let cr = self.v.ca.alloc_with_learnt(c, true);
+            self.v.ca.get_mut(cr).set_lbd(lbd);
+            self.learnts.push(cr);
+            self.v.attach_clause(cr);
+        }
+        self.v.ok = snapshot.ok;
+        if self.v.ok && self.v.propagate().is_some() {
+            self.v.ok = false;
+        }
+    }
+
+    /// Restore the eliminated variables among `vars`, and add back the clauses they were
+    /// removed from or that are blocked on them, which may restore other variables.
+    fn restore_vars(&mut self, vars: impl IntoIterator<Item = Var>) {
+        if self.elim.is_empty() {
+            return;
+        }
+        let self_v = &self.v;
+        let removed = |v: Var| self_v.eliminated[v] || self_v.blocking[v];
+        let mut todo: Vec<Var> = vars.into_iter().filter(|&v| removed(v)).collect();
+        let mut clauses = vec![];
+        while let Some(v) = todo.pop() {
+            if !self.v.eliminated[v] && !self.v.blocking[v] {
+                continue;
+            }
+            debug!("restore {:?}", v);
+            if self.v.eliminated[v] {
+                self.v.eliminated[v] = false;
+                self.v.set_decision_var(v, true);
+            }
+            self.v.blocking[v] = false;
+            let start = clauses.len();
+            self.elim.take_clauses_of(v, &mut clauses);
+            let self_v = &self.v;
+            let removed = |v: Var| self_v.eliminated[v] || self_v.blocking[v];
+            for c in &clauses[start..] {
+                todo.extend(c.iter().map(|l| l.var()).filter(|&x| removed(x)));
+            }
+        }
+        for mut c in clauses {
+            let id = self.v.proof.new_id();
+            self.add_clause_(&mut c, id);
+        }
+    }
+
+    /// Split the problem into at most `2^depth` cubes, by lookahead.
+    ///
+    /// The cubes are conjunctions of literals that cover all the models of the problem:
+    /// every model satisfies one of them. They are obtained by recursively branching on the
+    /// variable whose two polarities propagate the most literals (among the most active
+    /// ones), and the cubes refuted by unit propagation are left out, so an empty result
+    /// means that the problem is unsatisfiable. Failed literals found by the lookahead are
+    /// added to the cubes.
+    ///
+    /// Each cube can then be solved independently, by using it as assumptions,
+    /// see [`cube_and_conquer`](Self::cube_and_conquer).
+    pub fn cube(&mut self, depth: u32) -> Vec<Vec<Lit>> {
+        self.release_trail();
+        let mut th = theory::EmptyTheory::new();
+        self.cancel_until(&mut th, 0);
+        let mut cubes = vec![];
+        if !self.propagate_level0() {
+            return cubes;
+        }
+        // active clause groups are assumed at level 1
+        self.new_decision_level(&mut th);
+        for i in 0..self.groups.len() {
+            let act = self.groups[i];
+            if self.v.value_lit(act) == lbool::FALSE {
+                self.cancel_until(&mut th, 0);
+                return cubes;
+            } else if self.v.value_lit(act) == lbool::UNDEF {
+                self.v.vars.unchecked_enqueue(act, CRef::UNDEF);
+            }
+        }
+        if self.propagate_lookahead() {
+            self.cube_rec(depth, &mut vec![], &mut cubes);
+        }
+        self.cancel_until(&mut th, 0);
+        cubes
+    }
+
+    /// Add to `cubes` the cubes that extend `cube`, whose literals are assigned
+    /// at the decision levels after the first one.
+    fn cube_rec(&mut self, depth: u32, cube: &mut Vec<Lit>, cubes: &mut Vec<Vec<Lit>>) {
+        const LOOKAHEAD_VARS: usize = 64;
+        if depth == 0 {
+            cubes.push(cube.clone());
+            return;
+        }
+        let mut candidates: Vec<Var> = (0..self.num_vars())
+            .map(Var::from_idx)
+            .filter(|&v| self.v.decision[v] && self.v.value(v) == lbool::UNDEF)
+            .collect();
+        let branch = &self.v.vars.branch;
+        candidates.sort_unstable_by(|&x, &y| branch.score(y).total_cmp(&branch.score(x)));
+        candidates.truncate(LOOKAHEAD_VARS);
+        let mut best = None;
+        let mut best_score = 0;
+        for v in candidates {
+            let pos = self.lookahead(Lit::new(v, true));
+            let neg = self.lookahead(Lit::new(v, false));
+            let (pos, neg) = match (pos, neg) {
+                (Some(pos), Some(neg)) => (pos, neg),
+                (None, None) => return,
+                (Some(_), None) | (None, Some(_)) => {
+                    // failed literal: the other polarity is implied
+                    self.branch(Lit::new(v, pos.is_some()), depth, cube, cubes);
+                    return;
+                }
+            };
+            // favor balanced splits
+            let score = (pos + 1) * (neg + 1);
+            if score > best_score {
+                best = Some(v);
+                best_score = score;
             }
+        }
+        let Some(v) = best else {
+            // every variable is assigned
+            cubes.push(cube.clone());
+            return;
         };
+        self.branch(Lit::new(v, true), depth - 1, cube, cubes);
+        self.branch(Lit::new(v, false), depth - 1, cube, cubes);
+    }
+
+    /// Add `p` to `cube`, and continue with [`cube_rec`](Self::cube_rec) unless it is refuted.
+    fn branch(&mut self, p: Lit, depth: u32, cube: &mut Vec<Lit>, cubes: &mut Vec<Vec<Lit>>) {
+        let lvl = self.v.decision_level();
+        self.v.vars.new_decision_level();
+        self.v.vars.unchecked_enqueue(p, CRef::UNDEF);
+        cube.push(p);
+        if self.propagate_lookahead() {
+            self.cube_rec(depth, cube, cubes);
+        }
+        cube.pop();
+        self.v.cancel_until(lvl);
+    }
+
+    /// Number of literals propagated by `p`, or `None` if it leads to a conflict.
+    fn lookahead(&mut self, p: Lit) -> Option<usize> {
+        let lvl = self.v.decision_level();
+        let trail_len = self.v.vars.trail.len();
+        self.v.vars.new_decision_level();
+        self.v.vars.unchecked_enqueue(p, CRef::UNDEF);
+        let ok = self.propagate_lookahead();
+        let n = self.v.vars.trail.len() - trail_len;
+        self.v.cancel_until(lvl);
+        ok.then_some(n)
+    }
+
+    /// Propagate the clauses and native constraints, without learning anything.
+    ///
+    /// Returns `false` in case of conflict.
+    fn propagate_lookahead(&mut self) -> bool {
+        loop {
+            if self.v.propagate().is_some() {
+                return false;
+            }
+            self.tmp_c_th.clear();
+            let mut th_arg = TheoryArg {
+                v: &mut self.v,
+                lits: &mut self.tmp_c_th,
+                has_propagated: false,
+                conflict: TheoryConflict::Nil,
+            };
+            let mut natives = mem::take(&mut th_arg.v.natives);
+            natives.check(&mut th_arg);
+            th_arg.v.natives = natives;
+            if !th_arg.is_ok() {
+                return false;
+            }
+            if !th_arg.has_propagated {
+                return true;
+            }
+        }
+    }
+
+    /// Solve the problem by splitting it into [cubes](Self::cube) of depth `depth`,
+    /// which are then solved one after the other using assumptions.
+    ///
+    /// Clauses learnt while solving a cube are kept for the next ones. Returns `UNDEF`
+    /// if the search was interrupted; the model is available if the result is `TRUE`,
+    /// but there is no unsat core if it is `FALSE`.
+    pub fn cube_and_conquer(&mut self, depth: u32) -> lbool {
+        for cube in self.cube(depth) {
+            let res = self.solve_limited(&cube);
+            if res != lbool::FALSE {
+                return res;
+            }
+        }
+        lbool::FALSE
+    }
+
+    /// Assign the assumptions `assumps` at level 1, and propagate the clauses and the
+    /// native constraints, without any search or learning, to use the propagation of
+    /// the solver on its own.
+    ///
+    /// The resulting assignment is given by the view, until it is dropped. The active
+    /// clause groups are assumed before `assumps`. The solver backtracks to level 0 in
+    /// case of conflict.
+    pub fn propagate_under(
+        &mut self,
+        assumps: &[Lit],
+    ) -> Result<TrailView<'_, Cb>, PropagationConflict> {
+        self.release_trail();
+        let mut th = theory::EmptyTheory::new();
+        self.cancel_until(&mut th, 0);
+        if !self.elim.is_empty() {
+            self.restore_vars(assumps.iter().map(|l| l.var()));
+        }
+        if !self.propagate_level0() {
+            return Err(PropagationConflict::Unsat);
+        }
+        self.v.vars.new_decision_level();
+        match self.propagate_assumptions(assumps) {
+            Ok(start) => Ok(TrailView {
+                solver: self,
+                start,
+            }),
+            Err(confl) => {
+                self.v.cancel_until(0);
+                Err(confl)
+            }
+        }
+    }
+
+    /// Propagate the clauses and the native constraints at level 0, and mark the solver
+    /// `UNSAT` in case of conflict. Returns `false` if the solver is `UNSAT`.
+    ///
+    /// The native constraints are only propagated during the search otherwise, so a
+    /// conflict among the literals of level 0 would be found at level 1, and lost when
+    /// backtracking without analysis.
+    fn propagate_level0(&mut self) -> bool {
+        debug_assert_eq!(self.v.decision_level(), 0);
+        if !self.v.ok {
+            return false;
+        }
+        if let Some(confl) = self.v.propagate() {
+            self.v.proof_unsat(Some(confl));
+            self.v.ok = false;
+        } else if !self.propagate_lookahead() {
+            self.v.proof_unsat(None);
+            self.v.ok = false;
+        }
+        self.v.ok
+    }
+
+    /// Assign the active clause groups and `assumps`, and propagate.
+    ///
+    /// Returns the index on the trail of the first implied literal.
+    fn propagate_assumptions(&mut self, assumps: &[Lit]) -> Result<usize, PropagationConflict> {
+        for &lit in self.groups.iter().chain(assumps) {
+            if self.v.value_lit(lit) == lbool::FALSE {
+                return Err(PropagationConflict::Assumption(lit));
+            } else if self.v.value_lit(lit) == lbool::UNDEF {
+                self.v.vars.unchecked_enqueue(lit, CRef::UNDEF);
+            }
+        }
+        let start = self.v.vars.trail.len();
+        loop {
+            if let Some(cr) = self.v.propagate() {
+                let lits = self.v.ca.get_ref(cr).lits().to_vec();
+                return Err(PropagationConflict::Clause(lits));
+            }
+            self.tmp_c_th.clear();
+            let mut th_arg = TheoryArg {
+                v: &mut self.v,
+                lits: &mut self.tmp_c_th,
+                has_propagated: false,
+                conflict: TheoryConflict::Nil,
+            };
+            let mut natives = mem::take(&mut th_arg.v.natives);
+            natives.check(&mut th_arg);
+            th_arg.v.natives = natives;
+            match th_arg.conflict {
+                TheoryConflict::Nil if th_arg.has_propagated => continue,
+                TheoryConflict::Nil => return Ok(start),
+                TheoryConflict::Prop(p) => {
+                    let lits = self.v.natives.explain(p).unwrap_or(&[]).to_vec();
+                    return Err(PropagationConflict::Clause(lits));
+                }
+                TheoryConflict::Clause { .. } => {
+                    return Err(PropagationConflict::Clause(self.tmp_c_th.clone()));
+                }
+            }
+        }
+    }
+
+    /// Write into `out` the literals implied by the assumptions `assumps` through unit
+    /// propagation (and the propagation of the native constraints), without any search.
+    ///
+    /// The assumptions are not in `out`, nor the literals true at level 0. Returns
+    /// `false`, with `out` empty, if the assumptions are refuted by propagation or if the
+    /// solver is in an `UNSAT` state, which is also the case after a conflict among the
+    /// literals of level 0. See also [`propagate_under`](Self::propagate_under).
+    pub fn implies(&mut self, assumps: &[Lit], out: &mut Vec<Lit>) -> bool {
+        self.release_trail();
+        out.clear();
+        match self.propagate_under(assumps) {
+            Ok(view) => {
+                out.extend_from_slice(view.implied());
+                true
+            }
+            Err(_) => false,
+        }
+    }
+
+    /// Notify the callbacks that `c` was added during the search.
+    fn on_new_clause(&mut self, c: &[Lit], k: clause::Kind) {
+        self.cb.on_new_clause(c, k);
+        if let Some(f) = &mut self.learn {
+            if k == clause::Kind::Learnt && c.len() <= self.learn_max_len {
+                f(c)
+            }
+        }
+    }
+
+    /// Notify the callbacks and the listener that `c` was learnt.
+    fn on_learnt_clause(&mut self, c: &[Lit], lbd: u32) {
+        self.on_new_clause(c, clause::Kind::Learnt);
+        if let Some(listener) = &mut self.listener {
+            listener.on_learnt(c, lbd);
+        }
+    }
+
+    /// Notify the callbacks and the listener that the clause `old` was replaced by `new`.
+    fn on_strengthen_clause(&mut self, old: &[Lit], new: &[Lit]) {
+        self.on_new_clause(new, clause::Kind::Learnt);
+        self.cb.on_delete_clause(old);
+        if let Some(listener) = &mut self.listener {
+            listener.on_strengthened(old, new);
+        }
+    }
+
+    fn within_budget(&mut self) -> bool {
+        (self.v.conflict_budget < 0 || self.v.conflicts < self.v.conflict_budget as u64)
+            && (self.v.propagation_budget < 0
+                || self.v.propagations < self.v.propagation_budget as u64)
+            && (self.v.tick_budget < 0 || self.v.ticks < self.v.tick_budget as u64)
+            && self.within_time_limit()
+            && self.within_memory_limit()
+            && !self.cb.stop()
+            && !self.terminate.as_mut().is_some_and(|f| f())
+            && !self
+                .v
+                .stop_flag
+                .as_ref()
+                .is_some_and(|f| f.get().load(Ordering::Relaxed))
+    }
+
+    /// Check the memory limit from time to time, reducing the learnt clauses until the
+    /// solver is under it again, or until nothing more can be removed.
+    fn within_memory_limit(&mut self) -> bool {
+        if self.v.memory_limit == usize::MAX || self.v.conflicts < self.v.next_memory_check {
+            return !self.v.memory_out;
+        }
+        self.v.next_memory_check = self.v.conflicts + MEMORY_CHECK_INTERVAL;
+        while self.memory_usage() > self.v.memory_limit {
+            let learnts = self.learnts.len();
+            self.reduce_db();
+            if self.v.ca.wasted() > 0 {
+                self.garbage_collect();
+            }
+            // keep fewer learnt clauses from now on
+            self.v.max_learnts /= 2.0;
+            if self.learnts.len() == learnts {
+                info!("memory limit of {} bytes reached", self.v.memory_limit);
+                event!(WARN, limit = self.v.memory_limit, "memory_out");
+                self.v.memory_out = true;
+                return false;
+            }
+        }
+        true
+    }
+
+    #[cfg(feature = "std")]
+    fn within_time_limit(&self) -> bool {
+        self.v
+            .deadline
+            .is_none_or(|d| std::time::Instant::now() < d)
+    }
+
+    #[cfg(not(feature = "std"))]
+    fn within_time_limit(&self) -> bool {
+        true
+    }
+
+    /// Add clause, whose id in the proof is `id`.
+    ///
+    /// Precondition: `clause` is sorted for some ordering on `Lit`
+    fn add_clause_(&mut self, clause: &mut Vec<Lit>, id: u64) -> bool {
+        self.release_trail();
+        if !self.v.ok {
+            return false;
+        }
+
+        let mut last_lit = Lit::UNDEF;
+        let mut j = 0;
+        let mut false_lits = vec![];
+        // remove duplicates, true literals, etc.
+        for i in 0..clause.len() {
+            let lit_i = clause[i];
+            let value = self.v.value_lit(lit_i);
+            let lvl = self.v.level_lit(lit_i);
+            if (value == lbool::TRUE && lvl == 0) || lit_i == !last_lit {
+                return true; // tauto or satisfied already at level 0
+            } else if value == lbool::FALSE && lvl == 0 {
+                // duplicates would repeat the hints of the literal
+                if self.v.proof.lrat && false_lits.last() != Some(&lit_i) {
+                    false_lits.push(lit_i);
+                }
+            } else if lit_i != last_lit {
+                // not a duplicate
+                last_lit = lit_i;
+                clause[j] = lit_i;
+                j += 1;
+            }
+        }
+
+        if !false_lits.is_empty() {
+            // LRAT: deriving the simplified clause would take an id that belongs to
+            // the next original clause. Keep the false literals after the watched ones
+            // instead, they are removed in `simplify` once the input is complete.
+            if j == 0 {
+                self.v.proof.pending_unsat = Some((id, false_lits));
+                self.v.ok = false;
+                return false;
+            }
+            clause.truncate(j);
+            clause.extend_from_slice(&false_lits);
+            let cr = self.v.ca.alloc_with_learnt(clause, false);
+            self.v.set_clause_id(cr, id);
+            self.clauses.push(cr);
+            self.v.attach_clause(cr);
+            if j == 1 {
+                // `cr` is the reason of the unit
+                self.v.vars.unchecked_enqueue(clause[0], cr);
+            }
+            return true;
+        } else if j != clause.len() {
+            // the simplified clause is RUP (LRAT: only duplicates were removed)
+            clause.resize(j, Lit::UNDEF);
+            if !self.v.proof.lrat {
+                self.v.proof.add(0, clause);
+            }
+        }
+        if clause.is_empty() {
+            self.v.ok = false;
+            return false;
+        } else if clause.len() == 1 {
+            self.v.vars.unchecked_enqueue(clause[0], CRef::UNDEF);
+            self.v.set_unit_id(clause[0].var(), id);
+        } else {
+            let cr = self.v.ca.alloc_with_learnt(clause, false);
+            self.v.set_clause_id(cr, id);
+            self.clauses.push(cr);
+            self.v.attach_clause(cr);
+        }
+
+        true
+    }
+
+    /// Add an irredundant clause during search.
+    ///
+    /// If the clause is unit or false under the current assignment, the solver first
+    /// backtracks to the level where it propagates (or to the level below a conflict),
+    /// so that the watched literals are consistent with the trail.
+    fn add_clause_during_search<Th: Theory>(&mut self, th: &mut Th, clause: &mut Vec<Lit>) -> bool {
+        debug!("add internal clause {:?}", clause);
+        if !self.v.ok {
+            return false;
+        }
+
+        // theory lemmas are axioms from the SAT solver's point of view
+        let id = self.v.proof.new_id();
+        self.v.proof.add(id, clause);
+        clause.sort_unstable();
+        clause.dedup();
+        let v = &self.v;
+        if clause.windows(2).any(|w| w[0] == !w[1])
+            || clause
+                .iter()
+                .any(|&lit| v.value_lit(lit) == lbool::TRUE && v.level_lit(lit) == 0)
+        {
+            return true; // tautology, or satisfied at level 0
+        }
+        // the literals that are not false first, then the false ones by decreasing level
+        clause.sort_by_key(|&lit| {
+            let is_false = v.value_lit(lit) == lbool::FALSE;
+            (is_false, if is_false { -v.level_lit(lit) } else { 0 })
+        });
+        let level = |v: &SolverV, i: usize| clause.get(i).map_or(0, |&lit| v.level_lit(lit));
+        let is_false = |v: &SolverV, i: usize| v.value_lit(clause[i]) == lbool::FALSE;
+        if is_false(v, 0) {
+            // conflict: backtrack below the highest level, or to where it propagates
+            let (lvl0, lvl1) = (level(v, 0), level(v, 1));
+            if lvl0 == 0 {
+                self.cancel_until(th, 0);
+                return self.add_clause_(clause, id);
+            }
+            self.cancel_until(
+                th,
+                if lvl0 == lvl1 {
+                    lvl0 as u32 - 1
+                } else {
+                    lvl1 as u32
+                },
+            );
+        }
+        let v = &self.v;
+        let propagates = clause.len() == 1
+            || (is_false(v, 1)
+                && (v.value_lit(clause[0]) == lbool::UNDEF || level(v, 0) > level(v, 1)));
+        if propagates {
+            let lvl1 = level(v, 1);
+            if lvl1 == 0 {
+                self.cancel_until(th, 0);
+                return self.add_clause_(clause, id);
+            }
+            self.cancel_until(th, lvl1 as u32);
+        }
+        let cr = self.v.ca.alloc_with_learnt(clause, false);
+        self.v.set_clause_id(cr, id);
+        self.clauses.push(cr);
+        self.v.attach_clause(cr);
+        if propagates {
+            self.v.vars.unchecked_enqueue(clause[0], cr);
+        }
+        true
+    }
+}
+
+/// Theory-triggered conflict.
+enum TheoryConflict {
+    Nil,
+    Clause { costly: bool },
+    Prop(Lit),
+}
+
+/// The temporary theory argument, passed to the theory.
+///
+/// This is where the theory can perform actions such as adding clauses.
+/// When the theory is called (on a partial model that is satisfiable on
+/// the boolean level), it can use this object to trigger conflicts,
+/// add lemmas, and propagate literals.
+pub struct TheoryArg<'a> {
+    v: &'a mut SolverV,
+    lits: &'a mut Vec<Lit>,
+    has_propagated: bool,
+    conflict: TheoryConflict,
+}
+
+/// Temporary representation of a learnt clause, produced in `analyze`.
+struct LearntClause<'a> {
+    clause: &'a [Lit],  // the clause
+    backtrack_lvl: i32, // where to backtrack?
+    conflict_lvl: i32,  // level of the conflict
+    lbd: u32,           // number of distinct levels in the clause
+}
+
+#[derive(Clone, Copy, Debug)]
+#[allow(clippy::upper_case_acronyms)]
+enum Conflict<'a> {
+    BCP(CRef), // boolean propagation conflict
+    ThLemma { lits: &'a [Lit], add: bool },
+    ThProp(Lit), // literal was propagated, but is false
+}
+
+#[derive(Clone, Copy, Debug)]
+enum ResolveWith<'a> {
+    Init(Conflict<'a>), // initial conflict
+    Resolve(Lit, CRef), // propagation of lit because of clause
+}
+
+impl SolverV {
+    #[inline(always)]
+    pub fn num_assigns(&self) -> u32 {
+        self.vars.num_assigns()
+    }
+
+    #[inline(always)]
+    fn num_vars(&self) -> u32 {
+        self.next_var.idx()
+    }
+    fn num_clauses(&self) -> u64 {
+        self.num_clauses
+    }
+    fn num_conflicts(&self) -> u64 {
+        self.conflicts
+    }
+    fn num_props(&self) -> u64 {
+        self.propagations
+    }
+    fn num_learnts(&self) -> u64 {
+        self.num_learnts
+    }
+
+    /// Memory used by the clauses, the watches and the per-variable tables, in bytes.
+    fn memory_usage(&self) -> usize {
+        let vars = &self.vars;
+        let trail = vars.trail.capacity() * mem::size_of::<Lit>();
+        self.ca.memory()
+            + self.watches_data.memory()
+            + self.bin_watches_data.memory()
+            + vars.ass.memory_usage()
+            + vars.vardata.memory_usage()
+            + trail
+            + self.polarity.memory_usage()
+            + self.target.memory_usage()
+            + self.best.memory_usage()
+            + self.user_pol.memory_usage()
+            + self.assump_priority.memory_usage()
+            + self.core_hits.memory_usage()
+            + self.decision.memory_usage()
+            + self.frozen.memory_usage()
+            + self.eliminated.memory_usage()
+            + self.blocking.memory_usage()
+            + self.internal.memory_usage()
+            + self.seen.memory_usage()
+    }
+
+    #[inline(always)]
+    pub fn level(&self, x: Var) -> i32 {
+        self.vars.level(x)
+    }
+
+    /// Literals of the reason of the assignment of `x`, see [`Solver::reason_of`].
+    fn reason_lits(&self, x: Var) -> Option<&[Lit]> {
+        if self.value(x) == lbool::UNDEF {
+            return None;
+        }
+        let cr = self.reason(x);
+        if cr == CRef::UNDEF {
+            None
+        } else if cr == CRef::SPECIAL {
+            self.natives
+                .explain(Lit::new(x, self.value(x) == lbool::TRUE))
+        } else {
+            Some(self.ca.get_ref(cr).lits())
+        }
+    }
+
+    #[inline(always)]
+    pub fn level_lit(&self, x: Lit) -> i32 {
+        self.level(x.var())
+    }
+
+    #[inline(always)]
+    pub fn value(&self, x: Var) -> lbool {
+        self.vars.value(x)
+    }
+
+    #[inline(always)]
+    pub fn value_lit(&self, x: Lit) -> lbool {
+        self.vars.value_lit(x)
+    }
+
+    fn set_decision_var(&mut self, v: Var, b: bool) {
+        if b && !self.decision[v] {
+            self.dec_vars += 1;
+        } else if !b && self.decision[v] {
+            self.dec_vars -= 1;
+        }
+        self.decision[v] = b;
+        self.insert_var_order(v);
+    }
+
+    fn insert_var_order(&mut self, x: Var) {
+        if self.decision[x] {
+            self.vars.branch.insert(x);
+        }
+    }
+
+    /// Start over with a new decision heuristic, when [`SolverOpts::branching`] changes.
+    fn reset_branching(&mut self) {
+        self.vars.branch = Branching::new(self.opts.branching);
+        for v in (0..self.next_var.idx()).map(Var::from_idx) {
+            self.vars.branch.new_var(v, 0.0);
+        }
+        self.insert_all_var_order();
+    }
+
+    /// Insert all the decision variables in a new decision heuristic.
+    fn insert_all_var_order(&mut self) {
+        let decision = &self.decision;
+        let vars = (0..self.next_var.idx()).map(Var::from_idx);
+        self.vars.branch.insert_all(vars.filter(|&v| decision[v]));
+    }
+
+    /// Rename the variables with `map`, for [`Solver::compact`], once the removed variables
+    /// are only assigned at level 0. The `n` variables left watch the clauses `crefs`,
+    /// and the branching heuristic starts over with their order.
+    fn remap_vars(&mut self, map: &VMap<Var>, n: u32, crefs: impl Iterator<Item = CRef>) {
+        fn remap<T: Clone>(m: &mut VMap<T>, map: &VMap<Var>, n: u32, pad: T) {
+            let mut new = VMap::new();
+            for (v, x) in m.iter() {
+                if map.get(v).is_some_and(|&x| x != Var::UNDEF) {
+                    new.insert(map[v], x.clone(), pad.clone());
+                }
+            }
+            // do not keep the spare capacity of the growth
+            new.shrink_to(Var::from_idx(n));
+            *m = new;
+        }
+        debug_assert_eq!(self.decision_level(), 0);
+        let rename = |lit: Lit| Lit::new(map[lit.var()], lit.sign());
+        let kept = |lit: &Lit| map[lit.var()] != Var::UNDEF;
+        self.th_notified = self.vars.trail[..self.th_notified]
+            .iter()
+            .filter(|lit| kept(lit))
+            .count();
+        self.vars.trail.retain(kept);
+        self.vars
+            .trail
+            .iter_mut()
+            .for_each(|lit| *lit = rename(*lit));
+        self.qhead = self.vars.trail.len() as i32;
+        self.simp_db_assigns = -1;
+        let scores: Vec<(f64, Var)> = map
+            .iter()
+            .filter(|&(_, &x)| x != Var::UNDEF)
+            .map(|(v, &x)| (self.vars.branch.score(v), x))
+            .collect();
+
+        remap(&mut self.vars.ass, map, n, lbool::UNDEF);
+        remap(&mut self.vars.vardata, map, n, VarData::new(CRef::UNDEF, 0));
+        remap(&mut self.polarity, map, n, false);
+        remap(&mut self.target, map, n, lbool::UNDEF);
+        remap(&mut self.best, map, n, lbool::UNDEF);
+        remap(&mut self.user_pol, map, n, lbool::UNDEF);
+        remap(&mut self.assump_priority, map, n, 0);
+        remap(&mut self.core_hits, map, n, 0);
+        remap(&mut self.decision, map, n, false);
+        remap(&mut self.frozen, map, n, false);
+        remap(&mut self.eliminated, map, n, false);
+        remap(&mut self.blocking, map, n, false);
+        remap(&mut self.internal, map, n, false);
+        self.seen.free();
+        if n > 0 {
+            self.seen.reserve(Var::from_idx(n - 1));
+        }
+        self.proof.unit_ids.clear();
+        self.next_var = Var::from_idx(n);
+        self.th_st.next_var = n;
+        self.th_cache.clear();
+        self.free_vars.clear();
+        self.released.clear();
+        self.dec_vars = self.decision.iter().filter(|&(_, &d)| d).count() as u64;
+
+        self.watches_data = OccListsData::new();
+        self.bin_watches_data = OccListsData::new();
+        for v in (0..n).map(Var::from_idx) {
+            self.init_watches(v);
+        }
+        self.num_clauses = 0;
+        self.num_learnts = 0;
+        self.clauses_literals = 0;
+        self.learnts_literals = 0;
+        for cr in crefs {
+            self.attach_clause(cr);
+        }
+
+        self.rebuild_branching(scores);
+    }
+
+    /// Unassign the literals of level 0, which have no reason anymore, to start
+    /// over from an empty trail.
+    fn clear_level0(&mut self) {
+        debug_assert_eq!(self.decision_level(), 0);
+        for i in 0..self.vars.trail.len() {
+            let x = self.vars.trail[i].var();
+            self.vars.ass[x] = lbool::UNDEF;
+            self.vars.vardata[x] = VarData::new(CRef::UNDEF, 0);
+            self.vars.branch.on_unassign(x);
+            self.insert_var_order(x);
+        }
+        self.natives.backtrack(&self.vars.trail, 0);
+        self.th_cache.clear();
+        self.vars.trail.clear();
+        self.qhead = 0;
+        self.th_notified = 0;
+        self.simp_db_assigns = -1;
+        self.simp_db_props = 0;
+    }
+
+    /// Start over with a new decision heuristic, where the variables are in the order of
+    /// the given scores.
+    fn rebuild_branching(&mut self, mut scores: Vec<(f64, Var)>) {
+        // the variables are registered from the lowest score to the highest one, so that
+        // VMTF keeps their order, with scores scaled down for VSIDS
+        scores.sort_unstable_by(|a, b| a.0.total_cmp(&b.0));
+        let max = scores.last().map_or(0.0, |s| s.0);
+        self.vars.branch = Branching::new(self.opts.branching);
+        for &(score, v) in &scores {
+            let score = if max > 0.0 { score / max } else { 0.0 };
+            self.vars.branch.new_var(v, score as f32);
+        }
+        self.insert_all_var_order();
+    }
+
+    fn cla_decay_activity(&mut self) {
+        self.cla_inc *= 1.0 / self.opts.clause_decay;
+    }
+
+    fn cla_bump_activity(&mut self, learnts: &[CRef], cr: CRef) {
+        let new_activity = {
+            let mut c = self.ca.get_mut(cr);
+            let r = c.activity() + self.cla_inc as f32;
+            c.set_activity(r);
+            r
+        };
+        if new_activity > 1e20 {
+            // Rescale:
+            for &learnt in learnts.iter() {
+                let mut c = self.ca.get_mut(learnt);
+                let r = c.activity() * 1e-20;
+                c.set_activity(r);
+            }
+            self.cla_inc *= 1e-20;
+        }
+    }
+
+    /// Update the moving averages of Glucose restarts after learning a clause with the given
+    /// LBD, and block restarts if the trail is much larger than usual.
+    fn update_restart_emas(&mut self, lbd: u32) {
+        let trail = self.vars.trail.len() as f64;
+        if self.conflicts > GLUCOSE_BLOCK_FIRST
+            && trail > self.opts.restart_block_margin * self.trail_ema.value()
+        {
+            // the solver may be close to a model, do not restart for now
+            self.restart_blocked = self.conflicts + GLUCOSE_RESTART_MIN as u64;
+        }
+        self.trail_ema.update(trail);
+        self.lbd_ema_fast.update(lbd as f64);
+        self.lbd_ema_slow.update(lbd as f64);
+    }
+
+    /// Should the search restart, with Glucose restarts, after `conflict_c` conflicts?
+    ///
+    /// Restarts happen when the recent learnt clauses have a larger LBD than usual.
+    fn glucose_restart(&self, conflict_c: i32) -> bool {
+        self.restart_strategy() == RestartStrategy::Glucose
+            && conflict_c >= GLUCOSE_RESTART_MIN
+            && self.conflicts >= self.restart_blocked
+            && self.lbd_ema_fast.value() > self.opts.restart_margin * self.lbd_ema_slow.value()
+    }
+
+    /// The restart strategy of the current mode.
+    fn restart_strategy(&self) -> RestartStrategy {
+        match (self.opts.mode_switching, self.stable) {
+            (false, _) => self.opts.restarts(),
+            (true, false) => RestartStrategy::Glucose,
+            (true, true) => RestartStrategy::Luby,
+        }
+    }
+
+    /// Are the target phases used in the current mode?
+    fn target_phase(&self) -> bool {
+        if self.opts.mode_switching {
+            self.stable
+        } else {
+            self.opts.target_phase
+        }
+    }
+
+    /// Switch between the focused and stable modes, at a restart, if the current mode has
+    /// used up its ticks. Returns `true` if the mode was switched.
+    ///
+    /// The first focused mode lasts [`SolverOpts::mode_init`] conflicts, the ticks it used
+    /// give the length of the following modes, which grows quadratically with the number
+    /// of switches but is the same for the two modes of each pair.
+    fn switch_mode(&mut self) -> bool {
+        if !self.opts.mode_switching {
+            return false;
+        }
+        if self.mode_switches == 0 {
+            if self.conflicts < self.opts.mode_init as u64 {
+                return false;
+            }
+            self.mode_ticks = self.ticks.max(1);
+        } else if self.ticks < self.mode_limit {
+            return false;
+        }
+        self.mode_switches += 1;
+        self.stable = !self.stable;
+        let k = self.mode_switches / 2 + 1;
+        self.mode_limit = self.ticks + self.mode_ticks * k * k;
+        debug!(
+            "switch to {} mode",
+            if self.stable { "stable" } else { "focused" }
+        );
+        event!(DEBUG, stable = self.stable, "switch_mode");
+        true
+    }
+
+    /// Save the values of the conflict-free part of the trail (before the conflict level)
+    /// as target and best phases, if it is the largest so far.
+    fn save_phases(&mut self) {
+        let len = self.vars.trail_lim.last().map_or(0, |&x| x as u32);
+        if len > self.target_len {
+            for &lit in &self.vars.trail[..len as usize] {
+                self.target[lit.var()] = lbool::new(lit.sign());
+            }
+            self.target_len = len;
+        }
+        if len > self.best_len {
+            for &lit in &self.vars.trail[..len as usize] {
+                self.best[lit.var()] = lbool::new(lit.sign());
+            }
+            self.best_len = len;
+        }
+    }
+
+    /// Reset the saved phases of all variables, cycling through the best phases, the
+    /// original ones, the best ones again, inverted ones, best ones and random ones.
+    fn rephase(&mut self) {
+        self.num_rephases += 1;
+        self.next_rephase = self.conflicts + self.opts.rephase_int as u64 * (self.num_rephases + 1);
+        for v in (0..self.next_var.idx()).map(Var::from_idx) {
+            self.polarity[v] = match self.num_rephases % 6 {
+                1 | 3 | 5 if self.best[v] != lbool::UNDEF => self.best[v] == lbool::TRUE,
+                1 | 3 | 5 => self.polarity[v],
+                2 => false,
+                4 => true,
+                _ => self.rand.f64() < 0.5,
+            };
+            self.target[v] = lbool::UNDEF;
+        }
+        self.target_len = 0;
+        self.best_len = 0;
+        debug!("rephase #{}", self.num_rephases);
+    }
+
+    /// Flag the learnt clause `cr` as used in conflict analysis, and lower its LBD if
+    /// it has fewer distinct levels now.
+    fn update_lbd(&mut self, cr: CRef) {
+        let c = self.ca.get_ref(cr);
+        if !self.opts.lbd_reduce || c.lbd() <= self.opts.tier1_lbd {
+            return;
+        }
+        let lbd = self.lbd_levels.lbd(&self.vars, c.lits()).min(c.lbd());
+        let mut c = self.ca.get_mut(cr);
+        c.set_lbd(lbd);
+        c.set_used(true);
+    }
+
+    /// Pick a literal to make a decision with
+    fn pick_branch_lit(&mut self) -> Lit {
+        let mut next = Var::UNDEF;
+
+        // Random decision:
+        if self.opts.random_var_freq > 0.0
+            && self.rand.f64() < self.opts.random_var_freq
+            && self.next_var.idx() > 0
+        {
+            next = Var::from_idx(self.rand.below(self.next_var.idx()));
+            if self.value(next) == lbool::UNDEF && self.decision[next] {
+                self.rnd_decisions += 1;
+            } else {
+                next = Var::UNDEF;
+            }
+        }
+
+        // Heuristic decision:
+        if next == Var::UNDEF {
+            let (vars, decision) = (&mut self.vars, &self.decision);
+            next = vars
+                .branch
+                .pick(|v| vars.ass[v] == lbool::UNDEF && decision[v]);
+        }
+
+        // Choose polarity based on different polarity modes (global or per-variable):
+        if next == Var::UNDEF {
+            Lit::UNDEF
+        } else if self.user_pol[next] != lbool::UNDEF {
+            Lit::new(next, self.user_pol[next] == lbool::TRUE)
+        } else if self.opts.pol_freq() > 0.0 && self.rand.f64() < self.opts.pol_freq() {
+            Lit::new(next, self.rand.f64() < 0.5)
+        } else if self.target_phase() && self.target[next] != lbool::UNDEF {
+            Lit::new(next, self.target[next] == lbool::TRUE)
+        } else {
+            Lit::new(next, self.polarity[next])
+        }
+    }
+
+    fn watches(&mut self) -> OccLists<'_, Lit, Watcher, WatcherDeleted<'_>> {
+        self.watches_data.promote(WatcherDeleted { ca: &self.ca })
+    }
+
+    fn init_watches(&mut self, v: Var) {
+        for lit in [Lit::new(v, false), Lit::new(v, true)] {
+            self.watches_data.init(lit);
+            self.bin_watches_data.init(lit);
+        }
+    }
+
+    fn new_var(&mut self, upol: lbool, dvar: bool) -> Var {
+        self.new_fresh_vars();
+        let v = self.free_vars.pop().unwrap_or_else(|| {
+            let v = self.next_var;
+            self.next_var = Var::from_idx(self.next_var.idx() + 1);
+            v
+        });
+        self.init_var(v, upol, dvar);
+        self.th_st.next_var = self.next_var.idx();
+        v
+    }
+
+    /// Create the variables of the fresh literals allocated by the theory through
+    /// `th_st`, at the indices it gave them, and forget their definitions.
+    fn new_fresh_vars(&mut self) {
+        let end = self.th_st.next_var + self.th_st.fresh;
+        debug_assert!(self.th_st.fresh == 0 || self.next_var.idx() == self.th_st.next_var);
+        while self.next_var.idx() < end {
+            let v = self.next_var;
+            self.next_var = Var::from_idx(v.idx() + 1);
+            self.init_var(v, lbool::UNDEF, true);
+            self.frozen[v] = true;
+        }
+        self.th_st.fresh = 0;
+        self.th_st.next_var = self.next_var.idx();
+        self.th_st.defs.clear();
+        self.th_st.def_lits.clear();
+    }
+
+    fn init_var(&mut self, v: Var, upol: lbool, dvar: bool) {
+        self.init_watches(v);
+        self.vars.ass.insert_default(v, lbool::UNDEF);
+        self.vars
+            .vardata
+            .insert_default(v, VarData::new(CRef::UNDEF, 0));
+        let score = if self.opts.rnd_init_act {
+            (self.rand.f64() * 0.00001) as f32
+        } else {
+            0.0
+        };
+        self.vars.branch.new_var(v, score);
+        self.seen.insert(v, Seen::UNDEF);
+        self.polarity.insert_default(v, false);
+        self.target.insert_default(v, lbool::UNDEF);
+        self.best.insert_default(v, lbool::UNDEF);
+        self.user_pol.insert_default(v, upol);
+        self.assump_priority.insert_default(v, 0);
+        self.core_hits.insert_default(v, 0);
+        self.decision.reserve_default(v);
+        self.frozen.insert_default(v, false);
+        self.eliminated.insert_default(v, false);
+        self.blocking.insert_default(v, false);
+        self.internal.insert_default(v, false);
+        let len = self.vars.trail.len();
+        if v.idx() as usize > len {
+            self.vars.trail.reserve(v.idx() as usize + 1 - len);
+        }
+        self.set_decision_var(v, dvar);
+    }
+
+    /// Analyze conflict and produce a reason clause.
+    ///
+    /// # Pre-conditions:
+    ///
+    /// - current decision level must be greater than root level.
+    /// - `orig` is false in the current trail
+    ///
+    /// # Post-conditions:
+    ///
+    /// - `btlevel` is returned.
+    /// - `out_learnt[0]` is the asserting literal at level `btlevel`.
+    /// - if `out_learnt.size() > 1` then `out_learnt[1]` has the greatest decision level of the
+    ///   rest of literals. There may be others from the same level though.
+    fn analyze<'a, Th: Theory>(
+        &mut self,
+        orig: Conflict<'_>,
+        learnts: &[CRef],
+        out_learnt: &'a mut Vec<Lit>,
+        th: &mut Th,
+    ) -> LearntClause<'a> {
+        out_learnt.clear();
+
+        debug!("analyze.start {:?}", orig);
+
+        // at what level did the conflict happen?
+        let conflict_level = match orig {
+            // with chronological backtracking, the conflict may be below the current level
+            Conflict::BCP(cr) if self.opts.chrono >= 0 => self.watch_conflict_level(cr),
+            Conflict::ThProp(lit) if self.opts.chrono >= 0 => {
+                let lits = explain_special(
+                    &self.natives,
+                    th,
+                    &mut self.th_st,
+                    None,
+                    (&mut self.th_stats, self.opts.theory_timing),
+                    lit,
+                    false,
+                );
+                let vars = &self.vars;
+                let lvl = lits.iter().map(|&q| vars.level(q.var())).max().unwrap_or(0);
+                if lvl == 0 {
+                    trace!("analyze: conflict level 0, learn empty clause");
+                    return LearntClause {
+                        clause: &[],
+                        backtrack_lvl: 0,
+                        conflict_lvl: 0,
+                        lbd: 0,
+                    };
+                }
+                lvl
+            }
+            Conflict::BCP(_) | Conflict::ThProp(_) => {
+                self.decision_level() as i32 // current level
+            }
+            Conflict::ThLemma { lits, .. } => {
+                // check it's a proper conflict clause
+                debug_assert!(lits.iter().all(|&p| self.value_lit(p) == lbool::FALSE));
+                debug_assert!(!lits.is_empty(), "theory lemma should have at least 1 lit");
+
+                let lvl = lits
+                    .iter()
+                    .map(|&lit| self.level_lit(lit))
+                    .max()
+                    .unwrap_or(0);
+
+                if lits.len() == 1 {
+                    // unit clause: learn the clause itself at level 0
+                    trace!("analyze: learn unit clause {:?} itself", lits);
+                    out_learnt.extend_from_slice(lits);
+                    return LearntClause {
+                        clause: &*out_learnt,
+                        backtrack_lvl: 0,
+                        conflict_lvl: lvl,
+                        lbd: 1,
+                    };
+                } else if lvl == 0 {
+                    // all at level 0: empty clause
+                    trace!("analyze: conflict level 0, learn empty clause");
+                    return LearntClause {
+                        clause: &[],
+                        backtrack_lvl: 0,
+                        conflict_lvl: 0,
+                        lbd: 0,
+                    };
+                }
+
+                lvl
+            }
+        };
+
+        let mut cur_clause = ResolveWith::Init(orig);
+        let mut path_c = 0;
+        #[allow(unused)]
+        let mut p = Lit::UNDEF;
+        // with LRAT, the strengthened clauses would need their own antecedents
+        let otfs = self.opts.otfs && !self.proof.lrat;
+        self.otfs.clear();
+
+        out_learnt.push(Lit::UNDEF); // leave room for the UIP
+
+        let mut index = self.vars.trail.len();
+
+        loop {
+            // obtain literals to resolve with, as well as a flag indicating
+            let lits = match cur_clause {
+                ResolveWith::Init(Conflict::ThLemma { lits, .. }) => lits,
+                ResolveWith::Init(Conflict::ThProp(lit)) => {
+                    // theory propagation, ask the theory to justify `lit` with Γ.
+                    // The initial conflict is `Γ => lit`, which is false in current trail.
+                    // `lit` is false, its explanation is not cached.
+                    let lits = explain_special(
+                        &self.natives,
+                        th,
+                        &mut self.th_st,
+                        None,
+                        (&mut self.th_stats, self.opts.theory_timing),
+                        lit,
+                        false,
+                    );
+                    debug_assert_eq!(lits[0], lit);
+                    debug_assert!({
+                        let vars = &self.vars;
+                        lits.iter().all(|&q| vars.value_lit(q) == lbool::FALSE)
+                    });
+                    lits
+                }
+                ResolveWith::Init(Conflict::BCP(cr)) => {
+                    // bump activity if `cr` is a learnt clause
+                    let mut c = self.ca.get_ref(cr);
+                    if c.learnt() {
+                        self.cla_bump_activity(learnts, cr);
+                        self.update_lbd(cr);
+                        c = self.ca.get_ref(cr); // re-borrow
+                    }
+
+                    c.lits()
+                }
+                ResolveWith::Resolve(lit, cr) if cr == CRef::SPECIAL => {
+                    // theory propagation, ask the theory to justify `lit`
+                    let lits = explain_special(
+                        &self.natives,
+                        th,
+                        &mut self.th_st,
+                        Some(&mut self.th_cache).filter(|_| self.opts.cache_explanations),
+                        (&mut self.th_stats, self.opts.theory_timing),
+                        lit,
+                        false,
+                    );
+                    debug_assert_eq!(lits[0], lit);
+                    let lits = &lits[1..];
+                    debug_assert!({
+                        let vars = &self.vars;
+                        lits.iter().all(|&q| vars.value_lit(q) == lbool::FALSE)
+                    });
+                    lits
+                }
+                ResolveWith::Resolve(_lit, cr) if cr == CRef::UNDEF => {
+                    // should have `path_c==0`
+                    panic!(
+                        "analyze: reached a decision literal {:?}, path_c={}",
+                        _lit, path_c
+                    );
+                }
+                ResolveWith::Resolve(lit, cr) => {
+                    // bump activity if `cr` is a learnt clause
+                    let mut c = self.ca.get_ref(cr);
+                    if c.learnt() {
+                        self.cla_bump_activity(learnts, cr);
+                        self.update_lbd(cr);
+                        c = self.ca.get_ref(cr); // re-borrow
+                    }
+
+                    let lits = c.lits();
+
+                    // we are resolving the initial conflict against `c`,
+                    // which should be the clause which propagated `p`,
+                    // so we skip its first literal (`p`) since
+                    // it can't appear in the learnt clause
+                    debug_assert_eq!(lit.var(), lits[0].var());
+                    &lits[1..]
+                }
+            };
+            trace!(
+                "analyze.resolve-with {:?} ((p: {:?}, path_c: {}, from {:?})",
+                lits,
+                p,
+                path_c,
+                cur_clause
+            );
+
+            // number of literals of the clause, without the pivot, in the resolvent
+            let mut num_lits = 0;
+            for &q in lits {
+                let lvl = self.vars.level(q.var());
+                assert!(lvl <= conflict_level);
+                num_lits += (lvl > 0) as usize;
+                if !self.seen[q.var()].is_seen() && lvl > 0 {
+                    self.vars.branch.bump(q.var());
+                    self.seen[q.var()] = Seen::SOURCE;
+                    if lvl == conflict_level {
+                        // at conflict level: need to eliminate this lit by resolution
+                        path_c += 1;
+                    } else {
+                        out_learnt.push(q); // part of the learnt clause
+                    }
+                } else if self.seen[q.var()] == Seen::REMOVABLE {
+                    // the resolution goes back "up" the trail to `q`, which was
+                    // resolved again. This is wrong.
+                    panic!(
+                        "possible cycle in conflict graph between {:?} and {:?}",
+                        p, q
+                    );
+                }
+            }
+            if let ResolveWith::Resolve(lit, cr) = cur_clause {
+                // the resolvent subsumes the clause if it has as many literals, keep it
+                // for `strengthen_otfs` if it has two literals of the conflict level
+                if otfs
+                    && cr != CRef::SPECIAL
+                    && path_c >= 2
+                    && out_learnt.len() - 1 + path_c as usize == num_lits
+                {
+                    self.otfs.push((cr, lit));
+                }
+            }
+            // Select next literal in the trail to look at:
+            // (literals of lower levels can be after those of `conflict_level`, if they
+            // were kept by chronological backtracking)
+            while {
+                let x = self.vars.trail[index - 1].var();
+                !self.seen[x].is_seen() || self.vars.level(x) != conflict_level
+            } {
+                index -= 1;
+            }
+
+            p = self.vars.trail[index - 1];
+            index -= 1;
+            cur_clause = ResolveWith::Resolve(p, self.vars.reason(p.var()));
+            self.seen[p.var()] = Seen::REMOVABLE;
+            path_c -= 1;
+
+            if path_c <= 0 {
+                break;
+            }
+        }
+
+        // cleanup literals flagged `REMOVABLE`
+        index = self.vars.trail.len() - 1;
+        loop {
+            let q = self.vars.trail[index];
+            if self.seen[q.var()] == Seen::REMOVABLE {
+                self.seen[q.var()] = Seen::UNDEF;
+            }
+            if q == p {
+                break;
+            }
+            // avoid overflow by decreasing index only if we keep looping (#7)
+            index -= 1;
+        }
+
+        // check that the first literal is a decision lit at conflict_level
+        assert_ne!(p, Lit::UNDEF);
+        debug_assert!(self.value_lit(p) == lbool::TRUE);
+        out_learnt[0] = !p;
+        // FIXME debug_assert_eq!(self.vars.reason(p.var()), CRef::UNDEF);
+
+        /* NOTE:
+        // invariant on `seen`: all literals of `out_learnt` but the first
+        // are marked; nothing else is marked.
+        assert!(self.vars.trail.iter().all(|p|
+            !self.seen[p.var()].is_seen() ||
+            (self.vars.level(p.var()) < conflict_level && out_learnt.iter().any(|q| q.var()==p.var()))
+        ));
+        assert!(out_learnt[1..].iter().all(|p| self.seen[p.var()].is_seen()));
+        assert!({
+            let mut c = out_learnt.clone();
+            c.sort_unstable();
+            c.dedup();
+            c.len() == out_learnt.len()
+        });
+        */
+
+        trace!("analyze-learnt: {:?} (before minimization)", &out_learnt);
+        self.max_literals += out_learnt.len() as u64;
+
+        self.minimize_conflict(out_learnt, th);
+        self.seen.clear();
+
+        let mut lbd = self.lbd_levels.lbd(&self.vars, out_learnt);
+        let len = out_learnt.len();
+        // with LRAT, the binary clauses would be missing from the antecedents
+        if lbd <= self.opts.binary_minimize_lbd
+            && len <= BINARY_MINIMIZE_MAX_LEN
+            && !self.proof.lrat
+            && self.minimize_binary(out_learnt)
+        {
+            self.tot_literals -= (len - out_learnt.len()) as u64;
+            lbd = self.lbd_levels.lbd(&self.vars, out_learnt);
+        }
+
+        // Find correct backtrack level:
+        let btlevel = if out_learnt.len() == 1 {
+            0
+        } else {
+            let mut max_i = 1;
+            let mut max_level = self.level(out_learnt[max_i].var());
+            // Find the first literal assigned at the next-highest level:
+            for (i, lit) in out_learnt.iter().enumerate().skip(2) {
+                let level = self.level(lit.var());
+                if level > max_level {
+                    max_i = i;
+                    max_level = level;
+                }
+            }
+            // Swap-in this literal at index 1:
+            out_learnt.swap(max_i, 1);
+            self.level_lit(out_learnt[1])
+        };
+
+        debug_assert!(out_learnt
+            .iter()
+            .all(|&l| self.value_lit(l) == lbool::FALSE));
+        match orig {
+            Conflict::ThLemma { lits, add } => {
+                // add original lemma only if it's not the same as the clause
+                if add && lits != out_learnt.as_slice() {
+                    self.th_st.add_theory_lemma(lits);
+                }
+            }
+            Conflict::BCP(cr) if self.proof.lrat => self.lrat_chain(cr, out_learnt),
+            Conflict::ThProp(_) | Conflict::BCP(_) => {}
+        };
+        LearntClause {
+            backtrack_lvl: btlevel,
+            conflict_lvl: conflict_level,
+            lbd,
+            clause: out_learnt,
+        }
+    }
+
+    /// An abstraction of the level of a variable
+    #[inline]
+    fn abstract_level(&self, v: Var) -> u32 {
+        1 << (self.level(v) & 31)
+    }
+
+    fn minimize_conflict<Th: Theory>(&mut self, out_learnt: &mut Vec<Lit>, th: &mut Th) {
+        // Simplify conflict clause:
+        self.analyze_toclear.clear();
+        self.analyze_toclear.extend_from_slice(out_learnt);
+        let new_size = if self.opts.ccmin_mode == 2 {
+            let mut abstract_levels = 0;
+            for a in out_learnt[1..].iter() {
+                abstract_levels |= self.abstract_level(a.var())
+            }
+
+            let mut j = 1;
+            for i in 1..out_learnt.len() {
+                let lit = out_learnt[i];
+                // can eliminate `lit` only if it's redundant *and* not a decision
+                if self.reason(lit.var()) == CRef::UNDEF
+                    || !self.lit_redundant(lit, abstract_levels, th)
+                {
+                    out_learnt[j] = lit;
+                    j += 1;
+                }
+            }
+            j
+        } else if self.opts.ccmin_mode == 1 {
+            let mut j = 1;
+            for i in 1..out_learnt.len() {
+                let lit = out_learnt[i];
+                let x = lit.var();
+                let reason = self.reason(x);
+
+                let mut retain = true;
+                if reason == CRef::UNDEF || reason == CRef::SPECIAL {
+                    debug_assert!(self.level(x) > 0);
+                    retain = true;
+                } else {
+                    let c = self.ca.get_ref(reason);
+                    for k in 1..c.size() {
+                        let v = c[k].var();
+                        if !self.seen[v].is_seen() && self.level(v) > 0 {
+                            retain = true;
+                            break;
+                        }
+                    }
+                }
+                if retain {
+                    out_learnt[j] = lit;
+                    j += 1;
+                }
+            }
+            j
+        } else {
+            out_learnt.len()
+        };
+
+        self.tot_literals += new_size as u64;
+        debug_assert!(new_size <= out_learnt.len());
+        out_learnt.truncate(new_size);
+    }
+
+    /// Remove the literals `lit` of `out_learnt` such that `out_learnt[0] | !lit` is a binary
+    /// clause, as they are implied by the negation of the asserting literal (as in Glucose).
+    ///
+    /// Returns `true` if some literals were removed.
+    fn minimize_binary(&mut self, out_learnt: &mut Vec<Lit>) -> bool {
+        for &lit in &out_learnt[1..] {
+            self.seen[lit.var()] = Seen::SOURCE;
+        }
+        let p = !out_learnt[0];
+        let mut removed = 0;
+        let ws = self
+            .bin_watches_data
+            .lookup_mut_pred(p, &WatcherDeleted { ca: &self.ca });
+        for w in ws.iter() {
+            let imp = w.blocker;
+            // `!imp` is in `out_learnt`, as its literals are all false
+            if self.seen[imp.var()] == Seen::SOURCE && self.vars.value_lit(imp) == lbool::TRUE {
+                self.seen[imp.var()] = Seen::REMOVABLE;
+                removed += 1;
+            }
+        }
+        let seen = &mut self.seen;
+        out_learnt.retain(|lit| mem::take(&mut seen[lit.var()]) != Seen::REMOVABLE);
+        removed > 0
+    }
+
+    /// Specialized analysis procedure to express the final conflict in terms of assumptions.
+    /// Calculates the set of assumptions that led to the assignment of `!p`, where `p`
+    /// is an assumption, and stores it (along with `p`) in `out_core`.
+    fn analyze_final<Th: Theory>(&mut self, th: &mut Th, p: Lit, out_core: &mut LSet) {
+        out_core.clear();
+        out_core.insert(p);
+        debug!("analyze_final lit={:?}", p);
+        let p = !p; // true in the trail
+
+        if self.decision_level() == 0 {
+            return; // no assumptions
+        }
+        let confl = self.reason(p.var());
+
+        self.seen[p.var()] = Seen::SOURCE;
+
+        // FIXME: use a stack here too, to be more robust wrt. theory propagations
+        for &lit in self.vars.trail[self.vars.trail_lim[0] as usize..]
+            .iter()
+            .rev()
+        {
+            let x = lit.var();
+            if self.seen[x].is_seen() {
+                let reason = self.reason(x);
+                if reason == CRef::UNDEF {
+                    // decision, hence an assumption
+                    debug_assert!(self.level(x) > 0);
+                    out_core.insert(lit);
+                } else if reason == CRef::SPECIAL {
+                    // resolution with propagation reason
+                    let lits = explain_special(
+                        &self.natives,
+                        th,
+                        &mut self.th_st,
+                        None,
+                        (&mut self.th_stats, self.opts.theory_timing),
+                        lit,
+                        true,
+                    );
+                    debug_assert_eq!(lits[0], lit);
+                    for &p in &lits[1..] {
+                        if self.vars.level(p.var()) > 0 {
+                            self.seen[p.var()] = Seen::SOURCE;
+                        }
+                    }
+                } else {
+                    let c = self.ca.get_mut(reason);
+                    for j in 1..c.size() {
+                        if self.vars.level(c[j].var()) > 0 {
+                            self.seen[c[j].var()] = Seen::SOURCE;
+                        }
+                    }
+                }
+                self.seen[x] = Seen::UNDEF;
+            }
+        }
+
+        self.seen[p.var()] = Seen::UNDEF;
+        debug_assert!(self.seen.iter().all(|(_, &s)| s == Seen::UNDEF));
+
+        if self.proof.lrat {
+            if self.level(p.var()) == 0 {
+                let h = self.unit_id(p.var());
+                self.proof.hints.push(h);
+            } else if confl != CRef::UNDEF && confl != CRef::SPECIAL {
+                // `confl` propagated `p`, it is falsified by the assumptions
+                self.lrat_chain(confl, out_core);
+            }
+        }
+    }
+
+    /// Check if `p` can be removed from a conflict clause `C`.
+    ///
+    /// It can be removed from `C` if it is propagation-implied
+    /// by literals of level 0 exclusively or if `C x p.reason` subsumes `C`.
+    ///
+    /// The theory propagations are resolved with their explanations if they are cached,
+    /// see [`SolverOpts::cache_explanations`].
+    fn lit_redundant<Th: Theory>(&mut self, p: Lit, abstract_levels: u32, th: &mut Th) -> bool {
+        self.minimize_stack.clear();
+        self.minimize_stack.push(p);
+
+        let top = self.analyze_toclear.len();
+
+        while !self.minimize_stack.is_empty() {
+            let q = *self.minimize_stack.last().unwrap();
+            let cr = self.reason(q.var());
+            debug_assert_ne!(cr, CRef::UNDEF);
+            self.minimize_stack.pop();
+
+            // special case: theory propagation
+            let lits = if cr == CRef::SPECIAL {
+                if self.vars.level(q.var()) == 0 {
+                    continue; // level 0, just continue
+                } else if !self.opts.cache_explanations || self.proof.lrat {
+                    // we just bail out here, even though the theory propagation
+                    // could be caused by propagations that ultimately
+                    // come from level 0
+
+                    for a in self.analyze_toclear[top..].iter() {
+                        self.seen[a.var()] = Seen::UNDEF;
+                    }
+                    self.analyze_toclear.resize(top, Lit::UNDEF);
+                    return false;
+                }
+                // resolve with the explanation of `!q`, kept in the cache for the next
+                // conflicts since it is below the current level
+                match self.natives.explain(!q) {
+                    Some(lits) => lits,
+                    None => {
+                        explain_special(
+                            &self.natives,
+                            th,
+                            &mut self.th_st,
+                            Some(&mut self.th_cache),
+                            (&mut self.th_stats, self.opts.theory_timing),
+                            !q,
+                            false,
+                        );
+                        self.th_cache.get(!q)
+                    }
+                }
+            } else {
+                self.ca.get_ref(cr).lits()
+            };
+            // `q` comes from some propagation with `lits`, check if these lits can
+            // also be eliminated or are already in the learnt clause
+            for &l in lits[1..].iter() {
+                // Variable at level 0 or previously removable: just skip
+                if self.vars.level(l.var()) == 0 || self.seen[l.var()] == Seen::SOURCE {
+                    continue;
+                }
+
+                if self.reason(l.var()) != CRef::UNDEF
+                    && (self.abstract_level(l.var()) & abstract_levels) != 0
+                {
+                    // keep this literal.
+                    // NOTE: if the level of `l` isn't in `abstract_levels`, it
+                    // means it comes from propagations at a decision level
+                    // unrelated to the learnt clause, and therefore is
+                    // somehow implied by an unrelated decision, so there's no
+                    // chance to eliminate `l` via resolutions from the learnt clause.
+                    self.seen[l.var()] = Seen::SOURCE;
+                    self.minimize_stack.push(l);
+                    self.analyze_toclear.push(l);
+                } else {
+                    // cannot remove `l`, cancel
+                    for a in self.analyze_toclear[top..].iter() {
+                        self.seen[a.var()] = Seen::UNDEF;
+                    }
+                    self.analyze_toclear.resize(top, Lit::UNDEF);
+                    return false;
+                }
+            }
+        }
 
-        let mut cur_clause = ResolveWith::Init(orig);
-        let mut path_c = 0;
-        #[allow(unused)]
-        let mut p = Lit::UNDEF;
+        true
+    }
 
-        out_learnt.push(Lit::UNDEF); // leave room for the UIP
+    /// Propagates all enqueued facts.
+    ///
+    /// If a conflict arises, the conflicting clause and literal is returned,
+    /// otherwise `None`.
+    ///
+    /// # Post-conditions:
+    ///
+    /// - the propagation queue is empty, even if there was a conflict.
+    fn propagate(&mut self) -> Option<CRef> {
+        let mut confl = None;
+        let mut num_props: u32 = 0;
+        let mut ticks: u64 = 0;
 
-        let mut index = self.vars.trail.len();
+        while (self.qhead as usize) < self.vars.trail.len() {
+            // `p` is the next enqueued fact to propagate.
+            let p = self.vars.trail[self.qhead as usize];
 
-        loop {
-            // obtain literals to resolve with, as well as a flag indicating
-            let lits = match cur_clause {
-                ResolveWith::Init(Conflict::ThLemma { lits, .. }) => lits,
-                ResolveWith::Init(Conflict::ThProp(lit)) => {
-                    // theory propagation, ask the theory to justify `lit` with Γ.
-                    // The initial conflict is `Γ => lit`, which is false in current trail.
-                    let lits = th.explain_propagation_clause(lit, &mut self.th_st);
-                    debug_assert_eq!(lits[0], lit);
-                    debug_assert!({
-                        let vars = &self.vars;
-                        lits.iter().all(|&q| vars.value_lit(q) == lbool::FALSE)
-                    });
-                    lits
-                }
-                ResolveWith::Init(Conflict::BCP(cr)) => {
-                    // bump activity if `cr` is a learnt clause
-                    let mut c = self.ca.get_ref(cr);
-                    if c.learnt() {
-                        self.cla_bump_activity(learnts, cr);
-                        c = self.ca.get_ref(cr); // re-borrow
+            // eprintln!("propagating trail[{}] = {:?}", self.qhead, p);
+            self.qhead += 1;
+            num_props += 1;
+
+            // binary clauses first, they are only visited when they propagate
+            let bin = self
+                .bin_watches_data
+                .lookup_mut_pred(p, &WatcherDeleted { ca: &self.ca });
+            let bin_len = bin.len();
+            ticks += bin_len as u64;
+            for k in 0..bin_len {
+                let w = self.bin_watches_data[p][k];
+                let imp = w.blocker;
+                let value = self.vars.value_lit(imp);
+                if value == lbool::FALSE {
+                    confl = Some(w.cref);
+                    self.qhead = self.vars.trail.len() as i32;
+                    break;
+                } else if value == lbool::UNDEF {
+                    // the implied literal comes first in a reason
+                    let mut c = self.ca.get_mut(w.cref);
+                    if c[0] != imp {
+                        c[1] = c[0];
+                        c[0] = imp;
                     }
+                    let level = if self.opts.chrono >= 0 {
+                        self.vars.level(p.var())
+                    } else {
+                        self.vars.decision_level() as i32
+                    };
+                    self.vars.unchecked_enqueue_at(imp, w.cref, level);
+                }
+            }
+            if confl.is_some() {
+                break;
+            }
 
-                    c.lits()
+            // let ws = self.watches().lookup_mut(p);
+            let ws = self
+                .watches_data
+                .lookup_mut_pred(p, &WatcherDeleted { ca: &self.ca });
+            // eprintln!("watcher of {:?} = {:?}", p, ws);
+            let mut i: usize = 0;
+            let mut j: usize = 0;
+            let end: usize = ws.len();
+            ticks += 1 + end as u64;
+            'clauses: while i < end {
+                let ws = &mut self.watches_data[p];
+                // Try to avoid inspecting the clause:
+                let blocker = ws[i].blocker;
+                if self.vars.value_lit(blocker) == lbool::TRUE {
+                    ws[j] = ws[i];
+                    j += 1;
+                    i += 1;
+                    continue;
                 }
-                ResolveWith::Resolve(lit, cr) if cr == CRef::SPECIAL => {
-                    // theory propagation, ask the theory to justify `lit`
-                    let lits = th.explain_propagation_clause(lit, &mut self.th_st);
-                    debug_assert_eq!(lits[0], lit);
-                    let lits = &lits[1..];
-                    debug_assert!(lits.iter().all(|&q| self.value_lit(q) == lbool::FALSE));
-                    lits
+
+                // Make sure the false literal is data[1]:
+                let cr = ws[i].cref;
+                let mut c = self.ca.get_mut(cr);
+                let false_lit = !p;
+                if c[0] == false_lit {
+                    c[0] = c[1];
+                    c[1] = false_lit;
                 }
-                ResolveWith::Resolve(_lit, cr) if cr == CRef::UNDEF => {
-                    // should have `path_c==0`
-                    panic!(
-                        "analyze: reached a decision literal {:?}, path_c={}",
-                        _lit, path_c
-                    );
+                debug_assert_eq!(c[1], false_lit);
+                i += 1;
+
+                // If 0th watch is true, then clause is already satisfied.
+                let first = c[0];
+                let w = Watcher::new(cr, first);
+                if first != blocker && self.vars.value_lit(first) == lbool::TRUE {
+                    ws[j] = w;
+                    j += 1;
+                    continue;
                 }
-                ResolveWith::Resolve(lit, cr) => {
-                    // bump activity if `cr` is a learnt clause
-                    let mut c = self.ca.get_ref(cr);
-                    if c.learnt() {
-                        self.cla_bump_activity(learnts, cr);
-                        c = self.ca.get_ref(cr); // re-borrow
-                    }
 
-                    let lits = c.lits();
+                // Look for new watch:
+                for k in 2..c.size() {
+                    if self.vars.value_lit(c[k]) != lbool::FALSE {
+                        c[1] = c[k];
+                        c[k] = false_lit;
 
-                    // we are resolving the initial conflict against `c`,
-                    // which should be the clause which propagated `p`,
-                    // so we skip its first literal (`p`) since
-                    // it can't appear in the learnt clause
-                    debug_assert_eq!(lit.var(), lits[0].var());
-                    &lits[1..]
+                        // self.watches()[!c[1]].push(w);
+                        debug_assert_ne!(!c[1], p);
+                        self.watches_data[!c[1]].push(w);
+                        continue 'clauses;
+                    }
                 }
-            };
-            trace!(
-                "analyze.resolve-with {:?} ((p: {:?}, path_c: {}, from {:?})",
-                lits,
-                p,
-                path_c,
-                cur_clause
-            );
+                let ws = &mut self.watches_data[p];
 
-            for &q in lits {
-                let lvl = self.vars.level(q.var());
-                assert!(lvl <= conflict_level);
-                if !self.seen[q.var()].is_seen() && lvl > 0 {
-                    self.vars.var_bump_activity(q.var());
-                    self.seen[q.var()] = Seen::SOURCE;
-                    if lvl == conflict_level {
-                        // at conflict level: need to eliminate this lit by resolution
-                        path_c += 1;
-                    } else {
-                        out_learnt.push(q); // part of the learnt clause
+                // Did not find watch -- clause is unit under assignment:
+                ws[j] = w;
+                j += 1;
+                if self.vars.value_lit(first) == lbool::FALSE {
+                    // eprintln!("propagation: conflict at {:?}", first);
+                    confl = Some(cr);
+                    self.qhead = self.vars.trail.len() as i32;
+                    // Copy the remaining watches:
+                    while i < end {
+                        ws[j] = ws[i];
+                        j += 1;
+                        i += 1;
                     }
-                } else if self.seen[q.var()] == Seen::REMOVABLE {
-                    // the resolution goes back "up" the trail to `q`, which was
-                    // resolved again. This is wrong.
-                    panic!(
-                        "possible cycle in conflict graph between {:?} and {:?}",
-                        p, q
-                    );
+                } else {
+                    // eprintln!("propagation: got {:?}", first);
+                    let mut level = self.vars.decision_level() as i32;
+                    if self.opts.chrono >= 0 {
+                        // the trail may be out of order: `first` is implied at the highest
+                        // level of the other literals, and one of them must be watched so
+                        // that they are unassigned together
+                        let mut max_k = 1;
+                        level = self.vars.level(false_lit.var());
+                        for k in 2..c.size() {
+                            if level == self.vars.decision_level() as i32 {
+                                break;
+                            }
+                            let lvl = self.vars.level(c[k].var());
+                            if lvl > level {
+                                max_k = k;
+                                level = lvl;
+                            }
+                        }
+                        if max_k != 1 {
+                            c[1] = c[max_k];
+                            c[max_k] = false_lit;
+                            j -= 1;
+                            self.watches_data[!c[1]].push(w);
+                        }
+                    }
+                    self.vars.unchecked_enqueue_at(first, cr, level);
                 }
             }
-            // Select next literal in the trail to look at:
-            while !self.seen[self.vars.trail[index - 1].var()].is_seen() {
-                debug_assert!(self.vars.level(self.vars.trail[index - 1].var()) >= conflict_level);
-                index -= 1;
+            let ws = &mut self.watches_data[p];
+            let dummy = Watcher::DUMMY;
+            ws.resize(j, dummy);
+        }
+        self.propagations += num_props as u64;
+        self.ticks += ticks;
+        self.simp_db_props -= num_props as i64;
+
+        confl
+    }
+
+    /// Sort literals of `clause` so that unassigned literals are first,
+    /// followed by literals in decreasing assignment level
+    fn sort_clause_lits(&self, clause: &mut [Lit]) {
+        // sort clause to put unassigned/high level lits first
+        clause.sort_unstable_by(|&lit1, &lit2| {
+            let has_val1 = self.value_lit(lit1) != lbool::UNDEF;
+            let has_val2 = self.value_lit(lit2) != lbool::UNDEF;
+
+            // unassigned variables come first
+            if has_val1 && !has_val2 {
+                return cmp::Ordering::Greater;
+            }
+            if !has_val1 && has_val2 {
+                return cmp::Ordering::Less;
             }
 
-            p = self.vars.trail[index - 1];
-            index -= 1;
-            cur_clause = ResolveWith::Resolve(p, self.vars.reason(p.var()));
-            self.seen[p.var()] = Seen::REMOVABLE;
-            path_c -= 1;
+            let lvl1 = self.level_lit(lit1);
+            let lvl2 = self.level_lit(lit2);
+            if lvl1 != lvl2 {
+                lvl2.cmp(&lvl1) // higher level come first
+            } else {
+                lit1.cmp(&lit2) // otherwise default comparison
+            }
+        });
 
-            if path_c <= 0 {
-                break;
+        // check that the first literal is a proper watch
+        debug_assert!(
+            self.value_lit(clause[0]) == lbool::UNDEF || {
+                let lvl0 = self.level_lit(clause[0]);
+                clause[1..].iter().all(|&lit2| self.level_lit(lit2) <= lvl0)
+            }
+        );
+    }
+
+    /// Move to the given clause allocator, where clause indices might differ
+    fn reloc_all(
+        &mut self,
+        learnts: &mut Vec<CRef>,
+        clauses: &mut Vec<CRef>,
+        to: &mut ClauseAllocator,
+    ) {
+        macro_rules! is_removed {
+            ($ca:expr, $cr:expr) => {
+                $ca.get_ref($cr).mark() == 1
+            };
+        }
+        // All watchers, in the order propagation visits them so that the clauses it reads
+        // together end up close: the lists of the literals of the trail first, as they are
+        // likely to be assigned again, and the long clauses before the binary ones, which
+        // are only read when they propagate.
+        self.watches().clean_all();
+        self.bin_watches_data
+            .clean_all_pred(&WatcherDeleted { ca: &self.ca });
+        let mut order = self.vars.trail.clone();
+        for v in (0..self.num_vars()).map(Var::from_idx) {
+            for p in [Lit::new(v, false), Lit::new(v, true)] {
+                if self.value_lit(p) != lbool::TRUE {
+                    order.push(p);
+                }
+            }
+        }
+        for &p in &order {
+            for watch in &mut self.watches_data[p] {
+                self.ca.reloc(&mut watch.cref, to);
+            }
+        }
+        for &p in &order {
+            for watch in &mut self.bin_watches_data[p] {
+                self.ca.reloc(&mut watch.cref, to);
+            }
+        }
+
+        // All reasons:
+        for &lit in &self.vars.trail {
+            let v = lit.var();
+
+            // Note: it is not safe to call `locked()` on a relocated clause. This is why we keep
+            // `dangling` reasons here. It is safe and does not hurt.
+            let reason = self.reason(v);
+            if reason != CRef::UNDEF && reason != CRef::SPECIAL {
+                let cond = {
+                    let c = self.ca.get_ref(reason);
+                    c.reloced() || self.locked(c)
+                };
+                if cond {
+                    debug_assert!(!is_removed!(self.ca, reason));
+                    self.ca.reloc(&mut self.vars.vardata[v].reason, to);
+                }
             }
         }
 
-        // cleanup literals flagged `REMOVABLE`
-        index = self.vars.trail.len() - 1;
-        loop {
-            let q = self.vars.trail[index];
-            if self.seen[q.var()] == Seen::REMOVABLE {
-                self.seen[q.var()] = Seen::UNDEF;
-            }
-            if q == p {
-                break;
+        // All learnt:
+        {
+            let mut j = 0;
+            for i in 0..learnts.len() {
+                let mut cr = learnts[i];
+                if !is_removed!(self.ca, cr) {
+                    self.ca.reloc(&mut cr, to);
+                    learnts[j] = cr;
+                    j += 1;
+                }
             }
-            // avoid overflow by decreasing index only if we keep looping (#7)
-            index -= 1;
+            learnts.resize(j, CRef::UNDEF);
         }
 
-        // check that the first literal is a decision lit at conflict_level
-        assert_ne!(p, Lit::UNDEF);
-        debug_assert!(self.value_lit(p) == lbool::TRUE);
-        out_learnt[0] = !p;
-        // FIXME debug_assert_eq!(self.vars.reason(p.var()), CRef::UNDEF);
-
-        /* NOTE:
-        // invariant on `seen`: all literals of `out_learnt` but the first
-        // are marked; nothing else is marked.
-        assert!(self.vars.trail.iter().all(|p|
-            !self.seen[p.var()].is_seen() ||
-            (self.vars.level(p.var()) < conflict_level && out_learnt.iter().any(|q| q.var()==p.var()))
-        ));
-        assert!(out_learnt[1..].iter().all(|p| self.seen[p.var()].is_seen()));
-        assert!({
-            let mut c = out_learnt.clone();
-            c.sort_unstable();
-            c.dedup();
-            c.len() == out_learnt.len()
-        });
-        */
-
-        trace!("analyze-learnt: {:?} (before minimization)", &out_learnt);
-        self.max_literals += out_learnt.len() as u64;
-
-        self.minimize_conflict(out_learnt);
-
-        // Find correct backtrack level:
-        let btlevel = if out_learnt.len() == 1 {
-            0
-        } else {
-            let mut max_i = 1;
-            let mut max_level = self.level(out_learnt[max_i].var());
-            // Find the first literal assigned at the next-highest level:
-            for i in 2..out_learnt.len() {
-                let level = self.level(out_learnt[i].var());
-                if level > max_level {
-                    max_i = i;
-                    max_level = level;
+        // All original:
+        {
+            let mut j = 0;
+            for i in 0..clauses.len() {
+                let mut cr = clauses[i];
+                if !is_removed!(self.ca, cr) {
+                    self.ca.reloc(&mut cr, to);
+                    clauses[j] = cr;
+                    j += 1;
                 }
             }
-            // Swap-in this literal at index 1:
-            out_learnt.swap(max_i, 1);
-            self.level_lit(out_learnt[1])
-        };
-
-        for &lit in &self.analyze_toclear {
-            self.seen[lit.var()] = Seen::UNDEF; // (`seen[]` is now cleared)
+            clauses.resize(j, CRef::UNDEF);
         }
-        debug_assert!(out_learnt
-            .iter()
-            .all(|&l| self.value_lit(l) == lbool::FALSE));
-        match orig {
-            Conflict::ThLemma { lits, add } => {
-                // add original lemma only if it's not the same as the clause
-                if add && lits != out_learnt.as_slice() {
-                    self.th_st.add_theory_lemma(lits);
-                }
-            }
-            Conflict::ThProp(_) | Conflict::BCP(_) => {}
+    }
+
+    /// Attach a clause to watcher lists
+    fn attach_clause(&mut self, cr: CRef) {
+        let (c0, c1, learnt, size) = {
+            let c = self.ca.get_ref(cr);
+            debug_assert!(c.size() > 1);
+            (c[0], c[1], c.learnt(), c.size())
         };
-        LearntClause {
-            backtrack_lvl: btlevel,
-            clause: out_learnt,
+        let watches = if size == 2 {
+            &mut self.bin_watches_data
+        } else {
+            &mut self.watches_data
+        };
+        watches[!c0].push(Watcher::new(cr, c1));
+        watches[!c1].push(Watcher::new(cr, c0));
+        if learnt {
+            self.num_learnts += 1;
+            self.learnts_literals += size as u64;
+        } else {
+            self.num_clauses += 1;
+            self.clauses_literals += size as u64;
         }
     }
 
-    /// An abstraction of the level of a variable
-    #[inline]
-    fn abstract_level(&self, v: Var) -> u32 {
-        1 << (self.level(v) & 31)
+    /// Revert to the state at given level (keeping all assignment at `level` but not beyond).
+    /// Call `th.on_assign` on the literals of the trail it was not notified of yet.
+    fn notify_assignments<Th: Theory>(&mut self, th: &mut Th) {
+        let timer = Timer::start(self.opts.theory_timing);
+        for &lit in &self.vars.trail[self.th_notified..] {
+            let x = lit.var();
+            th.on_assign(
+                lit,
+                self.vars.reason(x) == CRef::UNDEF && self.vars.level(x) > 0,
+            );
+        }
+        self.th_notified = self.vars.trail.len();
+        timer.stop(&mut self.th_stats.assign_time);
     }
 
-    fn minimize_conflict(&mut self, out_learnt: &mut Vec<Lit>) {
-        // Simplify conflict clause:
-        self.analyze_toclear.clear();
-        self.analyze_toclear.extend_from_slice(out_learnt);
-        let new_size = if self.opts.ccmin_mode == 2 {
-            let mut abstract_levels = 0;
-            for a in out_learnt[1..].iter() {
-                abstract_levels |= self.abstract_level(a.var())
+    fn cancel_until(&mut self, level: u32) {
+        debug_assert!(self.decision_level() > level);
+        self.th_queue.clear(); // implied by the assignments that are undone
+        let trail_lim_last = *self.vars.trail_lim.last().expect("trail_lim is empty") as usize;
+        let trail_lim_level = self.vars.trail_lim[level as usize] as usize;
+        let mut kept = 0;
+        for c in trail_lim_level..self.vars.trail.len() {
+            let x = self.vars.trail[c].var();
+            if self.vars.level(x) <= level as i32 {
+                kept += 1; // assigned out of order, by chronological backtracking
+                continue;
             }
-
-            let mut j = 1;
-            for i in 1..out_learnt.len() {
-                let lit = out_learnt[i];
-                // can eliminate `lit` only if it's redundant *and* not a decision
-                if self.reason(lit.var()) == CRef::UNDEF
-                    || !self.lit_redundant(lit, abstract_levels)
-                {
-                    out_learnt[j] = lit;
+            self.vars.ass[x] = lbool::UNDEF;
+            self.vars.branch.on_unassign(x);
+            self.th_cache.remove(x);
+            if self.opts.phase_saving > 1 || (self.opts.phase_saving == 1 && c > trail_lim_last) {
+                self.polarity[x] = self.vars.trail[c].sign();
+            }
+            self.insert_var_order(x);
+        }
+        self.qhead = trail_lim_level as i32;
+        self.natives.backtrack(&self.vars.trail, trail_lim_level);
+        self.th_notified = self.th_notified.min(trail_lim_level);
+        let mut j = trail_lim_level;
+        if kept > 0 {
+            // keep the remaining literals in order, they are propagated again
+            for i in trail_lim_level..self.vars.trail.len() {
+                let lit = self.vars.trail[i];
+                if self.vars.value_lit(lit) != lbool::UNDEF {
+                    self.vars.trail[j] = lit;
                     j += 1;
                 }
             }
-            j
-        } else if self.opts.ccmin_mode == 1 {
-            let mut j = 1;
-            for i in 1..out_learnt.len() {
-                let lit = out_learnt[i];
-                let x = lit.var();
-                let reason = self.reason(x);
+        }
+        self.vars.trail.truncate(j);
+        if level == 0 {
+            self.th_cache.clear(); // the stale explanations too
+        }
+        // eprintln!("decision_level {} -> {}", self.trail_lim.len(), level);
+        self.vars.trail_lim.truncate(level as usize);
+    }
 
-                let mut retain = true;
-                if reason == CRef::UNDEF || reason == CRef::SPECIAL {
-                    debug_assert!(self.level(x) > 0);
-                    retain = true;
+    /// Detach a clause from watcher lists.
+    ///
+    /// param `strict` means we remove the clause from watchers eagerly, instead
+    /// of just marking the watchlist as "dirty"
+    fn detach_clause(&mut self, cr: CRef, strict: bool) {
+        let (c0, c1, csize, clearnt) = {
+            let c = self.ca.get_ref(cr);
+            (c[0], c[1], c.size(), c.learnt())
+        };
+        debug_assert!(csize > 1);
+
+        // Strict or lazy detaching. A binary clause is in the long watch lists if it was
+        // shortened after being attached.
+        if strict {
+            for (lit, other) in [(!c0, c1), (!c1, c0)] {
+                let w = Watcher::new(cr, other);
+                let bin = &mut self.bin_watches_data[lit];
+                if let Some(pos) = bin.iter().position(|x| x == &w).filter(|_| csize == 2) {
+                    bin.remove(pos);
                 } else {
-                    let c = self.ca.get_ref(reason);
-                    for k in 1..c.size() {
-                        let v = c[k].var();
-                        if !self.seen[v].is_seen() && self.level(v) > 0 {
-                            retain = true;
-                            break;
-                        }
-                    }
+                    let ws = &mut self.watches_data[lit];
+                    let pos = ws.iter().position(|x| x == &w).expect("Watcher not found");
+                    ws.remove(pos);
                 }
-                if retain {
-                    out_learnt[j] = lit;
-                    j += 1;
+            }
+        } else {
+            for lit in [!c0, !c1] {
+                self.watches_data.smudge(lit);
+                if csize == 2 {
+                    self.bin_watches_data.smudge(lit);
                 }
             }
-            j
+        }
+
+        if clearnt {
+            self.num_learnts -= 1;
+            self.learnts_literals -= csize as u64;
         } else {
-            out_learnt.len()
-        };
+            self.num_clauses -= 1;
+            self.clauses_literals -= csize as u64;
+        }
+    }
 
-        self.tot_literals += new_size as u64;
-        debug_assert!(new_size <= out_learnt.len());
-        out_learnt.truncate(new_size);
+    /// Detach and free a clause.
+    fn remove_clause(&mut self, cr: CRef) {
+        self.detach_clause(cr, false);
+        let c = self.ca.get_ref(cr);
+        // Don't leave pointers to free'd memory!
+        if self.locked(c) {
+            let x = c[0].var();
+            if self.proof.lrat {
+                // the unit can't be derived anymore once its reason is gone
+                self.unit_id(x);
+            }
+            self.vars.vardata[x].reason = CRef::UNDEF;
+        }
+        self.ca.get_mut(cr).set_mark(1); // used in reloc
+        self.ca.free(cr);
     }
 
-    /// Specialized analysis procedure to express the final conflict in terms of assumptions.
-    /// Calculates the (possibly empty) set of assumptions that led to the assignment of `p`, and
-    /// stores the result in `out_conflict`.
-    fn analyze_final<Th: Theory>(&mut self, th: &mut Th, p: Lit, out_conflict: &mut LSet) {
-        out_conflict.clear();
-        out_conflict.insert(p);
-        debug!("analyze_final lit={:?}", p);
+    fn set_proof_output(&mut self, out: Box<dyn ProofOutput + Send>) {
+        if out.wants_hints() && !self.proof.lrat {
+            assert_eq!(
+                self.proof.last_id, 0,
+                "clause ids must be tracked from the first clause on"
+            );
+            self.proof.lrat = true;
+            self.ca.set_clause_ids(true);
+        }
+        self.proof.out = Some(out);
+    }
 
-        if self.decision_level() == 0 {
-            return; // no assumptions
+    #[inline]
+    /// Whether some binary clause propagates a literal when `lit` becomes true.
+    fn has_binary_implication(&self, lit: Lit) -> bool {
+        self.bin_watches_data[lit]
+            .iter()
+            .any(|w| self.ca.get_ref(w.cref).mark() != 1)
+    }
+
+    fn set_clause_id(&mut self, cr: CRef, id: u64) {
+        if self.ca.has_clause_ids() {
+            self.ca.set_id(cr, id);
         }
+    }
 
-        self.seen[p.var()] = Seen::SOURCE;
+    #[inline]
+    fn clause_id(&self, cr: CRef) -> u64 {
+        if self.ca.has_clause_ids() {
+            self.ca.id(cr)
+        } else {
+            0
+        }
+    }
 
-        // FIXME: use a stack here too, to be more robust wrt. theory propagations
-        for &lit in self.vars.trail[self.vars.trail_lim[0] as usize..]
-            .iter()
-            .rev()
-        {
-            let x = lit.var();
-            if self.seen[x].is_seen() {
-                let reason = self.reason(x);
-                if reason == CRef::UNDEF {
-                    debug_assert!(self.level(x) > 0);
-                    out_conflict.insert(!lit);
-                } else if reason == CRef::SPECIAL {
-                    // resolution with propagation reason
-                    let lits = th.explain_propagation_clause_final(lit, &mut self.th_st);
-                    debug_assert_eq!(lits[0], lit);
-                    for &p in &lits[1..] {
-                        if self.vars.level(p.var()) > 0 {
-                            self.seen[p.var()] = Seen::SOURCE;
-                        }
-                    }
-                } else {
-                    let c = self.ca.get_mut(reason);
-                    for j in 1..c.size() {
-                        if self.vars.level(c[j].var()) > 0 {
-                            self.seen[c[j].var()] = Seen::SOURCE;
-                        }
+    #[inline]
+    fn set_unit_id(&mut self, x: Var, id: u64) {
+        if self.proof.lrat {
+            self.proof.unit_ids.insert(x, id, 0);
+        }
+    }
+
+    /// Id of the unit clause made of the value of `x`, assigned at level 0.
+    ///
+    /// Units propagated at level 0 are only derived in the proof when needed.
+    fn unit_id(&mut self, x: Var) -> u64 {
+        debug_assert!(self.proof.lrat);
+        debug_assert_eq!(self.level(x), 0);
+        let id = self.proof.unit_id(x);
+        if id != 0 {
+            return id;
+        }
+
+        self.proof.unit_stack.push(x);
+        while let Some(&y) = self.proof.unit_stack.last() {
+            if self.proof.unit_id(y) != 0 {
+                self.proof.unit_stack.pop();
+                continue;
+            }
+            let reason = self.reason(y);
+            let lit = Lit::new(y, self.value(y) == lbool::TRUE);
+            if reason != CRef::UNDEF && reason != CRef::SPECIAL {
+                // derive the other literals of `reason` first
+                let c = self.ca.get_ref(reason);
+                let mut ready = true;
+                for &q in &c.lits()[1..] {
+                    if self.proof.unit_id(q.var()) == 0 {
+                        self.proof.unit_stack.push(q.var());
+                        ready = false;
                     }
                 }
-                self.seen[x] = Seen::UNDEF;
+                if !ready {
+                    continue;
+                }
+                for &q in &c.lits()[1..] {
+                    let h = self.proof.unit_id(q.var());
+                    self.proof.unit_hints.push(h);
+                }
+                self.proof.unit_hints.push(self.ca.id(reason));
             }
+            // NOTE: theory propagations are not justified
+            let id = self.proof.new_id();
+            if let Some(out) = &mut self.proof.out {
+                out.add_clause_with_hints(id, &[lit], &self.proof.unit_hints);
+            }
+            self.proof.unit_hints.clear();
+            self.proof.unit_ids.insert(y, id, 0);
+            self.proof.unit_stack.pop();
         }
-
-        self.seen[p.var()] = Seen::UNDEF;
-        debug_assert!(self.seen.iter().all(|(_, &s)| s == Seen::UNDEF));
+        self.proof.unit_id(x)
     }
 
-    /// Check if `p` can be removed from a conflict clause `C`.
+    /// Compute, in `self.proof.hints`, the antecedents of `clause` which
+    /// was derived from the conflict `confl` by resolution.
     ///
-    /// It can be removed from `C` if it is propagation-implied
-    /// by literals of level 0 exclusively or if `C x p.reason` subsumes `C`.
-    fn lit_redundant(&mut self, p: Lit, abstract_levels: u32) -> bool {
-        self.minimize_stack.clear();
-        self.minimize_stack.push(p);
+    /// The reasons of the literals implied by the negation of `clause` are listed
+    /// in topological order, so that unit propagation on them falsifies `confl`.
+    fn lrat_chain(&mut self, confl: CRef, clause: &[Lit]) {
+        debug_assert!(self.proof.lrat);
+        self.analyze_toclear.clear();
+        for &lit in clause {
+            self.seen[lit.var()] = Seen::SOURCE;
+        }
+        for &q in self.ca.get_ref(confl).lits() {
+            self.proof.stack.push((q.var(), false));
+        }
+        while let Some((x, expanded)) = self.proof.stack.pop() {
+            if expanded {
+                let h = self.ca.id(self.reason(x));
+                self.proof.hints.push(h);
+                continue;
+            } else if self.seen[x].is_seen() {
+                continue;
+            }
+            self.seen[x] = Seen::REMOVABLE;
+            self.analyze_toclear.push(Lit::new(x, true));
+            if self.level(x) == 0 {
+                let h = self.unit_id(x);
+                self.proof.hints.push(h);
+                continue;
+            }
+            let reason = self.reason(x);
+            debug_assert_ne!(reason, CRef::UNDEF, "decision not in the learnt clause");
+            if reason == CRef::SPECIAL {
+                continue; // NOTE: theory propagations are not justified
+            }
+            // emit the reason of `x` after the reasons of its antecedents
+            self.proof.stack.push((x, true));
+            for &q in &self.ca.get_ref(reason).lits()[1..] {
+                self.proof.stack.push((q.var(), false));
+            }
+        }
+        self.proof.hints.push(self.ca.id(confl));
+        for &lit in clause {
+            self.seen[lit.var()] = Seen::UNDEF;
+        }
+        for &lit in &self.analyze_toclear {
+            self.seen[lit.var()] = Seen::UNDEF;
+        }
+        self.analyze_toclear.clear();
+    }
 
-        let top = self.analyze_toclear.len();
+    /// Log the refutation of an original clause that was false when it was added.
+    fn flush_pending_unsat(&mut self) {
+        if let Some((id, lits)) = self.proof.pending_unsat.take() {
+            for &lit in &lits {
+                let h = self.unit_id(lit.var());
+                self.proof.hints.push(h);
+            }
+            self.proof.hints.push(id);
+            let id = self.proof.new_id();
+            self.proof.add(id, &[]);
+        }
+    }
 
-        while self.minimize_stack.len() > 0 {
-            let q = *self.minimize_stack.last().unwrap();
-            let cr = self.reason(q.var());
-            debug_assert_ne!(cr, CRef::UNDEF);
-            self.minimize_stack.pop();
+    /// Record that the empty clause was derived, from conflict `confl` at level 0 if any
+    fn proof_unsat(&mut self, confl: Option<CRef>) {
+        if let Some(confl) = confl {
+            if self.proof.lrat {
+                self.lrat_chain(confl, &[]);
+            }
+        }
+        let id = self.proof.new_id();
+        self.proof.add(id, &[]);
+    }
 
-            // special case: theory propagation
-            if cr == CRef::SPECIAL {
-                if self.vars.level(q.var()) == 0 {
-                    continue; // level 0, just continue
-                } else {
-                    // we just bail out here, even though the theory propagation
-                    // could be caused by propagations that ultimately
-                    // come from level 0
-                    // TODO: actually perform resolution here
+    fn proof_delete(&mut self, cr: CRef) {
+        let id = self.clause_id(cr);
+        self.proof.delete(id, self.ca.get_ref(cr).lits());
+    }
 
-                    for a in self.analyze_toclear[top..].iter() {
-                        self.seen[a.var()] = Seen::UNDEF;
+    /// Highest level of the literals of `cr`, which are all assigned.
+    fn clause_level(&self, cr: CRef) -> i32 {
+        let c = self.ca.get_ref(cr);
+        c.iter().map(|&lit| self.level_lit(lit)).max().unwrap_or(0)
+    }
+
+    /// Watch the two highest-level literals of the conflict `cr`, and return the level of
+    /// the conflict.
+    ///
+    /// With chronological backtracking the conflict can be found through lower level
+    /// literals, which would stay watched and false once the conflict level is undone.
+    fn watch_conflict_level(&mut self, cr: CRef) -> i32 {
+        for i in 0..2 {
+            let k = {
+                let c = self.ca.get_ref(cr);
+                let mut k = i;
+                for j in i + 1..c.size() {
+                    if self.level_lit(c[j]) > self.level_lit(c[k]) {
+                        k = j;
                     }
-                    self.analyze_toclear.resize(top, Lit::UNDEF);
-                    return false;
                 }
+                k
+            };
+            if k == i {
+                continue;
+            }
+            let mut c = self.ca.get_mut(cr);
+            let old = c[i];
+            c[i] = c[k];
+            c[k] = old;
+            if k >= 2 {
+                // `old` is not watched anymore
+                let (new, other) = (c[i], c[1 - i]);
+                let mut watches = self.watches_data.promote(WatcherDeleted { ca: &self.ca });
+                let pos = watches[!old]
+                    .iter()
+                    .position(|w| w.cref == cr)
+                    .expect("Watcher not found");
+                watches[!old].remove(pos);
+                watches[!new].push(Watcher::new(cr, other));
             }
+        }
+        self.level_lit(self.ca.get_ref(cr)[0])
+    }
 
-            let c = self.ca.get_ref(cr);
-            // `q` comes from some propagation with `c`, check if these lits can
-            // also be eliminated or are already in the learnt clause
-            for &l in c.lits()[1..].iter() {
-                // Variable at level 0 or previously removable: just skip
-                if self.vars.level(l.var()) == 0 || self.seen[l.var()] == Seen::SOURCE {
-                    continue;
-                }
+    pub fn satisfied(&self, c: ClauseRef) -> bool {
+        c.iter().any(|&lit| self.value_lit(lit) == lbool::TRUE)
+    }
 
-                if self.reason(l.var()) != CRef::UNDEF
-                    && (self.abstract_level(l.var()) & abstract_levels) != 0
-                {
-                    // keep this literal.
-                    // NOTE: if the level of `l` isn't in `abstract_levels`, it
-                    // means it comes from propagations at a decision level
-                    // unrelated to the learnt clause, and therefore is
-                    // somehow implied by an unrelated decision, so there's no
-                    // chance to eliminate `l` via resolutions from the learnt clause.
-                    self.seen[l.var()] = Seen::SOURCE;
-                    self.minimize_stack.push(l);
-                    self.analyze_toclear.push(l);
-                } else {
-                    // cannot remove `l`, cancel
-                    for a in self.analyze_toclear[top..].iter() {
-                        self.seen[a.var()] = Seen::UNDEF;
-                    }
-                    self.analyze_toclear.resize(top, Lit::UNDEF);
-                    return false;
-                }
-            }
+    #[inline(always)]
+    pub fn decision_level(&self) -> u32 {
+        self.vars.decision_level()
+    }
+
+    #[inline(always)]
+    fn reason(&self, x: Var) -> CRef {
+        self.vars.reason(x)
+    }
+
+    /// Returns `true` if a clause is a reason for some implication in the current state.
+    fn locked(&self, c: ClauseRef) -> bool {
+        let reason = self.reason(c[0].var());
+        self.value_lit(c[0]) == lbool::TRUE
+            && reason != CRef::UNDEF
+            && reason != CRef::SPECIAL
+            && self.ca.get_ref(reason) == c
+    }
+    // inline bool     Solver::locked          (const Clause& c) const { return value(c[0]) == l_True && reason(var(c[0])) != CRef_Undef && ca.lea(reason(var(c[0]))) == &c; }
+
+    fn progress_estimate(&self) -> f64 {
+        let mut progress = 0.0;
+        let f = 1.0 / self.num_vars() as f64;
+        let mut f_pow_i = 1.0;
+
+        for i in 0..self.decision_level() + 1 {
+            let beg: i32 = if i == 0 {
+                0
+            } else {
+                self.vars.trail_lim[i as usize - 1]
+            };
+            let end: i32 = if i == self.decision_level() {
+                self.vars.trail.len() as i32
+            } else {
+                self.vars.trail_lim[i as usize]
+            };
+            progress += f_pow_i * (end - beg) as f64;
+            f_pow_i *= f;
         }
 
-        true
+        progress / self.num_vars() as f64
+    }
+    fn new(opts: &SolverOpts) -> Self {
+        Self {
+            opts: opts.clone(),
+            rand: Rand::new(opts.rand_seed()),
+            vars: VarState::new(opts.branching),
+            num_clauses: 0,
+            num_learnts: 0,
+            clauses_literals: 0,
+            learnts_literals: 0,
+
+            // Parameters (experimental):
+            learntsize_adjust_start_confl: 100,
+            learntsize_adjust_inc: 1.5,
+
+            // Statistics: (formerly in 'SolverStats')
+            solves: 0,
+            starts: 0,
+            decisions: 0,
+            rnd_decisions: 0,
+            otfs_strengthened: 0,
+            propagations: 0,
+            ticks: 0,
+            conflicts: 0,
+            dec_vars: 0,
+            // v.num_clauses: 0,
+            // v.num_learnts: 0,
+            // v.clauses_literals: 0,
+            // v.learnts_literals: 0,
+            max_literals: 0,
+            tot_literals: 0,
+            deleted_clauses: 0,
+            gcs: 0,
+            gc_reclaimed: 0,
+
+            polarity: VMap::new(),
+            target: VMap::new(),
+            best: VMap::new(),
+            user_pol: VMap::new(),
+            assump_priority: VMap::new(),
+            core_hits: VMap::new(),
+            decision: VMap::new(),
+            frozen: VMap::new(),
+            eliminated: VMap::new(),
+            blocking: VMap::new(),
+            internal: VMap::new(),
+            // v.vardata: VMap::new(),
+            watches_data: OccListsData::new(),
+            bin_watches_data: OccListsData::new(),
+            ok: true,
+            cla_inc: 1.0,
+            // v.var_inc: 1.0,
+            qhead: 0,
+            simp_db_assigns: -1,
+            simp_db_props: 0,
+            progress_estimate: 0.0,
+            remove_satisfied: false, // revert b5464ec81f76db9315dac3276b64614dd59cfe49
+            next_var: Var::from_idx(0),
+
+            ca: ClauseAllocator::new(),
+            free_vars: vec![],
+            released: vec![],
+            assumptions: vec![],
+
+            seen: IntMapClearable::new(),
+            minimize_stack: vec![],
+            analyze_toclear: vec![],
+            lbd_levels: LevelStamps::default(),
+            max_learnts: 0.0,
+            learntsize_adjust_confl: 0.0,
+            learntsize_adjust_cnt: 0,
+
+            // Resource constraints:
+            conflict_budget: -1,
+            propagation_budget: -1,
+            tick_budget: -1,
+            #[cfg(feature = "std")]
+            deadline: None,
+            stop_flag: None,
+            memory_limit: usize::MAX,
+            next_memory_check: 0,
+            memory_out: false,
+
+            next_vivify: VIVIFY_INTERVAL,
+            vivify_ticks: 0,
+            vivify_next: [0; 2],
+            next_probe: PROBE_INTERVAL,
+            probe_ticks: 0,
+            probe_next: 0,
+            next_reduce: opts.reduce_first as u64,
+            num_reduces: 0,
+            otfs: vec![],
+            lbd_ema_fast: Ema::new(1.0 / 32.0),
+            lbd_ema_slow: Ema::new(1.0 / 4096.0),
+            trail_ema: Ema::new(1.0 / 4096.0),
+            restart_blocked: 0,
+            stable: false,
+            mode_switches: 0,
+            trail_reuses: 0,
+            mode_ticks: 0,
+            mode_limit: 0,
+            target_len: 0,
+            best_len: 0,
+            next_rephase: opts.rephase_int as u64,
+            num_rephases: 0,
+            local_search_ticks: 0,
+
+            th_st: ExplainTheoryArg::new(),
+            th_stats: TheoryStats::default(),
+            th_notified: 0,
+            th_restart: false,
+            th_queue: vec![],
+            th_cache: ExplainCache::default(),
+            natives: NativeConstraints::default(),
+            proof: ProofState::default(),
+        }
     }
+}
 
-    /// Propagates all enqueued facts.
-    ///
-    /// If a conflict arises, the conflicting clause and literal is returned,
-    /// otherwise `None`.
-    ///
-    /// # Post-conditions:
-    ///
-    /// - the propagation queue is empty, even if there was a conflict.
-    fn propagate(&mut self) -> Option<CRef> {
-        let mut confl = None;
-        let mut num_props: u32 = 0;
+/// Restart interval in the stable mode, scaled by the Luby sequence.
+const STABLE_RESTART_FIRST: i32 = 1024;
 
-        while (self.qhead as usize) < self.vars.trail.len() {
-            // `p` is the next enqueued fact to propagate.
-            let p = self.vars.trail[self.qhead as usize];
+/// Minimum number of conflicts between two Glucose restarts.
+const GLUCOSE_RESTART_MIN: i32 = 50;
+/// Number of conflicts before Glucose restarts may be blocked.
+const GLUCOSE_BLOCK_FIRST: u64 = 10000;
 
-            // eprintln!("propagating trail[{}] = {:?}", self.qhead, p);
-            self.qhead += 1;
-            // let ws = self.watches().lookup_mut(p);
-            let ws = self
-                .watches_data
-                .lookup_mut_pred(p, &WatcherDeleted { ca: &self.ca });
-            // eprintln!("watcher of {:?} = {:?}", p, ws);
-            let mut i: usize = 0;
-            let mut j: usize = 0;
-            let end: usize = ws.len();
-            num_props += 1;
-            'clauses: while i < end {
-                let ws = &mut self.watches_data[p];
-                // Try to avoid inspecting the clause:
-                let blocker = ws[i].blocker;
-                if self.vars.value_lit(blocker) == lbool::TRUE {
-                    ws[j] = ws[i];
-                    j += 1;
-                    i += 1;
-                    continue;
-                }
+/// Maximum size of the learnt clauses shrunk by [`SolverOpts::binary_minimize_lbd`].
+const BINARY_MINIMIZE_MAX_LEN: usize = 30;
 
-                // Make sure the false literal is data[1]:
-                let cr = ws[i].cref;
-                let mut c = self.ca.get_mut(cr);
-                let false_lit = !p;
-                if c[0] == false_lit {
-                    c[0] = c[1];
-                    c[1] = false_lit;
-                }
-                debug_assert_eq!(c[1], false_lit);
-                i += 1;
+/// Number of conflicts between two vivifications of the clauses.
+const VIVIFY_INTERVAL: u64 = 5000;
+/// Number of conflicts between two calls to [`Solver::probe`] and
+/// [`Solver::substitute_equivalences`].
+const PROBE_INTERVAL: u64 = 5000;
+/// Number of conflicts between two checks of the memory limit.
+const MEMORY_CHECK_INTERVAL: u64 = 100;
 
-                // If 0th watch is true, then clause is already satisfied.
-                let first = c[0];
-                let w = Watcher::new(cr, first);
-                if first != blocker && self.vars.value_lit(first) == lbool::TRUE {
-                    ws[j] = w;
-                    j += 1;
-                    continue;
-                }
+#[test]
+fn test_vivify() {
+    let mut solver = crate::BasicSolver::default();
+    let [a, b, c, d] = [(); 4].map(|_| Lit::new(solver.new_var_default(), true));
+    solver.add_clause_reuse(&mut vec![a, b, c, d]);
+    solver.add_clause_reuse(&mut vec![a, !b]);
+    solver.add_clause_reuse(&mut vec![c, !d]);
+    // `!a` implies `!b`, `!c` implies `!d`, so the first clause can be shortened to `a | c`
+    solver.v.ticks = 1000;
+    assert!(solver.vivify());
+    let mut c0 = solver.v.ca.get_ref(solver.clauses[0]).lits().to_vec();
+    c0.sort();
+    assert_eq!(c0, vec![a, c]);
+    assert_eq!(solver.num_clauses(), 3);
+    assert_eq!(solver.solve_limited(&[!a, !c]), lbool::FALSE);
+}
 
-                // Look for new watch:
-                for k in 2..c.size() {
-                    if self.vars.value_lit(c[k]) != lbool::FALSE {
-                        c[1] = c[k];
-                        c[k] = false_lit;
+#[test]
+fn test_probe() {
+    let mut solver = crate::BasicSolver::default();
+    let [a, b, c, d, e] = [(); 5].map(|_| Lit::new(solver.new_var_default(), true));
+    solver.add_clause_reuse(&mut vec![!a, b]);
+    solver.add_clause_reuse(&mut vec![!a, c]);
+    solver.add_clause_reuse(&mut vec![!b, !c, d]);
+    solver.add_clause_reuse(&mut vec![!e, b]);
+    solver.add_clause_reuse(&mut vec![!e, !b]);
+    solver.v.opts.probe_effort = 1.0;
+    solver.v.ticks = 1000;
+    assert!(solver.probe());
+    // `e` is a failed literal, and `a` implies `d` through the ternary clause
+    assert_eq!(solver.v.value_lit(e), lbool::FALSE);
+    assert!(solver.learnts.iter().any(|&cr| {
+        let mut c = solver.v.ca.get_ref(cr).lits().to_vec();
+        c.sort();
+        c == [!a, d]
+    }));
+    assert_eq!(solver.solve_limited(&[a, !d]), lbool::FALSE);
+}
+
+#[test]
+fn test_glucose_restarts() {
+    let mut rng = crate::test_util::Rng::new(11);
+    let mut rand = |n| rng.next(n);
+    let mut solver = crate::BasicSolver::default();
+    solver.v.opts.restart_strategy = RestartStrategy::Glucose;
+    let vars: Vec<Var> = (0..250).map(|_| solver.new_var_default()).collect();
+    let clauses: Vec<Vec<Lit>> = (0..1065)
+        .map(|_| {
+            (0..3)
+                .map(|_| Lit::new(vars[rand(250) as usize], rand(2) == 0))
+                .collect()
+        })
+        .collect();
+    for c in &clauses {
+        solver.add_clause_reuse(&mut c.clone());
+    }
+    let res = solver.solve_limited(&[]);
+    assert_ne!(res, lbool::UNDEF);
+    if res == lbool::TRUE {
+        let is_true = |l: Lit| solver.value_lit(l) == lbool::TRUE;
+        assert!(clauses.iter().all(|c| c.iter().any(|&l| is_true(l))));
+    }
+    assert!(solver.v.starts > 1, "{} conflicts", solver.v.conflicts);
+}
+
+#[test]
+#[allow(deprecated)]
+fn test_luby_restart() {
+    // the deprecated switch turns the Luby restarts into geometric ones
+    let mut opts = SolverOpts::default();
+    assert_eq!(opts.restarts(), RestartStrategy::Luby);
+    opts.luby_restart = false;
+    assert_eq!(opts.restarts(), RestartStrategy::Geometric);
+    opts.restart_strategy = RestartStrategy::Glucose;
+    assert_eq!(opts.restarts(), RestartStrategy::Glucose);
+}
 
-                        // self.watches()[!c[1]].push(w);
-                        debug_assert_ne!(!c[1], p);
-                        self.watches_data[!c[1]].push(w);
-                        continue 'clauses;
-                    }
-                }
-                let ws = &mut self.watches_data[p];
+#[test]
+fn test_reduce_db_tiers() {
+    let mut solver = crate::BasicSolver::default();
+    let lits: Vec<Lit> = (0..8)
+        .map(|_| Lit::new(solver.new_var_default(), true))
+        .collect();
+    // (lbd, used, activity) of learnt clauses
+    let learnts = [
+        (2, false, 0.0),
+        (5, true, 1.0),
+        (5, false, 2.0),
+        (8, true, 3.0),
+        (8, false, 4.0),
+        (8, false, 5.0),
+    ];
+    for (lbd, used, act) in learnts {
+        let cr = solver.v.ca.alloc_with_learnt(&lits, true);
+        let mut c = solver.v.ca.get_mut(cr);
+        c.set_lbd(lbd);
+        c.set_used(used);
+        c.set_activity(act);
+        solver.v.attach_clause(cr);
+        solver.learnts.push(cr);
+    }
+    solver.reduce_db_tiers();
+    // the core clause and the used tier 2 clause are kept, as well as the most active
+    // half of the others
+    let mut kept: Vec<_> = solver
+        .learnts
+        .iter()
+        .map(|&cr| {
+            let c = solver.v.ca.get_ref(cr);
+            assert!(!c.used());
+            (c.lbd(), c.activity())
+        })
+        .collect();
+    kept.sort_by(|x, y| x.partial_cmp(y).unwrap());
+    assert_eq!(kept, [(2, 0.0), (5, 1.0), (8, 4.0), (8, 5.0)]);
+}
 
-                // Did not find watch -- clause is unit under assignment:
-                ws[j] = w;
-                j += 1;
-                if self.vars.value_lit(first) == lbool::FALSE {
-                    // eprintln!("propagation: conflict at {:?}", first);
-                    confl = Some(cr);
-                    self.qhead = self.vars.trail.len() as i32;
-                    // Copy the remaining watches:
-                    while i < end {
-                        ws[j] = ws[i];
-                        j += 1;
-                        i += 1;
-                    }
-                } else {
-                    // eprintln!("propagation: got {:?}", first);
-                    self.vars.unchecked_enqueue(first, cr);
-                }
-            }
-            let ws = &mut self.watches_data[p];
-            let dummy = Watcher::DUMMY;
-            ws.resize(j, dummy);
+#[test]
+fn test_rephase() {
+    let mut solver = crate::BasicSolver::default();
+    let vars: Vec<Var> = (0..4).map(|_| solver.new_var_default()).collect();
+    // a conflict at level 2: only the trail of level 1 is saved
+    for (i, &v) in vars[..3].iter().enumerate() {
+        if i < 2 {
+            solver.v.vars.new_decision_level();
         }
-        self.propagations += num_props as u64;
-        self.simp_db_props -= num_props as i64;
+        solver
+            .v
+            .vars
+            .unchecked_enqueue(Lit::new(v, i != 1), CRef::UNDEF);
+    }
+    solver.v.save_phases();
+    let target: Vec<lbool> = vars.iter().map(|&v| solver.v.target[v]).collect();
+    assert_eq!(
+        target,
+        [lbool::TRUE, lbool::UNDEF, lbool::UNDEF, lbool::UNDEF]
+    );
+    assert_eq!(solver.v.best[vars[0]], lbool::TRUE);
+    // best, original, best, inverted phases
+    let mut phases = vec![];
+    for _ in 0..4 {
+        solver.v.rephase();
+        assert_eq!(solver.v.target[vars[0]], lbool::UNDEF);
+        phases.push(
+            vars.iter()
+                .map(|&v| solver.v.polarity[v])
+                .collect::<Vec<_>>(),
+        );
+    }
+    assert_eq!(phases[0], [true, false, false, false]);
+    assert_eq!(phases[1], [false; 4]);
+    assert_eq!(phases[2], [true, false, false, false]);
+    assert_eq!(phases[3], [true; 4]);
+}
 
-        confl
+#[test]
+fn test_local_search() {
+    // random 3-SAT formulas near the threshold, with local search after each conflict
+    let mut rng = crate::test_util::Rng::new(17);
+    let mut rand = |n| rng.next(n);
+    for _ in 0..30 {
+        let clauses: Vec<Vec<(u64, bool)>> = (0..210)
+            .map(|_| (0..3).map(|_| (rand(50), rand(2) == 0)).collect())
+            .collect();
+        let results = [false, true].map(|local_search| {
+            let mut solver = crate::BasicSolver::default();
+            solver.v.opts.local_search = local_search;
+            solver.v.opts.local_search_effort = 10.0;
+            solver.v.opts.rephase = true;
+            solver.v.opts.rephase_int = 1;
+            let vars: Vec<Var> = (0..50).map(|_| solver.new_var_default()).collect();
+            let clauses: Vec<Vec<Lit>> = clauses
+                .iter()
+                .map(|c| {
+                    c.iter()
+                        .map(|&(v, s)| Lit::new(vars[v as usize], s))
+                        .collect()
+                })
+                .collect();
+            for c in &clauses {
+                solver.add_clause_reuse(&mut c.clone());
+            }
+            let res = solver.solve_limited(&[]);
+            if res == lbool::TRUE {
+                let is_true = |l: Lit| solver.value_lit(l) == lbool::TRUE;
+                assert!(clauses.iter().all(|c| c.iter().any(|&l| is_true(l))));
+            }
+            res
+        });
+        assert_eq!(results[0], results[1]);
     }
+}
 
-    /// Sort literals of `clause` so that unassigned literals are first,
-    /// followed by literals in decreasing assignment level
-    fn sort_clause_lits(&self, clause: &mut [Lit]) {
-        // sort clause to put unassigned/high level lits first
-        clause.sort_unstable_by(|&lit1, &lit2| {
-            let has_val1 = self.value_lit(lit1) != lbool::UNDEF;
-            let has_val2 = self.value_lit(lit2) != lbool::UNDEF;
+#[test]
+#[allow(deprecated)]
+fn test_seed() {
+    // runs with random decisions are reproduced exactly with the same seed
+    let mut rng = crate::test_util::Rng::new(19);
+    let mut rand = |n| rng.next(n);
+    let clauses: Vec<Vec<(u64, bool)>> = (0..420)
+        .map(|_| (0..3).map(|_| (rand(100), rand(2) == 0)).collect())
+        .collect();
+    let run = |set: &dyn Fn(&mut SolverOpts)| {
+        let mut solver = crate::BasicSolver::default();
+        let mut opts = solver.options();
+        opts.random_var_freq = 0.2;
+        opts.random_pol_freq = 0.2;
+        set(&mut opts);
+        solver.set_options(opts).unwrap();
+        let vars: Vec<Var> = (0..100).map(|_| solver.new_var_default()).collect();
+        for c in &clauses {
+            let mut c: Vec<Lit> = c
+                .iter()
+                .map(|&(v, s)| Lit::new(vars[v as usize], s))
+                .collect();
+            solver.add_clause_reuse(&mut c);
+        }
+        let res = solver.solve_limited(&[]);
+        (
+            res,
+            solver.v.decisions,
+            solver.v.rnd_decisions,
+            solver.v.conflicts,
+        )
+    };
+    let runs = [
+        run(&|o| o.seed = 1),
+        run(&|o| o.seed = 1),
+        run(&|o| o.seed = 2),
+    ];
+    assert_eq!(runs[0], runs[1]);
+    assert!(runs[0].2 > 0);
+    assert_eq!(runs[0].0, runs[2].0);
+    assert_ne!(runs[0], runs[2]);
+    // the deprecated options still work
+    assert_eq!(run(&|o| o.random_seed = 2.0), runs[2]);
+    assert_eq!(
+        run(&|o| o.rnd_pol = true),
+        run(&|o| o.random_pol_freq = 1.0)
+    );
+}
 
-            // unassigned variables come first
-            if has_val1 && !has_val2 {
-                return cmp::Ordering::Greater;
+#[test]
+fn test_remove_clause_random() {
+    // random 3-SAT formulas above the threshold, from which clauses are removed one at
+    // a time, compared to a fresh solver on the remaining clauses
+    let mut rng = crate::test_util::Rng::new(29);
+    let mut rand = |n| rng.next(n);
+    for _ in 0..5 {
+        let clauses: Vec<Vec<Lit>> = (0..150)
+            .map(|_| {
+                (0..3)
+                    .map(|_| Lit::new(Var::from_idx(rand(30) as u32), rand(2) == 0))
+                    .collect()
+            })
+            .collect();
+        let mut solver = crate::BasicSolver::default();
+        for _ in 0..30 {
+            solver.new_var_default();
+        }
+        let mut handles: Vec<Option<ClauseHandle>> = clauses
+            .iter()
+            .map(|c| solver.add_removable_clause(c))
+            .collect();
+        for _ in 0..60 {
+            let i = rand(clauses.len() as u64) as usize;
+            if let Some(h) = handles[i].take() {
+                assert!(solver.remove_clause(h));
             }
-            if !has_val1 && has_val2 {
-                return cmp::Ordering::Less;
+            let res = solver.solve_limited(&[]);
+            let remaining: Vec<&Vec<Lit>> = clauses
+                .iter()
+                .zip(&handles)
+                .filter(|(_, h)| h.is_some())
+                .map(|(c, _)| c)
+                .collect();
+            let mut fresh = crate::BasicSolver::default();
+            for _ in 0..30 {
+                fresh.new_var_default();
+            }
+            for c in &remaining {
+                fresh.add_clause_reuse(&mut c.to_vec());
+            }
+            assert_eq!(res, fresh.solve_limited(&[]));
+            if res == lbool::TRUE {
+                let is_true = |l: Lit| solver.value_lit(l) == lbool::TRUE;
+                assert!(remaining.iter().all(|c| c.iter().any(|&l| is_true(l))));
             }
+        }
+        // (tautologies are not added)
+        assert!(solver.num_clauses() <= handles.iter().flatten().count() as u64);
+    }
+}
 
-            let lvl1 = self.level_lit(lit1);
-            let lvl2 = self.level_lit(lit2);
-            if lvl1 != lvl2 {
-                lvl2.cmp(&lvl1) // higher level come first
-            } else {
-                lit1.cmp(&lit2) // otherwise default comparison
+#[test]
+fn test_compact() {
+    // random 3-SAT formulas over 30 variables, each solved with extra clauses guarded by
+    // a temporary variable, which is then released
+    let mut rng = crate::test_util::Rng::new(31);
+    let mut rand = |n| rng.next(n);
+    let heuristics = [
+        BranchingHeuristic::Vsids,
+        BranchingHeuristic::Vmtf,
+        BranchingHeuristic::Chb,
+        BranchingHeuristic::Lrb,
+    ];
+    let mut compacted = 0;
+    for i in 0..8 {
+        let mut solver = crate::BasicSolver::default();
+        solver.v.opts.branching = heuristics[i % 4];
+        solver.v.reset_branching();
+        let vars: Vec<Var> = (0..30).map(|_| solver.new_var_default()).collect();
+        let mut random_clause = |vars: &[Var]| -> Vec<Lit> {
+            (0..3)
+                .map(|_| Lit::new(vars[rand(vars.len() as u64) as usize], rand(2) == 0))
+                .collect()
+        };
+        let clauses: Vec<Vec<Lit>> = (0..90).map(|_| random_clause(&vars)).collect();
+        for c in &clauses {
+            solver.add_clause_reuse(&mut c.clone());
+        }
+        for _ in 0..20 {
+            let t = Lit::new(solver.new_var_default(), true);
+            for _ in 0..10 {
+                let mut c = random_clause(&vars);
+                c.push(!t);
+                solver.add_clause_reuse(&mut c);
             }
-        });
+            solver.solve_limited(&[t]);
+            solver.release_var(!t);
+        }
+        let expected = solver.solve_limited(&[]);
+        let model: Vec<lbool> = vars.iter().map(|&v| solver.value_var(v)).collect();
 
-        // check that the first literal is a proper watch
-        debug_assert!(
-            self.value_lit(clause[0]) == lbool::UNDEF || {
-                let lvl0 = self.level_lit(clause[0]);
-                clause[1..].iter().all(|&lit2| self.level_lit(lit2) <= lvl0)
+        assert_eq!(solver.num_vars(), 50);
+        if expected == lbool::FALSE {
+            assert!(solver.compact().is_none());
+            continue;
+        }
+        let map = solver.compact().unwrap();
+        compacted += 1;
+        assert_eq!(solver.num_vars(), 30);
+        // the variables of the formula come first and keep their number
+        for &v in &vars {
+            assert_eq!(map[v], v);
+        }
+        assert!((30..50).all(|i| map[Var::from_idx(i)] == Var::UNDEF));
+        // the per-variable tables only keep room for the remaining variables
+        assert!(solver.v.polarity.capacity() < 50 && solver.v.frozen.capacity() < 50);
+        assert!(vars
+            .iter()
+            .zip(&model)
+            .all(|(&v, &val)| solver.value_var(v) == val));
+        assert_eq!(solver.solve_limited(&[]), lbool::TRUE);
+        let is_true = |l: Lit| solver.value_lit(l) == lbool::TRUE;
+        assert!(clauses.iter().all(|c| c.iter().any(|&l| is_true(l))));
+        // the new variables reuse the numbers
+        assert_eq!(solver.new_var_default(), Var::from_idx(30));
+    }
+    assert!(compacted > 0);
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_write_dimacs() {
+    // random 3-SAT formulas over 40 variables with a few units, written after solving and
+    // read back into a fresh solver
+    let mut rng = crate::test_util::Rng::new(17);
+    let mut rand = |n| rng.next(n);
+    let mut results = [0; 2];
+    for i in 0..16 {
+        let mut solver = crate::BasicSolver::default();
+        let vars: Vec<Var> = (0..40).map(|_| solver.new_var_default()).collect();
+        for j in 0..170 {
+            let len = if j < 3 { 1 } else { 3 };
+            let mut c: Vec<Lit> = (0..len)
+                .map(|_| Lit::new(vars[rand(40) as usize], rand(2) == 0))
+                .collect();
+            solver.add_clause_reuse(&mut c);
+        }
+        let expected = solver.solve_limited(&[]);
+        let renumber = i % 2 == 0;
+        let mut out = vec![];
+        let map = solver.write_dimacs(&mut out, renumber).unwrap();
+        let mut copy = crate::BasicSolver::default();
+        crate::dimacs::parse_into(&mut &out[..], &mut copy).unwrap();
+        assert_eq!(copy.solve_limited(&[]), expected);
+        let header = std::str::from_utf8(&out).unwrap().lines().next().unwrap();
+        if expected == lbool::FALSE {
+            results[0] += 1;
+            let n = if renumber { 0 } else { 40 };
+            assert_eq!(header, format!("p cnf {} 1", n));
+            continue;
+        }
+        results[1] += 1;
+        if renumber {
+            let used: Vec<Var> = vars
+                .iter()
+                .map(|&v| map[v])
+                .filter(|&v| v != Var::UNDEF)
+                .collect();
+            assert!(used.iter().all(|v| v.idx() < used.len() as u32));
+            assert!(header.starts_with(&format!("p cnf {} ", used.len())));
+            // the model of the copy is one of the solver
+            let mut solver2 = solver.clone();
+            for &v in &vars {
+                if map[v] != Var::UNDEF {
+                    let lit = Lit::new(v, copy.value_var(map[v]) == lbool::TRUE);
+                    solver2.add_clause_reuse(&mut vec![lit]);
+                }
             }
-        );
+            assert_eq!(solver2.solve_limited(&[]), lbool::TRUE);
+        } else {
+            assert!(vars.iter().all(|&v| map[v] == v));
+            assert!(header.starts_with("p cnf 40 "));
+        }
     }
+    assert!(results.iter().all(|&n| n > 0));
+}
 
-    /// Move to the given clause allocator, where clause indices might differ
-    fn reloc_all(
-        &mut self,
-        learnts: &mut Vec<CRef>,
-        clauses: &mut Vec<CRef>,
-        to: &mut ClauseAllocator,
-    ) {
-        macro_rules! is_removed {
-            ($ca:expr, $cr:expr) => {
-                $ca.get_ref($cr).mark() == 1
-            };
+#[test]
+fn test_snapshot() {
+    let mut solver = crate::BasicSolver::default();
+    let [a, b, c] = [(); 3].map(|_| Lit::new(solver.new_var_default(), true));
+    solver.add_clause_reuse(&mut vec![a, b, c]);
+    solver.add_clause_reuse(&mut vec![!a, b]);
+    let snapshot = solver.save_state();
+    let d = Lit::new(solver.new_var_default(), true);
+    solver.add_clause_reuse(&mut vec![!b]);
+    solver.add_clause_reuse(&mut vec![!c, d]);
+    solver.add_clause_reuse(&mut vec![!d]);
+    assert_eq!(solver.solve_limited(&[]), lbool::FALSE);
+    solver.restore(snapshot.clone());
+    assert_eq!(solver.num_clauses(), 2);
+    assert_eq!(solver.solve_limited(&[!b, !d]), lbool::TRUE);
+    assert_eq!(solver.value_lit(c), lbool::TRUE);
+    assert_eq!(solver.solve_limited(&[!b, !c]), lbool::FALSE);
+
+    // random 3-SAT formulas near the threshold, solved again after going back to a
+    // snapshot taken in the middle of the search, with the learnt clauses
+    let mut rng = crate::test_util::Rng::new(37);
+    let mut rand = |n| rng.next(n);
+    for _ in 0..10 {
+        let mut solver = crate::BasicSolver::default();
+        let vars: Vec<Var> = (0..60).map(|_| solver.new_var_default()).collect();
+        let mut random_clause = || -> Vec<Lit> {
+            (0..3)
+                .map(|_| Lit::new(vars[rand(60) as usize], rand(2) == 0))
+                .collect()
+        };
+        let clauses: Vec<Vec<Lit>> = (0..255).map(|_| random_clause()).collect();
+        let extra: Vec<Vec<Lit>> = (0..30).map(|_| random_clause()).collect();
+        for c in &clauses {
+            solver.add_clause_reuse(&mut c.clone());
         }
-        // All watchers:
-        self.watches().clean_all();
-        for v in (0..self.num_vars()).map(Var::from_idx) {
-            for s in 0..2 {
-                let p = Lit::new(v, s != 0);
-                for watch in &mut self.watches_data[p] {
-                    self.ca.reloc(&mut watch.cref, to);
-                }
+        solver.set_conflict_budget(50);
+        let first = solver.solve_limited(&[]);
+        let snapshot = solver.save_state();
+        let learnts = solver.learnts.len();
+        for c in &extra {
+            solver.add_clause_reuse(&mut c.clone());
+        }
+        solver.set_conflict_budget(u64::MAX / 2);
+        solver.solve_limited(&[]);
+        solver.restore(snapshot);
+        assert_eq!(solver.learnts.len(), learnts);
+        let res = solver.solve_limited(&[]);
+        if first != lbool::UNDEF {
+            assert_eq!(res, first);
+        }
+        let mut fresh = crate::BasicSolver::default();
+        for _ in 0..60 {
+            fresh.new_var_default();
+        }
+        for c in &clauses {
+            fresh.add_clause_reuse(&mut c.clone());
+        }
+        assert_eq!(res, fresh.solve_limited(&[]));
+        if res == lbool::TRUE {
+            let is_true = |l: Lit| solver.value_lit(l) == lbool::TRUE;
+            assert!(clauses.iter().all(|c| c.iter().any(|&l| is_true(l))));
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_serde() {
+    // random 3-SAT formulas near the threshold, whose solver is serialized in the middle
+    // of the search and resumed
+    let mut rng = crate::test_util::Rng::new(41);
+    let mut rand = |n| rng.next(n);
+    for _ in 0..10 {
+        let mut solver = crate::BasicSolver::default();
+        let vars: Vec<Var> = (0..60).map(|_| solver.new_var_default()).collect();
+        let clauses: Vec<Vec<Lit>> = (0..255)
+            .map(|_| {
+                (0..3)
+                    .map(|_| Lit::new(vars[rand(60) as usize], rand(2) == 0))
+                    .collect()
+            })
+            .collect();
+        for c in &clauses {
+            solver.add_clause_reuse(&mut c.clone());
+        }
+        solver.set_conflict_budget(50);
+        solver.solve_limited(&[]);
+        let json = serde_json::to_string(&solver).unwrap();
+        let mut resumed: crate::BasicSolver = serde_json::from_str(&json).unwrap();
+        assert_eq!(resumed.num_clauses(), solver.num_clauses());
+        assert_eq!(resumed.learnts.len(), solver.learnts.len());
+        assert_eq!(resumed.num_conflicts(), solver.num_conflicts());
+        resumed.set_conflict_budget(u64::MAX / 2);
+        solver.set_conflict_budget(u64::MAX / 2);
+        let res = resumed.solve_limited(&[]);
+        assert_eq!(res, solver.solve_limited(&[]));
+        // the search goes on exactly as in the original solver
+        assert_eq!(resumed.num_conflicts(), solver.num_conflicts());
+        if res == lbool::TRUE {
+            let is_true = |l: Lit| resumed.value_lit(l) == lbool::TRUE;
+            assert!(clauses.iter().all(|c| c.iter().any(|&l| is_true(l))));
+        }
+    }
+}
+
+#[test]
+fn test_clone() {
+    // random 3-SAT formulas near the threshold, whose solver is forked in the middle of
+    // the search to try both polarities of a variable
+    let mut rng = crate::test_util::Rng::new(43);
+    let mut rand = |n| rng.next(n);
+    for _ in 0..10 {
+        let mut solver = crate::BasicSolver::default();
+        let vars: Vec<Var> = (0..60).map(|_| solver.new_var_default()).collect();
+        let clauses: Vec<Vec<Lit>> = (0..255)
+            .map(|_| {
+                (0..3)
+                    .map(|_| Lit::new(vars[rand(60) as usize], rand(2) == 0))
+                    .collect()
+            })
+            .collect();
+        for c in &clauses {
+            solver.add_clause_reuse(&mut c.clone());
+        }
+        solver.set_conflict_budget(50);
+        let first = solver.solve_limited(&[]);
+        solver.set_conflict_budget(u64::MAX / 2);
+
+        // the clone goes on exactly as the original solver
+        let mut fork = solver.clone();
+        let res = fork.solve_limited(&[]);
+        assert_eq!(res, solver.clone().solve_limited(&[]));
+        assert!(first == lbool::UNDEF || res == first);
+        let x = Lit::new(vars[0], true);
+        let mut pos = solver.clone();
+        let mut neg = solver.clone();
+        let res_pos = pos.solve_limited(&[x]);
+        let res_neg = neg.solve_limited(&[!x]);
+        assert_eq!(
+            res == lbool::TRUE,
+            res_pos == lbool::TRUE || res_neg == lbool::TRUE
+        );
+        for (s, r) in [(&pos, res_pos), (&neg, res_neg)] {
+            if r == lbool::TRUE {
+                let is_true = |l: Lit| s.value_lit(l) == lbool::TRUE;
+                assert!(clauses.iter().all(|c| c.iter().any(|&l| is_true(l))));
             }
         }
+        // with the trail of a model
+        let mut th = theory::EmptyTheory::new();
+        if solver.solve_limited_preserving_trail_th(&mut th, &[]) == lbool::TRUE {
+            // the theory is forked along
+            let (mut fork, mut fork_th) = (solver.clone(), th.clone());
+            assert!(vars
+                .iter()
+                .all(|&v| fork.raw_value_lit(Lit::new(v, true)) != lbool::UNDEF));
+            fork.pop_model(&mut fork_th);
+            assert_eq!(fork.solve_limited(&[]), lbool::TRUE);
+            solver.pop_model(&mut th);
+        }
+    }
+}
 
-        // All reasons:
-        for &lit in &self.vars.trail {
-            let v = lit.var();
+#[test]
+fn test_theory_decide() {
+    // a theory that decides the variables out of the formula positively, one at a time,
+    // then keeps proposing an assigned literal, which must be ignored
+    struct Decider {
+        levels: theory::EmptyTheory,
+        vars: Vec<Var>,
+        calls: usize,
+    }
+    impl Theory for Decider {
+        fn final_check(&mut self, _: &mut TheoryArg) {}
+        fn create_level(&mut self) {
+            self.levels.create_level()
+        }
+        fn pop_levels(&mut self, n: usize) {
+            self.levels.pop_levels(n)
+        }
+        fn n_levels(&self) -> usize {
+            self.levels.n_levels()
+        }
+        fn explain_propagation_clause(&mut self, _: Lit, _: &mut ExplainTheoryArg) -> &[Lit] {
+            unreachable!()
+        }
+        fn decide(&mut self, acts: &TheoryArg) -> Option<Lit> {
+            self.calls += 1;
+            let v = self.vars.iter().find(|&&v| acts.value(v) == lbool::UNDEF);
+            Some(Lit::new(*v.unwrap_or(&self.vars[0]), true))
+        }
+    }
+    let mut rng = crate::test_util::Rng::new(53);
+    let mut rand = |n| rng.next(n);
+    for _ in 0..10 {
+        let mut solver = crate::BasicSolver::default();
+        let vars: Vec<Var> = (0..50).map(|_| solver.new_var_default()).collect();
+        for _ in 0..150 {
+            let mut c: Vec<Lit> = (0..3)
+                .map(|_| Lit::new(vars[rand(50) as usize], rand(2) == 0))
+                .collect();
+            solver.add_clause_reuse(&mut c);
+        }
+        // not decision variables, so the solver leaves them unassigned
+        let free: Vec<Var> = (0..10)
+            .map(|_| solver.new_var(lbool::UNDEF, false))
+            .collect();
+        let expected = solver.clone().solve_limited(&[]);
+        let mut th = Decider {
+            levels: theory::EmptyTheory::new(),
+            vars: free.clone(),
+            calls: 0,
+        };
+        assert_eq!(solver.solve_limited_th(&mut th, &[]), expected);
+        if expected == lbool::TRUE {
+            assert!(free.iter().all(|&v| solver.value_var(v) == lbool::TRUE));
+            assert!(th.calls > free.len());
+        }
+    }
+}
 
-            // Note: it is not safe to call `locked()` on a relocated clause. This is why we keep
-            // `dangling` reasons here. It is safe and does not hurt.
-            let reason = self.reason(v);
-            if reason != CRef::UNDEF && reason != CRef::SPECIAL {
-                let cond = {
-                    let c = self.ca.get_ref(reason);
-                    c.reloced() || self.locked(c)
-                };
-                if cond {
-                    debug_assert!(!is_removed!(self.ca, reason));
-                    self.ca.reloc(&mut self.vars.vardata[v].reason, to);
+#[test]
+fn test_theory_on_assign() {
+    // a theory that keeps the notified literals in a stack, which must always match the
+    // trail when it is checked, with one decision per level
+    struct Mirror {
+        stack: Vec<(Lit, bool)>,
+        levels: Vec<usize>,
+        checks: usize,
+    }
+    impl Mirror {
+        fn check(&mut self, acts: &TheoryArg) {
+            let mut notified: Vec<Lit> = self.stack.iter().map(|&(lit, _)| lit).collect();
+            let mut model = acts.model().to_vec();
+            notified.sort_unstable();
+            model.sort_unstable();
+            assert_eq!(notified, model);
+            let decisions = self.stack.iter().filter(|&&(_, d)| d).count();
+            assert_eq!(decisions, self.levels.len());
+            self.checks += 1;
+        }
+    }
+    impl Theory for Mirror {
+        fn final_check(&mut self, acts: &mut TheoryArg) {
+            self.check(acts)
+        }
+        fn partial_check(&mut self, acts: &mut TheoryArg) {
+            self.check(acts)
+        }
+        fn on_assign(&mut self, lit: Lit, is_decision: bool) {
+            self.stack.push((lit, is_decision))
+        }
+        fn create_level(&mut self) {
+            self.levels.push(self.stack.len())
+        }
+        fn pop_levels(&mut self, n: usize) {
+            let len = self.levels[self.levels.len() - n];
+            self.levels.truncate(self.levels.len() - n);
+            self.stack.truncate(len);
+        }
+        fn n_levels(&self) -> usize {
+            self.levels.len()
+        }
+        fn explain_propagation_clause(&mut self, _: Lit, _: &mut ExplainTheoryArg) -> &[Lit] {
+            unreachable!()
+        }
+    }
+    let mut rng = crate::test_util::Rng::new(59);
+    let mut rand = |n| rng.next(n);
+    for i in 0..12 {
+        let mut solver = crate::BasicSolver::default();
+        solver.v.opts.chrono = [-1, 0, 100][i % 3];
+        let vars: Vec<Var> = (0..60).map(|_| solver.new_var_default()).collect();
+        for j in 0..255 {
+            let len = if j < 2 { 1 } else { 3 };
+            let mut c: Vec<Lit> = (0..len)
+                .map(|_| Lit::new(vars[rand(60) as usize], rand(2) == 0))
+                .collect();
+            solver.add_clause_reuse(&mut c);
+        }
+        let expected = solver.clone().solve_limited(&[]);
+        let mut th = Mirror {
+            stack: vec![],
+            levels: vec![],
+            checks: 0,
+        };
+        for _ in 0..2 {
+            assert_eq!(solver.solve_limited_th(&mut th, &[]), expected);
+        }
+        assert!(th.checks > 0 || !solver.is_ok());
+    }
+}
+
+#[test]
+fn test_theory_on_backtrack() {
+    // a theory that keeps the notified literals in a stack, undone by `on_backtrack`
+    // only, which must be the trail when it is checked
+    struct Trail {
+        levels: theory::EmptyTheory,
+        stack: Vec<Lit>,
+        checks: usize,
+    }
+    impl Trail {
+        fn check(&mut self, acts: &TheoryArg) {
+            assert_eq!(self.stack, acts.model());
+            self.checks += 1;
+        }
+    }
+    impl Theory for Trail {
+        fn final_check(&mut self, acts: &mut TheoryArg) {
+            self.check(acts)
+        }
+        fn partial_check(&mut self, acts: &mut TheoryArg) {
+            self.check(acts)
+        }
+        fn on_assign(&mut self, lit: Lit, _: bool) {
+            self.stack.push(lit)
+        }
+        fn on_backtrack(&mut self, new_level: u32, first_removed: usize) {
+            assert_eq!(new_level as usize, self.levels.n_levels());
+            assert!(first_removed <= self.stack.len());
+            self.stack.truncate(first_removed);
+        }
+        fn create_level(&mut self) {
+            self.levels.create_level()
+        }
+        fn pop_levels(&mut self, n: usize) {
+            self.levels.pop_levels(n)
+        }
+        fn n_levels(&self) -> usize {
+            self.levels.n_levels()
+        }
+        fn explain_propagation_clause(&mut self, _: Lit, _: &mut ExplainTheoryArg) -> &[Lit] {
+            unreachable!()
+        }
+    }
+    let mut rng = crate::test_util::Rng::new(73);
+    let mut rand = |n| rng.next(n);
+    for i in 0..12 {
+        let mut solver = crate::BasicSolver::default();
+        solver.v.opts.chrono = [-1, 0, 100][i % 3];
+        let vars: Vec<Var> = (0..60).map(|_| solver.new_var_default()).collect();
+        for _ in 0..255 {
+            let mut c: Vec<Lit> = (0..3)
+                .map(|_| Lit::new(vars[rand(60) as usize], rand(2) == 0))
+                .collect();
+            solver.add_clause_reuse(&mut c);
+        }
+        let expected = solver.clone().solve_limited(&[]);
+        let mut th = Trail {
+            levels: theory::EmptyTheory::new(),
+            stack: vec![],
+            checks: 0,
+        };
+        assert_eq!(solver.solve_limited_th(&mut th, &[]), expected);
+        assert!(th.checks > 0 || !solver.is_ok());
+    }
+}
+
+#[test]
+fn test_theory_stats() {
+    // a theory making the first 10 variables pairwise exclusive, by propagation or by
+    // conflict every other time: the statistics match its own counts of the calls
+    #[derive(Default)]
+    struct Exclusive {
+        levels: usize,
+        partial: u64,
+        finals: u64,
+        conflicts: u64,
+        propagations: u64,
+        explanations: u64,
+        expl: Vec<Lit>,
+    }
+    impl Exclusive {
+        fn check(&mut self, acts: &mut TheoryArg) {
+            let vars: Vec<Var> = (0..10).map(Var::from_idx).collect();
+            let true_vars: Vec<Var> = (vars.iter().copied())
+                .filter(|&v| acts.value(v) == lbool::TRUE)
+                .collect();
+            if true_vars.len() > 1 {
+                self.conflicts += 1;
+                let c = [Lit::new(true_vars[0], false), Lit::new(true_vars[1], false)];
+                return acts.raise_conflict(&c, false);
+            }
+            if let Some(&x) = true_vars.first() {
+                for &v in &vars {
+                    if acts.value(v) == lbool::UNDEF && (self.partial + self.finals) % 2 == 1 {
+                        self.propagations += 1;
+                        acts.propagate(Lit::new(v, false));
+                        self.expl = vec![Lit::new(v, false), Lit::new(x, false)];
+                    }
                 }
             }
         }
+    }
+    impl Theory for Exclusive {
+        fn final_check(&mut self, acts: &mut TheoryArg) {
+            self.finals += 1;
+            self.check(acts)
+        }
+        fn partial_check(&mut self, acts: &mut TheoryArg) {
+            self.partial += 1;
+            self.check(acts)
+        }
+        fn create_level(&mut self) {
+            self.levels += 1
+        }
+        fn pop_levels(&mut self, n: usize) {
+            self.levels -= n
+        }
+        fn n_levels(&self) -> usize {
+            self.levels
+        }
+        fn explain_propagation_clause(&mut self, p: Lit, _: &mut ExplainTheoryArg) -> &[Lit] {
+            // the explanations only depend on the true variable, found again here
+            self.explanations += 1;
+            self.expl[0] = p;
+            &self.expl
+        }
+    }
+    let mut rng = crate::test_util::Rng::new(61);
+    let mut rand = |n| rng.next(n);
+    let mut solver = crate::BasicSolver::default();
+    solver.v.opts.theory_timing = true;
+    let vars: Vec<Var> = (0..40).map(|_| solver.new_var_default()).collect();
+    for _ in 0..150 {
+        let mut c: Vec<Lit> = (0..3)
+            .map(|_| Lit::new(vars[rand(40) as usize], rand(3) == 0))
+            .collect();
+        solver.add_clause_reuse(&mut c);
+    }
+    let mut th = Exclusive::default();
+    for i in 0..4 {
+        let assumps = [Lit::new(vars[10 + i], i % 2 == 0)];
+        solver.solve_limited_th(&mut th, &assumps);
+        let stats = solver.stats().theory;
+        assert_eq!(stats.partial_checks, th.partial);
+        assert_eq!(stats.final_checks, th.finals);
+        assert_eq!(stats.conflicts, th.conflicts);
+        assert_eq!(stats.propagations, th.propagations);
+        assert_eq!(stats.explanations, th.explanations);
+    }
+    let stats = solver.stats().theory;
+    assert!(stats.conflicts > 0 && stats.propagations > 0 && stats.final_checks > 0);
+    #[cfg(feature = "std")]
+    assert!(stats.partial_check_time > Duration::ZERO && stats.level_time > Duration::ZERO);
+    #[cfg(not(feature = "std"))]
+    assert_eq!(stats.partial_check_time, Duration::ZERO);
+}
 
-        // All learnt:
-        {
-            let mut j = 0;
-            for i in 0..learnts.len() {
-                let mut cr = learnts[i];
-                if !is_removed!(self.ca, cr) {
-                    self.ca.reloc(&mut cr, to);
-                    learnts[j] = cr;
-                    j += 1;
+#[test]
+fn test_theory_prop_batch() {
+    // a theory making the first 4 variables pairwise exclusive by propagation
+    struct Exclusive {
+        levels: usize,
+        reason: Vec<Var>,
+        expl: [Lit; 2],
+    }
+    impl Theory for Exclusive {
+        fn final_check(&mut self, acts: &mut TheoryArg) {
+            self.partial_check(acts)
+        }
+        fn partial_check(&mut self, acts: &mut TheoryArg) {
+            let vars: Vec<Var> = (0..4).map(Var::from_idx).collect();
+            let Some(&x) = vars.iter().find(|&&v| acts.value(v) == lbool::TRUE) else {
+                return;
+            };
+            for &v in &vars {
+                if v != x && acts.value(v) != lbool::FALSE {
+                    self.reason[v.idx() as usize] = x;
+                    if !acts.propagate(Lit::new(v, false)) {
+                        return;
+                    }
                 }
             }
-            learnts.resize(j, CRef::UNDEF);
         }
+        fn create_level(&mut self) {
+            self.levels += 1
+        }
+        fn pop_levels(&mut self, n: usize) {
+            self.levels -= n
+        }
+        fn n_levels(&self) -> usize {
+            self.levels
+        }
+        fn explain_propagation_clause(&mut self, p: Lit, _: &mut ExplainTheoryArg) -> &[Lit] {
+            let x = self.reason[p.var().idx() as usize];
+            self.expl = [p, Lit::new(x, false)];
+            &self.expl
+        }
+    }
+    let x: Vec<Lit> = (0..11).map(|i| Lit::new(Var::from_idx(i), true)).collect();
+    for batch in [0, 1, 3] {
+        let new_solver = || {
+            let mut solver = crate::BasicSolver::default();
+            solver.v.opts.theory_prop_batch = batch;
+            for _ in 0..11 {
+                solver.new_var_default();
+            }
+            solver
+        };
+        let mut th = Exclusive {
+            levels: 0,
+            reason: vec![Var::UNDEF; 11],
+            expl: [Lit::UNDEF; 2],
+        };
+        let mut solver = new_solver();
+        solver.add_clause_reuse(&mut vec![x[0], x[1]]);
+        solver.add_clause_reuse(&mut vec![!x[5], x[2], x[3]]);
+        assert_eq!(solver.solve_limited_th(&mut th, &[x[0]]), lbool::TRUE);
+        assert!((1..4).all(|i| solver.value_lit(x[i]) == lbool::FALSE));
+        assert_eq!(solver.value_lit(x[5]), lbool::FALSE);
+        assert!(solver.v.th_queue.is_empty());
+        solver.add_clause_reuse(&mut vec![x[2], x[3]]);
+        assert_eq!(solver.solve_limited_th(&mut th, &[]), lbool::FALSE);
+
+        // the native constraints assign their propagations right away, their
+        // explanations refer to the trail
+        let mut solver = new_solver();
+        solver.add_at_most_k(&[x[1], !x[4], !x[5], !x[8]], 2);
+        solver.add_at_most_k(&[x[1], !x[5], x[8], x[9]], 1);
+        assert_eq!(
+            solver.solve_limited(&[!x[10]]),
+            lbool::TRUE,
+            "batch {}",
+            batch
+        );
+        solver.add_pb_constraint(&[(-2, x[5]), (0, !x[5]), (-1, !x[3]), (1, !x[6])], 0);
+        assert_eq!(
+            solver.solve_limited(&[x[2], !x[1]]),
+            lbool::TRUE,
+            "batch {}",
+            batch
+        );
+    }
+}
 
-        // All original:
-        {
-            let mut j = 0;
-            for i in 0..clauses.len() {
-                let mut cr = clauses[i];
-                if !is_removed!(self.ca, cr) {
-                    self.ca.reloc(&mut cr, to);
-                    clauses[j] = cr;
-                    j += 1;
+#[test]
+fn test_explain_cache_compact() {
+    // the explanations of unassigned variables do not accumulate
+    let mut cache = ExplainCache::default();
+    let lits: Vec<Lit> = (0..4).map(|i| Lit::new(Var::from_idx(i), true)).collect();
+    for _ in 0..100 {
+        for &p in &lits[..3] {
+            cache.insert(p, &lits[1..]);
+        }
+        for &p in &lits[1..3] {
+            cache.remove(p.var());
+        }
+    }
+    assert!(cache.lits.len() <= 20, "{}", cache.lits.len());
+    assert!(cache.contains(lits[0]) && !cache.contains(lits[1]));
+    assert_eq!(cache.get(lits[0]), &lits[1..]);
+}
+
+#[test]
+fn test_cache_explanations() {
+    // a theory making the first 8 variables pairwise exclusive by propagation, that
+    // fails if it is asked twice to explain the same propagation with the cache (but
+    // not a propagation of a false literal, which is a conflict)
+    struct Exclusive {
+        levels: usize,
+        reason: Vec<Var>,
+        explained: Vec<bool>,
+        conflict: Lit,
+        cached: bool,
+        expl: [Lit; 2],
+    }
+    impl Theory for Exclusive {
+        fn final_check(&mut self, acts: &mut TheoryArg) {
+            self.partial_check(acts)
+        }
+        fn partial_check(&mut self, acts: &mut TheoryArg) {
+            let vars: Vec<Var> = (0..8).map(Var::from_idx).collect();
+            let Some(&x) = vars.iter().find(|&&v| acts.value(v) == lbool::TRUE) else {
+                return;
+            };
+            for &v in &vars {
+                if v != x && acts.value(v) != lbool::FALSE {
+                    self.reason[v.idx() as usize] = x;
+                    self.explained[v.idx() as usize] = false;
+                    if !acts.propagate(Lit::new(v, false)) {
+                        self.conflict = Lit::new(v, false);
+                        return;
+                    }
                 }
             }
-            clauses.resize(j, CRef::UNDEF);
+        }
+        fn create_level(&mut self) {
+            self.levels += 1
+        }
+        fn pop_levels(&mut self, n: usize) {
+            self.levels -= n
+        }
+        fn n_levels(&self) -> usize {
+            self.levels
+        }
+        fn explain_propagation_clause(&mut self, p: Lit, _: &mut ExplainTheoryArg) -> &[Lit] {
+            let i = p.var().idx() as usize;
+            let twice = self.explained[i] && p != self.conflict;
+            assert!(!(self.cached && twice), "{:?} explained twice", p);
+            self.explained[i] = true;
+            self.expl = [p, Lit::new(self.reason[i], false)];
+            &self.expl
         }
     }
-
-    /// Attach a clause to watcher lists
-    fn attach_clause(&mut self, cr: CRef) {
-        let (c0, c1, learnt, size) = {
-            let c = self.ca.get_ref(cr);
-            debug_assert!(c.size() > 1);
-            (c[0], c[1], c.learnt(), c.size())
-        };
-        self.watches()[!c0].push(Watcher::new(cr, c1));
-        self.watches()[!c1].push(Watcher::new(cr, c0));
-        if learnt {
-            self.num_learnts += 1;
-            self.learnts_literals += size as u64;
-        } else {
-            self.num_clauses += 1;
-            self.clauses_literals += size as u64;
+    let mut rng = crate::test_util::Rng::new(31);
+    let mut rand = |n| rng.next(n);
+    let mut cached = 0;
+    for _ in 0..30 {
+        let clauses: Vec<Vec<Lit>> = (0..150)
+            .map(|_| {
+                (0..3)
+                    .map(|_| Lit::new(Var::from_idx(rand(40) as u32), rand(3) != 0))
+                    .collect()
+            })
+            .collect();
+        let mut results = vec![];
+        for (cache, chrono) in [(false, -1), (true, -1), (true, 0)] {
+            let mut solver = crate::BasicSolver::default();
+            solver.v.opts.cache_explanations = cache;
+            solver.v.opts.chrono = chrono;
+            for _ in 0..40 {
+                solver.new_var_default();
+            }
+            for c in &clauses {
+                solver.add_clause_reuse(&mut c.clone());
+            }
+            let mut th = Exclusive {
+                levels: 0,
+                reason: vec![Var::UNDEF; 40],
+                explained: vec![false; 40],
+                conflict: Lit::UNDEF,
+                cached: cache,
+                expl: [Lit::UNDEF; 2],
+            };
+            results.push(solver.solve_limited_th(&mut th, &[]));
+            let stats = solver.stats().theory;
+            if cache {
+                cached += stats.cached_explanations;
+            } else {
+                assert_eq!(stats.cached_explanations, 0);
+            }
         }
+        assert!(results.iter().all(|&r| r == results[0]));
     }
+    assert!(cached > 0);
+}
 
-    /// Revert to the state at given level (keeping all assignment at `level` but not beyond).
-    fn cancel_until(&mut self, level: u32) {
-        debug_assert!(self.decision_level() > level);
-        let trail_lim_last = *self.vars.trail_lim.last().expect("trail_lim is empty") as usize;
-        let trail_lim_level = self.vars.trail_lim[level as usize] as usize;
-        for c in trail_lim_level..self.vars.trail.len() {
-            let x = self.vars.trail[c].var();
-            self.vars.ass[x] = lbool::UNDEF;
-            if self.opts.phase_saving > 1 || (self.opts.phase_saving == 1 && c > trail_lim_last) {
-                self.polarity[x] = self.vars.trail[c].sign();
+#[test]
+fn test_explain_definitions() {
+    // a theory implying `p` from the conjunction of 8 variables, explained by a tree of
+    // definitions; once its root is true in the solver, it explains `p` alone
+    struct And {
+        levels: usize,
+        xs: Vec<Lit>,
+        p: Lit,
+        root: Option<Lit>,
+        use_root: bool,
+        expl: Vec<Lit>,
+    }
+    impl And {
+        fn check(&mut self, acts: &mut TheoryArg) {
+            if acts.value_lit(self.p) != lbool::TRUE
+                && self.xs.iter().all(|&x| acts.value_lit(x) == lbool::TRUE)
+            {
+                self.use_root = self.root.is_some_and(|r| acts.value_lit(r) == lbool::TRUE);
+                acts.propagate(self.p);
             }
-            self.insert_var_order(x);
         }
-        self.qhead = trail_lim_level as i32;
-        self.vars.trail.truncate(trail_lim_level);
-        // eprintln!("decision_level {} -> {}", self.trail_lim.len(), level);
-        self.vars.trail_lim.truncate(level as usize);
     }
+    impl Theory for And {
+        fn final_check(&mut self, acts: &mut TheoryArg) {
+            self.check(acts)
+        }
+        fn partial_check(&mut self, acts: &mut TheoryArg) {
+            self.check(acts)
+        }
+        fn create_level(&mut self) {
+            self.levels += 1
+        }
+        fn pop_levels(&mut self, n: usize) {
+            self.levels -= n
+        }
+        fn n_levels(&self) -> usize {
+            self.levels
+        }
+        fn explain_propagation_clause(&mut self, p: Lit, st: &mut ExplainTheoryArg) -> &[Lit] {
+            assert_eq!(p, self.p);
+            let root = match self.root {
+                Some(r) if self.use_root => r,
+                Some(_) => {
+                    self.expl = vec![p];
+                    self.expl.extend(self.xs.iter().map(|&x| !x));
+                    return &self.expl;
+                }
+                None => {
+                    let mut layer = self.xs.clone();
+                    while layer.len() > 1 {
+                        layer = layer.chunks(2).map(|c| st.define_and(c)).collect();
+                    }
+                    self.root = Some(layer[0]);
+                    layer[0]
+                }
+            };
+            self.expl = vec![p, !root];
+            &self.expl
+        }
+    }
+    let new_theory = |solver: &mut crate::BasicSolver| {
+        let xs: Vec<Lit> = (0..8)
+            .map(|_| Lit::new(solver.new_var_default(), true))
+            .collect();
+        let p = Lit::new(solver.new_var_default(), true);
+        And {
+            levels: 0,
+            xs,
+            p,
+            root: None,
+            use_root: false,
+            expl: vec![],
+        }
+    };
 
-    /// Detach a clause from watcher lists.
-    ///
-    /// param `strict` means we remove the clause from watchers eagerly, instead
-    /// of just marking the watchlist as "dirty"
-    fn detach_clause(&mut self, cr: CRef, strict: bool) {
-        let (c0, c1, csize, clearnt) = {
-            let c = self.ca.get_ref(cr);
-            (c[0], c[1], c.size(), c.learnt())
-        };
-        debug_assert!(csize > 1);
-
-        let mut watches = self.watches_data.promote(WatcherDeleted { ca: &self.ca });
+    // the conflict is analyzed with the assumptions, the 7 definitions are then created
+    let mut solver = crate::BasicSolver::default();
+    let mut th = new_theory(&mut solver);
+    let xs = th.xs.clone();
+    solver.add_clause_reuse(&mut vec![!th.p]);
+    assert_eq!(solver.solve_limited_th(&mut th, &xs), lbool::FALSE);
+    assert_eq!(solver.num_vars(), 16);
+    assert!((9..16).all(|i| solver.v.frozen[Var::from_idx(i)]));
+    let root = th.root.unwrap();
+    let mut assumps = xs[..7].to_vec();
+    assumps.push(!root);
+    assert_eq!(solver.solve_limited_th(&mut th, &assumps), lbool::TRUE);
+    assert_eq!(solver.value_lit(xs[7]), lbool::FALSE);
+    assert_eq!(
+        solver.solve_limited_th(&mut th, &[root, !xs[3]]),
+        lbool::FALSE
+    );
+    assert_eq!(solver.solve_limited_th(&mut th, &xs), lbool::FALSE);
+
+    // the conflict is analyzed during the search, after deciding the last variable
+    let mut solver = crate::BasicSolver::default();
+    let mut th = new_theory(&mut solver);
+    let xs = th.xs.clone();
+    solver.v.user_pol[xs[7].var()] = lbool::TRUE;
+    let y = Lit::new(solver.new_var_default(), true);
+    solver.add_clause_reuse(&mut vec![!th.p, y]);
+    solver.add_clause_reuse(&mut vec![!th.p, !y]);
+    assert_eq!(solver.solve_limited_th(&mut th, &xs[..7]), lbool::TRUE);
+    assert_eq!(solver.value_lit(xs[7]), lbool::FALSE);
+    assert_eq!(solver.num_vars(), 17);
+    assert_eq!(solver.value_lit(th.root.unwrap()), lbool::FALSE);
+}
 
-        // Strict or lazy detaching:
-        if strict {
-            // watches[!c0].remove_item(&Watcher::new(cr, c1));
-            // watches[!c1].remove_item(&Watcher::new(cr, c0));
-            let pos = watches[!c0]
-                .iter()
-                .position(|x| x == &Watcher::new(cr, c1))
-                .expect("Watcher not found");
-            watches[!c0].remove(pos);
-            let pos = watches[!c1]
-                .iter()
-                .position(|x| x == &Watcher::new(cr, c0))
-                .expect("Watcher not found");
-            watches[!c1].remove(pos);
-        } else {
-            watches.smudge(!c0);
-            watches.smudge(!c1);
+#[test]
+fn test_polarity_and_activity() {
+    let heuristics = [
+        BranchingHeuristic::Vsids,
+        BranchingHeuristic::Vmtf,
+        BranchingHeuristic::Chb,
+        BranchingHeuristic::Lrb,
+    ];
+    for (i, &heuristic) in heuristics.iter().enumerate() {
+        let mut solver = crate::BasicSolver::default();
+        solver.v.opts.branching = heuristic;
+        solver.v.reset_branching();
+        let vars: Vec<Var> = (0..20).map(|_| solver.new_var_default()).collect();
+        // the boosted variable is decided first
+        let v = vars[7 + i];
+        solver.bump_activity(v, 10.0);
+        assert!(vars
+            .iter()
+            .all(|&x| x == v || solver.activity(x) < solver.activity(v)));
+        assert_eq!(solver.v.pick_branch_lit().var(), v);
+        solver.v.vars.branch.insert(v);
+        // without clauses, the model follows the phases
+        for (j, &x) in vars.iter().enumerate() {
+            solver.set_polarity(x, j % 3 == 0);
+            assert_eq!(solver.polarity(x), j % 3 == 0);
         }
-
-        if clearnt {
-            self.num_learnts -= 1;
-            self.learnts_literals -= csize as u64;
-        } else {
-            self.num_clauses -= 1;
-            self.clauses_literals -= csize as u64;
+        solver.set_decision_var(vars[0], false);
+        assert!(!solver.is_decision_var(vars[0]));
+        assert_eq!(solver.solve_limited(&[]), lbool::TRUE);
+        for (j, &x) in vars.iter().enumerate().skip(1) {
+            assert_eq!(solver.value_var(x), lbool::new(j % 3 == 0));
         }
     }
+}
 
-    /// Detach and free a clause.
-    fn remove_clause(&mut self, cr: CRef) {
-        self.detach_clause(cr, false);
-        {
-            let c = self.ca.get_ref(cr);
-            // Don't leave pointers to free'd memory!
-            if self.locked(c) {
-                self.vars.vardata[c[0].var()].reason = CRef::UNDEF;
-            }
+#[test]
+fn test_initial_phases() {
+    // random 3-SAT formulas under the threshold: solved again without conflicts from
+    // their model
+    let mut rng = crate::test_util::Rng::new(79);
+    let mut rand = |n| rng.next(n);
+    let mut conflicts = 0;
+    for _ in 0..8 {
+        let mut solver = crate::BasicSolver::default();
+        let vars: Vec<Var> = (0..100).map(|_| solver.new_var_default()).collect();
+        for _ in 0..380 {
+            let mut c: Vec<Lit> = (0..3)
+                .map(|_| Lit::new(vars[rand(100) as usize], rand(2) == 0))
+                .collect();
+            solver.add_clause_reuse(&mut c);
         }
-        self.ca.get_mut(cr).set_mark(1); // used in reloc
-        self.ca.free(cr);
+        let mut warm = solver.clone();
+        if solver.solve_limited(&[]) != lbool::TRUE {
+            continue;
+        }
+        conflicts += solver.num_conflicts();
+        let model: Vec<Lit> = vars
+            .iter()
+            .map(|&v| Lit::new(v, solver.value_var(v) != lbool::FALSE))
+            .collect();
+        warm.set_initial_phases(&model);
+        assert_eq!(warm.solve_limited(&[]), lbool::TRUE);
+        assert_eq!(warm.num_conflicts(), 0);
     }
+    assert!(conflicts > 0);
+}
 
-    pub fn satisfied(&self, c: ClauseRef) -> bool {
-        c.iter().any(|&lit| self.value_lit(lit) == lbool::TRUE)
+#[test]
+fn test_stats() {
+    // random 3-SAT formulas at the threshold: the statistics agree with the counters of
+    // the solver, and accumulate over the calls
+    let mut rng = crate::test_util::Rng::new(83);
+    let mut rand = |n| rng.next(n);
+    let mut solver = crate::BasicSolver::default();
+    assert_eq!(solver.stats(), Stats::default());
+    let vars: Vec<Var> = (0..150).map(|_| solver.new_var_default()).collect();
+    for _ in 0..640 {
+        let mut c: Vec<Lit> = (0..3)
+            .map(|_| Lit::new(vars[rand(150) as usize], rand(2) == 0))
+            .collect();
+        solver.add_clause_reuse(&mut c);
+    }
+    let mut prev = solver.stats();
+    for (i, &v) in vars[..4].iter().enumerate() {
+        let assumps = [Lit::new(v, i % 2 == 0)];
+        solver.solve_limited(&assumps);
+        let stats = solver.stats();
+        assert_eq!(stats.solves, i as u64 + 1);
+        assert_eq!(stats.conflicts, solver.num_conflicts());
+        assert_eq!(stats.decisions, solver.num_decisions());
+        assert_eq!(stats.propagations, solver.num_propagations());
+        assert_eq!(stats.restarts, solver.num_restarts());
+        assert_eq!(stats.clauses, solver.num_clauses());
+        assert!(stats.conflicts >= prev.conflicts);
+        assert!(stats.learnt_literals >= prev.learnt_literals);
+        assert!(stats.deleted_clauses >= prev.deleted_clauses);
+        assert!(stats.gcs >= prev.gcs);
+        assert!(stats.clause_memory > 0);
+        prev = stats;
+    }
+    assert!(prev.conflicts > 0 && prev.learnt_literals > 0);
+    #[cfg(feature = "json")]
+    {
+        let json: serde_json::Value = serde_json::from_str(&prev.to_json()).unwrap();
+        assert_eq!(json["conflicts"], prev.conflicts);
+        assert_eq!(json["gcs"], prev.gcs);
     }
+}
 
-    #[inline(always)]
-    pub fn decision_level(&self) -> u32 {
-        self.vars.decision_level()
+#[test]
+fn test_garbage_collect_order() {
+    // the clauses watched by the literals of the trail are moved first
+    let mut solver = crate::BasicSolver::default();
+    let lits: Vec<Lit> = (0..20)
+        .map(|_| Lit::new(solver.new_var_default(), true))
+        .collect();
+    for i in 0..20 {
+        for j in 1..4 {
+            let mut c = vec![lits[i], lits[(i + j) % 20], !lits[(i + 2 * j) % 20]];
+            solver.add_clause_reuse(&mut c);
+        }
     }
+    let removed = solver.clauses.swap_remove(0);
+    let size = solver.v.ca.get_ref(removed).size();
+    solver.v.remove_clause(removed);
+    solver.v.vars.new_decision_level();
+    let p = !lits[7];
+    solver.v.vars.unchecked_enqueue(p, CRef::UNDEF);
+    solver.garbage_collect();
+    let first: Vec<CRef> = solver.v.watches_data[p].iter().map(|w| w.cref).collect();
+    assert!(!first.is_empty());
+    let others = solver.clauses.iter().filter(|cr| !first.contains(cr));
+    let last_first = first.iter().max().unwrap();
+    assert!(others.into_iter().all(|cr| cr > last_first));
+    let stats = solver.stats();
+    assert_eq!(stats.gcs, 1);
+    let unit = ClauseAllocator::UNIT_SIZE as u64 / 8;
+    assert_eq!(stats.gc_reclaimed, (size as u64 + 1) * unit);
+    assert_eq!(solver.clauses.len(), 59);
+    assert!(solver
+        .clauses
+        .iter()
+        .all(|&cr| solver.v.ca.get_ref(cr).size() == 3));
+}
 
-    #[inline(always)]
-    fn reason(&self, x: Var) -> CRef {
-        self.vars.reason(x)
+#[test]
+fn test_reduce_db_callback() {
+    // the reductions of the learnt clauses are reported to the callbacks
+    #[derive(Default)]
+    struct Reductions {
+        calls: u64,
+        deleted: u64,
+    }
+    impl Callbacks for Reductions {
+        fn on_reduce_db(&mut self, _kept: usize, deleted: usize) {
+            self.calls += 1;
+            self.deleted += deleted as u64;
+        }
     }
+    let mut rng = crate::test_util::Rng::new(89);
+    let mut rand = |n| rng.next(n);
+    let mut solver = Solver::new(SolverOpts::default(), Reductions::default());
+    let vars: Vec<Var> = (0..200).map(|_| solver.new_var_default()).collect();
+    for _ in 0..852 {
+        let mut c: Vec<Lit> = (0..3)
+            .map(|_| Lit::new(vars[rand(200) as usize], rand(2) == 0))
+            .collect();
+        solver.add_clause_reuse(&mut c);
+    }
+    solver.solve_limited(&[]);
+    assert!(solver.cb().calls > 0);
+    assert_eq!(solver.cb().deleted, solver.stats().deleted_clauses);
+}
 
-    /// Returns `true` if a clause is a reason for some implication in the current state.
-    fn locked(&self, c: ClauseRef) -> bool {
-        let reason = self.reason(c[0].var());
-        self.value_lit(c[0]) == lbool::TRUE
-            && reason != CRef::UNDEF
-            && reason != CRef::SPECIAL
-            && self.ca.get_ref(reason) == c
+#[test]
+fn test_clause_listener() {
+    // the deleted clauses were learnt (or strengthened) before
+    use std::collections::BTreeMap;
+    use std::sync::atomic::{AtomicUsize, Ordering::Relaxed};
+    #[derive(Default)]
+    struct Counts {
+        learnt: AtomicUsize,
+        deleted: AtomicUsize,
+        strengthened: AtomicUsize,
+    }
+    struct Listener {
+        clauses: BTreeMap<Vec<Lit>, usize>,
+        counts: Arc<Counts>,
+    }
+    fn sorted(c: &[Lit]) -> Vec<Lit> {
+        let mut c = c.to_vec();
+        c.sort();
+        c
+    }
+    impl ClauseListener for Listener {
+        fn on_learnt(&mut self, c: &[Lit], lbd: u32) {
+            assert!(lbd as usize <= c.len());
+            self.counts.learnt.fetch_add(1, Relaxed);
+            *self.clauses.entry(sorted(c)).or_default() += 1;
+        }
+        fn on_deleted(&mut self, c: &[Lit]) {
+            self.counts.deleted.fetch_add(1, Relaxed);
+            let n = self.clauses.get_mut(&sorted(c)).expect("unknown clause");
+            *n -= 1;
+        }
+        fn on_strengthened(&mut self, old: &[Lit], new: &[Lit]) {
+            assert!(new.len() < old.len() && new.iter().all(|l| old.contains(l)));
+            self.counts.strengthened.fetch_add(1, Relaxed);
+            if let Some(n) = self.clauses.get_mut(&sorted(old)).filter(|n| **n > 0) {
+                *n -= 1;
+                *self.clauses.entry(sorted(new)).or_default() += 1;
+            }
+        }
     }
-    // inline bool     Solver::locked          (const Clause& c) const { return value(c[0]) == l_True && reason(var(c[0])) != CRef_Undef && ca.lea(reason(var(c[0]))) == &c; }
+    let mut rng = crate::test_util::Rng::new(97);
+    let mut rand = |n| rng.next(n);
+    let counts = Arc::new(Counts::default());
+    let mut solver = crate::BasicSolver::default();
+    solver.set_clause_listener(Listener {
+        clauses: BTreeMap::new(),
+        counts: counts.clone(),
+    });
+    let vars: Vec<Var> = (0..200).map(|_| solver.new_var_default()).collect();
+    for _ in 0..852 {
+        let mut c: Vec<Lit> = (0..3)
+            .map(|_| Lit::new(vars[rand(200) as usize], rand(2) == 0))
+            .collect();
+        solver.add_clause_reuse(&mut c);
+    }
+    solver.solve_limited(&[]);
+    assert!(counts.learnt.load(Relaxed) as u64 >= solver.num_conflicts() / 2);
+    assert!(counts.deleted.load(Relaxed) > 0 && counts.strengthened.load(Relaxed) > 0);
+    assert!(solver.take_clause_listener().is_some());
+}
 
-    fn progress_estimate(&self) -> f64 {
-        let mut progress = 0.0;
-        let f = 1.0 / self.num_vars() as f64;
-        let mut f_pow_i = 1.0;
+#[test]
+fn test_solver_is_send() {
+    // the callbacks are `Send`, so that solvers can be moved to other threads
+    fn is_send<T: Send>(_: &T) {}
+    let mut solver = crate::BasicSolver::default();
+    solver.set_terminate_callback(|| false);
+    is_send(&solver);
+}
 
-        for i in 0..self.decision_level() + 1 {
-            let beg: i32 = if i == 0 {
-                0
-            } else {
-                self.vars.trail_lim[i as usize - 1]
-            };
-            let end: i32 = if i == self.decision_level() {
-                self.vars.trail.len() as i32
-            } else {
-                self.vars.trail_lim[i as usize]
-            };
-            progress += f_pow_i * (end - beg) as f64;
-            f_pow_i *= f;
+#[test]
+fn test_opts_presets() {
+    // the presets are valid, and the invalid options are reported by name
+    let presets = [
+        SolverOpts::sat_preset(),
+        SolverOpts::unsat_preset(),
+        SolverOpts::incremental_preset(),
+    ];
+    assert!(presets.iter().all(|o| o.validate().is_ok()));
+    let err = SolverOptsBuilder::new()
+        .tier1_lbd(8)
+        .tier2_lbd(4)
+        .build()
+        .err();
+    assert_eq!(err.map(|e| e.option), Some("tier1_lbd"));
+    let err = SolverOpts::sat_preset()
+        .into_builder()
+        .restart_inc(1.0)
+        .build()
+        .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "invalid option `restart_inc`: expected finite and greater than 1"
+    );
+
+    // the options change between the calls, invalid ones are rejected
+    let mut rng = crate::test_util::Rng::new(101);
+    let mut rand = |n| rng.next(n);
+    let mut solver = crate::BasicSolver::default();
+    let vars: Vec<Var> = (0..100).map(|_| solver.new_var_default()).collect();
+    for _ in 0..426 {
+        let mut c: Vec<Lit> = (0..3)
+            .map(|_| Lit::new(vars[rand(100) as usize], rand(2) == 0))
+            .collect();
+        solver.add_clause_reuse(&mut c);
+    }
+    let mut results = vec![];
+    for opts in presets {
+        solver.set_options(opts).unwrap();
+        results.push(solver.solve_limited(&[Lit::new(vars[0], true)]));
+    }
+    assert!(results.iter().all(|&r| r == results[0]), "{:?}", results);
+    let bad = SolverOpts {
+        ccmin_mode: 3,
+        ..SolverOpts::default()
+    };
+    assert_eq!(solver.set_options(bad).unwrap_err().option, "ccmin_mode");
+    assert_eq!(solver.options().probe_effort, 0.02);
+}
+
+#[test]
+fn test_set_option() {
+    let mut opts = SolverOpts::default();
+    opts.set("branching", "vmtf").unwrap();
+    opts.set("probe", "true").unwrap();
+    opts.set("garbage_frac", "0.5").unwrap();
+    assert_eq!(opts.branching, BranchingHeuristic::Vmtf);
+    assert!(opts.probe);
+    assert_eq!(opts.garbage_frac, 0.5);
+    assert_eq!(
+        opts.set("no_such_option", "1"),
+        Err(SetOptionError::Unknown("no_such_option".to_string()))
+    );
+    assert_eq!(
+        opts.set("restart_strategy", "sometimes"),
+        Err(SetOptionError::Parse("restart_strategy"))
+    );
+    let err = opts.set("random_var_freq", "2").unwrap_err();
+    assert!(matches!(err, SetOptionError::Invalid(e) if e.option == "random_var_freq"));
+    assert_eq!(opts.random_var_freq, 0.0);
+
+    // the same solver is reconfigured between the calls
+    let mut rng = crate::test_util::Rng::new(103);
+    let mut rand = |n| rng.next(n);
+    let mut solver = crate::BasicSolver::default();
+    let vars: Vec<Var> = (0..100).map(|_| solver.new_var_default()).collect();
+    for _ in 0..426 {
+        let mut c: Vec<Lit> = (0..3)
+            .map(|_| Lit::new(vars[rand(100) as usize], rand(2) == 0))
+            .collect();
+        solver.add_clause_reuse(&mut c);
+    }
+    let mut results = vec![];
+    for (name, value) in [
+        ("restart_strategy", "glucose"),
+        ("branching", "lrb"),
+        ("tier2_lbd", "4"),
+        ("lbd_reduce", "true"),
+    ] {
+        solver.set_option(name, value).unwrap();
+        results.push(solver.solve_limited(&[Lit::new(vars[1], false)]));
+    }
+    assert!(results.iter().all(|&r| r == results[0]), "{:?}", results);
+    assert!(solver.set_option("tier1_lbd", "5").is_err());
+    assert_eq!(solver.options().tier1_lbd, 2);
+}
+
+#[test]
+fn test_memory_limit() {
+    let mut rng = crate::test_util::Rng::new(11);
+    let mut rand = |n| rng.next(n);
+    let mut solver = crate::BasicSolver::default();
+    let vars: Vec<Var> = (0..250).map(|_| solver.new_var_default()).collect();
+    for _ in 0..1065 {
+        let mut c: Vec<Lit> = (0..3)
+            .map(|_| Lit::new(vars[rand(250) as usize], rand(2) == 0))
+            .collect();
+        solver.add_clause_reuse(&mut c);
+    }
+    let base = solver.memory_usage();
+    assert!(base > 0);
+    let mut unlimited = solver.clone();
+
+    // too low to search at all
+    solver.set_memory_limit(base / 2);
+    assert_eq!(solver.solve_limited(&[]), lbool::UNDEF);
+    assert!(solver.memory_limit_reached());
+
+    // learnt clauses are deleted to get under the limit
+    solver.set_memory_limit(usize::MAX);
+    solver.set_conflict_budget(3000);
+    assert_eq!(solver.solve_limited(&[]), lbool::UNDEF);
+    let (usage, learnts) = (solver.memory_usage(), solver.learnts.len() as u64);
+    let deleted = solver.stats().deleted_clauses;
+    solver.set_memory_limit(usage - 1);
+    solver.set_conflict_budget(1);
+    assert_eq!(solver.solve_limited(&[]), lbool::UNDEF);
+    assert!(!solver.memory_limit_reached());
+    assert!(solver.memory_usage() < usage);
+    assert!(solver.stats().deleted_clauses >= deleted + learnts / 4);
+
+    solver.set_memory_limit(usize::MAX);
+    solver.budget_off();
+    let res = solver.solve_limited(&[]);
+    assert!(!solver.memory_limit_reached());
+    assert_eq!(res, unlimited.solve_limited(&[]));
+}
+
+#[test]
+fn test_mode_switching() {
+    // random 3-SAT formulas at the threshold, solved with and without mode switches
+    let mut rng = crate::test_util::Rng::new(107);
+    let mut rand = |n| rng.next(n);
+    let opts = SolverOpts {
+        mode_switching: true,
+        mode_init: 100,
+        ..SolverOpts::default()
+    };
+    let mut switches = 0;
+    for _ in 0..4 {
+        let mut solver = Solver::new(opts.clone(), crate::callbacks::Basic::new());
+        let vars: Vec<Var> = (0..200).map(|_| solver.new_var_default()).collect();
+        let clauses: Vec<Vec<Lit>> = (0..852)
+            .map(|_| {
+                (0..3)
+                    .map(|_| Lit::new(vars[rand(200) as usize], rand(2) == 0))
+                    .collect()
+            })
+            .collect();
+        let mut reference = crate::BasicSolver::default();
+        for _ in 0..200 {
+            reference.new_var_default();
+        }
+        for c in &clauses {
+            solver.add_clause_reuse(&mut c.clone());
+            reference.add_clause_reuse(&mut c.clone());
         }
+        let res = solver.solve_limited(&[]);
+        assert_eq!(res, reference.solve_limited(&[]));
+        if res == lbool::TRUE {
+            let model = solver.get_model();
+            let is_true = |l: Lit| model[l.var().idx() as usize] == lbool::new(l.sign());
+            assert!(clauses.iter().all(|c| c.iter().any(|&l| is_true(l))));
+        }
+        switches += solver.stats().mode_switches;
+        assert_eq!(solver.v.stable, solver.stats().mode_switches % 2 == 1);
+    }
+    assert!(switches >= 2, "{} switches", switches);
+}
 
-        progress / self.num_vars() as f64
+#[test]
+fn test_theory_new_var() {
+    // a theory that creates variables in the middle of the search, and propagates some
+    // of them, which the solver must then assign like the others
+    struct Splitter {
+        levels: theory::EmptyTheory,
+        created: Vec<Var>,
+        max: usize,
+    }
+    impl Theory for Splitter {
+        fn final_check(&mut self, _: &mut TheoryArg) {}
+        fn partial_check(&mut self, acts: &mut TheoryArg) {
+            if self.n_levels() > 0 && self.created.len() < self.max {
+                let v = acts.new_var();
+                if self.created.len().is_multiple_of(2) {
+                    acts.propagate(Lit::new(v, true));
+                }
+                self.created.push(v);
+            }
+        }
+        fn create_level(&mut self) {
+            self.levels.create_level()
+        }
+        fn pop_levels(&mut self, n: usize) {
+            self.levels.pop_levels(n)
+        }
+        fn n_levels(&self) -> usize {
+            self.levels.n_levels()
+        }
+        fn explain_propagation_clause(&mut self, _: Lit, _: &mut ExplainTheoryArg) -> &[Lit] {
+            unreachable!("no clause contains the new variables")
+        }
     }
-    fn new(opts: &SolverOpts) -> Self {
-        Self {
-            opts: opts.clone(),
-            vars: VarState::new(),
-            num_clauses: 0,
-            num_learnts: 0,
-            clauses_literals: 0,
-            learnts_literals: 0,
+    let heuristics = [
+        BranchingHeuristic::Vsids,
+        BranchingHeuristic::Vmtf,
+        BranchingHeuristic::Chb,
+        BranchingHeuristic::Lrb,
+    ];
+    let mut rng = crate::test_util::Rng::new(61);
+    let mut rand = |n| rng.next(n);
+    for i in 0..12 {
+        let mut solver = crate::BasicSolver::default();
+        solver.v.opts.branching = heuristics[i % 4];
+        solver.v.reset_branching();
+        let vars: Vec<Var> = (0..50).map(|_| solver.new_var_default()).collect();
+        let clauses: Vec<Vec<Lit>> = (0..200)
+            .map(|_| {
+                (0..3)
+                    .map(|_| Lit::new(vars[rand(50) as usize], rand(2) == 0))
+                    .collect()
+            })
+            .collect();
+        for c in &clauses {
+            solver.add_clause_reuse(&mut c.clone());
+        }
+        let expected = solver.clone().solve_limited(&[]);
+        let mut th = Splitter {
+            levels: theory::EmptyTheory::new(),
+            created: vec![],
+            max: 20,
+        };
+        assert_eq!(solver.solve_limited_th(&mut th, &[]), expected);
+        assert_eq!(solver.num_vars(), 50 + th.created.len() as u32);
+        assert!(th
+            .created
+            .iter()
+            .all(|&v| v.idx() >= 50 && solver.v.frozen[v]));
+        if expected == lbool::TRUE {
+            assert!(th
+                .created
+                .iter()
+                .all(|&v| solver.value_var(v) != lbool::UNDEF));
+            let is_true = |l: Lit| solver.value_lit(l) == lbool::TRUE;
+            assert!(clauses.iter().all(|c| c.iter().any(|&l| is_true(l))));
+        }
+        // the new variables are usable in clauses afterwards
+        if let Some(&v) = th.created.first() {
+            solver.add_clause_reuse(&mut vec![Lit::new(v, false), Lit::new(vars[0], true)]);
+            solver.solve_limited(&[]);
+        }
+    }
+}
 
-            // Parameters (experimental):
-            learntsize_adjust_start_confl: 100,
-            learntsize_adjust_inc: 1.5,
+#[test]
+fn test_permanent_clause() {
+    // random 3-SAT formulas near the threshold, where half the clauses are only known by
+    // a theory, which adds them one at a time during the search
+    struct Hidden {
+        levels: theory::EmptyTheory,
+        clauses: Vec<Vec<Lit>>,
+    }
+    impl Theory for Hidden {
+        fn final_check(&mut self, acts: &mut TheoryArg) {
+            for c in self.clauses.drain(..) {
+                acts.add_permanent_clause(&c);
+            }
+        }
+        fn partial_check(&mut self, acts: &mut TheoryArg) {
+            if let Some(c) = self.clauses.pop() {
+                acts.add_permanent_clause(&c);
+            }
+        }
+        fn create_level(&mut self) {
+            self.levels.create_level()
+        }
+        fn pop_levels(&mut self, n: usize) {
+            self.levels.pop_levels(n)
+        }
+        fn n_levels(&self) -> usize {
+            self.levels.n_levels()
+        }
+        fn explain_propagation_clause(&mut self, _: Lit, _: &mut ExplainTheoryArg) -> &[Lit] {
+            unreachable!()
+        }
+    }
+    let mut rng = crate::test_util::Rng::new(67);
+    let mut rand = |n| rng.next(n);
+    for i in 0..12 {
+        let mut solver = crate::BasicSolver::default();
+        solver.v.opts.chrono = [-1, 0, 100][i % 3];
+        let vars: Vec<Var> = (0..60).map(|_| solver.new_var_default()).collect();
+        let clauses: Vec<Vec<Lit>> = (0..255)
+            .map(|j| {
+                let len = if j % 50 == 0 { 2 } else { 3 };
+                (0..len)
+                    .map(|_| Lit::new(vars[rand(60) as usize], rand(2) == 0))
+                    .collect()
+            })
+            .collect();
+        let mut full = solver.clone();
+        for c in &clauses {
+            full.add_clause_reuse(&mut c.clone());
+        }
+        let expected = full.solve_limited(&[]);
+        let (given, hidden) = clauses.split_at(clauses.len() / 2);
+        for c in given {
+            solver.add_clause_reuse(&mut c.clone());
+        }
+        let mut th = Hidden {
+            levels: theory::EmptyTheory::new(),
+            clauses: hidden.to_vec(),
+        };
+        assert_eq!(solver.solve_limited_th(&mut th, &[]), expected);
+        if expected == lbool::TRUE {
+            let is_true = |l: Lit| solver.value_lit(l) == lbool::TRUE;
+            assert!(clauses.iter().all(|c| c.iter().any(|&l| is_true(l))));
+            assert!(th.clauses.is_empty());
+        }
+        // the clauses of the theory are kept, without it
+        if th.clauses.is_empty() {
+            assert_eq!(solver.solve_limited(&[]), expected);
+            assert_eq!(solver.solve_limited(&[]), expected);
+        }
+    }
+}
 
-            // Statistics: (formerly in 'SolverStats')
-            solves: 0,
-            starts: 0,
-            decisions: 0,
-            rnd_decisions: 0,
-            propagations: 0,
-            conflicts: 0,
-            dec_vars: 0,
-            // v.num_clauses: 0,
-            // v.num_learnts: 0,
-            // v.clauses_literals: 0,
-            // v.learnts_literals: 0,
-            max_literals: 0,
-            tot_literals: 0,
+#[test]
+fn test_theory_facts_and_restarts() {
+    // random 3-SAT formulas near the threshold, with units only known by a theory, which
+    // adds them as facts during the search and sometimes asks for restarts
+    struct Facts {
+        levels: theory::EmptyTheory,
+        units: Vec<Lit>,
+        checks: usize,
+        restart_pending: bool,
+        restarts: usize,
+    }
+    impl Theory for Facts {
+        fn final_check(&mut self, acts: &mut TheoryArg) {
+            for lit in self.units.drain(..) {
+                acts.add_fact(lit);
+            }
+        }
+        fn partial_check(&mut self, acts: &mut TheoryArg) {
+            if mem::take(&mut self.restart_pending) && self.levels.n_levels() == 0 {
+                self.restarts += 1;
+            }
+            self.checks += 1;
+            if self.checks.is_multiple_of(5) {
+                if let Some(lit) = self.units.pop() {
+                    acts.add_fact(lit);
+                }
+            } else if self.checks.is_multiple_of(7) && self.restarts < 3 {
+                acts.request_restart();
+                self.restart_pending = true;
+            }
+        }
+        fn create_level(&mut self) {
+            self.levels.create_level()
+        }
+        fn pop_levels(&mut self, n: usize) {
+            self.levels.pop_levels(n)
+        }
+        fn n_levels(&self) -> usize {
+            self.levels.n_levels()
+        }
+        fn explain_propagation_clause(&mut self, _: Lit, _: &mut ExplainTheoryArg) -> &[Lit] {
+            unreachable!()
+        }
+    }
+    let mut rng = crate::test_util::Rng::new(79);
+    let mut rand = |n| rng.next(n);
+    let mut restarts = 0;
+    for i in 0..12 {
+        let mut solver = crate::BasicSolver::default();
+        solver.v.opts.chrono = [-1, 0, 100][i % 3];
+        let vars: Vec<Var> = (0..60).map(|_| solver.new_var_default()).collect();
+        let clauses: Vec<Vec<Lit>> = (0..230)
+            .map(|_| {
+                (0..3)
+                    .map(|_| Lit::new(vars[rand(60) as usize], rand(2) == 0))
+                    .collect()
+            })
+            .collect();
+        for c in &clauses {
+            solver.add_clause_reuse(&mut c.clone());
+        }
+        let units: Vec<Lit> = (0..4)
+            .map(|_| Lit::new(vars[rand(60) as usize], rand(2) == 0))
+            .collect();
+        let mut full = solver.clone();
+        for &lit in &units {
+            full.add_clause_reuse(&mut vec![lit]);
+        }
+        let expected = full.solve_limited(&[]);
+        let mut th = Facts {
+            levels: theory::EmptyTheory::new(),
+            units: units.clone(),
+            checks: 0,
+            restart_pending: false,
+            restarts: 0,
+        };
+        assert_eq!(solver.solve_limited_th(&mut th, &[]), expected);
+        restarts += th.restarts;
+        if expected == lbool::TRUE {
+            let is_true = |l: Lit| solver.value_lit(l) == lbool::TRUE;
+            assert!(clauses.iter().all(|c| c.iter().any(|&l| is_true(l))));
+            assert!(units.iter().all(|&l| is_true(l)));
+        }
+        // the facts stay true at level 0
+        if th.units.is_empty() && solver.is_ok() {
+            assert!(units
+                .iter()
+                .all(|&l| { solver.v.value_lit(l) == lbool::TRUE && solver.v.level_lit(l) == 0 }));
+        }
+    }
+    assert!(restarts > 0);
+}
 
-            polarity: VMap::new(),
-            user_pol: VMap::new(),
-            decision: VMap::new(),
-            // v.vardata: VMap::new(),
-            watches_data: OccListsData::new(),
-            ok: true,
-            cla_inc: 1.0,
-            // v.var_inc: 1.0,
-            qhead: 0,
-            simp_db_assigns: -1,
-            simp_db_props: 0,
-            progress_estimate: 0.0,
-            remove_satisfied: false, // revert b5464ec81f76db9315dac3276b64614dd59cfe49
-            next_var: Var::from_idx(0),
+#[test]
+fn test_dyn_theory() {
+    // theories chosen at runtime: the empty one, and one that forbids `x` to be true
+    struct Forbid {
+        levels: theory::EmptyTheory,
+        lit: Lit,
+    }
+    impl Theory for Forbid {
+        fn final_check(&mut self, acts: &mut TheoryArg) {
+            if acts.value_lit(self.lit) == lbool::TRUE {
+                acts.raise_conflict(&[!self.lit], false);
+            }
+        }
+        fn create_level(&mut self) {
+            self.levels.create_level()
+        }
+        fn pop_levels(&mut self, n: usize) {
+            self.levels.pop_levels(n)
+        }
+        fn n_levels(&self) -> usize {
+            self.levels.n_levels()
+        }
+        fn explain_propagation_clause(&mut self, _: Lit, _: &mut ExplainTheoryArg) -> &[Lit] {
+            unreachable!()
+        }
+    }
+    let mut solver = crate::BasicSolver::default();
+    let x = Lit::new(solver.new_var(lbool::TRUE, true), true);
+    let y = Lit::new(solver.new_var_default(), true);
+    solver.add_clause_reuse(&mut vec![x, y]);
+    let mut theories: Vec<Box<dyn Theory>> = vec![
+        Box::new(theory::EmptyTheory::new()),
+        Box::new(Forbid {
+            levels: theory::EmptyTheory::new(),
+            lit: x,
+        }),
+    ];
+    for (i, th) in theories.iter_mut().enumerate() {
+        assert_eq!(solver.solve_limited_th(th, &[]), lbool::TRUE);
+        assert_eq!(solver.value_lit(x), lbool::new(i == 0));
+        let mut th: &mut dyn Theory = &mut **th;
+        assert_eq!(solver.solve_limited_th(&mut th, &[y]), lbool::TRUE);
+        assert_eq!(solver.value_lit(x), lbool::new(i == 0));
+    }
+}
 
-            ca: ClauseAllocator::new(),
-            free_vars: vec![],
-            assumptions: vec![],
+#[test]
+fn test_theory_combiner() {
+    // two theories of implications `a => b` between random literals, which propagate
+    // eagerly, combined with random 3-SAT formulas: same results as with the
+    // implications as clauses
+    struct Implies {
+        levels: theory::EmptyTheory,
+        pairs: Vec<(Lit, Lit)>,
+        props: Vec<(Lit, Lit)>,
+        expl: Vec<Lit>,
+    }
+    impl Implies {
+        fn check(&mut self, acts: &mut TheoryArg) {
+            for &(a, b) in &self.pairs {
+                if acts.value_lit(a) != lbool::TRUE {
+                    continue;
+                }
+                let val = acts.value_lit(b);
+                if val == lbool::UNDEF {
+                    self.props.push((b, a));
+                    acts.propagate(b);
+                } else if val == lbool::FALSE {
+                    return acts.raise_conflict(&[!a, b], false);
+                }
+            }
+        }
+    }
+    impl Theory for Implies {
+        fn final_check(&mut self, acts: &mut TheoryArg) {
+            self.check(acts)
+        }
+        fn partial_check(&mut self, acts: &mut TheoryArg) {
+            self.check(acts)
+        }
+        fn create_level(&mut self) {
+            self.levels.create_level()
+        }
+        fn pop_levels(&mut self, n: usize) {
+            self.levels.pop_levels(n)
+        }
+        fn n_levels(&self) -> usize {
+            self.levels.n_levels()
+        }
+        fn explain_propagation_clause(&mut self, p: Lit, _: &mut ExplainTheoryArg) -> &[Lit] {
+            let &(_, a) = self.props.iter().rev().find(|&&(b, _)| b == p).unwrap();
+            self.expl = vec![p, !a];
+            &self.expl
+        }
+    }
+    let mut rng = crate::test_util::Rng::new(71);
+    let mut rand = |n| rng.next(n);
+    let mut results = [0; 2];
+    for _ in 0..16 {
+        let mut solver = crate::BasicSolver::default();
+        let vars: Vec<Var> = (0..40).map(|_| solver.new_var_default()).collect();
+        let mut random_lit = || Lit::new(vars[rand(40) as usize], rand(2) == 0);
+        let clauses: Vec<Vec<Lit>> = (0..100)
+            .map(|_| (0..3).map(|_| random_lit()).collect())
+            .collect();
+        let mut implies = || Implies {
+            levels: theory::EmptyTheory::new(),
+            pairs: (0..20)
+                .map(|_| (random_lit(), random_lit()))
+                .filter(|&(a, b)| a.var() != b.var())
+                .collect(),
+            props: vec![],
+            expl: vec![],
+        };
+        let mut th = theory::TheoryCombiner::new(implies(), implies());
+        let mut full = solver.clone();
+        for c in &clauses {
+            solver.add_clause_reuse(&mut c.clone());
+            full.add_clause_reuse(&mut c.clone());
+        }
+        for &(a, b) in th.first.pairs.iter().chain(&th.second.pairs) {
+            full.add_clause_reuse(&mut vec![!a, b]);
+        }
+        let expected = full.solve_limited(&[]);
+        assert_eq!(solver.solve_limited_th(&mut th, &[]), expected);
+        results[(expected == lbool::TRUE) as usize] += 1;
+        if expected == lbool::TRUE {
+            let is_true = |l: Lit| solver.value_lit(l) == lbool::TRUE;
+            let pairs = th.first.pairs.iter().chain(&th.second.pairs);
+            assert!(pairs.clone().all(|&(a, b)| !is_true(a) || is_true(b)));
+        }
+    }
+    assert!(results.iter().all(|&n| n > 0));
+}
 
-            seen: VMap::new(),
-            minimize_stack: vec![],
-            analyze_toclear: vec![],
-            max_learnts: 0.0,
-            learntsize_adjust_confl: 0.0,
-            learntsize_adjust_cnt: 0,
+#[test]
+fn test_otfs() {
+    // random 3-SAT formulas near the threshold, with and without on-the-fly subsumption
+    let mut rng = crate::test_util::Rng::new(23);
+    let mut rand = |n| rng.next(n);
+    let mut strengthened = 0;
+    for _ in 0..30 {
+        let clauses: Vec<Vec<(u64, bool)>> = (0..255)
+            .map(|_| (0..3).map(|_| (rand(60), rand(2) == 0)).collect())
+            .collect();
+        let results = [false, true].map(|otfs| {
+            let mut solver = crate::BasicSolver::default();
+            solver.v.opts.otfs = otfs;
+            let vars: Vec<Var> = (0..60).map(|_| solver.new_var_default()).collect();
+            let clauses: Vec<Vec<Lit>> = clauses
+                .iter()
+                .map(|c| {
+                    c.iter()
+                        .map(|&(v, s)| Lit::new(vars[v as usize], s))
+                        .collect()
+                })
+                .collect();
+            for c in &clauses {
+                solver.add_clause_reuse(&mut c.clone());
+            }
+            let res = solver.solve_limited(&[]);
+            if res == lbool::TRUE {
+                let is_true = |l: Lit| solver.value_lit(l) == lbool::TRUE;
+                assert!(clauses.iter().all(|c| c.iter().any(|&l| is_true(l))));
+            }
+            strengthened += solver.v.otfs_strengthened;
+            res
+        });
+        assert_eq!(results[0], results[1]);
+    }
+    assert!(strengthened > 0);
+}
 
-            // Resource constraints:
-            conflict_budget: -1,
-            propagation_budget: -1,
+#[test]
+fn test_minimize_binary() {
+    let mut solver = crate::BasicSolver::default();
+    let [x, y, z] = [0; 3].map(|_| Lit::new(solver.new_var_default(), true));
+    solver.v.vars.new_decision_level();
+    solver.v.vars.unchecked_enqueue(!y, CRef::UNDEF);
+    solver.v.vars.unchecked_enqueue(!z, CRef::UNDEF);
+    solver.v.vars.new_decision_level();
+    solver.v.vars.unchecked_enqueue(!x, CRef::UNDEF);
+    // `!x` implies `!y` by a binary clause, but not `!z`
+    let cr = solver.v.ca.alloc_with_learnt(&[x, !y], false);
+    solver.v.attach_clause(cr);
+    let cr = solver.v.ca.alloc_with_learnt(&[x, !z, y], false);
+    solver.v.attach_clause(cr);
+    let mut learnt = vec![x, y, z];
+    assert!(solver.v.minimize_binary(&mut learnt));
+    assert_eq!(learnt, [x, z]);
+    assert!(!solver.v.minimize_binary(&mut learnt));
+    assert!(solver.v.seen.iter().all(|(_, &s)| s == Seen::UNDEF));
+}
 
-            th_st: ExplainTheoryArg::new(),
+#[test]
+fn test_binary_watches() {
+    let mut solver = crate::BasicSolver::default();
+    let [a, b, c, d, e, f] = [0; 6].map(|_| Lit::new(solver.new_var_default(), true));
+    solver.add_clause_reuse(&mut vec![!a, b]);
+    solver.add_clause_reuse(&mut vec![!b, c]);
+    solver.add_clause_reuse(&mut vec![!b, !c, d]);
+    solver.add_clause_reuse(&mut vec![d, e, f]);
+    assert!(solver.v.has_binary_implication(a) && solver.v.has_binary_implication(!c));
+    assert!(!solver.v.has_binary_implication(c) && !solver.v.has_binary_implication(!d));
+    // the binary clauses are their own reasons, with the implied literal first
+    {
+        let view = solver.propagate_under(&[a]).unwrap();
+        assert_eq!(view.reason_of(b.var()), Some(&[b, !a][..]));
+        assert_eq!(view.reason_of(c.var()), Some(&[c, !b][..]));
+        assert_eq!(view.value_lit(d), lbool::TRUE);
+    }
+    // a clause shortened to two literals, as by `remove_satisfied`, stays in the long
+    // watch lists
+    let cr = *solver.clauses.last().unwrap();
+    solver.v.ca.get_mut(cr).shrink(2);
+    assert_eq!(solver.v.ca.get_ref(cr).lits(), [d, e]);
+    assert!(!solver.v.has_binary_implication(!d));
+    solver.v.detach_clause(cr, true);
+    solver.v.ca.get_mut(cr).set_mark(1);
+    solver.clauses.pop();
+    assert_eq!(
+        solver.propagate_under(&[!d]).map(|v| v.value_lit(e)).ok(),
+        Some(lbool::UNDEF)
+    );
+    // conflict through a binary clause
+    solver.add_clause_reuse(&mut vec![!c, !d]);
+    assert!(solver.propagate_under(&[a]).is_err());
+}
+
+#[test]
+fn test_substitute_equivalences() {
+    // random formulas with equivalences, compared with the truth tables
+    let mut rng = crate::test_util::Rng::new(5);
+    let mut rand = |n| rng.next(n);
+    let eval = |l: Lit, a: u32| ((a >> l.var().idx()) & 1 == 1) == l.sign();
+    let mut substituted = 0;
+    for _ in 0..300 {
+        let mut solver = crate::BasicSolver::default();
+        let vars: Vec<Var> = (0..10).map(|_| solver.new_var_default()).collect();
+        let mut rand_lit = || Lit::new(vars[rand(10) as usize], rand(2) == 0);
+        let mut clauses = vec![];
+        for _ in 0..5 {
+            let (a, b) = (rand_lit(), rand_lit());
+            clauses.push(vec![!a, b]);
+            clauses.push(vec![a, !b]);
         }
-    }
+        for _ in 0..12 {
+            clauses.push(vec![rand_lit(), rand_lit(), rand_lit()]);
+        }
+        let extra = vec![rand_lit(), rand_lit()];
+        let assumps = [rand_lit(), rand_lit()];
+        for c in &clauses {
+            solver.add_clause_reuse(&mut c.clone());
+        }
+        let check = |solver: &mut crate::BasicSolver, clauses: &[Vec<Lit>], assumps: &[Lit]| {
+            let sat = (0..1u32 << 10).any(|a| {
+                assumps.iter().all(|&l| eval(l, a))
+                    && clauses.iter().all(|c| c.iter().any(|&l| eval(l, a)))
+            });
+            let res = solver.solve_limited(assumps);
+            assert_eq!(res, if sat { lbool::TRUE } else { lbool::FALSE });
+            if sat {
+                let is_true = |l: Lit| solver.value_lit(l) == lbool::TRUE;
+                assert!(assumps.iter().all(|&l| is_true(l)));
+                assert!(clauses.iter().all(|c| c.iter().any(|&l| is_true(l))));
+            }
+        };
+        if solver.v.ok && solver.substitute_equivalences() {
+            substituted += vars.iter().filter(|&&v| solver.is_eliminated(v)).count();
+        }
+        check(&mut solver, &clauses, &[]);
+        // substituted variables are restored when used again
+        check(&mut solver, &clauses, &assumps);
+        solver.add_clause_reuse(&mut extra.clone());
+        clauses.push(extra);
+        check(&mut solver, &clauses, &[]);
+    }
+    assert!(substituted > 0);
 }
 
-/// Large f32 that is still small enough that it can't cause another f32 to overflow to infinity
-const THRESHOLD: f32 = 1.0141204e31;
 #[test]
-fn test_threshold() {
-    let f = f32::MAX * 2.0f32.powi(-1 - (f32::MANTISSA_DIGITS as i32));
-    assert_eq!(THRESHOLD, f);
-    // adding THRESHOLD to a float can never make it overflow to infinity
-    assert_eq!(f32::MAX + THRESHOLD, f32::MAX)
+fn test_chrono() {
+    // random 3-SAT formulas near the threshold, always backtracking chronologically or never
+    let mut rng = crate::test_util::Rng::new(7);
+    let mut rand = |n| rng.next(n);
+    for _ in 0..50 {
+        let clauses: Vec<Vec<(u64, bool)>> = (0..170)
+            .map(|_| (0..3).map(|_| (rand(40), rand(2) == 0)).collect())
+            .collect();
+        let results = [0, -1].map(|chrono| {
+            let mut solver = crate::BasicSolver::default();
+            solver.v.opts.chrono = chrono;
+            let vars: Vec<Var> = (0..40).map(|_| solver.new_var_default()).collect();
+            let clauses: Vec<Vec<Lit>> = clauses
+                .iter()
+                .map(|c| {
+                    c.iter()
+                        .map(|&(v, s)| Lit::new(vars[v as usize], s))
+                        .collect()
+                })
+                .collect();
+            for c in &clauses {
+                solver.add_clause_reuse(&mut c.clone());
+            }
+            let res = solver.solve_limited(&[]);
+            if res == lbool::TRUE {
+                let is_true = |l: Lit| solver.value_lit(l) == lbool::TRUE;
+                assert!(clauses.iter().all(|c| c.iter().any(|&l| is_true(l))));
+            }
+            res
+        });
+        assert_eq!(results[0], results[1]);
+    }
 }
 
-/// multiply a positive float `f` by 0.5f32.powi(`pow2`)
-/// truncates to positive 0 instead of using sub-normal numbers
-#[inline]
-fn scale_down_float(f: f32, pow2: u32) -> f32 {
-    f32::from_bits(
-        f.to_bits()
-            .saturating_sub(pow2 << (f32::MANTISSA_DIGITS - 1)),
-    )
+#[test]
+fn test_branching() {
+    // random 3-SAT formulas near the threshold, solved with each heuristic, which is
+    // also changed in the middle of the search
+    use BranchingHeuristic::*;
+    let mut rng = crate::test_util::Rng::new(13);
+    let mut rand = |n| rng.next(n);
+    for _ in 0..30 {
+        let clauses: Vec<Vec<(u64, bool)>> = (0..215)
+            .map(|_| (0..3).map(|_| (rand(50), rand(2) == 0)).collect())
+            .collect();
+        let results = [Vsids, Vmtf, Chb, Lrb].map(|branching| {
+            let mut solver = crate::BasicSolver::default();
+            solver.v.opts.branching = branching;
+            let vars: Vec<Var> = (0..50).map(|_| solver.new_var_default()).collect();
+            let clauses: Vec<Vec<Lit>> = clauses
+                .iter()
+                .map(|c| {
+                    c.iter()
+                        .map(|&(v, s)| Lit::new(vars[v as usize], s))
+                        .collect()
+                })
+                .collect();
+            for c in &clauses {
+                solver.add_clause_reuse(&mut c.clone());
+            }
+            solver.set_conflict_budget(20);
+            let mut res = solver.solve_limited(&[]);
+            if res == lbool::UNDEF {
+                let mut opts = solver.options();
+                opts.branching = if branching == Vsids { Vmtf } else { Vsids };
+                solver.set_options(opts).unwrap();
+                solver.set_conflict_budget(u64::MAX);
+                res = solver.solve_limited(&[]);
+            }
+            if res == lbool::TRUE {
+                let is_true = |l: Lit| solver.value_lit(l) == lbool::TRUE;
+                assert!(clauses.iter().all(|c| c.iter().any(|&l| is_true(l))));
+            }
+            res
+        });
+        assert!(results.iter().all(|&r| r == results[0]), "{:?}", results);
+    }
 }
 
 #[test]
-fn test_scale_down_float() {
-    assert_eq!(scale_down_float(42.0, 10), 42.0 * 0.5f32.powi(10));
-    let actual = scale_down_float(42.0, 140);
-    let expect = 42.0 * 0.5_f32.powi(140);
-    assert!(0.0 <= actual && actual <= expect && expect < f32::MIN_POSITIVE)
+fn test_approx_count_selectors() {
+    // the selectors of the random XOR constraints are reused from one round to the next
+    let mut solver = crate::BasicSolver::default();
+    let vars: Vec<Var> = (0..10).map(|_| solver.new_var_default()).collect();
+    let x = |i: usize| Lit::new(vars[i], true);
+    assert!(solver.add_clause_reuse(&mut vec![x(0), x(1), x(2)]));
+    assert!(solver.add_clause_reuse(&mut vec![!x(3), x(4)]));
+    let exact = 7 * 3 * 32;
+    for _ in 0..2 {
+        let approx = solver.approx_count_models(&vars, 0.8, 0.2).unwrap();
+        assert!(approx * 1.8 >= exact as f64 && approx <= exact as f64 * 1.8);
+        let selectors = solver.xor_selectors.len();
+        assert!(0 < selectors && selectors <= vars.len());
+        let xor_vars = (10..solver.num_vars())
+            .map(Var::from_idx)
+            .filter(|&v| solver.v.natives.xor.contains_var(v) && solver.v.value(v) == lbool::UNDEF)
+            .count();
+        assert_eq!(xor_vars, selectors);
+    }
+    assert_eq!(solver.count_models(&vars), Some(exact));
+    // the selectors are gone with the other variables
+    solver.reset();
+    let vars: Vec<Var> = (0..16).map(|_| solver.new_var_default()).collect();
+    let approx = solver.approx_count_models(&vars, 0.8, 0.2).unwrap();
+    assert!(approx * 1.8 >= 65536.0 && approx <= 65536.0 * 1.8);
+    assert!(solver.xor_selectors.iter().all(|&s| !vars.contains(&s)));
 }
 
 impl VarState {
-    fn new() -> Self {
+    fn new(branching: BranchingHeuristic) -> Self {
         Self {
+            branch: Branching::new(branching),
             ass: VMap::new(),
             vardata: VMap::new(),
-            activity: VMap::new(),
-            var_inc: 1.0,
             trail: vec![],
             trail_lim: vec![],
-            order_heap_data: HeapData::new(),
         }
     }
 
@@ -2156,7 +9005,7 @@ impl VarState {
         // find where the end of the level-0 part of the trail is
         let end = self
             .trail_lim
-            .get(0)
+            .first()
             .map_or(self.trail.len(), |&x| x as usize);
         &self.trail[..end]
     }
@@ -2181,27 +9030,17 @@ impl VarState {
         self.vardata[x].reason
     }
 
-    fn var_decay_activity(&mut self, decay: f32) {
-        self.var_inc *= 1.0 / decay;
-        if self.var_inc > THRESHOLD {
-            let scale = -f32::MIN_EXP as u32;
-            // Rescale:
-            for (_, x) in self.activity.iter_mut() {
-                *x = scale_down_float(*x, scale)
-            }
-            for x in self.order_heap_data.heap_mut().iter_mut() {
-                x.map_activity(|activity| scale_down_float(activity, scale))
-            }
-            self.var_inc = scale_down_float(self.var_inc, scale);
-        }
-    }
-
     #[inline(always)]
     pub fn decision_level(&self) -> u32 {
         self.trail_lim.len() as u32
     }
 
     fn unchecked_enqueue(&mut self, p: Lit, from: CRef) {
+        self.unchecked_enqueue_at(p, from, self.decision_level() as i32)
+    }
+
+    /// Enqueue `p`, implied at `level` which may be below the current level.
+    fn unchecked_enqueue_at(&mut self, p: Lit, from: CRef, level: i32) {
         debug_assert_eq!(
             self.value_lit(p),
             lbool::UNDEF,
@@ -2209,30 +9048,14 @@ impl VarState {
             p
         );
         self.ass[p.var()] = lbool::new(p.sign());
-        self.vardata[p.var()] = VarData::new(from, self.decision_level() as i32);
+        self.vardata[p.var()] = VarData::new(from, level);
         self.trail.push(p);
-    }
-
-    fn order_heap(&mut self) -> Heap<Var, VarOrder> {
-        self.order_heap_data.promote(VarOrder {
-            activity: &self.activity,
-        })
-    }
-
-    /// Increase a variable with the current 'bump' value.
-    fn var_bump_activity(&mut self, v: Var) {
-        self.activity[v] += self.var_inc;
-
-        // Update order_heap with respect to new activity:
-        let mut order_heap = self.order_heap();
-        if order_heap.in_heap(v) {
-            order_heap.decrease(v);
-        }
+        self.branch.on_assign(p.var());
     }
 
     #[allow(dead_code)]
     fn iter_trail<'a>(&'a self) -> impl Iterator<Item = Lit> + 'a {
-        self.trail.iter().map(|l| *l)
+        self.trail.iter().copied()
     }
 }
 
@@ -2254,15 +9077,32 @@ impl<'a> TheoryArg<'a> {
         self.v.vars.value(v)
     }
 
+    /// Value of given literal in current model.
+    #[inline(always)]
+    pub fn value_lit(&self, lit: Lit) -> lbool {
+        self.v.vars.value_lit(lit)
+    }
+
     /// Current (possibly partial) model, as a slice of true literals.
     #[inline(always)]
     pub fn model(&self) -> &[Lit] {
         &self.v.vars.trail
     }
 
-    /// Allocate a new literal.
+    /// Create a fresh variable during the search, for instance to split on demand or to
+    /// instantiate an axiom lazily. It is a decision variable with no preferred polarity,
+    /// frozen so that [`Solver::eliminate`] keeps it for the theory.
+    pub fn new_var(&mut self) -> Var {
+        let v = self.v.new_var(lbool::UNDEF, true);
+        self.v.frozen[v] = true;
+        v
+    }
+
+    /// Allocate a new literal, like [`new_var`](Self::new_var), whose variable is
+    /// decided to false first.
     pub fn mk_new_lit(&mut self) -> Lit {
-        let v = self.v.new_var(lbool::FALSE, true);
+        let v = self.new_var();
+        self.v.user_pol[v] = lbool::FALSE;
         Lit::new(v, true)
     }
 
@@ -2271,8 +9111,20 @@ impl<'a> TheoryArg<'a> {
     /// This is useful for lemma-on-demand or theory splitting, but can
     /// be relatively costly.
     ///
-    /// NOTE: This is not fully supported yet.
+    /// Same as [`add_permanent_clause`](Self::add_permanent_clause).
     pub fn add_theory_lemma(&mut self, c: &[Lit]) {
+        self.add_permanent_clause(c)
+    }
+
+    /// Add the clause `c`, a tautology of the theory, to the irredundant clauses of the
+    /// solver: unlike the learnt clauses, it is never removed by the reduction of the
+    /// clause database.
+    ///
+    /// The clause is queued, and added once the theory returns without a conflict. If it
+    /// is unit or false under the current assignment, the solver restarts to add it at
+    /// level 0; so a clause that is false should rather be given to
+    /// [`raise_conflict`](Self::raise_conflict), with `costly` set so that it is kept.
+    pub fn add_permanent_clause(&mut self, c: &[Lit]) {
         if self.is_ok() {
             self.v.th_st.add_theory_lemma(c)
         }
@@ -2284,7 +9136,9 @@ impl<'a> TheoryArg<'a> {
 
     /// Propagate the literal `p`, which is theory-implied by the current trail.
     ///
-    /// This will add `p` on the trail. The theory must be ready to
+    /// This will add `p` on the trail, right away or, with
+    /// [`SolverOpts::theory_prop_batch`], once the theory returns and the literals
+    /// queued before it are assigned. The theory must be ready to
     /// provide an explanation via `Theory::explain_prop(p)` if asked to
     /// during conflict resolution.
     ///
@@ -2293,6 +9147,26 @@ impl<'a> TheoryArg<'a> {
     /// If this returns `false`, the theory should avoid doing more work and
     /// return as early as reasonably possible.
     pub fn propagate(&mut self, p: Lit) -> bool {
+        if !self.is_ok() {
+            return false;
+        }
+        let v_p = self.v.vars.value_lit(p);
+        if v_p == lbool::TRUE {
+            true
+        } else if v_p == lbool::UNDEF && self.v.opts.theory_prop_batch > 0 {
+            // assigned later, by `assign_queue`
+            self.has_propagated = true;
+            self.v.th_queue.push(p);
+            true
+        } else {
+            self.propagate_now(p)
+        }
+    }
+
+    /// Like [`propagate`](Self::propagate), but `p` is assigned right away even with
+    /// [`SolverOpts::theory_prop_batch`], for the native constraints whose explanations
+    /// refer to the position of `p` on the trail.
+    pub(crate) fn propagate_now(&mut self, p: Lit) -> bool {
         if !self.is_ok() {
             return false;
         }
@@ -2300,8 +9174,8 @@ impl<'a> TheoryArg<'a> {
         if v_p == lbool::TRUE {
             true
         } else if v_p == lbool::UNDEF {
-            // propagate on the fly
             self.has_propagated = true;
+            // propagate on the fly
             let cr = CRef::SPECIAL; // indicates a theory propagation
             self.v.vars.unchecked_enqueue(p, cr);
             true
@@ -2313,6 +9187,63 @@ impl<'a> TheoryArg<'a> {
         }
     }
 
+    /// Assign the next batch of the queued propagations, skipping the ones that are
+    /// already true, or raise a conflict for the first one that is false.
+    fn assign_queue(&mut self) {
+        let mut queue = mem::take(&mut self.v.th_queue);
+        let batch = self.v.opts.theory_prop_batch as usize;
+        let (mut i, mut n) = (0, 0);
+        while i < queue.len() && n < batch {
+            let p = queue[i];
+            i += 1;
+            let v_p = self.v.vars.value_lit(p);
+            if v_p == lbool::UNDEF {
+                self.v.vars.unchecked_enqueue(p, CRef::SPECIAL);
+                self.has_propagated = true;
+                n += 1;
+            } else if v_p == lbool::FALSE {
+                self.conflict = TheoryConflict::Prop(p);
+                self.v.th_stats.conflicts += 1;
+                break;
+            }
+        }
+        self.v.th_stats.propagations += n as u64;
+        queue.drain(..i);
+        self.v.th_queue = queue;
+    }
+
+    /// Add `lit` as a fact, that is a theory lemma made of `lit` alone: it stays true
+    /// at level 0 from now on.
+    ///
+    /// If `lit` is unassigned, it is assigned at level 0 right away, out of order on the
+    /// trail, while the current decisions are kept; this needs chronological
+    /// backtracking (see [`chrono`](SolverOpts::chrono)). Otherwise, it is added like
+    /// [`add_permanent_clause`](Self::add_permanent_clause), for which the solver
+    /// restarts if `lit` is not already true at level 0.
+    pub fn add_fact(&mut self, lit: Lit) {
+        if !self.is_ok() {
+            return;
+        }
+        if self.v.value_lit(lit) == lbool::UNDEF && self.v.opts.chrono >= 0 {
+            let id = self.v.proof.new_id();
+            self.v.proof.add(id, &[lit]);
+            self.v.set_unit_id(lit.var(), id);
+            self.v.vars.unchecked_enqueue_at(lit, CRef::UNDEF, 0);
+            self.has_propagated = true;
+        } else if self.v.value_lit(lit) != lbool::TRUE || self.v.level_lit(lit) > 0 {
+            self.add_permanent_clause(&[lit]);
+        }
+    }
+
+    /// Ask the solver to restart, that is to backtrack to level 0, once a partial check
+    /// returns without a conflict or a propagation.
+    ///
+    /// It is ignored if the model is accepted by `final_check` meanwhile. The search
+    /// does not terminate if the theory keeps asking for restarts.
+    pub fn request_restart(&mut self) {
+        self.v.th_restart = true;
+    }
+
     /// Add a conflict clause.
     ///
     /// This should be used in the theory when the current partial model
@@ -2322,10 +9253,10 @@ impl<'a> TheoryArg<'a> {
     ///
     /// ## Params
     /// - `lits` a clause that is a tautology of the theory (ie a lemma)
-    ///     and that is false in the current (partial) model.
+    ///   and that is false in the current (partial) model.
     /// - `costly` if true, indicates that the conflict `c` was costly to produce.
-    ///     This is a hint for the SAT solver to keep the theory lemma that corresponds
-    ///     to `c` along with the actual learnt clause.
+    ///   This is a hint for the SAT solver to keep the theory lemma that corresponds
+    ///   to `c` along with the actual learnt clause.
     pub fn raise_conflict(&mut self, lits: &[Lit], costly: bool) {
         if self.is_ok() {
             self.conflict = TheoryConflict::Clause { costly };
@@ -2342,19 +9273,48 @@ enum ClauseSetSelect {
 }
 
 #[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 struct VarData {
     reason: CRef,
     level: i32,
 }
 
+/// Entry of the watch list of a literal, for a clause watching its negation.
 #[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 struct Watcher {
     cref: CRef,
+    /// Another literal of the clause (the other watch when the watcher was created): if it
+    /// is true, the clause is satisfied and `propagate` skips it without dereferencing it.
     blocker: Lit,
 }
 
-struct VarOrder<'a> {
-    activity: &'a VMap<f32>,
+/// Counts the distinct levels of the literals of a clause, that is its LBD.
+#[derive(Default, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+struct LevelStamps {
+    /// Last stamp of each level.
+    stamps: Vec<u64>,
+    stamp: u64,
+}
+
+impl LevelStamps {
+    /// LBD of `lits`, which are all assigned.
+    fn lbd(&mut self, vars: &VarState, lits: &[Lit]) -> u32 {
+        self.stamp += 1;
+        let mut lbd = 0;
+        for &lit in lits {
+            let lvl = vars.level(lit.var()) as usize;
+            if lvl >= self.stamps.len() {
+                self.stamps.resize(lvl + 1, 0);
+            }
+            if self.stamps[lvl] != self.stamp {
+                self.stamps[lvl] = self.stamp;
+                lbd += 1;
+            }
+        }
+        lbd
+    }
 }
 
 /// Predicate to test whether a clause has been removed from some lit's watchlist
@@ -2364,6 +9324,8 @@ struct WatcherDeleted<'a> {
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 #[repr(u8)]
+#[allow(clippy::upper_case_acronyms)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 enum Seen {
     UNDEF,
     SOURCE,
@@ -2401,6 +9363,52 @@ mod utils {
         }
     }
 
+    /// Exponential moving average, with a correction of its bias towards 0 at the start.
+    #[derive(Debug, Clone, Copy)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+    pub(super) struct Ema {
+        biased: f64,
+        alpha: f64,
+        /// `(1 - alpha)^n` after `n` updates.
+        beta: f64,
+    }
+
+    impl Ema {
+        pub(crate) fn new(alpha: f64) -> Self {
+            Ema {
+                biased: 0.0,
+                alpha,
+                beta: 1.0,
+            }
+        }
+
+        pub(crate) fn update(&mut self, x: f64) {
+            self.biased += self.alpha * (x - self.biased);
+            self.beta *= 1.0 - self.alpha;
+        }
+
+        pub(crate) fn value(&self) -> f64 {
+            if self.beta < 1.0 {
+                self.biased / (1.0 - self.beta)
+            } else {
+                0.0
+            }
+        }
+    }
+
+    #[test]
+    fn test_ema() {
+        let mut ema = Ema::new(0.25);
+        ema.update(4.0);
+        assert_eq!(ema.value(), 4.0);
+        ema.update(4.0);
+        assert!((ema.value() - 4.0).abs() < 1e-9);
+        for _ in 0..100 {
+            ema.update(8.0);
+        }
+        assert!((ema.value() - 8.0).abs() < 1e-9);
+    }
+
     #[test]
     fn test_luby() {
         let luby_seq = [1u32, 1, 2, 1, 1, 2, 4, 1, 1, 2, 1, 1, 2, 4, 8];
@@ -2413,17 +9421,52 @@ mod utils {
         }
     }
 
-    /// Generate a random double:
-    pub(super) fn drand(seed: &mut f64) -> f64 {
-        *seed *= 1389796.0;
-        let q = (*seed / 2147483647.0) as i32;
-        *seed -= q as f64 * 2147483647.0;
-        return *seed / 2147483647.0;
+    /// Pseudo-random number generator (xorshift64*), seeded by [`SolverOpts::seed`].
+    #[derive(Debug, Clone)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+    pub(super) struct Rand(u64);
+
+    impl Rand {
+        pub(crate) fn new(seed: u64) -> Self {
+            // splitmix64, so that close seeds give unrelated sequences, and the state is
+            // never 0
+            let mut z = seed.wrapping_add(0x9e3779b97f4a7c15);
+            z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+            Rand((z ^ (z >> 31)) | 1)
+        }
+
+        pub(crate) fn next_u64(&mut self) -> u64 {
+            self.0 ^= self.0 >> 12;
+            self.0 ^= self.0 << 25;
+            self.0 ^= self.0 >> 27;
+            self.0.wrapping_mul(0x2545f4914f6cdd1d)
+        }
+
+        /// Random double in `[0, 1)`.
+        pub(crate) fn f64(&mut self) -> f64 {
+            (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+        }
+
+        /// Random integer in `[0, n)`.
+        pub(crate) fn below(&mut self, n: u32) -> u32 {
+            (((self.next_u64() >> 32) * n as u64) >> 32) as u32
+        }
     }
 
-    /// Generate a random integer:
-    pub(super) fn irand(seed: &mut f64, size: i32) -> i32 {
-        (drand(seed) * size as f64) as i32
+    #[test]
+    fn test_rand() {
+        let mut rand = Rand::new(0);
+        let mut counts = [0; 4];
+        for _ in 0..4000 {
+            let x = rand.f64();
+            assert!((0.0..1.0).contains(&x));
+            counts[rand.below(4) as usize] += 1;
+        }
+        assert!(counts.iter().all(|&c| 900 < c && c < 1100), "{:?}", counts);
+        let mut a = Rand::new(42);
+        let mut b = Rand::new(42);
+        assert!((0..100).all(|_| a.next_u64() == b.next_u64()));
     }
 }
 
@@ -2451,42 +9494,6 @@ impl PartialEq for Watcher {
 }
 impl Eq for Watcher {}
 
-#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq)]
-struct VarOrderKey(u64);
-
-impl VarOrderKey {
-    #[inline]
-    fn new(var: Var, activity: f32) -> Self {
-        VarOrderKey((!(activity.to_bits() as u64) << u32::BITS) | (var.idx() as u64))
-    }
-
-    fn var(self) -> Var {
-        Var::unsafe_from_idx(self.0 as u32)
-    }
-
-    fn activity(self) -> f32 {
-        f32::from_bits(!((self.0 >> u32::BITS) as u32))
-    }
-
-    fn map_activity(&mut self, f: impl FnOnce(f32) -> f32) {
-        *self = VarOrderKey::new(self.var(), f(self.activity()))
-    }
-}
-impl<'a> CachedKeyComparator<Var> for VarOrder<'a> {
-    type Key = VarOrderKey;
-
-    fn cache_key(&self, t: Var) -> Self::Key {
-        VarOrderKey::new(t, self.activity[t])
-    }
-
-    fn max_key(&self) -> Self::Key {
-        VarOrderKey::new(Var::UNDEF, 0.0)
-    }
-
-    fn un_cache_key(&self, k: Self::Key) -> Var {
-        k.var()
-    }
-}
 impl<'a> DeletePred<Watcher> for WatcherDeleted<'a> {
     #[inline]
     fn deleted(&self, w: &Watcher) -> bool {
@@ -2521,19 +9528,39 @@ impl Watcher {
 /// Solver options.
 ///
 /// This can be used to tune the solver heuristics.
-#[derive(Clone)]
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct SolverOpts {
     pub var_decay: f32,
     pub clause_decay: f64,
+    /// The frequency of decisions on a random variable rather than on the one chosen by
+    /// the branching heuristic. (default 0)
     pub random_var_freq: f64,
+    /// The frequency of decisions with a random polarity rather than the saved one.
+    /// (default 0)
+    pub random_pol_freq: f64,
+    /// Seed of the random choices: runs with the same seed on the same problem, with the
+    /// same calls, are identical. (default 91648253)
+    pub seed: u64,
+    /// Former seed of the random choices, used instead of [`seed`](Self::seed) if it is
+    /// changed from its default. (default 91648253.0)
+    #[deprecated(since = "0.5.0", note = "use `seed` instead")]
     pub random_seed: f64,
+    /// Former switch for random polarities, the same as a
+    /// [`random_pol_freq`](Self::random_pol_freq) of 1. (default false)
+    #[deprecated(since = "0.5.0", note = "use `random_pol_freq` instead")]
+    pub rnd_pol: bool,
+    /// When to restart the search. (default Luby)
+    pub restart_strategy: RestartStrategy,
+    /// Former switch between the Luby and geometric restarts: when it is `false`, a
+    /// [`restart_strategy`](Self::restart_strategy) of Luby means geometric restarts.
+    /// (default true)
+    #[deprecated(since = "0.5.0", note = "use `restart_strategy` instead")]
     pub luby_restart: bool,
     /// Controls conflict clause minimization (0=none, 1=basic, 2=deep).
     pub ccmin_mode: i32,
     /// Controls the level of phase saving (0=none, 1=limited, 2=full).
     pub phase_saving: i32,
-    /// Use random polarities for branching heuristics.
-    pub rnd_pol: bool,
     /// Initialize variable activities with a small random value.
     pub rnd_init_act: bool,
     /// The fraction of wasted memory allowed before a garbage collection is triggered.
@@ -2545,30 +9572,273 @@ pub struct SolverOpts {
     pub restart_first: i32,
     /// The factor with which the restart limit is multiplied in each restart. (default 1.5)
     pub restart_inc: f64,
+    /// With Glucose restarts, restart when the average LBD of the recent learnt clauses is
+    /// this many times the overall one. (default 1.25)
+    pub restart_margin: f64,
+    /// With Glucose restarts, do not restart when the trail is this many times larger
+    /// than its average size at conflicts. (default 1.4)
+    pub restart_block_margin: f64,
     /// The intitial limit for learnt clauses is a factor of the original clauses. (default 1 / 3)
     pub learntsize_factor: f64,
     /// The limit for learnt clauses is multiplied with this factor each restart. (default 1.1)
     pub learntsize_inc: f64,
+
+    /// Variables are not eliminated if they produce a resolvent with more literals than
+    /// this, `-1` means no limit. (default 20)
+    pub elim_clause_lim: i32,
+    /// Number of clauses that the elimination of a variable may add. (default 0)
+    pub elim_grow: i32,
+    /// Clauses are not checked for subsumption if each of their variables occurs in more
+    /// clauses than this, `-1` means no limit. (default 1000)
+    pub subsumption_lim: i32,
+    /// Also remove blocked clauses in [`Solver::eliminate`]. (default false)
+    pub blocked_clause_elim: bool,
+    /// Also perform bounded variable addition in [`Solver::eliminate`], which adds
+    /// variables to shrink the clause database. (default false)
+    pub bva: bool,
+    /// Periodically vivify the long clauses during the search, that is shorten them by
+    /// propagating the negations of their literals. (default false)
+    pub vivify: bool,
+    /// Ticks spent vivifying, relative to those spent searching. (default 0.1)
+    pub vivify_effort: f64,
+    /// Periodically probe the literals that imply others by binary clauses, to find
+    /// failed literals and hyper-binary resolvents. (default false)
+    pub probe: bool,
+    /// Ticks spent probing, relative to those spent searching. (default 0.05)
+    pub probe_effort: f64,
+    /// Periodically substitute the literals that are equivalent according to the binary
+    /// clauses by a representative. The substituted variables are removed and restored like
+    /// those eliminated by [`Solver::eliminate`], so the variables that a theory uses must
+    /// be frozen. (default false)
+    pub substitute_equivalences: bool,
+    /// Backtrack chronologically, that is only undo the level of a conflict, when the
+    /// assertion level of the learnt clause is more than this many levels below it;
+    /// `-1` means always backtrack to the assertion level. (default -1)
+    pub chrono: i32,
+    /// Reduce the learnt clauses periodically by tiers of LBD (the number of distinct
+    /// levels in a clause) as in Glucose, rather than by activity when there are more
+    /// than a limit. (default false)
+    pub lbd_reduce: bool,
+    /// Learnt clauses with an LBD up to this are never removed. (default 2)
+    pub tier1_lbd: u32,
+    /// Learnt clauses with an LBD up to this are kept as long as they are used in conflict
+    /// analysis between two reductions. (default 6)
+    pub tier2_lbd: u32,
+    /// Number of conflicts before the first reduction by LBD. (default 2000)
+    pub reduce_first: i32,
+    /// Increase of the number of conflicts between two reductions by LBD. (default 300)
+    pub reduce_inc: i32,
+    /// The heuristic that chooses the decision variables. (default VSIDS)
+    pub branching: BranchingHeuristic,
+    /// Decide on the value a variable had in the largest conflict-free trail since the
+    /// last rephasing, rather than on its last value, as in CaDiCaL. (default false)
+    pub target_phase: bool,
+    /// Reset the saved phases of all the variables periodically, in turn to their value in
+    /// the largest conflict-free trail, to their original value, to the inverted one and to
+    /// a random one. (default false)
+    pub rephase: bool,
+    /// Number of conflicts before the first rephasing, the interval grows by as much after
+    /// each rephasing. (default 1000)
+    pub rephase_int: i32,
+    /// After each rephasing, run a local search (ProbSAT) on the original clauses from the
+    /// new phases, use the best assignment it finds as phases, and restart if it satisfies
+    /// all the clauses. (default false)
+    pub local_search: bool,
+    /// Ticks spent in local search, relative to those spent searching. (default 0.1)
+    pub local_search_effort: f64,
+    /// Remove a literal from the clauses that are subsumed by a resolvent during conflict
+    /// analysis (on-the-fly subsumption). (default false)
+    pub otfs: bool,
+    /// Shrink the learnt clauses with at most this LBD (and 30 literals) with the binary
+    /// clauses of the asserting literal, after the usual minimization; `0` means never.
+    /// (default 0)
+    pub binary_minimize_lbd: u32,
+    /// Alternate between a focused mode, with Glucose restarts driven by the LBD of the
+    /// learnt clauses and the saved phases, and a stable mode, with rare Luby restarts and
+    /// the target phases, as in CaDiCaL and Kissat. [`restart_strategy`](Self::restart_strategy)
+    /// and [`target_phase`](Self::target_phase) are then ignored. (default false)
+    pub mode_switching: bool,
+    /// Number of conflicts of the first focused mode with
+    /// [`mode_switching`](Self::mode_switching); the next modes get as many ticks as it
+    /// used, then more and more. (default 1000)
+    pub mode_init: i32,
+    /// Keep the decision levels of the assumptions on the trail after a solve, so that
+    /// the next one only backtracks to the longest prefix of assumptions they share,
+    /// instead of propagating them again. Other changes to the solver backtrack to
+    /// level 0 first. Theories are told that these levels were popped, and the next
+    /// solve creates them again and notifies the assignments. (default false)
+    pub reuse_trail: bool,
+    /// Order of the assumptions of equal priority, see
+    /// [`Solver::set_assumption_priority`]. (default Given)
+    pub assumption_order: AssumptionOrder,
+    /// Measure the time spent in each callback of the theory, see [`TheoryStats`]. It
+    /// reads the clock around every call, including `create_level` at each decision.
+    /// (default false)
+    pub theory_timing: bool,
+    /// Number of theory propagations assigned at a time, the boolean propagation
+    /// running to its fixpoint in between, or 0 to assign them right away.
+    ///
+    /// With a batch, [`TheoryArg::propagate`] queues the literals, and the theory is
+    /// only called again once the queue is empty: a boolean conflict found before is
+    /// analyzed without assigning the rest of the queue, and without asking the theory
+    /// to explain it. (default 0)
+    pub theory_prop_batch: u32,
+    /// Keep the explanations of the theory propagations until they are undone, so that
+    /// the conflicts analyzed in between do not ask the theory again. The learnt
+    /// clauses are then also minimized through the theory propagations of the lower
+    /// levels, which are explained once for all the conflicts. The explanations of the
+    /// conflicts of the theory and for the unsatisfiable cores are not cached.
+    /// (default false)
+    pub cache_explanations: bool,
+}
+
+/// Restart policy of the search, see [`SolverOpts::restart_strategy`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum RestartStrategy {
+    /// Restart after a number of conflicts following the Luby sequence, scaled by
+    /// [`SolverOpts::restart_first`] and [`SolverOpts::restart_inc`].
+    Luby,
+    /// Restart after a number of conflicts that starts at [`SolverOpts::restart_first`] and
+    /// is multiplied by [`SolverOpts::restart_inc`] at each restart.
+    Geometric,
+    /// Restart dynamically as in Glucose, when the recent learnt clauses have a larger LBD
+    /// than the average, unless the trail is much larger than usual.
+    Glucose,
+}
+
+/// Decision heuristic of the search, see [`SolverOpts::branching`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum BranchingHeuristic {
+    /// Pick the variable with the highest activity, bumped for the variables of each
+    /// conflict by an increment that grows by `1/`[`SolverOpts::var_decay`].
+    Vsids,
+    /// Move the variables of each conflict to the front of a queue, and pick the first
+    /// unassigned one.
+    Vmtf,
+    /// Conflict history-based branching: reward the assigned variables that recently took
+    /// part in conflicts.
+    Chb,
+    /// Learning rate branching: reward the variables that took part in many conflicts
+    /// while they were assigned.
+    Lrb,
+}
+
+/// Order in which the assumptions are decided, see [`SolverOpts::assumption_order`].
+///
+/// The assumptions decided first are the most likely to be in the unsat cores, and an
+/// order that changes between the solves keeps less of the trail with
+/// [`SolverOpts::reuse_trail`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum AssumptionOrder {
+    /// The order they are given in.
+    Given,
+    /// By decreasing activity in the branching heuristic, so that the assumptions
+    /// involved in recent conflicts come first.
+    Activity,
+    /// By decreasing number of the previous unsat cores they occurred in.
+    Cores,
+}
+
+impl std::str::FromStr for RestartStrategy {
+    type Err = ();
+    /// Parse `luby`, `geometric` or `glucose`.
+    fn from_str(s: &str) -> Result<Self, ()> {
+        match s {
+            "luby" => Ok(RestartStrategy::Luby),
+            "geometric" => Ok(RestartStrategy::Geometric),
+            "glucose" => Ok(RestartStrategy::Glucose),
+            _ => Err(()),
+        }
+    }
+}
+
+impl std::str::FromStr for AssumptionOrder {
+    type Err = ();
+    /// Parse `given`, `activity` or `cores`.
+    fn from_str(s: &str) -> Result<Self, ()> {
+        match s {
+            "given" => Ok(AssumptionOrder::Given),
+            "activity" => Ok(AssumptionOrder::Activity),
+            "cores" => Ok(AssumptionOrder::Cores),
+            _ => Err(()),
+        }
+    }
+}
+
+impl std::str::FromStr for BranchingHeuristic {
+    type Err = ();
+    /// Parse `vsids`, `vmtf`, `chb` or `lrb`.
+    fn from_str(s: &str) -> Result<Self, ()> {
+        match s {
+            "vsids" => Ok(BranchingHeuristic::Vsids),
+            "vmtf" => Ok(BranchingHeuristic::Vmtf),
+            "chb" => Ok(BranchingHeuristic::Chb),
+            "lrb" => Ok(BranchingHeuristic::Lrb),
+            _ => Err(()),
+        }
+    }
 }
 
+const DEFAULT_RANDOM_SEED: f64 = 91648253.0;
+
+#[allow(deprecated)]
 impl Default for SolverOpts {
     fn default() -> SolverOpts {
         Self {
             var_decay: 0.95,
             clause_decay: 0.999,
             random_var_freq: 0.0,
-            random_seed: 91648253.0,
+            random_pol_freq: 0.0,
+            seed: 91648253,
+            random_seed: DEFAULT_RANDOM_SEED,
+            rnd_pol: false,
             ccmin_mode: 2,
             phase_saving: 2,
             rnd_init_act: false,
+            restart_strategy: RestartStrategy::Luby,
             luby_restart: true,
             restart_first: 100,
             restart_inc: 2.0,
+            restart_margin: 1.25,
+            restart_block_margin: 1.4,
             garbage_frac: 0.20,
             min_learnts_lim: 0,
             learntsize_factor: 1.0 / 3.0,
             learntsize_inc: 1.1,
-            rnd_pol: false,
+            elim_clause_lim: 20,
+            elim_grow: 0,
+            subsumption_lim: 1000,
+            blocked_clause_elim: false,
+            bva: false,
+            vivify: false,
+            vivify_effort: 0.1,
+            probe: false,
+            probe_effort: 0.05,
+            substitute_equivalences: false,
+            chrono: -1,
+            lbd_reduce: false,
+            tier1_lbd: 2,
+            tier2_lbd: 6,
+            reduce_first: 2000,
+            reduce_inc: 300,
+            branching: BranchingHeuristic::Vsids,
+            target_phase: false,
+            rephase: false,
+            rephase_int: 1000,
+            local_search: false,
+            local_search_effort: 0.1,
+            otfs: false,
+            binary_minimize_lbd: 0,
+            mode_switching: false,
+            mode_init: 1000,
+            reuse_trail: false,
+            assumption_order: AssumptionOrder::Given,
+            theory_timing: false,
+            theory_prop_batch: 0,
+            cache_explanations: false,
         }
     }
 }
@@ -2576,15 +9846,346 @@ impl Default for SolverOpts {
 impl SolverOpts {
     /// Check that options are valid.
     pub fn check(&self) -> bool {
-        (1f32 / THRESHOLD < self.var_decay && self.var_decay < 1.0)
-            && (0.0 < self.clause_decay && self.clause_decay < 1.0)
-            && (0.0 <= self.random_var_freq && self.random_var_freq <= 1.0)
-            && (0.0 < self.random_seed && self.random_seed < f64::INFINITY)
-            && (0 <= self.ccmin_mode && self.ccmin_mode <= 2)
-            && (0 <= self.phase_saving && self.phase_saving <= 2)
-            && 1 <= self.restart_first
-            && (1.0 < self.restart_inc && self.restart_inc < f64::INFINITY)
-            && (0.0 < self.garbage_frac && self.garbage_frac < f64::INFINITY)
-            && 0 <= self.min_learnts_lim
+        self.validate().is_ok()
+    }
+
+    /// The seed of the random choices, taking the deprecated `random_seed` into account.
+    #[allow(deprecated)]
+    fn rand_seed(&self) -> u64 {
+        if self.random_seed == DEFAULT_RANDOM_SEED {
+            self.seed
+        } else {
+            self.random_seed as u64
+        }
+    }
+
+    /// The frequency of random polarities, taking the deprecated `rnd_pol` into account.
+    #[allow(deprecated)]
+    fn pol_freq(&self) -> f64 {
+        if self.rnd_pol {
+            1.0
+        } else {
+            self.random_pol_freq
+        }
+    }
+
+    /// The restart strategy, taking the deprecated `luby_restart` into account.
+    #[allow(deprecated)]
+    fn restarts(&self) -> RestartStrategy {
+        match self.restart_strategy {
+            RestartStrategy::Luby if !self.luby_restart => RestartStrategy::Geometric,
+            strategy => strategy,
+        }
+    }
+
+    /// Check that options are valid, or return the first invalid one.
+    #[allow(deprecated)]
+    pub fn validate(&self) -> Result<(), InvalidOption> {
+        let finite_pos = |x: f64| 0.0 < x && x < f64::INFINITY;
+        let finite_nonneg = |x: f64| (0.0..f64::INFINITY).contains(&x);
+        let checks = [
+            (
+                1f32 / THRESHOLD < self.var_decay && self.var_decay < 1.0,
+                "var_decay",
+                "in ]0, 1[",
+            ),
+            (
+                0.0 < self.clause_decay && self.clause_decay < 1.0,
+                "clause_decay",
+                "in ]0, 1[",
+            ),
+            (
+                (0.0..=1.0).contains(&self.random_var_freq),
+                "random_var_freq",
+                "in [0, 1]",
+            ),
+            (
+                (0.0..=1.0).contains(&self.random_pol_freq),
+                "random_pol_freq",
+                "in [0, 1]",
+            ),
+            (
+                finite_pos(self.random_seed),
+                "random_seed",
+                "finite and positive",
+            ),
+            (
+                (0..=2).contains(&self.ccmin_mode),
+                "ccmin_mode",
+                "0, 1 or 2",
+            ),
+            (
+                (0..=2).contains(&self.phase_saving),
+                "phase_saving",
+                "0, 1 or 2",
+            ),
+            (1 <= self.restart_first, "restart_first", "at least 1"),
+            (
+                1.0 < self.restart_inc && self.restart_inc < f64::INFINITY,
+                "restart_inc",
+                "finite and greater than 1",
+            ),
+            (
+                finite_pos(self.restart_margin),
+                "restart_margin",
+                "finite and positive",
+            ),
+            (
+                finite_pos(self.restart_block_margin),
+                "restart_block_margin",
+                "finite and positive",
+            ),
+            (
+                finite_pos(self.garbage_frac),
+                "garbage_frac",
+                "finite and positive",
+            ),
+            (0 <= self.min_learnts_lim, "min_learnts_lim", "non-negative"),
+            (-1 <= self.elim_clause_lim, "elim_clause_lim", "at least -1"),
+            (0 <= self.elim_grow, "elim_grow", "non-negative"),
+            (-1 <= self.subsumption_lim, "subsumption_lim", "at least -1"),
+            (
+                finite_nonneg(self.vivify_effort),
+                "vivify_effort",
+                "finite and non-negative",
+            ),
+            (
+                finite_nonneg(self.probe_effort),
+                "probe_effort",
+                "finite and non-negative",
+            ),
+            (-1 <= self.chrono, "chrono", "at least -1"),
+            (
+                self.tier1_lbd <= self.tier2_lbd,
+                "tier1_lbd",
+                "at most tier2_lbd",
+            ),
+            (1 <= self.reduce_first, "reduce_first", "at least 1"),
+            (0 <= self.reduce_inc, "reduce_inc", "non-negative"),
+            (1 <= self.rephase_int, "rephase_int", "at least 1"),
+            (1 <= self.mode_init, "mode_init", "at least 1"),
+            (
+                finite_nonneg(self.local_search_effort),
+                "local_search_effort",
+                "finite and non-negative",
+            ),
+        ];
+        match checks.iter().find(|(ok, _, _)| !ok) {
+            None => Ok(()),
+            Some(&(_, option, expected)) => Err(InvalidOption { option, expected }),
+        }
+    }
+
+    /// Options for problems that are expected to be satisfiable: the phases are kept
+    /// longer between restarts, and improved by local search.
+    pub fn sat_preset() -> Self {
+        SolverOpts {
+            restart_strategy: RestartStrategy::Luby,
+            restart_first: 300,
+            target_phase: true,
+            rephase: true,
+            local_search: true,
+            ..Default::default()
+        }
+    }
+
+    /// Options for problems that are expected to be unsatisfiable: frequent restarts
+    /// driven by the quality of the learnt clauses, without target phases, and more
+    /// time spent simplifying the clauses.
+    pub fn unsat_preset() -> Self {
+        SolverOpts {
+            restart_strategy: RestartStrategy::Glucose,
+            lbd_reduce: true,
+            target_phase: false,
+            rephase: false,
+            local_search: false,
+            vivify: true,
+            vivify_effort: 0.2,
+            probe: true,
+            probe_effort: 0.1,
+            otfs: true,
+            binary_minimize_lbd: 6,
+            ..Default::default()
+        }
+    }
+
+    /// Options for many short calls under assumptions: the phases of a call are kept
+    /// for the next one, and little time is spent in inprocessing.
+    pub fn incremental_preset() -> Self {
+        SolverOpts {
+            rephase: false,
+            local_search: false,
+            substitute_equivalences: false,
+            probe_effort: 0.02,
+            ..Default::default()
+        }
+    }
+
+    /// A builder starting from these options.
+    pub fn into_builder(self) -> SolverOptsBuilder {
+        SolverOptsBuilder(self)
+    }
+}
+
+/// An option of [`SolverOpts`] outside of its valid range, see [`SolverOpts::validate`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InvalidOption {
+    /// The name of the option.
+    pub option: &'static str,
+    /// The values it may take.
+    pub expected: &'static str,
+}
+
+impl fmt::Display for InvalidOption {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "invalid option `{}`: expected {}",
+            self.option, self.expected
+        )
+    }
+}
+
+impl core::error::Error for InvalidOption {}
+
+/// Error of [`SolverOpts::set`] and [`Solver::set_option`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SetOptionError {
+    /// There is no option with this name.
+    Unknown(String),
+    /// The value could not be parsed for this option.
+    Parse(&'static str),
+    /// The value is outside of the valid range of the option.
+    Invalid(InvalidOption),
+}
+
+impl fmt::Display for SetOptionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SetOptionError::Unknown(name) => write!(f, "unknown option `{}`", name),
+            SetOptionError::Parse(name) => write!(f, "invalid value for option `{}`", name),
+            SetOptionError::Invalid(e) => e.fmt(f),
+        }
+    }
+}
+
+impl core::error::Error for SetOptionError {}
+
+/// Builder of [`SolverOpts`], which are validated by [`build`](Self::build).
+///
+/// ```
+/// use platsat::{RestartStrategy, SolverOpts};
+/// let opts = SolverOpts::unsat_preset()
+///     .into_builder()
+///     .restart_strategy(RestartStrategy::Luby)
+///     .probe(false)
+///     .build()
+///     .unwrap();
+/// assert!(!opts.probe);
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct SolverOptsBuilder(SolverOpts);
+
+// setters of the builder, and of the options by name
+macro_rules! opts_setters {
+    ($($name:ident: $ty:ty),* $(,)?) => {
+        impl SolverOptsBuilder {
+            $(
+                #[doc = concat!("See [`SolverOpts::", stringify!($name), "`].")]
+                pub fn $name(mut self, $name: $ty) -> Self {
+                    self.0.$name = $name;
+                    self
+                }
+            )*
+        }
+
+        impl SolverOpts {
+            /// Set the option called `name` (the name of the field) to `value`, parsed
+            /// like the values of its type, or the lowercase name of a variant for the
+            /// enumerations.
+            ///
+            /// The options are left unchanged if `name` or `value` is invalid.
+            pub fn set(&mut self, name: &str, value: &str) -> Result<(), SetOptionError> {
+                let old = self.clone();
+                match name {
+                    $(
+                        stringify!($name) => {
+                            self.$name = value
+                                .parse::<$ty>()
+                                .map_err(|_| SetOptionError::Parse(stringify!($name)))?;
+                        }
+                    )*
+                    _ => return Err(SetOptionError::Unknown(name.to_string())),
+                }
+                self.validate().map_err(|e| {
+                    *self = old;
+                    SetOptionError::Invalid(e)
+                })
+            }
+        }
+    };
+}
+
+opts_setters! {
+    var_decay: f32,
+    clause_decay: f64,
+    random_var_freq: f64,
+    random_pol_freq: f64,
+    seed: u64,
+    restart_strategy: RestartStrategy,
+    ccmin_mode: i32,
+    phase_saving: i32,
+    rnd_init_act: bool,
+    garbage_frac: f64,
+    min_learnts_lim: i32,
+    restart_first: i32,
+    restart_inc: f64,
+    restart_margin: f64,
+    restart_block_margin: f64,
+    learntsize_factor: f64,
+    learntsize_inc: f64,
+    elim_clause_lim: i32,
+    elim_grow: i32,
+    subsumption_lim: i32,
+    blocked_clause_elim: bool,
+    bva: bool,
+    vivify: bool,
+    vivify_effort: f64,
+    probe: bool,
+    probe_effort: f64,
+    substitute_equivalences: bool,
+    chrono: i32,
+    lbd_reduce: bool,
+    tier1_lbd: u32,
+    tier2_lbd: u32,
+    reduce_first: i32,
+    reduce_inc: i32,
+    branching: BranchingHeuristic,
+    target_phase: bool,
+    rephase: bool,
+    rephase_int: i32,
+    local_search: bool,
+    local_search_effort: f64,
+    otfs: bool,
+    binary_minimize_lbd: u32,
+    mode_switching: bool,
+    mode_init: i32,
+    reuse_trail: bool,
+    assumption_order: AssumptionOrder,
+    theory_timing: bool,
+    theory_prop_batch: u32,
+    cache_explanations: bool,
+}
+
+impl SolverOptsBuilder {
+    /// A builder starting from the default options.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The options, if they are valid.
+    pub fn build(self) -> Result<SolverOpts, InvalidOption> {
+        self.0.validate()?;
+        Ok(self.0)
     }
 }
@@ -21,18 +21,141 @@ OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWA
 use no_std_compat::prelude::v1::*;
 use {
     crate::{
+        callbacks::Callbacks,
+        core::{ClauseGroup, Solver},
         interface::SolverInterface,
-        {lbool, Lit},
+        maxsat::MaxSatSolver,
+        qbf::{Qbf, Quantifier},
+        {Lit, Print, Var},
+    },
+    std::{
+        error, fmt,
+        io::{self, BufRead, Write},
     },
-    std::io::{self, BufRead},
 };
 
+/// Error returned when parsing a DIMACS file.
+#[derive(Debug)]
+pub enum ParseError {
+    /// Error while reading the input
+    Io(io::Error),
+    /// Unexpected character in the input
+    UnexpectedChar(char),
+    /// The input ended in the middle of a clause
+    UnexpectedEof,
+    /// An integer is too large to be a literal
+    IntOverflow,
+    /// The header is not of the form `p cnf <num vars> <num clauses>`
+    /// (or `p wcnf <num vars> <num clauses> [<top>]` for WCNF files,
+    /// `p gcnf <num vars> <num clauses> <num groups>` for GCNF files,
+    /// `p inccnf` for iCNF files)
+    InvalidHeader,
+    /// A clause occurs before the header (strict mode)
+    MissingHeader,
+    /// A literal uses a variable larger than declared in the header (strict mode)
+    VarOutOfRange { var: u32, num_vars: u32 },
+    /// The number of clauses differs from the header (strict mode)
+    ClauseCountMismatch { expected: u32, found: u32 },
+    /// A clause of a GCNF file is in a group larger than declared in the header
+    GroupOutOfRange { group: u32, num_groups: u32 },
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseError::Io(e) => write!(f, "{}", e),
+            ParseError::UnexpectedChar(ch) => write!(f, "PARSE ERROR! Unexpected char: {}", ch),
+            ParseError::UnexpectedEof => write!(f, "PARSE ERROR! Unexpected EOF"),
+            ParseError::IntOverflow => write!(f, "PARSE ERROR! Integer too large"),
+            ParseError::InvalidHeader => write!(f, "PARSE ERROR! Invalid DIMACS header"),
+            ParseError::MissingHeader => {
+                write!(f, "PARSE ERROR! DIMACS header missing before first clause")
+            }
+            ParseError::VarOutOfRange { var, num_vars } => write!(
+                f,
+                "PARSE ERROR! DIMACS header mismatch: variable {} but only {} declared",
+                var, num_vars
+            ),
+            ParseError::ClauseCountMismatch { expected, found } => write!(
+                f,
+                "PARSE ERROR! DIMACS header mismatch: {} clauses declared, {} found",
+                expected, found
+            ),
+            ParseError::GroupOutOfRange { group, num_groups } => write!(
+                f,
+                "PARSE ERROR! GCNF header mismatch: group {} but only {} declared",
+                group, num_groups
+            ),
+        }
+    }
+}
+
+impl error::Error for ParseError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            ParseError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for ParseError {
+    fn from(e: io::Error) -> Self {
+        ParseError::Io(e)
+    }
+}
+
+impl From<ParseError> for io::Error {
+    fn from(e: ParseError) -> Self {
+        match e {
+            ParseError::Io(e) => e,
+            e => io::Error::new(io::ErrorKind::InvalidInput, e),
+        }
+    }
+}
+
+/// Wrap `input` so that it is transparently decompressed if it starts with the magic
+/// bytes of gzip (with the `gzip` feature) or xz (with the `xz` feature).
+///
+/// Other inputs are returned unchanged.
+pub fn decompress<'a, R: BufRead + 'a>(mut input: R) -> io::Result<Box<dyn BufRead + 'a>> {
+    let head = input.fill_buf()?;
+    let (is_gz, is_xz) = (
+        head.starts_with(b"\x1F\x8B"),
+        head.starts_with(b"\xFD7zXZ\0"),
+    );
+    #[cfg(feature = "gzip")]
+    if is_gz {
+        let decoder = flate2::bufread::MultiGzDecoder::new(input);
+        return Ok(Box::new(io::BufReader::new(decoder)));
+    }
+    #[cfg(feature = "xz")]
+    if is_xz {
+        let decoder = xz2::bufread::XzDecoder::new_multi_decoder(input);
+        return Ok(Box::new(io::BufReader::new(decoder)));
+    }
+    let _ = (is_gz, is_xz);
+    Ok(Box::new(input))
+}
+
+/// `parse_into(input, solver)` adds the clauses of the DIMACS file `input` to the solver,
+/// creating variables as needed.
+///
+/// The `p cnf` header is mandatory and the number of variables and clauses it declares are checked.
+pub fn parse_into<S: SolverInterface, R: BufRead>(
+    input: &mut R,
+    solver: &mut S,
+) -> Result<(), ParseError> {
+    parse_inner(input, solver, true, false, &mut solve_cube)
+}
+
 /// `parse(input, solver)` adds the content of `input` to the solver
 ///
 /// ## Params
 /// - `is_strict` if true, will fail if number of clauses/vars does not match the declared header
 /// - `incremental` if true, accept the [.icnf format](http://www.siert.nl/icnf/)
-/// - `solver` is used to process incremental calls (`a` lines in icnf)
+/// - `solver` is used to process incremental calls (`a` lines in icnf), whose results
+///   are only logged; use [`parse_icnf`] to handle them.
 /// - `th` is given to `solver` to solve.
 pub fn parse<S: SolverInterface, R: BufRead>(
     input: &mut R,
@@ -40,82 +163,378 @@ pub fn parse<S: SolverInterface, R: BufRead>(
     is_strict: bool,
     incremental: bool,
 ) -> io::Result<()> {
+    Ok(parse_inner(
+        input,
+        solver,
+        is_strict,
+        incremental,
+        &mut solve_cube,
+    )?)
+}
+
+/// Solve under the assumptions `lits`, and log the result.
+fn solve_cube<S: SolverInterface>(solver: &mut S, lits: &[Lit]) {
+    debug!(
+        "solve with assumptions {:?} (ok: {})",
+        &lits,
+        solver.is_ok()
+    );
+    solver.simplify();
+    let _res = solver.solve_limited(lits); // solve under assumptions
+    info!("result under assumptions {:?}: {:?}", &lits, _res);
+}
+
+/// `parse_icnf(input, solver, on_cube)` reads the [iCNF file](http://www.siert.nl/icnf/) `input`,
+/// whose header is `p inccnf`.
+///
+/// Clauses are added to the solver, and `on_cube(solver, lits)` is called for every line
+/// `a <lits> 0`, with the clauses that precede it. Files produced by cube-and-conquer tools
+/// contain a formula followed by its cubes, see [`Solver::cube`](crate::Solver::cube).
+pub fn parse_icnf<S: SolverInterface, R: BufRead>(
+    input: &mut R,
+    solver: &mut S,
+    mut on_cube: impl FnMut(&mut S, &[Lit]),
+) -> Result<(), ParseError> {
+    parse_inner(input, solver, false, true, &mut on_cube)
+}
+
+/// Write the clauses `clauses` followed by the cubes `cubes` into `out`, in the iCNF format.
+pub fn write_icnf<W: Write, C: AsRef<[Lit]>>(
+    out: &mut W,
+    clauses: &[C],
+    cubes: &[C],
+) -> io::Result<()> {
+    writeln!(out, "p inccnf")?;
+    for c in clauses {
+        writeln!(out, "{}", c.as_ref().pp_dimacs())?;
+    }
+    for c in cubes {
+        writeln!(out, "a {}", c.as_ref().pp_dimacs())?;
+    }
+    Ok(())
+}
+
+fn parse_inner<S: SolverInterface, R: BufRead>(
+    input: &mut R,
+    solver: &mut S,
+    is_strict: bool,
+    incremental: bool,
+    on_cube: &mut dyn FnMut(&mut S, &[Lit]),
+) -> Result<(), ParseError> {
     let mut lits = vec![];
-    // let mut num_vars = 0;
-    let mut num_clauses = 0;
+    // `(num_vars, num_clauses)`
+    let mut header = None;
     let mut num_read_clauses = 0;
+    let is_strict = is_strict && !incremental;
     loop {
         skip_whitespace(input)?;
         let ch = next_byte(input)?;
         if ch == Some(b'p') {
             if incremental {
-                skip_line(input)?;
+                let mut p_inccnf = [0; 8];
+                input.read_exact(&mut p_inccnf)?;
+                if &p_inccnf != b"p inccnf" {
+                    return Err(ParseError::InvalidHeader);
+                }
                 continue;
             }
-            let mut header = [0; 5];
-            input.read_exact(&mut header)?;
-            if &header != b"p cnf" {
-                return parse_error(format!("PARSE ERROR! Unexpected char: p"));
+            let mut p_cnf = [0; 5];
+            input.read_exact(&mut p_cnf)?;
+            if &p_cnf != b"p cnf" {
+                return Err(ParseError::InvalidHeader);
             }
-            // num_vars = parse_int(input)?;
-            parse_int(input)?;
-            num_clauses = parse_int(input)?;
-        // eprintln!("num_vars = {}", num_vars);
-        // eprintln!("num_clauses = {}", num_clauses);
+            let num_vars = parse_int(input)?;
+            let num_clauses = parse_int(input)?;
+            if num_vars < 0 || num_clauses < 0 {
+                return Err(ParseError::InvalidHeader);
+            }
+            header = Some((num_vars as u32, num_clauses as u32));
         } else if ch == Some(b'c') {
             skip_line(input)?;
         } else if incremental && ch == Some(b'a') {
             input.consume(1); // skip 'a'
-            read_clause(input, solver, &mut lits)?;
-            debug!(
-                "solve with assumptions {:?} (ok: {})",
-                &lits,
-                solver.is_ok()
-            );
-            solver.simplify();
-            let res = solver.solve_limited(&lits); // solve under assumptions
-            match res {
-                x if x == lbool::TRUE => println!("SAT"),
-                x if x == lbool::FALSE => println!("UNSAT"),
-                x => {
-                    assert_eq!(x, lbool::UNDEF);
-                    println!("UNKNOWN")
-                }
-            }
-        } else if let Some(_) = ch {
-            read_clause(input, solver, &mut lits)?;
+            read_clause(input, solver, &mut lits, None)?;
+            on_cube(solver, &lits);
+        } else if ch.is_some() {
+            let max_var = match header {
+                Some((num_vars, _)) if is_strict => Some(num_vars),
+                None if is_strict => return Err(ParseError::MissingHeader),
+                _ => None,
+            };
+            read_clause(input, solver, &mut lits, max_var)?;
             solver.add_clause_reuse(&mut lits);
             num_read_clauses += 1;
         } else {
             break;
         }
     }
-    if is_strict && !incremental && num_clauses != num_read_clauses {
-        return parse_error(format!(
-            "PARSE ERROR! DIMACS header mismatch: wrong number of clauses"
-        ));
+    if is_strict {
+        let expected = header.map_or(0, |(_, num_clauses)| num_clauses);
+        if expected != num_read_clauses {
+            return Err(ParseError::ClauseCountMismatch {
+                expected,
+                found: num_read_clauses,
+            });
+        }
+    }
+    Ok(())
+}
+
+/// `parse_wcnf(input, solver)` adds the hard and soft clauses of the WCNF file `input`
+/// to the MaxSAT solver, creating variables as needed.
+///
+/// Both the format with a `p wcnf <num vars> <num clauses> <top>` header, where the clauses
+/// whose weight is at least `top` are hard, and the newer format without header, where
+/// hard clauses start with `h`, are accepted.
+pub fn parse_wcnf<Cb: Callbacks, R: BufRead>(
+    input: &mut R,
+    solver: &mut MaxSatSolver<Cb>,
+) -> Result<(), ParseError> {
+    let mut lits = vec![];
+    let mut top = None;
+    loop {
+        skip_whitespace(input)?;
+        let ch = next_byte(input)?;
+        if ch == Some(b'p') {
+            let mut p_wcnf = [0; 6];
+            input.read_exact(&mut p_wcnf)?;
+            if &p_wcnf != b"p wcnf" {
+                return Err(ParseError::InvalidHeader);
+            }
+            let num_vars = parse_int(input)?;
+            let num_clauses = parse_int(input)?;
+            if num_vars < 0 || num_clauses < 0 {
+                return Err(ParseError::InvalidHeader);
+            }
+            // `top` is optional, all clauses are soft without it
+            while matches!(next_byte(input)?, Some(b' ' | b'\t')) {
+                input.consume(1);
+            }
+            if next_byte(input)?.is_some_and(|ch| ch.is_ascii_digit()) {
+                top = Some(parse_weight(input)?);
+            }
+        } else if ch == Some(b'c') {
+            skip_line(input)?;
+        } else if ch == Some(b'h') {
+            input.consume(1); // skip 'h'
+            read_clause(input, solver.solver_mut(), &mut lits, None)?;
+            solver.add_hard_clause(&lits);
+        } else if ch.is_some() {
+            let weight = parse_weight(input)?;
+            read_clause(input, solver.solver_mut(), &mut lits, None)?;
+            if top.is_some_and(|top| weight >= top) {
+                solver.add_hard_clause(&lits);
+            } else {
+                solver.add_soft_clause(&lits, weight);
+            }
+        } else {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// `parse_gcnf(input, solver)` adds the clauses of the
+/// [GCNF file](http://www.satcompetition.org/2011/rules.pdf) `input` to the solver, and
+/// returns the clause groups `1..=n` it declares.
+///
+/// Each clause is preceded by its group `{g}`, the clauses of the group `{0}` are not in
+/// any group. The `p gcnf` header is mandatory and the numbers it declares are checked:
+/// the groups are created after the variables, so that the variables of the file keep
+/// their numbers. The minimal unsatisfiable subsets of groups are given by
+/// [`Solver::extract_group_mus`].
+pub fn parse_gcnf<Cb: Callbacks, R: BufRead>(
+    input: &mut R,
+    solver: &mut Solver<Cb>,
+) -> Result<Vec<ClauseGroup>, ParseError> {
+    let mut lits = vec![];
+    // `(num_vars, num_clauses)`
+    let mut header = None;
+    let mut groups = vec![];
+    let mut num_read_clauses = 0;
+    loop {
+        skip_whitespace(input)?;
+        let ch = next_byte(input)?;
+        if ch == Some(b'p') {
+            let mut p_gcnf = [0; 6];
+            input.read_exact(&mut p_gcnf)?;
+            if &p_gcnf != b"p gcnf" {
+                return Err(ParseError::InvalidHeader);
+            }
+            let num_vars = parse_int(input)?;
+            let num_clauses = parse_int(input)?;
+            let num_groups = parse_int(input)?;
+            if num_vars < 0 || num_clauses < 0 || num_groups < 0 || header.is_some() {
+                return Err(ParseError::InvalidHeader);
+            }
+            if num_vars > 0 {
+                solver.var_of_int(num_vars as u32 - 1);
+            }
+            groups = (0..num_groups).map(|_| solver.new_clause_group()).collect();
+            header = Some((num_vars as u32, num_clauses as u32));
+        } else if ch == Some(b'c') {
+            skip_line(input)?;
+        } else if ch == Some(b'{') {
+            let Some((num_vars, _)) = header else {
+                return Err(ParseError::MissingHeader);
+            };
+            input.consume(1); // skip '{'
+            let group = parse_int(input)?;
+            match next_byte(input)? {
+                Some(b'}') => input.consume(1),
+                Some(ch) => return Err(ParseError::UnexpectedChar(ch as char)),
+                None => return Err(ParseError::UnexpectedEof),
+            }
+            if group < 0 || group as usize > groups.len() {
+                return Err(ParseError::GroupOutOfRange {
+                    group: group.unsigned_abs(),
+                    num_groups: groups.len() as u32,
+                });
+            }
+            read_clause(input, solver, &mut lits, Some(num_vars))?;
+            if group == 0 {
+                solver.add_clause_reuse(&mut lits);
+            } else {
+                solver.add_clause_in_group(groups[group as usize - 1], &lits);
+            }
+            num_read_clauses += 1;
+        } else if let Some(ch) = ch {
+            return Err(ParseError::UnexpectedChar(ch as char));
+        } else {
+            break;
+        }
+    }
+    let expected = header.map_or(0, |(_, num_clauses)| num_clauses);
+    if expected != num_read_clauses {
+        return Err(ParseError::ClauseCountMismatch {
+            expected,
+            found: num_read_clauses,
+        });
+    }
+    Ok(groups)
+}
+
+/// Write the clauses `hard`, in the group `{0}`, and the clauses of each group of
+/// `groups`, numbered from `1`, into `out` in the GCNF format.
+pub fn write_gcnf<W: Write, C: AsRef<[Lit]>, G: AsRef<[C]>>(
+    out: &mut W,
+    hard: &[C],
+    groups: &[G],
+) -> io::Result<()> {
+    let clauses = || {
+        let hard = hard.iter().map(|c| (0, c.as_ref()));
+        let groups = groups.iter().enumerate();
+        hard.chain(groups.flat_map(|(i, g)| g.as_ref().iter().map(move |c| (i + 1, c.as_ref()))))
+    };
+    let num_vars = clauses()
+        .flat_map(|(_, c)| c.iter().map(|lit| lit.var().idx() + 1))
+        .max()
+        .unwrap_or(0);
+    let num_clauses = clauses().count();
+    writeln!(out, "p gcnf {} {} {}", num_vars, num_clauses, groups.len())?;
+    for (group, c) in clauses() {
+        writeln!(out, "{{{}}} {}", group, c.pp_dimacs())?;
     }
     Ok(())
 }
 
+/// `parse_qdimacs(input)` reads the [QDIMACS file](https://www.qbflib.org/qdimacs.html)
+/// `input`: a `p cnf` header, quantifier blocks `e <vars> 0` or `a <vars> 0`, then
+/// clauses.
+///
+/// The numbers of variables and clauses declared in the header are checked.
+pub fn parse_qdimacs<R: BufRead>(input: &mut R) -> Result<Qbf, ParseError> {
+    let mut qbf = Qbf::default();
+    let mut header = None;
+    let mut lits = vec![];
+    let read_lits = |input: &mut R, lits: &mut Vec<Lit>, num_vars| loop {
+        let parsed_lit = parse_int(input)?;
+        if parsed_lit == 0 {
+            return Ok(());
+        }
+        let var = parsed_lit.unsigned_abs();
+        if var > num_vars {
+            return Err(ParseError::VarOutOfRange { var, num_vars });
+        }
+        lits.push(Lit::new(Var::from_idx(var - 1), parsed_lit > 0));
+    };
+    loop {
+        skip_whitespace(input)?;
+        let ch = next_byte(input)?;
+        if ch == Some(b'p') {
+            let mut p_cnf = [0; 5];
+            input.read_exact(&mut p_cnf)?;
+            if &p_cnf != b"p cnf" {
+                return Err(ParseError::InvalidHeader);
+            }
+            let num_vars = parse_int(input)?;
+            let num_clauses = parse_int(input)?;
+            if num_vars < 0 || num_clauses < 0 || header.is_some() {
+                return Err(ParseError::InvalidHeader);
+            }
+            qbf.num_vars = num_vars as u32;
+            header = Some(num_clauses as u32);
+        } else if ch == Some(b'c') {
+            skip_line(input)?;
+        } else if let Some(ch) = ch {
+            if header.is_none() {
+                return Err(ParseError::MissingHeader);
+            }
+            lits.clear();
+            let q = match ch {
+                b'e' => Some(Quantifier::Exists),
+                b'a' => Some(Quantifier::Forall),
+                _ => None,
+            };
+            if let Some(q) = q {
+                if !qbf.clauses.is_empty() {
+                    return Err(ParseError::UnexpectedChar(ch as char));
+                }
+                input.consume(1); // skip 'e' or 'a'
+                read_lits(input, &mut lits, qbf.num_vars)?;
+                qbf.prefix
+                    .push((q, lits.iter().map(|lit| lit.var()).collect()));
+            } else {
+                read_lits(input, &mut lits, qbf.num_vars)?;
+                qbf.clauses.push(lits.clone());
+            }
+        } else {
+            break;
+        }
+    }
+    let expected = header.unwrap_or(0);
+    if expected as usize != qbf.clauses.len() {
+        return Err(ParseError::ClauseCountMismatch {
+            expected,
+            found: qbf.clauses.len() as u32,
+        });
+    }
+    Ok(qbf)
+}
+
+/// Read a clause into `lits`, failing on variables above `max_var` if given
 fn read_clause<S: SolverInterface, R: BufRead>(
     input: &mut R,
     solver: &mut S,
     lits: &mut Vec<Lit>,
-) -> io::Result<()> {
+    max_var: Option<u32>,
+) -> Result<(), ParseError> {
     lits.clear();
     loop {
         let parsed_lit = parse_int(input)?;
         if parsed_lit == 0 {
             return Ok(());
         }
-        let var = (parsed_lit.abs() - 1) as u32;
-        let lit = Lit::new(solver.var_of_int(var), parsed_lit > 0);
+        let var = parsed_lit.unsigned_abs();
+        if let Some(num_vars) = max_var.filter(|&n| var > n) {
+            return Err(ParseError::VarOutOfRange { var, num_vars });
+        }
+        let lit = Lit::new(solver.var_of_int(var - 1), parsed_lit > 0);
         lits.push(lit);
     }
 }
 
-fn parse_int<R: BufRead>(input: &mut R) -> io::Result<i32> {
+pub(crate) fn parse_int<R: BufRead>(input: &mut R) -> Result<i32, ParseError> {
     skip_whitespace(input)?;
     let ch = next_byte(input)?;
     let neg = if ch == Some(b'+') || ch == Some(b'-') {
@@ -125,19 +544,22 @@ fn parse_int<R: BufRead>(input: &mut R) -> io::Result<i32> {
         false
     };
     if let Some(ch) = next_byte(input)? {
-        if !(b'0' <= ch && ch <= b'9') {
-            return parse_error(format!("PARSE ERROR! Unexpected char: {}", ch as char));
+        if !ch.is_ascii_digit() {
+            return Err(ParseError::UnexpectedChar(ch as char));
         }
     } else {
-        return parse_error(format!("PARSE ERROR! Unexpected EOF"));
+        return Err(ParseError::UnexpectedEof);
     };
-    let mut val = 0;
+    let mut val: i32 = 0;
     while let Some(ch) = next_byte(input)? {
-        if !(b'0' <= ch && ch <= b'9') {
+        if !ch.is_ascii_digit() {
             break;
         }
         input.consume(1);
-        val = val * 10 + (ch - b'0') as i32;
+        val = val
+            .checked_mul(10)
+            .and_then(|val| val.checked_add((ch - b'0') as i32))
+            .ok_or(ParseError::IntOverflow)?;
     }
     if neg {
         Ok(-val)
@@ -146,20 +568,41 @@ fn parse_int<R: BufRead>(input: &mut R) -> io::Result<i32> {
     }
 }
 
+fn parse_weight<R: BufRead>(input: &mut R) -> Result<u64, ParseError> {
+    skip_whitespace(input)?;
+    match next_byte(input)? {
+        Some(ch) if ch.is_ascii_digit() => (),
+        Some(ch) => return Err(ParseError::UnexpectedChar(ch as char)),
+        None => return Err(ParseError::UnexpectedEof),
+    }
+    let mut val: u64 = 0;
+    while let Some(ch) = next_byte(input)? {
+        if !ch.is_ascii_digit() {
+            break;
+        }
+        input.consume(1);
+        val = val
+            .checked_mul(10)
+            .and_then(|val| val.checked_add((ch - b'0') as u64))
+            .ok_or(ParseError::IntOverflow)?;
+    }
+    Ok(val)
+}
+
 #[inline(always)]
 fn is_whitespace(ch: Option<u8>) -> bool {
-    ch.map(|ch| b'\x09' <= ch && ch <= b'\x0d' || ch == b' ')
+    ch.map(|ch| (b'\x09'..=b'\x0d').contains(&ch) || ch == b' ')
         .unwrap_or(false)
 }
 
-fn skip_whitespace<R: BufRead>(input: &mut R) -> io::Result<()> {
+pub(crate) fn skip_whitespace<R: BufRead>(input: &mut R) -> io::Result<()> {
     while is_whitespace(next_byte(input)?) {
         input.consume(1);
     }
     Ok(())
 }
 
-fn skip_line<R: BufRead>(input: &mut R) -> io::Result<()> {
+pub(crate) fn skip_line<R: BufRead>(input: &mut R) -> io::Result<()> {
     loop {
         if let Some(ch) = next_byte(input)? {
             input.consume(1);
@@ -172,10 +615,189 @@ fn skip_line<R: BufRead>(input: &mut R) -> io::Result<()> {
     }
 }
 
-fn next_byte<R: BufRead>(input: &mut R) -> io::Result<Option<u8>> {
-    Ok(input.fill_buf()?.first().map(|&ch| ch))
+pub(crate) fn next_byte<R: BufRead>(input: &mut R) -> io::Result<Option<u8>> {
+    Ok(input.fill_buf()?.first().copied())
 }
 
-fn parse_error<T>(message: String) -> io::Result<T> {
-    Err(io::Error::new(io::ErrorKind::InvalidInput, message))
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{lbool, BasicSolver};
+
+    fn parse_str(s: &str) -> Result<BasicSolver, ParseError> {
+        let mut solver = BasicSolver::default();
+        parse_into(&mut s.as_bytes(), &mut solver)?;
+        Ok(solver)
+    }
+
+    #[test]
+    fn test_parse_into() {
+        let mut solver = parse_str("c comment\np cnf 3 2\n1 -2 0\n2 3\n0\n").unwrap();
+        assert_eq!(solver.num_vars(), 3);
+        assert_eq!(solver.num_clauses(), 2);
+        assert_eq!(solver.solve_limited(&[]), lbool::TRUE);
+        assert!(matches!(
+            parse_str("1 2 0\n"),
+            Err(ParseError::MissingHeader)
+        ));
+        assert!(matches!(
+            parse_str("p cnf 2 1\n1 3 0\n"),
+            Err(ParseError::VarOutOfRange {
+                var: 3,
+                num_vars: 2
+            })
+        ));
+        assert!(matches!(
+            parse_str("p cnf 2 2\n1 2 0\n"),
+            Err(ParseError::ClauseCountMismatch {
+                expected: 2,
+                found: 1
+            })
+        ));
+        assert!(matches!(
+            parse_str("p cnf 2 1\n1 x 0\n"),
+            Err(ParseError::UnexpectedChar('x'))
+        ));
+        assert!(matches!(
+            parse_str("p cnf 2 1\n1 2"),
+            Err(ParseError::UnexpectedEof)
+        ));
+        assert!(matches!(
+            parse_str("p cnf 1 1\n3000000000 0\n"),
+            Err(ParseError::IntOverflow)
+        ));
+        assert!(matches!(
+            parse_str("p dnf 1 1\n"),
+            Err(ParseError::InvalidHeader)
+        ));
+    }
+
+    #[test]
+    fn test_parse_wcnf() {
+        for wcnf in [
+            "c old format\np wcnf 3 4 10\n10 -1 -2 0\n3 1 0\n2 2 0\n4 2 3 0\n",
+            "c new format\nh -1 -2 0\n3 1 0\n2 2 0\n4 2 3 0\n",
+        ] {
+            let mut solver = MaxSatSolver::new(BasicSolver::default());
+            parse_wcnf(&mut wcnf.as_bytes(), &mut solver).unwrap();
+            assert_eq!(solver.num_soft_clauses(), 3);
+            assert_eq!(solver.solve(), lbool::TRUE);
+            assert_eq!(solver.cost(), Some(2));
+        }
+        let mut solver = MaxSatSolver::new(BasicSolver::default());
+        let res = parse_wcnf(&mut "p cnf 1 1\n1 1 0\n".as_bytes(), &mut solver);
+        assert!(matches!(res, Err(ParseError::InvalidHeader)));
+    }
+
+    #[test]
+    fn test_icnf() {
+        let mut solver = BasicSolver::default();
+        let a = Lit::new(solver.new_var_default(), true);
+        let b = Lit::new(solver.new_var_default(), true);
+        let clauses = [vec![a, b], vec![!a, b]];
+        let cubes = [vec![a], vec![!a, !b]];
+        let mut out = vec![];
+        write_icnf(&mut out, &clauses, &cubes).unwrap();
+        let icnf = String::from_utf8(out).unwrap();
+        assert_eq!(icnf, "p inccnf\n1 2 0\n-1 2 0\na 1 0\na -1 -2 0\n");
+
+        let mut solver = BasicSolver::default();
+        let mut res = vec![];
+        let on_cube =
+            |s: &mut BasicSolver, lits: &[Lit]| res.push((lits.len(), s.solve_limited(lits)));
+        parse_icnf(&mut icnf.as_bytes(), &mut solver, on_cube).unwrap();
+        assert_eq!(res, [(1, lbool::TRUE), (2, lbool::FALSE)]);
+        let res = parse_icnf(&mut "p cnf 1 1\n1 0\n".as_bytes(), &mut solver, |_, _| ());
+        assert!(matches!(res, Err(ParseError::InvalidHeader)));
+    }
+
+    #[test]
+    fn test_gcnf() {
+        let mut solver = BasicSolver::default();
+        let a = Lit::new(solver.new_var_default(), true);
+        let b = Lit::new(solver.new_var_default(), true);
+        let hard = [vec![a, b]];
+        let groups = [vec![vec![!a]], vec![vec![!b], vec![a]], vec![vec![!a, !b]]];
+        let mut out = vec![];
+        write_gcnf(&mut out, &hard, &groups).unwrap();
+        let gcnf = String::from_utf8(out).unwrap();
+        assert_eq!(
+            gcnf,
+            "p gcnf 2 5 3\n{0} 1 2 0\n{1} -1 0\n{2} -2 0\n{2} 1 0\n{3} -1 -2 0\n"
+        );
+
+        let mut solver = BasicSolver::default();
+        let groups = parse_gcnf(&mut gcnf.as_bytes(), &mut solver).unwrap();
+        assert_eq!(groups.len(), 3);
+        // the variables of the file come first
+        assert!(groups.iter().all(|g| g.activation_lit().var().idx() >= 2));
+        assert_eq!(solver.solve_limited(&[]), lbool::FALSE);
+        let mus = solver.extract_group_mus().unwrap();
+        assert!(mus.len() == 2 && mus.contains(&groups[0]) && mus.contains(&groups[1]));
+
+        let parse = |s: &str| parse_gcnf(&mut s.as_bytes(), &mut BasicSolver::default());
+        assert!(matches!(
+            parse("p gcnf 1 1 1\n{2} 1 0\n"),
+            Err(ParseError::GroupOutOfRange {
+                group: 2,
+                num_groups: 1
+            })
+        ));
+        assert!(matches!(parse("{0} 1 0\n"), Err(ParseError::MissingHeader)));
+        assert!(matches!(
+            parse("p gcnf 1 1 1\n1 0\n"),
+            Err(ParseError::UnexpectedChar('1'))
+        ));
+        assert!(matches!(
+            parse("p gcnf 1 2 1\n{1} 1 0\n"),
+            Err(ParseError::ClauseCountMismatch {
+                expected: 2,
+                found: 1
+            })
+        ));
+    }
+
+    #[test]
+    fn test_qdimacs() {
+        let qdimacs = "c forall x exists y, x xor y\np cnf 3 2\na 1 0\ne 2 0\n1 2 0\n-1 -2 0\n";
+        let qbf = parse_qdimacs(&mut qdimacs.as_bytes()).unwrap();
+        assert_eq!(qbf.num_vars, 3);
+        assert_eq!(qbf.prefix.len(), 2);
+        assert_eq!(qbf.prefix[0].0, Quantifier::Forall);
+        assert_eq!(qbf.solve(&mut vec![]), lbool::TRUE);
+        // exists y forall x is false
+        let qdimacs = "p cnf 2 2\ne 2 0\na 1 0\n1 2 0\n-1 -2 0\n";
+        let qbf = parse_qdimacs(&mut qdimacs.as_bytes()).unwrap();
+        assert_eq!(qbf.solve(&mut vec![]), lbool::FALSE);
+        assert!(matches!(
+            parse_qdimacs(&mut "p cnf 1 1\n1 0\ne 1 0\n".as_bytes()),
+            Err(ParseError::UnexpectedChar('e'))
+        ));
+        assert!(matches!(
+            parse_qdimacs(&mut "p cnf 1 1\na 2 0\n1 0\n".as_bytes()),
+            Err(ParseError::VarOutOfRange {
+                var: 2,
+                num_vars: 1
+            })
+        ));
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn test_decompress_gzip() {
+        use std::io::Write;
+        let cnf = "p cnf 2 2\n1 2 0\n-1 0\n";
+        let mut enc = flate2::write::GzEncoder::new(vec![], flate2::Compression::default());
+        enc.write_all(cnf.as_bytes()).unwrap();
+        let gz = enc.finish().unwrap();
+        let mut solver = BasicSolver::default();
+        parse_into(&mut decompress(&gz[..]).unwrap(), &mut solver).unwrap();
+        assert_eq!(solver.solve_limited(&[]), lbool::TRUE);
+        let b = Lit::new(solver.var_of_int(1), true);
+        assert_eq!(solver.value_lit(b), lbool::TRUE);
+        // uncompressed input is read as is
+        let mut solver = BasicSolver::default();
+        parse_into(&mut decompress(cnf.as_bytes()).unwrap(), &mut solver).unwrap();
+        assert_eq!(solver.num_vars(), 2);
+    }
 }
@@ -1,14 +1,58 @@
-//! DRAT proofs
+//! DRAT and LRAT proofs
 use no_std_compat::prelude::v1::*;
 use {
     crate::{clause::ClauseIterable, Lit},
-    std::{fmt, i32},
+    std::{any::Any, fmt},
 };
 
 /// A serialized DRAT proof.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct Proof(Vec<i32>);
 
+/// A destination for the proof steps emitted by the solver.
+///
+/// See [`Solver::set_proof_output`](crate::Solver::set_proof_output). The output given
+/// back by [`Solver::take_proof_output`](crate::Solver::take_proof_output) can be
+/// downcast to its concrete type, through `Box<dyn Any>`.
+pub trait ProofOutput: Any {
+    /// Register the addition of a clause that is RUP (or RAT on its first literal)
+    /// with respect to the current clause set.
+    fn add_clause(&mut self, c: &[Lit]);
+
+    /// Register the deletion of a clause.
+    fn delete_clause(&mut self, c: &[Lit]);
+
+    /// Does this output need clause identifiers and antecedents, as in LRAT?
+    ///
+    /// If it does, the solver numbers the clauses it is given in the order they are
+    /// added (starting from `1`), and calls [`add_clause_with_hints`](Self::add_clause_with_hints)
+    /// and [`delete_clause_with_id`](Self::delete_clause_with_id) instead of the
+    /// plain DRAT methods.
+    fn wants_hints(&self) -> bool {
+        false
+    }
+
+    /// Register the addition of clause `id`, which follows by unit propagation
+    /// over the clauses `hints`, in this order.
+    fn add_clause_with_hints(&mut self, id: u64, c: &[Lit], hints: &[u64]) {
+        let _ = (id, hints);
+        self.add_clause(c)
+    }
+
+    /// Register the deletion of clause `id`.
+    fn delete_clause_with_id(&mut self, id: u64, c: &[Lit]) {
+        let _ = id;
+        self.delete_clause(c)
+    }
+
+    /// Flush any buffered output, returning the first error encountered
+    /// while writing proof steps, if any.
+    #[cfg(feature = "std")]
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
 mod proof {
     use {super::*, std::fmt::Write};
 
@@ -35,8 +79,7 @@ mod proof {
         }
 
         fn push_lit(&mut self, lit: Lit) {
-            let i: i32 = (if lit.sign() { 1 } else { -1 }) * ((lit.var().idx() + 1) as i32);
-            self.0.push(i)
+            self.0.push(lit_to_int(lit))
         }
 
         /// Register clause creation.
@@ -63,9 +106,431 @@ mod proof {
             self.0.push(0);
         }
     }
+
+    impl ProofOutput for Proof {
+        fn add_clause(&mut self, c: &[Lit]) {
+            self.create_clause(&c)
+        }
+
+        fn delete_clause(&mut self, c: &[Lit]) {
+            Proof::delete_clause(self, &c)
+        }
+    }
+}
+
+/// DIMACS integer representation of `lit`
+#[inline]
+fn lit_to_int(lit: Lit) -> i32 {
+    (if lit.sign() { 1 } else { -1 }) * ((lit.var().idx() + 1) as i32)
+}
+
+#[cfg(feature = "std")]
+pub use self::writer::{DratWriter, LratWriter};
+
+#[cfg(feature = "std")]
+mod writer {
+    use {
+        super::*,
+        std::io::{self, Write},
+    };
+
+    /// Streams a DRAT proof into an `io::Write` sink, in either the textual
+    /// or the binary format accepted by `drat-trim`.
+    ///
+    /// Errors are latched: after the first failed write, further steps are
+    /// dropped and the error is returned by [`ProofOutput::flush`].
+    pub struct DratWriter<W: Write> {
+        out: W,
+        binary: bool,
+        err: Option<io::Error>,
+        buf: Vec<u8>,
+    }
+
+    impl<W: Write> DratWriter<W> {
+        /// Write a textual DRAT proof into `out`.
+        pub fn new(out: W) -> Self {
+            DratWriter {
+                out,
+                binary: false,
+                err: None,
+                buf: vec![],
+            }
+        }
+
+        /// Write a binary DRAT proof into `out`.
+        pub fn new_binary(out: W) -> Self {
+            DratWriter {
+                binary: true,
+                ..DratWriter::new(out)
+            }
+        }
+
+        /// Recover the underlying sink.
+        pub fn into_inner(self) -> W {
+            self.out
+        }
+
+        fn push_lit(&mut self, lit: Lit) {
+            if self.binary {
+                // variable-length encoding of `2*var + sign`
+                push_varint(
+                    &mut self.buf,
+                    2 * (lit.var().idx() as u64 + 1) + !lit.sign() as u64,
+                );
+            } else {
+                write!(self.buf, "{} ", lit_to_int(lit)).unwrap();
+            }
+        }
+
+        fn step(&mut self, delete: bool, c: &[Lit]) {
+            if self.err.is_some() {
+                return;
+            }
+            self.buf.clear();
+            match (self.binary, delete) {
+                (true, false) => self.buf.push(b'a'),
+                (true, true) => self.buf.push(b'd'),
+                (false, true) => self.buf.extend_from_slice(b"d "),
+                (false, false) => (),
+            }
+            for &lit in c {
+                self.push_lit(lit);
+            }
+            if self.binary {
+                self.buf.push(0);
+            } else {
+                self.buf.extend_from_slice(b"0\n");
+            }
+            if let Err(e) = self.out.write_all(&self.buf) {
+                self.err = Some(e);
+            }
+        }
+    }
+
+    /// Variable-length encoding used by binary DRAT and LRAT
+    fn push_varint(buf: &mut Vec<u8>, mut u: u64) {
+        while u > 127 {
+            buf.push(128 | (u & 127) as u8);
+            u >>= 7;
+        }
+        buf.push(u as u8);
+    }
+
+    impl<W: Write + 'static> ProofOutput for DratWriter<W> {
+        fn add_clause(&mut self, c: &[Lit]) {
+            self.step(false, c)
+        }
+
+        fn delete_clause(&mut self, c: &[Lit]) {
+            self.step(true, c)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            if let Some(e) = self.err.take() {
+                return Err(e);
+            }
+            self.out.flush()
+        }
+    }
+
+    /// Streams a LRAT proof into an `io::Write` sink, in either the textual
+    /// or the binary format.
+    ///
+    /// Clauses of the original problem are numbered from `1`, in the order
+    /// they were added to the solver. As for [`DratWriter`], errors are latched.
+    pub struct LratWriter<W: Write> {
+        out: W,
+        binary: bool,
+        err: Option<io::Error>,
+        buf: Vec<u8>,
+        last_id: u64,
+    }
+
+    impl<W: Write> LratWriter<W> {
+        /// Write a textual LRAT proof into `out`.
+        pub fn new(out: W) -> Self {
+            LratWriter {
+                out,
+                binary: false,
+                err: None,
+                buf: vec![],
+                last_id: 0,
+            }
+        }
+
+        /// Write a binary LRAT proof into `out`.
+        pub fn new_binary(out: W) -> Self {
+            LratWriter {
+                binary: true,
+                ..LratWriter::new(out)
+            }
+        }
+
+        /// Recover the underlying sink.
+        pub fn into_inner(self) -> W {
+            self.out
+        }
+
+        fn push_lit(&mut self, lit: Lit) {
+            if self.binary {
+                push_varint(
+                    &mut self.buf,
+                    2 * (lit.var().idx() as u64 + 1) + !lit.sign() as u64,
+                );
+            } else {
+                write!(self.buf, "{} ", lit_to_int(lit)).unwrap();
+            }
+        }
+
+        fn push_id(&mut self, id: u64) {
+            if self.binary {
+                push_varint(&mut self.buf, 2 * id);
+            } else {
+                write!(self.buf, "{} ", id).unwrap();
+            }
+        }
+
+        fn push_zero(&mut self) {
+            if self.binary {
+                self.buf.push(0);
+            } else {
+                self.buf.extend_from_slice(b"0");
+            }
+        }
+
+        fn write_buf(&mut self) {
+            if !self.binary {
+                self.buf.push(b'\n');
+            }
+            if let Err(e) = self.out.write_all(&self.buf) {
+                self.err = Some(e);
+            }
+        }
+    }
+
+    impl<W: Write + 'static> ProofOutput for LratWriter<W> {
+        fn add_clause(&mut self, _: &[Lit]) {
+            panic!("LRAT proofs need clause ids")
+        }
+
+        fn delete_clause(&mut self, _: &[Lit]) {
+            panic!("LRAT proofs need clause ids")
+        }
+
+        fn wants_hints(&self) -> bool {
+            true
+        }
+
+        fn add_clause_with_hints(&mut self, id: u64, c: &[Lit], hints: &[u64]) {
+            if self.err.is_some() {
+                return;
+            }
+            self.last_id = self.last_id.max(id);
+            self.buf.clear();
+            if self.binary {
+                self.buf.push(b'a');
+            }
+            self.push_id(id);
+            for &lit in c {
+                self.push_lit(lit);
+            }
+            self.push_zero();
+            if !self.binary {
+                self.buf.push(b' ');
+            }
+            for &h in hints {
+                self.push_id(h);
+            }
+            self.push_zero();
+            self.write_buf();
+        }
+
+        fn delete_clause_with_id(&mut self, id: u64, _: &[Lit]) {
+            if self.err.is_some() {
+                return;
+            }
+            self.buf.clear();
+            if self.binary {
+                self.buf.push(b'd');
+            } else {
+                // the id of a deletion step is irrelevant, it must just not be a future id
+                write!(self.buf, "{} d ", self.last_id).unwrap();
+            }
+            self.push_id(id);
+            self.push_zero();
+            self.write_buf();
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            if let Some(e) = self.err.take() {
+                return Err(e);
+            }
+            self.out.flush()
+        }
+    }
 }
 
-// TODO:
-// - define `Proof` struct here
-// - remove proof handling from core
-// - use it in main in callbacks to optionally record proofs (with `on_axiom` + `on_learnt`)
+#[cfg(all(test, feature = "std"))]
+mod test {
+    use super::*;
+    use crate::{lbool, BasicSolver, SolverInterface};
+    use std::sync::{Arc, Mutex};
+
+    /// Shared buffer, so the test can look at the proof after the solver took ownership.
+    #[derive(Clone, Default)]
+    struct Shared(Arc<Mutex<Vec<u8>>>);
+
+    impl std::io::Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_writer_text() {
+        let mut sat = BasicSolver::default();
+        let a = Lit::new(sat.new_var_default(), true);
+        let b = Lit::new(sat.new_var_default(), true);
+        let mut w = DratWriter::new(vec![]);
+        w.add_clause(&[a, !b]);
+        w.delete_clause(&[!a]);
+        w.add_clause(&[]);
+        assert_eq!(&w.into_inner(), b"1 -2 0\nd -1 0\n0\n");
+    }
+
+    #[test]
+    fn test_writer_binary() {
+        let mut sat = BasicSolver::default();
+        let a = Lit::new(sat.var_of_int(62), false);
+        let mut w = DratWriter::new_binary(vec![]);
+        w.add_clause(&[a]);
+        w.delete_clause(&[!a]);
+        // -63 is encoded as 2*63+1 = 127, 63 as 126
+        assert_eq!(&w.into_inner(), &[b'a', 127, 0, b'd', 126, 0]);
+    }
+
+    #[test]
+    fn test_solver_proof_ends_with_empty_clause() {
+        let mut sat = BasicSolver::default();
+        let buf = Shared::default();
+        sat.set_drat_output(buf.clone(), false);
+        let a = Lit::new(sat.new_var_default(), true);
+        let b = Lit::new(sat.new_var_default(), true);
+        for (x, y) in [(a, b), (a, !b), (!a, b), (!a, !b)] {
+            sat.add_clause_reuse(&mut vec![x, y]);
+        }
+        assert_eq!(sat.solve_limited(&[]), lbool::FALSE);
+        sat.flush_proof().unwrap();
+        let proof = String::from_utf8(buf.0.lock().unwrap().clone()).unwrap();
+        assert!(proof.ends_with("\n0\n"), "proof: {:?}", proof);
+    }
+
+    #[test]
+    fn test_lrat_writer() {
+        let mut sat = BasicSolver::default();
+        let a = Lit::new(sat.new_var_default(), true);
+        let b = Lit::new(sat.new_var_default(), true);
+        let mut w = LratWriter::new(vec![]);
+        w.add_clause_with_hints(5, &[a, !b], &[1, 3]);
+        w.delete_clause_with_id(3, &[!a]);
+        w.add_clause_with_hints(6, &[], &[5, 2]);
+        assert_eq!(&w.into_inner(), b"5 1 -2 0 1 3 0\n5 d 3 0\n6 0 5 2 0\n");
+
+        let mut w = LratWriter::new_binary(vec![]);
+        w.add_clause_with_hints(5, &[a, !b], &[1, 3]);
+        w.delete_clause_with_id(3, &[!a]);
+        assert_eq!(&w.into_inner(), &[b'a', 10, 2, 5, 0, 2, 6, 0, b'd', 6, 0]);
+    }
+
+    /// Check a textual LRAT refutation of `cnf`.
+    fn check_lrat(cnf: &[Vec<i32>], proof: &str) {
+        use std::collections::{HashMap, HashSet};
+        let mut db: HashMap<u64, Vec<i32>> = (1..).zip(cnf.iter().cloned()).collect();
+        let mut refuted = false;
+        for line in proof.lines() {
+            let toks: Vec<&str> = line.split_whitespace().collect();
+            if toks[1] == "d" {
+                for id in &toks[2..toks.len() - 1] {
+                    db.remove(&id.parse().unwrap());
+                }
+                continue;
+            }
+            let nums: Vec<i64> = toks.iter().map(|t| t.parse().unwrap()).collect();
+            let zero = nums[1..].iter().position(|&x| x == 0).unwrap() + 1;
+            let clause: Vec<i32> = nums[1..zero].iter().map(|&x| x as i32).collect();
+            let mut assigned: HashSet<i32> = clause.iter().map(|&l| -l).collect();
+            let mut conflict = false;
+            for &h in &nums[zero + 1..nums.len() - 1] {
+                let c = &db[&(h as u64)];
+                let open: Vec<i32> = c
+                    .iter()
+                    .cloned()
+                    .filter(|l| !assigned.contains(&-l))
+                    .collect();
+                match open.len() {
+                    0 => {
+                        conflict = true;
+                        break;
+                    }
+                    1 => assigned.insert(open[0]),
+                    _ => panic!("hint {} is not unit in {:?}", h, line),
+                };
+            }
+            assert!(conflict, "no conflict in {:?}", line);
+            refuted |= clause.is_empty();
+            assert!(
+                db.insert(nums[0] as u64, clause).is_none(),
+                "id reused: {:?}",
+                line
+            );
+        }
+        assert!(refuted, "no empty clause");
+    }
+
+    fn lrat_refutation(cnf: &[Vec<i32>]) -> String {
+        let mut sat = BasicSolver::default();
+        let buf = Shared::default();
+        sat.set_lrat_output(buf.clone(), false);
+        for c in cnf {
+            let mut c = c
+                .iter()
+                .map(|&i| Lit::new(sat.var_of_int(i.unsigned_abs() - 1), i > 0))
+                .collect();
+            sat.add_clause_reuse(&mut c);
+        }
+        assert_eq!(sat.solve_limited(&[]), lbool::FALSE);
+        sat.flush_proof().unwrap();
+        let proof = String::from_utf8(buf.0.lock().unwrap().clone()).unwrap();
+        proof
+    }
+
+    #[test]
+    fn test_solver_lrat_pigeons() {
+        // 4 pigeons in 3 holes, `x(p,h)` means pigeon `p` is in hole `h`
+        let x = |p: i32, h: i32| 1 + 3 * p + h;
+        let mut cnf = vec![];
+        for p in 0..4 {
+            cnf.push((0..3).map(|h| x(p, h)).collect());
+        }
+        for h in 0..3 {
+            for p in 0..4 {
+                for q in 0..p {
+                    cnf.push(vec![-x(p, h), -x(q, h)]);
+                }
+            }
+        }
+        check_lrat(&cnf, &lrat_refutation(&cnf));
+    }
+
+    #[test]
+    fn test_solver_lrat_simplified_input() {
+        // clauses that are partially or totally false at level 0 when added
+        let cnf = vec![vec![1], vec![-1, 2, 3], vec![-2], vec![-1, 2, -3]];
+        check_lrat(&cnf, &lrat_refutation(&cnf));
+        let cnf = vec![vec![1], vec![-1, 2], vec![-1, -2, 3], vec![-3, -2]];
+        check_lrat(&cnf, &lrat_refutation(&cnf));
+    }
+}
@@ -0,0 +1,453 @@
+//! Cardinality and pseudo-Boolean constraints, compiled into clauses
+//!
+//! Unlike [`Solver::add_at_most_k`](crate::Solver::add_at_most_k) and
+//! [`Solver::add_pb_constraint`](crate::Solver::add_pb_constraint), which are propagated
+//! natively, these encodings only use clauses and fresh variables, so they work with
+//! any [`SolverInterface`]. They count the true inputs with output literals, which are
+//! implied by the sums they stand for: the bound is enforced by making the outputs
+//! above it false, and it can then be strengthened by adding more units. This is what
+//! linear search in optimization loops needs, as each model found tightens the bound.
+use no_std_compat::prelude::v1::*;
+use {
+    crate::{clause::Lit, interface::SolverInterface},
+    std::collections::BTreeMap,
+};
+
+/// Encoding of a [`Cardinality`] constraint.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CardEncoding {
+    /// A tree of unary adders, with `O(n k)` clauses.
+    Totalizer,
+    /// Sinz's sequential counter, with `O(n k)` clauses.
+    SequentialCounter,
+    /// Batcher's odd-even merge sort, with `O(n log² n)` clauses.
+    SortingNetwork,
+}
+
+/// The constraint that at most `k` of some literals are true.
+#[derive(Clone, Debug)]
+pub struct Cardinality {
+    /// `outputs[j]` is implied by `j + 1` of the inputs being true, for `j <= k` of the
+    /// initial bound.
+    outputs: Vec<Lit>,
+    bound: usize,
+}
+
+impl Cardinality {
+    /// Encode into `solver` the constraint that at most `k` of `lits` are true.
+    pub fn new<S: SolverInterface>(
+        solver: &mut S,
+        lits: &[Lit],
+        k: usize,
+        encoding: CardEncoding,
+    ) -> Self {
+        let outputs = if lits.is_empty() {
+            vec![]
+        } else {
+            match encoding {
+                CardEncoding::Totalizer => totalizer(solver, lits, k + 1),
+                CardEncoding::SequentialCounter => sequential_counter(solver, lits, k + 1),
+                CardEncoding::SortingNetwork => {
+                    let mut outputs = sorting_network(solver, lits);
+                    outputs.truncate(k + 1);
+                    outputs
+                }
+            }
+        };
+        let mut card = Cardinality {
+            outputs,
+            bound: usize::MAX,
+        };
+        card.strengthen(solver, k);
+        card
+    }
+
+    /// The current bound.
+    pub fn bound(&self) -> usize {
+        self.bound
+    }
+
+    /// Literals counting the true inputs: `outputs()[j]` is implied by `j + 1` of them
+    /// being true, for `j` up to the initial bound.
+    ///
+    /// `!outputs()[j]` can be used as an assumption, to solve with the bound `j`.
+    pub fn outputs(&self) -> &[Lit] {
+        &self.outputs
+    }
+
+    /// Make the bound `k`, if it is smaller than the current one.
+    ///
+    /// Returns `false` if the solver is then known to be unsatisfiable.
+    pub fn strengthen<S: SolverInterface>(&mut self, solver: &mut S, k: usize) -> bool {
+        for &o in self.outputs.iter().take(self.bound).skip(k) {
+            solver.add_clause_reuse(&mut vec![!o]);
+        }
+        self.bound = self.bound.min(k);
+        solver.is_ok()
+    }
+}
+
+/// The constraint that the sum of the weights of some true literals is at most `k`.
+///
+/// It is encoded by a generalized totalizer, whose outputs are the sums that can be
+/// reached (the ones above the initial bound being merged).
+#[derive(Clone, Debug)]
+pub struct PseudoBoolean {
+    /// Sums with their literals, each implied by the sum of the true inputs being
+    /// exactly it.
+    outputs: Vec<(u64, Lit)>,
+    bound: u64,
+}
+
+impl PseudoBoolean {
+    /// Encode into `solver` the constraint that the sum of the weights `w` of the true
+    /// literals `lit` of `terms`, given as pairs `(w, lit)`, is at most `k`.
+    pub fn new<S: SolverInterface>(solver: &mut S, terms: &[(u64, Lit)], k: u64) -> Self {
+        let terms: Vec<_> = terms.iter().filter(|(w, _)| *w > 0).copied().collect();
+        let outputs = if terms.is_empty() {
+            vec![]
+        } else {
+            generalized_totalizer(solver, &terms, k.saturating_add(1))
+        };
+        let mut pb = PseudoBoolean {
+            outputs,
+            bound: u64::MAX,
+        };
+        pb.strengthen(solver, k);
+        pb
+    }
+
+    /// The current bound.
+    pub fn bound(&self) -> u64 {
+        self.bound
+    }
+
+    /// The sums that can be reached, in increasing order, with their literals.
+    pub fn outputs(&self) -> &[(u64, Lit)] {
+        &self.outputs
+    }
+
+    /// Make the bound `k`, if it is smaller than the current one.
+    ///
+    /// Returns `false` if the solver is then known to be unsatisfiable.
+    pub fn strengthen<S: SolverInterface>(&mut self, solver: &mut S, k: u64) -> bool {
+        for &(w, o) in &self.outputs {
+            if w > k && w <= self.bound {
+                solver.add_clause_reuse(&mut vec![!o]);
+            }
+        }
+        self.bound = self.bound.min(k);
+        solver.is_ok()
+    }
+}
+
+/// Encoding of an at-most-one constraint, see [`at_most_one`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AmoEncoding {
+    /// A binary clause for each pair of literals, without new variables.
+    Pairwise,
+    /// Klieber and Kwon's commander encoding: groups of three literals have a
+    /// commander variable, implied by them, over which the constraint is recursive.
+    Commander,
+    /// Chen's product encoding: the literals are laid out in a grid, and each one
+    /// implies the variables of its row and column, over which the constraint is
+    /// recursive. It has about `2n + 4 √n` clauses.
+    Product,
+}
+
+/// Encode into `solver` the constraint that at most one of `lits` is true.
+///
+/// The recursive encodings are pairwise on at most six literals. Returns `false` if the
+/// solver is then known to be unsatisfiable.
+pub fn at_most_one<S: SolverInterface>(
+    solver: &mut S,
+    lits: &[Lit],
+    encoding: AmoEncoding,
+) -> bool {
+    if lits.len() <= 6 || encoding == AmoEncoding::Pairwise {
+        for (i, &x) in lits.iter().enumerate() {
+            for &y in &lits[i + 1..] {
+                solver.add_clause_reuse(&mut vec![!x, !y]);
+            }
+        }
+        return solver.is_ok();
+    }
+    match encoding {
+        AmoEncoding::Commander => {
+            let mut commanders = vec![];
+            for group in lits.chunks(3) {
+                let c = new_lit(solver);
+                at_most_one(solver, group, AmoEncoding::Pairwise);
+                for &x in group {
+                    solver.add_clause_reuse(&mut vec![!x, c]);
+                }
+                commanders.push(c);
+            }
+            at_most_one(solver, &commanders, encoding)
+        }
+        AmoEncoding::Product => {
+            let mut rows = 1;
+            while rows * rows < lits.len() {
+                rows += 1;
+            }
+            let cols = lits.len().div_ceil(rows);
+            let r: Vec<Lit> = (0..rows).map(|_| new_lit(solver)).collect();
+            let c: Vec<Lit> = (0..cols).map(|_| new_lit(solver)).collect();
+            for (k, &x) in lits.iter().enumerate() {
+                solver.add_clause_reuse(&mut vec![!x, r[k / cols]]);
+                solver.add_clause_reuse(&mut vec![!x, c[k % cols]]);
+            }
+            at_most_one(solver, &r, encoding) && at_most_one(solver, &c, encoding)
+        }
+        AmoEncoding::Pairwise => unreachable!(),
+    }
+}
+
+fn new_lit<S: SolverInterface>(solver: &mut S) -> Lit {
+    Lit::new(solver.new_var_default(), true)
+}
+
+/// Outputs of a totalizer over `inputs`, counting up to `cap`.
+fn totalizer<S: SolverInterface>(solver: &mut S, inputs: &[Lit], cap: usize) -> Vec<Lit> {
+    if inputs.len() == 1 {
+        return inputs.to_vec();
+    }
+    let (left, right) = inputs.split_at(inputs.len() / 2);
+    let left = totalizer(solver, left, cap);
+    let right = totalizer(solver, right, cap);
+    let n = (left.len() + right.len()).min(cap);
+    let outputs: Vec<Lit> = (0..n).map(|_| new_lit(solver)).collect();
+    // `left[i - 1] ∧ right[j - 1] => outputs[i + j - 1]`, where `left[-1]` is true
+    for i in 0..=left.len() {
+        for j in 0..=right.len() {
+            if i + j == 0 || i + j > n {
+                continue;
+            }
+            let mut c = vec![outputs[i + j - 1]];
+            c.extend(i.checked_sub(1).map(|i| !left[i]));
+            c.extend(j.checked_sub(1).map(|j| !right[j]));
+            solver.add_clause_reuse(&mut c);
+        }
+    }
+    outputs
+}
+
+/// Last column of a sequential counter over `inputs`, counting up to `cap`.
+fn sequential_counter<S: SolverInterface>(solver: &mut S, inputs: &[Lit], cap: usize) -> Vec<Lit> {
+    // `counts[j]` is implied by `j + 1` of the inputs seen so far being true
+    let mut counts: Vec<Lit> = vec![];
+    for &x in inputs {
+        let n = (counts.len() + 1).min(cap);
+        let next: Vec<Lit> = (0..n).map(|_| new_lit(solver)).collect();
+        for (j, &r) in next.iter().enumerate() {
+            if let Some(&prev) = counts.get(j) {
+                solver.add_clause_reuse(&mut vec![!prev, r]);
+            }
+            match j.checked_sub(1) {
+                None => solver.add_clause_reuse(&mut vec![!x, r]),
+                Some(j) => solver.add_clause_reuse(&mut vec![!x, !counts[j], r]),
+            };
+        }
+        counts = next;
+    }
+    counts
+}
+
+/// Outputs of a sorting network over `inputs`, the true ones first.
+fn sorting_network<S: SolverInterface>(solver: &mut S, inputs: &[Lit]) -> Vec<Lit> {
+    // pad to a power of two with `false`
+    let n = inputs.len().next_power_of_two();
+    let mut lits = inputs.to_vec();
+    if n > lits.len() {
+        let f = new_lit(solver);
+        solver.add_clause_reuse(&mut vec![!f]);
+        lits.resize(n, f);
+    }
+    let mut outputs = sort(solver, &lits);
+    outputs.truncate(inputs.len());
+    outputs
+}
+
+fn sort<S: SolverInterface>(solver: &mut S, lits: &[Lit]) -> Vec<Lit> {
+    if lits.len() == 1 {
+        return lits.to_vec();
+    }
+    let (left, right) = lits.split_at(lits.len() / 2);
+    let left = sort(solver, left);
+    let right = sort(solver, right);
+    merge(solver, &left, &right)
+}
+
+/// Merge the sorted sequences `a` and `b`, of the same length.
+fn merge<S: SolverInterface>(solver: &mut S, a: &[Lit], b: &[Lit]) -> Vec<Lit> {
+    if a.len() == 1 {
+        let (max, min) = comparator(solver, a[0], b[0]);
+        return vec![max, min];
+    }
+    fn every_other(xs: &[Lit], start: usize) -> impl Iterator<Item = Lit> + '_ {
+        xs.iter().skip(start).step_by(2).copied()
+    }
+    let (a0, b0): (Vec<_>, Vec<_>) = (every_other(a, 0).collect(), every_other(b, 0).collect());
+    let (a1, b1): (Vec<_>, Vec<_>) = (every_other(a, 1).collect(), every_other(b, 1).collect());
+    let even = merge(solver, &a0, &b0);
+    let odd = merge(solver, &a1, &b1);
+    let mut outputs = vec![even[0]];
+    for i in 0..odd.len() - 1 {
+        let (max, min) = comparator(solver, odd[i], even[i + 1]);
+        outputs.push(max);
+        outputs.push(min);
+    }
+    outputs.push(odd[odd.len() - 1]);
+    outputs
+}
+
+/// Half comparator: the disjunction and the conjunction of `a` and `b`, implied by them.
+fn comparator<S: SolverInterface>(solver: &mut S, a: Lit, b: Lit) -> (Lit, Lit) {
+    let (max, min) = (new_lit(solver), new_lit(solver));
+    solver.add_clause_reuse(&mut vec![!a, max]);
+    solver.add_clause_reuse(&mut vec![!b, max]);
+    solver.add_clause_reuse(&mut vec![!a, !b, min]);
+    (max, min)
+}
+
+/// Outputs of a generalized totalizer over `terms`, the sums from `cap` being merged.
+fn generalized_totalizer<S: SolverInterface>(
+    solver: &mut S,
+    terms: &[(u64, Lit)],
+    cap: u64,
+) -> Vec<(u64, Lit)> {
+    if let [(w, lit)] = *terms {
+        return vec![(w.min(cap), lit)];
+    }
+    let (left, right) = terms.split_at(terms.len() / 2);
+    let left = generalized_totalizer(solver, left, cap);
+    let right = generalized_totalizer(solver, right, cap);
+    let mut outputs = BTreeMap::new();
+    // `left[a] ∧ right[b] => outputs[a + b]`, with the empty sum `0` being true
+    let zero = [(0, None)];
+    let with_zero = |xs: &[(u64, Lit)]| {
+        let xs: Vec<_> = xs.iter().map(|&(w, lit)| (w, Some(lit))).collect();
+        [&zero[..], &xs].concat()
+    };
+    for &(a, l) in &with_zero(&left) {
+        for &(b, r) in &with_zero(&right) {
+            if a + b == 0 {
+                continue;
+            }
+            let o =
+                *(outputs.entry(a.saturating_add(b).min(cap))).or_insert_with(|| new_lit(solver));
+            let mut c = vec![o];
+            c.extend(l.map(|l| !l));
+            c.extend(r.map(|r| !r));
+            solver.add_clause_reuse(&mut c);
+        }
+    }
+    outputs.into_iter().collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{drat::ProofOutput, lbool, BasicSolver, Var};
+
+    #[test]
+    fn test_cardinality() {
+        let n = 7;
+        for encoding in [
+            CardEncoding::Totalizer,
+            CardEncoding::SequentialCounter,
+            CardEncoding::SortingNetwork,
+        ] {
+            for k in 0..=n {
+                let mut solver = BasicSolver::default();
+                let lits: Vec<Lit> = (0..n)
+                    .map(|i| Lit::new(solver.var_of_int(i), true))
+                    .collect();
+                let mut card = Cardinality::new(&mut solver, &lits, k as usize, encoding);
+                for bound in (0..=k).rev() {
+                    card.strengthen(&mut solver, bound as usize);
+                    assert_eq!(card.bound(), bound as usize);
+                    for m in 0..1u32 << n {
+                        let assumps: Vec<Lit> = (0..n)
+                            .map(|i| Lit::new(Var::from_idx(i), m >> i & 1 != 0))
+                            .collect();
+                        let expected = lbool::new(m.count_ones() <= bound);
+                        assert_eq!(solver.solve_limited(&assumps), expected, "{:?}", encoding);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_at_most_one() {
+        for encoding in [
+            AmoEncoding::Pairwise,
+            AmoEncoding::Commander,
+            AmoEncoding::Product,
+        ] {
+            for n in [1, 2, 7, 11] {
+                let mut solver = BasicSolver::default();
+                let lits: Vec<Lit> = (0..n)
+                    .map(|i| Lit::new(solver.var_of_int(i), true))
+                    .collect();
+                assert!(at_most_one(&mut solver, &lits, encoding));
+                for m in 0..1u32 << n {
+                    let assumps: Vec<Lit> = (0..n)
+                        .map(|i| Lit::new(Var::from_idx(i), m >> i & 1 != 0))
+                        .collect();
+                    let expected = lbool::new(m.count_ones() <= 1);
+                    assert_eq!(solver.solve_limited(&assumps), expected, "{:?}", encoding);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_add_at_most_one() {
+        struct Discard;
+        impl ProofOutput for Discard {
+            fn add_clause(&mut self, _: &[Lit]) {}
+            fn delete_clause(&mut self, _: &[Lit]) {}
+        }
+        // natively, then with each encoding
+        for (n, proof) in [(3, false), (9, false), (9, true), (70, true)] {
+            let mut solver = BasicSolver::default();
+            if proof {
+                solver.set_proof_output(Box::new(Discard));
+            }
+            let lits: Vec<Lit> = (0..n)
+                .map(|i| Lit::new(solver.var_of_int(i), true))
+                .collect();
+            assert!(solver.add_at_most_one(&lits));
+            for (i, j) in [(0, 1), (1, n as usize - 1), (0, n as usize / 2)] {
+                assert_eq!(solver.solve_limited(&[lits[i]]), lbool::TRUE);
+                assert_eq!(solver.solve_limited(&[lits[i], lits[j]]), lbool::FALSE);
+            }
+        }
+    }
+
+    #[test]
+    fn test_pseudo_boolean() {
+        let weights = [3, 1, 4, 1, 5, 0];
+        let mut solver = BasicSolver::default();
+        let terms: Vec<(u64, Lit)> = (weights.iter().enumerate())
+            .map(|(i, &w)| (w, Lit::new(solver.var_of_int(i as u32), true)))
+            .collect();
+        let mut pb = PseudoBoolean::new(&mut solver, &terms, 9);
+        for k in (0..=9).rev() {
+            pb.strengthen(&mut solver, k);
+            for m in 0..1u32 << weights.len() {
+                let assumps: Vec<Lit> = (0..weights.len() as u32)
+                    .map(|i| Lit::new(Var::from_idx(i), m >> i & 1 != 0))
+                    .collect();
+                let sum: u64 = (0..weights.len())
+                    .filter(|i| m >> i & 1 != 0)
+                    .map(|i| weights[i])
+                    .sum();
+                let expected = lbool::new(sum <= k);
+                assert_eq!(solver.solve_limited(&assumps), expected);
+            }
+        }
+    }
+}
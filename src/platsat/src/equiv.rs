@@ -0,0 +1,111 @@
+//! Equivalent literals, found as the strongly connected components of the binary
+//! implication graph
+use crate::clause::Lit;
+use crate::intmap::AsIndex;
+use no_std_compat::prelude::v1::*;
+
+/// Strongly connected components of the implication graph of the binary clauses
+/// `binaries` over `num_vars` variables, that have more than one literal.
+///
+/// The clause `a | b` gives the edges `!a -> b` and `!b -> a`, so all the literals of a
+/// component are equivalent, and the negations of a component form another one.
+pub(crate) fn binary_sccs(num_vars: u32, binaries: &[[Lit; 2]]) -> Vec<Vec<Lit>> {
+    let n = 2 * num_vars as usize;
+    // successors of each literal, in compressed rows
+    let mut start = vec![0u32; n + 1];
+    for &[a, b] in binaries {
+        start[(!a).as_index() + 1] += 1;
+        start[(!b).as_index() + 1] += 1;
+    }
+    for i in 0..n {
+        start[i + 1] += start[i];
+    }
+    let mut succ = vec![Lit::UNDEF; binaries.len() * 2];
+    let mut fill = start.clone();
+    for &[a, b] in binaries {
+        for (x, y) in [(!a, b), (!b, a)] {
+            succ[fill[x.as_index()] as usize] = y;
+            fill[x.as_index()] += 1;
+        }
+    }
+
+    // iterative Tarjan's algorithm
+    const UNVISITED: u32 = u32::MAX;
+    let mut index = vec![UNVISITED; n];
+    let mut low = vec![0u32; n];
+    let mut on_stack = vec![false; n];
+    let mut stack: Vec<Lit> = vec![];
+    // literals being visited, with the position of their next successor
+    let mut calls: Vec<(Lit, u32)> = vec![];
+    let mut next_index = 0;
+    let mut sccs = vec![];
+    for root in 0..n {
+        if index[root] != UNVISITED || start[root] == start[root + 1] {
+            continue;
+        }
+        calls.push((Lit::from_index(root), start[root]));
+        index[root] = next_index;
+        low[root] = next_index;
+        next_index += 1;
+        stack.push(Lit::from_index(root));
+        on_stack[root] = true;
+        while let Some(&mut (x, ref mut pos)) = calls.last_mut() {
+            let xi = x.as_index();
+            if *pos < start[xi + 1] {
+                let y = succ[*pos as usize];
+                *pos += 1;
+                let yi = y.as_index();
+                if index[yi] == UNVISITED {
+                    index[yi] = next_index;
+                    low[yi] = next_index;
+                    next_index += 1;
+                    stack.push(y);
+                    on_stack[yi] = true;
+                    calls.push((y, start[yi]));
+                } else if on_stack[yi] {
+                    low[xi] = low[xi].min(index[yi]);
+                }
+                continue;
+            }
+            calls.pop();
+            if let Some(&(parent, _)) = calls.last() {
+                let pi = parent.as_index();
+                low[pi] = low[pi].min(low[xi]);
+            }
+            if low[xi] == index[xi] {
+                let mut scc = vec![];
+                loop {
+                    let y = stack.pop().unwrap();
+                    on_stack[y.as_index()] = false;
+                    scc.push(y);
+                    if y == x {
+                        break;
+                    }
+                }
+                if scc.len() > 1 {
+                    sccs.push(scc);
+                }
+            }
+        }
+    }
+    sccs
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::clause::Var;
+
+    #[test]
+    fn test_binary_sccs() {
+        let [a, b, c, d] = [0, 1, 2, 3].map(|i| Lit::new(Var::from_idx(i), true));
+        // a -> b -> !c -> a, and c -> d
+        let binaries = [[!a, b], [!b, !c], [c, a], [!c, d]];
+        let mut sccs = binary_sccs(4, &binaries);
+        for scc in &mut sccs {
+            scc.sort();
+        }
+        sccs.sort();
+        assert_eq!(sccs, vec![vec![a, b, !c], vec![!a, !b, c]]);
+    }
+}
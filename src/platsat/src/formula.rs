@@ -0,0 +1,310 @@
+//! Propositional formulas, encoded into clauses
+//!
+//! A [`Formula`] is built from literals with the usual connectives, and an [`Encoder`]
+//! adds it to a solver by introducing a variable for each connective:
+//!
+//! - the Tseitin encoding defines each variable as equivalent to its subformula;
+//! - the Plaisted–Greenbaum encoding only adds the clauses of the polarities the
+//!   subformula occurs with, so that the variable implies it (or is implied by it).
+//!   It has fewer clauses, and is enough to assert formulas.
+//!
+//! ```
+//! use platsat::{formula::{Encoder, Formula}, BasicSolver, SolverInterface, lbool};
+//! let mut solver = BasicSolver::default();
+//! let [a, b, c] = [0, 1, 2].map(|i| Formula::from(solver.var_of_int(i)));
+//! let f = Formula::ite(a, Formula::xor(b.clone(), c.clone()), Formula::iff(b, c));
+//! Encoder::plaisted_greenbaum().assert(&mut solver, &f);
+//! assert_eq!(solver.solve_limited(&[]), lbool::TRUE);
+//! ```
+use no_std_compat::prelude::v1::*;
+use {
+    crate::{
+        clause::{Lit, Var},
+        interface::SolverInterface,
+    },
+    std::ops,
+};
+
+/// A propositional formula over the literals of a solver.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Formula {
+    Const(bool),
+    Lit(Lit),
+    Not(Box<Formula>),
+    /// Conjunction, `true` if empty.
+    And(Vec<Formula>),
+    /// Disjunction, `false` if empty.
+    Or(Vec<Formula>),
+    Xor(Box<Formula>, Box<Formula>),
+    Iff(Box<Formula>, Box<Formula>),
+    /// `Ite(c, t, e)` is `t` if `c` holds, `e` otherwise.
+    Ite(Box<Formula>, Box<Formula>, Box<Formula>),
+}
+
+impl From<Lit> for Formula {
+    fn from(lit: Lit) -> Self {
+        Formula::Lit(lit)
+    }
+}
+
+impl From<Var> for Formula {
+    fn from(v: Var) -> Self {
+        Formula::Lit(Lit::new(v, true))
+    }
+}
+
+impl From<bool> for Formula {
+    fn from(b: bool) -> Self {
+        Formula::Const(b)
+    }
+}
+
+impl ops::Not for Formula {
+    type Output = Formula;
+    fn not(self) -> Formula {
+        match self {
+            Formula::Const(b) => Formula::Const(!b),
+            Formula::Lit(lit) => Formula::Lit(!lit),
+            Formula::Not(f) => *f,
+            f => Formula::Not(Box::new(f)),
+        }
+    }
+}
+
+impl Formula {
+    /// Conjunction of `fs`.
+    pub fn and(fs: impl IntoIterator<Item = impl Into<Formula>>) -> Self {
+        Formula::And(fs.into_iter().map(Into::into).collect())
+    }
+
+    /// Disjunction of `fs`.
+    pub fn or(fs: impl IntoIterator<Item = impl Into<Formula>>) -> Self {
+        Formula::Or(fs.into_iter().map(Into::into).collect())
+    }
+
+    pub fn xor(a: impl Into<Formula>, b: impl Into<Formula>) -> Self {
+        Formula::Xor(Box::new(a.into()), Box::new(b.into()))
+    }
+
+    pub fn iff(a: impl Into<Formula>, b: impl Into<Formula>) -> Self {
+        Formula::Iff(Box::new(a.into()), Box::new(b.into()))
+    }
+
+    /// `a` implies `b`.
+    pub fn implies(a: impl Into<Formula>, b: impl Into<Formula>) -> Self {
+        Formula::Or(vec![!a.into(), b.into()])
+    }
+
+    /// `t` if `c` holds, `e` otherwise.
+    pub fn ite(c: impl Into<Formula>, t: impl Into<Formula>, e: impl Into<Formula>) -> Self {
+        Formula::Ite(Box::new(c.into()), Box::new(t.into()), Box::new(e.into()))
+    }
+
+    /// Value of the formula when each literal `lit` has the value `value(lit)`.
+    pub fn eval(&self, value: &impl Fn(Lit) -> bool) -> bool {
+        match self {
+            Formula::Const(b) => *b,
+            Formula::Lit(lit) => value(*lit),
+            Formula::Not(f) => !f.eval(value),
+            Formula::And(fs) => fs.iter().all(|f| f.eval(value)),
+            Formula::Or(fs) => fs.iter().any(|f| f.eval(value)),
+            Formula::Xor(a, b) => a.eval(value) != b.eval(value),
+            Formula::Iff(a, b) => a.eval(value) == b.eval(value),
+            Formula::Ite(c, t, e) => {
+                if c.eval(value) {
+                    t.eval(value)
+                } else {
+                    e.eval(value)
+                }
+            }
+        }
+    }
+}
+
+/// Adds formulas to a solver, see the [module documentation](self).
+///
+/// Subformulas get a new variable each time they are encoded, even if they are equal.
+#[derive(Clone, Copy, Debug)]
+pub struct Encoder {
+    plaisted_greenbaum: bool,
+}
+
+impl Encoder {
+    /// The Tseitin encoding: [`encode`](Self::encode) returns a literal equivalent to the
+    /// formula.
+    pub fn tseitin() -> Self {
+        Encoder {
+            plaisted_greenbaum: false,
+        }
+    }
+
+    /// The Plaisted–Greenbaum encoding: [`encode`](Self::encode) returns a literal that
+    /// implies the formula.
+    pub fn plaisted_greenbaum() -> Self {
+        Encoder {
+            plaisted_greenbaum: true,
+        }
+    }
+
+    /// A literal equivalent to `f` with the Tseitin encoding, or that implies it with
+    /// the Plaisted–Greenbaum encoding.
+    pub fn encode<S: SolverInterface>(&self, solver: &mut S, f: &Formula) -> Lit {
+        self.lit(solver, f, true, !self.plaisted_greenbaum)
+    }
+
+    /// Add `f` as a constraint of `solver`.
+    ///
+    /// Conjunctions at the top are split, and disjunctions become clauses directly.
+    /// Returns `false` if the solver is then known to be unsatisfiable.
+    pub fn assert<S: SolverInterface>(&self, solver: &mut S, f: &Formula) -> bool {
+        let mut clause = vec![];
+        match f {
+            Formula::Const(b) => {
+                if !b {
+                    solver.add_clause_reuse(&mut clause);
+                }
+            }
+            Formula::And(fs) => {
+                for f in fs {
+                    self.assert(solver, f);
+                }
+            }
+            Formula::Or(fs) => {
+                for f in fs {
+                    clause.push(self.encode(solver, f));
+                }
+                solver.add_clause_reuse(&mut clause);
+            }
+            f => {
+                clause.push(self.encode(solver, f));
+                solver.add_clause_reuse(&mut clause);
+            }
+        }
+        solver.is_ok()
+    }
+
+    /// Literal `x` for `f`, such that `x` implies `f` if `pos`, and `f` implies `x` if
+    /// `neg`.
+    fn lit<S: SolverInterface>(&self, solver: &mut S, f: &Formula, pos: bool, neg: bool) -> Lit {
+        let add = |solver: &mut S, c: &[Lit]| {
+            solver.add_clause_reuse(&mut c.to_vec());
+        };
+        match f {
+            Formula::Lit(lit) => return *lit,
+            Formula::Not(f) => return !self.lit(solver, f, neg, pos),
+            _ => {}
+        }
+        let x = Lit::new(solver.new_var_default(), true);
+        match f {
+            Formula::Const(b) => add(solver, &[x ^ !b]),
+            Formula::And(fs) | Formula::Or(fs) => {
+                // `Or` is the dual of `And` on negated literals
+                let is_or = matches!(f, Formula::Or(_));
+                let (x, pos, neg) = if is_or { (!x, neg, pos) } else { (x, pos, neg) };
+                let mut long = vec![x];
+                for f in fs {
+                    let (p, n) = if is_or { (neg, pos) } else { (pos, neg) };
+                    let c = self.lit(solver, f, p, n) ^ is_or;
+                    if pos {
+                        add(solver, &[!x, c]);
+                    }
+                    long.push(!c);
+                }
+                if neg {
+                    add(solver, &long);
+                }
+            }
+            Formula::Xor(a, b) | Formula::Iff(a, b) => {
+                // the operands occur with both polarities, and `a <=> b` is `a xor !b`
+                let a = self.lit(solver, a, true, true);
+                let b = self.lit(solver, b, true, true) ^ matches!(f, Formula::Iff(..));
+                if pos {
+                    add(solver, &[!x, a, b]);
+                    add(solver, &[!x, !a, !b]);
+                }
+                if neg {
+                    add(solver, &[x, !a, b]);
+                    add(solver, &[x, a, !b]);
+                }
+            }
+            Formula::Ite(c, t, e) => {
+                let c = self.lit(solver, c, true, true);
+                let t = self.lit(solver, t, pos, neg);
+                let e = self.lit(solver, e, pos, neg);
+                if pos {
+                    add(solver, &[!x, !c, t]);
+                    add(solver, &[!x, c, e]);
+                }
+                if neg {
+                    add(solver, &[x, !c, !t]);
+                    add(solver, &[x, c, !e]);
+                }
+            }
+            Formula::Lit(_) | Formula::Not(_) => unreachable!(),
+        }
+        x
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{lbool, test_util::Rng, BasicSolver};
+
+    /// A random formula over the variables `0..4`.
+    fn random(rand: &mut dyn FnMut(u64) -> u64, depth: u32) -> Formula {
+        let n = if depth == 0 { 2 } else { 9 };
+        let sub = |rand: &mut dyn FnMut(u64) -> u64| Box::new(random(rand, depth - 1));
+        match rand(n) {
+            0 => Formula::Lit(Lit::new(Var::from_idx(rand(4) as u32), rand(2) == 0)),
+            1 if rand(8) == 0 => Formula::Const(rand(2) == 0),
+            1 => Formula::Lit(Lit::new(Var::from_idx(rand(4) as u32), rand(2) == 0)),
+            2 => Formula::Not(sub(rand)),
+            3 | 4 => {
+                let fs = (0..rand(4)).map(|_| *sub(rand)).collect();
+                if rand(2) == 0 {
+                    Formula::And(fs)
+                } else {
+                    Formula::Or(fs)
+                }
+            }
+            5 => Formula::Xor(sub(rand), sub(rand)),
+            6 => Formula::Iff(sub(rand), sub(rand)),
+            _ => Formula::Ite(sub(rand), sub(rand), sub(rand)),
+        }
+    }
+
+    #[test]
+    fn test_encode() {
+        let mut rng = Rng::new(3);
+        let mut rand = |n| rng.next(n);
+        for _ in 0..100 {
+            let f = random(&mut rand, 3);
+            for enc in [Encoder::tseitin(), Encoder::plaisted_greenbaum()] {
+                let mut solver = BasicSolver::default();
+                for i in 0..4 {
+                    solver.var_of_int(i);
+                }
+                let x = enc.encode(&mut solver, &f);
+                let mut asserted = solver.clone();
+                enc.assert(&mut asserted, &f);
+                for m in 0..16u32 {
+                    let value = |lit: Lit| (m >> lit.var().idx() & 1 != 0) == lit.sign();
+                    let assumps: Vec<Lit> = (0..4)
+                        .map(|i| Lit::new(Var::from_idx(i), m >> i & 1 != 0))
+                        .collect();
+                    let expected = lbool::new(f.eval(&value));
+                    let sat = |solver: &mut BasicSolver, extra: &[Lit]| {
+                        let assumps = [&assumps[..], extra].concat();
+                        lbool::new(solver.solve_limited(&assumps) == lbool::TRUE)
+                    };
+                    assert_eq!(sat(&mut solver, &[x]), expected, "{:?}", f);
+                    assert_eq!(sat(&mut asserted, &[]), expected, "{:?}", f);
+                    if !enc.plaisted_greenbaum {
+                        assert_eq!(sat(&mut solver, &[!x]), expected ^ true, "{:?}", f);
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,183 @@
+//! Export of the implication graph of the current assignment, to visualize it with
+//! Graphviz (DOT) or any tool reading GraphML.
+//!
+//! Each node is a true literal of the trail, labelled with its DIMACS literal and its
+//! decision level; decisions and assumptions are boxes. Each literal has an edge from the
+//! negation of every other literal of its reason. An optional conflict clause, false in
+//! the assignment, is drawn as a node with an edge from the negation of each literal.
+use crate::clause::{lbool, Lit, Var};
+use no_std_compat::prelude::v1::*;
+use std::io::{self, Write};
+
+/// Format of [`Solver::dump_implication_graph`](crate::Solver::dump_implication_graph).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GraphFormat {
+    /// The DOT language of Graphviz.
+    Dot,
+    /// GraphML, with the attributes `label` (the DIMACS literal), `level` and `decision`.
+    GraphMl,
+}
+
+/// The assignment being exported.
+pub(crate) struct ImplicationGraph<'a, V, L, R> {
+    pub trail: &'a [Lit],
+    pub value: V,
+    pub level: L,
+    pub reason: R,
+    pub conflict: Option<&'a [Lit]>,
+}
+
+fn dimacs(lit: Lit) -> i64 {
+    let v = lit.var().idx() as i64 + 1;
+    if lit.sign() {
+        v
+    } else {
+        -v
+    }
+}
+
+fn node(v: Var) -> String {
+    format!("v{}", v.idx() + 1)
+}
+
+impl<'a, V, L, R> ImplicationGraph<'a, V, L, R>
+where
+    V: Fn(Lit) -> lbool,
+    L: Fn(Var) -> u32,
+    R: Fn(Var) -> Option<&'a [Lit]>,
+{
+    /// Edges `(from, to)`, the conflict node being `None`.
+    fn edges(&self) -> Vec<(Var, Option<Var>)> {
+        let mut edges = vec![];
+        for &lit in self.trail {
+            if let Some(reason) = (self.reason)(lit.var()) {
+                edges.extend(reason[1..].iter().map(|&q| (q.var(), Some(lit.var()))));
+            }
+        }
+        if let Some(c) = self.conflict {
+            debug_assert!(c.iter().all(|&q| (self.value)(q) == lbool::FALSE));
+            edges.extend(c.iter().map(|&q| (q.var(), None)));
+        }
+        edges
+    }
+
+    pub fn write<W: Write>(&self, w: &mut W, format: GraphFormat) -> io::Result<()> {
+        match format {
+            GraphFormat::Dot => self.write_dot(w),
+            GraphFormat::GraphMl => self.write_graphml(w),
+        }
+    }
+
+    fn write_dot<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        writeln!(w, "digraph implications {{")?;
+        for &lit in self.trail {
+            let v = lit.var();
+            let shape = if (self.reason)(v).is_none() && (self.level)(v) > 0 {
+                "box"
+            } else {
+                "ellipse"
+            };
+            writeln!(
+                w,
+                "  {} [label=\"{} @{}\", shape={}];",
+                node(v),
+                dimacs(lit),
+                (self.level)(v),
+                shape
+            )?;
+        }
+        if self.conflict.is_some() {
+            writeln!(
+                w,
+                "  conflict [label=\"conflict\", shape=octagon, color=red];"
+            )?;
+        }
+        for (from, to) in self.edges() {
+            match to {
+                Some(to) => writeln!(w, "  {} -> {};", node(from), node(to))?,
+                None => writeln!(w, "  {} -> conflict [color=red];", node(from))?,
+            }
+        }
+        writeln!(w, "}}")
+    }
+
+    fn write_graphml<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        writeln!(w, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>")?;
+        writeln!(
+            w,
+            "<graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">"
+        )?;
+        writeln!(
+            w,
+            "  <key id=\"label\" for=\"node\" attr.name=\"label\" attr.type=\"string\"/>"
+        )?;
+        writeln!(
+            w,
+            "  <key id=\"level\" for=\"node\" attr.name=\"level\" attr.type=\"int\"/>"
+        )?;
+        writeln!(
+            w,
+            "  <key id=\"decision\" for=\"node\" attr.name=\"decision\" attr.type=\"boolean\"/>"
+        )?;
+        writeln!(w, "  <graph id=\"implications\" edgedefault=\"directed\">")?;
+        for &lit in self.trail {
+            let v = lit.var();
+            let decision = (self.reason)(v).is_none() && (self.level)(v) > 0;
+            writeln!(w, "    <node id=\"{}\">", node(v))?;
+            writeln!(w, "      <data key=\"label\">{}</data>", dimacs(lit))?;
+            writeln!(w, "      <data key=\"level\">{}</data>", (self.level)(v))?;
+            writeln!(w, "      <data key=\"decision\">{}</data>", decision)?;
+            writeln!(w, "    </node>")?;
+        }
+        if self.conflict.is_some() {
+            writeln!(w, "    <node id=\"conflict\">")?;
+            writeln!(w, "      <data key=\"label\">conflict</data>")?;
+            writeln!(w, "    </node>")?;
+        }
+        for (from, to) in self.edges() {
+            let to = to.map_or_else(|| "conflict".to_string(), node);
+            writeln!(w, "    <edge source=\"{}\" target=\"{}\"/>", node(from), to)?;
+        }
+        writeln!(w, "  </graph>")?;
+        writeln!(w, "</graphml>")
+    }
+}
+
+#[test]
+fn test_implication_graph() {
+    use crate::{BasicSolver, SolverInterface};
+    let mut solver = BasicSolver::default();
+    let lits: Vec<Lit> = (0..4)
+        .map(|_| Lit::new(solver.new_var_default(), true))
+        .collect();
+    let (a, b, c, d) = (lits[0], lits[1], lits[2], lits[3]);
+    solver.add_clause_reuse(&mut vec![!a, b]);
+    solver.add_clause_reuse(&mut vec![!a, !b, c]);
+    solver.add_clause_reuse(&mut vec![d]);
+    let view = solver.propagate_under(&[a]).unwrap();
+    let mut out = vec![];
+    let conflict = [!c, !d];
+    view.dump_implication_graph(&mut out, GraphFormat::Dot, Some(&conflict))
+        .unwrap();
+    let dot = String::from_utf8(out).unwrap();
+    for line in [
+        "v4 [label=\"4 @0\", shape=ellipse];",
+        "v1 [label=\"1 @1\", shape=box];",
+        "v3 [label=\"3 @1\", shape=ellipse];",
+        "v1 -> v2;",
+        "v1 -> v3;",
+        "v2 -> v3;",
+        "v3 -> conflict [color=red];",
+        "v4 -> conflict [color=red];",
+    ] {
+        assert!(dot.contains(line), "{} not in {}", line, dot);
+    }
+    assert_eq!(dot.matches("->").count(), 5);
+    let mut out = vec![];
+    view.dump_implication_graph(&mut out, GraphFormat::GraphMl, None)
+        .unwrap();
+    let graphml = String::from_utf8(out).unwrap();
+    assert_eq!(graphml.matches("<node ").count(), 4);
+    assert_eq!(graphml.matches("<edge ").count(), 3);
+    assert!(graphml.contains("<edge source=\"v1\" target=\"v2\"/>"));
+}
@@ -1,17 +1,25 @@
+//! Indexed priority queue, as used by the decision heuristics
 use crate::intmap::{AsIndex, IntMap};
 use no_std_compat::prelude::v1::*;
 use std::fmt::Debug;
 use std::{mem, ops};
 
-/// Quaternary Heap
+/// Quaternary heap of keys `K`, which knows the position of each key so that it can
+/// be moved when its priority changes.
+///
+/// The heap only stores the cached priorities `V` of its keys. It is ordered by a
+/// [`CachedKeyComparator`], which computes them and is given to [`promote`](Self::promote)
+/// to get a [`Heap`] with the operations that need the order. The smallest cached
+/// priority comes first.
 #[derive(Debug, Clone)]
-pub struct HeapData<K: AsIndex, V> {
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct IdxHeap<K: AsIndex, V> {
     heap: Box<[V]>,
     next_slot: usize,
     indices: IntMap<K, i32>,
 }
 
-impl<K: AsIndex, V> Default for HeapData<K, V> {
+impl<K: AsIndex, V> Default for IdxHeap<K, V> {
     fn default() -> Self {
         Self {
             heap: Box::new([]),
@@ -21,21 +29,25 @@ impl<K: AsIndex, V> Default for HeapData<K, V> {
     }
 }
 
-impl<K: AsIndex, V> HeapData<K, V> {
+impl<K: AsIndex, V> IdxHeap<K, V> {
     pub fn new() -> Self {
         Self::default()
     }
+    /// Number of keys in the heap
     pub fn len(&self) -> usize {
-        self.heap.len()
+        self.next_slot.saturating_sub(ROOT as usize)
     }
     pub fn is_empty(&self) -> bool {
         self.next_slot <= ROOT as usize
     }
     pub fn in_heap(&self, k: K) -> bool {
-        self.indices.has(k) && self.indices[k] >= 0
+        self.indices.get(k).is_some_and(|&i| i >= 0)
     }
 
-    pub fn promote<Comp: CachedKeyComparator<K, Key = V>>(&mut self, comp: Comp) -> Heap<K, Comp> {
+    pub fn promote<Comp: CachedKeyComparator<K, Key = V>>(
+        &mut self,
+        comp: Comp,
+    ) -> Heap<'_, K, Comp> {
         Heap { data: self, comp }
     }
 
@@ -49,38 +61,44 @@ impl<K: AsIndex, V> HeapData<K, V> {
     }
 }
 
-impl<K: AsIndex, V> ops::Index<usize> for HeapData<K, V> {
+impl<K: AsIndex, V> ops::Index<usize> for IdxHeap<K, V> {
     type Output = V;
     fn index(&self, index: usize) -> &Self::Output {
         &self.heap[index]
     }
 }
 
+/// Order of the keys of an [`IdxHeap`], by their cached priorities.
 pub trait CachedKeyComparator<T> {
+    /// Priority of a key, which must also identify the key.
     type Key: Ord + Copy;
 
+    /// Current priority of `t`.
     fn cache_key(&self, t: T) -> Self::Key;
 
+    /// A priority larger than the ones of all the keys, used as padding.
     fn max_key(&self) -> Self::Key;
 
+    /// The key of a priority given by `cache_key`.
     fn un_cache_key(&self, k: Self::Key) -> T;
 }
 
+/// An [`IdxHeap`] with its order.
 #[derive(Debug)]
 pub struct Heap<'a, K: AsIndex + 'a, Comp: CachedKeyComparator<K>> {
-    data: &'a mut HeapData<K, Comp::Key>,
+    data: &'a mut IdxHeap<K, Comp::Key>,
     comp: Comp,
 }
 
 impl<'a, K: AsIndex + 'a, Comp: CachedKeyComparator<K>> ops::Deref for Heap<'a, K, Comp> {
-    type Target = HeapData<K, Comp::Key>;
+    type Target = IdxHeap<K, Comp::Key>;
     fn deref(&self) -> &Self::Target {
-        &self.data
+        self.data
     }
 }
 impl<'a, K: AsIndex + 'a, Comp: CachedKeyComparator<K>> ops::DerefMut for Heap<'a, K, Comp> {
     fn deref_mut(&mut self) -> &mut Self::Target {
-        &mut self.data
+        self.data
     }
 }
 
@@ -89,7 +107,7 @@ impl<'a, K: AsIndex + 'a, Comp: CachedKeyComparator<K>> Heap<'a, K, Comp> {
     #[cold]
     #[inline(never)]
     fn heap_reserve(&mut self) {
-        debug_assert_eq!(self.next_slot, self.data.len());
+        debug_assert_eq!(self.next_slot, self.heap.len());
         if self.next_slot == 0 {
             self.next_slot = ROOT as usize;
             // Enough space for the root and 4 children
@@ -160,6 +178,7 @@ impl<'a, K: AsIndex + 'a, Comp: CachedKeyComparator<K>> Heap<'a, K, Comp> {
         self.data.indices[self.comp.un_cache_key(x)] = i as i32;
     }
 
+    /// Restore the position of `k` after its key decreased
     pub fn decrease(&mut self, k: K) {
         debug_assert!(self.in_heap(k));
         let k_index = self.indices[k];
@@ -167,6 +186,28 @@ impl<'a, K: AsIndex + 'a, Comp: CachedKeyComparator<K>> Heap<'a, K, Comp> {
         self.percolate_up(k_index as u32);
     }
 
+    /// Restore the position of `k` after its key increased
+    pub fn increase(&mut self, k: K) {
+        debug_assert!(self.in_heap(k));
+        let k_index = self.indices[k];
+        self.heap[k_index as usize] = self.comp.cache_key(k);
+        self.percolate_down(k_index as u32);
+    }
+
+    /// Restore the position of `k` after its key changed in either direction
+    pub fn update(&mut self, k: K) {
+        debug_assert!(self.in_heap(k));
+        let k_index = self.indices[k] as u32;
+        let old = self.heap[k_index as usize];
+        let new = self.comp.cache_key(k);
+        self.heap[k_index as usize] = new;
+        if new < old {
+            self.percolate_up(k_index);
+        } else {
+            self.percolate_down(k_index);
+        }
+    }
+
     pub fn insert(&mut self, k: K) {
         self.indices.reserve(k, -1);
         debug_assert!(!self.in_heap(k));
@@ -175,6 +216,41 @@ impl<'a, K: AsIndex + 'a, Comp: CachedKeyComparator<K>> Heap<'a, K, Comp> {
         self.percolate_up(k_index);
     }
 
+    /// The key with the smallest priority, if any
+    pub fn peek_min(&self) -> Option<K> {
+        (!self.is_empty()).then(|| self.comp.un_cache_key(self.heap[ROOT as usize]))
+    }
+
+    /// Remove all the keys
+    pub fn clear(&mut self) {
+        for slot in ROOT as usize..self.next_slot {
+            let k = self.comp.un_cache_key(self.heap[slot]);
+            self.data.indices[k] = -1;
+            self.data.heap[slot] = self.comp.max_key();
+        }
+        self.next_slot = self.next_slot.min(ROOT as usize);
+    }
+
+    /// Replace the content of the heap with `keys`, which must be distinct, in linear
+    /// time rather than by inserting them one by one.
+    pub fn rebuild(&mut self, keys: impl IntoIterator<Item = K>) {
+        self.clear();
+        for k in keys {
+            self.indices.reserve(k, -1);
+            debug_assert!(!self.in_heap(k));
+            let k_index = self.heap_push(self.comp.cache_key(k));
+            self.indices[k] = k_index as i32;
+        }
+        if self.len() < 2 {
+            return;
+        }
+        // sift down the inner nodes, from the last one to the root
+        let last_parent = parent_index(self.next_slot as u32 - 1);
+        for i in (ROOT..=last_parent).rev() {
+            self.percolate_down(i);
+        }
+    }
+
     pub fn remove_min(&mut self) -> K {
         assert!(!self.is_empty(), "cannot pop from empty heap");
         let x = self.heap[ROOT as usize];
@@ -209,3 +285,77 @@ fn left_index(i: u32) -> u32 {
 fn parent_index(i: u32) -> u32 {
     (i >> 2) + 2
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        clause::{VMap, Var},
+        test_util::Rng,
+    };
+    use std::cmp;
+
+    struct ByPrio<'a>(&'a VMap<u32>);
+
+    impl CachedKeyComparator<Var> for ByPrio<'_> {
+        type Key = (u32, u32);
+
+        fn cache_key(&self, v: Var) -> Self::Key {
+            (self.0[v], v.idx())
+        }
+
+        fn max_key(&self) -> Self::Key {
+            (u32::MAX, u32::MAX)
+        }
+
+        fn un_cache_key(&self, k: Self::Key) -> Var {
+            Var::from_idx(k.1)
+        }
+    }
+
+    #[test]
+    fn test_idx_heap() {
+        let mut rng = Rng::new(7);
+        let mut rand = |n| rng.next(n);
+        let mut prio: VMap<u32> = VMap::new();
+        let mut heap: IdxHeap<Var, (u32, u32)> = IdxHeap::new();
+        for round in 0..20 {
+            let n = rand(60) as u32 + 1;
+            for i in 0..n {
+                prio.insert(Var::from_idx(i), rand(100) as u32, 0);
+            }
+            let vars = (0..n).map(Var::from_idx);
+            if round % 2 == 0 {
+                heap.promote(ByPrio(&prio)).rebuild(vars.clone());
+            } else {
+                heap.promote(ByPrio(&prio)).clear();
+                vars.clone()
+                    .for_each(|v| heap.promote(ByPrio(&prio)).insert(v));
+            }
+            assert_eq!(heap.len(), n as usize);
+            // change some priorities in both directions
+            for _ in 0..n / 2 {
+                let v = Var::from_idx(rand(n as u64) as u32);
+                let old = prio[v];
+                prio[v] = rand(100) as u32;
+                let mut heap = heap.promote(ByPrio(&prio));
+                match prio[v].cmp(&old) {
+                    cmp::Ordering::Less => heap.decrease(v),
+                    cmp::Ordering::Greater => heap.increase(v),
+                    cmp::Ordering::Equal => heap.update(v),
+                }
+            }
+            let mut expected: Vec<(u32, u32)> = vars.map(|v| (prio[v], v.idx())).collect();
+            expected.sort_unstable();
+            let mut heap = heap.promote(ByPrio(&prio));
+            for &(_, i) in &expected[..n as usize / 2] {
+                assert_eq!(heap.peek_min(), Some(Var::from_idx(i)));
+                assert_eq!(heap.remove_min(), Var::from_idx(i));
+            }
+            assert_eq!(heap.len(), n as usize - n as usize / 2);
+            assert!(heap.in_heap(Var::from_idx(expected[n as usize - 1].1)));
+        }
+        heap.promote(ByPrio(&prio)).clear();
+        assert!(heap.is_empty() && heap.promote(ByPrio(&prio)).peek_min().is_none());
+    }
+}
@@ -58,6 +58,9 @@ pub trait SolverInterface {
     /// Search for a model that respects a given set of assumptions (with resource constraints).
     ///
     /// - `assumps` is the list of assumptions to use (the literals that can be part of the unsat core)
+    ///
+    /// Returns `UNDEF` if the search was interrupted, for instance because a budget
+    /// was exhausted; it can then be resumed by calling this again.
     fn solve_limited(&mut self, assumps: &[Lit]) -> lbool {
         self.solve_limited_th(&mut theory::EmptyTheory::new(), assumps)
     }
@@ -124,6 +127,10 @@ pub trait SolverInterface {
 
     /// Query whole model, as a mapping from `Var` to `lbool`.
     ///
+    /// The model is given over the original problem: the values of the variables
+    /// removed by preprocessing (see [`Solver::eliminate`](crate::Solver::eliminate))
+    /// are reconstructed so that the removed clauses are satisfied.
+    ///
     /// Precondition: last result was `Sat` (ie `lbool::TRUE`)
     fn get_model(&self) -> &[lbool];
 
@@ -140,6 +147,11 @@ pub trait SolverInterface {
 
     /// Return unsat core (as a subset of assumptions).
     ///
+    /// These are the assumptions that were used to derive the conflict, so adding the
+    /// negation of one of them as a clause is implied by the formula. The core is empty
+    /// if the formula is unsat without assumptions.
+    /// It is not minimal in general, see [`Solver::minimize_unsat_core`](crate::Solver::minimize_unsat_core).
+    ///
     /// Precondition: last result was `Unsat`
     fn unsat_core(&self) -> &[Lit];
 
@@ -169,6 +181,7 @@ pub trait SolverInterface {
 /// Result of calling [`SolverInterface::solve_limited_th_full`], contains the unsat-core
 /// if the solver returned unsat and a [`SolverModel`] otherwise
 pub enum SolveResult<'a, S: SolverInterface + ?Sized + 'a, Th: Theory + 'a> {
+    /// Unsat under the assumptions in the given core, see [`SolverInterface::unsat_core`]
     Unsat(&'a [Lit]),
     Sat(SolverModel<'a, S, Th>),
     Unknown(SolverModel<'a, S, Th>),
@@ -189,7 +202,7 @@ impl<'a, S: SolverInterface + ?Sized + 'a, Th: Theory + 'a> Drop for SolverModel
 impl<'a, S: SolverInterface + ?Sized + 'a, Th: Theory + 'a> SolverModel<'a, S, Th> {
     /// State of the [`Theory`]
     pub fn theory(&self) -> &Th {
-        &self.theory
+        self.theory
     }
 
     /// Query model for lit.
@@ -200,7 +213,7 @@ impl<'a, S: SolverInterface + ?Sized + 'a, Th: Theory + 'a> SolverModel<'a, S, T
 
 #[cfg(test)]
 mod test {
-    use crate::*;
+    use crate::{test_util::Rng, *};
     use no_std_compat::prelude::v1::*;
     #[test]
     fn test_reg7() {
@@ -216,4 +229,877 @@ mod test {
         assert_eq!(solver.solve_limited(&[]), lbool::TRUE);
         assert_eq!(solver.solve_limited(&[a]), lbool::FALSE);
     }
+
+    #[test]
+    fn test_unsat_core() {
+        let mut solver: Solver<callbacks::Basic> =
+            Solver::new(Default::default(), Default::default());
+        let a = Lit::new(solver.new_var_default(), true);
+        let b = Lit::new(solver.new_var_default(), false);
+        let c = Lit::new(solver.new_var_default(), true);
+        assert!(solver.add_clause_reuse(&mut vec![!a, !b]));
+        assert_eq!(solver.solve_limited(&[c, a, b]), lbool::FALSE);
+        let mut core = solver.unsat_core().to_vec();
+        core.sort();
+        assert_eq!(core, [a, b]);
+        assert!(solver.unsat_core_contains_lit(a));
+        assert!(!solver.unsat_core_contains_lit(!a));
+        assert!(!solver.unsat_core_contains_var(c.var()));
+        assert!(solver.add_clause_reuse(&mut vec![!c]));
+        assert_eq!(solver.solve_limited(&[c, a]), lbool::FALSE);
+        assert_eq!(solver.unsat_core(), [c]);
+    }
+
+    #[test]
+    fn test_minimize_unsat_core() {
+        let mut solver: Solver<callbacks::Basic> =
+            Solver::new(Default::default(), Default::default());
+        let lits: Vec<Lit> = (0..5)
+            .map(|_| Lit::new(solver.new_var_default(), true))
+            .collect();
+        let x = Lit::new(solver.new_var_default(), true);
+        // `lits[0..3]` imply `x`, which conflicts with `lits[3]` alone
+        assert!(solver.add_clause_reuse(&mut vec![!lits[0], !lits[1], !lits[2], x]));
+        assert!(solver.add_clause_reuse(&mut vec![!x, !lits[3]]));
+        assert!(solver.add_clause_reuse(&mut vec![!lits[2], !lits[3]]));
+        assert_eq!(solver.solve_limited(&lits), lbool::FALSE);
+        let core = solver.minimize_unsat_core().to_vec();
+        assert_eq!(core, solver.unsat_core());
+        assert_eq!(core.len(), 2);
+        assert!(core.contains(&lits[2]) && core.contains(&lits[3]));
+        for i in 0..core.len() {
+            let mut assumps = core.clone();
+            assumps.remove(i);
+            assert_eq!(solver.solve_limited(&assumps), lbool::TRUE);
+        }
+    }
+
+    #[test]
+    fn test_clause_groups() {
+        let mut solver: Solver<callbacks::Basic> =
+            Solver::new(Default::default(), Default::default());
+        let a = Lit::new(solver.new_var_default(), true);
+        let b = Lit::new(solver.new_var_default(), true);
+        assert!(solver.add_clause_reuse(&mut vec![a, b]));
+        let g1 = solver.new_clause_group();
+        let g2 = solver.new_clause_group();
+        assert!(solver.add_clause_in_group(g1, &[!a]));
+        assert!(solver.add_clause_in_group(g2, &[!b]));
+        assert_eq!(solver.solve_limited(&[]), lbool::FALSE);
+        let mut core = solver.unsat_core().to_vec();
+        core.sort();
+        assert_eq!(core, [g1.activation_lit(), g2.activation_lit()]);
+        assert!(solver.retract_clause_group(g2));
+        assert!(!solver.is_clause_group_active(g2));
+        assert!(solver.is_clause_group_active(g1));
+        assert_eq!(solver.solve_limited(&[]), lbool::TRUE);
+        assert_eq!(solver.value_lit(b), lbool::TRUE);
+        assert_eq!(solver.solve_limited(&[!b]), lbool::FALSE);
+        assert!(solver.unsat_core_contains_lit(g1.activation_lit()));
+        assert!(solver.retract_clause_group(g1));
+        assert_eq!(solver.solve_limited(&[!b]), lbool::TRUE);
+    }
+
+    #[test]
+    fn test_clause_groups_release() {
+        let mut solver: Solver<callbacks::Basic> =
+            Solver::new(Default::default(), Default::default());
+        let a = Lit::new(solver.new_var_default(), true);
+        let groups: Vec<_> = (0..3).map(|_| solver.new_clause_group()).collect();
+        assert!(solver.add_clause_in_group(groups[0], &[a]));
+        assert!(solver.retract_clause_group(groups[0]));
+        assert!(solver.retract_clause_group(groups[0]));
+        // the last group took the place of the first one
+        assert!(!solver.is_clause_group_active(groups[0]));
+        assert!(solver.is_clause_group_active(groups[1]));
+        assert!(solver.is_clause_group_active(groups[2]));
+        assert!(solver.add_clause_in_group(groups[2], &[!a]));
+        assert_eq!(solver.group_clauses(groups[2]), [[!a]]);
+        assert_eq!(solver.solve_limited(&[]), lbool::TRUE);
+        assert_eq!(solver.value_lit(a), lbool::FALSE);
+        // the activation variable of the retracted group is reclaimed
+        let map = solver.compact().unwrap();
+        assert_eq!(solver.num_vars(), 3);
+        assert_eq!(map[groups[0].activation_lit().var()], Var::UNDEF);
+        assert_eq!(solver.solve_limited(&[a]), lbool::FALSE);
+    }
+
+    #[test]
+    fn test_remove_clause() {
+        let mut solver: Solver<callbacks::Basic> =
+            Solver::new(Default::default(), Default::default());
+        let a = Lit::new(solver.new_var_default(), true);
+        let b = Lit::new(solver.new_var_default(), true);
+        assert!(solver.add_clause_reuse(&mut vec![a, b]));
+        let h1 = solver.add_removable_clause(&[!a]).unwrap();
+        let h2 = solver.add_removable_clause(&[!b, a]).unwrap();
+        assert_eq!(solver.solve_limited(&[]), lbool::FALSE);
+        assert_eq!(solver.num_clauses(), 3);
+        assert!(solver.remove_clause(h1));
+        assert_eq!(solver.num_clauses(), 2);
+        assert!(!solver.has_clause(h1));
+        assert!(solver.has_clause(h2));
+        assert_eq!(solver.solve_limited(&[]), lbool::TRUE);
+        assert_eq!(solver.value_lit(a), lbool::TRUE);
+        assert_eq!(solver.num_clauses(), 2);
+        assert!(solver.remove_clause(h2));
+        assert_eq!(solver.num_clauses(), 1);
+        assert_eq!(solver.solve_limited(&[!a]), lbool::TRUE);
+    }
+
+    #[test]
+    fn test_iter_models() {
+        let mut solver: Solver<callbacks::Basic> =
+            Solver::new(Default::default(), Default::default());
+        let x: Vec<Lit> = (0..4)
+            .map(|_| Lit::new(solver.new_var_default(), true))
+            .collect();
+        assert!(solver.add_clause_reuse(&mut vec![x[0], x[1]]));
+        assert!(solver.add_clause_reuse(&mut vec![!x[1], x[2], !x[3]]));
+        let mut models = solver.iter_models();
+        let mut all: Vec<Vec<Lit>> = models.by_ref().collect();
+        assert!(models.is_exhausted());
+        drop(models);
+        // x1 is false: 4 assignments of x2 and x3, x1 is true: 2 times 3 of them
+        assert_eq!(all.len(), 10);
+        assert!(all.iter().all(|m| m.len() == 4));
+        all.sort();
+        all.dedup();
+        assert_eq!(all.len(), 10);
+        let vars = [x[1].var(), x[3].var()];
+        assert_eq!(solver.iter_projected_models(&vars).count(), 4);
+        assert_eq!(solver.iter_projected_models(&[]).count(), 1);
+        // the blocking clauses are gone
+        assert_eq!(solver.solve_limited(&[x[1], !x[2], !x[3]]), lbool::TRUE);
+        assert!(solver.add_clause_reuse(&mut vec![!x[0]]));
+        assert!(solver.add_clause_reuse(&mut vec![!x[1]]));
+        assert_eq!(solver.iter_models().count(), 0);
+    }
+
+    #[test]
+    fn test_iter_models_internal_vars() {
+        let mut solver: Solver<callbacks::Basic> =
+            Solver::new(Default::default(), Default::default());
+        let x: Vec<Lit> = (0..2)
+            .map(|_| Lit::new(solver.new_var_default(), true))
+            .collect();
+        let group = solver.new_clause_group();
+        assert!(solver.add_clause_in_group(group, &[x[0], x[1]]));
+        let is_user_lit = |m: &Vec<Lit>| {
+            m.iter()
+                .all(|l| l.var() == x[0].var() || l.var() == x[1].var())
+        };
+        let models: Vec<Vec<Lit>> = solver.iter_models().collect();
+        assert_eq!(models.len(), 3);
+        assert!(models.iter().all(|m| m.len() == 2 && is_user_lit(m)));
+        // neither the group nor the blocking clauses of the last enumeration add variables
+        assert!(solver.retract_clause_group(group));
+        let models: Vec<Vec<Lit>> = solver.iter_models().collect();
+        assert_eq!(models.len(), 4);
+        assert!(models.iter().all(|m| m.len() == 2 && is_user_lit(m)));
+    }
+
+    #[test]
+    fn test_count_models() {
+        let mut solver: Solver<callbacks::Basic> =
+            Solver::new(Default::default(), Default::default());
+        let x: Vec<Lit> = (0..12)
+            .map(|_| Lit::new(solver.new_var_default(), true))
+            .collect();
+        for i in 0..4 {
+            assert!(solver.add_clause_reuse(&mut vec![x[3 * i], !x[3 * i + 1], x[3 * i + 2]]));
+        }
+        assert!(solver.add_clause_reuse(&mut vec![!x[0], !x[11]]));
+        let vars: Vec<Var> = x.iter().map(|l| l.var()).collect();
+        let exact = (0..1u32 << 12)
+            .filter(|a| {
+                let val = |i: usize| a >> i & 1 == 1;
+                (0..4).all(|i| val(3 * i) || !val(3 * i + 1) || val(3 * i + 2))
+                    && !(val(0) && val(11))
+            })
+            .count() as u64;
+        assert_eq!(solver.count_models(&vars), Some(exact));
+        assert_eq!(solver.count_models(&vars[..2]), Some(4));
+        let approx = solver.approx_count_models(&vars, 0.8, 0.2).unwrap();
+        assert!(approx * 1.8 >= exact as f64 && approx <= exact as f64 * 1.8);
+        // small counts are exact
+        assert_eq!(solver.approx_count_models(&vars[..3], 0.8, 0.2), Some(7.0));
+        // the random XOR constraints are gone
+        assert_eq!(solver.count_models(&vars), Some(exact));
+    }
+
+    #[test]
+    fn test_assumption_order() {
+        let solver = |order: &str| {
+            let mut solver: Solver<callbacks::Basic> =
+                Solver::new(Default::default(), Default::default());
+            solver.set_option("assumption_order", order).unwrap();
+            let a = Lit::new(solver.new_var_default(), true);
+            let b = Lit::new(solver.new_var_default(), true);
+            assert!(solver.add_clause_reuse(&mut vec![!a]));
+            assert!(solver.add_clause_reuse(&mut vec![!b]));
+            (solver, a, b)
+        };
+        // the first failed assumption is the core
+        let (mut given, a, b) = solver("given");
+        assert_eq!(given.solve_limited(&[a, b]), lbool::FALSE);
+        assert_eq!(given.unsat_core(), [a]);
+        given.set_assumption_priority(b.var(), 1);
+        assert_eq!(given.assumption_priority(b.var()), 1);
+        assert_eq!(given.solve_limited(&[a, b]), lbool::FALSE);
+        assert_eq!(given.unsat_core(), [b]);
+
+        let (mut cores, a, b) = solver("cores");
+        assert_eq!(cores.solve_limited(&[a, b]), lbool::FALSE);
+        assert_eq!(cores.unsat_core(), [a]);
+        assert_eq!(cores.solve_limited(&[b, a]), lbool::FALSE);
+        assert_eq!(cores.unsat_core(), [a]);
+
+        let (mut activity, a, b) = solver("activity");
+        activity.bump_activity(b.var(), 10.0);
+        assert_eq!(activity.solve_limited(&[a, b]), lbool::FALSE);
+        assert_eq!(activity.unsat_core(), [b]);
+        assert!(activity.set_option("assumption_order", "random").is_err());
+    }
+
+    #[test]
+    fn test_extract_mus() {
+        let mut solver: Solver<callbacks::Basic> =
+            Solver::new(Default::default(), Default::default());
+        let mut lit = || Lit::new(solver.new_var_default(), true);
+        let (a, b, c) = (lit(), lit(), lit());
+        let s: Vec<Lit> = (0..5).map(|_| lit()).collect();
+        let clauses = [vec![a], vec![!a, b], vec![!b], vec![c], vec![!a, !c]];
+        for (c, &s) in clauses.iter().zip(&s) {
+            let mut c = c.clone();
+            c.push(!s);
+            assert!(solver.add_clause_reuse(&mut c));
+        }
+        assert_eq!(solver.extract_mus(&s[1..]), None);
+        let mus = solver.extract_mus(&s).unwrap();
+        assert!(mus.len() == 3 && mus.contains(&s[0]));
+        // unsatisfiable, and minimal
+        assert_eq!(solver.solve_limited(&mus), lbool::FALSE);
+        for i in 0..mus.len() {
+            let mut rest = mus.clone();
+            rest.remove(i);
+            assert_eq!(solver.solve_limited(&rest), lbool::TRUE);
+        }
+
+        // with clause groups, one of which is outside of the MUS
+        let mut solver: Solver<callbacks::Basic> =
+            Solver::new(Default::default(), Default::default());
+        let (a, b) = (solver.new_var_default(), solver.new_var_default());
+        let (a, b) = (Lit::new(a, true), Lit::new(b, true));
+        let groups: Vec<ClauseGroup> = (0..4).map(|_| solver.new_clause_group()).collect();
+        assert!(solver.add_clause_in_group(groups[0], &[a, b]));
+        assert!(solver.add_clause_in_group(groups[1], &[!b]));
+        assert!(solver.add_clause_in_group(groups[2], &[b]));
+        assert!(solver.add_clause_in_group(groups[3], &[!a]));
+        let mus = solver.extract_group_mus().unwrap();
+        assert!(mus.len() == 2 && mus.contains(&groups[1]) && mus.contains(&groups[2]));
+        assert!(groups.iter().all(|&g| solver.is_clause_group_active(g)));
+        assert!(solver.retract_clause_group(groups[1]));
+        assert_eq!(solver.extract_group_mus(), None);
+    }
+
+    #[test]
+    fn test_budget() {
+        let mut solver = pigeonhole(6);
+        solver.set_conflict_budget(10);
+        assert_eq!(solver.solve_limited(&[]), lbool::UNDEF);
+        assert!(solver.num_conflicts() <= 11);
+        // budgets are counted from the call to the setter, and learnt clauses are kept
+        let mut res = lbool::UNDEF;
+        for _ in 0..10_000 {
+            solver.set_conflict_budget(100);
+            solver.set_propagation_budget(100_000);
+            res = solver.solve_limited(&[]);
+            if res != lbool::UNDEF {
+                break;
+            }
+        }
+        assert_eq!(res, lbool::FALSE);
+        solver.budget_off();
+        assert_eq!(solver.solve_limited(&[]), lbool::FALSE);
+    }
+
+    /// Pigeonhole problem with `n + 1` pigeons and `n` holes.
+    fn pigeonhole(n: usize) -> Solver<callbacks::Basic> {
+        let mut solver: Solver<callbacks::Basic> =
+            Solver::new(Default::default(), Default::default());
+        let p: Vec<Vec<Lit>> = (0..n + 1)
+            .map(|_| {
+                (0..n)
+                    .map(|_| Lit::new(solver.new_var_default(), true))
+                    .collect()
+            })
+            .collect();
+        for row in &p {
+            assert!(solver.add_clause_reuse(&mut row.clone()));
+        }
+        for h in 0..n {
+            for (i, pi) in p.iter().enumerate() {
+                for pj in &p[..i] {
+                    assert!(solver.add_clause_reuse(&mut vec![!pi[h], !pj[h]]));
+                }
+            }
+        }
+        solver
+    }
+
+    #[test]
+    fn test_tick_limit() {
+        // the same limit always gives the same result
+        let run = |limit| {
+            let mut solver = pigeonhole(8);
+            solver.set_tick_limit(limit);
+            let res = solver.solve_limited(&[]);
+            (res, solver.num_conflicts())
+        };
+        let (res, conflicts) = run(20_000);
+        assert_eq!(res, lbool::UNDEF);
+        assert_eq!(run(20_000), (res, conflicts));
+        assert!(run(40_000).1 > conflicts);
+    }
+
+    #[test]
+    fn test_callbacks() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+        let mut solver = pigeonhole(6);
+        let calls = Arc::new(AtomicUsize::new(0));
+        let c = calls.clone();
+        solver.set_terminate_callback(move || c.fetch_add(1, Ordering::Relaxed) >= 50);
+        // learnt clauses are implied by the problem
+        let learnt = Arc::new(AtomicUsize::new(0));
+        let l = learnt.clone();
+        let mut solver2 = pigeonhole(6);
+        solver.set_learn_callback(8, move |c| {
+            assert!(!c.is_empty() && c.len() <= 8);
+            let assumps: Vec<Lit> = c.iter().map(|&l| !l).collect();
+            assert_eq!(solver2.solve_limited(&assumps), lbool::FALSE);
+            l.fetch_add(1, Ordering::Relaxed);
+        });
+        assert_eq!(solver.solve_limited(&[]), lbool::UNDEF);
+        let n_calls = calls.load(Ordering::Relaxed);
+        assert!(n_calls > 50);
+        assert!(learnt.load(Ordering::Relaxed) > 0);
+        solver.clear_callbacks();
+        assert_eq!(solver.solve_limited(&[]), lbool::FALSE);
+        assert_eq!(calls.load(Ordering::Relaxed), n_calls);
+    }
+
+    #[test]
+    fn test_cube() {
+        let mut solver = pigeonhole(6);
+        let cubes = solver.cube(3);
+        assert!(!cubes.is_empty() && cubes.len() <= 8);
+        assert_eq!(solver.cube_and_conquer(3), lbool::FALSE);
+
+        // random formulas: the cubes cover every model
+        let mut rng = Rng::new(7);
+        let mut rand = |n| rng.next(n);
+        let eval = |l: Lit, a: u32| ((a >> l.var().idx()) & 1 == 1) == l.sign();
+        for _ in 0..200 {
+            let mut solver: Solver<callbacks::Basic> =
+                Solver::new(Default::default(), Default::default());
+            let vars: Vec<Var> = (0..10).map(|_| solver.new_var_default()).collect();
+            let clauses: Vec<Vec<Lit>> = (0..rand(45))
+                .map(|_| {
+                    let c: Vec<Lit> = (0..3)
+                        .map(|_| Lit::new(vars[rand(10) as usize], rand(2) == 0))
+                        .collect();
+                    solver.add_clause_reuse(&mut c.clone());
+                    c
+                })
+                .collect();
+            let depth = rand(4) as u32;
+            let cubes = solver.cube(depth);
+            assert!(cubes.len() <= 1 << depth);
+            let mut sat = false;
+            for a in 0..1u32 << 10 {
+                if clauses.iter().all(|c| c.iter().any(|&l| eval(l, a))) {
+                    sat = true;
+                    assert!(cubes.iter().any(|c| c.iter().all(|&l| eval(l, a))));
+                }
+            }
+            let res = solver.cube_and_conquer(depth);
+            assert_eq!(res, if sat { lbool::TRUE } else { lbool::FALSE });
+            if sat {
+                assert!(clauses
+                    .iter()
+                    .all(|c| c.iter().any(|&l| solver.value_lit(l) == lbool::TRUE)));
+            }
+        }
+    }
+
+    #[test]
+    fn test_cube_native_conflict() {
+        let (mut solver, _) = native_conflict_at_level0();
+        assert!(solver.cube(2).is_empty());
+        assert_eq!(solver.solve_limited(&[]), lbool::FALSE);
+    }
+
+    #[test]
+    fn test_implies() {
+        let mut solver: Solver<callbacks::Basic> =
+            Solver::new(Default::default(), Default::default());
+        let lits: Vec<Lit> = (0..6)
+            .map(|_| Lit::new(solver.new_var_default(), true))
+            .collect();
+        let (a, b, c, d, e, f) = (lits[0], lits[1], lits[2], lits[3], lits[4], lits[5]);
+        assert!(solver.add_clause_reuse(&mut vec![!a, b]));
+        assert!(solver.add_clause_reuse(&mut vec![!b, !c, d]));
+        assert!(solver.add_clause_reuse(&mut vec![e]));
+        assert!(solver.add_clause_reuse(&mut vec![!d, !f, !a]));
+        let mut out = vec![];
+        assert!(solver.implies(&[a, c], &mut out));
+        out.sort_unstable();
+        let mut expected = vec![b, d, !f];
+        expected.sort_unstable();
+        assert_eq!(out, expected);
+        // the assumptions are refuted by propagation
+        assert!(!solver.implies(&[a, c, f], &mut out));
+        assert!(out.is_empty());
+        assert!(!solver.implies(&[a, !b], &mut out));
+        // the solver is left at level 0
+        assert!(solver.implies(&[], &mut out));
+        assert!(out.is_empty());
+        assert_eq!(solver.solve_limited(&[a, c]), lbool::TRUE);
+        assert_eq!(solver.value_lit(f), lbool::FALSE);
+    }
+
+    #[test]
+    fn test_propagate_under() {
+        let mut solver: Solver<callbacks::Basic> =
+            Solver::new(Default::default(), Default::default());
+        let lits: Vec<Lit> = (0..6)
+            .map(|_| Lit::new(solver.new_var_default(), true))
+            .collect();
+        let (a, b, c, d, e, f) = (lits[0], lits[1], lits[2], lits[3], lits[4], lits[5]);
+        assert!(solver.add_clause_reuse(&mut vec![!a, b]));
+        assert!(solver.add_clause_reuse(&mut vec![!b, !c, d]));
+        assert!(solver.add_clause_reuse(&mut vec![e]));
+        assert!(solver.add_xor_clause(&[d, e, f], true));
+        {
+            let view = solver.propagate_under(&[a, c]).unwrap();
+            assert_eq!(view.trail()[0], e);
+            assert_eq!(view.implied().len(), 3);
+            assert_eq!(view.value_lit(f), lbool::TRUE);
+            assert_eq!(view.reason(a), None);
+            assert_eq!(view.reason(e), None);
+            for &lit in view.implied() {
+                let reason = view.reason(lit).unwrap();
+                assert_eq!(reason[0], lit);
+                assert!(reason[1..]
+                    .iter()
+                    .all(|&l| view.value_lit(l) == lbool::FALSE));
+            }
+        }
+        // the solver is back at level 0
+        assert_eq!(solver.value_lit(a), lbool::UNDEF);
+        assert_eq!(
+            solver.propagate_under(&[a, !e]).err(),
+            Some(PropagationConflict::Assumption(!e))
+        );
+        // the xor constraint is false
+        let res = solver.propagate_under(&[b, c, !f]).err();
+        assert!(matches!(res, Some(PropagationConflict::Clause(ref lits))
+            if lits.iter().all(|l| [!d, !e, f].contains(l))));
+        let res = solver.propagate_under(&[a, c, !d]).err();
+        let clauses = [vec![!a, b], vec![!b, !c, d]];
+        assert!(matches!(res, Some(PropagationConflict::Clause(ref lits))
+            if clauses.iter().any(|c| c.len() == lits.len() && c.iter().all(|l| lits.contains(l)))));
+        assert!(!solver.add_clause_reuse(&mut vec![!e]));
+        assert_eq!(
+            solver.propagate_under(&[]).err(),
+            Some(PropagationConflict::Unsat)
+        );
+    }
+
+    /// At most one of `[x1, !x3, !x4, x6]`, with `!x3` and `!x4` true at level 0.
+    fn native_conflict_at_level0() -> (Solver<callbacks::Basic>, Vec<Lit>) {
+        let mut solver: Solver<callbacks::Basic> =
+            Solver::new(Default::default(), Default::default());
+        let x: Vec<Lit> = (0..7)
+            .map(|_| Lit::new(solver.new_var_default(), true))
+            .collect();
+        assert!(solver.add_at_most_k(&[x[1], !x[3], !x[4], x[6]], 1));
+        assert!(solver.add_clause_reuse(&mut vec![!x[4]]));
+        assert!(solver.add_clause_reuse(&mut vec![!x[3]]));
+        (solver, x)
+    }
+
+    #[test]
+    fn test_propagate_under_native_conflict() {
+        let (mut solver, x) = native_conflict_at_level0();
+        assert_eq!(
+            solver.propagate_under(&[x[5]]).err(),
+            Some(PropagationConflict::Unsat)
+        );
+        assert_eq!(solver.solve_limited(&[]), lbool::FALSE);
+    }
+
+    #[test]
+    fn test_implies_native_conflict() {
+        let (mut solver, x) = native_conflict_at_level0();
+        let mut out = vec![x[0]];
+        assert!(!solver.implies(&[x[5]], &mut out));
+        assert!(out.is_empty());
+        assert_eq!(solver.solve_limited(&[]), lbool::FALSE);
+
+        // the at-most-one and the xor constraints propagate with the clauses
+        let mut solver: Solver<callbacks::Basic> =
+            Solver::new(Default::default(), Default::default());
+        let x: Vec<Lit> = (0..5)
+            .map(|_| Lit::new(solver.new_var_default(), true))
+            .collect();
+        assert!(solver.add_at_most_k(&[x[0], x[1], x[2]], 1));
+        assert!(solver.add_xor_clause(&[x[1], x[3]], true));
+        assert!(solver.add_clause_reuse(&mut vec![!x[4], x[0]]));
+        assert!(solver.implies(&[x[4]], &mut out));
+        out.sort_unstable();
+        let mut expected = vec![x[0], !x[1], !x[2], x[3]];
+        expected.sort_unstable();
+        assert_eq!(out, expected);
+        assert_eq!(solver.solve_limited(&[x[4]]), lbool::TRUE);
+    }
+
+    #[test]
+    fn test_trail_inspection() {
+        let mut solver: Solver<callbacks::Basic> =
+            Solver::new(Default::default(), Default::default());
+        let lits: Vec<Lit> = (0..4)
+            .map(|_| Lit::new(solver.new_var_default(), true))
+            .collect();
+        let (a, b, c, d) = (lits[0], lits[1], lits[2], lits[3]);
+        assert!(solver.add_clause_reuse(&mut vec![!a, b]));
+        assert!(solver.add_clause_reuse(&mut vec![d]));
+        assert!(solver.add_clause_reuse(&mut vec![!d, c]));
+        // level 0, between the calls
+        assert_eq!(solver.trail(), &[d, c]);
+        assert_eq!(solver.trail_limits().len(), 0);
+        assert_eq!(solver.level_of(c.var()), Some(0));
+        assert_eq!(solver.level_of(a.var()), None);
+        assert_eq!(solver.reason_of(a.var()), None);
+        assert_eq!(solver.reason_of(d.var()), None);
+        let view = solver.propagate_under(&[a]).unwrap();
+        assert_eq!(view.trail_limits().collect::<Vec<_>>(), [2]);
+        assert_eq!(view.level_of(a.var()), Some(1));
+        assert_eq!(view.level_of(b.var()), Some(1));
+        assert_eq!(view.reason_of(a.var()), None);
+        let reason = view.reason_of(b.var()).unwrap();
+        assert_eq!(reason[0], b);
+        assert_eq!(reason[1], !a);
+    }
+
+    #[test]
+    fn test_eliminate() {
+        let mut solver: Solver<callbacks::Basic> =
+            Solver::new(Default::default(), Default::default());
+        let a = Lit::new(solver.new_var_default(), true);
+        let b = Lit::new(solver.new_var_default(), true);
+        let c = Lit::new(solver.new_var_default(), true);
+        solver.add_clause_reuse(&mut vec![a, b]);
+        solver.add_clause_reuse(&mut vec![!a, c]);
+        solver.set_frozen(b.var(), true);
+        solver.set_frozen(c.var(), true);
+        assert!(solver.eliminate());
+        assert!(solver.is_eliminated(a.var()));
+        assert_eq!(solver.num_clauses(), 1);
+        assert_eq!(solver.solve_limited(&[!b]), lbool::TRUE);
+        assert_eq!(solver.value_lit(a), lbool::TRUE);
+        // assuming `!a` restores it
+        assert_eq!(solver.solve_limited(&[!a, !b]), lbool::FALSE);
+        assert!(!solver.is_eliminated(a.var()));
+
+        // random formulas, compared with the truth tables
+        let mut rng = Rng::new(11);
+        let mut rand = |n| rng.next(n);
+        let eval = |l: Lit, a: u32| ((a >> l.var().idx()) & 1 == 1) == l.sign();
+        for i in 0..300 {
+            let opts = SolverOpts {
+                blocked_clause_elim: i % 2 == 1,
+                elim_clause_lim: if i % 4 == 3 { 0 } else { 20 },
+                bva: i % 3 == 2,
+                ..Default::default()
+            };
+            let mut solver: Solver<callbacks::Basic> = Solver::new(opts, Default::default());
+            let vars: Vec<Var> = (0..10).map(|_| solver.new_var_default()).collect();
+            let mut rand_clause = |len| -> Vec<Lit> {
+                (0..len)
+                    .map(|_| Lit::new(vars[rand(10) as usize], rand(2) == 0))
+                    .collect()
+            };
+            let mut clauses: Vec<Vec<Lit>> = (0..35).map(|i| rand_clause(2 + i % 3)).collect();
+            let extra = rand_clause(2);
+            let assumps = rand_clause(2);
+            for c in &clauses {
+                solver.add_clause_reuse(&mut c.clone());
+            }
+            solver.set_frozen(vars[0], true);
+            solver.eliminate();
+            assert!(!solver.is_eliminated(vars[0]));
+            let check = |solver: &mut Solver<_>, clauses: &[Vec<Lit>], assumps: &[Lit]| {
+                let sat = (0..1u32 << 10).any(|a| {
+                    assumps.iter().all(|&l| eval(l, a))
+                        && clauses.iter().all(|c| c.iter().any(|&l| eval(l, a)))
+                });
+                let res = solver.solve_limited(assumps);
+                assert_eq!(res, if sat { lbool::TRUE } else { lbool::FALSE });
+                if sat {
+                    let is_true = |l: Lit| solver.value_lit(l) == lbool::TRUE;
+                    assert!(assumps.iter().all(|&l| is_true(l)));
+                    assert!(clauses.iter().all(|c| c.iter().any(|&l| is_true(l))));
+                }
+            };
+            check(&mut solver, &clauses, &[]);
+            // eliminated variables are restored when used again
+            check(&mut solver, &clauses, &assumps);
+            solver.add_clause_reuse(&mut extra.clone());
+            clauses.push(extra);
+            check(&mut solver, &clauses, &[]);
+            solver.eliminate();
+            check(&mut solver, &clauses, &assumps);
+        }
+    }
+
+    #[test]
+    fn test_eliminate_gate() {
+        let mut solver: Solver<callbacks::Basic> =
+            Solver::new(Default::default(), Default::default());
+        let x = Lit::new(solver.new_var_default(), true);
+        let [a, b, c, d, e, f] = [(); 6].map(|_| Lit::new(solver.new_var_default(), true));
+        for l in [a, b, c, d, e, f] {
+            solver.set_frozen(l.var(), true);
+        }
+        // `x = a & b`
+        let mut clauses = vec![vec![!x, a], vec![!x, b], vec![x, !a, !b]];
+        clauses.extend([vec![x, c], vec![x, d], vec![!x, e, f]]);
+        for c in &clauses {
+            solver.add_clause_reuse(&mut c.clone());
+        }
+        // 7 non-tautological resolvents, but only 5 with the clauses of the gate
+        assert!(solver.eliminate());
+        assert!(solver.is_eliminated(x.var()));
+        assert_eq!(solver.num_clauses(), 5);
+        for assumps in [[a, b], [!a, !e], [!c, !f]] {
+            assert_eq!(solver.solve_limited(&assumps), lbool::TRUE);
+            let is_true = |l: Lit| solver.value_lit(l) == lbool::TRUE;
+            assert!(clauses.iter().all(|c| c.iter().any(|&l| is_true(l))));
+        }
+        assert_eq!(solver.solve_limited(&[a, b, !e, !f]), lbool::FALSE);
+    }
+
+    #[test]
+    fn test_blocked_clause_elim() {
+        let opts = SolverOpts {
+            blocked_clause_elim: true,
+            elim_clause_lim: 0,
+            ..Default::default()
+        };
+        let mut solver: Solver<callbacks::Basic> = Solver::new(opts, Default::default());
+        let a = Lit::new(solver.new_var_default(), true);
+        let b = Lit::new(solver.new_var_default(), true);
+        let c = Lit::new(solver.new_var_default(), true);
+        solver.set_frozen(b.var(), true);
+        solver.set_frozen(c.var(), true);
+        solver.add_clause_reuse(&mut vec![a, b]);
+        solver.add_clause_reuse(&mut vec![!a, !b]);
+        solver.add_clause_reuse(&mut vec![!a, c]);
+        // `!a | !b` is blocked on `!a`, `a` is not eliminated
+        assert!(solver.eliminate());
+        assert!(!solver.is_eliminated(a.var()));
+        assert_eq!(solver.num_clauses(), 2);
+        assert_eq!(solver.solve_limited(&[b, c]), lbool::TRUE);
+        assert_eq!(solver.value_lit(a), lbool::FALSE);
+        // using `a` again restores the blocked clause
+        solver.add_clause_reuse(&mut vec![a]);
+        assert_eq!(solver.num_clauses(), 3);
+        assert_eq!(solver.solve_limited(&[b]), lbool::FALSE);
+    }
+
+    #[test]
+    fn test_bva() {
+        let opts = SolverOpts {
+            bva: true,
+            ..Default::default()
+        };
+        let mut solver: Solver<callbacks::Basic> = Solver::new(opts, Default::default());
+        let x: Vec<Lit> = (0..6)
+            .map(|_| Lit::new(solver.new_var_default(), true))
+            .collect();
+        // exactly one of the literals, with the pairwise encoding
+        solver.add_clause_reuse(&mut x.clone());
+        for i in 0..6 {
+            solver.set_frozen(x[i].var(), true);
+            for j in 0..i {
+                solver.add_clause_reuse(&mut vec![!x[i], !x[j]]);
+            }
+        }
+        assert!(solver.eliminate());
+        assert!(solver.num_vars() > 6);
+        assert!(solver.num_clauses() < 16);
+        for i in 0..6 {
+            assert_eq!(solver.solve_limited(&[x[i]]), lbool::TRUE);
+            assert!((0..6).all(|j| solver.value_lit(x[j]) == lbool::new(i == j)));
+            assert_eq!(solver.solve_limited(&[x[i], x[(i + 1) % 6]]), lbool::FALSE);
+        }
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_time_limit() {
+        let mut solver = pigeonhole(11);
+        solver.set_time_limit(std::time::Duration::ZERO);
+        assert_eq!(solver.solve_limited(&[]), lbool::UNDEF);
+        solver.budget_off();
+        solver.set_conflict_budget(100);
+        assert_eq!(solver.solve_limited(&[]), lbool::UNDEF);
+        assert!(solver.num_conflicts() >= 100);
+    }
+
+    #[test]
+    fn test_stop_flag() {
+        use std::sync::atomic::{AtomicBool, Ordering};
+        use std::sync::Arc;
+        let mut solver: Solver<callbacks::Basic> =
+            Solver::new(Default::default(), Default::default());
+        let a = Lit::new(solver.new_var_default(), true);
+        let b = Lit::new(solver.new_var_default(), true);
+        assert!(solver.add_clause_reuse(&mut vec![a, b]));
+        assert!(solver.add_clause_reuse(&mut vec![!a, b]));
+        let flag = Arc::new(AtomicBool::new(true));
+        solver.set_stop_flag(flag.clone());
+        assert_eq!(solver.solve_limited(&[]), lbool::UNDEF);
+        flag.store(false, Ordering::Relaxed);
+        assert_eq!(solver.solve_limited(&[]), lbool::TRUE);
+        assert_eq!(solver.value_lit(b), lbool::TRUE);
+        flag.store(true, Ordering::Relaxed);
+        assert_eq!(solver.solve_limited(&[!b]), lbool::UNDEF);
+        solver.clear_stop_flag();
+        assert_eq!(solver.solve_limited(&[!b]), lbool::FALSE);
+        static STOP: AtomicBool = AtomicBool::new(true);
+        solver.set_static_stop_flag(&STOP);
+        assert_eq!(solver.solve_limited(&[]), lbool::UNDEF);
+        STOP.store(false, Ordering::Relaxed);
+        assert_eq!(solver.solve_limited(&[]), lbool::TRUE);
+    }
+
+    #[test]
+    fn test_minimize_model() {
+        let mut solver: Solver<callbacks::Basic> =
+            Solver::new(Default::default(), Default::default());
+        let x: Vec<Lit> = (0..4)
+            .map(|_| Lit::new(solver.new_var_default(), true))
+            .collect();
+        let vars: Vec<Var> = x.iter().map(|l| l.var()).collect();
+        assert!(solver.add_clause_reuse(&mut vec![x[0], x[1], x[2]]));
+        assert!(solver.add_clause_reuse(&mut vec![x[0], !x[3]]));
+        assert_eq!(solver.solve_limited(&[x[0], x[1], x[2], x[3]]), lbool::TRUE);
+        assert_eq!(solver.minimize_model(&vars), [x[0]]);
+        // `x[0]` is kept anyway
+        assert_eq!(solver.minimize_model(&vars[1..]), []);
+        assert_eq!(solver.solve_limited(&[!x[0], x[1], x[2]]), lbool::TRUE);
+        let m = solver.minimize_model(&vars);
+        assert!(m.len() == 2 && m.contains(&!x[3]));
+
+        // random formulas: the implicant satisfies the clauses and is prime
+        let mut rng = Rng::new(13);
+        let mut rand = |n| rng.next(n);
+        for _ in 0..200 {
+            let mut solver: Solver<callbacks::Basic> =
+                Solver::new(Default::default(), Default::default());
+            let vars: Vec<Var> = (0..8).map(|_| solver.new_var_default()).collect();
+            let clauses: Vec<Vec<Lit>> = (0..rand(12))
+                .map(|_| {
+                    let mut c: Vec<Lit> = (0..3)
+                        .map(|_| Lit::new(vars[rand(8) as usize], rand(2) == 0))
+                        .collect();
+                    solver.add_clause_reuse(&mut c.clone());
+                    c.sort();
+                    c
+                })
+                .collect();
+            if solver.solve_limited(&[]) != lbool::TRUE {
+                continue;
+            }
+            let relevant = &vars[..6];
+            let mut implicant = solver.minimize_model(relevant);
+            implicant.extend(
+                vars[6..]
+                    .iter()
+                    .map(|&v| Lit::new(v, solver.value_var(v) == lbool::TRUE)),
+            );
+            let tautology = |c: &[Lit]| c.iter().any(|&l| c.contains(&!l));
+            let covered = |imp: &[Lit]| {
+                clauses
+                    .iter()
+                    .all(|c| tautology(c) || c.iter().any(|l| imp.contains(l)))
+            };
+            assert!(covered(&implicant));
+            for i in 0..implicant.len() {
+                let v = implicant[i].var();
+                if relevant.contains(&v) && !solver.proved_at_lvl_0().contains(&implicant[i]) {
+                    let mut rest = implicant.clone();
+                    rest.remove(i);
+                    assert!(!covered(&rest));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_reuse_trail() {
+        let mut rng = Rng::new(5);
+        let mut rand = |n| rng.next(n);
+        let opts = SolverOpts {
+            reuse_trail: true,
+            ..Default::default()
+        };
+        let mut reusing: Solver<callbacks::Basic> = Solver::new(opts, Default::default());
+        let mut solver: Solver<callbacks::Basic> =
+            Solver::new(Default::default(), Default::default());
+        let vars: Vec<Var> = (0..30).map(|_| solver.new_var_default()).collect();
+        for _ in 0..30 {
+            reusing.new_var_default();
+        }
+        let rand_lit =
+            |rand: &mut dyn FnMut(u64) -> u64| Lit::new(vars[rand(30) as usize], rand(2) == 0);
+        let add_clause = |reusing: &mut Solver<_>, solver: &mut Solver<_>, c: &[Lit]| {
+            reusing.add_clause_reuse(&mut c.to_vec());
+            solver.add_clause_reuse(&mut c.to_vec());
+        };
+        for _ in 0..100 {
+            let c: Vec<Lit> = (0..3).map(|_| rand_lit(&mut rand)).collect();
+            add_clause(&mut reusing, &mut solver, &c);
+        }
+        // queries that change the last assumptions, as in MUS or MaxSAT loops
+        let mut assumps: Vec<Lit> = vec![];
+        for i in 0..300 {
+            let keep = rand(assumps.len() as u64 + 1) as usize;
+            assumps.truncate(keep);
+            for _ in 0..rand(4) {
+                assumps.push(rand_lit(&mut rand));
+            }
+            if i % 50 == 49 {
+                let c: Vec<Lit> = (0..4).map(|_| rand_lit(&mut rand)).collect();
+                add_clause(&mut reusing, &mut solver, &c);
+            }
+            let res = reusing.solve_limited(&assumps);
+            assert_eq!(res, solver.solve_limited(&assumps));
+            assert!(reusing.trail_limits().len() <= assumps.len());
+            if res == lbool::TRUE {
+                for &lit in &assumps {
+                    assert_eq!(reusing.value_lit(lit), lbool::TRUE);
+                }
+            } else if res == lbool::FALSE {
+                for &lit in reusing.unsat_core() {
+                    assert!(assumps.contains(&lit));
+                }
+            }
+        }
+        assert!(reusing.stats().trail_reuses > 0);
+    }
 }
@@ -0,0 +1,265 @@
+//! Craig interpolants from resolution proofs
+//!
+//! The clauses of the two sides are refuted by a separate solver, whose LRAT hints are
+//! recorded. Each lemma follows from its hints by unit propagation, which is a chain
+//! of resolutions: starting from the conflicting hint, the reason of each implied
+//! literal is resolved with it, in the reverse order of propagation. McMillan's system
+//! labels the clauses along these chains:
+//!
+//! - a clause of `A` gets the disjunction of its literals over the global variables
+//!   (those that occur in `A` and `B`), a clause of `B` gets `true`;
+//! - a resolvent gets the disjunction of the labels of its premises if the pivot only
+//!   occurs in `A`, their conjunction otherwise.
+//!
+//! The label of the empty clause is the interpolant.
+use no_std_compat::prelude::v1::*;
+use {
+    crate::{
+        aig::{Aig, AigLit},
+        check::{self, LratStep},
+        clause::{lbool, Lit, VMap, Var},
+        drat::ProofOutput,
+        interface::SolverInterface,
+        intmap::IntMapBool,
+        BasicSolver,
+    },
+    std::any::Any,
+};
+
+/// Records the LRAT steps of a solver.
+#[derive(Default)]
+struct Recorder(Vec<LratStep>);
+
+impl ProofOutput for Recorder {
+    fn add_clause(&mut self, _: &[Lit]) {
+        unreachable!("LRAT proofs need clause ids")
+    }
+
+    fn delete_clause(&mut self, _: &[Lit]) {}
+
+    fn wants_hints(&self) -> bool {
+        true
+    }
+
+    fn add_clause_with_hints(&mut self, id: u64, c: &[Lit], hints: &[u64]) {
+        let step = LratStep::Add {
+            id,
+            clause: c.to_vec(),
+            hints: hints.to_vec(),
+        };
+        self.0.push(step)
+    }
+
+    fn delete_clause_with_id(&mut self, _: u64, _: &[Lit]) {}
+}
+
+/// Clauses of the proof, numbered from `1`, with their labels.
+struct Labels {
+    clauses: Vec<Vec<Lit>>,
+    labels: Vec<AigLit>,
+    vals: VMap<lbool>,
+}
+
+impl Labels {
+    fn clause(&self, id: u64) -> &[Lit] {
+        &self.clauses[id as usize - 1]
+    }
+
+    fn value(&self, lit: Lit) -> lbool {
+        self.vals[lit.var()] ^ !lit.sign()
+    }
+
+    /// Label of the lemma `c`, which follows from `hints` by unit propagation; `None`
+    /// if it does not.
+    fn lemma(
+        &mut self,
+        aig: &mut Aig,
+        a_local: &IntMapBool<Var>,
+        c: &[Lit],
+        hints: &[u64],
+    ) -> Option<AigLit> {
+        // propagate the negation of `c`, recording the reason of each implied literal
+        let mut assigned = vec![];
+        let mut implied = vec![];
+        let mut conflict = None;
+        for &lit in c {
+            if self.value(lit) == lbool::UNDEF {
+                self.vals[lit.var()] = lbool::new(!lit.sign());
+                assigned.push(lit.var());
+            }
+        }
+        for &h in hints {
+            let mut unit = None;
+            for &lit in self.clause(h) {
+                if self.value(lit) != lbool::FALSE {
+                    unit = Some(lit);
+                }
+            }
+            match unit {
+                Some(lit) => {
+                    self.vals[lit.var()] = lbool::new(lit.sign());
+                    assigned.push(lit.var());
+                    implied.push((lit, h));
+                }
+                None => {
+                    conflict = Some(h);
+                    break;
+                }
+            }
+        }
+        for v in assigned {
+            self.vals[v] = lbool::UNDEF;
+        }
+        let conflict = conflict?;
+        // resolve the conflict with the reasons, backward
+        let mut resolvent = self.clause(conflict).to_vec();
+        let mut label = self.labels[conflict as usize - 1];
+        for &(lit, h) in implied.iter().rev() {
+            let Some(i) = resolvent.iter().position(|&x| x == !lit) else {
+                continue;
+            };
+            resolvent.swap_remove(i);
+            for &x in self.clause(h) {
+                if x != lit && !resolvent.contains(&x) {
+                    resolvent.push(x);
+                }
+            }
+            let other = self.labels[h as usize - 1];
+            label = if a_local[lit.var()] {
+                aig.or(label, other)
+            } else {
+                aig.and(label, other)
+            };
+        }
+        Some(label)
+    }
+}
+
+/// Interpolant of the clauses `a` and `b`, if they have no model together.
+pub(crate) fn interpolant(a: &[Vec<Lit>], b: &[Vec<Lit>]) -> Option<Aig> {
+    // the empty clause is not derived in the proof if it is given
+    for (clauses, itp) in [(a, AigLit::FALSE), (b, AigLit::TRUE)] {
+        if clauses.iter().any(|c| c.is_empty()) {
+            let mut aig = Aig::new();
+            aig.add_output(itp);
+            return Some(aig);
+        }
+    }
+    let mut solver = BasicSolver::default();
+    solver.set_proof_output(Box::new(Recorder::default()));
+    let mut in_a = IntMapBool::new();
+    let mut in_b = IntMapBool::new();
+    for (clauses, occurs) in [(a, &mut in_a), (b, &mut in_b)] {
+        for c in clauses {
+            for &lit in c {
+                solver.var_of_int(lit.var().idx());
+                occurs.insert(lit.var());
+            }
+            solver.add_clause_reuse(&mut c.clone());
+        }
+    }
+    if solver.solve_limited(&[]) != lbool::FALSE {
+        return None;
+    }
+    let recorder: Box<dyn Any> = solver.take_proof_output()?;
+    let steps = recorder.downcast::<Recorder>().ok()?.0;
+    let trimmed = check::trim_lrat(a.len() + b.len(), &steps).ok()?;
+
+    let num_vars = solver.num_vars();
+    let mut a_local = IntMapBool::new();
+    let mut global = IntMapBool::new();
+    for v in (0..num_vars).map(Var::from_idx) {
+        let (x, y) = (in_a.has(v) && in_a[v], in_b.has(v) && in_b[v]);
+        a_local.reserve(v);
+        a_local.set(v, x && !y);
+        global.reserve(v);
+        global.set(v, x && y);
+    }
+    let mut aig = Aig::new();
+    let mut labels = Labels {
+        clauses: vec![],
+        labels: vec![],
+        vals: VMap::new(),
+    };
+    for v in (0..num_vars).map(Var::from_idx) {
+        labels.vals.insert(v, lbool::UNDEF, lbool::UNDEF);
+    }
+    for &i in &trimmed.core {
+        let (c, label) = match i.checked_sub(a.len()) {
+            None => {
+                let c = &a[i];
+                let mut label = AigLit::FALSE;
+                for &lit in c.iter().filter(|lit| global[lit.var()]) {
+                    let x = aig.lit(lit);
+                    label = aig.or(label, x);
+                }
+                (c, label)
+            }
+            Some(j) => (&b[j], AigLit::TRUE),
+        };
+        labels.clauses.push(c.clone());
+        labels.labels.push(label);
+    }
+    let mut root = AigLit::TRUE;
+    for step in &trimmed.proof {
+        if let LratStep::Add { id, clause, hints } = step {
+            debug_assert_eq!(*id as usize, labels.clauses.len() + 1);
+            root = labels.lemma(&mut aig, &a_local, clause, hints)?;
+            labels.clauses.push(clause.clone());
+            labels.labels.push(root);
+        }
+    }
+    aig.add_output(root);
+    Some(aig)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{aig::AigNode, test_util::Rng};
+
+    #[test]
+    fn test_interpolant() {
+        let mut rng = Rng::new(7);
+        let mut rand = |n| rng.next(n);
+        // random 3-SAT over the variables `0..8` for `a`, `4..12` for `b`
+        let mut num_unsat = 0;
+        for _ in 0..40 {
+            let mut clauses = [vec![], vec![]];
+            for (side, clauses) in clauses.iter_mut().enumerate() {
+                for _ in 0..30 {
+                    let c: Vec<Lit> = (0..3)
+                        .map(|_| {
+                            let v = Var::from_idx(4 * side as u32 + rand(8) as u32);
+                            Lit::new(v, rand(2) == 0)
+                        })
+                        .collect();
+                    clauses.push(c);
+                }
+            }
+            let [a, b] = &clauses;
+            let Some(aig) = interpolant(a, b) else {
+                continue;
+            };
+            num_unsat += 1;
+            let itp = aig.outputs()[0];
+            for node in aig.nodes() {
+                if let AigNode::Input(v) = node {
+                    assert!((4..8).contains(&v.idx()), "{:?} is not global", v);
+                }
+            }
+            // `a` implies the interpolant, which contradicts `b`
+            for m in 0..1u32 << 12 {
+                let value = |v: Var| m >> v.idx() & 1 != 0;
+                let sat = |c: &Vec<Lit>| c.iter().any(|&lit| value(lit.var()) == lit.sign());
+                if a.iter().all(sat) {
+                    assert!(aig.eval(itp, value));
+                }
+                if b.iter().all(sat) {
+                    assert!(!aig.eval(itp, value));
+                }
+            }
+        }
+        assert!(num_unsat > 5, "{} unsatisfiable pairs", num_unsat);
+    }
+}
@@ -22,6 +22,7 @@ use bit_vec::BitVec;
 use no_std_compat::prelude::v1::*;
 use std::iter;
 use std::marker::PhantomData;
+use std::mem;
 use std::ops;
 
 pub trait AsIndex: Copy {
@@ -29,9 +30,19 @@ pub trait AsIndex: Copy {
     fn from_index(index: usize) -> Self;
 }
 
+/// Check that `index` is in bounds in debug mode, to report the misuse of a map
+/// with a clearer message. Release builds rely on the bounds check of the buffer.
+#[inline(always)]
+fn debug_check_index(index: usize, len: usize, ty: &str) {
+    debug_assert!(index < len, "{ty}: key {index} out of bounds ({len} keys)");
+}
+
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct IntMap<K: AsIndex, V> {
     map: Vec<V>,
+    /// The keys that were inserted, rather than padded by `reserve`.
+    occupied: BitVec,
     _marker: PhantomData<fn(K)>, // contravariance
 }
 
@@ -39,6 +50,7 @@ impl<K: AsIndex, V> Default for IntMap<K, V> {
     fn default() -> Self {
         Self {
             map: Vec::new(),
+            occupied: BitVec::new(),
             _marker: PhantomData,
         }
     }
@@ -52,6 +64,31 @@ impl<K: AsIndex, V> IntMap<K, V> {
     pub fn has(&self, k: K) -> bool {
         k.as_index() < self.map.len()
     }
+    /// Was `k` inserted, by `insert`, `insert_default` or `get_or_insert_with`?
+    #[inline]
+    pub fn contains_key(&self, k: K) -> bool {
+        self.occupied.get(k.as_index()).unwrap_or(false)
+    }
+    /// The value of `k`, or `None` beyond the end of the map.
+    #[inline]
+    pub fn get(&self, k: K) -> Option<&V> {
+        self.map.get(k.as_index())
+    }
+    #[inline]
+    pub fn get_mut(&mut self, k: K) -> Option<&mut V> {
+        self.map.get_mut(k.as_index())
+    }
+    /// The value of `k`, set to `f()` first if `k` was not inserted yet (the keys
+    /// before it are padded with the default value).
+    pub fn get_or_insert_with(&mut self, key: K, f: impl FnOnce() -> V) -> &mut V
+    where
+        V: Default,
+    {
+        if !self.contains_key(key) {
+            self.insert_default(key, f());
+        }
+        &mut self[key]
+    }
     pub fn reserve(&mut self, key: K, pad: V)
     where
         V: Clone,
@@ -79,6 +116,7 @@ impl<K: AsIndex, V> IntMap<K, V> {
     {
         self.reserve(key, pad);
         self[key] = val;
+        self.occupy(key);
     }
     pub fn insert_default(&mut self, key: K, val: V)
     where
@@ -86,17 +124,44 @@ impl<K: AsIndex, V> IntMap<K, V> {
     {
         self.reserve_default(key);
         self[key] = val;
+        self.occupy(key);
+    }
+    fn occupy(&mut self, key: K) {
+        let index = key.as_index();
+        if index >= self.occupied.len() {
+            self.occupied.grow(index + 1 - self.occupied.len(), false);
+        }
+        self.occupied.set(index, true);
     }
 
     /// Clear content, keep internal buffers. Does not allocate.
     pub fn clear(&mut self) {
         self.map.clear();
+        self.occupied.truncate(0);
     }
 
     /// Clear content, free memory
     pub fn free(&mut self) {
         self.map.clear();
         self.map.shrink_to_fit();
+        self.occupied = BitVec::new();
+    }
+    /// Number of keys the map can hold without reallocating.
+    pub fn capacity(&self) -> usize {
+        self.map.capacity()
+    }
+    /// Remove the keys from `key` on, and free the memory beyond the remaining ones.
+    pub fn shrink_to(&mut self, key: K) {
+        let len = key.as_index();
+        self.map.truncate(len);
+        self.map.shrink_to_fit();
+        self.occupied.truncate(len);
+        self.occupied.shrink_to_fit();
+    }
+    /// Bytes allocated for the values and the occupied keys, not counting the memory
+    /// the values own
+    pub fn memory_usage(&self) -> usize {
+        self.map.capacity() * mem::size_of::<V>() + self.occupied.capacity() / 8
     }
     pub fn iter(&self) -> impl iter::Iterator<Item = (K, &V)> {
         self.map
@@ -110,23 +175,153 @@ impl<K: AsIndex, V> IntMap<K, V> {
             .enumerate()
             .map(|(k, v)| (K::from_index(k), v))
     }
+    /// Iterate over the keys that were inserted, unlike [`iter`](Self::iter) which
+    /// also yields the padding.
+    pub fn iter_occupied(&self) -> impl iter::Iterator<Item = (K, &V)> {
+        let occupied = &self.occupied;
+        self.iter()
+            .filter(move |(k, _)| occupied.get(k.as_index()) == Some(true))
+    }
+    /// Panic if the internal invariants are broken. This does nothing in release mode.
+    pub fn check_invariants(&self) {
+        if cfg!(debug_assertions) {
+            assert!(
+                self.occupied.len() <= self.map.len(),
+                "IntMap: occupied key without value"
+            );
+        }
+    }
 }
 
 impl<K: AsIndex, V> ops::Index<K> for IntMap<K, V> {
     type Output = V;
     #[inline]
     fn index(&self, index: K) -> &Self::Output {
+        debug_check_index(index.as_index(), self.map.len(), "IntMap");
         &self.map[index.as_index()]
     }
 }
 impl<K: AsIndex, V> ops::IndexMut<K> for IntMap<K, V> {
     #[inline]
     fn index_mut(&mut self, index: K) -> &mut Self::Output {
+        debug_check_index(index.as_index(), self.map.len(), "IntMap");
         &mut self.map[index.as_index()]
     }
 }
 
+/// A map that can be cleared in O(1), for temporaries that are cleared often.
+///
+/// Each value is stamped with the generation in which it was last written, and
+/// values of an older generation read as the default value. `clear` just starts
+/// a new generation.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct IntMapClearable<K: AsIndex, V> {
+    map: Vec<V>,
+    stamps: Vec<u32>,
+    generation: u32,
+    pad: V,
+    _marker: PhantomData<fn(K)>, // contravariance
+}
+
+impl<K: AsIndex, V: Default> Default for IntMapClearable<K, V> {
+    fn default() -> Self {
+        Self {
+            map: Vec::new(),
+            stamps: Vec::new(),
+            generation: 1,
+            pad: V::default(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<K: AsIndex, V: Default + Clone> IntMapClearable<K, V> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    #[inline]
+    pub fn has(&self, k: K) -> bool {
+        k.as_index() < self.map.len()
+    }
+    pub fn reserve(&mut self, key: K) {
+        let index = key.as_index();
+        if index >= self.map.len() {
+            self.map.resize(index + 1, V::default());
+            self.stamps.resize(index + 1, 0);
+        }
+    }
+    pub fn insert(&mut self, key: K, val: V) {
+        self.reserve(key);
+        self[key] = val;
+    }
+
+    /// Reset every value to the default, in constant time (except once every
+    /// `u32::MAX` calls).
+    pub fn clear(&mut self) {
+        if self.generation == u32::MAX {
+            self.stamps.iter_mut().for_each(|s| *s = 0);
+            self.generation = 0;
+        }
+        self.generation += 1;
+    }
+
+    /// Remove every key, and free memory
+    pub fn free(&mut self) {
+        *self = Self::default();
+    }
+    /// Bytes allocated for the values and their stamps
+    pub fn memory_usage(&self) -> usize {
+        self.map.capacity() * mem::size_of::<V>() + self.stamps.capacity() * mem::size_of::<u32>()
+    }
+    pub fn iter(&self) -> impl iter::Iterator<Item = (K, &V)> {
+        (0..self.map.len()).map(|k| (K::from_index(k), &self[K::from_index(k)]))
+    }
+    /// Panic if the internal invariants are broken. This does nothing in release mode.
+    pub fn check_invariants(&self) {
+        if cfg!(debug_assertions) {
+            assert_eq!(
+                self.stamps.len(),
+                self.map.len(),
+                "IntMapClearable: missing stamps"
+            );
+            let generation = self.generation;
+            assert!(
+                self.stamps.iter().all(|&s| s <= generation),
+                "IntMapClearable: stamp from a future generation"
+            );
+        }
+    }
+}
+
+impl<K: AsIndex, V> ops::Index<K> for IntMapClearable<K, V> {
+    type Output = V;
+    #[inline]
+    fn index(&self, index: K) -> &Self::Output {
+        let i = index.as_index();
+        debug_check_index(i, self.map.len(), "IntMapClearable");
+        if self.stamps[i] == self.generation {
+            &self.map[i]
+        } else {
+            &self.pad
+        }
+    }
+}
+impl<K: AsIndex, V: Clone> ops::IndexMut<K> for IntMapClearable<K, V> {
+    #[inline]
+    fn index_mut(&mut self, index: K) -> &mut Self::Output {
+        let i = index.as_index();
+        debug_check_index(i, self.map.len(), "IntMapClearable");
+        if self.stamps[i] != self.generation {
+            self.stamps[i] = self.generation;
+            self.map[i] = self.pad.clone();
+        }
+        &mut self.map[i]
+    }
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct IntMapBool<K: AsIndex> {
     map: BitVec,
     _marker: PhantomData<fn(K)>, // contravariance
@@ -142,6 +337,7 @@ impl<K: AsIndex> ops::Index<K> for IntMapBool<K> {
     type Output = bool;
     #[inline]
     fn index(&self, index: K) -> &Self::Output {
+        debug_check_index(index.as_index(), self.map.len(), "IntMapBool");
         &self.map[index.as_index()]
     }
 }
@@ -150,15 +346,21 @@ impl<K: AsIndex> IntMapBool<K> {
     pub fn new() -> Self {
         Self {
             map: BitVec::new(),
-            _marker: PhantomData::default(),
+            _marker: PhantomData,
         }
     }
     #[inline]
     pub fn has(&self, k: K) -> bool {
         k.as_index() < self.map.len()
     }
+    /// The value of `k`, or `None` beyond the end of the map.
+    #[inline]
+    pub fn get(&self, k: K) -> Option<bool> {
+        self.map.get(k.as_index())
+    }
     #[inline]
     pub fn set(&mut self, k: K, b: bool) {
+        debug_check_index(k.as_index(), self.map.len(), "IntMapBool");
         self.map.set(k.as_index(), b);
     }
     pub fn reserve(&mut self, key: K) {
@@ -184,6 +386,7 @@ impl<K: AsIndex> IntMapBool<K> {
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct IntSet<K: AsIndex> {
     in_set: IntMapBool<K>,
     xs: Vec<K>,
@@ -204,6 +407,9 @@ impl<K: AsIndex> IntSet<K> {
     pub fn len(&self) -> usize {
         self.xs.len()
     }
+    pub fn is_empty(&self) -> bool {
+        self.xs.is_empty()
+    }
     pub fn clear(&mut self) {
         self.in_set.clear();
         self.xs.clear()
@@ -219,7 +425,40 @@ impl<K: AsIndex> IntSet<K> {
         }
     }
     pub fn has(&self, k: K) -> bool {
-        self.in_set.has(k) && self.in_set[k]
+        self.in_set.get(k).unwrap_or(false)
+    }
+    /// Remove `k`, and return whether it was in the set. The last element takes its
+    /// place, and finding it takes a time linear in its distance to the end.
+    pub fn remove(&mut self, k: K) -> bool {
+        if !self.has(k) {
+            return false;
+        }
+        self.in_set.set(k, false);
+        let i = self.xs.iter().rposition(|x| x.as_index() == k.as_index());
+        self.xs.swap_remove(i.expect("element of the set"));
+        true
+    }
+    /// Remove and return the last inserted element that remains.
+    pub fn pop(&mut self) -> Option<K> {
+        let k = self.xs.pop()?;
+        self.in_set.set(k, false);
+        Some(k)
+    }
+    /// Panic if the internal invariants are broken: the elements must be marked, and
+    /// be the only marked keys. This does nothing in release mode.
+    pub fn check_invariants(&self) {
+        if cfg!(debug_assertions) {
+            assert!(
+                self.xs.iter().all(|&k| self.has(k)),
+                "IntSet: unmarked element"
+            );
+            let marked = self.in_set.map.iter().filter(|&b| b).count();
+            assert_eq!(
+                marked,
+                self.xs.len(),
+                "IntSet: duplicate or marked non-element"
+            );
+        }
     }
 }
 impl<K: AsIndex> ops::Index<usize> for IntSet<K> {
@@ -235,3 +474,107 @@ impl<K: AsIndex> ops::Deref for IntSet<K> {
         &self.xs
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::clause::{VMap, Var};
+
+    #[test]
+    fn test_occupied() {
+        let v = |i| Var::from_idx(i);
+        let mut m: VMap<u32> = VMap::new();
+        assert_eq!(m.get(v(3)), None);
+        *m.get_or_insert_with(v(3), || 7) += 1;
+        m.insert(v(5), 2, 0);
+        m.reserve(v(8), 0);
+        assert_eq!(m.get(v(3)), Some(&8));
+        assert_eq!(m.get(v(1)), Some(&0)); // padding
+        assert_eq!(m.get(v(9)), None);
+        assert!(m.contains_key(v(5)) && !m.contains_key(v(1)) && !m.contains_key(v(8)));
+        assert_eq!(*m.get_or_insert_with(v(3), || 0), 8);
+        let occupied: Vec<(Var, u32)> = m.iter_occupied().map(|(k, &x)| (k, x)).collect();
+        assert_eq!(occupied, vec![(v(3), 8), (v(5), 2)]);
+        assert_eq!(m.iter().count(), 9);
+        let usage = m.memory_usage();
+        assert!(m.capacity() >= 9);
+        m.shrink_to(v(4));
+        assert!(m.has(v(3)) && !m.has(v(4)) && !m.contains_key(v(5)));
+        assert!(m.capacity() < 9 && m.memory_usage() < usage);
+        m.clear();
+        assert!(!m.contains_key(v(3)) && m.iter_occupied().next().is_none());
+    }
+
+    #[test]
+    fn test_int_set_remove() {
+        let v = |i| Var::from_idx(i);
+        let mut set: IntSet<Var> = IntSet::new();
+        for i in [4, 1, 7, 2] {
+            set.insert(v(i));
+        }
+        assert!(set.remove(v(1)) && !set.remove(v(1)) && !set.remove(v(9)));
+        assert_eq!(set.as_slice(), &[v(4), v(2), v(7)]);
+        assert!(!set.has(v(1)) && set.has(v(2)));
+        assert_eq!(set.pop(), Some(v(7)));
+        assert!(!set.has(v(7)));
+        set.insert(v(1));
+        assert_eq!(set.as_slice(), &[v(4), v(2), v(1)]);
+        while set.pop().is_some() {}
+        assert!(set.is_empty() && !set.has(v(4)));
+    }
+
+    #[test]
+    fn test_checked_access() {
+        let v = |i| Var::from_idx(i);
+        let mut b: IntMapBool<Var> = IntMapBool::new();
+        b.insert(v(2));
+        assert_eq!(
+            (b.get(v(2)), b.get(v(1)), b.get(v(3))),
+            (Some(true), Some(false), None)
+        );
+        let mut set: IntSet<Var> = IntSet::new();
+        set.insert(v(3));
+        set.insert(v(1));
+        set.check_invariants();
+        assert!(set.has(v(1)) && !set.has(v(2)) && !set.has(v(9)));
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    #[should_panic(expected = "IntSet: duplicate or marked non-element")]
+    fn test_int_set_invariants() {
+        let mut set: IntSet<Var> = IntSet::new();
+        set.insert(Var::from_idx(3));
+        set.in_set.set(Var::from_idx(0), true);
+        set.check_invariants();
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    #[should_panic(expected = "IntMap: key 4 out of bounds (2 keys)")]
+    fn test_debug_index() {
+        let mut m: VMap<u32> = VMap::new();
+        m.insert_default(Var::from_idx(1), 3);
+        m[Var::from_idx(4)] += 1;
+    }
+
+    #[test]
+    fn test_clearable() {
+        let v = |i| Var::from_idx(i);
+        let mut m: IntMapClearable<Var, u32> = IntMapClearable::new();
+        m.reserve(v(5));
+        m.insert(v(2), 3);
+        m[v(4)] += 1;
+        assert_eq!((m[v(2)], m[v(4)], m[v(5)]), (3, 1, 0));
+        m.clear();
+        assert!(m.iter().all(|(_, &x)| x == 0));
+        m[v(2)] += 2;
+        assert_eq!((m[v(2)], m[v(4)]), (2, 0));
+        // the stamps are reset when the generations wrap around
+        m.generation = u32::MAX;
+        m[v(4)] = 7;
+        m.clear();
+        assert_eq!((m.generation, m[v(2)], m[v(4)]), (1, 0, 0));
+        assert!(m.has(v(5)) && !m.has(v(6)));
+    }
+}
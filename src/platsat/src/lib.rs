@@ -18,6 +18,9 @@ OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWA
 **************************************************************************************************/
 #![forbid(unsafe_code)]
 #![no_std]
+// with the `std` feature, the prelude of `no_std_compat` brings the `panic!` of `std`
+// next to the one of `core`
+#![allow(ambiguous_panic_imports)]
 //! Platsat
 //!
 //! A SAT solver in rust, originally reimplement from Minisat 2.2 (which is in C++),
@@ -45,29 +48,106 @@ pub(crate) mod log {
 #[macro_use]
 pub(crate) extern crate log;
 
+//======== TRACING ============
+
+// stubs when tracing is not enabled
+#[cfg(not(feature = "tracing"))]
+#[macro_use]
+pub(crate) mod tracing_stubs {
+    macro_rules! span {
+        ($lvl:ident, $( $x:tt )*) => {
+            ()
+        };
+    }
+    macro_rules! event {
+        ($lvl:ident, $( $x:tt )*) => {};
+    }
+}
+
+// `span!(LEVEL, ..)` enters a span until the end of the scope it is bound in,
+// `event!(LEVEL, ..)` records an event
+#[cfg(feature = "tracing")]
+#[macro_use]
+pub(crate) mod tracing_macros {
+    macro_rules! span {
+        ($lvl:ident, $( $x:tt )*) => {
+            tracing::span!(tracing::Level::$lvl, $( $x )*).entered()
+        };
+    }
+    macro_rules! event {
+        ($lvl:ident, $( $x:tt )*) => {
+            tracing::event!(tracing::Level::$lvl, $( $x )*)
+        };
+    }
+}
+
 //======== PUBLIC INTERFACE ============
 
+pub mod aig;
+#[cfg(feature = "std")]
+pub mod aiger;
 pub mod alloc;
+mod branch;
 pub mod callbacks;
+mod card;
+pub mod check;
 pub mod clause;
 pub mod core;
 
 #[cfg(feature = "std")]
 pub mod dimacs;
 pub mod drat;
-mod heap;
+pub mod encodings;
+mod equiv;
+pub mod formula;
+#[cfg(feature = "std")]
+pub mod graph;
+pub mod heap;
 pub mod interface;
+mod interpolate;
 pub mod intmap;
+pub mod maxsat;
+mod native;
+#[cfg(feature = "std")]
+pub mod parallel;
+mod pb;
+pub mod propagator;
+pub mod qbf;
+mod reconstruct;
+mod simd;
+mod simp;
+#[cfg(test)]
+mod test_util;
 pub mod theory;
+mod walk;
+mod xor;
 
 pub use crate::{
-    callbacks::{Basic as BasicCallbacks, Callbacks, ProgressStatus, Stats as StatsCallbacks},
+    aig::{Aig, AigLit, AigNode},
+    callbacks::{
+        Basic as BasicCallbacks, Callbacks, ClauseListener, ProgressStatus, Stats as StatsCallbacks,
+    },
     clause::{display::Print, lbool, Kind as ClauseKind, LMap, LSet, Lit, VMap, Var},
-    core::{Solver, SolverOpts},
+    core::{
+        AssumptionOrder, BranchingHeuristic, ClauseGroup, ClauseHandle, InvalidOption, Models,
+        PropagationConflict, RestartStrategy, SetOptionError, Snapshot, Solver, SolverOpts,
+        SolverOptsBuilder, Stats, TheoryStats, TrailView,
+    },
+    encodings::{CardEncoding, Cardinality, PseudoBoolean},
+    formula::{Encoder, Formula},
     interface::SolverInterface,
-    theory::{EmptyTheory, Theory, TheoryArg},
+    maxsat::{MaxSatSolver, Optimizer},
+    propagator::{ExternalPropagator, UserPropagator},
+    qbf::{Qbf, Quantifier},
+    theory::{EmptyTheory, Theory, TheoryArg, TheoryCombiner},
 };
 
+#[cfg(feature = "std")]
+pub use crate::parallel::ParallelSolver;
+
+#[cfg(feature = "logging")]
+pub use crate::callbacks::Logging as LoggingCallbacks;
+
 /// Basic solver, with basic callbacks and no theory.
 ///
 /// Use this if you just want a normal SAT solver.
@@ -0,0 +1,522 @@
+//! Weighted partial MaxSAT, on top of the incremental interface
+use crate::{
+    callbacks::Callbacks,
+    clause::{lbool, LMap, Lit},
+    core::Solver,
+    interface::SolverInterface,
+};
+use no_std_compat::prelude::v1::*;
+
+const NONE: u32 = u32::MAX;
+
+/// Is the clause `lits` false in `model`?
+fn is_false(lits: &[Lit], model: &[lbool]) -> bool {
+    lits.iter()
+        .all(|&l| model[l.var().idx() as usize] ^ !l.sign() == lbool::FALSE)
+}
+
+/// Push the term `coef * lit` into `terms`, as several terms if `coef` does not fit in `i64`.
+fn push_terms(terms: &mut Vec<(i64, Lit)>, mut coef: i128, lit: Lit) {
+    while coef != 0 {
+        let c = coef.clamp(i64::MIN as i128 + 1, i64::MAX as i128);
+        terms.push((c as i64, lit));
+        coef -= c;
+    }
+}
+
+/// A soft clause, which costs `weight` when it is false.
+#[derive(Debug, Clone)]
+struct Soft {
+    lits: Vec<Lit>,
+    weight: u64,
+    /// Literal that is true if the clause may be false, created by `solve`.
+    relax: Option<Lit>,
+}
+
+/// Weighted partial MaxSAT solver: find a model of the hard clauses that minimizes
+/// the total weight of the false soft clauses.
+///
+/// Each soft clause `C` is relaxed into the hard clause `C ∨ r` for a fresh literal `r`
+/// (or `r = ¬l` if `C` is the unit clause `l`). The optimum is then found by linear
+/// SAT-UNSAT search: every time a model of cost `c` is found, the next call to the
+/// underlying solver assumes that the weights of the true relaxation literals add up to
+/// less than `c`, until it becomes unsatisfiable. The bounds are only enabled by
+/// assumptions, so clauses can still be added between calls to [`solve`](Self::solve).
+///
+/// See [`Optimizer`] for a core-guided algorithm, which is usually faster when the
+/// optimum is small compared to the total weight.
+pub struct MaxSatSolver<Cb: Callbacks> {
+    solver: Solver<Cb>,
+    softs: Vec<Soft>,
+    /// Best model found by the last call to `solve`, with its cost.
+    best: Option<(Vec<lbool>, u128)>,
+}
+
+impl<Cb: Callbacks> MaxSatSolver<Cb> {
+    /// Create a MaxSAT solver whose hard clauses are those of `solver`.
+    pub fn new(solver: Solver<Cb>) -> Self {
+        MaxSatSolver {
+            solver,
+            softs: vec![],
+            best: None,
+        }
+    }
+
+    /// The underlying solver, which contains the hard clauses.
+    pub fn solver(&self) -> &Solver<Cb> {
+        &self.solver
+    }
+
+    /// Mutable access to the underlying solver, for instance to create variables.
+    pub fn solver_mut(&mut self) -> &mut Solver<Cb> {
+        &mut self.solver
+    }
+
+    /// Get back the underlying solver.
+    pub fn into_solver(self) -> Solver<Cb> {
+        self.solver
+    }
+
+    /// Add a clause that must be satisfied. Returns `false` if the hard clauses are unsatisfiable.
+    pub fn add_hard_clause(&mut self, lits: &[Lit]) -> bool {
+        self.best = None;
+        self.solver.add_clause_reuse(&mut lits.to_vec())
+    }
+
+    /// Add a clause that costs `weight` if it is not satisfied.
+    pub fn add_soft_clause(&mut self, lits: &[Lit], weight: u64) {
+        self.best = None;
+        if weight == 0 {
+            return;
+        }
+        self.softs.push(Soft {
+            lits: lits.to_vec(),
+            weight,
+            relax: None,
+        });
+    }
+
+    /// Relax the soft clauses added since the last call to `solve`.
+    ///
+    /// This is done lazily, so that the relaxation variables are created after the
+    /// variables of a problem that is being read with [`var_of_int`](SolverInterface::var_of_int).
+    fn relax(&mut self) {
+        for soft in &mut self.softs {
+            if soft.relax.is_some() {
+                continue;
+            }
+            let relax = if soft.lits.len() == 1 {
+                !soft.lits[0]
+            } else {
+                let relax = Lit::new(self.solver.new_var_default(), true);
+                let mut c = soft.lits.clone();
+                c.push(relax);
+                self.solver.add_clause_reuse(&mut c);
+                relax
+            };
+            soft.relax = Some(relax);
+        }
+    }
+
+    /// Number of soft clauses.
+    pub fn num_soft_clauses(&self) -> usize {
+        self.softs.len()
+    }
+
+    /// The soft clauses, with their weights.
+    pub fn soft_clauses(&self) -> impl Iterator<Item = (&[Lit], u64)> {
+        self.softs.iter().map(|s| (&s.lits[..], s.weight))
+    }
+
+    /// Total weight of the soft clauses that are false in `model`.
+    pub fn cost_of(&self, model: &[lbool]) -> u128 {
+        self.softs
+            .iter()
+            .filter(|s| is_false(&s.lits, model))
+            .map(|s| s.weight as u128)
+            .sum()
+    }
+
+    /// Find a model of the hard clauses of minimal cost.
+    ///
+    /// Returns `lbool::TRUE` if the optimum was found, `lbool::FALSE` if the hard clauses
+    /// are unsatisfiable, and `lbool::UNDEF` if the search was interrupted by the callbacks,
+    /// in which case [`model`](Self::model) may still hold the best model found so far.
+    pub fn solve(&mut self) -> lbool {
+        self.solve_with(|_, _| ())
+    }
+
+    /// Like [`solve`](Self::solve), but call `on_model(model, cost)` on each improving model.
+    pub fn solve_with<F: FnMut(&[lbool], u128)>(&mut self, mut on_model: F) -> lbool {
+        self.best = None;
+        self.relax();
+        let total: u128 = self.softs.iter().map(|s| s.weight as u128).sum();
+        let mut acts = vec![];
+        let mut ret = self.solver.solve_limited(&[]);
+        while ret == lbool::TRUE {
+            let model = self.solver.get_model().to_vec();
+            let cost = self.cost_of(&model);
+            on_model(&model, cost);
+            self.best = Some((model, cost));
+            if cost == 0 {
+                break;
+            }
+            // `act => sum(weight * relax) < cost`, that is
+            // `sum(weight * !relax) - (total - cost + 1) * act >= 0`, where the
+            // coefficients that do not fit in `i64` are split over several terms
+            let act = Lit::new(self.solver.new_var(lbool::UNDEF, false), true);
+            let mut terms: Vec<(i64, Lit)> = vec![];
+            for s in &self.softs {
+                push_terms(&mut terms, s.weight as i128, !s.relax.unwrap());
+            }
+            push_terms(&mut terms, -((total - cost + 1) as i128), act);
+            self.solver.add_pb_constraint(&terms, 0);
+            acts.push(act);
+            ret = self.solver.solve_limited(&[act]);
+            if ret == lbool::FALSE {
+                ret = lbool::TRUE;
+                break;
+            }
+        }
+        // disable the bounds for good
+        for act in acts {
+            self.solver.add_clause_reuse(&mut vec![!act]);
+        }
+        ret
+    }
+
+    /// Best model found by the last call to [`solve`](Self::solve), or an empty slice.
+    pub fn model(&self) -> &[lbool] {
+        self.best.as_ref().map_or(&[], |b| &b.0)
+    }
+
+    /// Cost of [`model`](Self::model), if there is one.
+    pub fn cost(&self) -> Option<u128> {
+        self.best.as_ref().map(|b| b.1)
+    }
+}
+
+/// Core-guided MaxSAT optimizer, using the OLL algorithm (as in RC2).
+///
+/// Each soft clause `C` is represented by an assumption `a` with the hard clause `¬a ∨ C`
+/// (or by `l` itself if `C` is the unit clause `l`), and the solver is called under these
+/// assumptions. An unsat core is a set of soft clauses of which at least one is false:
+/// the lower bound on the cost grows by their minimal weight `w`, which is subtracted
+/// from their weights, and they are replaced by the constraint "at most one of them is
+/// false" with weight `w`. This constraint is an output of a totalizer (a CNF encoding
+/// of the number of false soft clauses), and when it occurs in a later core, it is relaxed
+/// into "at most two of them are false", and so on. The first model found is optimal.
+///
+/// All the clauses added to the solver only constrain fresh variables, so it can be
+/// used again afterwards.
+pub struct Optimizer<Cb: Callbacks> {
+    solver: Solver<Cb>,
+    /// Assumptions with a positive weight.
+    assumps: Vec<Lit>,
+    weights: LMap<u128>,
+    /// For the assumptions of the form `!totalizers[i][j]`, `(i, j)`.
+    bounds: LMap<(u32, u32)>,
+    /// Outputs of the totalizers: `outputs[j]` is true if at least `j + 1`
+    /// of the inputs are true.
+    totalizers: Vec<Vec<Lit>>,
+    /// Optimal model found by the last call to `maximize`, with its cost.
+    best: Option<(Vec<lbool>, u128)>,
+}
+
+impl<Cb: Callbacks> Optimizer<Cb> {
+    /// Create an optimizer whose hard clauses are those of `solver`.
+    pub fn new(solver: Solver<Cb>) -> Self {
+        Optimizer {
+            solver,
+            assumps: vec![],
+            weights: LMap::new(),
+            bounds: LMap::new(),
+            totalizers: vec![],
+            best: None,
+        }
+    }
+
+    /// The underlying solver, which contains the hard clauses.
+    pub fn solver(&self) -> &Solver<Cb> {
+        &self.solver
+    }
+
+    /// Mutable access to the underlying solver, for instance to add hard clauses.
+    pub fn solver_mut(&mut self) -> &mut Solver<Cb> {
+        &mut self.solver
+    }
+
+    /// Get back the underlying solver.
+    pub fn into_solver(self) -> Solver<Cb> {
+        self.solver
+    }
+
+    fn new_lit(&mut self) -> Lit {
+        Lit::new(self.solver.new_var(lbool::UNDEF, false), true)
+    }
+
+    fn add_assump(&mut self, lit: Lit, weight: u128) {
+        self.weights.reserve(lit, 0);
+        if self.weights[lit] == 0 {
+            self.assumps.push(lit);
+        }
+        self.weights[lit] += weight;
+    }
+
+    /// Outputs of a totalizer over `inputs`: `outputs[j]` is implied by
+    /// `j + 1` of the inputs being true.
+    fn totalizer(&mut self, inputs: &[Lit]) -> Vec<Lit> {
+        if inputs.len() == 1 {
+            return inputs.to_vec();
+        }
+        let (left, right) = inputs.split_at(inputs.len() / 2);
+        let left = self.totalizer(left);
+        let right = self.totalizer(right);
+        let outputs: Vec<Lit> = (0..inputs.len()).map(|_| self.new_lit()).collect();
+        // `left[i - 1] ∧ right[j - 1] => outputs[i + j - 1]`, where `left[-1]` is true
+        for i in 0..=left.len() {
+            for j in 0..=right.len() {
+                if i + j == 0 {
+                    continue;
+                }
+                let mut c = vec![outputs[i + j - 1]];
+                c.extend(i.checked_sub(1).map(|i| !left[i]));
+                c.extend(j.checked_sub(1).map(|j| !right[j]));
+                self.solver.add_clause_reuse(&mut c);
+            }
+        }
+        outputs
+    }
+
+    /// Find a model of the hard clauses that maximizes the total weight of the satisfied
+    /// soft clauses, given as pairs `(clause, weight)`.
+    ///
+    /// Returns `lbool::TRUE` if the optimum was found, `lbool::FALSE` if the hard clauses
+    /// are unsatisfiable, and `lbool::UNDEF` if the search was interrupted by the callbacks.
+    pub fn maximize<C: AsRef<[Lit]>>(&mut self, soft_clauses: &[(C, u64)]) -> lbool {
+        self.best = None;
+        self.assumps.clear();
+        self.weights.clear();
+        self.bounds.clear();
+        self.totalizers.clear();
+        let mut lower_bound: u128 = 0;
+        for (c, weight) in soft_clauses {
+            let (c, weight) = (c.as_ref(), *weight as u128);
+            if weight == 0 {
+                continue;
+            }
+            match c {
+                [] => lower_bound += weight,
+                [lit] => self.add_assump(*lit, weight),
+                _ => {
+                    let a = self.new_lit();
+                    let mut c = c.to_vec();
+                    c.push(!a);
+                    self.solver.add_clause_reuse(&mut c);
+                    self.add_assump(a, weight);
+                }
+            }
+        }
+        loop {
+            let weights = &self.weights;
+            self.assumps.retain(|&a| weights[a] > 0);
+            let ret = self.solver.solve_limited(&self.assumps);
+            if ret == lbool::TRUE {
+                let model = self.solver.get_model().to_vec();
+                let cost = soft_clauses
+                    .iter()
+                    .filter(|(c, _)| is_false(c.as_ref(), &model))
+                    .map(|s| s.1 as u128)
+                    .sum();
+                debug_assert_eq!(cost, lower_bound);
+                self.best = Some((model, cost));
+                return ret;
+            } else if ret == lbool::UNDEF {
+                return ret;
+            }
+            let core = self.solver.unsat_core().to_vec();
+            if core.is_empty() {
+                return lbool::FALSE;
+            }
+            // one of the soft constraints of the core is false
+            let weight = core.iter().map(|&a| self.weights[a]).min().unwrap();
+            lower_bound += weight;
+            for &a in &core {
+                self.weights[a] -= weight;
+                // `!outputs[j]` becomes `!outputs[j + 1]`
+                if self.bounds.get(a).is_some_and(|b| b.0 != NONE) {
+                    let (i, j) = self.bounds[a];
+                    if let Some(&o) = self.totalizers[i as usize].get(j as usize + 1) {
+                        self.bounds.insert(!o, (i, j + 1), (NONE, 0));
+                        self.add_assump(!o, weight);
+                    }
+                }
+            }
+            if core.len() > 1 {
+                // at most one of them is false
+                let inputs: Vec<Lit> = core.iter().map(|&a| !a).collect();
+                let outputs = self.totalizer(&inputs);
+                let i = self.totalizers.len() as u32;
+                self.bounds.insert(!outputs[1], (i, 1), (NONE, 0));
+                self.add_assump(!outputs[1], weight);
+                self.totalizers.push(outputs);
+            }
+        }
+    }
+
+    /// Optimal model found by the last call to [`maximize`](Self::maximize), or an empty slice.
+    pub fn model(&self) -> &[lbool] {
+        self.best.as_ref().map_or(&[], |b| &b.0)
+    }
+
+    /// Cost of [`model`](Self::model), that is the total weight of its false soft clauses.
+    pub fn cost(&self) -> Option<u128> {
+        self.best.as_ref().map(|b| b.1)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{test_util::Rng, *};
+    use no_std_compat::prelude::v1::*;
+
+    fn eval(lits: &[Lit], a: u32) -> bool {
+        lits.iter()
+            .any(|l| ((a >> l.var().idx()) & 1 == 1) == l.sign())
+    }
+
+    #[test]
+    fn test_maxsat() {
+        let mut solver = MaxSatSolver::new(BasicSolver::default());
+        let x: Vec<Lit> = (0..3)
+            .map(|_| Lit::new(solver.solver_mut().new_var_default(), true))
+            .collect();
+        solver.add_hard_clause(&[!x[0], !x[1]]);
+        solver.add_soft_clause(&[x[0]], 3);
+        solver.add_soft_clause(&[x[1]], 2);
+        solver.add_soft_clause(&[x[1], x[2]], 4);
+        assert_eq!(solver.solve(), lbool::TRUE);
+        assert_eq!(solver.cost(), Some(2));
+        assert_eq!(solver.model()[x[2].var().idx() as usize], lbool::TRUE);
+        // the solver can be used again
+        solver.add_hard_clause(&[!x[2]]);
+        assert_eq!(solver.solve(), lbool::TRUE);
+        assert_eq!(solver.cost(), Some(3));
+        solver.add_hard_clause(&[x[0]]);
+        solver.add_hard_clause(&[x[1]]);
+        assert_eq!(solver.solve(), lbool::FALSE);
+        assert_eq!(solver.cost(), None);
+    }
+
+    #[test]
+    fn test_maxsat_random() {
+        const N: u32 = 8;
+        let mut rng = Rng::new(7);
+        for _ in 0..100 {
+            let mut solver = MaxSatSolver::new(BasicSolver::default());
+            let vars: Vec<Var> = (0..N)
+                .map(|_| solver.solver_mut().new_var_default())
+                .collect();
+            let clause = |rng: &mut Rng| -> Vec<Lit> {
+                (0..rng.next(3) + 1)
+                    .map(|_| Lit::new(vars[rng.next(N as u64) as usize], rng.next(2) == 0))
+                    .collect()
+            };
+            let mut hards = vec![];
+            let mut softs = vec![];
+            for _ in 0..rng.next(10) {
+                let c = clause(&mut rng);
+                solver.add_hard_clause(&c);
+                hards.push(c);
+            }
+            for _ in 0..rng.next(10) + 1 {
+                let (c, w) = (clause(&mut rng), rng.next(10));
+                solver.add_soft_clause(&c, w);
+                softs.push((c, w));
+            }
+            let cost = |a: u32| -> u128 {
+                softs
+                    .iter()
+                    .filter(|s| !eval(&s.0, a))
+                    .map(|s| s.1 as u128)
+                    .sum()
+            };
+            let optimum = (0..1u32 << N)
+                .filter(|&a| hards.iter().all(|c| eval(c, a)))
+                .map(cost)
+                .min();
+            // same problem, with the core-guided optimizer
+            let mut opt = Optimizer::new(BasicSolver::default());
+            for _ in 0..N {
+                opt.solver_mut().new_var_default();
+            }
+            for c in &hards {
+                opt.solver_mut().add_clause_reuse(&mut c.clone());
+            }
+            let results = [
+                (solver.solve(), solver.cost(), solver.model().to_vec()),
+                (opt.maximize(&softs), opt.cost(), opt.model().to_vec()),
+            ];
+            for (res, res_cost, model) in results {
+                match optimum {
+                    None => assert_eq!(res, lbool::FALSE),
+                    Some(optimum) => {
+                        assert_eq!(res, lbool::TRUE);
+                        assert_eq!(res_cost, Some(optimum));
+                        let a = vars
+                            .iter()
+                            .filter(|v| model[v.idx() as usize] == lbool::TRUE)
+                            .fold(0, |a, v| a | 1 << v.idx());
+                        assert!(hards.iter().all(|c| eval(c, a)));
+                        assert_eq!(cost(a), optimum);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_maxsat_large_weights() {
+        let mut solver = MaxSatSolver::new(BasicSolver::default());
+        let x: Vec<Lit> = (0..3)
+            .map(|_| Lit::new(solver.solver_mut().new_var_default(), true))
+            .collect();
+        // the total weight does not fit in `u64`
+        solver.add_hard_clause(&[!x[0], !x[1]]);
+        solver.add_hard_clause(&[!x[1], !x[2]]);
+        let softs = [(x[0], u64::MAX), (x[1], u64::MAX), (x[2], u64::MAX - 1)];
+        for (lit, w) in softs {
+            solver.add_soft_clause(&[lit], w);
+        }
+        assert_eq!(solver.solve(), lbool::TRUE);
+        assert_eq!(solver.cost(), Some(u64::MAX as u128));
+        assert_eq!(solver.model()[x[1].var().idx() as usize], lbool::FALSE);
+        let mut opt = Optimizer::new(solver.into_solver());
+        let softs = softs.map(|(lit, w)| ([lit], w));
+        assert_eq!(opt.maximize(&softs), lbool::TRUE);
+        assert_eq!(opt.cost(), Some(u64::MAX as u128));
+    }
+
+    #[test]
+    fn test_optimizer() {
+        let mut solver = BasicSolver::default();
+        let x: Vec<Lit> = (0..4)
+            .map(|_| Lit::new(solver.new_var_default(), true))
+            .collect();
+        // at most one of them
+        for i in 0..4 {
+            for j in 0..i {
+                solver.add_clause_reuse(&mut vec![!x[i], !x[j]]);
+            }
+        }
+        let mut opt = Optimizer::new(solver);
+        let softs: Vec<(Vec<Lit>, u64)> = (0..4).map(|i| (vec![x[i]], i as u64 + 1)).collect();
+        assert_eq!(opt.maximize(&softs), lbool::TRUE);
+        assert_eq!(opt.cost(), Some(6));
+        assert_eq!(opt.model()[x[3].var().idx() as usize], lbool::TRUE);
+        // the solver is still usable, and equivalent to the original one
+        let mut solver = opt.into_solver();
+        assert_eq!(solver.solve_limited(&[x[0]]), lbool::TRUE);
+        assert_eq!(solver.solve_limited(&[x[0], x[1]]), lbool::FALSE);
+    }
+}
@@ -0,0 +1,116 @@
+//! Constraints that are propagated natively by the solver, besides clauses
+use crate::{
+    card::CardEngine,
+    clause::{Lit, VMap, Var},
+    core::TheoryArg,
+    pb::PbEngine,
+    xor::XorEngine,
+};
+use no_std_compat::prelude::v1::*;
+
+const NONE: u32 = u32::MAX;
+
+/// Explanations of the literals propagated by the native constraints.
+///
+/// These literals are on the trail with the reason `CRef::SPECIAL`, like theory
+/// propagations, and their explanation is computed eagerly.
+#[derive(Debug, Default, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub(crate) struct Reasons {
+    /// Propagated literals in trail order, with the start of their
+    /// explanation in `expl` and their index in the trail.
+    props: Vec<(Lit, u32, u32)>,
+    expl: Vec<Lit>,
+    /// Index in `props` of the propagation of each variable, or `NONE`.
+    reason: VMap<u32>,
+}
+
+impl Reasons {
+    /// Propagate `p`, which is implied by the negation of `tail` (all false).
+    ///
+    /// Returns `false` if this results in an immediate conflict.
+    pub(crate) fn propagate(&mut self, acts: &mut TheoryArg, p: Lit, tail: &[Lit]) -> bool {
+        self.reason.reserve(p.var(), NONE);
+        self.reason[p.var()] = self.props.len() as u32;
+        let trail_idx = acts.model().len() as u32;
+        self.props.push((p, self.expl.len() as u32, trail_idx));
+        self.expl.push(p);
+        self.expl.extend_from_slice(tail);
+        acts.propagate_now(p)
+    }
+
+    /// Explanation of `p` if it was propagated by a native constraint: a clause
+    /// whose first literal is `p` and whose other literals are false.
+    pub(crate) fn explain(&self, p: Lit) -> Option<&[Lit]> {
+        let i = match self.reason.get(p.var()) {
+            Some(&i) if i != NONE => i as usize,
+            _ => return None,
+        };
+        let start = self.props[i].1 as usize;
+        let end = self
+            .props
+            .get(i + 1)
+            .map_or(self.expl.len(), |e| e.1 as usize);
+        debug_assert_eq!(self.expl[start], p);
+        Some(&self.expl[start..end])
+    }
+
+    fn backtrack(&mut self, trail_len: usize) {
+        while let Some(&(p, start, trail_idx)) = self.props.last() {
+            if (trail_idx as usize) < trail_len {
+                break;
+            }
+            self.reason[p.var()] = NONE;
+            self.expl.truncate(start as usize);
+            self.props.pop();
+        }
+    }
+}
+
+/// All the native constraints of a solver.
+#[derive(Debug, Default, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub(crate) struct NativeConstraints {
+    pub(crate) xor: XorEngine,
+    pub(crate) card: CardEngine,
+    pub(crate) pb: PbEngine,
+    reasons: Reasons,
+}
+
+impl NativeConstraints {
+    /// Propagate the native constraints under the current (partial) model,
+    /// or raise a conflict.
+    pub(crate) fn check(&mut self, acts: &mut TheoryArg) {
+        self.card.check(acts, &mut self.reasons);
+        if acts.is_ok() {
+            self.pb.check(acts, &mut self.reasons);
+        }
+        if acts.is_ok() {
+            self.xor.check(acts, &mut self.reasons);
+        }
+    }
+
+    /// Does `v` occur in a native constraint?
+    pub(crate) fn contains_var(&self, v: Var) -> bool {
+        self.xor.contains_var(v) || self.card.contains_var(v) || self.pb.contains_var(v)
+    }
+
+    /// Are there no native constraints?
+    pub(crate) fn is_empty(&self) -> bool {
+        self.xor.is_empty() && self.card.is_empty() && self.pb.is_empty()
+    }
+
+    /// See [`Reasons::explain`].
+    pub(crate) fn explain(&self, p: Lit) -> Option<&[Lit]> {
+        self.reasons.explain(p)
+    }
+
+    /// Forget about the assignments of `trail[trail_len..]`, which are being
+    /// removed from the trail.
+    pub(crate) fn backtrack(&mut self, trail: &[Lit], trail_len: usize) {
+        self.reasons.backtrack(trail_len);
+        self.card.backtrack(trail, trail_len);
+        self.pb.backtrack(trail, trail_len);
+        self.xor.backtrack(trail_len);
+    }
+}
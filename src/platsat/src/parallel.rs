@@ -0,0 +1,445 @@
+//! Portfolio of solvers running in parallel and sharing their short learnt clauses
+use crate::{
+    callbacks::Basic,
+    clause::{lbool, Lit, Var},
+    core::{BranchingHeuristic, RestartStrategy, Solver, SolverOpts},
+    interface::SolverInterface,
+    intmap::AsIndex,
+};
+use no_std_compat::prelude::v1::*;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+
+/// Number of conflicts between two exchanges of clauses.
+const ROUND_CONFLICTS: u64 = 1000;
+
+/// Number of literals a worker can publish before overwriting the oldest ones.
+const BUFFER_CAPACITY: usize = 1 << 16;
+
+/// Clauses published by a worker, in a ring buffer that is shared without locks.
+///
+/// Only the worker appends to it, and the others read it from where they stopped the
+/// last time. Each slot holds a literal, or `Lit::UNDEF` at the end of a clause, along
+/// with the low 32 bits of its position, so that readers notice the slots that were
+/// overwritten in the meantime: the clauses they belong to are skipped, which only
+/// means that fewer clauses are shared.
+struct Buffer {
+    slots: Vec<AtomicU64>,
+    /// Number of literals published so far, the slots before it are complete clauses.
+    len: AtomicUsize,
+}
+
+impl Buffer {
+    fn new() -> Self {
+        Buffer {
+            slots: (0..BUFFER_CAPACITY)
+                .map(|_| AtomicU64::new(u64::MAX))
+                .collect(),
+            len: AtomicUsize::new(0),
+        }
+    }
+
+    /// Publish the clause `c`, only called by the owner of the buffer.
+    fn publish(&self, c: &[Lit]) {
+        let mut pos = self.len.load(Ordering::Relaxed);
+        for &lit in c.iter().chain([Lit::UNDEF].iter()) {
+            let slot = (pos as u32 as u64) << 32 | lit.as_index() as u64;
+            self.slots[pos % BUFFER_CAPACITY].store(slot, Ordering::Relaxed);
+            pos += 1;
+        }
+        self.len.store(pos, Ordering::Release);
+    }
+
+    /// Call `f` on the clauses published since position `*read`, which is moved past them.
+    fn read(&self, read: &mut usize, c: &mut Vec<Lit>, mut f: impl FnMut(&mut Vec<Lit>)) {
+        let len = self.len.load(Ordering::Acquire);
+        // skip the clause in progress, if its start was lost
+        let mut skip = false;
+        if len - *read > BUFFER_CAPACITY {
+            *read = len - BUFFER_CAPACITY;
+            skip = true;
+        }
+        c.clear();
+        for pos in *read..len {
+            let slot = self.slots[pos % BUFFER_CAPACITY].load(Ordering::Relaxed);
+            if (slot >> 32) as u32 != pos as u32 {
+                skip = true;
+                continue;
+            }
+            let lit = Lit::from_index(slot as u32 as usize);
+            if lit != Lit::UNDEF {
+                c.push(lit);
+                continue;
+            }
+            if !skip {
+                f(c);
+            }
+            skip = false;
+            c.clear();
+        }
+        *read = len;
+    }
+}
+
+/// Outcome of a call to [`ParallelSolver::solve`], set by the first worker that finishes.
+struct Answer {
+    res: lbool,
+    model: Vec<lbool>,
+    core: Vec<Lit>,
+}
+
+/// Requests sent to the workers.
+enum Command {
+    AddClause(Vec<Lit>),
+    SetMaxSharedLen(usize),
+    SetStopFlag(Arc<AtomicBool>),
+    Solve { num_vars: u32, assumps: Arc<[Lit]> },
+}
+
+/// A thread running a solver, until the [`ParallelSolver`] is dropped.
+struct Worker {
+    commands: Sender<Command>,
+    handle: JoinHandle<()>,
+}
+
+/// Portfolio SAT solver, running differently configured solvers on several threads.
+///
+/// Every worker solves the whole problem; the first answer is returned, and the other
+/// workers are then interrupted. Workers share the clauses they learn that have at most
+/// [`max_shared_len`](Self::set_max_shared_len) literals: they publish them as soon as
+/// they are learnt, and import those of the others every [`ROUND_CONFLICTS`] conflicts.
+///
+/// Each worker owns its solver for the lifetime of the portfolio, so what it learnt
+/// is kept from one call to [`solve`](Self::solve) to the next.
+pub struct ParallelSolver {
+    workers: Vec<Worker>,
+    answers: Receiver<Option<Answer>>,
+    /// Set once a worker found the answer, to interrupt the others.
+    done: Arc<AtomicBool>,
+    num_vars: u32,
+    answer: Option<Answer>,
+}
+
+impl ParallelSolver {
+    /// Create a portfolio of `num_threads` solvers with different options.
+    ///
+    /// The first solver uses the default options.
+    pub fn new(num_threads: usize) -> Self {
+        assert!(num_threads > 0, "at least one thread is needed");
+        let opts = (0..num_threads)
+            .map(|i| {
+                let mut opts = SolverOpts::default();
+                if i > 0 {
+                    opts.seed = opts.seed.wrapping_add(i as u64);
+                    opts.rnd_init_act = true;
+                    opts.restart_strategy = [
+                        RestartStrategy::Glucose,
+                        RestartStrategy::Luby,
+                        RestartStrategy::Geometric,
+                    ][i % 3];
+                    opts.branching = [
+                        BranchingHeuristic::Vsids,
+                        BranchingHeuristic::Lrb,
+                        BranchingHeuristic::Vmtf,
+                        BranchingHeuristic::Chb,
+                    ][(i / 2) % 4];
+                    opts.random_pol_freq = if i % 3 == 2 { 1.0 } else { 0.0 };
+                    opts.local_search = i % 2 == 1;
+                    opts.var_decay = [0.95, 0.9, 0.85, 0.99][i % 4];
+                }
+                opts
+            })
+            .collect();
+        Self::with_options(opts)
+    }
+
+    /// Create a portfolio with one solver, hence one thread, for each element of `opts`.
+    pub fn with_options(opts: Vec<SolverOpts>) -> Self {
+        assert!(!opts.is_empty(), "at least one thread is needed");
+        for o in &opts {
+            o.validate().expect("invalid options");
+        }
+        let exchange: Arc<[Buffer]> = opts.iter().map(|_| Buffer::new()).collect();
+        let done = Arc::new(AtomicBool::new(false));
+        let (answer_tx, answers) = channel();
+        let workers = opts
+            .into_iter()
+            .enumerate()
+            .map(|(i, opts)| {
+                let (commands, rx) = channel();
+                let (exchange, done, answers) = (exchange.clone(), done.clone(), answer_tx.clone());
+                let handle = thread::spawn(move || worker(i, opts, exchange, done, rx, answers));
+                Worker { commands, handle }
+            })
+            .collect();
+        ParallelSolver {
+            workers,
+            answers,
+            done,
+            num_vars: 0,
+            answer: None,
+        }
+    }
+
+    /// Number of threads used by [`solve`](Self::solve).
+    pub fn num_threads(&self) -> usize {
+        self.workers.len()
+    }
+
+    fn send(&self, cmd: impl Fn() -> Command) {
+        for w in &self.workers {
+            w.commands.send(cmd()).expect("a worker panicked");
+        }
+    }
+
+    /// Only share the learnt clauses that have at most `len` literals (8 by default).
+    pub fn set_max_shared_len(&mut self, len: usize) {
+        self.send(|| Command::SetMaxSharedLen(len));
+    }
+
+    /// Interrupt the search whenever `flag` is set, see [`Solver::set_stop_flag`].
+    pub fn set_stop_flag(&mut self, flag: Arc<AtomicBool>) {
+        self.send(|| Command::SetStopFlag(flag.clone()));
+    }
+
+    /// Create a new variable.
+    pub fn new_var(&mut self) -> Var {
+        self.num_vars += 1;
+        Var::from_idx(self.num_vars - 1)
+    }
+
+    /// Number of variables.
+    pub fn num_vars(&self) -> u32 {
+        self.num_vars
+    }
+
+    /// Add a clause over variables created by [`new_var`](Self::new_var).
+    pub fn add_clause(&mut self, lits: &[Lit]) {
+        assert!(lits.iter().all(|l| l.var().idx() < self.num_vars));
+        self.send(|| Command::AddClause(lits.to_vec()));
+    }
+
+    /// Solve under the assumptions `assumps`.
+    ///
+    /// Returns `UNDEF` if the search was interrupted by the [stop flag](Self::set_stop_flag).
+    pub fn solve(&mut self, assumps: &[Lit]) -> lbool {
+        self.answer = None;
+        // all the workers are idle
+        self.done.store(false, Ordering::SeqCst);
+        let assumps: Arc<[Lit]> = assumps.into();
+        let num_vars = self.num_vars;
+        self.send(|| Command::Solve {
+            num_vars,
+            assumps: assumps.clone(),
+        });
+        for _ in 0..self.workers.len() {
+            if let Some(answer) = self.answers.recv().expect("a worker panicked") {
+                self.answer = Some(answer);
+            }
+        }
+        self.answer.as_ref().map_or(lbool::UNDEF, |a| a.res)
+    }
+
+    /// Model found by the last call to [`solve`](Self::solve), if it returned `TRUE`.
+    pub fn get_model(&self) -> &[lbool] {
+        match &self.answer {
+            Some(a) => &a.model,
+            None => &[],
+        }
+    }
+
+    /// Value of `lit` in the [model](Self::get_model), `UNDEF` if there is none.
+    pub fn value_lit(&self, lit: Lit) -> lbool {
+        let model = self.get_model();
+        model
+            .get(lit.var().idx() as usize)
+            .map_or(lbool::UNDEF, |&v| v ^ !lit.sign())
+    }
+
+    /// Subset of the assumptions that is unsatisfiable, if the last call to
+    /// [`solve`](Self::solve) returned `FALSE`.
+    pub fn unsat_core(&self) -> &[Lit] {
+        match &self.answer {
+            Some(a) => &a.core,
+            None => &[],
+        }
+    }
+}
+
+impl Drop for ParallelSolver {
+    fn drop(&mut self) {
+        for Worker { commands, handle } in self.workers.drain(..) {
+            // the worker stops once its commands are disconnected
+            drop(commands);
+            let _ = handle.join();
+        }
+    }
+}
+
+/// Run the commands for the `i`-th solver, until the portfolio is dropped.
+fn worker(
+    i: usize,
+    opts: SolverOpts,
+    exchange: Arc<[Buffer]>,
+    done: Arc<AtomicBool>,
+    commands: Receiver<Command>,
+    answers: Sender<Option<Answer>>,
+) {
+    let mut solver: Solver<Basic> = Solver::new(opts, Basic::new());
+    solver.set_stop_flag(done.clone());
+    let set_max_shared_len = |solver: &mut Solver<Basic>, len| {
+        let exchange = exchange.clone();
+        solver.set_learn_callback(len, move |c| exchange[i].publish(c));
+    };
+    set_max_shared_len(&mut solver, 8);
+    let reserve = |solver: &mut Solver<Basic>, num_vars| {
+        while solver.num_vars() < num_vars {
+            solver.new_var_default();
+        }
+    };
+    let mut stop: Option<Arc<AtomicBool>> = None;
+    // how far each buffer was read
+    let mut read = vec![0; exchange.len()];
+    let mut c = vec![];
+    for cmd in commands {
+        let (num_vars, assumps) = match cmd {
+            Command::AddClause(mut c) => {
+                let num_vars = c.iter().map(|l| l.var().idx() + 1).max().unwrap_or(0);
+                reserve(&mut solver, num_vars);
+                solver.add_clause_reuse(&mut c);
+                continue;
+            }
+            Command::SetMaxSharedLen(len) => {
+                set_max_shared_len(&mut solver, len);
+                continue;
+            }
+            Command::SetStopFlag(flag) => {
+                let f = flag.clone();
+                solver.set_terminate_callback(move || f.load(Ordering::Relaxed));
+                stop = Some(flag);
+                continue;
+            }
+            Command::Solve { num_vars, assumps } => (num_vars, assumps),
+        };
+        reserve(&mut solver, num_vars);
+        let res = loop {
+            solver.set_conflict_budget(ROUND_CONFLICTS);
+            let res = solver.solve_limited(&assumps);
+            let stopped = stop.as_ref().is_some_and(|s| s.load(Ordering::Relaxed));
+            if res != lbool::UNDEF || done.load(Ordering::Relaxed) || stopped {
+                break res;
+            }
+            for (j, buf) in exchange.iter().enumerate() {
+                if j != i {
+                    buf.read(&mut read[j], &mut c, |c| {
+                        solver.add_clause_reuse(c);
+                    });
+                }
+            }
+        };
+        let answer = (res != lbool::UNDEF && !done.swap(true, Ordering::SeqCst)).then(|| Answer {
+            res,
+            model: solver.get_model().to_vec(),
+            core: solver.unsat_core().to_vec(),
+        });
+        if answers.send(answer).is_err() {
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parallel() {
+        // pigeonhole: 8 pigeons in 7 holes
+        let mut solver = ParallelSolver::new(4);
+        let p: Vec<Vec<Lit>> = (0..8)
+            .map(|_| (0..7).map(|_| Lit::new(solver.new_var(), true)).collect())
+            .collect();
+        for row in &p {
+            solver.add_clause(row);
+        }
+        for h in 0..7 {
+            for (i, pi) in p.iter().enumerate() {
+                for pj in &p[..i] {
+                    solver.add_clause(&[!pi[h], !pj[h]]);
+                }
+            }
+        }
+        let a = Lit::new(solver.new_var(), true);
+        solver.add_clause(&[!a, p[0][0]]);
+        assert_eq!(solver.solve(&[a]), lbool::FALSE);
+        assert!(solver.unsat_core().iter().all(|&l| l == a));
+        assert_eq!(solver.value_lit(a), lbool::UNDEF);
+
+        let flag = Arc::new(AtomicBool::new(true));
+        solver.set_stop_flag(flag);
+        assert_eq!(solver.solve(&[]), lbool::UNDEF);
+    }
+
+    #[test]
+    fn test_parallel_incremental() {
+        // 5 pigeons in 5 holes, then a hole is removed
+        let mut solver = ParallelSolver::new(3);
+        solver.set_max_shared_len(20);
+        let p: Vec<Vec<Lit>> = (0..5)
+            .map(|_| (0..5).map(|_| Lit::new(solver.new_var(), true)).collect())
+            .collect();
+        let mut clauses = p.clone();
+        for h in 0..5 {
+            for (i, pi) in p.iter().enumerate() {
+                for pj in &p[..i] {
+                    clauses.push(vec![!pi[h], !pj[h]]);
+                }
+            }
+        }
+        for c in &clauses {
+            solver.add_clause(c);
+        }
+        assert_eq!(solver.value_lit(p[0][0]), lbool::UNDEF);
+        assert_eq!(solver.solve(&[p[0][0], p[1][1]]), lbool::TRUE);
+        assert_eq!(solver.value_lit(p[0][0]), lbool::TRUE);
+        let sat = |c: &Vec<Lit>| c.iter().any(|&l| solver.value_lit(l) == lbool::TRUE);
+        assert!(clauses.iter().all(sat));
+        // the variables created after the first call are known to the workers
+        let a = Lit::new(solver.new_var(), true);
+        for pi in &p {
+            solver.add_clause(&[!a, !pi[4]]);
+        }
+        assert_eq!(solver.solve(&[a]), lbool::FALSE);
+        assert_eq!(solver.unsat_core(), &[a]);
+        assert_eq!(solver.solve(&[!a]), lbool::TRUE);
+    }
+
+    #[test]
+    fn test_exchange_buffer() {
+        let buf = Buffer::new();
+        let x = |i: u32| Lit::new(Var::from_idx(i), true);
+        let (mut read, mut c) = (0, vec![]);
+        let mut received = vec![];
+        buf.publish(&[x(0), !x(1)]);
+        buf.publish(&[x(2)]);
+        buf.read(&mut read, &mut c, |c| received.push(c.clone()));
+        assert_eq!(received, vec![vec![x(0), !x(1)], vec![x(2)]]);
+        // a reader that is late by more than the capacity skips the lost clauses
+        received.clear();
+        let old = read;
+        for i in 0..BUFFER_CAPACITY / 2 {
+            buf.publish(&[x(i as u32), x(0)]);
+        }
+        buf.publish(&[x(5)]);
+        read = old;
+        buf.read(&mut read, &mut c, |c| received.push(c.clone()));
+        assert_eq!(read, buf.len.load(Ordering::Relaxed));
+        assert_eq!(received.last(), Some(&vec![x(5)]));
+        assert!(received.len() <= BUFFER_CAPACITY / 3);
+        assert!(received[..received.len() - 1]
+            .iter()
+            .all(|c| c.len() == 2 && c[1] == x(0)));
+    }
+}
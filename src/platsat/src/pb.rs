@@ -0,0 +1,363 @@
+//! Pseudo-Boolean constraints, propagated with watched sums
+use crate::{
+    clause::{lbool, LMap, Lit, Var},
+    core::TheoryArg,
+    native::Reasons,
+};
+use no_std_compat::prelude::v1::*;
+use std::cmp;
+
+/// `sum(coef * lit for (coef, lit) in terms) >= bound`, with positive coefficients
+/// that are at most `bound`, by decreasing coefficient.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+struct Pb {
+    terms: Vec<(u128, Lit)>,
+    /// Which terms are watched.
+    watched: Vec<bool>,
+    bound: i128,
+    /// Sum of the coefficients minus the bound.
+    max_slack: i128,
+    /// Sum of the coefficients of the watched literals that are not false in the
+    /// processed part of the trail.
+    watch_sum: i128,
+}
+
+impl Pb {
+    /// While the watched sum reaches this, no literal can be propagated.
+    fn watch_target(&self) -> i128 {
+        self.bound + self.terms[0].0 as i128
+    }
+}
+
+/// Set of pseudo-Boolean constraints.
+///
+/// Each constraint watches enough of its literals for their coefficients to add up to
+/// the bound plus the largest coefficient, or all its literals that are not false.
+/// When a watched literal becomes false, other literals are watched instead if
+/// possible. Otherwise the watched sum minus the bound is the slack: every unassigned
+/// literal whose coefficient exceeds it is propagated, and a negative slack is a conflict.
+///
+/// Explanations are clauses made of as few false literals as possible (a weakening of
+/// the constraint), which are then used by the usual clausal conflict analysis: there
+/// is no cutting-planes reasoning, the learnt constraints are clauses.
+#[derive(Debug, Default, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub(crate) struct PbEngine {
+    pbs: Vec<Pb>,
+    /// For each literal, the constraints in which its negation occurs, with the index
+    /// of the term.
+    occs: LMap<Vec<(u32, u32)>>,
+    /// Length of the prefix of the trail that was processed.
+    processed: usize,
+    /// Constraints that may propagate before any of their literals is false.
+    pending: Vec<u32>,
+    tmp: Vec<Lit>,
+}
+
+impl PbEngine {
+    pub(crate) fn contains_var(&self, v: Var) -> bool {
+        let occurs = |lit| self.occs.get(lit).is_some_and(|o| !o.is_empty());
+        occurs(Lit::new(v, true)) || occurs(Lit::new(v, false))
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.pbs.is_empty()
+    }
+
+    /// Add the constraint `sum(coef * lit) >= bound`, where `terms` has no duplicate
+    /// variables, coefficients are between `1` and `bound` and their sum is at least `bound`.
+    pub(crate) fn add(&mut self, terms: &[(u128, Lit)], bound: u128) {
+        let i = self.pbs.len() as u32;
+        let mut terms = terms.to_vec();
+        terms.sort_unstable_by_key(|t| cmp::Reverse(t.0));
+        for (t, &(coef, lit)) in terms.iter().enumerate() {
+            debug_assert!(coef > 0 && coef <= bound);
+            self.occs
+                .get_or_insert_with(!lit, Vec::new)
+                .push((i, t as u32));
+        }
+        let max_slack = terms.iter().map(|t| t.0 as i128).sum::<i128>() - bound as i128;
+        debug_assert!(max_slack >= 0);
+        let mut pb = Pb {
+            watched: vec![false; terms.len()],
+            terms,
+            bound: bound as i128,
+            max_slack,
+            watch_sum: 0,
+        };
+        // the largest coefficients first, the fewest literals to watch
+        let target = pb.watch_target();
+        let mut sum = 0;
+        for t in 0..pb.terms.len() {
+            if sum >= target {
+                break;
+            }
+            pb.watched[t] = true;
+            sum += pb.terms[t].0 as i128;
+        }
+        if sum < target {
+            self.pending.push(i);
+        }
+        self.pbs.push(pb);
+        // process again from the start of the trail, which only contains level 0
+        for pb in &mut self.pbs {
+            pb.watch_sum = (pb.terms.iter().zip(&pb.watched))
+                .filter(|(_, &w)| w)
+                .map(|(t, _)| t.0 as i128)
+                .sum();
+        }
+        self.processed = 0;
+    }
+
+    /// Put in `self.tmp` false literals of `pb`, with the largest coefficients,
+    /// whose coefficients add up to more than `min_sum`.
+    fn weakened_reason(&mut self, acts: &TheoryArg, pb: usize, min_sum: i128) {
+        self.tmp.clear();
+        let mut sum = 0;
+        for &(coef, lit) in &self.pbs[pb].terms {
+            if sum > min_sum {
+                break;
+            }
+            if acts.value_lit(lit) == lbool::FALSE {
+                sum += coef as i128;
+                self.tmp.push(lit);
+            }
+        }
+        debug_assert!(sum > min_sum);
+    }
+
+    /// The watched literal `t` of constraint `i` is now false: watch other literals,
+    /// or propagate the literals of the constraint whose coefficient exceeds the slack,
+    /// or raise a conflict. Returns `false` in case of conflict.
+    fn update(&mut self, acts: &mut TheoryArg, reasons: &mut Reasons, i: usize, t: usize) -> bool {
+        let pb = &mut self.pbs[i];
+        let target = pb.watch_target();
+        for u in 0..pb.terms.len() {
+            if pb.watch_sum >= target {
+                break;
+            }
+            if !pb.watched[u] && acts.value_lit(pb.terms[u].1) != lbool::FALSE {
+                pb.watched[u] = true;
+                pb.watch_sum += pb.terms[u].0 as i128;
+            }
+        }
+        if pb.watch_sum >= target {
+            // `t` was already removed from the sum, and is no longer needed
+            pb.watched[t] = false;
+            return true;
+        }
+        // all the literals that are not false are watched
+        self.propagate(acts, reasons, i)
+    }
+
+    /// Propagate the literals of constraint `i` whose coefficient exceeds the slack,
+    /// or raise a conflict. Returns `false` in case of conflict.
+    fn propagate(&mut self, acts: &mut TheoryArg, reasons: &mut Reasons, i: usize) -> bool {
+        let pb = &self.pbs[i];
+        let (slack, max_slack) = (pb.watch_sum - pb.bound, pb.max_slack);
+        if slack < 0 {
+            // the false literals leave less than `bound` for the others
+            self.weakened_reason(acts, i, max_slack);
+            acts.raise_conflict(&self.tmp, false);
+            return false;
+        }
+        for t in 0..self.pbs[i].terms.len() {
+            let (coef, q) = self.pbs[i].terms[t];
+            if coef as i128 <= slack {
+                break;
+            }
+            if acts.value_lit(q) == lbool::UNDEF {
+                self.weakened_reason(acts, i, max_slack - coef as i128);
+                if !reasons.propagate(acts, q, &self.tmp) {
+                    return false;
+                }
+            }
+        }
+        true
+    }
+
+    /// Process the newly false literals, and propagate or raise a conflict
+    /// in the constraints whose watched sum became too small.
+    pub(crate) fn check(&mut self, acts: &mut TheoryArg, reasons: &mut Reasons) {
+        if self.pbs.is_empty() {
+            return;
+        }
+        loop {
+            // propagations are appended to the trail, and processed in turn
+            while self.processed < acts.model().len() {
+                let lit = acts.model()[self.processed];
+                self.processed += 1;
+                if !self.occs.has(lit) {
+                    continue;
+                }
+                // update every watched sum first, `backtrack` relies on it
+                for &(i, t) in &self.occs[lit] {
+                    let pb = &mut self.pbs[i as usize];
+                    if pb.watched[t as usize] {
+                        pb.watch_sum -= pb.terms[t as usize].0 as i128;
+                    }
+                }
+                for j in 0..self.occs[lit].len() {
+                    let (i, t) = self.occs[lit][j];
+                    let (i, t) = (i as usize, t as usize);
+                    if self.pbs[i].watched[t] && !self.update(acts, reasons, i, t) {
+                        return;
+                    }
+                }
+            }
+            let Some(i) = self.pending.pop() else {
+                break;
+            };
+            if !self.propagate(acts, reasons, i as usize) {
+                return;
+            }
+        }
+    }
+
+    /// Stop counting the literals of `trail[trail_len..]`.
+    pub(crate) fn backtrack(&mut self, trail: &[Lit], trail_len: usize) {
+        if self.processed <= trail_len {
+            return;
+        }
+        for &lit in &trail[trail_len..self.processed] {
+            for &(i, t) in self.occs.get(lit).into_iter().flatten() {
+                let pb = &mut self.pbs[i as usize];
+                if pb.watched[t as usize] {
+                    pb.watch_sum += pb.terms[t as usize].0 as i128;
+                }
+            }
+        }
+        self.processed = trail_len;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{test_util::Rng, *};
+    use no_std_compat::prelude::v1::*;
+
+    fn eval(lit: Lit, a: u32) -> bool {
+        ((a >> lit.var().idx()) & 1 == 1) == lit.sign()
+    }
+
+    #[test]
+    fn test_pb() {
+        let mut solver = BasicSolver::default();
+        let x: Vec<Lit> = (0..4)
+            .map(|_| Lit::new(solver.new_var_default(), true))
+            .collect();
+        // 3 x0 + 2 x1 + 2 x2 + x3 >= 5
+        let terms = [(3, x[0]), (2, x[1]), (2, x[2]), (1, x[3])];
+        assert!(solver.add_pb_constraint(&terms, 5));
+        assert_eq!(solver.solve_limited(&[!x[0], !x[3]]), lbool::FALSE);
+        assert_eq!(solver.solve_limited(&[!x[0]]), lbool::TRUE);
+        assert!(x[1..].iter().all(|&l| solver.value_lit(l) == lbool::TRUE));
+        // 2 x1 + 2 x2 + x3 + 3 !x0 <= 4
+        let terms = [(-2, x[1]), (-2, x[2]), (-1, x[3]), (-3, !x[0])];
+        assert!(solver.add_pb_constraint(&terms, -4));
+        assert_eq!(solver.solve_limited(&[!x[0]]), lbool::FALSE);
+        assert_eq!(solver.solve_limited(&[x[1], x[2]]), lbool::TRUE);
+        assert_eq!(solver.value_lit(x[0]), lbool::TRUE);
+        assert_eq!(solver.value_lit(x[3]), lbool::FALSE);
+    }
+
+    #[test]
+    fn test_pb_large_coefs() {
+        let mut solver = BasicSolver::default();
+        let x: Vec<Lit> = (0..4)
+            .map(|_| Lit::new(solver.new_var_default(), true))
+            .collect();
+        // the normalized bound is `2^64 - 1`: two of x0, x1, x2, or x0, x1, x3
+        let terms = [
+            (i64::MAX, x[0]),
+            (i64::MAX, x[1]),
+            (i64::MIN, !x[2]),
+            (1, x[3]),
+        ];
+        assert!(solver.add_pb_constraint(&terms, i64::MAX));
+        assert_eq!(solver.solve_limited(&[!x[0]]), lbool::TRUE);
+        assert!(x[1..3].iter().all(|&l| solver.value_lit(l) == lbool::TRUE));
+        assert_eq!(solver.solve_limited(&[x[0], !x[2]]), lbool::TRUE);
+        assert_eq!(solver.value_lit(x[1]), lbool::TRUE);
+        assert_eq!(solver.value_lit(x[3]), lbool::TRUE);
+        assert_eq!(solver.solve_limited(&[!x[2], !x[3]]), lbool::FALSE);
+    }
+
+    #[test]
+    fn test_pb_watches() {
+        let mut solver = BasicSolver::default();
+        let x: Vec<Lit> = (0..6)
+            .map(|_| Lit::new(solver.new_var_default(), true))
+            .collect();
+        // 5 x0 + x1 + ... + x5 >= 5: the small terms are only watched once x0 is false
+        let terms: Vec<(i64, Lit)> = x.iter().map(|&l| (1, l)).collect();
+        let terms = [&[(4, x[0])], &terms[..]].concat();
+        assert!(solver.add_pb_constraint(&terms, 5));
+        assert_eq!(solver.solve_limited(&[x[0], !x[1], !x[2]]), lbool::TRUE);
+        assert_eq!(solver.solve_limited(&[!x[0]]), lbool::TRUE);
+        assert!(x[1..].iter().all(|&l| solver.value_lit(l) == lbool::TRUE));
+        assert_eq!(solver.solve_limited(&[!x[4], !x[0]]), lbool::FALSE);
+        let mut core = solver.unsat_core().to_vec();
+        core.sort();
+        assert_eq!(core, [!x[0], !x[4]]);
+        // the watches are restored on backtracking
+        assert_eq!(solver.solve_limited(&[!x[4]]), lbool::TRUE);
+        assert_eq!(solver.value_lit(x[0]), lbool::TRUE);
+    }
+
+    #[test]
+    fn test_pb_random() {
+        const N: u32 = 9;
+        let mut rng = Rng::new(5);
+        for _ in 0..100 {
+            let mut solver = BasicSolver::default();
+            let vars: Vec<Var> = (0..N).map(|_| solver.new_var_default()).collect();
+            let mut pbs = vec![];
+            let mut clauses = vec![];
+            for _ in 0..rng.next(4) + 1 {
+                let terms: Vec<(i64, Lit)> = (0..rng.next(6) + 2)
+                    .map(|_| {
+                        let lit = Lit::new(vars[rng.next(N as u64) as usize], rng.next(2) == 0);
+                        (rng.next(11) as i64 - 5, lit)
+                    })
+                    .collect();
+                let bound = rng.next(9) as i64 - 2;
+                solver.add_pb_constraint(&terms, bound);
+                pbs.push((terms, bound));
+            }
+            for _ in 0..rng.next(6) {
+                let mut c: Vec<Lit> = (0..3)
+                    .map(|_| Lit::new(vars[rng.next(N as u64) as usize], rng.next(2) == 0))
+                    .collect();
+                clauses.push(c.clone());
+                solver.add_clause_reuse(&mut c);
+            }
+            let assumps: Vec<Lit> = (0..rng.next(4))
+                .map(|_| Lit::new(vars[rng.next(N as u64) as usize], rng.next(2) == 0))
+                .collect();
+            let is_model = |a: u32| {
+                pbs.iter().all(|(terms, bound)| {
+                    let sum: i64 = terms.iter().map(|&(c, l)| c * eval(l, a) as i64).sum();
+                    sum >= *bound
+                }) && clauses.iter().all(|c| c.iter().any(|&l| eval(l, a)))
+            };
+            let sat = (0..1u32 << N).any(|a| is_model(a) && assumps.iter().all(|&l| eval(l, a)));
+            let res = solver.solve_limited(&assumps);
+            if sat {
+                assert_eq!(res, lbool::TRUE);
+                let a = vars
+                    .iter()
+                    .filter(|&&v| solver.value_var(v) == lbool::TRUE)
+                    .fold(0, |a, v| a | 1 << v.idx());
+                assert!(is_model(a));
+            } else {
+                assert_eq!(res, lbool::FALSE);
+                let core = solver.unsat_core().to_vec();
+                assert!(core.iter().all(|l| assumps.contains(l)));
+                let sat = |a| is_model(a) && core.iter().all(|&l| eval(l, a));
+                assert!(!(0..1u32 << N).any(sat));
+            }
+        }
+    }
+}
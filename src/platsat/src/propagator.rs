@@ -0,0 +1,341 @@
+//! User propagators following [IPASIR-UP](https://doi.org/10.4230/LIPIcs.SAT.2023.8),
+//! implemented on top of [`Theory`].
+//!
+//! An [`ExternalPropagator`] observes the assignments of some variables, and can
+//! propagate literals (explained lazily by a reason clause), add clauses, choose decisions,
+//! and check the models found by the solver. [`UserPropagator`] adapts it into a theory,
+//! to be given to [`solve_limited_th`](crate::SolverInterface::solve_limited_th).
+use crate::clause::{lbool, Lit, VMap, Var};
+use crate::core::ExplainTheoryArg;
+use crate::theory::{Theory, TheoryArg};
+use no_std_compat::prelude::v1::*;
+
+/// Propagator written against the IPASIR-UP interface.
+///
+/// The solver only tells the propagator about the variables it observes, see
+/// [`UserPropagator::add_observed_var`].
+pub trait ExternalPropagator {
+    /// Notification that the observed literals `lits` were assigned, in the order of
+    /// the trail.
+    fn notify_assignment(&mut self, lits: &[Lit]);
+
+    /// Notification that the solver opened a new decision level.
+    fn notify_new_decision_level(&mut self);
+
+    /// Notification that the solver backtracked to decision level `new_level`, so the
+    /// assignments made above it are undone.
+    fn notify_backtrack(&mut self, new_level: usize);
+
+    /// Check the complete model found by the solver, given as its observed literals.
+    ///
+    /// If it is rejected, the propagator must provide a clause that excludes it through
+    /// [`add_external_clause`](Self::add_external_clause).
+    fn check_found_model(&mut self, model: &[Lit]) -> bool;
+
+    /// Choose the next decision literal, or `None` to leave the choice to the solver.
+    fn decide(&mut self) -> Option<Lit> {
+        None
+    }
+
+    /// Literal implied by the current assignment, or `None` if there is none.
+    ///
+    /// The solver asks for the next one after assigning it.
+    fn propagate(&mut self) -> Option<Lit> {
+        None
+    }
+
+    /// Write into `clause` the reason of `propagated`, a literal returned by
+    /// [`propagate`](Self::propagate): a clause containing it whose other literals are
+    /// false.
+    ///
+    /// It is only asked when the solver needs it, during conflict analysis.
+    fn add_reason_clause(&mut self, propagated: Lit, _clause: &mut Vec<Lit>) {
+        unreachable!("no reason for {:?}", propagated)
+    }
+
+    /// Write into `clause` a clause to add to the solver, and return `true`, or return
+    /// `false` if there is none.
+    ///
+    /// The solver asks again until there is none left.
+    fn add_external_clause(&mut self, _clause: &mut Vec<Lit>) -> bool {
+        false
+    }
+}
+
+/// Theory that connects an [`ExternalPropagator`] to the solver.
+///
+/// The external clauses are added to the irredundant clauses of the solver, see
+/// [`TheoryArg::add_permanent_clause`].
+#[derive(Clone, Debug)]
+pub struct UserPropagator<P> {
+    /// The propagator.
+    pub prop: P,
+    observed: VMap<bool>,
+    /// Is the observed variable assigned by a decision?
+    decisions: VMap<bool>,
+    /// Observed literals assigned since the last notification.
+    assigned: Vec<Lit>,
+    levels: usize,
+    clause: Vec<Lit>,
+}
+
+impl<P: ExternalPropagator> UserPropagator<P> {
+    /// Connect `prop`, which observes no variable yet.
+    pub fn new(prop: P) -> Self {
+        UserPropagator {
+            prop,
+            observed: VMap::new(),
+            decisions: VMap::new(),
+            assigned: vec![],
+            levels: 0,
+            clause: vec![],
+        }
+    }
+
+    /// Notify the propagator of the assignments of `v`.
+    ///
+    /// The variable must not be eliminated by the solver, so it should be frozen (see
+    /// [`Solver::set_frozen`](crate::Solver::set_frozen)) if the solver simplifies the
+    /// formula.
+    pub fn add_observed_var(&mut self, v: Var) {
+        self.observed.insert(v, true, false);
+    }
+
+    /// Stop notifying the propagator of the assignments of `v`.
+    pub fn remove_observed_var(&mut self, v: Var) {
+        if self.observed.has(v) {
+            self.observed[v] = false;
+        }
+    }
+
+    /// Stop observing all the variables.
+    pub fn reset_observed_vars(&mut self) {
+        self.observed.clear();
+    }
+
+    /// Is `v` observed?
+    pub fn is_observed(&self, v: Var) -> bool {
+        self.observed.has(v) && self.observed[v]
+    }
+
+    /// Was the observed literal `lit`, which is assigned, decided rather than propagated?
+    pub fn is_decision(&self, lit: Lit) -> bool {
+        self.decisions.has(lit.var()) && self.decisions[lit.var()]
+    }
+
+    fn notify(&mut self) {
+        if !self.assigned.is_empty() {
+            self.prop.notify_assignment(&self.assigned);
+            self.assigned.clear();
+        }
+    }
+
+    /// Add the external clauses of the propagator, returns `true` if there was one.
+    fn add_external_clauses(&mut self, acts: &mut TheoryArg) -> bool {
+        let mut added = false;
+        self.clause.clear();
+        while self.prop.add_external_clause(&mut self.clause) {
+            acts.add_permanent_clause(&self.clause);
+            self.clause.clear();
+            added = true;
+        }
+        added
+    }
+
+    /// Propagate the literals of the propagator, returns `true` if one of them was
+    /// assigned or false.
+    fn propagate(&mut self, acts: &mut TheoryArg) -> bool {
+        // one new literal at a time, so the propagator is notified of its consequences
+        // before the next one
+        while let Some(p) = self.prop.propagate() {
+            let new = acts.value_lit(p) == lbool::UNDEF;
+            if !acts.propagate(p) || new {
+                return true;
+            }
+        }
+        false
+    }
+}
+
+impl<P: ExternalPropagator> Theory for UserPropagator<P> {
+    fn final_check(&mut self, acts: &mut TheoryArg) {
+        self.notify();
+        if self.add_external_clauses(acts) || self.propagate(acts) {
+            return;
+        }
+        let model: Vec<Lit> = acts
+            .model()
+            .iter()
+            .copied()
+            .filter(|&lit| self.is_observed(lit.var()))
+            .collect();
+        if !self.prop.check_found_model(&model) {
+            let added = self.add_external_clauses(acts);
+            debug_assert!(added, "the model was rejected without a clause");
+        }
+    }
+    fn create_level(&mut self) {
+        self.levels += 1;
+        self.prop.notify_new_decision_level();
+    }
+    fn pop_levels(&mut self, n: usize) {
+        debug_assert!(self.levels >= n);
+        self.levels -= n;
+        self.prop.notify_backtrack(self.levels);
+    }
+    fn n_levels(&self) -> usize {
+        self.levels
+    }
+    fn partial_check(&mut self, acts: &mut TheoryArg) {
+        self.notify();
+        self.add_external_clauses(acts);
+        self.propagate(acts);
+    }
+    fn on_assign(&mut self, lit: Lit, is_decision: bool) {
+        if self.is_observed(lit.var()) {
+            self.decisions.insert(lit.var(), is_decision, false);
+            self.assigned.push(lit);
+        }
+    }
+    fn decide(&mut self, _acts: &TheoryArg) -> Option<Lit> {
+        self.prop.decide()
+    }
+    fn explain_propagation_clause(&mut self, p: Lit, _st: &mut ExplainTheoryArg) -> &[Lit] {
+        self.clause.clear();
+        self.prop.add_reason_clause(p, &mut self.clause);
+        let i = self.clause.iter().position(|&lit| lit == p);
+        let i = i.expect("the reason clause must contain the propagated literal");
+        self.clause.swap(0, i);
+        &self.clause
+    }
+}
+
+#[test]
+fn test_user_propagator() {
+    use crate::interface::SolverInterface;
+    // at most one literal of each block of 4 observed variables is true: propagated
+    // eagerly for the even blocks, checked on the models for the odd ones; same results
+    // as with pairwise clauses
+    struct AtMostOne {
+        blocks: Vec<Vec<Var>>,
+        value: Vec<lbool>,
+        trail: Vec<Var>,
+        lims: Vec<usize>,
+        reasons: Vec<Lit>,
+        clauses: Vec<Vec<Lit>>,
+    }
+    impl AtMostOne {
+        fn assign(&mut self, lit: Lit) {
+            let v = lit.var();
+            if self.value[v.idx() as usize] == lbool::UNDEF {
+                self.value[v.idx() as usize] = lbool::new(lit.sign());
+                self.trail.push(v);
+            }
+        }
+    }
+    impl ExternalPropagator for AtMostOne {
+        fn notify_assignment(&mut self, lits: &[Lit]) {
+            for &lit in lits {
+                self.assign(lit)
+            }
+        }
+        fn notify_new_decision_level(&mut self) {
+            self.lims.push(self.trail.len());
+        }
+        fn notify_backtrack(&mut self, new_level: usize) {
+            let len = self.lims[new_level];
+            self.lims.truncate(new_level);
+            for v in self.trail.drain(len..) {
+                self.value[v.idx() as usize] = lbool::UNDEF;
+            }
+        }
+        fn check_found_model(&mut self, model: &[Lit]) -> bool {
+            for block in self.blocks.iter().skip(1).step_by(2) {
+                let pos: Vec<Lit> = model
+                    .iter()
+                    .copied()
+                    .filter(|lit| lit.sign() && block.contains(&lit.var()))
+                    .collect();
+                if pos.len() > 1 {
+                    self.clauses.push(vec![!pos[0], !pos[1]]);
+                }
+            }
+            self.clauses.is_empty()
+        }
+        fn decide(&mut self) -> Option<Lit> {
+            let v = self.blocks.iter().flatten().copied();
+            let mut v = v.filter(|v| self.value[v.idx() as usize] == lbool::UNDEF);
+            v.next().map(|v| Lit::new(v, false))
+        }
+        fn propagate(&mut self) -> Option<Lit> {
+            for block in self.blocks.iter().step_by(2) {
+                let value = |v: &Var| self.value[v.idx() as usize];
+                let Some(&t) = block.iter().find(|v| value(v) == lbool::TRUE) else {
+                    continue;
+                };
+                let other = |v: &&Var| **v != t && value(v) != lbool::FALSE;
+                if let Some(&u) = block.iter().find(other) {
+                    let p = Lit::new(u, false);
+                    self.reasons[u.idx() as usize] = Lit::new(t, false);
+                    self.assign(p);
+                    return Some(p);
+                }
+            }
+            None
+        }
+        fn add_reason_clause(&mut self, propagated: Lit, clause: &mut Vec<Lit>) {
+            clause.extend([propagated, self.reasons[propagated.var().idx() as usize]]);
+        }
+        fn add_external_clause(&mut self, clause: &mut Vec<Lit>) -> bool {
+            self.clauses.pop().map(|c| clause.extend(c)).is_some()
+        }
+    }
+    let mut rng = crate::test_util::Rng::new(5);
+    let mut rand = |n| rng.next(n);
+    let mut results = [0; 2];
+    for _ in 0..16 {
+        let mut solver = crate::BasicSolver::default();
+        let vars: Vec<Var> = (0..40).map(|_| solver.new_var_default()).collect();
+        for _ in 0..150 {
+            let mut c: Vec<Lit> = (0..3)
+                .map(|_| Lit::new(vars[rand(40) as usize], rand(2) == 0))
+                .collect();
+            solver.add_clause_reuse(&mut c);
+        }
+        let blocks: Vec<Vec<Var>> = vars[..24].chunks(4).map(|b| b.to_vec()).collect();
+        let mut full = solver.clone();
+        for block in &blocks {
+            for (i, &a) in block.iter().enumerate() {
+                for &b in &block[i + 1..] {
+                    full.add_clause_reuse(&mut vec![Lit::new(a, false), Lit::new(b, false)]);
+                }
+            }
+        }
+        let mut th = UserPropagator::new(AtMostOne {
+            blocks: blocks.clone(),
+            value: vec![lbool::UNDEF; 40],
+            trail: vec![],
+            lims: vec![],
+            reasons: vec![Lit::UNDEF; 40],
+            clauses: vec![],
+        });
+        for &v in blocks.iter().flatten() {
+            th.add_observed_var(v);
+        }
+        let expected = full.solve_limited(&[]);
+        assert_eq!(solver.solve_limited_th(&mut th, &[]), expected);
+        if expected == lbool::TRUE {
+            results[0] += 1;
+            let model = solver.get_model();
+            for block in &blocks {
+                let n = block
+                    .iter()
+                    .filter(|v| model[v.idx() as usize] == lbool::TRUE);
+                assert!(n.count() <= 1);
+            }
+        } else {
+            results[1] += 1;
+        }
+    }
+    assert!(results[0] > 0 && results[1] > 0, "{:?}", results);
+}
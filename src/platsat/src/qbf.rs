@@ -0,0 +1,345 @@
+//! Quantified Boolean formulas in prenex CNF
+//!
+//! A [`Qbf`] is a CNF matrix under a prefix of quantifier blocks, as in the QDIMACS
+//! format (see [`dimacs::parse_qdimacs`](crate::dimacs::parse_qdimacs)). It is solved
+//! with SAT solvers:
+//!
+//! - formulas of the form `forall X. exists Y. F` by counterexample-guided abstraction
+//!   refinement (CEGAR): a solver of `F` checks the candidate counterexamples `X` found
+//!   by a second solver, which learns from each `Y` that it must falsify `F` under it;
+//! - the other ones by expanding the universal variables, innermost first: each one is
+//!   replaced by the conjunction of the two values of the formula, whose variables
+//!   quantified after it are copied. This is exponential in the number of universal
+//!   variables.
+use crate::{
+    clause::{lbool, Lit, Var},
+    interface::SolverInterface,
+    BasicSolver,
+};
+use no_std_compat::prelude::v1::*;
+
+/// Quantifier of a block of variables.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Quantifier {
+    Exists,
+    Forall,
+}
+
+/// A quantified Boolean formula in prenex CNF.
+///
+/// The variables that are not quantified are existential, in a block before the
+/// prefix.
+#[derive(Clone, Debug, Default)]
+pub struct Qbf {
+    /// Number of variables, the variables are `0..num_vars`.
+    pub num_vars: u32,
+    /// Quantifier blocks, outermost first.
+    pub prefix: Vec<(Quantifier, Vec<Var>)>,
+    pub clauses: Vec<Vec<Lit>>,
+}
+
+impl Qbf {
+    /// Decide the formula.
+    ///
+    /// If the outermost block is existential and the formula is true, `witness` is set
+    /// to the values of its variables in a model. If it is universal and the formula
+    /// is false, it is set to a counterexample when the formula was solved by CEGAR.
+    /// Otherwise it is empty.
+    pub fn solve(&self, witness: &mut Vec<Lit>) -> lbool {
+        witness.clear();
+        let (depth, clauses) = self.reduce();
+        let max_depth = clauses
+            .iter()
+            .flat_map(|c| c.iter().map(|lit| depth[lit.var().idx() as usize]))
+            .max()
+            .unwrap_or(0);
+        // after universal reduction, the innermost variable of each clause is existential
+        if clauses.iter().any(|c| c.is_empty()) {
+            lbool::FALSE
+        } else if max_depth <= 1 {
+            self.solve_sat(&clauses, &depth, witness)
+        } else if max_depth == 3 && !self.occurs(&clauses, &depth, &[0, 1]) {
+            self.solve_cegar(&clauses, &depth, witness)
+        } else {
+            self.solve_expanded(clauses, depth, witness)
+        }
+    }
+
+    /// Depth of each variable, where the unquantified ones are at depth 0, the
+    /// existential blocks at odd depths and the universal ones at even depths, and the
+    /// clauses after universal reduction:
+    /// the universal literals quantified after all the existential literals of their
+    /// clause are removed. Tautologies are removed too.
+    fn reduce(&self) -> (Vec<u32>, Vec<Vec<Lit>>) {
+        let mut depth = vec![0; self.num_vars as usize];
+        let mut d = 0;
+        for (q, vars) in &self.prefix {
+            // the blocks of the same quantifier are merged
+            let exists = *q == Quantifier::Exists;
+            if d == 0 || (d & 1 == 1) != exists {
+                d += 1 + (d == 0 && !exists) as u32;
+            }
+            for &v in vars {
+                depth[v.idx() as usize] = d;
+            }
+        }
+        let is_universal = |d: u32| d > 0 && d & 1 == 0;
+        let mut clauses = vec![];
+        for c in &self.clauses {
+            if c.iter().any(|&lit| c.contains(&!lit)) {
+                continue;
+            }
+            let depths = c.iter().map(|lit| depth[lit.var().idx() as usize]);
+            let max_exists = depths.filter(|&d| !is_universal(d)).max();
+            let c = c.iter().copied().filter(|lit| {
+                let d = depth[lit.var().idx() as usize];
+                !is_universal(d) || max_exists.is_some_and(|m| d < m)
+            });
+            clauses.push(c.collect());
+        }
+        (depth, clauses)
+    }
+
+    /// Do variables of the depths `ds` occur in `clauses`?
+    fn occurs(&self, clauses: &[Vec<Lit>], depth: &[u32], ds: &[u32]) -> bool {
+        let is_in_ds = |lit: &Lit| ds.contains(&depth[lit.var().idx() as usize]);
+        clauses.iter().any(|c| c.iter().any(is_in_ds))
+    }
+
+    /// Solve `clauses`, whose variables are all existential.
+    fn solve_sat(&self, clauses: &[Vec<Lit>], depth: &[u32], witness: &mut Vec<Lit>) -> lbool {
+        let mut solver = self.solver(clauses);
+        let res = solver.solve_limited(&[]);
+        if res == lbool::TRUE {
+            // the outermost block is at depth 0 or 1
+            let outer = (0..self.num_vars).filter(|&i| depth[i as usize] <= 1);
+            witness.extend(outer.map(|i| {
+                let v = Var::from_idx(i);
+                Lit::new(v, solver.value_var(v) == lbool::TRUE)
+            }));
+        }
+        res
+    }
+
+    /// A solver with the variables of the formula and `clauses`.
+    fn solver(&self, clauses: &[Vec<Lit>]) -> BasicSolver {
+        let mut solver = BasicSolver::default();
+        if self.num_vars > 0 {
+            solver.var_of_int(self.num_vars - 1);
+        }
+        for c in clauses {
+            solver.add_clause_reuse(&mut c.clone());
+        }
+        solver
+    }
+
+    /// Solve `forall X. exists Y. clauses`, where the variables of `X` are at depth 2
+    /// and those of `Y` at depth 3.
+    fn solve_cegar(&self, clauses: &[Vec<Lit>], depth: &[u32], witness: &mut Vec<Lit>) -> lbool {
+        let universal: Vec<Var> = (0..self.num_vars)
+            .filter(|&i| depth[i as usize] == 2)
+            .map(Var::from_idx)
+            .collect();
+        let is_universal = |lit: Lit| depth[lit.var().idx() as usize] == 2;
+        let mut matrix = self.solver(clauses);
+        // the candidate counterexamples, which falsify the matrix under each `Y` found
+        let mut candidates = self.solver(&[]);
+        let mut assumps = vec![];
+        let mut falsified = vec![];
+        loop {
+            if candidates.solve_limited(&[]) == lbool::FALSE {
+                return lbool::TRUE;
+            }
+            assumps.clear();
+            assumps.extend(
+                (universal.iter()).map(|&v| Lit::new(v, candidates.value_var(v) == lbool::TRUE)),
+            );
+            let res = matrix.solve_limited(&assumps);
+            if res == lbool::FALSE {
+                witness.extend_from_slice(&assumps);
+                return lbool::FALSE;
+            } else if res != lbool::TRUE {
+                return lbool::UNDEF;
+            }
+            // one of the clauses that `Y` does not satisfy must be falsified by `X`
+            falsified.clear();
+            for c in clauses {
+                let sat_by_y =
+                    |lit: Lit| !is_universal(lit) && matrix.value_lit(lit) == lbool::TRUE;
+                if c.iter().any(|&lit| sat_by_y(lit)) {
+                    continue;
+                }
+                let t = Lit::new(candidates.new_var_default(), true);
+                for &lit in c.iter().filter(|&&lit| is_universal(lit)) {
+                    candidates.add_clause_reuse(&mut vec![!t, !lit]);
+                }
+                falsified.push(t);
+            }
+            candidates.add_clause_reuse(&mut falsified);
+        }
+    }
+
+    /// Solve `clauses` after expanding the universal variables.
+    fn solve_expanded(
+        &self,
+        mut clauses: Vec<Vec<Lit>>,
+        mut depth: Vec<u32>,
+        witness: &mut Vec<Lit>,
+    ) -> lbool {
+        let mut universal: Vec<Var> = (0..self.num_vars)
+            .filter(|&i| depth[i as usize] > 0 && depth[i as usize] & 1 == 0)
+            .map(Var::from_idx)
+            .collect();
+        universal.sort_by_key(|v| depth[v.idx() as usize]);
+        let mut copy: Vec<Var> = vec![];
+        while let Some(x) = universal.pop() {
+            let d = depth[x.idx() as usize];
+            let is_inner = |depth: &[u32], lit: &Lit| depth[lit.var().idx() as usize] > d;
+            // copies of the inner variables for `x = 1`, the original ones are for `x = 0`
+            copy.clear();
+            for i in 0..depth.len() {
+                let v = if depth[i] > d {
+                    depth.push(depth[i]);
+                    Var::from_idx(depth.len() as u32 - 1)
+                } else {
+                    Var::UNDEF
+                };
+                copy.push(v);
+            }
+            let mut expanded = vec![];
+            for c in clauses {
+                let x_value = c.iter().find(|lit| lit.var() == x).map(|lit| lit.sign());
+                let inner = c.iter().any(|lit| is_inner(&depth, lit));
+                if x_value != Some(false) {
+                    let lits = c.iter().copied().filter(|lit| lit.var() != x);
+                    expanded.push(lits.collect());
+                }
+                if x_value != Some(true) && (inner || x_value.is_some()) {
+                    let rename = |lit: Lit| match copy.get(lit.var().idx() as usize) {
+                        Some(&v) if v != Var::UNDEF => Lit::new(v, lit.sign()),
+                        _ => lit,
+                    };
+                    let lits = c.iter().copied().filter(|lit| lit.var() != x);
+                    expanded.push(lits.map(rename).collect());
+                }
+            }
+            clauses = expanded;
+        }
+        let qbf = Qbf {
+            num_vars: depth.len() as u32,
+            prefix: vec![],
+            clauses: vec![],
+        };
+        let res = qbf.solve_sat(&clauses, &depth, witness);
+        witness.retain(|lit| lit.var().idx() < self.num_vars);
+        res
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::Rng;
+
+    /// Decide `qbf` by evaluating the quantifiers over all the assignments.
+    fn eval(qbf: &Qbf, block: usize, m: u32) -> bool {
+        let Some((q, vars)) = qbf.prefix.get(block) else {
+            let value = |lit: &Lit| (m >> lit.var().idx() & 1 != 0) == lit.sign();
+            return qbf.clauses.iter().all(|c| c.iter().any(value));
+        };
+        let mut values = (0..1u32 << vars.len()).map(|a| {
+            let mut m = m;
+            for (i, v) in vars.iter().enumerate() {
+                m = m & !(1 << v.idx()) | (a >> i & 1) << v.idx();
+            }
+            eval(qbf, block + 1, m)
+        });
+        match q {
+            Quantifier::Exists => values.any(|b| b),
+            Quantifier::Forall => values.all(|b| b),
+        }
+    }
+
+    #[test]
+    fn test_qbf() {
+        let mut rng = Rng::new(11);
+        let mut rand = |n| rng.next(n);
+        let mut num = [0; 2];
+        for i in 0..300 {
+            let first = rand(2) as usize;
+            // a random prefix over the variables `0..7`, with `7` unquantified
+            let mut qbf = Qbf {
+                num_vars: 8,
+                ..Qbf::default()
+            };
+            let num_blocks = 1 + i % 4;
+            for v in (0..7).map(Var::from_idx) {
+                let block = rand(num_blocks as u64) as usize;
+                while qbf.prefix.len() <= block {
+                    let q = if (qbf.prefix.len() + first) & 1 == 0 {
+                        Quantifier::Forall
+                    } else {
+                        Quantifier::Exists
+                    };
+                    qbf.prefix.push((q, vec![]));
+                }
+                qbf.prefix[block].1.push(v);
+            }
+            let vars = 7 + (i % 3 == 0) as u64;
+            for _ in 0..4 + rand(12) {
+                let c = (0..1 + rand(3))
+                    .map(|_| Lit::new(Var::from_idx(rand(vars) as u32), rand(2) == 0));
+                qbf.clauses.push(c.collect());
+            }
+            let mut expected = eval(&qbf, 0, 0);
+            if vars == 8 {
+                expected = (0..2).any(|x| eval(&qbf, 0, x << 7));
+            }
+            let mut witness = vec![];
+            let res = qbf.solve(&mut witness);
+            assert_eq!(res, lbool::new(expected), "{:?}", qbf);
+            num[expected as usize] += 1;
+            // the formula has the same value with the outermost block fixed by the witness
+            if !witness.is_empty() {
+                let mut fixed = qbf.clone();
+                for (_, vars) in &mut fixed.prefix {
+                    vars.retain(|&v| !witness.iter().any(|lit| lit.var() == v));
+                }
+                fixed.clauses.extend(witness.iter().map(|&lit| vec![lit]));
+                assert_eq!(fixed.solve(&mut vec![]), res, "{:?} {:?}", qbf, witness);
+            }
+        }
+        assert!(num[0] > 30 && num[1] > 30, "{:?}", num);
+
+        // `forall 0..5. exists 5..11`, solved by CEGAR
+        let mut num = [0; 2];
+        for _ in 0..100 {
+            let mut qbf = Qbf {
+                num_vars: 11,
+                prefix: vec![
+                    (Quantifier::Forall, (0..5).map(Var::from_idx).collect()),
+                    (Quantifier::Exists, (5..11).map(Var::from_idx).collect()),
+                ],
+                clauses: vec![],
+            };
+            for _ in 0..10 + rand(15) {
+                let mut c = vec![Lit::new(Var::from_idx(5 + rand(6) as u32), rand(2) == 0)];
+                c.extend((0..2).map(|_| Lit::new(Var::from_idx(rand(11) as u32), rand(2) == 0)));
+                qbf.clauses.push(c);
+            }
+            let expected = eval(&qbf, 0, 0);
+            let mut witness = vec![];
+            assert_eq!(qbf.solve(&mut witness), lbool::new(expected), "{:?}", qbf);
+            num[expected as usize] += 1;
+            if !expected {
+                // a counterexample
+                assert_eq!(witness.len(), 5);
+                let m = witness
+                    .iter()
+                    .fold(0, |m, lit| m | (lit.sign() as u32) << lit.var().idx());
+                assert!(!eval(&qbf, 1, m));
+            }
+        }
+        assert!(num[0] > 10 && num[1] > 10, "{:?}", num);
+    }
+}
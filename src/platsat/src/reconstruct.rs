@@ -0,0 +1,136 @@
+//! Model reconstruction for the clauses removed by preprocessing
+use crate::clause::{lbool, Lit, VMap, Var};
+use no_std_compat::prelude::v1::*;
+
+/// Stack of the clauses removed by variable elimination, blocked clause elimination
+/// or equivalence substitution, used to extend the models of the simplified problem
+/// into models of the original one.
+///
+/// Each clause is pushed with a witness literal: the literal of the eliminated variable
+/// that it contains, or the literal on which it is blocked. A model is extended by
+/// walking the stack from the top, and making the witness of every clause that is not
+/// satisfied true. Variables that occur in the stack only as witnesses are first
+/// assigned to false.
+#[derive(Debug, Default, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub(crate) struct Reconstruction {
+    /// Literals of the removed clauses, one after the other.
+    lits: Vec<Lit>,
+    /// The witness of each removed clause, and the end of its literals in `lits`.
+    clauses: Vec<(Lit, u32)>,
+}
+
+impl Reconstruction {
+    pub(crate) fn is_empty(&self) -> bool {
+        self.clauses.is_empty()
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.lits.clear();
+        self.clauses.clear();
+    }
+
+    /// Record the removal of `clause`, which contains `witness`.
+    pub(crate) fn push(&mut self, witness: Lit, clause: &[Lit]) {
+        debug_assert!(clause.contains(&witness));
+        self.lits.extend_from_slice(clause);
+        self.clauses.push((witness, self.lits.len() as u32));
+    }
+
+    /// Move the clauses of `other` on top of this stack.
+    pub(crate) fn append(&mut self, other: &mut Self) {
+        for (witness, c) in other.iter() {
+            self.push(witness, c);
+        }
+        other.clear();
+    }
+
+    /// The removed clauses with their witnesses, from the bottom of the stack.
+    pub(crate) fn iter(&self) -> impl DoubleEndedIterator<Item = (Lit, &[Lit])> {
+        (0..self.clauses.len()).map(move |i| {
+            let start = if i == 0 { 0 } else { self.clauses[i - 1].1 };
+            let (witness, end) = self.clauses[i];
+            (witness, &self.lits[start as usize..end as usize])
+        })
+    }
+
+    /// Remove the clauses whose witness is a literal of `v`, and push them to `out`.
+    pub(crate) fn take_clauses_of(&mut self, v: Var, out: &mut Vec<Vec<Lit>>) {
+        let mut kept = Reconstruction::default();
+        for (witness, c) in self.iter() {
+            if witness.var() == v {
+                out.push(c.to_vec());
+            } else {
+                kept.push(witness, c);
+            }
+        }
+        *self = kept;
+    }
+
+    /// Rename the variables of the removed clauses with `map`. The literals of the variables
+    /// mapped to `Var::UNDEF`, which must not be witnesses, are fixed by `value`: the
+    /// clauses where one of them is true are dropped, and the false ones are removed.
+    pub(crate) fn remap(&mut self, map: &VMap<Var>, value: impl Fn(Lit) -> lbool) {
+        let mut kept = Reconstruction::default();
+        let mut lits = vec![];
+        'clauses: for (witness, c) in self.iter() {
+            lits.clear();
+            for &lit in c {
+                let v = map[lit.var()];
+                if v != Var::UNDEF {
+                    lits.push(Lit::new(v, lit.sign()));
+                } else if value(lit) == lbool::TRUE {
+                    continue 'clauses;
+                }
+            }
+            debug_assert_ne!(map[witness.var()], Var::UNDEF);
+            kept.push(Lit::new(map[witness.var()], witness.sign()), &lits);
+        }
+        *self = kept;
+    }
+
+    /// Extend `model`, a model of the simplified problem indexed by variables, so that
+    /// it satisfies the removed clauses.
+    pub(crate) fn extend(&self, model: &mut [lbool]) {
+        for &(x, _) in &self.clauses {
+            if model[x.var().idx() as usize] == lbool::UNDEF {
+                model[x.var().idx() as usize] = lbool::FALSE;
+            }
+        }
+        for (x, c) in self.iter().rev() {
+            let value_lit = |l: Lit| model[l.var().idx() as usize] ^ !l.sign();
+            if !c.iter().any(|&l| value_lit(l) == lbool::TRUE) {
+                model[x.var().idx() as usize] = lbool::new(x.sign());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_extend() {
+        let [a, b, c] = [0, 1, 2].map(|i| Lit::new(Var::from_idx(i), true));
+        // `a` eliminated from `a | b`, `!a | c`, then `b` replaced by `!c`
+        let mut rec = Reconstruction::default();
+        rec.push(a, &[a, b]);
+        rec.push(!a, &[!a, c]);
+        rec.push(b, &[b, c]);
+        rec.push(!b, &[!b, !c]);
+        for val_c in [lbool::TRUE, lbool::FALSE] {
+            let mut model = vec![lbool::UNDEF, lbool::UNDEF, val_c];
+            rec.extend(&mut model);
+            assert_eq!(model[1], val_c ^ true);
+            assert!(rec.iter().all(|(_, c)| c
+                .iter()
+                .any(|&l| model[l.var().idx() as usize] ^ !l.sign() == lbool::TRUE)));
+        }
+
+        let mut out = vec![];
+        rec.take_clauses_of(a.var(), &mut out);
+        assert_eq!(out, vec![vec![a, b], vec![!a, c]]);
+        assert_eq!(rec.iter().count(), 2);
+    }
+}
@@ -0,0 +1,96 @@
+//! Searches in slices of literals, vectorized with the `simd` feature (8 literals at a
+//! time) and scalar otherwise.
+//!
+//! They are used by the subsumption checks of the simplifier, which scan long clauses for
+//! the variables of short ones.
+use crate::clause::{Lit, Var};
+#[cfg(feature = "simd")]
+use wide::{i32x8, u32x8};
+
+/// Number of literals compared at once.
+#[cfg(feature = "simd")]
+const LANES: usize = 8;
+
+#[cfg(feature = "simd")]
+#[inline]
+fn load(chunk: &[Lit]) -> u32x8 {
+    let mut a = [0u32; LANES];
+    a.copy_from_slice(bytemuck::must_cast_slice(chunk));
+    u32x8::new(a)
+}
+
+/// Bit `i` is set iff lane `i` of `mask` is set.
+#[cfg(feature = "simd")]
+#[inline]
+fn move_mask(mask: u32x8) -> u32 {
+    bytemuck::cast::<_, i32x8>(mask).move_mask() as u32
+}
+
+/// Index of the first literal of `lits[from..]` whose variable is not smaller than `v`,
+/// or `lits.len()` if there is none. `lits` must be sorted.
+#[inline]
+pub(crate) fn lower_bound_var(lits: &[Lit], from: usize, v: Var) -> usize {
+    // `lit.var() < v` iff `lit < Lit::new(v, true)`, the smallest literal of `v`
+    let key = Lit::new(v, true);
+    let mut i = from;
+    #[cfg(feature = "simd")]
+    {
+        let k = u32x8::splat(key.idx());
+        while let Some(chunk) = lits.get(i..i + LANES) {
+            // the literals smaller than `key` are a prefix of the chunk
+            let smaller = move_mask(load(chunk).cmp_lt(k));
+            if smaller != (1 << LANES) - 1 {
+                return i + smaller.trailing_ones() as usize;
+            }
+            i += LANES;
+        }
+    }
+    while i < lits.len() && lits[i] < key {
+        i += 1;
+    }
+    i
+}
+
+/// Index of `lit` in `lits`, if it occurs.
+#[inline]
+pub(crate) fn find_lit(lits: &[Lit], lit: Lit) -> Option<usize> {
+    #[cfg(feature = "simd")]
+    {
+        let k = u32x8::splat(lit.idx());
+        let mut chunks = lits.chunks_exact(LANES);
+        for (n, chunk) in chunks.by_ref().enumerate() {
+            let eq = move_mask(load(chunk).cmp_eq(k));
+            if eq != 0 {
+                return Some(n * LANES + eq.trailing_zeros() as usize);
+            }
+        }
+        let rest = chunks.remainder();
+        let offset = lits.len() - rest.len();
+        rest.iter().position(|&x| x == lit).map(|i| offset + i)
+    }
+    #[cfg(not(feature = "simd"))]
+    lits.iter().position(|&x| x == lit)
+}
+
+#[test]
+fn test_simd_search() {
+    use no_std_compat::prelude::v1::*;
+    let mut rng = crate::test_util::Rng::new(9);
+    let mut rand = |n| rng.next(n);
+    for len in 0..40 {
+        let mut lits: Vec<Lit> = (0..len)
+            .map(|_| Lit::new(Var::from_idx(rand(50) as u32), rand(2) == 0))
+            .collect();
+        for _ in 0..20 {
+            let lit = Lit::new(Var::from_idx(rand(52) as u32), rand(2) == 0);
+            assert_eq!(find_lit(&lits, lit), lits.iter().position(|&x| x == lit));
+        }
+        lits.sort_unstable();
+        for _ in 0..20 {
+            let v = Var::from_idx(rand(52) as u32);
+            let from = rand(len as u64 + 1) as usize;
+            let expected = from + lits[from..].iter().take_while(|x| x.var() < v).count();
+            assert_eq!(lower_bound_var(&lits, from, v), expected);
+        }
+    }
+}
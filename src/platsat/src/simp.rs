@@ -0,0 +1,665 @@
+//! Preprocessing by subsumption, self-subsuming resolution and bounded variable
+//! elimination, as in MiniSat's `SimpSolver`, by blocked clause elimination and by
+//! bounded variable addition
+use crate::clause::{lbool, LMap, Lit, VMap, Var};
+use crate::intmap::AsIndex;
+use crate::reconstruct::Reconstruction;
+use crate::simd;
+use core::mem;
+use no_std_compat::prelude::v1::*;
+use std::collections::BinaryHeap;
+
+/// Maximum number of clauses visited by [`Simplifier::bva`].
+const BVA_STEPS: u64 = 10_000_000;
+
+/// Limits on the work done by the [`Simplifier`].
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct SimpLimits {
+    /// Variables with a resolvent longer than this are not eliminated (`-1` means no limit).
+    pub(crate) clause_lim: i32,
+    /// Number of clauses that the elimination of a variable may add.
+    pub(crate) grow: i32,
+    /// Clauses are not used for subsumption if the occurrence lists of all their
+    /// variables are longer than this (`-1` means no limit).
+    pub(crate) subsumption_lim: i32,
+}
+
+#[derive(Debug)]
+struct SClause {
+    /// Sorted literals, none of which is assigned.
+    lits: Vec<Lit>,
+    removed: bool,
+    queued: bool,
+}
+
+/// Result of [`subsumes`].
+enum Subsumption {
+    None,
+    /// The first clause subsumes the second one.
+    Subsumes,
+    /// The first clause contains this literal, and the second one its negation;
+    /// the negation can be removed from the second one.
+    Strengthens(Lit),
+}
+
+/// Does `c` subsume `d`, possibly after flipping one literal? Both must be sorted.
+fn subsumes(c: &[Lit], d: &[Lit]) -> Subsumption {
+    let mut flipped = None;
+    let mut j = 0;
+    for &x in c {
+        j = simd::lower_bound_var(d, j, x.var());
+        if j == d.len() || d[j].var() != x.var() {
+            return Subsumption::None;
+        } else if d[j] != x {
+            if flipped.is_some() {
+                return Subsumption::None;
+            }
+            flipped = Some(x);
+        }
+        j += 1;
+    }
+    match flipped {
+        None => Subsumption::Subsumes,
+        Some(x) => Subsumption::Strengthens(x),
+    }
+}
+
+/// If `d` is `c` with `l` replaced by another literal, return this literal. Both
+/// must be sorted.
+fn replaced(c: &[Lit], l: Lit, d: &[Lit]) -> Option<Lit> {
+    if c.len() != d.len() {
+        return None;
+    }
+    let mut rest = c.iter().filter(|&&x| x != l).peekable();
+    let mut other = None;
+    for &x in d {
+        if rest.peek() == Some(&&x) {
+            rest.next();
+        } else if other.is_none() {
+            other = Some(x);
+        } else {
+            return None;
+        }
+    }
+    other.filter(|&x| x != l)
+}
+
+/// Put in `out` the resolvent of the sorted clauses `p` and `n` on `v`, and return
+/// `false` if it is a tautology.
+fn resolve(p: &[Lit], n: &[Lit], v: Var, out: &mut Vec<Lit>) -> bool {
+    out.clear();
+    let (mut i, mut j) = (0, 0);
+    while i < p.len() || j < n.len() {
+        let x = if j == n.len() || (i < p.len() && p[i] <= n[j]) {
+            i += 1;
+            p[i - 1]
+        } else {
+            j += 1;
+            n[j - 1]
+        };
+        if x.var() == v {
+            continue;
+        }
+        match out.last() {
+            Some(&y) if y == x => (),
+            Some(&y) if y == !x => return false,
+            _ => out.push(x),
+        }
+    }
+    true
+}
+
+/// Clause database on which the simplifications are done, outside of the solver.
+///
+/// The clauses given to the simplifier must not contain assigned literals. Units
+/// that are found are assigned and propagated immediately, so this remains true.
+pub(crate) struct Simplifier {
+    num_vars: u32,
+    clauses: Vec<SClause>,
+    /// Clauses in which each literal occurs, which may also contain removed
+    /// clauses and clauses that lost the literal.
+    occs: LMap<Vec<u32>>,
+    /// Number of live clauses in which each literal occurs.
+    n_occ: LMap<u32>,
+    value: VMap<lbool>,
+    eliminable: VMap<bool>,
+    /// Units found during the simplification, in order.
+    pub(crate) units: Vec<Lit>,
+    units_head: usize,
+    /// Removed clauses of the eliminated variables and blocked clauses, with the literal
+    /// of the variable they contain or on which they are blocked, in order of removal.
+    pub(crate) elim: Reconstruction,
+    pub(crate) eliminated: Vec<Var>,
+    /// Clauses to check for subsumption.
+    queue: Vec<u32>,
+    /// Variables to consider for elimination.
+    touched: VMap<bool>,
+    touched_list: Vec<Var>,
+    /// Steps `(is_addition, clause)` of the DRAT proof, if one is needed.
+    pub(crate) proof: Option<Vec<(bool, Vec<Lit>)>>,
+    pub(crate) ok: bool,
+    limits: SimpLimits,
+    tmp: Vec<Lit>,
+}
+
+impl Simplifier {
+    pub(crate) fn new(num_vars: u32, limits: SimpLimits, proof: bool) -> Self {
+        let mut simp = Simplifier {
+            num_vars,
+            clauses: vec![],
+            occs: LMap::new(),
+            n_occ: LMap::new(),
+            value: VMap::new(),
+            eliminable: VMap::new(),
+            units: vec![],
+            units_head: 0,
+            elim: Reconstruction::default(),
+            eliminated: vec![],
+            queue: vec![],
+            touched: VMap::new(),
+            touched_list: vec![],
+            proof: proof.then(Vec::new),
+            ok: true,
+            limits,
+            tmp: vec![],
+        };
+        if num_vars > 0 {
+            let last = Var::from_idx(num_vars - 1);
+            simp.occs.reserve_default(Lit::new(last, false));
+            simp.n_occ.reserve(Lit::new(last, false), 0);
+            simp.value.reserve(last, lbool::UNDEF);
+            simp.eliminable.reserve(last, false);
+            simp.touched.reserve(last, false);
+        }
+        simp
+    }
+
+    /// Allow the elimination of `v`, which must be unassigned.
+    pub(crate) fn set_eliminable(&mut self, v: Var) {
+        self.eliminable[v] = true;
+        self.touch(v);
+    }
+
+    pub(crate) fn num_vars(&self) -> u32 {
+        self.num_vars
+    }
+
+    /// Add a new variable, which is not eliminable.
+    fn new_var(&mut self) -> Var {
+        let v = Var::from_idx(self.num_vars);
+        self.num_vars += 1;
+        self.occs.reserve_default(Lit::new(v, false));
+        self.n_occ.reserve(Lit::new(v, false), 0);
+        self.value.reserve(v, lbool::UNDEF);
+        self.eliminable.reserve(v, false);
+        self.touched.reserve(v, false);
+        v
+    }
+
+    /// Add a clause of the problem, with no assigned literal and no duplicate.
+    pub(crate) fn add_clause(&mut self, lits: &[Lit]) {
+        debug_assert!(lits.len() > 1);
+        let mut lits = lits.to_vec();
+        lits.sort_unstable();
+        let i = self.clauses.len() as u32;
+        for &l in &lits {
+            self.occs[l].push(i);
+            self.n_occ[l] += 1;
+            self.touch(l.var());
+        }
+        self.clauses.push(SClause {
+            lits,
+            removed: false,
+            queued: true,
+        });
+        self.queue.push(i);
+    }
+
+    /// The clauses that were not removed.
+    pub(crate) fn clauses(&self) -> impl Iterator<Item = &[Lit]> {
+        self.clauses
+            .iter()
+            .filter(|c| !c.removed)
+            .map(|c| &c.lits[..])
+    }
+
+    fn log(&mut self, add: bool, lits: &[Lit]) {
+        if let Some(proof) = &mut self.proof {
+            proof.push((add, lits.to_vec()));
+        }
+    }
+
+    fn touch(&mut self, v: Var) {
+        if !self.touched[v] {
+            self.touched[v] = true;
+            self.touched_list.push(v);
+        }
+    }
+
+    /// Live clauses containing `l`, after cleaning its occurrence list.
+    fn live_occs(&mut self, l: Lit) -> Vec<u32> {
+        let clauses = &self.clauses;
+        self.occs[l].retain(|&i| {
+            let c = &clauses[i as usize];
+            !c.removed && c.lits.binary_search(&l).is_ok()
+        });
+        self.occs[l].clone()
+    }
+
+    fn remove_clause(&mut self, i: u32, log: bool) {
+        let c = &mut self.clauses[i as usize];
+        debug_assert!(!c.removed);
+        c.removed = true;
+        let lits = core::mem::take(&mut c.lits);
+        for &l in &lits {
+            self.n_occ[l] -= 1;
+            self.touch(l.var());
+        }
+        if log {
+            self.log(false, &lits);
+        }
+        self.clauses[i as usize].lits = lits;
+    }
+
+    /// Remove `l` from clause `i`.
+    fn strengthen(&mut self, i: u32, l: Lit) {
+        let c = &mut self.clauses[i as usize];
+        let old = c.lits.clone();
+        c.lits.retain(|&x| x != l);
+        let lits = c.lits.clone();
+        self.n_occ[l] -= 1;
+        self.touch(l.var());
+        self.log(true, &lits);
+        self.log(false, &old);
+        match lits[..] {
+            [] => {
+                self.clauses[i as usize].removed = true;
+                self.ok = false;
+            }
+            [unit] => {
+                self.clauses[i as usize].removed = true;
+                self.n_occ[unit] -= 1;
+                self.assign(unit);
+            }
+            _ => {
+                let c = &mut self.clauses[i as usize];
+                if !c.queued {
+                    c.queued = true;
+                    self.queue.push(i);
+                }
+            }
+        }
+    }
+
+    /// Add a clause derived by resolution.
+    fn add_derived(&mut self, lits: &[Lit]) {
+        self.log(true, lits);
+        match *lits {
+            [] => self.ok = false,
+            [unit] => self.assign(unit),
+            _ => self.add_clause(lits),
+        }
+    }
+
+    fn assign(&mut self, l: Lit) {
+        if self.value[l.var()] == lbool::UNDEF {
+            self.value[l.var()] = lbool::new(l.sign());
+            self.units.push(l);
+        } else if self.value[l.var()] != lbool::new(l.sign()) {
+            self.log(true, &[]);
+            self.ok = false;
+        }
+    }
+
+    /// Remove the clauses satisfied by the new units, and their negations from the others.
+    fn propagate_units(&mut self) {
+        while self.ok && self.units_head < self.units.len() {
+            let l = self.units[self.units_head];
+            self.units_head += 1;
+            for i in self.live_occs(l) {
+                self.remove_clause(i, true);
+            }
+            for i in self.live_occs(!l) {
+                if !self.ok {
+                    return;
+                }
+                self.strengthen(i, !l);
+            }
+        }
+    }
+
+    /// Remove the clauses subsumed by the queued ones, and strengthen those that can
+    /// be by self-subsuming resolution.
+    fn subsume_queue(&mut self) {
+        while let Some(i) = self.queue.pop() {
+            self.clauses[i as usize].queued = false;
+            self.propagate_units();
+            if !self.ok {
+                return;
+            }
+            if self.clauses[i as usize].removed {
+                continue;
+            }
+            // the variable with the fewest occurrences
+            let n_occs = |l: Lit| self.n_occ[l] + self.n_occ[!l];
+            let c = &self.clauses[i as usize].lits;
+            let best = c.iter().copied().min_by_key(|&l| n_occs(l)).unwrap();
+            let lim = self.limits.subsumption_lim;
+            if lim >= 0 && n_occs(best) > lim as u32 {
+                continue;
+            }
+            for l in [best, !best] {
+                for j in self.live_occs(l) {
+                    let (c, d) = (&self.clauses[i as usize], &self.clauses[j as usize]);
+                    if c.removed {
+                        break;
+                    } else if j == i || d.removed || d.lits.len() < c.lits.len() {
+                        continue;
+                    }
+                    match subsumes(&c.lits, &d.lits) {
+                        Subsumption::None => (),
+                        Subsumption::Subsumes => self.remove_clause(j, true),
+                        Subsumption::Strengthens(x) => self.strengthen(j, !x),
+                    }
+                    if !self.ok {
+                        return;
+                    }
+                }
+            }
+        }
+        self.propagate_units();
+    }
+
+    /// Clause `lits` (with no duplicate), if it is live.
+    fn find_clause(&self, lits: &[Lit]) -> Option<u32> {
+        let mut lits = lits.to_vec();
+        lits.sort_unstable();
+        self.occs[lits[0]].iter().copied().find(|&i| {
+            let c = &self.clauses[i as usize];
+            !c.removed && c.lits == lits
+        })
+    }
+
+    /// Clauses that define `v` as an AND, ITE or XOR gate of other variables, among the
+    /// live clauses `pos` and `neg` that contain `v` and `!v`.
+    fn find_gate(&self, v: Var, pos: &[u32], neg: &[u32]) -> Option<Vec<u32>> {
+        let lits = |i: u32| &self.clauses[i as usize].lits[..];
+        // AND gate `l = a_1 & ... & a_k`: `!l | a_i` for each `i`, and `l | !a_1 | ... | !a_k`
+        // (an OR gate for `!l`)
+        for (l, l_occs, not_l_occs) in [
+            (Lit::new(v, true), pos, neg),
+            (Lit::new(v, false), neg, pos),
+        ] {
+            let mut bins: Vec<(Lit, u32)> = not_l_occs
+                .iter()
+                .filter(|&&i| lits(i).len() == 2)
+                .map(|&i| (lits(i).iter().copied().find(|&x| x != !l).unwrap(), i))
+                .collect();
+            bins.sort_unstable();
+            let bin = |x: Lit| {
+                let k = bins.binary_search_by_key(&x, |b| b.0).ok()?;
+                Some(bins[k].1)
+            };
+            for &i in l_occs {
+                if lits(i).iter().all(|&x| x == l || bin(!x).is_some()) {
+                    debug!("AND gate {:?} = {:?}", l, lits(i));
+                    let mut gate = vec![i];
+                    gate.extend(
+                        lits(i)
+                            .iter()
+                            .filter(|&&x| x != l)
+                            .map(|&x| bin(!x).unwrap()),
+                    );
+                    return Some(gate);
+                }
+            }
+        }
+        // ITE gate `v = c ? t : e`: `!v | !c | t`, `!v | c | e`, `v | !c | !t` and `v | c | !e`,
+        // which is an XOR gate `v = c ^ e` when `t = !e`
+        let v = Lit::new(v, true);
+        let ternary: Vec<u32> = neg
+            .iter()
+            .copied()
+            .filter(|&i| lits(i).len() == 3)
+            .collect();
+        let others = |i: u32| {
+            let mut it = lits(i).iter().copied().filter(|&x| x != !v);
+            [it.next().unwrap(), it.next().unwrap()]
+        };
+        for &i in &ternary {
+            let [x, y] = others(i);
+            for (not_c, t) in [(x, y), (y, x)] {
+                for &j in &ternary {
+                    let [x2, y2] = others(j);
+                    let e = match () {
+                        _ if x2 == !not_c => y2,
+                        _ if y2 == !not_c => x2,
+                        _ => continue,
+                    };
+                    let k1 = self.find_clause(&[v, not_c, !t]);
+                    let k2 = self.find_clause(&[v, !not_c, !e]);
+                    if let (Some(k1), Some(k2)) = (k1, k2) {
+                        debug!("{} gate {:?}", if t == !e { "XOR" } else { "ITE" }, v);
+                        return Some(vec![i, j, k1, k2]);
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    /// Eliminate `v` by resolution if this does not add too many clauses.
+    ///
+    /// If `v` is the output of a gate, only the resolvents of the clauses of the gate with
+    /// the other ones are needed: the others are tautologies or are implied by these.
+    fn eliminate_var(&mut self, v: Var) {
+        if !self.eliminable[v] || self.value[v] != lbool::UNDEF {
+            return;
+        }
+        let pos = self.live_occs(Lit::new(v, true));
+        let neg = self.live_occs(Lit::new(v, false));
+        if pos.is_empty() && neg.is_empty() {
+            return;
+        }
+        let gate = self.find_gate(v, &pos, &neg).unwrap_or_default();
+        let max_resolvents = pos.len() + neg.len() + self.limits.grow.max(0) as usize;
+        let mut resolvents = vec![];
+        for &p in &pos {
+            for &n in &neg {
+                if !gate.is_empty() && gate.contains(&p) == gate.contains(&n) {
+                    continue;
+                }
+                let (p, n) = (&self.clauses[p as usize], &self.clauses[n as usize]);
+                if !resolve(&p.lits, &n.lits, v, &mut self.tmp) {
+                    continue;
+                }
+                let lim = self.limits.clause_lim;
+                if resolvents.len() == max_resolvents || (lim >= 0 && self.tmp.len() > lim as usize)
+                {
+                    return;
+                }
+                resolvents.push(self.tmp.clone());
+            }
+        }
+        self.eliminable[v] = false;
+        self.eliminated.push(v);
+        for (i, sign) in pos
+            .iter()
+            .map(|&i| (i, true))
+            .chain(neg.iter().map(|&i| (i, false)))
+        {
+            self.elim
+                .push(Lit::new(v, sign), &self.clauses[i as usize].lits);
+            // the clause stays in the proof, in case `v` is restored
+            self.remove_clause(i, false);
+        }
+        for r in resolvents {
+            self.add_derived(&r);
+            if !self.ok {
+                return;
+            }
+        }
+    }
+
+    /// Remove the clauses that are blocked on a literal `l` of an eliminable variable:
+    /// their resolvents on `l` with all the clauses containing `!l` are tautologies.
+    ///
+    /// The removed clauses are kept with `l`, which is made true when a model does
+    /// not satisfy them.
+    pub(crate) fn eliminate_blocked(&mut self) {
+        for v in (0..self.num_vars).map(Var::from_idx) {
+            if !self.eliminable[v] || self.value[v] != lbool::UNDEF {
+                continue;
+            }
+            for l in [Lit::new(v, true), Lit::new(v, false)] {
+                let lim = self.limits.subsumption_lim;
+                if lim >= 0 && self.n_occ[!l] > lim as u32 {
+                    continue;
+                }
+                let neg = self.live_occs(!l);
+                for i in self.live_occs(l) {
+                    let c = &self.clauses[i as usize].lits;
+                    let blocked = neg
+                        .iter()
+                        .all(|&j| !resolve(c, &self.clauses[j as usize].lits, v, &mut self.tmp));
+                    if blocked {
+                        self.elim.push(l, c);
+                        self.remove_clause(i, false);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Bounded variable addition: replace the clauses `l_i | C_j`, for all the literals
+    /// `l_i` of a set `L` and all the clauses `C_j` of a set `M`, by the clauses `!x | l_i`
+    /// and `x | C_j` over a new variable `x`, when this removes clauses.
+    ///
+    /// The sets are grown greedily from the most frequent literals, as in SimpleBVA.
+    pub(crate) fn bva(&mut self) {
+        self.propagate_units();
+        let reduction = |n_lits: usize, n_clauses: usize| {
+            (n_lits * n_clauses) as i64 - n_lits as i64 - n_clauses as i64
+        };
+        let mut steps = 0;
+        let mut heap: BinaryHeap<(u32, Lit)> = (0..2 * self.num_vars as usize)
+            .map(Lit::from_index)
+            .filter(|&l| self.n_occ[l] > 0)
+            .map(|l| (self.n_occ[l], l))
+            .collect();
+        while let Some((n, l)) = heap.pop() {
+            if !self.ok || steps > BVA_STEPS {
+                return;
+            } else if n != self.n_occ[l] {
+                if self.n_occ[l] > 0 {
+                    heap.push((self.n_occ[l], l));
+                }
+                continue;
+            }
+            let mut lits = vec![l];
+            let mut cls = self.live_occs(l);
+            // for each clause of `cls`, the clauses where `l` is replaced by `lits[1..]`
+            let mut matched = vec![vec![]; cls.len()];
+            loop {
+                // `(l2, k, j)`: clause `j` is clause `cls[k]` with `l` replaced by `l2`
+                let mut pairs = vec![];
+                for (k, &i) in cls.iter().enumerate() {
+                    let c = &self.clauses[i as usize].lits;
+                    let n_occ = &self.n_occ;
+                    let lmin = *c
+                        .iter()
+                        .filter(|&&x| x != l)
+                        .min_by_key(|&&x| n_occ[x])
+                        .unwrap();
+                    for j in self.live_occs(lmin) {
+                        steps += 1;
+                        let c = &self.clauses[i as usize].lits;
+                        match replaced(c, l, &self.clauses[j as usize].lits) {
+                            Some(l2) if !lits.contains(&l2) => pairs.push((l2, k, j)),
+                            _ => (),
+                        }
+                    }
+                }
+                pairs.sort_unstable();
+                pairs.dedup_by_key(|&mut (l2, k, _)| (l2, k));
+                // the replacement that occurs with the most clauses
+                let mut best = (0, Lit::UNDEF);
+                for group in pairs.chunk_by(|a, b| a.0 == b.0) {
+                    best = best.max((group.len(), group[0].0));
+                }
+                let (count, lmax) = best;
+                if count == 0
+                    || reduction(lits.len() + 1, count) <= reduction(lits.len(), cls.len())
+                {
+                    break;
+                }
+                let mut new_cls = vec![];
+                let mut new_matched = vec![];
+                for &(l2, k, j) in &pairs {
+                    if l2 == lmax {
+                        new_cls.push(cls[k]);
+                        let mut m = mem::take(&mut matched[k]);
+                        m.push(j);
+                        new_matched.push(m);
+                    }
+                }
+                lits.push(lmax);
+                cls = new_cls;
+                matched = new_matched;
+            }
+            if reduction(lits.len(), cls.len()) <= 0 {
+                continue;
+            }
+            let x = Lit::new(self.new_var(), true);
+            debug!("bva {:?} on {:?} with {} clauses", x, lits, cls.len());
+            // `!x | l_i` is RAT on `!x` as `x` is new, and then `x | C_j` is RAT on `x`
+            for &li in &lits {
+                self.log(true, &[!x, li]);
+                self.add_clause(&[!x, li]);
+            }
+            for &i in &cls {
+                let mut c = vec![x];
+                c.extend(self.clauses[i as usize].lits.iter().filter(|&&y| y != l));
+                self.log(true, &c);
+                self.add_clause(&c);
+            }
+            for (&i, m) in cls.iter().zip(&matched) {
+                for &j in Some(&i).into_iter().chain(m) {
+                    if !self.clauses[j as usize].removed {
+                        self.remove_clause(j, true);
+                    }
+                }
+            }
+            for y in lits.into_iter().chain([x, !x]) {
+                heap.push((self.n_occ[y], y));
+            }
+        }
+    }
+
+    /// Simplify the clauses until no variable can be eliminated anymore.
+    pub(crate) fn run(&mut self) {
+        loop {
+            self.subsume_queue();
+            if !self.ok || self.touched_list.is_empty() {
+                return;
+            }
+            let mut candidates = core::mem::take(&mut self.touched_list);
+            for &v in &candidates {
+                self.touched[v] = false;
+            }
+            candidates.retain(|&v| self.eliminable[v]);
+            let cost = |v: Var| {
+                let (p, n) = (Lit::new(v, true), Lit::new(v, false));
+                self.n_occ[p] as u64 * self.n_occ[n] as u64
+            };
+            candidates.sort_by_cached_key(|&v| cost(v));
+            for v in candidates {
+                self.eliminate_var(v);
+                self.subsume_queue();
+                if !self.ok {
+                    return;
+                }
+            }
+        }
+    }
+}
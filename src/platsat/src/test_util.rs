@@ -0,0 +1,16 @@
+//! Helpers shared by the unit tests
+
+/// Tiny deterministic random number generator (linear congruential).
+pub(crate) struct Rng(u64);
+
+impl Rng {
+    pub(crate) fn new(seed: u64) -> Self {
+        Rng(seed)
+    }
+
+    /// A number in `0..n`.
+    pub(crate) fn next(&mut self, n: u64) -> u64 {
+        self.0 = self.0.wrapping_mul(6364136223846793005).wrapping_add(1);
+        (self.0 >> 33) % n
+    }
+}
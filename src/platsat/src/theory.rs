@@ -1,10 +1,18 @@
-use {crate::clause::Lit, std::default::Default};
+use no_std_compat::prelude::v1::*;
+use {
+    crate::clause::{lbool, Lit, VMap},
+    std::default::Default,
+};
 
 use crate::core::ExplainTheoryArg;
 /// Argument passed to the Theory
 pub use crate::core::TheoryArg;
 
 /// Theory that parametrizes the solver and can react on events.
+///
+/// The trait is dyn-compatible, and implemented by `Box<dyn Theory>` and
+/// `&mut dyn Theory`, so a theory can be chosen (or built from several ones) at runtime
+/// without a copy of the solver for each theory type.
 pub trait Theory {
     /// Check the model candidate `model` thoroughly.
     ///
@@ -23,6 +31,17 @@ pub trait Theory {
     /// Number of levels
     fn n_levels(&self) -> usize;
 
+    /// Notification that the solver backtracked to decision level `new_level`, right
+    /// after `pop_levels`.
+    ///
+    /// The literals of the trail (`acts.model()`) from index `first_removed` on were
+    /// unassigned, so the theory can undo its work for each of them. The ones that
+    /// chronological backtracking keeps are put back from that index, and notified
+    /// again by [`on_assign`](Self::on_assign).
+    ///
+    /// The default implementation does nothing.
+    fn on_backtrack(&mut self, _new_level: u32, _first_removed: usize) {}
+
     /// Check partial model (best effort).
     ///
     /// The whole partial model so far is `acts.model()`,
@@ -36,6 +55,31 @@ pub trait Theory {
     /// The default implementation just returns without doing anything.
     fn partial_check(&mut self, _acts: &mut TheoryArg) {}
 
+    /// Notification that `lit` was assigned, `is_decision` being true if it is a
+    /// decision (or an assumption) rather than a propagation.
+    ///
+    /// The literals are notified in the order of the trail, before each call to
+    /// `partial_check` or `final_check`, so the theory can update its state eagerly
+    /// instead of diffing `acts.model()`. The literals kept out of order on the trail by
+    /// chronological backtracking are notified again after backtracking. The literals
+    /// assigned at level 0 are only notified once, so the same theory should be given to
+    /// every call to the solver.
+    ///
+    /// The default implementation does nothing.
+    fn on_assign(&mut self, _lit: Lit, _is_decision: bool) {}
+
+    /// Choose the next decision literal, before the branching heuristic of the solver.
+    ///
+    /// This is called whenever the solver is about to make a decision, once the
+    /// assumptions are all decided, so the theory can force the case splits it deems
+    /// relevant. The returned literal is ignored if it is already assigned, or if its
+    /// variable was eliminated, in which case the solver picks the decision itself.
+    ///
+    /// The default implementation returns `None`, leaving the choice to the solver.
+    fn decide(&mut self, _acts: &TheoryArg) -> Option<Lit> {
+        None
+    }
+
     /// If the theory uses `TheoryArgument::propagate`, it must implement
     /// this function to explain the propagations.
     ///
@@ -45,6 +89,9 @@ pub trait Theory {
     /// ## Returns
     /// - `lits` a clause that is a tautology of the theory (ie a lemma).
     ///   `lits[0]` must be `p`, and all other elements in `lits` must be false in the current model
+    ///
+    /// The explanation may introduce fresh literals and lemmas through `st`, and contain
+    /// the negation of literals defined by [`ExplainTheoryArg::define_and`].
     fn explain_propagation_clause(&mut self, p: Lit, st: &mut ExplainTheoryArg) -> &[Lit];
 
     /// Similar to `explain_propagation_clause` but theories should prefer larger older explanations
@@ -53,13 +100,61 @@ pub trait Theory {
     /// but `explain_propagation_clause_final` may as well explain using `[a, b]` since otherwise
     /// it would just be asked to explain `c` anyway.
     ///
+    /// This is used when computing the unsat core (see [`SolverInterface::unsat_core`](crate::interface::SolverInterface::unsat_core)),
+    /// where the explanation is only used to find the assumptions that led to a conflict.
+    ///
     /// The default implementation just calls `explain_propagation`
     fn explain_propagation_clause_final(&mut self, p: Lit, st: &mut ExplainTheoryArg) -> &[Lit] {
         self.explain_propagation_clause(p, st)
     }
 }
 
+macro_rules! forward_theory {
+    ($t:ty) => {
+        impl<T: Theory + ?Sized> Theory for $t {
+            fn final_check(&mut self, acts: &mut TheoryArg) {
+                (**self).final_check(acts)
+            }
+            fn create_level(&mut self) {
+                (**self).create_level()
+            }
+            fn pop_levels(&mut self, n: usize) {
+                (**self).pop_levels(n)
+            }
+            fn n_levels(&self) -> usize {
+                (**self).n_levels()
+            }
+            fn on_backtrack(&mut self, new_level: u32, first_removed: usize) {
+                (**self).on_backtrack(new_level, first_removed)
+            }
+            fn partial_check(&mut self, acts: &mut TheoryArg) {
+                (**self).partial_check(acts)
+            }
+            fn on_assign(&mut self, lit: Lit, is_decision: bool) {
+                (**self).on_assign(lit, is_decision)
+            }
+            fn decide(&mut self, acts: &TheoryArg) -> Option<Lit> {
+                (**self).decide(acts)
+            }
+            fn explain_propagation_clause(&mut self, p: Lit, st: &mut ExplainTheoryArg) -> &[Lit] {
+                (**self).explain_propagation_clause(p, st)
+            }
+            fn explain_propagation_clause_final(
+                &mut self,
+                p: Lit,
+                st: &mut ExplainTheoryArg,
+            ) -> &[Lit] {
+                (**self).explain_propagation_clause_final(p, st)
+            }
+        }
+    };
+}
+
+forward_theory!(Box<T>);
+forward_theory!(&mut T);
+
 /// Trivial theory that does nothing
+#[derive(Clone, Debug)]
 pub struct EmptyTheory(usize);
 
 impl EmptyTheory {
@@ -92,3 +187,118 @@ impl Theory for EmptyTheory {
         unreachable!()
     }
 }
+
+/// Theory made of two theories, that both receive the events of the solver.
+///
+/// `first` is called before `second`, which is skipped if `first` raised a conflict.
+/// The solver propagates the literals of each theory before calling them again, so
+/// they exchange their propagations through the trail (Nelson-Oppen style), and the
+/// explanations are asked to the theory that propagated the literal.
+///
+/// More theories can be combined by nesting, as in
+/// `TheoryCombiner<A, TheoryCombiner<B, C>>`.
+#[derive(Clone, Debug)]
+pub struct TheoryCombiner<T1, T2> {
+    /// The theory called first.
+    pub first: T1,
+    /// The theory called after `first`.
+    pub second: T2,
+    /// Was the last propagation of each variable done by `second`?
+    by_second: VMap<bool>,
+}
+
+impl<T1: Theory, T2: Theory> TheoryCombiner<T1, T2> {
+    /// Combine `first` and `second`.
+    pub fn new(first: T1, second: T2) -> Self {
+        TheoryCombiner {
+            first,
+            second,
+            by_second: VMap::new(),
+        }
+    }
+
+    /// Call `check` on each theory, and record the owner of their propagations.
+    fn check_both(
+        &mut self,
+        acts: &mut TheoryArg,
+        check1: impl FnOnce(&mut T1, &mut TheoryArg),
+        check2: impl FnOnce(&mut T2, &mut TheoryArg),
+    ) {
+        let len = acts.model().len();
+        check1(&mut self.first, acts);
+        self.set_owner(&acts.model()[len..], false);
+        if acts.is_ok() {
+            let len = acts.model().len();
+            check2(&mut self.second, acts);
+            self.set_owner(&acts.model()[len..], true);
+        }
+    }
+
+    fn set_owner(&mut self, props: &[Lit], second: bool) {
+        for &lit in props {
+            self.by_second.reserve(lit.var(), false);
+            self.by_second[lit.var()] = second;
+        }
+    }
+
+    fn is_second(&self, p: Lit) -> bool {
+        self.by_second.has(p.var()) && self.by_second[p.var()]
+    }
+}
+
+impl<T1: Theory, T2: Theory> Theory for TheoryCombiner<T1, T2> {
+    fn final_check(&mut self, acts: &mut TheoryArg) {
+        self.check_both(
+            acts,
+            |t, acts| t.final_check(acts),
+            |t, acts| t.final_check(acts),
+        )
+    }
+    fn create_level(&mut self) {
+        self.first.create_level();
+        self.second.create_level();
+    }
+    fn pop_levels(&mut self, n: usize) {
+        self.first.pop_levels(n);
+        self.second.pop_levels(n);
+    }
+    fn n_levels(&self) -> usize {
+        debug_assert_eq!(self.first.n_levels(), self.second.n_levels());
+        self.first.n_levels()
+    }
+    fn on_backtrack(&mut self, new_level: u32, first_removed: usize) {
+        self.first.on_backtrack(new_level, first_removed);
+        self.second.on_backtrack(new_level, first_removed);
+    }
+    fn partial_check(&mut self, acts: &mut TheoryArg) {
+        self.check_both(
+            acts,
+            |t, acts| t.partial_check(acts),
+            |t, acts| t.partial_check(acts),
+        )
+    }
+    fn on_assign(&mut self, lit: Lit, is_decision: bool) {
+        self.first.on_assign(lit, is_decision);
+        self.second.on_assign(lit, is_decision);
+    }
+    fn decide(&mut self, acts: &TheoryArg) -> Option<Lit> {
+        let first = self.first.decide(acts);
+        first
+            .filter(|&lit| acts.value_lit(lit) == lbool::UNDEF)
+            .or_else(|| self.second.decide(acts))
+    }
+    fn explain_propagation_clause(&mut self, p: Lit, st: &mut ExplainTheoryArg) -> &[Lit] {
+        if self.is_second(p) {
+            self.second.explain_propagation_clause(p, st)
+        } else {
+            self.first.explain_propagation_clause(p, st)
+        }
+    }
+    fn explain_propagation_clause_final(&mut self, p: Lit, st: &mut ExplainTheoryArg) -> &[Lit] {
+        if self.is_second(p) {
+            self.second.explain_propagation_clause_final(p, st)
+        } else {
+            self.first.explain_propagation_clause_final(p, st)
+        }
+    }
+}
@@ -0,0 +1,177 @@
+//! Local search (ProbSAT), to find assignments that falsify few clauses, which are
+//! used as phases by the CDCL search
+use crate::clause::Lit;
+use crate::intmap::AsIndex;
+use no_std_compat::prelude::v1::*;
+
+/// Look for an assignment falsifying as few clauses as possible, starting from `phases`,
+/// by flipping a variable of a falsified clause at a time (ProbSAT).
+///
+/// `clauses` holds the literals of non-empty clauses, each one followed by `Lit::UNDEF`,
+/// over variables smaller than `phases.len()`. The best assignment found is written to
+/// `phases`, the value of `v` being `phases[v.idx()]`.
+/// Returns the number of clauses it falsifies, and the number of ticks (visited
+/// occurrences) used, which is about `max_ticks` at most.
+pub(crate) fn walk(
+    clauses: &[Lit],
+    phases: &mut [bool],
+    max_ticks: u64,
+    seed: u64,
+) -> (usize, u64) {
+    // bounds of each clause in `clauses`
+    let mut bounds: Vec<(u32, u32)> = vec![];
+    let mut start = 0;
+    for (i, &lit) in clauses.iter().enumerate() {
+        if lit == Lit::UNDEF {
+            debug_assert!(start < i, "empty clause");
+            bounds.push((start as u32, i as u32));
+            start = i + 1;
+        }
+    }
+    let mut occs: Vec<Vec<u32>> = vec![vec![]; 2 * phases.len()];
+    for (ci, &(b, e)) in bounds.iter().enumerate() {
+        for &lit in &clauses[b as usize..e as usize] {
+            occs[lit.as_index()].push(ci as u32);
+        }
+    }
+
+    // number of true literals of each clause, and the falsified clauses with their
+    // position in `unsat`
+    let mut num_true: Vec<u32> = bounds
+        .iter()
+        .map(|&(b, e)| {
+            let lits = &clauses[b as usize..e as usize];
+            lits.iter()
+                .filter(|&&l| phases[l.var().idx() as usize] == l.sign())
+                .count() as u32
+        })
+        .collect();
+    let mut unsat: Vec<u32> = vec![];
+    let mut pos: Vec<u32> = vec![u32::MAX; bounds.len()];
+    for (ci, &n) in num_true.iter().enumerate() {
+        if n == 0 {
+            pos[ci] = unsat.len() as u32;
+            unsat.push(ci as u32);
+        }
+    }
+
+    // the probability to flip a variable is proportional to `cb^-breaks`, where `breaks`
+    // is the number of clauses it would falsify, and `cb` grows with the clause size
+    let avg_len = (clauses.len() - bounds.len()) as f64 / bounds.len().max(1) as f64;
+    let cb = match (avg_len + 0.5) as u32 {
+        0..=3 => 2.5,
+        4 => 3.0,
+        5 => 3.7,
+        6 => 5.1,
+        _ => 7.4,
+    };
+    let mut weights: Vec<f64> = vec![1.0; 64];
+    for b in 1..64 {
+        weights[b] = weights[b - 1] / cb;
+    }
+
+    let mut rng = seed | 1;
+    let mut rand = move || {
+        // xorshift64
+        rng ^= rng << 13;
+        rng ^= rng >> 7;
+        rng ^= rng << 17;
+        rng
+    };
+    let mut best = unsat.len();
+    // variables flipped since the best assignment
+    let mut flipped: Vec<u32> = vec![];
+    let mut scores: Vec<f64> = vec![];
+    let mut ticks = 0;
+    while !unsat.is_empty() && ticks < max_ticks {
+        let ci = unsat[(rand() % unsat.len() as u64) as usize] as usize;
+        let lits = &clauses[bounds[ci].0 as usize..bounds[ci].1 as usize];
+        scores.clear();
+        let mut sum = 0.0;
+        for &lit in lits {
+            // flipping makes `lit` true, and breaks the clauses where `!lit` is the only
+            // true literal
+            let occ = &occs[(!lit).as_index()];
+            let breaks = occ.iter().filter(|&&cj| num_true[cj as usize] == 1).count();
+            ticks += occ.len() as u64 + 1;
+            let w = weights[breaks.min(63)];
+            sum += w;
+            scores.push(w);
+        }
+        let mut r = (rand() >> 11) as f64 / (1u64 << 53) as f64 * sum;
+        let mut lit = lits[lits.len() - 1];
+        for (&l, &w) in lits.iter().zip(&scores) {
+            if r < w {
+                lit = l;
+                break;
+            }
+            r -= w;
+        }
+
+        phases[lit.var().idx() as usize] = lit.sign();
+        flipped.push(lit.var().idx());
+        for &cj in &occs[lit.as_index()] {
+            let cj = cj as usize;
+            num_true[cj] += 1;
+            if num_true[cj] == 1 {
+                // satisfied now, remove from `unsat`
+                let last = unsat.pop().unwrap();
+                if last as usize != cj {
+                    unsat[pos[cj] as usize] = last;
+                    pos[last as usize] = pos[cj];
+                }
+            }
+        }
+        for &cj in &occs[(!lit).as_index()] {
+            let cj = cj as usize;
+            num_true[cj] -= 1;
+            if num_true[cj] == 0 {
+                pos[cj] = unsat.len() as u32;
+                unsat.push(cj as u32);
+            }
+        }
+        ticks += (occs[lit.as_index()].len() + occs[(!lit).as_index()].len()) as u64;
+        if unsat.len() < best {
+            best = unsat.len();
+            flipped.clear();
+        }
+    }
+    // go back to the best assignment
+    for &v in flipped.iter().rev() {
+        phases[v as usize] = !phases[v as usize];
+    }
+    (best, ticks)
+}
+
+#[test]
+fn test_walk() {
+    use crate::clause::Var;
+    // random 3-SAT formulas well below the threshold, starting from all false
+    let mut rng = crate::test_util::Rng::new(3);
+    let mut rand = |n| rng.next(n);
+    for i in 0..10 {
+        let mut clauses = vec![];
+        for _ in 0..300 {
+            for _ in 0..3 {
+                clauses.push(Lit::new(Var::from_idx(rand(100) as u32), rand(2) == 0));
+            }
+            clauses.push(Lit::UNDEF);
+        }
+        let mut phases = vec![false; 100];
+        let (unsat, ticks) = walk(&clauses, &mut phases, 1_000_000, i);
+        assert_eq!(unsat, 0);
+        assert!(ticks < 1_000_000);
+        let is_true = |l: &Lit| phases[l.var().idx() as usize] == l.sign();
+        assert!(clauses
+            .split(|&l| l == Lit::UNDEF)
+            .all(|c| c.is_empty() || c.iter().any(is_true)));
+    }
+    // the best assignment is kept when the search stops early
+    let clauses: Vec<Lit> = (0..4)
+        .flat_map(|i| [Lit::new(Var::from_idx(i), i % 2 == 0), Lit::UNDEF])
+        .collect();
+    let mut phases = vec![true; 4];
+    let (unsat, _) = walk(&clauses, &mut phases, 0, 1);
+    assert_eq!(unsat, 2);
+    assert_eq!(phases, [true; 4]);
+}
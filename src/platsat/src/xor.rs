@@ -0,0 +1,317 @@
+//! XOR constraints, propagated with Gauss-Jordan elimination
+use no_std_compat::prelude::v1::*;
+use {
+    crate::{
+        clause::{lbool, Lit, VMap, Var},
+        core::TheoryArg,
+        native::Reasons,
+    },
+    bit_vec::BitVec,
+    std::mem,
+};
+
+const NONE: u32 = u32::MAX;
+
+/// A row of the matrix: the XOR of the variables of its columns is `rhs`.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+struct Row {
+    cols: BitVec,
+    rhs: bool,
+    /// Column that occurs in no other row.
+    basic: u32,
+}
+
+/// Set of XOR constraints over the solver's variables.
+///
+/// The constraints are kept as a matrix in reduced row echelon form: each row has
+/// a basic column that occurs in no other row. When the variable of a basic column
+/// gets assigned, an unassigned column of the row becomes basic instead, so the
+/// matrix stays valid (and is never restored) on backtracking.
+/// A row whose only unassigned column is its basic one propagates it,
+/// and a row whose columns are all assigned may be in conflict.
+#[derive(Debug, Default, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub(crate) struct XorEngine {
+    rows: Vec<Row>,
+    /// Variable of each column.
+    col_var: Vec<Var>,
+    /// Column of each variable, or `NONE`.
+    var_col: VMap<u32>,
+    /// Columns whose variable is assigned, updated by `check`.
+    assigned: BitVec,
+    /// Length of the trail when `check` was last called.
+    checked_len: usize,
+    /// Were rows added since the last `check`?
+    dirty: bool,
+    tmp: Vec<Lit>,
+}
+
+impl XorEngine {
+    fn col(&mut self, v: Var) -> usize {
+        self.var_col.reserve(v, NONE);
+        if self.var_col[v] == NONE {
+            self.var_col[v] = self.col_var.len() as u32;
+            self.col_var.push(v);
+            for row in &mut self.rows {
+                row.cols.grow(1, false);
+            }
+            self.assigned.grow(1, false);
+        }
+        self.var_col[v] as usize
+    }
+
+    fn has_col(&self, v: Var) -> bool {
+        self.var_col.get(v).is_some_and(|&c| c != NONE)
+    }
+
+    pub(crate) fn contains_var(&self, v: Var) -> bool {
+        self.has_col(v)
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.col_var.is_empty()
+    }
+
+    /// Add the constraint `vars[0] ^ vars[1] ^ ... = rhs`, where `vars` has no duplicates.
+    ///
+    /// Returns `false` if it contradicts the constraints already added.
+    pub(crate) fn add(&mut self, vars: &[Var], rhs: bool) -> bool {
+        let cols: Vec<usize> = vars.iter().map(|&v| self.col(v)).collect();
+        let mut row = Row {
+            cols: BitVec::from_elem(self.col_var.len(), false),
+            rhs,
+            basic: NONE,
+        };
+        for c in cols {
+            row.cols.set(c, true);
+        }
+        // remove the basic columns of the other rows
+        for r in &self.rows {
+            if row.cols[r.basic as usize] {
+                row.cols.xor(&r.cols);
+                row.rhs ^= r.rhs;
+            }
+        }
+        let Some(basic) = row.cols.iter().position(|b| b) else {
+            // linear combination of the other rows
+            return !row.rhs;
+        };
+        self.rows.push(row);
+        self.pivot(self.rows.len() - 1, basic);
+        self.dirty = true;
+        true
+    }
+
+    /// Existentially quantify `v`: the rows are replaced by their consequences
+    /// that do not contain `v`.
+    pub(crate) fn forget(&mut self, v: Var) {
+        if !self.has_col(v) {
+            return;
+        }
+        let c = self.var_col[v] as usize;
+        if let Some(i) = self.rows.iter().position(|r| r.cols[c]) {
+            // `c` then only occurs in row `i`
+            self.pivot(i, c);
+            self.rows.swap_remove(i);
+            self.dirty = true;
+        }
+    }
+
+    /// Make `c` the basic column of row `i`, removing it from the other rows.
+    fn pivot(&mut self, i: usize, c: usize) {
+        self.rows[i].basic = c as u32;
+        let pivot = mem::take(&mut self.rows[i].cols);
+        let rhs = self.rows[i].rhs;
+        for (j, row) in self.rows.iter_mut().enumerate() {
+            if j != i && row.cols[c] {
+                row.cols.xor(&pivot);
+                row.rhs ^= rhs;
+            }
+        }
+        self.rows[i].cols = pivot;
+    }
+
+    /// Some unassigned column of row `i`.
+    fn unassigned_col(&self, i: usize) -> Option<usize> {
+        let blocks = self.rows[i].cols.blocks().zip(self.assigned.blocks());
+        let (b, (r, a)) = blocks.enumerate().find(|(_, (r, a))| r & !a != 0)?;
+        Some(b * 32 + (r & !a).trailing_zeros() as usize)
+    }
+
+    /// Number of unassigned columns of row `i`, up to 2.
+    fn num_unassigned(&self, i: usize) -> u32 {
+        let cols = self.rows[i].cols.blocks().zip(self.assigned.blocks());
+        let mut n = 0;
+        for (r, a) in cols {
+            n += (r & !a).count_ones();
+            if n >= 2 {
+                break;
+            }
+        }
+        n
+    }
+
+    /// Propagate the consequences of the XOR constraints under the current
+    /// (partial) model, or raise a conflict.
+    pub(crate) fn check(&mut self, acts: &mut TheoryArg, reasons: &mut Reasons) {
+        let trail = acts.model();
+        let trail_len = trail.len();
+        let new_lits = &trail[self.checked_len.min(trail_len)..];
+        if !self.dirty && new_lits.iter().all(|lit| !self.has_col(lit.var())) {
+            self.checked_len = trail_len;
+            return;
+        }
+        self.dirty = false;
+        for (c, &v) in self.col_var.iter().enumerate() {
+            self.assigned.set(c, acts.value(v) != lbool::UNDEF);
+        }
+        // make sure basic columns are unassigned whenever possible. Pivoting a row
+        // can add unassigned columns to the other rows, hence the fixpoint.
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for i in 0..self.rows.len() {
+                if self.assigned[self.rows[i].basic as usize] {
+                    if let Some(c) = self.unassigned_col(i) {
+                        self.pivot(i, c);
+                        changed = true;
+                    }
+                }
+            }
+        }
+        for i in 0..self.rows.len() {
+            if self.num_unassigned(i) >= 2 {
+                continue;
+            }
+            // the other columns are the reason of the propagation (or the conflict)
+            let mut parity = self.rows[i].rhs;
+            let mut unassigned = None;
+            self.tmp.clear();
+            for (c, _) in self.rows[i].cols.iter().enumerate().filter(|(_, b)| *b) {
+                let v = self.col_var[c];
+                if self.assigned[c] {
+                    let val = acts.value(v) == lbool::TRUE;
+                    parity ^= val;
+                    self.tmp.push(Lit::new(v, !val));
+                } else {
+                    unassigned = Some(c);
+                }
+            }
+            let Some(c) = unassigned else {
+                if parity {
+                    acts.raise_conflict(&self.tmp, false);
+                    return;
+                }
+                continue;
+            };
+            let p = Lit::new(self.col_var[c], parity);
+            debug_assert_eq!(acts.value(p.var()), lbool::UNDEF);
+            self.assigned.set(c, true);
+            reasons.propagate(acts, p, &self.tmp);
+        }
+        // the propagations above may enable more of them
+        self.checked_len = trail_len;
+    }
+
+    /// The trail is being shrunk to `trail_len`.
+    pub(crate) fn backtrack(&mut self, trail_len: usize) {
+        self.checked_len = self.checked_len.min(trail_len);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{test_util::Rng, *};
+    use no_std_compat::prelude::v1::*;
+
+    fn eval(lits: &[Lit], a: u32) -> impl Iterator<Item = bool> + '_ {
+        lits.iter()
+            .map(move |l| ((a >> l.var().idx()) & 1 == 1) == l.sign())
+    }
+
+    #[test]
+    fn test_xor_inconsistent() {
+        let mut solver = BasicSolver::default();
+        let x: Vec<Lit> = (0..5)
+            .map(|_| Lit::new(solver.new_var_default(), true))
+            .collect();
+        assert!(solver.add_xor_clause(&[x[0], x[1], x[2]], true));
+        assert!(solver.add_xor_clause(&[x[2], x[3], x[4]], false));
+        // the sum of the two others has parity `true`
+        assert!(!solver.add_xor_clause(&[x[0], !x[1], x[3], x[4]], true));
+        assert_eq!(solver.solve_limited(&[]), lbool::FALSE);
+    }
+
+    #[test]
+    fn test_xor_elimination() {
+        let mut solver = BasicSolver::default();
+        let x: Vec<Lit> = (0..6)
+            .map(|_| Lit::new(solver.new_var_default(), true))
+            .collect();
+        assert!(solver.add_xor_clause(&[x[0], x[1], x[2]], true));
+        assert!(solver.add_xor_clause(&[x[1], x[2], x[3]], false));
+        // their sum is `x0 ^ x3 = 1`, which neither of them implies alone
+        assert_eq!(solver.solve_limited(&[x[0]]), lbool::TRUE);
+        assert_eq!(solver.value_lit(x[3]), lbool::FALSE);
+        assert_eq!(solver.solve_limited(&[x[0], x[3]]), lbool::FALSE);
+        let mut core = solver.unsat_core().to_vec();
+        core.sort();
+        assert_eq!(core, [x[0], x[3]]);
+        // two variables are added as clauses: `x4 = !x5`
+        assert!(solver.add_xor_clause(&[x[4], x[5]], true));
+        assert_eq!(solver.solve_limited(&[!x[4]]), lbool::TRUE);
+        assert_eq!(solver.value_lit(x[5]), lbool::TRUE);
+    }
+
+    #[test]
+    fn test_xor_random() {
+        const N: u32 = 8;
+        let mut rng = Rng::new(42);
+        for _ in 0..100 {
+            let mut solver = BasicSolver::default();
+            let vars: Vec<Var> = (0..N).map(|_| solver.new_var_default()).collect();
+            let mut xors = vec![];
+            let mut clauses = vec![];
+            for _ in 0..rng.next(5) + 1 {
+                let lits: Vec<Lit> = (0..rng.next(4) + 1)
+                    .map(|_| Lit::new(vars[rng.next(N as u64) as usize], rng.next(2) == 0))
+                    .collect();
+                let parity = rng.next(2) == 0;
+                solver.add_xor_clause(&lits, parity);
+                xors.push((lits, parity));
+            }
+            for _ in 0..rng.next(8) {
+                let mut c: Vec<Lit> = (0..3)
+                    .map(|_| Lit::new(vars[rng.next(N as u64) as usize], rng.next(2) == 0))
+                    .collect();
+                clauses.push(c.clone());
+                solver.add_clause_reuse(&mut c);
+            }
+            let assumps: Vec<Lit> = (0..rng.next(3))
+                .map(|_| Lit::new(vars[rng.next(N as u64) as usize], rng.next(2) == 0))
+                .collect();
+            let is_model = |a: u32| {
+                xors.iter()
+                    .all(|(lits, parity)| eval(lits, a).fold(false, |x, b| x ^ b) == *parity)
+                    && clauses.iter().all(|c| eval(c, a).any(|b| b))
+            };
+            let sat = (0..1u32 << N).any(|a| is_model(a) && eval(&assumps, a).all(|b| b));
+            let res = solver.solve_limited(&assumps);
+            if sat {
+                assert_eq!(res, lbool::TRUE);
+                let a = vars
+                    .iter()
+                    .filter(|&&v| solver.value_var(v) == lbool::TRUE)
+                    .fold(0, |a, v| a | 1 << v.idx());
+                assert!(is_model(a));
+            } else {
+                assert_eq!(res, lbool::FALSE);
+                // the core is a subset of the assumptions that is enough for unsat
+                let core = solver.unsat_core().to_vec();
+                assert!(core.iter().all(|l| assumps.contains(l)));
+                assert!(!(0..1u32 << N).any(|a| is_model(a) && eval(&core, a).all(|b| b)));
+            }
+        }
+    }
+}